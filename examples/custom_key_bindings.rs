@@ -4,8 +4,8 @@ use rustyline::highlight::Highlighter;
 use rustyline::hint::HistoryHinter;
 use rustyline::history::DefaultHistory;
 use rustyline::{
-    Cmd, ConditionalEventHandler, Editor, Event, EventContext, EventHandler, KeyEvent, RepeatCount,
-    Result,
+    Cmd, ConditionalEventHandler, Editor, Event, EventContext, EventHandler, KeyEvent, Movement,
+    RepeatCount, Result, Word,
 };
 use rustyline::{Completer, Helper, Hinter, Validator};
 
@@ -95,10 +95,12 @@ fn main() -> Result<()> {
         KeyEvent::from('\t'),
         EventHandler::Conditional(Box::new(TabEventHandler)),
     );
-    rl.bind_sequence(
-        Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('E')]),
-        EventHandler::Simple(Cmd::Suspend), // TODO external editor
-    );
+    for (keys, cmd) in rustyline::bindings! {
+        "C-x C-e" => Suspend, // TODO external editor
+        "M-Left" => Move(Movement::BackwardWord(1, Word::Emacs)),
+    } {
+        rl.bind_sequence(keys, cmd);
+    }
 
     loop {
         let line = rl.readline("> ")?;
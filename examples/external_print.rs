@@ -12,9 +12,14 @@ fn main() -> Result<()> {
         let mut rng = rng();
         let mut i = 0usize;
         loop {
-            printer
-                .print(format!("External message #{i}"))
-                .expect("External print failure");
+            let msg = format!("External message #{i}");
+            // diagnostics go to stderr so that they don't pollute piped output
+            if i.is_multiple_of(2) {
+                printer.print(msg)
+            } else {
+                printer.eprint(msg)
+            }
+            .expect("External print failure");
             let wait_ms = rng.random_range(1000..10000);
             thread::sleep(Duration::from_millis(wait_ms));
             i += 1;
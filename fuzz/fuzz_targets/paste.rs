@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(text) = rustyline::fuzzing::decode_paste(data) {
+        assert!(!text.contains('\r'));
+    }
+});
@@ -0,0 +1,58 @@
+//! Redraw cost of typical editing sessions, driven by a scripted terminal.
+//!
+//! Besides the timings, the rendering counters of the last iteration are
+//! printed so that a change in the number of bytes written per redraw is
+//! noticed even when it doesn't show in the timings.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rustyline::{Config, DefaultEditor, KeyEvent, RenderStats, TestTerminal};
+
+/// Type `text`, then `keys`, then accept the line
+fn edit(text: &str, keys: &[KeyEvent]) -> RenderStats {
+    let term = TestTerminal::new(80, 24);
+    term.push_str(text);
+    term.push_keys(keys.iter().copied());
+    term.push_keys([KeyEvent::from('\r')]);
+    let config = Config::builder()
+        .render_stats(true)
+        .coalesce_refresh(false)
+        .build();
+    let mut editor = DefaultEditor::with_config(config).unwrap();
+    editor.set_host(term);
+    editor.readline("> ").unwrap();
+    editor.render_stats().unwrap()
+}
+
+fn bench(c: &mut Criterion, name: &str, text: &str, keys: &[KeyEvent]) {
+    let mut stats = None;
+    c.bench_function(name, |b| {
+        b.iter_batched(|| (), |()| stats = Some(edit(text, keys)), BatchSize::SmallInput)
+    });
+    if let Some(stats) = stats {
+        println!(
+            "{name}: {} refreshes, {} layouts, {:.1} bytes per refresh, at most {} bytes",
+            stats.refreshes,
+            stats.layouts,
+            stats.bytes_per_refresh(),
+            stats.max_bytes
+        );
+    }
+}
+
+fn typing(c: &mut Criterion) {
+    let text = "the quick brown fox jumps over the lazy dog ".repeat(4);
+    bench(c, "type short line", &text[..40], &[]);
+    bench(c, "type wrapped line", &text, &[]);
+    bench(c, "type wide chars", &"中文字符".repeat(20), &[]);
+}
+
+fn editing(c: &mut Criterion) {
+    let text = "the quick brown fox jumps over the lazy dog ".repeat(4);
+    // insertions at the start of a wrapped line redraw everything
+    let mut keys = vec![KeyEvent::ctrl('A')];
+    keys.extend("inserted ".chars().map(KeyEvent::from));
+    bench(c, "insert at start of wrapped line", &text, &keys);
+    bench(c, "move by words", &text, &[KeyEvent::alt('b'); 20]);
+}
+
+criterion_group!(benches, typing, editing);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+/* Subset of the GNU History API implemented by rustyline */
+#ifndef RUSTYLINE_HISTORY_H
+#define RUSTYLINE_HISTORY_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+extern void add_history(const char *line);
+extern void clear_history(void);
+extern void stifle_history(int max);
+extern int read_history(const char *filename);
+extern int write_history(const char *filename);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,26 @@
+/* Subset of the GNU Readline API implemented by rustyline */
+#ifndef RUSTYLINE_READLINE_H
+#define RUSTYLINE_READLINE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef char *rl_compentry_func_t(const char *text, int state);
+
+extern rl_compentry_func_t *rl_completion_entry_function;
+extern const char *rl_completer_word_break_characters;
+extern char *rl_line_buffer;
+extern int rl_point;
+extern const char *rl_readline_name;
+
+extern int rl_initialize(void);
+extern char *readline(const char *prompt);
+
+#ifdef __cplusplus
+}
+#endif
+
+#include "history.h"
+
+#endif
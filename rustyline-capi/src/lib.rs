@@ -0,0 +1,3 @@
+//! GNU Readline compatible C library, built as `libreadline` (to be linked
+//! instead of GNU Readline, with the headers of the `include` directory).
+pub use rustyline::capi::*;
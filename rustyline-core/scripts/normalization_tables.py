@@ -0,0 +1,70 @@
+#!/usr/bin/env python3
+# Generate `src/normalize/tables.rs` from the Unicode Character Database of
+# the running Python (`unicodedata`):
+#
+#     python3 scripts/normalization_tables.py > src/normalize/tables.rs
+import unicodedata
+
+HANGUL = range(0xAC00, 0xAC00 + 11172)
+
+
+def char(cp):
+    return "'\\u{%x}'" % cp
+
+
+def rows(items, per_row):
+    for i in range(0, len(items), per_row):
+        yield "    " + " ".join(items[i : i + per_row])
+
+
+def main():
+    classes = []
+    decompositions = []
+    for cp in range(0x110000):
+        c = chr(cp)
+        ccc = unicodedata.combining(c)
+        if ccc:
+            if classes and classes[-1][1] == cp - 1 and classes[-1][2] == ccc:
+                classes[-1][1] = cp
+            else:
+                classes.append([cp, cp, ccc])
+        d = unicodedata.decomposition(c)
+        if d and not d.startswith("<") and cp not in HANGUL:
+            decompositions.append((cp, [int(x, 16) for x in d.split()]))
+    compositions = sorted(
+        (d[0], d[1], cp)
+        for cp, d in decompositions
+        if len(d) == 2
+        and unicodedata.combining(chr(d[0])) == 0
+        and unicodedata.normalize("NFC", chr(d[0]) + chr(d[1])) == chr(cp)
+    )
+
+    print("// Generated by `scripts/normalization_tables.py` from the Unicode")
+    print("// Character Database %s: do not edit." % unicodedata.unidata_version)
+    print()
+    print("/// Non-zero canonical combining classes: first, last, class")
+    print("pub(super) static COMBINING_CLASSES: &[(char, char, u8)] = &[")
+    items = ["(%s, %s, %d)," % (char(a), char(b), c) for a, b, c in classes]
+    print("\n".join(rows(items, 3)))
+    print("];")
+    print()
+    print("/// Canonical decompositions (`'\\0'` when a single character), Hangul")
+    print("/// syllables excepted")
+    print("pub(super) static DECOMPOSITIONS: &[(char, char, char)] = &[")
+    items = [
+        "(%s, %s, %s)," % (char(cp), char(d[0]), char(d[1]) if len(d) == 2 else "'\\0'")
+        for cp, d in decompositions
+    ]
+    print("\n".join(rows(items, 3)))
+    print("];")
+    print()
+    print("/// Primary composites, sorted by their decomposition, Hangul syllables")
+    print("/// excepted")
+    print("pub(super) static COMPOSITIONS: &[(char, char, char)] = &[")
+    items = ["(%s, %s, %s)," % (char(a), char(b), char(c)) for a, b, c in compositions]
+    print("\n".join(rows(items, 3)))
+    print("];")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,381 @@
+//! Bidirectional text: display order of lines mixing left-to-right and
+//! right-to-left (Arabic, Hebrew, ...) scripts.
+//!
+//! The line is stored in logical order (the order it is typed) and
+//! reordered for display with the implicit rules of the Unicode
+//! Bidirectional Algorithm (UAX #9): each line is a paragraph whose
+//! direction is given by its first strong character. Explicit embeddings,
+//! overrides and isolates are not supported (their formatting characters
+//! are treated as neutrals), and characters are classified by script
+//! blocks instead of the full Unicode database.
+//!
+//! ```
+//! use rustyline_core::bidi;
+//!
+//! // "abc" followed by the Hebrew letters alef, bet, gimel
+//! let line = "abc \u{5d0}\u{5d1}\u{5d2}";
+//! let (display, pos) = bidi::visual(line, line.len()).unwrap();
+//! assert_eq!("abc \u{5d2}\u{5d1}\u{5d0}", display);
+//! assert_eq!(display.len(), pos);
+//! assert!(bidi::visual("abc", 0).is_none());
+//! ```
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Bidirectional character types (the ones used by the implicit rules)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)] // UAX #9 names
+enum Class {
+    /// Left-to-right
+    L,
+    /// Right-to-left
+    R,
+    /// Arabic letter
+    AL,
+    /// European number
+    EN,
+    /// European separator
+    ES,
+    /// European terminator
+    ET,
+    /// Arabic number
+    AN,
+    /// Common separator
+    CS,
+    /// Non-spacing mark
+    NSM,
+    /// Segment separator
+    S,
+    /// Whitespace
+    WS,
+    /// Other neutral
+    ON,
+}
+
+use Class::{AL, AN, CS, EN, ES, ET, L, NSM, ON, R, S, WS};
+
+fn class(c: char) -> Class {
+    match c {
+        '0'..='9'
+        | '\u{b2}'
+        | '\u{b3}'
+        | '\u{b9}'
+        | '\u{6f0}'..='\u{6f9}'
+        | '\u{ff10}'..='\u{ff19}' => EN,
+        '\u{600}'..='\u{605}' | '\u{660}'..='\u{669}' | '\u{66b}' | '\u{66c}' => AN,
+        '+' | '-' | '\u{207a}' | '\u{207b}' | '\u{ff0b}' | '\u{ff0d}' => ES,
+        '#'
+        | '$'
+        | '%'
+        | '\u{a2}'..='\u{a5}'
+        | '\u{b0}'
+        | '\u{b1}'
+        | '\u{66a}'
+        | '\u{2030}'..='\u{2034}'
+        | '\u{20a0}'..='\u{20cf}' => ET,
+        ',' | '.' | '/' | ':' | '\u{a0}' | '\u{60c}' | '\u{202f}' | '\u{2044}' => CS,
+        '\t' | '\u{b}' | '\u{1f}' => S,
+        ' '
+        | '\u{c}'
+        | '\u{1680}'
+        | '\u{2000}'..='\u{200a}'
+        | '\u{2028}'
+        | '\u{205f}'
+        | '\u{3000}' => WS,
+        '\u{200e}' => L,
+        '\u{200f}' => R,
+        '\u{300}'..='\u{36f}'
+        | '\u{591}'..='\u{5bd}'
+        | '\u{5bf}'
+        | '\u{5c1}'
+        | '\u{5c2}'
+        | '\u{5c4}'
+        | '\u{5c5}'
+        | '\u{5c7}'
+        | '\u{610}'..='\u{61a}'
+        | '\u{64b}'..='\u{65f}'
+        | '\u{670}'
+        | '\u{6d6}'..='\u{6dc}'
+        | '\u{6df}'..='\u{6e4}'
+        | '\u{6e7}'
+        | '\u{6e8}'
+        | '\u{6ea}'..='\u{6ed}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe00}'..='\u{fe0f}'
+        | '\u{fe20}'..='\u{fe2f}' => NSM,
+        // Hebrew, NKo, Samaritan, Mandaic and the other historic scripts
+        '\u{590}'..='\u{5ff}'
+        | '\u{7c0}'..='\u{85f}'
+        | '\u{fb1d}'..='\u{fb4f}'
+        | '\u{10800}'..='\u{10cff}'
+        | '\u{10d40}'..='\u{10eff}'
+        | '\u{1e800}'..='\u{1edff}' => R,
+        // Arabic, Syriac, Thaana and their presentation forms
+        '\u{600}'..='\u{7bf}'
+        | '\u{860}'..='\u{8ff}'
+        | '\u{fb50}'..='\u{fdff}'
+        | '\u{fe70}'..='\u{feff}'
+        | '\u{10d00}'..='\u{10d3f}'
+        | '\u{1ee00}'..='\u{1eeff}' => AL,
+        c if c.is_alphabetic() => L,
+        _ => ON,
+    }
+}
+
+/// Tell if `text` contains right-to-left characters (and so may be
+/// displayed in another order)
+#[must_use]
+pub fn has_rtl(text: &str) -> bool {
+    text.chars().any(|c| matches!(class(c), R | AL))
+}
+
+/// Embedding level of a line: 1 when its first strong character is
+/// right-to-left, 0 otherwise (rules P2 and P3)
+#[must_use]
+pub fn paragraph_level(line: &str) -> u8 {
+    line.chars()
+        .map(class)
+        .find(|class| matches!(class, L | R | AL))
+        .map_or(0, |class| u8::from(class != L))
+}
+
+/// Embedding level of each grapheme cluster of a single `line` (without
+/// newline) at paragraph `level`
+fn levels(graphemes: &[&str], level: u8) -> Vec<u8> {
+    let e = if level % 2 == 1 { R } else { L };
+    // a grapheme cluster has the type of its base character
+    let original: Vec<Class> = graphemes
+        .iter()
+        .map(|g| g.chars().next().map_or(ON, class))
+        .collect();
+    let mut types = original.clone();
+    // W1: marks without base take the type of the previous character
+    let mut prev = e;
+    for t in &mut types {
+        if *t == NSM {
+            *t = prev;
+        }
+        prev = *t;
+    }
+    // W2, W3: numbers after an Arabic letter are Arabic numbers
+    let mut strong = e;
+    for t in &mut types {
+        match *t {
+            L | R => strong = *t,
+            AL => {
+                strong = AL;
+                *t = R;
+            }
+            EN if strong == AL => *t = AN,
+            _ => {}
+        }
+    }
+    // W4: single separator between two numbers of the same kind
+    for i in 1..types.len().saturating_sub(1) {
+        let (before, after) = (types[i - 1], types[i + 1]);
+        if before == after
+            && ((types[i] == ES && before == EN) || (types[i] == CS && matches!(before, EN | AN)))
+        {
+            types[i] = before;
+        }
+    }
+    // W5: terminators next to European numbers
+    let mut i = 0;
+    while i < types.len() {
+        if types[i] != ET {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < types.len() && types[i] == ET {
+            i += 1;
+        }
+        if (start > 0 && types[start - 1] == EN) || types.get(i) == Some(&EN) {
+            types[start..i].fill(EN);
+        }
+    }
+    // W6, W7
+    let mut strong = e;
+    for t in &mut types {
+        match *t {
+            ES | ET | CS => *t = ON,
+            L | R => strong = *t,
+            EN if strong == L => *t = L,
+            _ => {}
+        }
+    }
+    // N1, N2: neutrals take the direction of the surrounding strong types
+    // (numbers being right-to-left), the embedding direction otherwise
+    let direction = |t: Class| match t {
+        L => Some(L),
+        R | EN | AN => Some(R),
+        _ => None,
+    };
+    let mut i = 0;
+    while i < types.len() {
+        if direction(types[i]).is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < types.len() && direction(types[i]).is_none() {
+            i += 1;
+        }
+        let before = start
+            .checked_sub(1)
+            .map_or(e, |j| direction(types[j]).unwrap_or(e));
+        let after = types.get(i).map_or(e, |t| direction(*t).unwrap_or(e));
+        types[start..i].fill(if before == after { before } else { e });
+    }
+    // I1, I2
+    let mut levels: Vec<u8> = types
+        .iter()
+        .map(|t| match (level % 2, t) {
+            (0, R) => level + 1,
+            (0, AN | EN) => level + 2,
+            (1, L | EN | AN) => level + 1,
+            _ => level,
+        })
+        .collect();
+    // L1: segment separators and trailing whitespace are at paragraph level
+    let mut trailing = true;
+    for (l, t) in levels.iter_mut().zip(&original).rev() {
+        if *t == S {
+            *l = level;
+            trailing = true;
+        } else if trailing && *t == WS {
+            *l = level;
+        } else {
+            trailing = false;
+        }
+    }
+    levels
+}
+
+/// Visual order of the items at `levels` (rule L2): `order[v]` is the
+/// logical index of the item displayed at visual index `v`
+fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let Some(&max) = levels.iter().max() else {
+        return order;
+    };
+    let Some(min_odd) = levels.iter().filter(|l| *l % 2 == 1).min().copied() else {
+        return order;
+    };
+    for level in (min_odd..=max).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+    order
+}
+
+/// Mirrored glyph of `grapheme` displayed right-to-left (rule L4)
+fn mirrored(grapheme: &str) -> Option<&'static str> {
+    Some(match grapheme {
+        "(" => ")",
+        ")" => "(",
+        "[" => "]",
+        "]" => "[",
+        "{" => "}",
+        "}" => "{",
+        "<" => ">",
+        ">" => "<",
+        "\u{ab}" => "\u{bb}",
+        "\u{bb}" => "\u{ab}",
+        _ => return None,
+    })
+}
+
+/// `text` in display order with the byte position where the cursor at
+/// logical `pos` is displayed (on the character following the cursor, at
+/// the end of the line when there is none).
+///
+/// Each line (separated by `\n`) is reordered independently, so lines wider
+/// than the terminal are reordered before being wrapped.
+/// Return `None` when `text` is only left-to-right.
+#[must_use]
+pub fn visual(text: &str, pos: usize) -> Option<(String, usize)> {
+    if !has_rtl(text) {
+        return None;
+    }
+    let mut display = String::with_capacity(text.len());
+    let mut display_pos = None;
+    let mut start = 0;
+    for line in text.split('\n') {
+        if start > 0 {
+            display.push('\n');
+        }
+        let graphemes: Vec<(usize, &str)> = line.grapheme_indices(true).collect();
+        let level = paragraph_level(line);
+        let strs: Vec<&str> = graphemes.iter().map(|(_, g)| *g).collect();
+        let levels = levels(&strs, level);
+        for i in visual_order(&levels) {
+            let (offset, grapheme) = graphemes[i];
+            if start + offset == pos {
+                display_pos = Some(display.len());
+            }
+            match mirrored(grapheme).filter(|_| levels[i] % 2 == 1) {
+                Some(mirror) => display.push_str(mirror),
+                None => display.push_str(grapheme),
+            }
+        }
+        if display_pos.is_none() && pos <= start + line.len() {
+            // end of line
+            display_pos = Some(display.len());
+        }
+        start += line.len() + 1;
+    }
+    let display_pos = display_pos.unwrap_or(display.len());
+    Some((display, display_pos))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{paragraph_level, visual};
+
+    #[test]
+    fn reorder() {
+        // alef bet gimel
+        let hebrew = "\u{5d0}\u{5d1}\u{5d2}";
+        let reversed = "\u{5d2}\u{5d1}\u{5d0}";
+        assert_eq!(None, visual("abc (1)", 0));
+        // left-to-right paragraph, the cursor on bet
+        let line = format!("ls {hebrew} -l");
+        assert_eq!(
+            Some((format!("ls {reversed} -l"), 3 + 2)),
+            visual(&line, 3 + 2)
+        );
+        // a number following right-to-left text belongs to its run
+        let line = format!("ls {hebrew} 12");
+        assert_eq!(Some((format!("ls 12 {reversed}"), 3)), visual(&line, 10));
+        // right-to-left paragraph: numbers keep their order, brackets are
+        // mirrored and trailing spaces are on the left
+        let line = format!("{hebrew} (12) ab ");
+        assert_eq!(Some((format!(" ab (12) {reversed}"), 4)), visual(&line, 10));
+        // multi-line
+        let line = format!("a\n{hebrew}");
+        assert_eq!(Some((format!("a\n{reversed}"), 2 + 4)), visual(&line, 2));
+        assert_eq!(Some((format!("a\n{reversed}"), 8)), visual(&line, 8));
+    }
+
+    #[test]
+    fn arabic() {
+        // alef lam with a fatha mark, then Arabic-Indic digits
+        let line = "\u{627}\u{644}\u{64e} \u{661}\u{662}";
+        assert_eq!(1, paragraph_level(line));
+        let (display, _) = visual(line, 0).unwrap();
+        assert_eq!("\u{661}\u{662} \u{644}\u{64e}\u{627}", display);
+    }
+}
@@ -0,0 +1,263 @@
+//! Editing engine: a line buffer with its undo manager and kill ring, for
+//! applications (GUI, TUI frameworks, ...) rendering the line by themselves.
+//!
+//! ```
+//! use rustyline_core::engine::Engine;
+//! use rustyline_core::line_buffer::WordAction;
+//! use rustyline_core::movement::{Movement, Word};
+//!
+//! let mut engine = Engine::new(16);
+//! engine.insert_str("hello world");
+//! engine.kill(&Movement::BackwardWord(1, Word::Emacs));
+//! assert_eq!("hello ", engine.line().as_str());
+//! engine.move_by(&Movement::BeginningOfLine);
+//! engine.edit_word(WordAction::Uppercase);
+//! engine.move_by(&Movement::EndOfLine);
+//! engine.yank(1);
+//! assert_eq!("HELLO world", engine.line().as_str());
+//! engine.undo(2);
+//! assert_eq!("hello ", engine.line().as_str());
+//! ```
+use crate::keymap::Command;
+use crate::kill_ring::KillRing;
+use crate::layout::Layout;
+use crate::line_buffer::{
+    ChangeListener, DeleteListener, Direction, LineBuffer, NoListener, WordAction, MAX_LINE,
+};
+use crate::movement::{Movement, RepeatCount};
+use crate::undo::Changeset;
+
+/// Listener recording the changes (in a [`Changeset`] by default) and the
+/// killed text in a [`KillRing`]
+pub struct UndoAndKill<'a, C: ChangeListener = Changeset>(pub &'a mut C, pub &'a mut KillRing);
+
+impl<C: ChangeListener> DeleteListener for UndoAndKill<'_, C> {
+    fn start_killing(&mut self) {
+        self.0.start_killing();
+        self.1.start_killing();
+    }
+
+    fn delete(&mut self, idx: usize, string: &str, dir: Direction) {
+        self.0.delete(idx, string, dir);
+        self.1.delete(idx, string, dir);
+    }
+
+    fn stop_killing(&mut self) {
+        self.0.stop_killing();
+        self.1.stop_killing();
+    }
+}
+
+impl<C: ChangeListener> ChangeListener for UndoAndKill<'_, C> {
+    fn insert_char(&mut self, idx: usize, c: char) {
+        self.0.insert_char(idx, c);
+    }
+
+    fn insert_str(&mut self, idx: usize, string: &str) {
+        self.0.insert_str(idx, string);
+    }
+
+    fn replace(&mut self, idx: usize, old: &str, new: &str) {
+        self.0.replace(idx, old, new);
+    }
+}
+
+/// Line buffer edited with the Emacs mode semantics of Rustyline: changes
+/// are undoable and consecutive kills are accumulated in the kill ring.
+///
+/// Each method returns `false` when nothing changed (e.g. no word to
+/// transpose).
+pub struct Engine {
+    line: LineBuffer,
+    changes: Changeset,
+    kill_ring: KillRing,
+    layout: Layout,
+}
+
+impl Engine {
+    /// Empty line, with a kill ring of `kill_ring_size` entries
+    #[must_use]
+    pub fn new(kill_ring_size: usize) -> Self {
+        Self {
+            line: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
+            changes: Changeset::new(),
+            kill_ring: KillRing::new(kill_ring_size),
+            layout: Layout::default(),
+        }
+    }
+
+    /// Line being edited
+    #[must_use]
+    pub fn line(&self) -> &LineBuffer {
+        &self.line
+    }
+
+    /// Undo manager (e.g. to change its grouping)
+    pub fn changes_mut(&mut self) -> &mut Changeset {
+        &mut self.changes
+    }
+
+    /// Kill ring (e.g. to synchronize it with the clipboard)
+    pub fn kill_ring_mut(&mut self) -> &mut KillRing {
+        &mut self.kill_ring
+    }
+
+    /// Layout used by [`Movement::LineUp`] and [`Movement::LineDown`] to
+    /// keep the cursor column (no prompt and Unicode widths by default)
+    pub fn layout_mut(&mut self) -> &mut Layout {
+        &mut self.layout
+    }
+
+    /// Replace the whole line, the cursor at `pos`, and forget the changes
+    /// (e.g. for a new line)
+    pub fn reset(&mut self, text: &str, pos: usize) {
+        self.line.update(text, pos, &mut NoListener);
+        let _ = self.changes.take();
+        self.kill_ring.reset();
+    }
+
+    /// Insert `c` `n` times at the cursor
+    pub fn insert(&mut self, c: char, n: RepeatCount) -> bool {
+        self.kill_ring.reset();
+        self.line.insert(c, n, &mut self.changes).is_some()
+    }
+
+    /// Insert `text` at the cursor, moved after it
+    pub fn insert_str(&mut self, text: &str) -> bool {
+        self.kill_ring.reset();
+        self.line.yank(text, 1, &mut self.changes).is_some()
+    }
+
+    /// Move the cursor (see [`LineBuffer::move_by`])
+    pub fn move_by(&mut self, mvt: &Movement) -> bool {
+        self.kill_ring.reset();
+        self.line.move_by(mvt, &self.layout)
+    }
+
+    /// Kill the text covered by `mvt`, appended to (or prepended to) the
+    /// text killed by the previous command when it was also a kill
+    pub fn kill(&mut self, mvt: &Movement) -> bool {
+        if matches!(mvt, Movement::BackwardChar(_) | Movement::ForwardChar(_)) {
+            // simple deletion
+            self.kill_ring.reset();
+        }
+        let mut listener = UndoAndKill(&mut self.changes, &mut self.kill_ring);
+        self.line.kill(mvt, &mut listener)
+    }
+
+    /// Insert the last killed text `n` times at the cursor
+    pub fn yank(&mut self, n: RepeatCount) -> bool {
+        let Some(text) = self.kill_ring.yank() else {
+            return false;
+        };
+        self.line.yank(text, n, &mut self.changes).is_some()
+    }
+
+    /// Replace the text just yanked with the previous kill ring entry
+    pub fn yank_pop(&mut self) -> bool {
+        let Some((yank_size, text)) = self.kill_ring.yank_pop() else {
+            return false;
+        };
+        self.changes.begin();
+        let done = self
+            .line
+            .yank_pop(yank_size, text, &mut self.changes)
+            .is_some();
+        self.changes.end();
+        done
+    }
+
+    /// Exchange the character before the cursor with the one at the cursor
+    pub fn transpose_chars(&mut self) -> bool {
+        self.grouped(|line, changes| line.transpose_chars(changes))
+    }
+
+    /// Exchange the word before the cursor with the `n`th one after
+    pub fn transpose_words(&mut self, n: RepeatCount) -> bool {
+        self.grouped(|line, changes| line.transpose_words(n, changes))
+    }
+
+    /// Change the case of the word at the cursor
+    pub fn edit_word(&mut self, a: WordAction) -> bool {
+        self.grouped(|line, changes| line.edit_word(a, changes))
+    }
+
+    /// Undo the last `n` changes
+    pub fn undo(&mut self, n: RepeatCount) -> bool {
+        self.kill_ring.reset();
+        self.changes.undo(&mut self.line, n)
+    }
+
+    /// Redo the last `n` undone changes
+    pub fn redo(&mut self, n: RepeatCount) -> bool {
+        self.kill_ring.reset();
+        self.changes.redo(&mut self.line, n)
+    }
+
+    /// Apply `cmd` (see [`keymap`](crate::keymap))
+    pub fn execute(&mut self, cmd: &Command) -> bool {
+        match *cmd {
+            Command::SelfInsert(n, c) => self.insert(c, n),
+            Command::Move(ref mvt) => self.move_by(mvt),
+            Command::Kill(ref mvt) => self.kill(mvt),
+            Command::Yank(n) => self.yank(n),
+            Command::YankPop => self.yank_pop(),
+            Command::TransposeChars => self.transpose_chars(),
+            Command::TransposeWords(n) => self.transpose_words(n),
+            Command::EditWord(a) => self.edit_word(a),
+            Command::Undo(n) => self.undo(n),
+            Command::Redo(n) => self.redo(n),
+        }
+    }
+
+    /// Apply `f`, undone as a single change
+    fn grouped(&mut self, f: impl FnOnce(&mut LineBuffer, &mut Changeset) -> bool) -> bool {
+        self.kill_ring.reset();
+        self.changes.begin();
+        let done = f(&mut self.line, &mut self.changes);
+        self.changes.end();
+        done
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Engine;
+    use crate::movement::{At, CharSearch, Movement, Word};
+
+    #[test]
+    fn kills() {
+        let mut engine = Engine::new(4);
+        engine.insert_str("one two three");
+        // consecutive kills are accumulated
+        engine.kill(&Movement::BackwardWord(1, Word::Emacs));
+        engine.kill(&Movement::BackwardWord(1, Word::Emacs));
+        assert_eq!("one ", engine.line().as_str());
+        engine.move_by(&Movement::BeginningOfLine);
+        engine.kill(&Movement::ForwardWord(1, At::AfterEnd, Word::Emacs));
+        assert_eq!(" ", engine.line().as_str());
+        engine.move_by(&Movement::EndOfLine);
+        assert!(engine.yank(1));
+        assert_eq!(" one", engine.line().as_str());
+        assert!(engine.yank_pop());
+        assert_eq!(" two three", engine.line().as_str());
+        assert!(engine.undo(1));
+        assert_eq!(" one", engine.line().as_str());
+    }
+
+    #[test]
+    fn moves() {
+        let mut engine = Engine::new(1);
+        engine.reset("ab\n  cd", 0);
+        assert!(engine.move_by(&Movement::ViCharSearch(1, CharSearch::Forward('d'))));
+        assert_eq!(6, engine.line().pos());
+        assert!(engine.move_by(&Movement::LineUp(1)));
+        assert_eq!(2, engine.line().pos());
+        assert!(engine.move_by(&Movement::LineDown(1)));
+        engine.reset("  cd", 4);
+        assert!(engine.move_by(&Movement::ViFirstPrint));
+        assert_eq!(2, engine.line().pos());
+        assert!(!engine.move_by(&Movement::WholeLine));
+        assert!(!engine.undo(1));
+    }
+}
@@ -0,0 +1,318 @@
+//! Key bindings of the Emacs mode of Rustyline editing the line: the
+//! interpretation of the keys (with their digit argument, like `M-3 C-f`)
+//! into [`Command`]s, applied by [`Engine::execute`](crate::engine::Engine::execute).
+//!
+//! Only the commands editing the line are bound here: what needs more than
+//! the line (accepting it, history, completion, searches, clearing the
+//! screen, ...) and the key sequences (`C-x C-u`, `M-g g`, ...) are left to
+//! the caller, which gets `None` for these keys.
+//!
+//! ```
+//! use rustyline_core::engine::Engine;
+//! use rustyline_core::keymap::Emacs;
+//! use rustyline_core::keys::KeyEvent;
+//!
+//! let mut emacs = Emacs::new();
+//! let mut engine = Engine::new(16);
+//! for key in "hello world".chars().map(KeyEvent::from).chain([
+//!     KeyEvent::alt('2'),
+//!     KeyEvent::alt('b'),
+//!     KeyEvent::ctrl('K'),
+//! ]) {
+//!     if let Some(cmd) = emacs.next_cmd(key) {
+//!         engine.execute(&cmd);
+//!     }
+//! }
+//! assert_eq!("", engine.line().as_str());
+//! ```
+use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+use crate::line_buffer::WordAction;
+use crate::movement::{At, Movement, RepeatCount, Word};
+
+/// Command editing the line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Command {
+    /// Insert a character `n` times
+    SelfInsert(RepeatCount, char),
+    /// Move the cursor
+    Move(Movement),
+    /// Kill (or delete) the text covered by the movement
+    Kill(Movement),
+    /// Insert the last killed text `n` times
+    Yank(RepeatCount),
+    /// Replace the text just yanked with the previous kill ring entry
+    YankPop,
+    /// Exchange the character before the cursor with the one at the cursor
+    TransposeChars,
+    /// Exchange the word before the cursor with the `n`th one after
+    TransposeWords(RepeatCount),
+    /// Change the case of the word at the cursor
+    EditWord(WordAction),
+    /// Undo the last `n` changes
+    Undo(RepeatCount),
+    /// Redo the last `n` undone changes
+    Redo(RepeatCount),
+}
+
+/// `mvt` when `positive`, `opposite` otherwise (negative digit argument)
+fn directed(positive: bool, mvt: Movement, opposite: Movement) -> Movement {
+    if positive {
+        mvt
+    } else {
+        opposite
+    }
+}
+
+/// Command bound to `key` in Emacs mode, repeated `n` times forward
+/// (`positive`) or backward
+#[must_use]
+pub fn emacs(key: E, n: RepeatCount, positive: bool) -> Option<Command> {
+    let backward_word = Movement::BackwardWord(n, Word::Emacs);
+    let forward_word = Movement::ForwardWord(n, At::AfterEnd, Word::Emacs);
+    Some(match key {
+        E(K::Char(c), M::NONE) if positive => Command::SelfInsert(n, c),
+        E(K::Char('A'), M::CTRL) => Command::Move(Movement::BeginningOfLine),
+        E(K::Char('B'), M::CTRL) => Command::Move(directed(
+            positive,
+            Movement::BackwardChar(n),
+            Movement::ForwardChar(n),
+        )),
+        E(K::Char('E'), M::CTRL) => Command::Move(Movement::EndOfLine),
+        E(K::Char('F'), M::CTRL) => Command::Move(directed(
+            positive,
+            Movement::ForwardChar(n),
+            Movement::BackwardChar(n),
+        )),
+        E(K::Char('H'), M::CTRL) | E::BACKSPACE => Command::Kill(directed(
+            positive,
+            Movement::BackwardChar(n),
+            Movement::ForwardChar(n),
+        )),
+        E(K::Char('K'), M::CTRL) => Command::Kill(directed(
+            positive,
+            Movement::EndOfLine,
+            Movement::BeginningOfLine,
+        )),
+        E(K::Backspace, M::ALT) => Command::Kill(directed(positive, backward_word, forward_word)),
+        E(K::Char('{'), M::ALT) => Command::Move(directed(
+            positive,
+            Movement::BackwardParagraph(n),
+            Movement::ForwardParagraph(n),
+        )),
+        E(K::Char('}'), M::ALT) => Command::Move(directed(
+            positive,
+            Movement::ForwardParagraph(n),
+            Movement::BackwardParagraph(n),
+        )),
+        E(K::Char('B' | 'b') | K::Left, M::ALT) | E(K::Left, M::CTRL) => {
+            Command::Move(directed(positive, backward_word, forward_word))
+        }
+        E(K::Char('C' | 'c'), M::ALT) => Command::EditWord(WordAction::Capitalize),
+        E(K::Char('D' | 'd'), M::ALT) => {
+            Command::Kill(directed(positive, forward_word, backward_word))
+        }
+        E(K::Char('F' | 'f') | K::Right, M::ALT) | E(K::Right, M::CTRL) => {
+            Command::Move(directed(positive, forward_word, backward_word))
+        }
+        E(K::Char('L' | 'l'), M::ALT) => Command::EditWord(WordAction::Lowercase),
+        E(K::Char('T' | 't'), M::ALT) => Command::TransposeWords(n),
+        E(K::Char('U' | 'u'), M::ALT) => Command::EditWord(WordAction::Uppercase),
+        E(K::Char('Y' | 'y'), M::ALT) => Command::YankPop,
+        _ => return common(key, n, positive),
+    })
+}
+
+/// Command bound to `key` in both Emacs and Vi insert modes, repeated `n`
+/// times forward (`positive`) or backward
+#[must_use]
+pub fn common(key: E, n: RepeatCount, positive: bool) -> Option<Command> {
+    Some(match key {
+        E(K::Home, M::NONE) => Command::Move(Movement::BeginningOfLine),
+        E(K::Home, M::CTRL) => Command::Move(Movement::BeginningOfBuffer),
+        E(K::End, M::CTRL) => Command::Move(Movement::EndOfBuffer),
+        E(K::Left, M::NONE) => Command::Move(directed(
+            positive,
+            Movement::BackwardChar(n),
+            Movement::ForwardChar(n),
+        )),
+        E(K::Delete, M::NONE) => Command::Kill(directed(
+            positive,
+            Movement::ForwardChar(n),
+            Movement::BackwardChar(n),
+        )),
+        E(K::End, M::NONE) => Command::Move(Movement::EndOfLine),
+        E(K::Right, M::NONE) => Command::Move(directed(
+            positive,
+            Movement::ForwardChar(n),
+            Movement::BackwardChar(n),
+        )),
+        E(K::Char('T'), M::CTRL) => Command::TransposeChars,
+        E(K::Char('U'), M::CTRL) => Command::Kill(directed(
+            positive,
+            Movement::BeginningOfLine,
+            Movement::EndOfLine,
+        )),
+        E(K::Char('W'), M::CTRL) => Command::Kill(directed(
+            positive,
+            Movement::BackwardWord(n, Word::Big),
+            Movement::ForwardWord(n, At::AfterEnd, Word::Big),
+        )),
+        E(K::Char('Y'), M::CTRL) if positive => Command::Yank(n),
+        E(K::Char('_'), M::CTRL) => Command::Undo(n),
+        E(K::Char('_'), M::CTRL_ALT) => Command::Redo(n),
+        _ => return None,
+    })
+}
+
+/// Emacs mode interpreter: digit arguments (`M-` followed by digits, or
+/// `M--` for a negative one) then the bindings of [`emacs`]
+#[derive(Debug, Default)]
+pub struct Emacs {
+    argument: Option<i16>,
+}
+
+impl Emacs {
+    /// No digit argument typed
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Command bound to `key`, `None` when unbound or part of a digit
+    /// argument
+    pub fn next_cmd(&mut self, key: E) -> Option<Command> {
+        if self.digit_argument(key) {
+            return None;
+        }
+        let (n, positive) = self.take_count();
+        emacs(key, n, positive)
+    }
+
+    /// Add `key` to the digit argument: `false` when it doesn't start
+    /// (`M-` followed by a digit or `-`) or continue (a digit, with or
+    /// without `M-`) one
+    #[expect(clippy::cast_possible_truncation)]
+    pub fn digit_argument(&mut self, key: E) -> bool {
+        match (self.argument, key) {
+            (None, E(K::Char(digit @ '0'..='9'), M::ALT)) => {
+                self.argument = Some(digit.to_digit(10).unwrap() as i16);
+            }
+            (None, E(K::Char('-'), M::ALT)) => self.argument = Some(-1),
+            (Some(arg), E(K::Char(digit @ '0'..='9'), M::NONE | M::ALT)) => {
+                let digit = digit.to_digit(10).unwrap() as i16;
+                self.argument = Some(if arg == -1 {
+                    -digit
+                } else if arg.abs() < 1000 {
+                    // shouldn't ever need more than 4 digits
+                    arg.saturating_mul(10).saturating_add(digit)
+                } else {
+                    arg
+                });
+            }
+            (Some(_), E(K::Char('-'), M::NONE | M::ALT)) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Digit argument being typed (e.g. to be displayed like `(arg: 12)`)
+    #[must_use]
+    pub fn argument(&self) -> Option<i16> {
+        self.argument
+    }
+
+    /// Repeat count and direction given by the digit argument (1 and
+    /// forward without), which is consumed
+    pub fn take_count(&mut self) -> (RepeatCount, bool) {
+        match self.argument.take() {
+            None | Some(0) => (1, true),
+            Some(arg) => (arg.unsigned_abs(), arg > 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Command, Emacs};
+    use crate::engine::Engine;
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+    use crate::line_buffer::WordAction;
+    use crate::movement::Movement;
+
+    fn type_keys(engine: &mut Engine, keys: &[E]) {
+        let mut emacs = Emacs::new();
+        for key in keys {
+            if let Some(cmd) = emacs.next_cmd(*key) {
+                engine.execute(&cmd);
+            }
+        }
+    }
+
+    #[test]
+    fn digit_argument() {
+        let mut emacs = Emacs::new();
+        assert!(emacs.digit_argument(E::alt('1')));
+        assert!(emacs.digit_argument(E::from('2')));
+        assert_eq!(Some(12), emacs.argument());
+        assert_eq!(
+            Some(Command::Move(Movement::BackwardChar(12))),
+            emacs.next_cmd(E::ctrl('B'))
+        );
+        assert_eq!(None, emacs.argument());
+        // negative argument: opposite direction
+        assert_eq!(None, emacs.next_cmd(E::alt('-')));
+        assert_eq!(None, emacs.next_cmd(E::from('3')));
+        assert_eq!(
+            Some(Command::Move(Movement::ForwardChar(3))),
+            emacs.next_cmd(E::ctrl('B'))
+        );
+        // digits inserted without argument
+        assert_eq!(
+            Some(Command::SelfInsert(1, '1')),
+            emacs.next_cmd(E::from('1'))
+        );
+        // `M-3 a` inserts `aaa`
+        assert_eq!(None, emacs.next_cmd(E::alt('3')));
+        assert_eq!(
+            Some(Command::SelfInsert(3, 'a')),
+            emacs.next_cmd(E::from('a'))
+        );
+    }
+
+    #[test]
+    fn bindings() {
+        let mut emacs = Emacs::new();
+        assert_eq!(
+            Some(Command::EditWord(WordAction::Uppercase)),
+            emacs.next_cmd(E::alt('u'))
+        );
+        assert_eq!(Some(Command::Undo(1)), emacs.next_cmd(E::ctrl('_')));
+        // left to the caller
+        assert_eq!(None, emacs.next_cmd(E::ENTER));
+        assert_eq!(None, emacs.next_cmd(E::ctrl('X')));
+        assert_eq!(None, emacs.next_cmd(E(K::Up, M::NONE)));
+    }
+
+    #[test]
+    fn edit() {
+        let mut engine = Engine::new(16);
+        let mut keys: Vec<E> = "one two three".chars().map(E::from).collect();
+        // kill two words backward then yank them at the start
+        keys.extend([
+            E::alt('2'),
+            E(K::Backspace, M::ALT),
+            E::ctrl('A'),
+            E::ctrl('Y'),
+        ]);
+        type_keys(&mut engine, &keys);
+        assert_eq!("two threeone ", engine.line().as_str());
+        type_keys(
+            &mut engine,
+            &[E::ctrl('E'), E::ctrl('T'), E::alt('b'), E::alt('c')],
+        );
+        assert_eq!("two threeon E", engine.line().as_str());
+        type_keys(&mut engine, &[E::ctrl('_')]);
+        assert_eq!("two threeon e", engine.line().as_str());
+    }
+}
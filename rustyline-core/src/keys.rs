@@ -0,0 +1,391 @@
+//! Keys pressed (with their modifiers) and their readline style
+//! specifications (`C-x`, `M-Enter`)
+use alloc::format;
+use alloc::string::String;
+
+/// Input key pressed and modifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyEvent(pub KeyCode, pub Modifiers);
+
+impl KeyEvent {
+    /// Constant value representing an unmodified press of `KeyCode::Backspace`.
+    pub const BACKSPACE: Self = Self(KeyCode::Backspace, Modifiers::NONE);
+    /// Constant value representing an unmodified press of `KeyCode::Enter`.
+    pub const ENTER: Self = Self(KeyCode::Enter, Modifiers::NONE);
+    /// Constant value representing an unmodified press of `KeyCode::Esc`.
+    pub const ESC: Self = Self(KeyCode::Esc, Modifiers::NONE);
+
+    /// Constructor from `char` and modifiers
+    #[must_use]
+    pub fn new(c: char, mut mods: Modifiers) -> Self {
+        use {KeyCode as K, KeyEvent as E, Modifiers as M};
+
+        if !c.is_control() {
+            if !mods.is_empty() {
+                mods.remove(M::SHIFT); // TODO Validate: no SHIFT even if
+                                       // `c` is uppercase
+            }
+            return E(K::Char(c), mods);
+        }
+        match c {
+            '\x00' => E(K::Char('@'), mods | M::CTRL), // '\0'
+            '\x01' => E(K::Char('A'), mods | M::CTRL),
+            '\x02' => E(K::Char('B'), mods | M::CTRL),
+            '\x03' => E(K::Char('C'), mods | M::CTRL),
+            '\x04' => E(K::Char('D'), mods | M::CTRL),
+            '\x05' => E(K::Char('E'), mods | M::CTRL),
+            '\x06' => E(K::Char('F'), mods | M::CTRL),
+            '\x07' => E(K::Char('G'), mods | M::CTRL), // '\a'
+            #[cfg(not(windows))]
+            '\x08' => E(K::Backspace, mods), // '\b'
+            #[cfg(windows)]
+            '\x08' => E(K::Char('H'), mods | M::CTRL),
+            #[cfg(not(windows))]
+            '\x09' => {
+                // '\t'
+                if mods.contains(M::SHIFT) {
+                    mods.remove(M::SHIFT);
+                    E(K::BackTab, mods)
+                } else {
+                    E(K::Tab, mods)
+                }
+            }
+            #[cfg(windows)]
+            '\x09' => E(K::Char('I'), mods | M::CTRL),
+            '\x0a' => E(K::Char('J'), mods | M::CTRL), // '\n' (10)
+            '\x0b' => E(K::Char('K'), mods | M::CTRL),
+            '\x0c' => E(K::Char('L'), mods | M::CTRL),
+            #[cfg(not(windows))]
+            '\x0d' => E(K::Enter, mods), // '\r' (13)
+            #[cfg(windows)]
+            '\x0d' => E(K::Char('M'), mods | M::CTRL),
+            '\x0e' => E(K::Char('N'), mods | M::CTRL),
+            '\x0f' => E(K::Char('O'), mods | M::CTRL),
+            '\x10' => E(K::Char('P'), mods | M::CTRL),
+            '\x11' => E(K::Char('Q'), mods | M::CTRL),
+            '\x12' => E(K::Char('R'), mods | M::CTRL),
+            '\x13' => E(K::Char('S'), mods | M::CTRL),
+            '\x14' => E(K::Char('T'), mods | M::CTRL),
+            '\x15' => E(K::Char('U'), mods | M::CTRL),
+            '\x16' => E(K::Char('V'), mods | M::CTRL),
+            '\x17' => E(K::Char('W'), mods | M::CTRL),
+            '\x18' => E(K::Char('X'), mods | M::CTRL),
+            '\x19' => E(K::Char('Y'), mods | M::CTRL),
+            '\x1a' => E(K::Char('Z'), mods | M::CTRL),
+            '\x1b' => E(K::Esc, mods), // Ctrl-[, '\e'
+            '\x1c' => E(K::Char('\\'), mods | M::CTRL),
+            '\x1d' => E(K::Char(']'), mods | M::CTRL),
+            '\x1e' => E(K::Char('^'), mods | M::CTRL),
+            '\x1f' => E(K::Char('_'), mods | M::CTRL),
+            '\x7f' => E(K::Backspace, mods), // Rubout, Ctrl-?
+            '\u{9b}' => E(K::Esc, mods | M::SHIFT),
+            _ => E(K::Null, mods),
+        }
+    }
+
+    /// Constructor from `char` with Ctrl modifier
+    #[must_use]
+    pub fn ctrl(c: char) -> Self {
+        Self::new(c, Modifiers::CTRL)
+    }
+
+    /// Constructor from `char` with Alt modifier
+    #[must_use]
+    pub fn alt(c: char) -> Self {
+        Self::new(c, Modifiers::ALT)
+    }
+
+    /// ctrl-a => ctrl-A (uppercase)
+    /// shift-A => A (no SHIFT modifier)
+    /// shift-Tab => `BackTab`
+    #[must_use]
+    pub fn normalize(e: Self) -> Self {
+        use {KeyCode as K, KeyEvent as E, Modifiers as M};
+
+        match e {
+            E(K::Char(c), m) if c.is_ascii_control() => Self::new(c, m),
+            E(K::Char(c), m) if c.is_ascii_lowercase() && m.contains(M::CTRL) => {
+                E(K::Char(c.to_ascii_uppercase()), m)
+            }
+            E(K::Char(c), m) if c.is_ascii_uppercase() && m.contains(M::SHIFT) => {
+                E(K::Char(c), m ^ M::SHIFT)
+            }
+            E(K::Tab, m) if m.contains(M::SHIFT) => E(K::BackTab, m ^ M::SHIFT),
+            _ => e,
+        }
+    }
+}
+
+impl KeyEvent {
+    /// Parse a readline style key specification: modifiers (`C-` or `Ctrl-`,
+    /// `M-`, `Meta-` or `Alt-`, `S-` or `Shift-`) followed by a character or
+    /// a key name (`Enter`, `Esc`, `Tab`, `Backspace`, `Up`, `F1`, ...),
+    /// like `C-x` or `M-Enter`.
+    #[must_use]
+    pub fn parse(spec: &str) -> Option<Self> {
+        use {KeyCode as K, KeyEvent as E, Modifiers as M};
+
+        let mut mods = M::NONE;
+        let mut key = spec;
+        while let Some((prefix, rest)) = key.split_once('-') {
+            if rest.is_empty() {
+                break; // `-` key
+            }
+            mods |= match prefix.to_ascii_lowercase().as_str() {
+                "c" | "ctrl" | "control" => M::CTRL,
+                "m" | "meta" | "alt" => M::ALT,
+                "s" | "shift" => M::SHIFT,
+                _ => return None,
+            };
+            key = rest;
+        }
+        let mut chars = key.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(Self::normalize(Self::new(c, mods)));
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            "backspace" | "rubout" => K::Backspace,
+            "backtab" => K::BackTab,
+            "del" | "delete" => K::Delete,
+            "down" => K::Down,
+            "end" => K::End,
+            "enter" | "return" | "ret" => K::Enter,
+            "esc" | "escape" => K::Esc,
+            "home" => K::Home,
+            "ins" | "insert" => K::Insert,
+            "left" => K::Left,
+            "pagedown" | "pgdn" => K::PageDown,
+            "pageup" | "pgup" => K::PageUp,
+            "right" => K::Right,
+            "space" | "spc" => K::Char(' '),
+            "tab" => K::Tab,
+            "up" => K::Up,
+            name => match name.strip_prefix('f').map(str::parse) {
+                Some(Ok(n @ 1..=24)) => K::F(n),
+                _ => return None,
+            },
+        };
+        Some(Self::normalize(E(code, mods)))
+    }
+
+    /// Specification of this key, parsed back by [`KeyEvent::parse`]
+    /// (`None` for the pseudo keys like `BracketedPasteStart`)
+    #[must_use]
+    pub fn spec(&self) -> Option<String> {
+        use {KeyCode as K, Modifiers as M};
+
+        let mut spec = String::new();
+        for (m, prefix) in [(M::CTRL, "C-"), (M::ALT, "M-"), (M::SHIFT, "S-")] {
+            if self.1.contains(m) {
+                spec.push_str(prefix);
+            }
+        }
+        match self.0 {
+            K::Char(' ') => spec.push_str("Space"),
+            K::Char(c) => spec.push(c),
+            K::F(n) => spec.push_str(&format!("F{n}")),
+            K::Backspace => spec.push_str("Backspace"),
+            K::BackTab => spec.push_str("BackTab"),
+            K::Delete => spec.push_str("Delete"),
+            K::Down => spec.push_str("Down"),
+            K::End => spec.push_str("End"),
+            K::Enter => spec.push_str("Enter"),
+            K::Esc => spec.push_str("Esc"),
+            K::Home => spec.push_str("Home"),
+            K::Insert => spec.push_str("Insert"),
+            K::Left => spec.push_str("Left"),
+            K::PageDown => spec.push_str("PageDown"),
+            K::PageUp => spec.push_str("PageUp"),
+            K::Right => spec.push_str("Right"),
+            K::Tab => spec.push_str("Tab"),
+            K::Up => spec.push_str("Up"),
+            K::UnknownEscSeq
+            | K::BracketedPasteStart
+            | K::BracketedPasteEnd
+            | K::FocusIn
+            | K::FocusOut
+            | K::Null => return None,
+        }
+        Some(spec)
+    }
+}
+
+/// Keys as their specification (`C-x`)
+#[cfg(feature = "serde")]
+impl serde_core::Serialize for KeyEvent {
+    fn serialize<S: serde_core::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.spec() {
+            Some(spec) => serializer.serialize_str(&spec),
+            None => Err(serde_core::ser::Error::custom(format_args!(
+                "no specification of the key {self:?}"
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde_core::Deserialize<'de> for KeyEvent {
+    fn deserialize<D: serde_core::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Spec;
+
+        impl serde_core::de::Visitor<'_> for Spec {
+            type Value = KeyEvent;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str("a key like `C-x`")
+            }
+
+            fn visit_str<E: serde_core::de::Error>(self, v: &str) -> Result<KeyEvent, E> {
+                KeyEvent::parse(v)
+                    .ok_or_else(|| E::invalid_value(serde_core::de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(Spec)
+    }
+}
+
+impl From<char> for KeyEvent {
+    fn from(c: char) -> Self {
+        Self::new(c, Modifiers::NONE)
+    }
+}
+
+/// Input key pressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum KeyCode {
+    /// Unsupported escape sequence (on unix platform)
+    UnknownEscSeq,
+    /// ⌫ or Ctrl-H
+    Backspace,
+    /// ⇤ (usually Shift-Tab)
+    BackTab,
+    /// Paste (on unix platform)
+    BracketedPasteStart,
+    /// Paste (on unix platform)
+    BracketedPasteEnd,
+    /// Single char
+    Char(char),
+    /// ⌦
+    Delete,
+    /// ↓ arrow key
+    Down,
+    /// ⇲
+    End,
+    /// Terminal gained focus (on unix platform, when focus events are
+    /// enabled)
+    FocusIn,
+    /// Terminal lost focus (on unix platform)
+    FocusOut,
+    /// ↵ or Ctrl-M
+    Enter,
+    /// Escape or Ctrl-[
+    Esc,
+    /// Function key
+    F(u8),
+    /// ⇱
+    Home,
+    /// Insert key
+    Insert,
+    /// ← arrow key
+    Left,
+    /// \0
+    Null,
+    /// ⇟
+    PageDown,
+    /// ⇞
+    PageUp,
+    /// → arrow key
+    Right,
+    /// ⇥ or Ctrl-I
+    Tab,
+    /// ↑ arrow key
+    Up,
+}
+
+bitflags::bitflags! {
+    /// The set of modifier keys that were triggered along with a key press.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct Modifiers: u8 {
+        /// Control modifier
+        const CTRL  = 1<<3;
+        /// Escape or Alt modifier
+        const ALT  = 1<<2;
+        /// Shift modifier
+        const SHIFT = 1<<1;
+
+        /// No modifier
+        const NONE = 0;
+        /// Ctrl + Shift
+        const CTRL_SHIFT = Self::CTRL.bits() | Self::SHIFT.bits();
+        /// Alt + Shift
+        const ALT_SHIFT = Self::ALT.bits() | Self::SHIFT.bits();
+        /// Ctrl + Alt
+        const CTRL_ALT = Self::CTRL.bits() | Self::ALT.bits();
+        /// Ctrl + Alt + Shift
+        const CTRL_ALT_SHIFT = Self::CTRL.bits() | Self::ALT.bits() | Self::SHIFT.bits();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyCode as K, KeyEvent as E, Modifiers as M};
+
+    #[test]
+    fn new() {
+        assert_eq!(E::ESC, E::new('\x1b', M::NONE));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn from() {
+        assert_eq!(E(K::Tab, M::NONE), E::from('\t'));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn from() {
+        assert_eq!(E(K::Char('I'), M::CTRL), E::from('\t'));
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(E::ctrl('A'), E::normalize(E(K::Char('\x01'), M::NONE)));
+        assert_eq!(E::ctrl('A'), E::normalize(E::ctrl('a')));
+        assert_eq!(E::from('A'), E::normalize(E(K::Char('A'), M::SHIFT)));
+        assert_eq!(E(K::BackTab, M::NONE), E::normalize(E(K::Tab, M::SHIFT)));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Some(E::ctrl('X')), E::parse("C-x"));
+        assert_eq!(Some(E::ctrl('X')), E::parse("Ctrl-X"));
+        assert_eq!(Some(E::alt('.')), E::parse("M-."));
+        assert_eq!(Some(E(K::Char('-'), M::CTRL_ALT)), E::parse("C-M--"));
+        assert_eq!(Some(E::from('-')), E::parse("-"));
+        assert_eq!(Some(E(K::Enter, M::ALT)), E::parse("Alt-Enter"));
+        assert_eq!(Some(E(K::BackTab, M::NONE)), E::parse("S-Tab"));
+        assert_eq!(Some(E(K::F(5), M::NONE)), E::parse("F5"));
+        assert_eq!(Some(E(K::Char(' '), M::CTRL)), E::parse("C-Space"));
+        assert_eq!(None, E::parse(""));
+        assert_eq!(None, E::parse("F25"));
+        assert_eq!(None, E::parse("Hyper-x"));
+        assert_eq!(None, E::parse("Foo"));
+    }
+
+    #[test]
+    fn spec() {
+        for key in [
+            E::ctrl('X'),
+            E::alt('.'),
+            E(K::Enter, M::ALT),
+            E(K::F(5), M::NONE),
+        ] {
+            assert_eq!(Some(key), E::parse(&key.spec().unwrap()));
+        }
+        assert_eq!("C-M-x", E(K::Char('x'), M::CTRL_ALT).spec().unwrap());
+        assert_eq!("Space", E::from(' ').spec().unwrap());
+        assert_eq!(None, E(K::BracketedPasteStart, M::NONE).spec());
+    }
+}
@@ -0,0 +1,336 @@
+//! Kill Ring management
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::line_buffer::{DeleteListener, Direction};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    Kill,
+    Yank(usize),
+    Other,
+}
+
+/// Where killed text is added to the text killed just before
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// After (e.g. `kill-word`)
+    Append,
+    /// Before (e.g. `backward-kill-word`)
+    Prepend,
+}
+
+/// Ring of killed texts, yanked back with `yank` and `yank-pop`
+pub struct KillRing {
+    slots: Vec<String>,
+    // where we are in the kill ring
+    index: usize,
+    // slot of the last killed text
+    top: usize,
+    // whether or not the last command was a kill or a yank
+    last_action: Action,
+    killing: bool,
+    // whether or not some text has been killed since `take_killed`
+    killed: bool,
+}
+
+impl KillRing {
+    /// Create a new kill-ring of the given `size`.
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(size),
+            index: 0,
+            top: 0,
+            last_action: Action::Other,
+            killing: false,
+            killed: false,
+        }
+    }
+
+    /// Reset `last_action` state.
+    pub fn reset(&mut self) {
+        self.last_action = Action::Other;
+    }
+
+    /// Add `text` to the kill-ring.
+    pub fn kill(&mut self, text: &str, dir: Mode) {
+        if let Action::Kill = self.last_action {
+            if self.slots.capacity() == 0 {
+                // disabled
+                return;
+            }
+            match dir {
+                Mode::Append => self.slots[self.index].push_str(text),
+                Mode::Prepend => self.slots[self.index].insert_str(0, text),
+            };
+            self.killed = true;
+        } else {
+            self.last_action = Action::Kill;
+            if self.slots.capacity() == 0 {
+                // disabled
+                return;
+            }
+            if self.index == self.slots.capacity() - 1 {
+                // full
+                self.index = 0;
+            } else if !self.slots.is_empty() {
+                self.index += 1;
+            }
+            if self.index == self.slots.len() {
+                self.slots.push(String::from(text));
+            } else {
+                self.slots[self.index] = String::from(text);
+            }
+            self.top = self.index;
+            self.killed = true;
+        }
+    }
+
+    /// Text killed since the previous call, if any.
+    pub fn take_killed(&mut self) -> Option<&String> {
+        if core::mem::take(&mut self.killed) {
+            self.slots.get(self.index)
+        } else {
+            None
+        }
+    }
+
+    /// Add `text` (copied outside the terminal) to the kill-ring unless it is
+    /// the last killed text.
+    pub fn sync(&mut self, text: &str) {
+        if text.is_empty() || self.slots.get(self.index).is_some_and(|last| last == text) {
+            return;
+        }
+        self.push(text);
+    }
+
+    /// Add `text` in a new slot, without marking it as killed
+    pub fn push(&mut self, text: &str) {
+        self.last_action = Action::Other;
+        self.kill(text, Mode::Append);
+        self.last_action = Action::Other;
+        self.killed = false; // already in the clipboard
+    }
+
+    /// Change the capacity of the kill-ring, keeping the most recent texts.
+    pub fn resize(&mut self, size: usize) {
+        let (entries, _) = self.entries();
+        let entries: Vec<String> = entries.into_iter().take(size).map(str::to_owned).collect();
+        *self = Self::new(size);
+        for text in entries.iter().rev() {
+            self.push(text);
+        }
+    }
+
+    /// Yank previously killed text.
+    /// Return `None` when kill-ring is empty.
+    pub fn yank(&mut self) -> Option<&String> {
+        if self.slots.is_empty() {
+            None
+        } else {
+            self.last_action = Action::Yank(self.slots[self.index].len());
+            Some(&self.slots[self.index])
+        }
+    }
+
+    /// Yank killed text stored in previous slot.
+    /// Return `None` when the previous command was not a yank.
+    pub fn yank_pop(&mut self) -> Option<(usize, &String)> {
+        match self.last_action {
+            Action::Yank(yank_size) => {
+                if self.slots.is_empty() {
+                    return None;
+                }
+                if self.index == 0 {
+                    self.index = self.slots.len() - 1;
+                } else {
+                    self.index -= 1;
+                }
+                self.last_action = Action::Yank(self.slots[self.index].len());
+                Some((yank_size, &self.slots[self.index]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Killed texts, most recent first, with the position of the text last
+    /// yanked.
+    pub fn entries(&self) -> (Vec<&str>, usize) {
+        let len = self.slots.len();
+        let entries = (0..len)
+            .map(|i| self.slots[(self.top + len - i) % len].as_str())
+            .collect();
+        (entries, (self.top + len - self.index) % len.max(1))
+    }
+}
+
+impl DeleteListener for KillRing {
+    fn start_killing(&mut self) {
+        self.killing = true;
+    }
+
+    fn delete(&mut self, _: usize, string: &str, dir: Direction) {
+        if !self.killing {
+            return;
+        }
+        let mode = match dir {
+            Direction::Forward => Mode::Append,
+            Direction::Backward => Mode::Prepend,
+        };
+        self.kill(string, mode);
+    }
+
+    fn stop_killing(&mut self) {
+        self.killing = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, KillRing, Mode};
+
+    #[test]
+    fn disabled() {
+        let mut kill_ring = KillRing::new(0);
+        kill_ring.kill("text", Mode::Append);
+        assert!(kill_ring.slots.is_empty());
+        assert_eq!(0, kill_ring.index);
+        assert_eq!(Action::Kill, kill_ring.last_action);
+
+        assert_eq!(None, kill_ring.yank());
+        assert_eq!(Action::Kill, kill_ring.last_action);
+    }
+
+    #[test]
+    fn one_kill() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.kill("word1", Mode::Append);
+        assert_eq!(0, kill_ring.index);
+        assert_eq!(1, kill_ring.slots.len());
+        assert_eq!("word1", kill_ring.slots[0]);
+        assert_eq!(Action::Kill, kill_ring.last_action);
+    }
+
+    #[test]
+    fn kill_append() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.kill(" word2", Mode::Append);
+        assert_eq!(0, kill_ring.index);
+        assert_eq!(1, kill_ring.slots.len());
+        assert_eq!("word1 word2", kill_ring.slots[0]);
+        assert_eq!(Action::Kill, kill_ring.last_action);
+    }
+
+    #[test]
+    fn kill_backward() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.kill("word1", Mode::Prepend);
+        kill_ring.kill("word2 ", Mode::Prepend);
+        assert_eq!(0, kill_ring.index);
+        assert_eq!(1, kill_ring.slots.len());
+        assert_eq!("word2 word1", kill_ring.slots[0]);
+        assert_eq!(Action::Kill, kill_ring.last_action);
+    }
+
+    #[test]
+    fn kill_other_kill() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word2", Mode::Append);
+        assert_eq!(1, kill_ring.index);
+        assert_eq!(2, kill_ring.slots.len());
+        assert_eq!("word1", kill_ring.slots[0]);
+        assert_eq!("word2", kill_ring.slots[1]);
+        assert_eq!(Action::Kill, kill_ring.last_action);
+    }
+
+    #[test]
+    fn many_kill() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word2", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word3", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word4", Mode::Append);
+        assert_eq!(1, kill_ring.index);
+        assert_eq!(2, kill_ring.slots.len());
+        assert_eq!("word3", kill_ring.slots[0]);
+        assert_eq!("word4", kill_ring.slots[1]);
+        assert_eq!(Action::Kill, kill_ring.last_action);
+    }
+
+    #[test]
+    fn yank() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word2", Mode::Append);
+
+        assert_eq!(Some(&"word2".to_owned()), kill_ring.yank());
+        assert_eq!(Action::Yank(5), kill_ring.last_action);
+        assert_eq!(Some(&"word2".to_owned()), kill_ring.yank());
+        assert_eq!(Action::Yank(5), kill_ring.last_action);
+    }
+
+    #[test]
+    fn yank_pop() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("longword2", Mode::Append);
+
+        assert_eq!(None, kill_ring.yank_pop());
+        kill_ring.yank();
+        assert_eq!(Some((9, &"word1".to_owned())), kill_ring.yank_pop());
+        assert_eq!(Some((5, &"longword2".to_owned())), kill_ring.yank_pop());
+        assert_eq!(Some((9, &"word1".to_owned())), kill_ring.yank_pop());
+    }
+
+    #[test]
+    fn resize() {
+        let mut kill_ring = KillRing::new(3);
+        for text in ["word1", "word2", "word3"] {
+            kill_ring.kill(text, Mode::Append);
+            kill_ring.reset();
+        }
+        kill_ring.resize(2);
+        assert_eq!((vec!["word3", "word2"], 0), kill_ring.entries());
+        assert_eq!(None, kill_ring.take_killed());
+    }
+
+    #[test]
+    fn entries() {
+        let mut kill_ring = KillRing::new(2);
+        assert_eq!((vec![], 0), kill_ring.entries());
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word2", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word3", Mode::Append);
+        assert_eq!((vec!["word3", "word2"], 0), kill_ring.entries());
+        kill_ring.yank();
+        kill_ring.yank_pop();
+        assert_eq!((vec!["word3", "word2"], 1), kill_ring.entries());
+    }
+
+    #[test]
+    fn take_killed() {
+        let mut kill_ring = KillRing::new(2);
+        assert_eq!(None, kill_ring.take_killed());
+        kill_ring.kill("word1", Mode::Append);
+        assert_eq!(Some(&"word1".to_owned()), kill_ring.take_killed());
+        assert_eq!(None, kill_ring.take_killed());
+
+        kill_ring.sync("word1");
+        assert_eq!(1, kill_ring.slots.len());
+        kill_ring.sync("word2");
+        assert_eq!(1, kill_ring.index);
+        assert_eq!("word2", kill_ring.slots[1]);
+        assert_eq!(None, kill_ring.take_killed());
+    }
+}
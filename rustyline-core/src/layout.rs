@@ -0,0 +1,313 @@
+//! Width of the text and position of the cursor on screen
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// Tell how grapheme clusters are supported / rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GraphemeClusterMode {
+    /// Support grapheme clustering
+    #[default]
+    Unicode,
+    /// Doesn't support shaping
+    WcWidth,
+    /// Skip zero-width joiner
+    NoZwj,
+}
+
+impl GraphemeClusterMode {
+    /// Use environment variables to guess current mode
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn from_env() -> Self {
+        let gcm = match std::env::var("TERM_PROGRAM").as_deref() {
+            Ok("Apple_Terminal") => GraphemeClusterMode::Unicode,
+            Ok("iTerm.app") => GraphemeClusterMode::Unicode,
+            Ok("WezTerm") => GraphemeClusterMode::Unicode,
+            Err(std::env::VarError::NotPresent) => match std::env::var("TERM").as_deref() {
+                Ok("xterm-kitty") => GraphemeClusterMode::NoZwj,
+                _ => GraphemeClusterMode::WcWidth,
+            },
+            _ => GraphemeClusterMode::WcWidth,
+        };
+        log::debug!(target: "rustyline", "GraphemeClusterMode: {:?}", gcm);
+        gcm
+    }
+
+    /// Grapheme with / number of columns
+    pub fn width(&self, s: &str) -> Unit {
+        self.width_with(s, AmbiguousWidth::Narrow)
+    }
+
+    /// Grapheme with / number of columns, East Asian ambiguous characters
+    /// being `ambiguous` wide
+    pub fn width_with(&self, s: &str, ambiguous: AmbiguousWidth) -> Unit {
+        match self {
+            GraphemeClusterMode::Unicode => uwidth(s, ambiguous),
+            GraphemeClusterMode::WcWidth => wcwidth(s, ambiguous),
+            GraphemeClusterMode::NoZwj => no_zwj(s, ambiguous),
+        }
+    }
+}
+
+/// Width of the East Asian ambiguous characters (Unicode Standard Annex #11:
+/// box drawing, some punctuation and symbols like `§` or `○`), which
+/// depends on the terminal and its font rather than on the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AmbiguousWidth {
+    /// One column (most terminals, non-CJK locales)
+    #[default]
+    Narrow,
+    /// Two columns (CJK locales or fonts)
+    Wide,
+}
+
+impl AmbiguousWidth {
+    /// Character width / number of columns (like [`cwidh`])
+    #[must_use]
+    pub fn char_width(self, c: char) -> Unit {
+        use unicode_width::UnicodeWidthChar;
+        let width = match self {
+            AmbiguousWidth::Narrow => c.width(),
+            AmbiguousWidth::Wide => c.width_cjk(),
+        };
+        Unit::try_from(width.unwrap_or(0)).unwrap()
+    }
+}
+
+/// Height, width
+pub type Unit = u16;
+/// Character width / number of columns
+#[must_use]
+pub fn cwidh(c: char) -> Unit {
+    AmbiguousWidth::Narrow.char_width(c)
+}
+
+fn uwidth(s: &str, ambiguous: AmbiguousWidth) -> Unit {
+    use unicode_width::UnicodeWidthStr;
+    let width = match ambiguous {
+        AmbiguousWidth::Narrow => s.width(),
+        AmbiguousWidth::Wide => s.width_cjk(),
+    };
+    Unit::try_from(width).unwrap()
+}
+
+fn wcwidth(s: &str, ambiguous: AmbiguousWidth) -> Unit {
+    let mut width = 0;
+    for c in s.chars() {
+        width += ambiguous.char_width(c);
+    }
+    width
+}
+
+const ZWJ: char = '\u{200D}';
+fn no_zwj(s: &str, ambiguous: AmbiguousWidth) -> Unit {
+    let mut width = 0;
+    for x in s.split(ZWJ) {
+        width += uwidth(x, ambiguous);
+    }
+    width
+}
+
+/// Minimum number of printable ASCII characters measured at once
+const ASCII_RUN: usize = 16;
+
+/// Number of leading bytes of `bytes` which can be measured without
+/// segmentation: printable ASCII characters, each one column wide and a
+/// grapheme cluster of its own (except the last one of a run, which may be
+/// extended by a combining mark). Always 0 without the `simd` feature.
+#[must_use]
+pub fn ascii_run(bytes: &[u8]) -> usize {
+    if !cfg!(feature = "simd") {
+        return 0;
+    }
+    let n = printable_ascii_len(bytes);
+    if n == bytes.len() {
+        n
+    } else if n > ASCII_RUN {
+        n - 1
+    } else {
+        0
+    }
+}
+
+/// Length of the leading run of printable ASCII characters, checked eight
+/// bytes at a time
+fn printable_ascii_len(bytes: &[u8]) -> usize {
+    const LO: u64 = 0x0101_0101_0101_0101;
+    const HI: u64 = LO * 0x80;
+    let mut i = 0;
+    while let Some(chunk) = bytes.get(i..i + 8) {
+        let x = u64::from_le_bytes(chunk.try_into().unwrap());
+        // high bit set for the bytes >= 0x80, < 0x20 or == 0x7f
+        let del = x ^ (LO * 0x7f);
+        if (x | x.wrapping_sub(LO * 0x20) | (del.wrapping_sub(LO) & !del)) & HI != 0 {
+            break;
+        }
+        i += 8;
+    }
+    i + bytes[i..]
+        .iter()
+        .position(|b| !(b' '..=b'~').contains(b))
+        .unwrap_or(bytes.len() - i)
+}
+
+/// Position on screen
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    /// Column: the leftmost column is number 0.
+    pub col: Unit,
+    /// Row: the highest row is number 0.
+    pub row: Unit,
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.row.cmp(&other.row) {
+            Ordering::Equal => self.col.cmp(&other.col),
+            o => o,
+        }
+    }
+}
+
+/// Layout of the prompt and the line on screen
+#[derive(Debug, Default)]
+pub struct Layout {
+    /// How grapheme clusters are rendered
+    pub grapheme_cluster_mode: GraphemeClusterMode,
+    /// How East Asian ambiguous characters are rendered
+    pub ambiguous_width: AmbiguousWidth,
+    /// Prompt Unicode/visible width and height
+    pub prompt_size: Position,
+    /// Whether the editor prompt is displayed (not a search prompt)
+    pub default_prompt: bool,
+    /// Cursor position (relative to the start of the prompt)
+    pub cursor: Position,
+    /// Number of rows used so far (from start of prompt to end of input)
+    pub end: Position,
+    /// Continuation prompts width (from the second line of input)
+    pub continuation_prompt_cols: Vec<Unit>,
+    /// Prompt width of the first line of input when it is scrolled out of
+    /// the viewport
+    pub first_prompt_col: Option<Unit>,
+}
+
+impl Layout {
+    /// Empty layout
+    #[must_use]
+    pub fn new(grapheme_cluster_mode: GraphemeClusterMode) -> Self {
+        Self {
+            grapheme_cluster_mode,
+            ambiguous_width: AmbiguousWidth::Narrow,
+            prompt_size: Position::default(),
+            default_prompt: false,
+            cursor: Position::default(),
+            end: Position::default(),
+            continuation_prompt_cols: Vec::new(),
+            first_prompt_col: None,
+        }
+    }
+
+    /// Number of columns of `s`
+    #[must_use]
+    pub fn width(&self, s: &str) -> Unit {
+        self.grapheme_cluster_mode
+            .width_with(s, self.ambiguous_width)
+    }
+
+    /// Column where the `line_number`th line of input starts (after the
+    /// prompt or continuation prompt)
+    #[must_use]
+    pub fn line_offset(&self, line_number: usize) -> Unit {
+        match line_number {
+            0 => self.first_prompt_col.unwrap_or(self.prompt_size.col),
+            n => self
+                .continuation_prompt_cols
+                .get(n - 1)
+                .copied()
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn printable_ascii_len() {
+        use super::printable_ascii_len;
+        assert_eq!(0, printable_ascii_len(b""));
+        assert_eq!(5, printable_ascii_len(b"hello"));
+        let line = b"0123456789abcdef0123456789abcdef";
+        assert_eq!(32, printable_ascii_len(line));
+        for i in 0..line.len() {
+            for b in [b'\t', b'\x1b', 0x7f, 0xc3] {
+                let mut bytes = line.to_vec();
+                bytes[i] = b;
+                assert_eq!(i, printable_ascii_len(&bytes));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn ascii_run() {
+        assert_eq!(1, super::ascii_run(b"a"));
+        assert_eq!(0, super::ascii_run("abc\u{301}".as_bytes()));
+        assert_eq!(
+            31,
+            super::ascii_run("0123456789abcdef0123456789abcdef\u{301}".as_bytes())
+        );
+    }
+
+    use super::AmbiguousWidth::{Narrow as N, Wide};
+
+    #[test]
+    fn ambiguous_width() {
+        use super::GraphemeClusterMode;
+        // box drawing, white circle
+        for gcm in [
+            GraphemeClusterMode::Unicode,
+            GraphemeClusterMode::WcWidth,
+            GraphemeClusterMode::NoZwj,
+        ] {
+            assert_eq!(2, gcm.width("\u{2500}\u{25cb}"));
+            assert_eq!(4, gcm.width_with("\u{2500}\u{25cb}", Wide));
+            assert_eq!(3, gcm.width_with("a\u{4f60}", Wide));
+        }
+    }
+
+    #[test]
+    fn unicode_width() {
+        assert_eq!(1, super::uwidth("a", N));
+        assert_eq!(2, super::uwidth("👩‍🚀", N));
+        assert_eq!(2, super::uwidth("👋🏿", N));
+        assert_eq!(2, super::uwidth("👨‍👩‍👧‍👦", N));
+        // iTerm2, Terminal.app KO
+        assert_eq!(2, super::uwidth("👩🏼‍👨🏼‍👦🏼‍👦🏼", N));
+        // WezTerm KO, Terminal.app (rendered width = 1)
+        assert_eq!(2, super::uwidth("❤️", N));
+    }
+    #[test]
+    fn test_wcwidth() {
+        assert_eq!(1, super::wcwidth("a", N));
+        assert_eq!(4, super::wcwidth("👩‍🚀", N));
+        assert_eq!(4, super::wcwidth("👋🏿", N));
+        assert_eq!(8, super::wcwidth("👨‍👩‍👧‍👦", N));
+        assert_eq!(16, super::wcwidth("👩🏼‍👨🏼‍👦🏼‍👦🏼", N));
+        assert_eq!(1, super::wcwidth("❤️", N));
+    }
+    #[test]
+    fn test_no_zwj() {
+        assert_eq!(1, super::no_zwj("a", N));
+        assert_eq!(4, super::no_zwj("👩‍🚀", N));
+        assert_eq!(2, super::no_zwj("👋🏿", N));
+        assert_eq!(8, super::no_zwj("👨‍👩‍👧‍👦", N));
+        assert_eq!(8, super::no_zwj("👩🏼‍👨🏼‍👦🏼‍👦🏼", N));
+        assert_eq!(2, super::no_zwj("️❤️", N));
+    }
+}
@@ -0,0 +1,53 @@
+//! Editing core of Rustyline: line buffer, movements, kill ring and undo
+//! manager, without any I/O.
+//!
+//! This crate is `#![no_std]` (with `alloc`) so that shells without an
+//! operating system (e.g. on a microcontroller) can reuse the exact editing
+//! semantics of Rustyline while reading keys and rendering the line by
+//! themselves.
+//!
+//! # Example
+//!
+//! ```
+//! use rustyline_core::line_buffer::{LineBuffer, NoListener};
+//! use rustyline_core::movement::Word;
+//! use rustyline_core::undo::Changeset;
+//!
+//! let mut line = LineBuffer::with_capacity(80);
+//! let mut changes = Changeset::new();
+//! line.insert_str(0, "hello world", &mut changes);
+//! line.set_pos(11);
+//! line.delete_prev_word(Word::Emacs, 1, &mut changes);
+//! assert_eq!("hello ", line.as_str());
+//! changes.undo(&mut line, 1);
+//! assert_eq!("hello world", line.as_str());
+//! ```
+//!
+//! [`engine::Engine`] bundles these pieces with the command semantics of
+//! Rustyline (kill accumulation, grouped undo, ...) for GUI and TUI editors,
+//! and [`keymap`] interprets the keys (see [`keys`]) editing the line like
+//! the Emacs mode of Rustyline.
+//!
+//! # Features
+//!
+//! - `std` (default): time based undo grouping with [`std::time::Instant`]
+//!   (see [`Changeset::set_clock`](undo::Changeset::set_clock) otherwise)
+//! - `debug-line-buffer`: `LineBuffer` invariant checks
+//! - `simd`: measure of long ASCII runs eight bytes at a time
+#![cfg_attr(not(test), no_std)]
+#![warn(missing_docs)]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod bidi;
+pub mod engine;
+pub mod keymap;
+pub mod keys;
+pub mod kill_ring;
+pub mod layout;
+pub mod line_buffer;
+pub mod movement;
+pub mod normalize;
+pub mod undo;
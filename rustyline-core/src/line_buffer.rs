@@ -0,0 +1,2999 @@
+//! Line buffer with current cursor position
+use crate::layout::{Layout, Position, Unit};
+use crate::movement::{At, CharSearch, Movement, RepeatCount, Word};
+use alloc::borrow::ToOwned;
+use alloc::string::{Drain, String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::fmt;
+use core::iter;
+use core::ops::{Deref, Index, Range};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Default maximum buffer size for the line read
+pub const MAX_LINE: usize = 4096;
+/// Spaces inserted by (smart) indentation, sliced to the required width
+pub const INDENT: &str = "                                ";
+
+/// Word's case change
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordAction {
+    /// Capitalize word
+    Capitalize,
+    /// lowercase word
+    Lowercase,
+    /// uppercase word
+    Uppercase,
+}
+
+/// Delete (kill) direction
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// After cursor
+    #[default]
+    Forward,
+    /// Before cursor
+    Backward,
+}
+
+/// Listener to be notified when some text is deleted.
+pub trait DeleteListener {
+    /// used to make the distinction between simple character(s) deletion and
+    /// word(s)/line(s) deletion
+    fn start_killing(&mut self) {}
+    /// `string` deleted at `idx` index
+    fn delete(&mut self, idx: usize, string: &str, dir: Direction);
+    /// used to make the distinction between simple character(s) deletion and
+    /// word(s)/line(s) deletion
+    fn stop_killing(&mut self) {}
+}
+
+/// Listener to be notified when the line is modified.
+pub trait ChangeListener: DeleteListener {
+    /// `c`har inserted at `idx` index
+    fn insert_char(&mut self, idx: usize, c: char);
+    /// `string` inserted at `idx` index
+    fn insert_str(&mut self, idx: usize, string: &str);
+    /// `old` text replaced by `new` text at `idx` index
+    fn replace(&mut self, idx: usize, old: &str, new: &str);
+}
+
+/// Listener ignoring all the changes
+pub struct NoListener;
+
+impl DeleteListener for NoListener {
+    fn delete(&mut self, _idx: usize, _string: &str, _dir: Direction) {}
+}
+impl ChangeListener for NoListener {
+    fn insert_char(&mut self, _idx: usize, _c: char) {}
+
+    fn insert_str(&mut self, _idx: usize, _string: &str) {}
+
+    fn replace(&mut self, _idx: usize, _old: &str, _new: &str) {}
+}
+
+// TODO split / cache lines ?
+
+/// Represent the current input (text and cursor position).
+///
+/// The methods do text manipulations or/and cursor movements.
+pub struct LineBuffer {
+    buf: String,                // Edited line buffer (rl_line_buffer)
+    pos: usize,                 // Current cursor position (byte position) (rl_point)
+    can_growth: bool,           // Whether to allow dynamic growth
+    marks: [Option<usize>; 26], // Vi marks `a`-`z` (byte positions)
+    word_chars: Arc<str>,       // Word constituents besides alphanumerics
+}
+
+impl fmt::Debug for LineBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineBuffer")
+            .field("buf", &self.buf)
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+impl LineBuffer {
+    /// Create a new line buffer with the given maximum `capacity`.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: String::with_capacity(capacity),
+            pos: 0,
+            can_growth: false,
+            marks: [None; 26],
+            word_chars: Arc::from(""),
+        }
+    }
+
+    /// Set the characters (besides alphanumerics) which are part of words
+    pub fn set_word_chars(&mut self, word_chars: Arc<str>) {
+        self.word_chars = word_chars;
+    }
+
+    /// Set whether to allow dynamic allocation
+    #[must_use]
+    pub fn can_growth(mut self, can_growth: bool) -> Self {
+        self.can_growth = can_growth;
+        self
+    }
+
+    fn must_truncate(&self, new_len: usize) -> bool {
+        !self.can_growth && new_len > self.buf.capacity()
+    }
+
+    /// Buffer containing `line` with the cursor at `pos`
+    #[must_use]
+    pub fn init(line: &str, pos: usize) -> Self {
+        let mut lb = Self::with_capacity(MAX_LINE);
+        assert!(lb.insert_str(0, line, &mut NoListener));
+        lb.set_pos(pos);
+        lb
+    }
+
+    /// Extracts a string slice containing the entire buffer.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.buf
+    }
+
+    /// Converts a buffer into a `String` without copying or allocating.
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.buf
+    }
+
+    /// Current cursor position (byte position)
+    #[must_use]
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Set cursor position (byte position)
+    pub fn set_pos(&mut self, pos: usize) {
+        assert!(pos <= self.buf.len());
+        self.pos = pos;
+    }
+
+    /// Returns the length of this buffer, in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns `true` if this buffer has a length of zero.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Set line content (`buf`) and cursor position (`pos`).
+    pub fn update<C: ChangeListener>(&mut self, buf: &str, pos: usize, cl: &mut C) {
+        assert!(pos <= buf.len());
+        let end = self.len();
+        self.drain(0..end, Direction::default(), cl);
+        let max = self.buf.capacity();
+        if self.must_truncate(buf.len()) {
+            self.insert_str(0, &buf[..max], cl);
+            self.pos = max.min(pos);
+        } else {
+            self.insert_str(0, buf, cl);
+            self.pos = pos;
+        }
+        // marks do not survive a whole content replacement
+        self.marks = [None; 26];
+    }
+
+    fn end_of_line(&self) -> usize {
+        if let Some(n) = self.buf[self.pos..].find('\n') {
+            n + self.pos
+        } else {
+            self.buf.len()
+        }
+    }
+
+    fn start_of_line(&self) -> usize {
+        if let Some(i) = self.buf[..self.pos].rfind('\n') {
+            // `i` is before the new line, e.g. at the end of the previous one.
+            i + 1
+        } else {
+            0
+        }
+    }
+
+    /// Returns the character at current cursor position.
+    #[must_use]
+    pub fn grapheme_at_cursor(&self) -> Option<&str> {
+        if self.pos == self.buf.len() {
+            None
+        } else {
+            self.buf[self.pos..].graphemes(true).next()
+        }
+    }
+
+    /// Returns the position of the character just after the current cursor
+    /// position.
+    #[must_use]
+    pub fn next_pos(&self, n: RepeatCount) -> Option<usize> {
+        if self.pos == self.buf.len() {
+            return None;
+        }
+        self.buf[self.pos..]
+            .grapheme_indices(true)
+            .take(usize::from(n))
+            .last()
+            .map(|(i, s)| i + self.pos + s.len())
+    }
+
+    /// Returns the position of the character just before the current cursor
+    /// position.
+    fn prev_pos(&self, n: RepeatCount) -> Option<usize> {
+        if self.pos == 0 {
+            return None;
+        }
+        self.buf[..self.pos]
+            .grapheme_indices(true)
+            .rev()
+            .take(usize::from(n))
+            .last()
+            .map(|(i, _)| i)
+    }
+
+    /// Insert the character `ch` at current cursor position
+    /// and advance cursor position accordingly.
+    /// Return `None` when maximum buffer size has been reached,
+    /// `true` when the character has been appended to the end of the line.
+    pub fn insert<C: ChangeListener>(
+        &mut self,
+        ch: char,
+        n: RepeatCount,
+        cl: &mut C,
+    ) -> Option<bool> {
+        let n = usize::from(n);
+        let shift = ch.len_utf8() * n;
+        if self.must_truncate(self.buf.len() + shift) {
+            return None;
+        }
+        let push = self.pos == self.buf.len();
+        if n == 1 {
+            self.buf.insert(self.pos, ch);
+            self.shift_marks(self.pos..self.pos, ch.len_utf8());
+            cl.insert_char(self.pos, ch);
+        } else {
+            let text = iter::repeat_n(ch, n).collect::<String>();
+            let pos = self.pos;
+            self.insert_str(pos, &text, cl);
+        }
+        self.pos += shift;
+        Some(push)
+    }
+
+    /// Yank/paste `text` at current position.
+    /// Return `None` when maximum buffer size has been reached or is empty,
+    /// `true` when the character has been appended to the end of the line.
+    pub fn yank<C: ChangeListener>(
+        &mut self,
+        text: &str,
+        n: RepeatCount,
+        cl: &mut C,
+    ) -> Option<bool> {
+        let n = usize::from(n);
+        let shift = text.len() * n;
+        if text.is_empty() || self.must_truncate(self.buf.len() + shift) {
+            return None;
+        }
+        let push = self.pos == self.buf.len();
+        let pos = self.pos;
+        if n == 1 {
+            self.insert_str(pos, text, cl);
+        } else {
+            let text = text.repeat(n);
+            self.insert_str(pos, &text, cl);
+        }
+        self.pos += shift;
+        Some(push)
+    }
+
+    /// Delete previously yanked text and yank/paste `text` at current position.
+    pub fn yank_pop<C: ChangeListener>(
+        &mut self,
+        yank_size: usize,
+        text: &str,
+        cl: &mut C,
+    ) -> Option<bool> {
+        let end = self.pos;
+        let start = end - yank_size;
+        self.drain(start..end, Direction::default(), cl);
+        self.pos -= yank_size;
+        self.yank(text, 1, cl)
+    }
+
+    /// Move cursor on the left.
+    pub fn move_backward(&mut self, n: RepeatCount) -> bool {
+        match self.prev_pos(n) {
+            Some(pos) => {
+                self.pos = pos;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move cursor on the right.
+    pub fn move_forward(&mut self, n: RepeatCount) -> bool {
+        match self.next_pos(n) {
+            Some(pos) => {
+                self.pos = pos;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move cursor to the start of the buffer.
+    pub fn move_buffer_start(&mut self) -> bool {
+        if self.pos > 0 {
+            self.pos = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move cursor to the end of the buffer.
+    pub fn move_buffer_end(&mut self) -> bool {
+        if self.pos == self.buf.len() {
+            false
+        } else {
+            self.pos = self.buf.len();
+            true
+        }
+    }
+
+    /// Move cursor to the start of the line.
+    pub fn move_home(&mut self) -> bool {
+        let start = self.start_of_line();
+        if self.pos > start {
+            self.pos = start;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move cursor to the end of the line.
+    pub fn move_end(&mut self) -> bool {
+        let end = self.end_of_line();
+        if self.pos == end {
+            false
+        } else {
+            self.pos = end;
+            true
+        }
+    }
+
+    /// Is cursor at the end of input (whitespaces after cursor is discarded)
+    #[must_use]
+    pub fn is_end_of_input(&self) -> bool {
+        self.pos >= self.buf.trim_end().len()
+    }
+
+    /// Delete the character at the right of the cursor without altering the
+    /// cursor position. Basically this is what happens with the "Delete"
+    /// keyboard key.
+    /// Return the number of characters deleted.
+    pub fn delete<D: DeleteListener>(&mut self, n: RepeatCount, dl: &mut D) -> Option<String> {
+        match self.next_pos(n) {
+            Some(pos) => {
+                let start = self.pos;
+                let chars = self
+                    .drain(start..pos, Direction::Forward, dl)
+                    .collect::<String>();
+                Some(chars)
+            }
+            None => None,
+        }
+    }
+
+    /// Delete the character at the left of the cursor.
+    /// Basically that is what happens with the "Backspace" keyboard key.
+    pub fn backspace<D: DeleteListener>(&mut self, n: RepeatCount, dl: &mut D) -> bool {
+        match self.prev_pos(n) {
+            Some(pos) => {
+                let end = self.pos;
+                self.drain(pos..end, Direction::Backward, dl);
+                self.pos = pos;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Kill the text from point to the end of the line.
+    pub fn kill_line<D: DeleteListener>(&mut self, dl: &mut D) -> bool {
+        if !self.buf.is_empty() && self.pos < self.buf.len() {
+            let start = self.pos;
+            let end = self.end_of_line();
+            if start == end {
+                self.delete(1, dl);
+            } else {
+                self.drain(start..end, Direction::Forward, dl);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Kill the text from point to the end of the buffer.
+    pub fn kill_buffer<D: DeleteListener>(&mut self, dl: &mut D) -> bool {
+        if !self.buf.is_empty() && self.pos < self.buf.len() {
+            let start = self.pos;
+            let end = self.buf.len();
+            self.drain(start..end, Direction::Forward, dl);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Kill backward from point to the beginning of the line.
+    pub fn discard_line<D: DeleteListener>(&mut self, dl: &mut D) -> bool {
+        if self.pos > 0 && !self.buf.is_empty() {
+            let start = self.start_of_line();
+            let end = self.pos;
+            if end == start {
+                self.backspace(1, dl)
+            } else {
+                self.drain(start..end, Direction::Backward, dl);
+                self.pos = start;
+                true
+            }
+        } else {
+            false
+        }
+    }
+
+    /// Kill backward from point to the beginning of the buffer.
+    pub fn discard_buffer<D: DeleteListener>(&mut self, dl: &mut D) -> bool {
+        if self.pos > 0 && !self.buf.is_empty() {
+            let end = self.pos;
+            self.drain(0..end, Direction::Backward, dl);
+            self.pos = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Exchange the char before cursor with the character at cursor.
+    pub fn transpose_chars<C: ChangeListener>(&mut self, cl: &mut C) -> bool {
+        if self.pos == 0 || self.buf.graphemes(true).count() < 2 {
+            return false;
+        }
+        if self.pos == self.buf.len() {
+            self.move_backward(1);
+        }
+        let chars = self.delete(1, cl).unwrap();
+        self.move_backward(1);
+        self.yank(&chars, 1, cl);
+        self.move_forward(1);
+        true
+    }
+
+    /// Go left until start of word
+    fn prev_word_pos(&self, pos: usize, word_def: Word, n: RepeatCount) -> Option<usize> {
+        if pos == 0 {
+            return None;
+        }
+        let mut sow = 0;
+        let mut gis = self.buf[..pos].grapheme_indices(true).rev();
+        'outer: for _ in 0..n {
+            sow = 0;
+            let mut gj = gis.next();
+            'inner: loop {
+                if let Some((j, y)) = gj {
+                    let gi = gis.next();
+                    if let Some((_, x)) = gi {
+                        if is_start_of_word(word_def, &self.word_chars, x, y) {
+                            sow = j;
+                            break 'inner;
+                        }
+                        gj = gi;
+                    } else {
+                        break 'outer;
+                    }
+                } else {
+                    break 'outer;
+                }
+            }
+        }
+        Some(sow)
+    }
+
+    /// Moves the cursor to the beginning of previous word.
+    pub fn move_to_prev_word(&mut self, word_def: Word, n: RepeatCount) -> bool {
+        if let Some(pos) = self.prev_word_pos(self.pos, word_def, n) {
+            self.pos = pos;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Delete the previous word, maintaining the cursor at the start of the
+    /// current word.
+    pub fn delete_prev_word<D: DeleteListener>(
+        &mut self,
+        word_def: Word,
+        n: RepeatCount,
+        dl: &mut D,
+    ) -> bool {
+        if let Some(pos) = self.prev_word_pos(self.pos, word_def, n) {
+            let end = self.pos;
+            self.drain(pos..end, Direction::Backward, dl);
+            self.pos = pos;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next_word_pos(&self, pos: usize, at: At, word_def: Word, n: RepeatCount) -> Option<usize> {
+        if pos == self.buf.len() {
+            return None;
+        }
+        let mut wp = 0;
+        let mut gis = self.buf[pos..].grapheme_indices(true);
+        let mut gi = if at == At::BeforeEnd {
+            // TODO Validate
+            gis.next()
+        } else {
+            None
+        };
+        'outer: for _ in 0..n {
+            wp = 0;
+            gi = gis.next();
+            'inner: loop {
+                if let Some((i, x)) = gi {
+                    let gj = gis.next();
+                    if let Some((j, y)) = gj {
+                        if at == At::Start && is_start_of_word(word_def, &self.word_chars, x, y) {
+                            wp = j;
+                            break 'inner;
+                        } else if at != At::Start
+                            && is_end_of_word(word_def, &self.word_chars, x, y)
+                        {
+                            if word_def == Word::Emacs || at == At::AfterEnd {
+                                wp = j;
+                            } else {
+                                wp = i;
+                            }
+                            break 'inner;
+                        }
+                        gi = gj;
+                    } else {
+                        break 'outer;
+                    }
+                } else {
+                    break 'outer;
+                }
+            }
+        }
+        if wp == 0 {
+            if word_def == Word::Emacs || at == At::AfterEnd {
+                Some(self.buf.len())
+            } else {
+                match gi {
+                    Some((i, _)) if i != 0 => Some(i + pos),
+                    _ => None,
+                }
+            }
+        } else {
+            Some(wp + pos)
+        }
+    }
+
+    /// Moves the cursor to the end of next word.
+    pub fn move_to_next_word(&mut self, at: At, word_def: Word, n: RepeatCount) -> bool {
+        if let Some(pos) = self.next_word_pos(self.pos, at, word_def, n) {
+            self.pos = pos;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves the cursor to the same column in the line above
+    pub fn move_to_line_up(&mut self, n: RepeatCount, layout: &Layout) -> bool {
+        match self.buf[..self.pos].rfind('\n') {
+            Some(off) => {
+                let mut line_number = self.buf[..off].matches('\n').count() + 1;
+                let column =
+                    layout.width(&self.buf[off + 1..self.pos]) + layout.line_offset(line_number);
+
+                let mut dest_start = self.buf[..off].rfind('\n').map_or(0, |n| n + 1);
+                let mut dest_end = off;
+                line_number -= 1;
+                for _ in 1..n {
+                    if dest_start == 0 {
+                        break;
+                    }
+                    dest_end = dest_start - 1;
+                    dest_start = self.buf[..dest_end].rfind('\n').map_or(0, |n| n + 1);
+                    line_number -= 1;
+                }
+                let offset = layout.line_offset(line_number);
+                let gidx = self.buf[dest_start..dest_end]
+                    .grapheme_indices(true)
+                    .nth(column.saturating_sub(offset) as usize);
+
+                self.pos = gidx.map_or(off, |(idx, _)| dest_start + idx); // if there's no enough columns
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// N lines up starting from the current one
+    ///
+    /// Fails if the cursor is on the first line
+    fn n_lines_up(&self, n: RepeatCount) -> Option<(usize, usize)> {
+        let mut start = if let Some(off) = self.buf[..self.pos].rfind('\n') {
+            off + 1
+        } else {
+            return None;
+        };
+        let end = self.buf[self.pos..]
+            .find('\n')
+            .map_or_else(|| self.buf.len(), |x| self.pos + x + 1);
+        for _ in 0..n {
+            if let Some(off) = self.buf[..start - 1].rfind('\n') {
+                start = off + 1;
+            } else {
+                start = 0;
+                break;
+            }
+        }
+        Some((start, end))
+    }
+
+    /// N lines down starting from the current one
+    ///
+    /// Fails if the cursor is on the last line
+    fn n_lines_down(&self, n: RepeatCount) -> Option<(usize, usize)> {
+        let mut end = if let Some(off) = self.buf[self.pos..].find('\n') {
+            self.pos + off + 1
+        } else {
+            return None;
+        };
+        let start = self.buf[..self.pos].rfind('\n').unwrap_or(0);
+        for _ in 0..n {
+            if let Some(off) = self.buf[end..].find('\n') {
+                end = end + off + 1;
+            } else {
+                end = self.buf.len();
+                break;
+            };
+        }
+        Some((start, end))
+    }
+
+    /// Moves the cursor to the same column in the line above
+    pub fn move_to_line_down(&mut self, n: RepeatCount, layout: &Layout) -> bool {
+        match self.buf[self.pos..].find('\n') {
+            Some(off) => {
+                let line_start = self.buf[..self.pos].rfind('\n').map_or(0, |n| n + 1);
+                let mut line_number = self.buf[..line_start].matches('\n').count();
+                let column =
+                    layout.width(&self.buf[line_start..self.pos]) + layout.line_offset(line_number);
+                let mut dest_start = self.pos + off + 1;
+                let mut dest_end = self.buf[dest_start..]
+                    .find('\n')
+                    .map_or_else(|| self.buf.len(), |v| dest_start + v);
+                line_number += 1;
+                for _ in 1..n {
+                    if dest_end == self.buf.len() {
+                        break;
+                    }
+                    dest_start = dest_end + 1;
+                    dest_end = self.buf[dest_start..]
+                        .find('\n')
+                        .map_or_else(|| self.buf.len(), |v| dest_start + v);
+                    line_number += 1;
+                }
+                let offset = layout.line_offset(line_number);
+                self.pos = self.buf[dest_start..dest_end]
+                    .grapheme_indices(true)
+                    .nth(column.saturating_sub(offset) as usize)
+                    .map_or(dest_end, |(idx, _)| dest_start + idx); // if there's no enough columns
+                debug_assert!(self.pos <= self.buf.len());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cursor position on screen (relative to the prompt row) for each
+    /// grapheme boundary when lines wrap at `cols` columns
+    fn visual_positions(&self, cols: Unit, layout: &Layout) -> Vec<(usize, Position)> {
+        let mut line_number = 0;
+        let mut cursor = Position {
+            col: layout.line_offset(0),
+            row: 0,
+        };
+        let mut positions = Vec::with_capacity(self.buf.len() + 1);
+        positions.push((0, cursor));
+        for (i, g) in self.buf.grapheme_indices(true) {
+            if g == "\n" {
+                line_number += 1;
+                cursor.row += 1;
+                cursor.col = layout.line_offset(line_number);
+            } else {
+                let width = layout.width(g);
+                cursor.col += width;
+                if cursor.col > cols {
+                    cursor.row += 1;
+                    cursor.col = width;
+                }
+                if cursor.col == cols {
+                    cursor.row += 1;
+                    cursor.col = 0;
+                }
+            }
+            positions.push((i + g.len(), cursor));
+        }
+        positions
+    }
+
+    /// Moves the cursor to the same column `n` display rows above (or below
+    /// when `dir` is `Direction::Forward`), wrapped lines being split at
+    /// `cols` columns.
+    /// Return `false` when there is no such row.
+    pub fn move_to_visual_line(
+        &mut self,
+        n: RepeatCount,
+        dir: Direction,
+        cols: Unit,
+        layout: &Layout,
+    ) -> bool {
+        let positions = self.visual_positions(cols, layout);
+        let Some(&(_, cursor)) = positions.iter().find(|(pos, _)| *pos == self.pos) else {
+            return false;
+        };
+        let row = match dir {
+            Direction::Forward => cursor.row.checked_add(n),
+            Direction::Backward => cursor.row.checked_sub(n),
+        };
+        let Some(row) = row else {
+            return false;
+        };
+        let mut on_row = positions.iter().filter(|(_, p)| p.row == row).peekable();
+        let Some(&&(first, _)) = on_row.peek() else {
+            return false;
+        };
+        self.pos = on_row
+            .take_while(|(_, p)| p.col <= cursor.col)
+            .last()
+            .map_or(first, |(pos, _)| *pos);
+        true
+    }
+
+    /// Moves the cursor to the start (`Direction::Backward`) or the end
+    /// (`Direction::Forward`) of the current display row.
+    /// Return `false` when the cursor has not moved.
+    pub fn move_to_visual_line_edge(
+        &mut self,
+        dir: Direction,
+        cols: Unit,
+        layout: &Layout,
+    ) -> bool {
+        let positions = self.visual_positions(cols, layout);
+        let Some(&(_, cursor)) = positions.iter().find(|(pos, _)| *pos == self.pos) else {
+            return false;
+        };
+        let mut on_row = positions.iter().filter(|(_, p)| p.row == cursor.row);
+        let pos = match dir {
+            Direction::Forward => on_row.next_back(),
+            Direction::Backward => on_row.next(),
+        };
+        match pos {
+            Some(&(pos, _)) if pos != self.pos => {
+                self.pos = pos;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Display column of `pos` (the prompt width being included)
+    pub fn column(&self, pos: usize, layout: &Layout) -> Unit {
+        let start = self.buf[..pos].rfind('\n').map_or(0, |n| n + 1);
+        let line_number = self.buf[..start].matches('\n').count();
+        layout.line_offset(line_number) + layout.width(&self.buf[start..pos])
+    }
+
+    /// Byte offset of the first grapheme displayed at or after `column` in
+    /// the line starting at `start` or `None` if the line is too short
+    pub fn column_to_pos(&self, start: usize, column: Unit, layout: &Layout) -> Option<usize> {
+        let line_number = self.buf[..start].matches('\n').count();
+        let mut col = layout.line_offset(line_number);
+        let end = self.buf[start..]
+            .find('\n')
+            .map_or(self.buf.len(), |n| start + n);
+        for (i, g) in self.buf[start..end].grapheme_indices(true) {
+            if col >= column {
+                return Some(start + i);
+            }
+            col += layout.width(g);
+        }
+        (col >= column).then_some(end)
+    }
+
+    /// Display columns and byte ranges, on each line, of the rectangle whose
+    /// opposite corners are `anchor` and the cursor (vi visual block)
+    pub fn block(&self, anchor: usize, layout: &Layout) -> (Range<Unit>, Vec<Range<usize>>) {
+        let anchor = anchor.min(self.buf.len());
+        let grapheme_width = |pos: usize| {
+            self.buf[pos..]
+                .graphemes(true)
+                .next()
+                .filter(|g| *g != "\n")
+                .map_or(1, |g| layout.width(g).max(1))
+        };
+        let (a, b) = (min(anchor, self.pos), anchor.max(self.pos));
+        let (ca, cb) = (self.column(anchor, layout), self.column(self.pos, layout));
+        let columns = ca.min(cb)..(ca + grapheme_width(anchor)).max(cb + grapheme_width(self.pos));
+
+        let first = self.buf[..a].rfind('\n').map_or(0, |n| n + 1);
+        let last = self.buf[b..].find('\n').map_or(self.buf.len(), |n| b + n);
+        let line_numbers = self.buf[..first].matches('\n').count()..;
+        let mut start = first;
+        let mut ranges = vec![];
+        for (line_number, line) in line_numbers.zip(self.buf[first..last].split('\n')) {
+            let mut col = layout.line_offset(line_number);
+            let mut range = start + line.len()..start + line.len();
+            for (i, g) in line.grapheme_indices(true) {
+                let width = layout.width(g);
+                if col < columns.end && col + width > columns.start {
+                    if range.start > start + i {
+                        range.start = start + i;
+                    }
+                    range.end = start + i + g.len();
+                }
+                col += width;
+            }
+            ranges.push(range);
+            start += line.len() + 1;
+        }
+        (columns, ranges)
+    }
+
+    /// All the cursors (the current position and the extra `cursors`),
+    /// sorted, and the index of the current position
+    fn all_cursors(&self, cursors: &[usize]) -> (Vec<usize>, usize) {
+        let mut all = Vec::with_capacity(cursors.len() + 1);
+        all.push(self.pos);
+        all.extend(cursors.iter().filter(|&&pos| pos <= self.buf.len()));
+        all.sort_unstable();
+        all.dedup();
+        let main = all.binary_search(&self.pos).unwrap_or_default();
+        (all, main)
+    }
+
+    /// Inverse of `all_cursors`: merged cursors are dropped
+    fn set_cursors(&mut self, all: &[usize], main: usize, cursors: &mut Vec<usize>) {
+        self.pos = all[main];
+        cursors.clear();
+        cursors.extend(all.iter().filter(|&&pos| pos != self.pos));
+        cursors.dedup();
+    }
+
+    /// Insert `text` at the cursor position and at each of the extra
+    /// `cursors`, which are shifted accordingly.
+    /// Return `false` when maximum buffer size has been reached or `text` is
+    /// empty.
+    pub fn insert_at_cursors<C: ChangeListener>(
+        &mut self,
+        cursors: &mut Vec<usize>,
+        text: &str,
+        cl: &mut C,
+    ) -> bool {
+        let (mut all, main) = self.all_cursors(cursors);
+        if text.is_empty() || self.must_truncate(self.buf.len() + text.len() * all.len()) {
+            return false;
+        }
+        for &pos in all.iter().rev() {
+            self.insert_str(pos, text, cl);
+        }
+        for (i, pos) in all.iter_mut().enumerate() {
+            *pos += (i + 1) * text.len();
+        }
+        self.set_cursors(&all, main, cursors);
+        true
+    }
+
+    /// Delete `n` graphemes after (`Direction::Forward`) or before
+    /// (`Direction::Backward`) the cursor position and each of the extra
+    /// `cursors`, without crossing the next or previous cursor.
+    /// Return `false` when nothing has been deleted.
+    pub fn delete_at_cursors<D: DeleteListener>(
+        &mut self,
+        cursors: &mut Vec<usize>,
+        n: RepeatCount,
+        dir: Direction,
+        dl: &mut D,
+    ) -> bool {
+        let (mut all, main) = self.all_cursors(cursors);
+        let ranges: Vec<Range<usize>> = (0..all.len())
+            .map(|i| {
+                let pos = all[i];
+                match dir {
+                    Direction::Forward => {
+                        let end = self.grapheme_after(pos, n);
+                        pos..all.get(i + 1).map_or(end, |&next| end.min(next))
+                    }
+                    Direction::Backward => {
+                        let start = self.grapheme_before(pos, n);
+                        i.checked_sub(1).map_or(start, |prev| start.max(all[prev]))..pos
+                    }
+                }
+            })
+            .collect();
+        if ranges.iter().all(Range::is_empty) {
+            return false;
+        }
+        for range in ranges.iter().rev().filter(|range| !range.is_empty()) {
+            self.drain(range.clone(), dir, dl);
+        }
+        let mut deleted = 0;
+        for (pos, range) in all.iter_mut().zip(ranges) {
+            *pos = range.start - deleted;
+            deleted += range.len();
+        }
+        self.set_cursors(&all, main, cursors);
+        true
+    }
+
+    /// Move the cursor position and each of the extra `cursors` by `n`
+    /// graphemes forward or backward.
+    /// Return `false` when no cursor has moved.
+    pub fn move_cursors(
+        &mut self,
+        cursors: &mut Vec<usize>,
+        n: RepeatCount,
+        dir: Direction,
+    ) -> bool {
+        let (mut all, main) = self.all_cursors(cursors);
+        let mut moved = false;
+        for pos in &mut all {
+            let new = match dir {
+                Direction::Forward => self.grapheme_after(*pos, n),
+                Direction::Backward => self.grapheme_before(*pos, n),
+            };
+            moved |= new != *pos;
+            *pos = new;
+        }
+        self.set_cursors(&all, main, cursors);
+        moved
+    }
+
+    /// Position `n` graphemes after `pos` (or the end of the buffer)
+    fn grapheme_after(&self, pos: usize, n: RepeatCount) -> usize {
+        self.buf[pos..]
+            .grapheme_indices(true)
+            .take(usize::from(n))
+            .last()
+            .map_or(pos, |(i, s)| pos + i + s.len())
+    }
+
+    /// Position `n` graphemes before `pos` (or the start of the buffer)
+    fn grapheme_before(&self, pos: usize, n: RepeatCount) -> usize {
+        self.buf[..pos]
+            .grapheme_indices(true)
+            .rev()
+            .take(usize::from(n))
+            .last()
+            .map_or(pos, |(i, _)| i)
+    }
+
+    fn search_char_pos(&self, cs: CharSearch, n: RepeatCount) -> Option<usize> {
+        let n = usize::from(n);
+        let mut shift = 0;
+        let search_result = match cs {
+            CharSearch::Backward(c) | CharSearch::BackwardAfter(c) => self.buf[..self.pos]
+                .char_indices()
+                .rev()
+                .filter(|&(_, ch)| ch == c)
+                .take(n)
+                .last()
+                .map(|(i, _)| i),
+            CharSearch::Forward(c) | CharSearch::ForwardBefore(c) => {
+                if let Some(cc) = self.grapheme_at_cursor() {
+                    shift = self.pos + cc.len();
+                    if shift < self.buf.len() {
+                        self.buf[shift..]
+                            .char_indices()
+                            .filter(|&(_, ch)| ch == c)
+                            .take(n)
+                            .last()
+                            .map(|(i, _)| i)
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                }
+            }
+        };
+        search_result.map(|pos| match cs {
+            CharSearch::Backward(_) => pos,
+            CharSearch::BackwardAfter(c) => pos + c.len_utf8(),
+            CharSearch::Forward(_) => shift + pos,
+            CharSearch::ForwardBefore(_) => {
+                shift + pos
+                    - self.buf[..shift + pos]
+                        .chars()
+                        .next_back()
+                        .unwrap()
+                        .len_utf8()
+            }
+        })
+    }
+
+    /// Move cursor to the matching character position.
+    /// Return `true` when the search succeeds.
+    pub fn move_to(&mut self, cs: CharSearch, n: RepeatCount) -> bool {
+        if let Some(pos) = self.search_char_pos(cs, n) {
+            self.pos = pos;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Kill from the cursor to the end of the current word,
+    /// or, if between words, to the end of the next word.
+    pub fn delete_word<D: DeleteListener>(
+        &mut self,
+        at: At,
+        word_def: Word,
+        n: RepeatCount,
+        dl: &mut D,
+    ) -> bool {
+        if let Some(pos) = self.next_word_pos(self.pos, at, word_def, n) {
+            let start = self.pos;
+            self.drain(start..pos, Direction::Forward, dl);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Delete range specified by `cs` search.
+    pub fn delete_to<D: DeleteListener>(
+        &mut self,
+        cs: CharSearch,
+        n: RepeatCount,
+        dl: &mut D,
+    ) -> bool {
+        let search_result = match cs {
+            CharSearch::ForwardBefore(c) => self.search_char_pos(CharSearch::Forward(c), n),
+            _ => self.search_char_pos(cs, n),
+        };
+        if let Some(pos) = search_result {
+            match cs {
+                CharSearch::Backward(_) | CharSearch::BackwardAfter(_) => {
+                    let end = self.pos;
+                    self.pos = pos;
+                    self.drain(pos..end, Direction::Backward, dl);
+                }
+                CharSearch::ForwardBefore(_) => {
+                    let start = self.pos;
+                    self.drain(start..pos, Direction::Forward, dl);
+                }
+                CharSearch::Forward(c) => {
+                    let start = self.pos;
+                    self.drain(start..pos + c.len_utf8(), Direction::Forward, dl);
+                }
+            };
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&self) -> Option<usize> {
+        if self.pos == self.buf.len() {
+            return None;
+        }
+        self.buf[self.pos..]
+            .grapheme_indices(true)
+            .find_map(|(i, ch)| {
+                if is_word_char(Word::Emacs, &self.word_chars, ch) {
+                    Some(i)
+                } else {
+                    None
+                }
+            })
+            .map(|i| i + self.pos)
+    }
+
+    /// Alter the next word.
+    pub fn edit_word<C: ChangeListener>(&mut self, a: WordAction, cl: &mut C) -> bool {
+        if let Some(start) = self.skip_whitespace() {
+            if let Some(end) = self.next_word_pos(start, At::AfterEnd, Word::Emacs, 1) {
+                if start == end {
+                    return false;
+                }
+                let word = self
+                    .drain(start..end, Direction::default(), cl)
+                    .collect::<String>();
+                let result = match a {
+                    WordAction::Capitalize => {
+                        let ch = word.graphemes(true).next().unwrap();
+                        let cap = ch.to_uppercase();
+                        cap + &word[ch.len()..].to_lowercase()
+                    }
+                    WordAction::Lowercase => word.to_lowercase(),
+                    WordAction::Uppercase => word.to_uppercase(),
+                };
+                self.insert_str(start, &result, cl);
+                self.pos = start + result.len();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Transpose two words
+    pub fn transpose_words<C: ChangeListener>(&mut self, n: RepeatCount, cl: &mut C) -> bool {
+        let word_def = Word::Emacs;
+        self.move_to_next_word(At::AfterEnd, word_def, n);
+        let w2_end = self.pos;
+        self.move_to_prev_word(word_def, 1);
+        let w2_beg = self.pos;
+        self.move_to_prev_word(word_def, n);
+        let w1_beg = self.pos;
+        self.move_to_next_word(At::AfterEnd, word_def, 1);
+        let w1_end = self.pos;
+        if w1_beg == w2_beg || w2_beg < w1_end {
+            return false;
+        }
+
+        let w1 = self.buf[w1_beg..w1_end].to_owned();
+
+        let w2 = self
+            .drain(w2_beg..w2_end, Direction::default(), cl)
+            .collect::<String>();
+        self.insert_str(w2_beg, &w1, cl);
+
+        self.drain(w1_beg..w1_end, Direction::default(), cl);
+        self.insert_str(w1_beg, &w2, cl);
+
+        self.pos = w2_end;
+        true
+    }
+
+    /// Replaces the content between [`start`..`end`] with `text`
+    /// and positions the cursor to the end of text.
+    pub fn replace<C: ChangeListener>(&mut self, range: Range<usize>, text: &str, cl: &mut C) {
+        let start = range.start;
+        cl.replace(start, self.buf.index(range.clone()), text);
+        self.shift_marks(range.clone(), text.len());
+        self.buf.drain(range);
+        if start == self.buf.len() {
+            self.buf.push_str(text);
+        } else {
+            self.buf.insert_str(start, text);
+        }
+        self.pos = start + text.len();
+    }
+
+    /// Insert the `s`tring at the specified position.
+    /// Return `true` if the text has been inserted at the end of the line.
+    pub fn insert_str<C: ChangeListener>(&mut self, idx: usize, s: &str, cl: &mut C) -> bool {
+        cl.insert_str(idx, s);
+        self.shift_marks(idx..idx, s.len());
+        if idx == self.buf.len() {
+            self.buf.push_str(s);
+            true
+        } else {
+            self.buf.insert_str(idx, s);
+            false
+        }
+    }
+
+    /// Remove the specified `range` in the line.
+    pub fn delete_range<D: DeleteListener>(&mut self, range: Range<usize>, dl: &mut D) {
+        self.set_pos(range.start);
+        self.drain(range, Direction::default(), dl);
+    }
+
+    fn drain<D: DeleteListener>(
+        &mut self,
+        range: Range<usize>,
+        dir: Direction,
+        dl: &mut D,
+    ) -> Drain<'_> {
+        dl.delete(range.start, &self.buf[range.start..range.end], dir);
+        self.shift_marks(range.clone(), 0);
+        self.buf.drain(range)
+    }
+
+    /// Set the vi mark `mark` (`a`-`z`) at current cursor position.
+    /// Return `false` when `mark` is not a valid mark name.
+    pub fn set_mark(&mut self, mark: char) -> bool {
+        if let Some(i) = mark_index(mark) {
+            self.marks[i] = Some(self.pos);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the position of the mark `mark` or, when `line_wise`, the
+    /// position of the first non-blank character of the line containing it.
+    fn mark_pos(&self, mark: char, line_wise: bool) -> Option<usize> {
+        let pos = self.marks[mark_index(mark)?]?;
+        if !line_wise {
+            return Some(pos);
+        }
+        let start = self.buf[..pos].rfind('\n').map_or(0, |i| i + 1);
+        let blanks = self.buf[start..]
+            .find(|c: char| c == '\n' || !c.is_whitespace())
+            .unwrap_or(self.buf.len() - start);
+        Some(start + blanks)
+    }
+
+    /// Returns the position of the `n`th blank line before or after the
+    /// cursor (or the start / end of the buffer)
+    fn paragraph_pos(&self, n: RepeatCount, dir: Direction) -> usize {
+        let is_blank = |start: usize| {
+            self.buf[start..]
+                .split('\n')
+                .next()
+                .is_none_or(|line| line.trim().is_empty())
+        };
+        let mut pos = self.pos;
+        for _ in 0..n {
+            let mut start = self.buf[..pos].rfind('\n').map_or(0, |i| i + 1);
+            // from a blank line, skip to the end of the blank lines first
+            let mut skip_blank = is_blank(start);
+            match dir {
+                Direction::Forward => loop {
+                    let Some(next) = self.buf[start..].find('\n').map(|i| start + i + 1) else {
+                        return self.buf.len();
+                    };
+                    start = next;
+                    if !is_blank(start) {
+                        skip_blank = false;
+                    } else if !skip_blank {
+                        break;
+                    }
+                },
+                Direction::Backward => loop {
+                    if start == 0 {
+                        return 0;
+                    }
+                    start = self.buf[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+                    if !is_blank(start) {
+                        skip_blank = false;
+                    } else if !skip_blank {
+                        break;
+                    }
+                },
+            }
+            pos = start;
+        }
+        pos
+    }
+
+    /// Move cursor to the `n`th blank line before or after the current line.
+    /// Return `false` when the cursor has not moved.
+    pub fn move_to_paragraph(&mut self, n: RepeatCount, dir: Direction) -> bool {
+        let pos = self.paragraph_pos(n, dir);
+        if pos == self.pos {
+            return false;
+        }
+        self.pos = pos;
+        true
+    }
+
+    /// Returns the position of the first non-blank character of the `n`th
+    /// line (the last line when there are less than `n` lines).
+    fn line_pos(&self, n: RepeatCount) -> usize {
+        let start = self
+            .buf
+            .match_indices('\n')
+            .take(usize::from(n.max(1)) - 1)
+            .last()
+            .map_or(0, |(i, _)| i + 1);
+        let blanks = self.buf[start..]
+            .find(|c: char| c == '\n' || !c.is_whitespace())
+            .unwrap_or(self.buf.len() - start);
+        start + blanks
+    }
+
+    /// Returns the whole lines between current cursor position and the `n`th
+    /// line.
+    fn line_range(&self, n: RepeatCount) -> (usize, usize) {
+        let pos = self.line_pos(n);
+        let (start, end) = (min(pos, self.pos), pos.max(self.pos));
+        let start = self.buf[..start].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.buf[end..]
+            .find('\n')
+            .map_or(self.buf.len(), |i| end + i);
+        (start, end)
+    }
+
+    /// Move cursor to the first non-blank character of the `n`th line.
+    /// Return `false` when the cursor has not moved.
+    pub fn move_to_line(&mut self, n: RepeatCount) -> bool {
+        let pos = self.line_pos(n);
+        if pos == self.pos {
+            return false;
+        }
+        self.pos = pos;
+        true
+    }
+
+    /// Returns the range between current cursor position and the mark `mark`
+    /// (extended to whole lines when `line_wise`).
+    fn mark_range(&self, mark: char, line_wise: bool) -> Option<(usize, usize)> {
+        let pos = self.mark_pos(mark, line_wise)?;
+        let (start, end) = if pos < self.pos {
+            (pos, self.pos)
+        } else {
+            (self.pos, pos)
+        };
+        if line_wise {
+            let start = self.buf[..start].rfind('\n').map_or(0, |i| i + 1);
+            let end = self.buf[end..]
+                .find('\n')
+                .map_or(self.buf.len(), |i| end + i);
+            Some((start, end))
+        } else if start == end {
+            None
+        } else {
+            Some((start, end))
+        }
+    }
+
+    /// Move cursor to the mark `mark`.
+    pub fn move_to_mark(&mut self, mark: char, line_wise: bool) -> bool {
+        match self.mark_pos(mark, line_wise) {
+            Some(pos) if pos != self.pos => {
+                self.pos = pos;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move the cursor to `pos` (clamped to the buffer and to a char
+    /// boundary).
+    /// Return `false` when the cursor does not move.
+    pub fn move_to_position(&mut self, pos: usize) -> bool {
+        let mut pos = pos.min(self.buf.len());
+        while !self.buf.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        if pos == self.pos {
+            return false;
+        }
+        self.pos = pos;
+        true
+    }
+
+    /// Keep marks in sync when `range` is replaced by `len` bytes.
+    fn shift_marks(&mut self, range: Range<usize>, len: usize) {
+        for mark in self.marks.iter_mut().flatten() {
+            if *mark >= range.end {
+                *mark = *mark - range.end + range.start + len;
+            } else if *mark > range.start {
+                *mark = range.start;
+            }
+        }
+    }
+
+    /// Return the content between current cursor position and `mvt` position.
+    /// Return `None` when the buffer is empty or when the movement fails.
+    #[must_use]
+    pub fn copy(&self, mvt: &Movement) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        match *mvt {
+            Movement::WholeLine => {
+                let start = self.start_of_line();
+                let end = self.end_of_line();
+                if start == end {
+                    None
+                } else {
+                    Some(self.buf[start..self.pos].to_owned())
+                }
+            }
+            Movement::BeginningOfLine => {
+                let start = self.start_of_line();
+                if self.pos == start {
+                    None
+                } else {
+                    Some(self.buf[start..self.pos].to_owned())
+                }
+            }
+            Movement::ViFirstPrint => {
+                if self.pos == 0 {
+                    None
+                } else {
+                    self.next_word_pos(0, At::Start, Word::Big, 1)
+                        .map(|pos| self.buf[pos..self.pos].to_owned())
+                }
+            }
+            Movement::EndOfLine => {
+                let end = self.end_of_line();
+                if self.pos == end {
+                    None
+                } else {
+                    Some(self.buf[self.pos..end].to_owned())
+                }
+            }
+            Movement::EndOfBuffer => {
+                if self.pos == self.buf.len() {
+                    None
+                } else {
+                    Some(self.buf[self.pos..].to_owned())
+                }
+            }
+            Movement::WholeBuffer => {
+                if self.buf.is_empty() {
+                    None
+                } else {
+                    Some(self.buf.clone())
+                }
+            }
+            Movement::BeginningOfBuffer => {
+                if self.pos == 0 {
+                    None
+                } else {
+                    Some(self.buf[..self.pos].to_owned())
+                }
+            }
+            Movement::BackwardWord(n, word_def) => self
+                .prev_word_pos(self.pos, word_def, n)
+                .map(|pos| self.buf[pos..self.pos].to_owned()),
+            Movement::ForwardWord(n, at, word_def) => self
+                .next_word_pos(self.pos, at, word_def, n)
+                .map(|pos| self.buf[self.pos..pos].to_owned()),
+            Movement::ViCharSearch(n, cs) => {
+                let search_result = match cs {
+                    CharSearch::ForwardBefore(c) => self.search_char_pos(CharSearch::Forward(c), n),
+                    _ => self.search_char_pos(cs, n),
+                };
+                search_result.map(|pos| match cs {
+                    CharSearch::Backward(_) | CharSearch::BackwardAfter(_) => {
+                        self.buf[pos..self.pos].to_owned()
+                    }
+                    CharSearch::ForwardBefore(_) => self.buf[self.pos..pos].to_owned(),
+                    CharSearch::Forward(c) => self.buf[self.pos..pos + c.len_utf8()].to_owned(),
+                })
+            }
+            Movement::BackwardChar(n) => self
+                .prev_pos(n)
+                .map(|pos| self.buf[pos..self.pos].to_owned()),
+            Movement::ForwardChar(n) => self
+                .next_pos(n)
+                .map(|pos| self.buf[self.pos..pos].to_owned()),
+            Movement::LineUp(n) => {
+                if let Some((start, end)) = self.n_lines_up(n) {
+                    Some(self.buf[start..end].to_owned())
+                } else {
+                    None
+                }
+            }
+            Movement::LineDown(n) => {
+                if let Some((start, end)) = self.n_lines_down(n) {
+                    Some(self.buf[start..end].to_owned())
+                } else {
+                    None
+                }
+            }
+            Movement::ViMark(mark, line_wise) => self
+                .mark_range(mark, line_wise)
+                .map(|(start, end)| self.buf[start..end].to_owned()),
+            Movement::BackwardParagraph(..)
+            | Movement::ForwardParagraph(..)
+            | Movement::GotoLine(..) => self
+                .range(mvt)
+                .map(|(start, end)| self.buf[start..end].to_owned()),
+        }
+    }
+
+    /// Return the range between current cursor position and `mvt` position.
+    fn range(&self, mvt: &Movement) -> Option<(usize, usize)> {
+        let range = match *mvt {
+            Movement::WholeLine => Some((self.start_of_line(), self.end_of_line())),
+            Movement::BeginningOfLine => Some((self.start_of_line(), self.pos)),
+            Movement::ViFirstPrint => self
+                .next_word_pos(0, At::Start, Word::Big, 1)
+                .map(|pos| (pos, self.pos)),
+            Movement::EndOfLine => Some((self.pos, self.end_of_line())),
+            Movement::EndOfBuffer => Some((self.pos, self.buf.len())),
+            Movement::WholeBuffer => Some((0, self.buf.len())),
+            Movement::BeginningOfBuffer => Some((0, self.pos)),
+            Movement::BackwardWord(n, word_def) => self
+                .prev_word_pos(self.pos, word_def, n)
+                .map(|pos| (pos, self.pos)),
+            Movement::ForwardWord(n, at, word_def) => self
+                .next_word_pos(self.pos, at, word_def, n)
+                .map(|pos| (self.pos, pos)),
+            Movement::ViCharSearch(n, cs) => {
+                let search_result = match cs {
+                    CharSearch::ForwardBefore(c) => self.search_char_pos(CharSearch::Forward(c), n),
+                    _ => self.search_char_pos(cs, n),
+                };
+                search_result.map(|pos| match cs {
+                    CharSearch::Backward(_) | CharSearch::BackwardAfter(_) => (pos, self.pos),
+                    CharSearch::ForwardBefore(_) => (self.pos, pos),
+                    CharSearch::Forward(c) => (self.pos, pos + c.len_utf8()),
+                })
+            }
+            Movement::BackwardChar(n) => self.prev_pos(n).map(|pos| (pos, self.pos)),
+            Movement::ForwardChar(n) => self.next_pos(n).map(|pos| (self.pos, pos)),
+            Movement::LineUp(n) => self.n_lines_up(n),
+            Movement::LineDown(n) => self.n_lines_down(n),
+            Movement::ViMark(mark, line_wise) => self.mark_range(mark, line_wise),
+            Movement::BackwardParagraph(n) => {
+                Some((self.paragraph_pos(n, Direction::Backward), self.pos))
+            }
+            Movement::ForwardParagraph(n) => {
+                Some((self.pos, self.paragraph_pos(n, Direction::Forward)))
+            }
+            Movement::GotoLine(n) => Some(self.line_range(n)),
+        };
+        range.filter(|(start, end)| start < end)
+    }
+
+    /// Surround the text covered by `mvt` with `c` (quote or bracket).
+    pub fn surround<C: ChangeListener>(&mut self, mvt: &Movement, c: char, cl: &mut C) -> bool {
+        let Some((start, end)) = self.range(mvt) else {
+            return false;
+        };
+        let (open, close) = surround_pair(c);
+        self.insert_str(end, close.encode_utf8(&mut [0; 4]), cl);
+        self.insert_str(start, open.encode_utf8(&mut [0; 4]), cl);
+        self.pos = start;
+        true
+    }
+
+    /// Returns the positions of the `c` quotes or brackets surrounding the
+    /// cursor.
+    fn find_surround(&self, c: char) -> Option<(usize, usize)> {
+        let (open, close) = surround_pair(c);
+        let at_cursor = self.buf[self.pos..].starts_with(open);
+        if open == close {
+            // quotes are searched only in the current line
+            let start = self.start_of_line();
+            let end = self.end_of_line();
+            let before = &self.buf[start..self.pos];
+            if at_cursor && before.matches(open).count() % 2 == 0 {
+                let from = self.pos + open.len_utf8();
+                let right = self.buf[from..end].find(open)?;
+                return Some((self.pos, from + right));
+            }
+            let left = before.rfind(open)?;
+            let right = self.buf[self.pos..end].find(open)?;
+            return Some((start + left, self.pos + right));
+        }
+        let left = if at_cursor {
+            self.pos
+        } else {
+            let mut depth = 0;
+            self.buf[..self.pos]
+                .char_indices()
+                .rev()
+                .find(|&(_, ch)| {
+                    if ch == close {
+                        depth += 1;
+                    } else if ch == open {
+                        if depth == 0 {
+                            return true;
+                        }
+                        depth -= 1;
+                    }
+                    false
+                })?
+                .0
+        };
+        let from = left + open.len_utf8();
+        let mut depth = 0;
+        let right = self.buf[from..]
+            .char_indices()
+            .find(|&(_, ch)| {
+                if ch == open {
+                    depth += 1;
+                } else if ch == close {
+                    if depth == 0 {
+                        return true;
+                    }
+                    depth -= 1;
+                }
+                false
+            })?
+            .0;
+        Some((left, from + right))
+    }
+
+    /// Delete the `c` quotes or brackets surrounding the cursor.
+    pub fn delete_surround<D: DeleteListener>(&mut self, c: char, dl: &mut D) -> bool {
+        let Some((left, right)) = self.find_surround(c) else {
+            return false;
+        };
+        let (open, close) = surround_pair(c);
+        self.drain(right..right + close.len_utf8(), Direction::default(), dl);
+        self.drain(left..left + open.len_utf8(), Direction::default(), dl);
+        self.pos = left;
+        true
+    }
+
+    /// Replace the `old` quotes or brackets surrounding the cursor by `new`
+    /// ones.
+    pub fn change_surround<C: ChangeListener>(&mut self, old: char, new: char, cl: &mut C) -> bool {
+        let Some((left, right)) = self.find_surround(old) else {
+            return false;
+        };
+        let (old_open, old_close) = surround_pair(old);
+        let (new_open, new_close) = surround_pair(new);
+        self.replace(
+            right..right + old_close.len_utf8(),
+            new_close.encode_utf8(&mut [0; 4]),
+            cl,
+        );
+        self.replace(
+            left..left + old_open.len_utf8(),
+            new_open.encode_utf8(&mut [0; 4]),
+            cl,
+        );
+        self.pos = left;
+        true
+    }
+
+    /// Move the cursor as specified by `mvt` (lines up and down at the same
+    /// column of `layout`). Return `false` for the ranges (`WholeLine` and
+    /// `WholeBuffer`) and when the cursor cannot move.
+    pub fn move_by(&mut self, mvt: &Movement, layout: &Layout) -> bool {
+        match *mvt {
+            Movement::BeginningOfLine => self.move_home(),
+            Movement::EndOfLine => self.move_end(),
+            Movement::ViFirstPrint => {
+                let moved = self.move_home();
+                if self.starts_with(char::is_whitespace) {
+                    self.move_to_next_word(At::Start, Word::Big, 1) || moved
+                } else {
+                    moved
+                }
+            }
+            Movement::BackwardWord(n, word_def) => self.move_to_prev_word(word_def, n),
+            Movement::ForwardWord(n, at, word_def) => self.move_to_next_word(at, word_def, n),
+            Movement::ViCharSearch(n, cs) => self.move_to(cs, n),
+            Movement::BackwardChar(n) => self.move_backward(n),
+            Movement::ForwardChar(n) => self.move_forward(n),
+            Movement::LineUp(n) => self.move_to_line_up(n, layout),
+            Movement::LineDown(n) => self.move_to_line_down(n, layout),
+            Movement::BeginningOfBuffer => self.move_buffer_start(),
+            Movement::EndOfBuffer => self.move_buffer_end(),
+            Movement::ViMark(mark, line_wise) => self.move_to_mark(mark, line_wise),
+            Movement::BackwardParagraph(n) => self.move_to_paragraph(n, Direction::Backward),
+            Movement::ForwardParagraph(n) => self.move_to_paragraph(n, Direction::Forward),
+            Movement::GotoLine(n) => self.move_to_line(n),
+            Movement::WholeLine | Movement::WholeBuffer => false,
+        }
+    }
+
+    /// Kill range specified by `mvt`.
+    pub fn kill<D: DeleteListener>(&mut self, mvt: &Movement, dl: &mut D) -> bool {
+        let notify = !matches!(*mvt, Movement::ForwardChar(_) | Movement::BackwardChar(_));
+        if notify {
+            dl.start_killing();
+        }
+        let killed = match *mvt {
+            Movement::ForwardChar(n) => {
+                // Delete (forward) `n` characters at point.
+                self.delete(n, dl).is_some()
+            }
+            Movement::BackwardChar(n) => {
+                // Delete `n` characters backward.
+                self.backspace(n, dl)
+            }
+            Movement::EndOfLine => {
+                // Kill the text from point to the end of the line.
+                self.kill_line(dl)
+            }
+            Movement::WholeLine => {
+                self.move_home();
+                self.kill_line(dl)
+            }
+            Movement::BeginningOfLine => {
+                // Kill backward from point to the beginning of the line.
+                self.discard_line(dl)
+            }
+            Movement::BackwardWord(n, word_def) => {
+                // kill `n` words backward (until start of word)
+                self.delete_prev_word(word_def, n, dl)
+            }
+            Movement::ForwardWord(n, at, word_def) => {
+                // kill `n` words forward (until start/end of word)
+                self.delete_word(at, word_def, n, dl)
+            }
+            Movement::ViCharSearch(n, cs) => self.delete_to(cs, n, dl),
+            Movement::LineUp(n) => {
+                if let Some((start, end)) = self.n_lines_up(n) {
+                    self.delete_range(start..end, dl);
+                    true
+                } else {
+                    false
+                }
+            }
+            Movement::LineDown(n) => {
+                if let Some((start, end)) = self.n_lines_down(n) {
+                    self.delete_range(start..end, dl);
+                    true
+                } else {
+                    false
+                }
+            }
+            Movement::ViFirstPrint => {
+                false // TODO
+            }
+            Movement::EndOfBuffer => {
+                // Kill the text from point to the end of the buffer.
+                self.kill_buffer(dl)
+            }
+            Movement::BeginningOfBuffer => {
+                // Kill backward from point to the beginning of the buffer.
+                self.discard_buffer(dl)
+            }
+            Movement::WholeBuffer => {
+                self.move_buffer_start();
+                self.kill_buffer(dl)
+            }
+            Movement::ViMark(mark, line_wise) => {
+                if let Some((start, end)) = self.mark_range(mark, line_wise) {
+                    self.delete_range(start..end, dl);
+                    true
+                } else {
+                    false
+                }
+            }
+            Movement::BackwardParagraph(..)
+            | Movement::ForwardParagraph(..)
+            | Movement::GotoLine(..) => {
+                if let Some((start, end)) = self.range(mvt) {
+                    self.delete_range(start..end, dl);
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        if notify {
+            dl.stop_killing();
+        }
+        killed
+    }
+
+    /// Indent range specified by `mvt`.
+    pub fn indent<C: ChangeListener>(
+        &mut self,
+        mvt: &Movement,
+        amount: u8,
+        dedent: bool,
+        cl: &mut C,
+    ) -> bool {
+        let pair = match *mvt {
+            // All inline operators are the same: indent current line
+            Movement::WholeLine
+            | Movement::BeginningOfLine
+            | Movement::ViFirstPrint
+            | Movement::EndOfLine
+            | Movement::BackwardChar(..)
+            | Movement::ForwardChar(..)
+            | Movement::ViCharSearch(..) => Some((self.pos, self.pos)),
+            Movement::EndOfBuffer => Some((self.pos, self.buf.len())),
+            Movement::WholeBuffer => Some((0, self.buf.len())),
+            Movement::BeginningOfBuffer => Some((0, self.pos)),
+            Movement::BackwardWord(n, word_def) => self
+                .prev_word_pos(self.pos, word_def, n)
+                .map(|pos| (pos, self.pos)),
+            Movement::ForwardWord(n, at, word_def) => self
+                .next_word_pos(self.pos, at, word_def, n)
+                .map(|pos| (self.pos, pos)),
+            Movement::LineUp(n) => self.n_lines_up(n),
+            Movement::LineDown(n) => self.n_lines_down(n),
+            Movement::ViMark(mark, line_wise) => self.mark_range(mark, line_wise),
+            Movement::BackwardParagraph(..)
+            | Movement::ForwardParagraph(..)
+            | Movement::GotoLine(..) => self.range(mvt),
+        };
+        let amount = usize::from(amount);
+        let (start, end) = pair.unwrap_or((self.pos, self.pos));
+        let start = self.buf[..start].rfind('\n').map_or(0, |pos| pos + 1);
+        let end = self.buf[end..]
+            .rfind('\n')
+            .map_or_else(|| self.buf.len(), |pos| end + pos);
+        let mut index = start;
+        if dedent {
+            #[expect(clippy::unnecessary_to_owned)]
+            for line in self.buf[start..end].to_string().split('\n') {
+                let max = line.len() - line.trim_start().len();
+                let deleting = min(max, amount);
+                self.drain(index..index + deleting, Direction::default(), cl);
+                if self.pos >= index {
+                    if self.pos.saturating_sub(index) < deleting {
+                        // don't wrap into the previous line
+                        self.pos = index;
+                    } else {
+                        self.pos -= deleting;
+                    }
+                }
+                index += line.len() + 1 - deleting;
+            }
+        } else {
+            #[expect(clippy::unnecessary_to_owned)]
+            for line in self.buf[start..end].to_string().split('\n') {
+                for off in (0..amount).step_by(INDENT.len()) {
+                    self.insert_str(index, &INDENT[..min(amount - off, INDENT.len())], cl);
+                }
+                if self.pos >= index {
+                    self.pos += amount;
+                }
+                index += amount + line.len() + 1;
+            }
+        }
+        true
+    }
+}
+
+impl Deref for LineBuffer {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Invariant of a [`LineBuffer`] broken by an edit
+#[cfg(any(test, feature = "debug-line-buffer"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvariantViolation {
+    /// The cursor is after the end of the buffer
+    CursorOutOfBounds {
+        /// cursor position
+        pos: usize,
+        /// buffer length
+        len: usize,
+    },
+    /// The cursor is inside a UTF-8 sequence
+    CursorNotOnCharBoundary(usize),
+    /// The cursor is inside a grapheme cluster
+    CursorNotOnGraphemeBoundary(usize),
+    /// A vi mark is after the end of the buffer or inside a UTF-8 sequence
+    InvalidMark {
+        /// mark name
+        mark: char,
+        /// mark position
+        pos: usize,
+    },
+}
+
+#[cfg(any(test, feature = "debug-line-buffer"))]
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CursorOutOfBounds { pos, len } => {
+                write!(f, "cursor at {pos} after the end of the buffer ({len})")
+            }
+            Self::CursorNotOnCharBoundary(pos) => {
+                write!(f, "cursor at {pos} inside a UTF-8 sequence")
+            }
+            Self::CursorNotOnGraphemeBoundary(pos) => {
+                write!(f, "cursor at {pos} inside a grapheme cluster")
+            }
+            Self::InvalidMark { mark, pos } => write!(f, "invalid position {pos} of mark '{mark}'"),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "debug-line-buffer"))]
+impl core::error::Error for InvariantViolation {}
+
+/// Invariant checks, for applications editing the buffer directly
+/// (e.g. from `Completer::update` in Rustyline)
+/// and their property tests
+#[cfg(any(test, feature = "debug-line-buffer"))]
+impl LineBuffer {
+    /// Buffer containing `line` with the cursor at `pos`, if valid
+    pub fn try_init(line: &str, pos: usize) -> Result<Self, InvariantViolation> {
+        let mut lb = Self::with_capacity(line.len()).can_growth(true);
+        lb.buf.push_str(line);
+        lb.pos = pos;
+        lb.check_invariants()?;
+        Ok(lb)
+    }
+
+    /// Check that the cursor and the vi marks are on grapheme cluster (or at
+    /// least character) boundaries.
+    ///
+    /// Note that inserting a combining character or a zero width joiner
+    /// just before the cursor may legitimately merge two grapheme clusters:
+    /// only the generated text should be restricted to prevent this.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        let (pos, len) = (self.pos, self.buf.len());
+        if pos > len {
+            return Err(InvariantViolation::CursorOutOfBounds { pos, len });
+        } else if !self.buf.is_char_boundary(pos) {
+            return Err(InvariantViolation::CursorNotOnCharBoundary(pos));
+        } else if pos < len && !self.is_grapheme_boundary(pos) {
+            return Err(InvariantViolation::CursorNotOnGraphemeBoundary(pos));
+        }
+        for (mark, pos) in ('a'..='z').zip(self.marks) {
+            match pos {
+                Some(pos) if pos > len || !self.buf.is_char_boundary(pos) => {
+                    return Err(InvariantViolation::InvalidMark { mark, pos });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply `edit` and then check the invariants
+    pub fn checked<R, F: FnOnce(&mut Self) -> R>(
+        &mut self,
+        edit: F,
+    ) -> Result<R, InvariantViolation> {
+        let r = edit(self);
+        self.check_invariants()?;
+        Ok(r)
+    }
+
+    /// Valid cursor positions (from `0` to `len()` included), to pick
+    /// positions or ranges in property tests
+    #[must_use]
+    pub fn grapheme_boundaries(&self) -> Vec<usize> {
+        self.buf
+            .grapheme_indices(true)
+            .map(|(i, _)| i)
+            .chain(iter::once(self.buf.len()))
+            .collect()
+    }
+
+    fn is_grapheme_boundary(&self, pos: usize) -> bool {
+        self.buf.grapheme_indices(true).any(|(i, _)| i == pos)
+    }
+}
+
+const fn surround_pair(c: char) -> (char, char) {
+    match c {
+        '(' | ')' => ('(', ')'),
+        '[' | ']' => ('[', ']'),
+        '{' | '}' => ('{', '}'),
+        '<' | '>' => ('<', '>'),
+        c => (c, c),
+    }
+}
+
+fn mark_index(mark: char) -> Option<usize> {
+    if mark.is_ascii_lowercase() {
+        Some(mark as usize - 'a' as usize)
+    } else {
+        None
+    }
+}
+
+fn is_start_of_word(word_def: Word, word_chars: &str, previous: &str, grapheme: &str) -> bool {
+    (!is_word_char(word_def, word_chars, previous) && is_word_char(word_def, word_chars, grapheme))
+        || (word_def == Word::Vi
+            && !is_other_char(word_chars, previous)
+            && is_other_char(word_chars, grapheme))
+}
+fn is_end_of_word(word_def: Word, word_chars: &str, grapheme: &str, next: &str) -> bool {
+    (!is_word_char(word_def, word_chars, next) && is_word_char(word_def, word_chars, grapheme))
+        || (word_def == Word::Vi
+            && !is_other_char(word_chars, next)
+            && is_other_char(word_chars, grapheme))
+}
+
+fn is_word_char(word_def: Word, word_chars: &str, grapheme: &str) -> bool {
+    match word_def {
+        Word::Emacs => grapheme
+            .chars()
+            .all(|c| c.is_alphanumeric() || word_chars.contains(c)),
+        Word::Vi => is_vi_word_char(word_chars, grapheme),
+        Word::Big => !grapheme.chars().any(char::is_whitespace),
+    }
+}
+fn is_vi_word_char(word_chars: &str, grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || word_chars.contains(c))
+}
+fn is_other_char(word_chars: &str, grapheme: &str) -> bool {
+    !(grapheme.chars().any(char::is_whitespace) || is_vi_word_char(word_chars, grapheme))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ChangeListener, DeleteListener, Direction, InvariantViolation, LineBuffer, NoListener,
+        WordAction, MAX_LINE,
+    };
+    use crate::{
+        layout::Layout,
+        movement::{At, CharSearch, Movement, RepeatCount, Word},
+    };
+
+    struct Listener {
+        deleted_str: Option<String>,
+    }
+
+    impl Listener {
+        fn new() -> Self {
+            Self { deleted_str: None }
+        }
+
+        fn assert_deleted_str_eq(&self, expected: &str) {
+            let actual = self.deleted_str.as_ref().expect("no deleted string");
+            assert_eq!(expected, actual)
+        }
+    }
+
+    impl DeleteListener for Listener {
+        fn delete(&mut self, _: usize, string: &str, _: Direction) {
+            self.deleted_str = Some(string.to_owned());
+        }
+    }
+    impl ChangeListener for Listener {
+        fn insert_char(&mut self, _: usize, _: char) {}
+
+        fn insert_str(&mut self, _: usize, _: &str) {}
+
+        fn replace(&mut self, _: usize, _: &str, _: &str) {}
+    }
+
+    #[test]
+    fn next_pos() {
+        let s = LineBuffer::init("ö̲g̈", 0);
+        assert_eq!(7, s.len());
+        let pos = s.next_pos(1);
+        assert_eq!(Some(4), pos);
+
+        let s = LineBuffer::init("ö̲g̈", 4);
+        let pos = s.next_pos(1);
+        assert_eq!(Some(7), pos);
+    }
+
+    #[test]
+    fn prev_pos() {
+        let s = LineBuffer::init("ö̲g̈", 4);
+        assert_eq!(7, s.len());
+        let pos = s.prev_pos(1);
+        assert_eq!(Some(0), pos);
+
+        let s = LineBuffer::init("ö̲g̈", 7);
+        let pos = s.prev_pos(1);
+        assert_eq!(Some(4), pos);
+    }
+
+    #[test]
+    fn insert() {
+        let mut s = LineBuffer::with_capacity(MAX_LINE);
+        let push = s.insert('α', 1, &mut NoListener).unwrap();
+        assert_eq!("α", s.buf);
+        assert_eq!(2, s.pos);
+        assert!(push);
+
+        let push = s.insert('ß', 1, &mut NoListener).unwrap();
+        assert_eq!("αß", s.buf);
+        assert_eq!(4, s.pos);
+        assert!(push);
+
+        s.pos = 0;
+        let push = s.insert('γ', 1, &mut NoListener).unwrap();
+        assert_eq!("γαß", s.buf);
+        assert_eq!(2, s.pos);
+        assert!(!push);
+    }
+
+    #[test]
+    fn yank_after() {
+        let mut s = LineBuffer::init("αß", 2);
+        s.move_forward(1);
+        let ok = s.yank("γδε", 1, &mut NoListener);
+        assert_eq!(Some(true), ok);
+        assert_eq!("αßγδε", s.buf);
+        assert_eq!(10, s.pos);
+    }
+
+    #[test]
+    fn yank_before() {
+        let mut s = LineBuffer::init("αε", 2);
+        let ok = s.yank("ßγδ", 1, &mut NoListener);
+        assert_eq!(Some(false), ok);
+        assert_eq!("αßγδε", s.buf);
+        assert_eq!(8, s.pos);
+    }
+
+    #[test]
+    fn moves() {
+        let mut s = LineBuffer::init("αß", 4);
+        let ok = s.move_backward(1);
+        assert_eq!("αß", s.buf);
+        assert_eq!(2, s.pos);
+        assert!(ok);
+
+        let ok = s.move_forward(1);
+        assert_eq!("αß", s.buf);
+        assert_eq!(4, s.pos);
+        assert!(ok);
+
+        let ok = s.move_home();
+        assert_eq!("αß", s.buf);
+        assert_eq!(0, s.pos);
+        assert!(ok);
+
+        let ok = s.move_end();
+        assert_eq!("αß", s.buf);
+        assert_eq!(4, s.pos);
+        assert!(ok);
+    }
+
+    #[test]
+    fn move_home_end_multiline() {
+        let text = "αa\nsdf ßc\nasdf";
+        let mut s = LineBuffer::init(text, 7);
+        let ok = s.move_home();
+        assert_eq!(text, s.buf);
+        assert_eq!(4, s.pos);
+        assert!(ok);
+
+        let ok = s.move_home();
+        assert_eq!(text, s.buf);
+        assert_eq!(4, s.pos);
+        assert!(!ok);
+
+        let ok = s.move_end();
+        assert_eq!(text, s.buf);
+        assert_eq!(11, s.pos);
+        assert!(ok);
+
+        let ok = s.move_end();
+        assert_eq!(text, s.buf);
+        assert_eq!(11, s.pos);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn move_buffer_multiline() {
+        let text = "αa\nsdf ßc\nasdf";
+        let mut s = LineBuffer::init(text, 7);
+        let ok = s.move_buffer_start();
+        assert_eq!(text, s.buf);
+        assert_eq!(0, s.pos);
+        assert!(ok);
+
+        let ok = s.move_buffer_start();
+        assert_eq!(text, s.buf);
+        assert_eq!(0, s.pos);
+        assert!(!ok);
+
+        let ok = s.move_buffer_end();
+        assert_eq!(text, s.buf);
+        assert_eq!(text.len(), s.pos);
+        assert!(ok);
+
+        let ok = s.move_buffer_end();
+        assert_eq!(text, s.buf);
+        assert_eq!(text.len(), s.pos);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn move_grapheme() {
+        let mut s = LineBuffer::init("ag̈", 4);
+        assert_eq!(4, s.len());
+        let ok = s.move_backward(1);
+        assert!(ok);
+        assert_eq!(1, s.pos);
+
+        let ok = s.move_forward(1);
+        assert!(ok);
+        assert_eq!(4, s.pos);
+    }
+
+    #[test]
+    fn delete() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("αß", 2);
+        let chars = s.delete(1, &mut cl);
+        assert_eq!("α", s.buf);
+        assert_eq!(2, s.pos);
+        assert_eq!(Some("ß".to_owned()), chars);
+
+        let ok = s.backspace(1, &mut cl);
+        assert_eq!("", s.buf);
+        assert_eq!(0, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("α");
+    }
+
+    #[test]
+    fn kill() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("αßγδε", 6);
+        let ok = s.kill_line(&mut cl);
+        assert_eq!("αßγ", s.buf);
+        assert_eq!(6, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("δε");
+
+        s.pos = 4;
+        let ok = s.discard_line(&mut cl);
+        assert_eq!("γ", s.buf);
+        assert_eq!(0, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("αß");
+    }
+
+    #[test]
+    fn kill_multiline() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("αß\nγδ 12\nε f4", 7);
+
+        let ok = s.kill_line(&mut cl);
+        assert_eq!("αß\nγ\nε f4", s.buf);
+        assert_eq!(7, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("δ 12");
+
+        let ok = s.kill_line(&mut cl);
+        assert_eq!("αß\nγε f4", s.buf);
+        assert_eq!(7, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("\n");
+
+        let ok = s.kill_line(&mut cl);
+        assert_eq!("αß\nγ", s.buf);
+        assert_eq!(7, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("ε f4");
+
+        let ok = s.kill_line(&mut cl);
+        assert_eq!(7, s.pos);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn discard_multiline() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("αß\nc γδε", 9);
+
+        let ok = s.discard_line(&mut cl);
+        assert_eq!("αß\nδε", s.buf);
+        assert_eq!(5, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("c γ");
+
+        let ok = s.discard_line(&mut cl);
+        assert_eq!("αßδε", s.buf);
+        assert_eq!(4, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("\n");
+
+        let ok = s.discard_line(&mut cl);
+        assert_eq!("δε", s.buf);
+        assert_eq!(0, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("αß");
+
+        let ok = s.discard_line(&mut cl);
+        assert_eq!(0, s.pos);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn transpose() {
+        let mut s = LineBuffer::init("aßc", 1);
+        let ok = s.transpose_chars(&mut NoListener);
+        assert_eq!("ßac", s.buf);
+        assert_eq!(3, s.pos);
+        assert!(ok);
+
+        s.buf = String::from("aßc");
+        s.pos = 3;
+        let ok = s.transpose_chars(&mut NoListener);
+        assert_eq!("acß", s.buf);
+        assert_eq!(4, s.pos);
+        assert!(ok);
+
+        s.buf = String::from("aßc");
+        s.pos = 4;
+        let ok = s.transpose_chars(&mut NoListener);
+        assert_eq!("acß", s.buf);
+        assert_eq!(4, s.pos);
+        assert!(ok);
+    }
+
+    #[test]
+    fn move_to_prev_word() {
+        let mut s = LineBuffer::init("a ß  c", 6); // before 'c'
+        let ok = s.move_to_prev_word(Word::Emacs, 1);
+        assert_eq!("a ß  c", s.buf);
+        assert_eq!(2, s.pos); // before 'ß'
+        assert!(ok);
+
+        assert!(s.move_end()); // after 'c'
+        assert_eq!(7, s.pos);
+        let ok = s.move_to_prev_word(Word::Emacs, 1);
+        assert!(ok);
+        assert_eq!(6, s.pos); // before 'c'
+
+        let ok = s.move_to_prev_word(Word::Emacs, 2);
+        assert!(ok);
+        assert_eq!(0, s.pos);
+    }
+
+    #[test]
+    fn word_chars() {
+        let mut s = LineBuffer::init("cd /usr/local-bin", 17);
+        s.set_word_chars("/-".into());
+        assert!(s.move_to_prev_word(Word::Emacs, 1));
+        assert_eq!(3, s.pos);
+        assert!(s.move_to_next_word(At::AfterEnd, Word::Emacs, 1));
+        assert_eq!(17, s.pos);
+        assert!(s.move_to_prev_word(Word::Vi, 1));
+        assert_eq!(3, s.pos);
+
+        s.set_word_chars("".into());
+        s.move_end();
+        assert!(s.move_to_prev_word(Word::Emacs, 1));
+        assert_eq!(14, s.pos);
+        assert!(s.move_to_prev_word(Word::Vi, 1));
+        assert_eq!(13, s.pos);
+    }
+
+    #[test]
+    fn move_to_prev_vi_word() {
+        let mut s = LineBuffer::init("alpha ,beta/rho; mu", 19);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(17, s.pos);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(15, s.pos);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(12, s.pos);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(11, s.pos);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(7, s.pos);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(6, s.pos);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(0, s.pos);
+        let ok = s.move_to_prev_word(Word::Vi, 1);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn move_to_prev_big_word() {
+        let mut s = LineBuffer::init("alpha ,beta/rho; mu", 19);
+        let ok = s.move_to_prev_word(Word::Big, 1);
+        assert!(ok);
+        assert_eq!(17, s.pos);
+        let ok = s.move_to_prev_word(Word::Big, 1);
+        assert!(ok);
+        assert_eq!(6, s.pos);
+        let ok = s.move_to_prev_word(Word::Big, 1);
+        assert!(ok);
+        assert_eq!(0, s.pos);
+        let ok = s.move_to_prev_word(Word::Big, 1);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn move_to_forward() {
+        let mut s = LineBuffer::init("αßγδε", 2);
+        let ok = s.move_to(CharSearch::ForwardBefore('ε'), 1);
+        assert!(ok);
+        assert_eq!(6, s.pos);
+
+        let mut s = LineBuffer::init("αßγδε", 2);
+        let ok = s.move_to(CharSearch::Forward('ε'), 1);
+        assert!(ok);
+        assert_eq!(8, s.pos);
+
+        let mut s = LineBuffer::init("αßγδε", 2);
+        let ok = s.move_to(CharSearch::Forward('ε'), 10);
+        assert!(ok);
+        assert_eq!(8, s.pos);
+    }
+
+    #[test]
+    fn move_to_backward() {
+        let mut s = LineBuffer::init("αßγδε", 8);
+        let ok = s.move_to(CharSearch::BackwardAfter('ß'), 1);
+        assert!(ok);
+        assert_eq!(4, s.pos);
+
+        let mut s = LineBuffer::init("αßγδε", 8);
+        let ok = s.move_to(CharSearch::Backward('ß'), 1);
+        assert!(ok);
+        assert_eq!(2, s.pos);
+    }
+
+    #[test]
+    fn delete_prev_word() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("a ß  c", 6);
+        let ok = s.delete_prev_word(Word::Big, 1, &mut cl);
+        assert_eq!("a c", s.buf);
+        assert_eq!(2, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("ß  ");
+    }
+
+    #[test]
+    fn move_to_next_word() {
+        let mut s = LineBuffer::init("a ß  c", 1); // after 'a'
+        let ok = s.move_to_next_word(At::AfterEnd, Word::Emacs, 1);
+        assert_eq!("a ß  c", s.buf);
+        assert!(ok);
+        assert_eq!(4, s.pos); // after 'ß'
+
+        let ok = s.move_to_next_word(At::AfterEnd, Word::Emacs, 1);
+        assert!(ok);
+        assert_eq!(7, s.pos); // after 'c'
+
+        s.move_home();
+        let ok = s.move_to_next_word(At::AfterEnd, Word::Emacs, 1);
+        assert!(ok);
+        assert_eq!(1, s.pos); // after 'a'
+
+        let ok = s.move_to_next_word(At::AfterEnd, Word::Emacs, 2);
+        assert!(ok);
+        assert_eq!(7, s.pos); // after 'c'
+    }
+
+    #[test]
+    fn move_to_end_of_word() {
+        let mut s = LineBuffer::init("a ßeta  c", 1);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert_eq!("a ßeta  c", s.buf);
+        assert_eq!(6, s.pos);
+        assert!(ok);
+    }
+
+    #[test]
+    fn move_to_end_of_vi_word() {
+        let mut s = LineBuffer::init("alpha ,beta/rho; mu", 0);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(4, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(6, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(10, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(11, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(14, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(15, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(18, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Vi, 1);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn move_to_end_of_big_word() {
+        let mut s = LineBuffer::init("alpha ,beta/rho; mu", 0);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Big, 1);
+        assert!(ok);
+        assert_eq!(4, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Big, 1);
+        assert!(ok);
+        assert_eq!(15, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Big, 1);
+        assert!(ok);
+        assert_eq!(18, s.pos);
+        let ok = s.move_to_next_word(At::BeforeEnd, Word::Big, 1);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn move_to_start_of_word() {
+        let mut s = LineBuffer::init("a ß  c", 2);
+        let ok = s.move_to_next_word(At::Start, Word::Emacs, 1);
+        assert_eq!("a ß  c", s.buf);
+        assert_eq!(6, s.pos);
+        assert!(ok);
+    }
+
+    #[test]
+    fn move_to_start_of_vi_word() {
+        let mut s = LineBuffer::init("alpha ,beta/rho; mu", 0);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(6, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(7, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(11, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(12, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(15, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(17, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(ok);
+        assert_eq!(18, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Vi, 1);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn move_to_start_of_big_word() {
+        let mut s = LineBuffer::init("alpha ,beta/rho; mu", 0);
+        let ok = s.move_to_next_word(At::Start, Word::Big, 1);
+        assert!(ok);
+        assert_eq!(6, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Big, 1);
+        assert!(ok);
+        assert_eq!(17, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Big, 1);
+        assert!(ok);
+        assert_eq!(18, s.pos);
+        let ok = s.move_to_next_word(At::Start, Word::Big, 1);
+        assert!(!ok);
+    }
+
+    #[test]
+    fn delete_word() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("a ß  c", 1);
+        let ok = s.delete_word(At::AfterEnd, Word::Emacs, 1, &mut cl);
+        assert_eq!("a  c", s.buf);
+        assert_eq!(1, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq(" ß");
+
+        let mut s = LineBuffer::init("test", 0);
+        let ok = s.delete_word(At::AfterEnd, Word::Vi, 1, &mut cl);
+        assert_eq!("", s.buf);
+        assert_eq!(0, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("test");
+    }
+
+    #[test]
+    fn delete_til_start_of_word() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("a ß  c", 2);
+        let ok = s.delete_word(At::Start, Word::Emacs, 1, &mut cl);
+        assert_eq!("a c", s.buf);
+        assert_eq!(2, s.pos);
+        assert!(ok);
+        cl.assert_deleted_str_eq("ß  ");
+    }
+
+    #[test]
+    fn delete_to_forward() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("αßγδε", 2);
+        let ok = s.delete_to(CharSearch::ForwardBefore('ε'), 1, &mut cl);
+        assert!(ok);
+        cl.assert_deleted_str_eq("ßγδ");
+        assert_eq!("αε", s.buf);
+        assert_eq!(2, s.pos);
+
+        let mut s = LineBuffer::init("αßγδε", 2);
+        let ok = s.delete_to(CharSearch::Forward('ε'), 1, &mut cl);
+        assert!(ok);
+        cl.assert_deleted_str_eq("ßγδε");
+        assert_eq!("α", s.buf);
+        assert_eq!(2, s.pos);
+    }
+
+    #[test]
+    fn delete_to_backward() {
+        let mut cl = Listener::new();
+        let mut s = LineBuffer::init("αßγδε", 8);
+        let ok = s.delete_to(CharSearch::BackwardAfter('α'), 1, &mut cl);
+        assert!(ok);
+        cl.assert_deleted_str_eq("ßγδ");
+        assert_eq!("αε", s.buf);
+        assert_eq!(2, s.pos);
+
+        let mut s = LineBuffer::init("αßγδε", 8);
+        let ok = s.delete_to(CharSearch::Backward('ß'), 1, &mut cl);
+        assert!(ok);
+        cl.assert_deleted_str_eq("ßγδ");
+        assert_eq!("αε", s.buf);
+        assert_eq!(2, s.pos);
+    }
+
+    #[test]
+    fn edit_word() {
+        let mut s = LineBuffer::init("a ßeta  c", 1);
+        assert!(s.edit_word(WordAction::Uppercase, &mut NoListener));
+        assert_eq!("a SSETA  c", s.buf);
+        assert_eq!(7, s.pos);
+
+        let mut s = LineBuffer::init("a ßetA  c", 1);
+        assert!(s.edit_word(WordAction::Lowercase, &mut NoListener));
+        assert_eq!("a ßeta  c", s.buf);
+        assert_eq!(7, s.pos);
+
+        let mut s = LineBuffer::init("a ßETA  c", 1);
+        assert!(s.edit_word(WordAction::Capitalize, &mut NoListener));
+        assert_eq!("a SSeta  c", s.buf);
+        assert_eq!(7, s.pos);
+
+        let mut s = LineBuffer::init("test", 1);
+        assert!(s.edit_word(WordAction::Capitalize, &mut NoListener));
+        assert_eq!("tEst", s.buf);
+        assert_eq!(4, s.pos);
+    }
+
+    #[test]
+    fn transpose_words() {
+        let mut s = LineBuffer::init("ßeta / δelta__", 15);
+        assert!(s.transpose_words(1, &mut NoListener));
+        assert_eq!("δelta__ / ßeta", s.buf);
+        assert_eq!(16, s.pos);
+
+        let mut s = LineBuffer::init("ßeta / δelta", 14);
+        assert!(s.transpose_words(1, &mut NoListener));
+        assert_eq!("δelta / ßeta", s.buf);
+        assert_eq!(14, s.pos);
+
+        let mut s = LineBuffer::init(" / δelta", 8);
+        assert!(!s.transpose_words(1, &mut NoListener));
+
+        let mut s = LineBuffer::init("ßeta / __", 9);
+        assert!(!s.transpose_words(1, &mut NoListener));
+    }
+
+    #[test]
+    fn marks() {
+        let mut s = LineBuffer::init("Hello, world!", 7);
+        assert!(s.set_mark('a'));
+        assert!(!s.set_mark('A'));
+        s.set_pos(0);
+        assert!(!s.insert_str(0, ">> ", &mut NoListener));
+        assert!(s.move_to_mark('a', false));
+        assert_eq!(10, s.pos());
+        s.delete_range(3..12, &mut NoListener);
+        s.set_pos(0);
+        assert!(s.move_to_mark('a', false));
+        assert_eq!(3, s.pos());
+        assert!(!s.move_to_mark('b', false));
+
+        let mut s = LineBuffer::init("line1\n  line2", 10);
+        assert!(s.set_mark('a'));
+        s.set_pos(0);
+        assert!(s.move_to_mark('a', true));
+        assert_eq!(8, s.pos());
+        s.set_pos(0);
+        assert_eq!(
+            Some("line1\n  line2".to_owned()),
+            s.copy(&Movement::ViMark('a', true))
+        );
+        assert_eq!(
+            Some("line1\n  li".to_owned()),
+            s.copy(&Movement::ViMark('a', false))
+        );
+    }
+
+    #[test]
+    fn surround() {
+        let mut s = LineBuffer::init("a b c", 2);
+        assert!(s.surround(&Movement::ForwardChar(1), '[', &mut NoListener));
+        assert_eq!("a [b] c", s.as_str());
+        assert_eq!(2, s.pos());
+        s.set_pos(0);
+        assert!(!s.surround(&Movement::BackwardChar(1), '"', &mut NoListener));
+
+        let mut s = LineBuffer::init("'a' 'b'", 1);
+        assert!(s.delete_surround('\'', &mut NoListener));
+        assert_eq!("a 'b'", s.as_str());
+        let mut s = LineBuffer::init("'a' 'b'", 4);
+        assert!(s.change_surround('\'', '"', &mut NoListener));
+        assert_eq!("'a' \"b\"", s.as_str());
+        assert_eq!(4, s.pos());
+
+        let mut s = LineBuffer::init("f(x, g(y))", 2);
+        assert!(s.delete_surround(')', &mut NoListener));
+        assert_eq!("fx, g(y)", s.as_str());
+        let mut s = LineBuffer::init("f(x, g(y))", 9);
+        assert!(s.change_surround('(', '{', &mut NoListener));
+        assert_eq!("f{x, g(y)}", s.as_str());
+        assert_eq!(1, s.pos());
+        let mut s = LineBuffer::init("no brackets", 3);
+        assert!(!s.delete_surround('(', &mut NoListener));
+    }
+
+    #[test]
+    fn move_by_line() {
+        let text = "aa123\nsdf bc\nasdf";
+        let mut s = LineBuffer::init(text, 14);
+        let mut layout = Layout::default();
+        // move up
+        let ok = s.move_to_line_up(1, &layout);
+        assert_eq!(7, s.pos);
+        assert!(ok);
+
+        let ok = s.move_to_line_up(1, &layout);
+        assert_eq!(1, s.pos);
+        assert!(ok);
+
+        let ok = s.move_to_line_up(1, &layout);
+        assert_eq!(1, s.pos);
+        assert!(!ok);
+
+        // move down
+        let ok = s.move_to_line_down(1, &layout);
+        assert_eq!(7, s.pos);
+        assert!(ok);
+
+        let ok = s.move_to_line_down(1, &layout);
+        assert_eq!(14, s.pos);
+        assert!(ok);
+
+        let ok = s.move_to_line_down(1, &layout);
+        assert_eq!(14, s.pos);
+        assert!(!ok);
+
+        // move by multiple steps
+        let ok = s.move_to_line_up(2, &layout);
+        assert_eq!(1, s.pos);
+        assert!(ok);
+
+        let ok = s.move_to_line_down(2, &layout);
+        assert_eq!(14, s.pos);
+        assert!(ok);
+
+        // non-empty prompt
+        layout.prompt_size.col = 2;
+        s.move_to_line_up(1, &layout);
+        assert_eq!(7, s.pos);
+        s.move_to_line_up(1, &layout);
+        assert_eq!(0, s.pos);
+        s.move_to_line_down(1, &layout);
+        assert_eq!(8, s.pos);
+
+        // continuation prompts
+        layout.continuation_prompt_cols = vec![2, 2];
+        s.move_to_line_up(1, &layout);
+        assert_eq!(2, s.pos);
+        s.move_to_line_down(2, &layout);
+        assert_eq!(15, s.pos);
+    }
+
+    #[test]
+    fn block() {
+        let layout = Layout::default();
+        let s = LineBuffer::init("abcd\nx\nefgh", 9);
+        let (columns, ranges) = s.block(1, &layout);
+        assert_eq!(1..3, columns);
+        assert_eq!(vec![1..3, 6..6, 8..10], ranges);
+        assert_eq!(Some(8), s.column_to_pos(7, 1, &layout));
+        assert_eq!(None, s.column_to_pos(5, 2, &layout));
+        assert_eq!(1, s.column(8, &layout));
+    }
+
+    #[test]
+    fn multi_cursors() {
+        let mut s = LineBuffer::init("a1\nb2\nc3", 2);
+        let mut cursors = vec![5, 8];
+        let mut cl = Listener::new();
+        assert!(s.insert_at_cursors(&mut cursors, "x", &mut cl));
+        assert_eq!("a1x\nb2x\nc3x", s.buf);
+        assert_eq!(3, s.pos);
+        assert_eq!(vec![7, 11], cursors);
+
+        assert!(s.delete_at_cursors(&mut cursors, 2, Direction::Backward, &mut cl));
+        assert_eq!("a\nb\nc", s.buf);
+        assert_eq!(1, s.pos);
+        assert_eq!(vec![3, 5], cursors);
+
+        assert!(s.move_cursors(&mut cursors, 1, Direction::Backward));
+        assert_eq!(0, s.pos);
+        assert_eq!(vec![2, 4], cursors);
+        assert!(s.delete_at_cursors(&mut cursors, 5, Direction::Forward, &mut cl));
+        assert_eq!("", s.buf);
+        assert_eq!(0, s.pos);
+        assert!(cursors.is_empty());
+        assert!(!s.move_cursors(&mut cursors, 1, Direction::Forward));
+    }
+
+    #[test]
+    fn move_to_paragraph() {
+        let text = "a\nb\n\n \nc\n\nd";
+        let mut s = LineBuffer::init(text, 0);
+        assert!(s.move_to_paragraph(1, Direction::Forward));
+        assert_eq!(4, s.pos);
+        assert!(s.move_to_paragraph(1, Direction::Forward));
+        assert_eq!(9, s.pos);
+        assert!(s.move_to_paragraph(1, Direction::Forward));
+        assert_eq!(text.len(), s.pos);
+        assert!(!s.move_to_paragraph(1, Direction::Forward));
+        assert!(s.move_to_paragraph(2, Direction::Backward));
+        assert_eq!(5, s.pos);
+        assert!(s.move_to_paragraph(1, Direction::Backward));
+        assert_eq!(0, s.pos);
+
+        s.set_pos(0);
+        let mut cl = Listener::new();
+        assert!(s.kill(&Movement::ForwardParagraph(1), &mut cl));
+        cl.assert_deleted_str_eq("a\nb\n");
+    }
+
+    #[test]
+    fn move_to_line() {
+        let mut s = LineBuffer::init("a\n  b\nc", 0);
+        assert!(s.move_to_line(2));
+        assert_eq!(4, s.pos);
+        assert!(s.move_to_line(RepeatCount::MAX));
+        assert_eq!(6, s.pos);
+        assert!(s.move_to_line(1));
+        assert_eq!(0, s.pos);
+        assert!(!s.move_to_line(0));
+        assert_eq!(Some("a\n  b".to_owned()), s.copy(&Movement::GotoLine(2)));
+    }
+
+    #[test]
+    fn move_by_visual_line() {
+        let layout = Layout::default();
+        let mut s = LineBuffer::init("abcdefgh\nij", 1);
+        assert!(s.move_to_visual_line(1, Direction::Forward, 3, &layout));
+        assert_eq!(4, s.pos);
+        assert!(s.move_to_visual_line(1, Direction::Forward, 3, &layout));
+        assert_eq!(7, s.pos);
+        assert!(s.move_to_visual_line(1, Direction::Forward, 3, &layout));
+        assert_eq!(10, s.pos);
+        assert!(!s.move_to_visual_line(1, Direction::Forward, 3, &layout));
+        assert!(s.move_to_visual_line(3, Direction::Backward, 3, &layout));
+        assert_eq!(1, s.pos);
+        assert!(!s.move_to_visual_line(1, Direction::Backward, 3, &layout));
+
+        s.set_pos(4);
+        assert!(s.move_to_visual_line_edge(Direction::Backward, 3, &layout));
+        assert_eq!(3, s.pos);
+        assert!(s.move_to_visual_line_edge(Direction::Forward, 3, &layout));
+        assert_eq!(5, s.pos);
+        assert!(!s.move_to_visual_line_edge(Direction::Forward, 3, &layout));
+    }
+
+    #[test]
+    fn invariants() {
+        assert!(LineBuffer::try_init("ab", 2).is_ok());
+        assert_eq!(
+            Err(InvariantViolation::CursorOutOfBounds { pos: 3, len: 2 }),
+            LineBuffer::try_init("ab", 3).map(|_| ())
+        );
+        assert_eq!(
+            Err(InvariantViolation::CursorNotOnCharBoundary(1)),
+            LineBuffer::try_init("ß", 1).map(|_| ())
+        );
+        assert_eq!(
+            Err(InvariantViolation::CursorNotOnGraphemeBoundary(1)),
+            LineBuffer::try_init("e\u{301}", 1).map(|_| ())
+        );
+        let mut s = LineBuffer::try_init("αß", 2).unwrap();
+        assert_eq!(vec![0, 2, 4], s.grapheme_boundaries());
+        assert!(s.checked(|s| s.set_mark('a')).unwrap());
+        s.marks[0] = Some(3);
+        assert_eq!(
+            Err(InvariantViolation::InvalidMark { mark: 'a', pos: 3 }),
+            s.check_invariants()
+        );
+    }
+
+    #[test]
+    fn random_edits() {
+        use rand::{Rng, SeedableRng};
+
+        const TEXTS: [&str; 8] = ["a", "é", "e\u{301}", "ß", "中", "😀", " ", "\n"];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(419);
+        let mut s = LineBuffer::try_init("", 0).unwrap();
+        for _ in 0..5000 {
+            let text = TEXTS[rng.random_range(0..TEXTS.len())];
+            let n = rng.random_range(1..3);
+            let boundaries = s.grapheme_boundaries();
+            let at = boundaries[rng.random_range(0..boundaries.len())];
+            let to = boundaries[rng.random_range(0..boundaries.len())];
+            let op = rng.random_range(0..16);
+            let r = s.checked(|s| match op {
+                0 | 1 => {
+                    s.yank(text, n, &mut NoListener);
+                }
+                2 => {
+                    s.insert('x', n, &mut NoListener);
+                }
+                3 => {
+                    s.delete(n, &mut NoListener);
+                }
+                4 => {
+                    s.backspace(n, &mut NoListener);
+                }
+                5 => {
+                    s.move_backward(n);
+                }
+                6 => {
+                    s.move_forward(n);
+                }
+                7 => {
+                    s.move_to_prev_word(Word::Vi, n);
+                }
+                8 => {
+                    s.move_to_next_word(At::AfterEnd, Word::Emacs, n);
+                }
+                9 => {
+                    s.transpose_chars(&mut NoListener);
+                }
+                10 => {
+                    s.edit_word(WordAction::Uppercase, &mut NoListener);
+                }
+                11 => {
+                    s.delete_prev_word(Word::Big, n, &mut NoListener);
+                }
+                12 => {
+                    s.kill_line(&mut NoListener);
+                }
+                13 => {
+                    s.move_to_position(at);
+                    s.set_mark('m');
+                }
+                14 => s.delete_range(at.min(to)..at.max(to), &mut NoListener),
+                _ => s.replace(at.min(to)..at.max(to), text, &mut NoListener),
+            });
+            assert_eq!(Ok(()), r, "{op} on {s:?}");
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<LineBuffer>();
+    }
+
+    #[test]
+    fn test_sync() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<LineBuffer>();
+    }
+}
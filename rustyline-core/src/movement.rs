@@ -0,0 +1,140 @@
+//! Movements of the cursor (and ranges of text) in a line buffer
+
+/// The number of times one command should be repeated.
+pub type RepeatCount = u16;
+
+/// Different word definitions
+#[derive(Debug, Clone, Eq, PartialEq, Copy)]
+pub enum Word {
+    /// non-blanks characters
+    Big,
+    /// alphanumeric characters
+    Emacs,
+    /// alphanumeric (and '_') characters
+    Vi,
+}
+
+/// Where to move with respect to word boundary
+#[derive(Debug, Clone, Eq, PartialEq, Copy)]
+pub enum At {
+    /// Start of word.
+    Start,
+    /// Before end of word.
+    BeforeEnd,
+    /// After end of word.
+    AfterEnd,
+}
+
+/// character search
+#[derive(Debug, Clone, Eq, PartialEq, Copy)]
+pub enum CharSearch {
+    /// Forward search
+    Forward(char),
+    /// Forward search until
+    ForwardBefore(char),
+    /// Backward search
+    Backward(char),
+    /// Backward search until
+    BackwardAfter(char),
+}
+
+impl CharSearch {
+    /// Search in the opposite direction (vi `,`)
+    #[must_use]
+    pub const fn opposite(self) -> Self {
+        match self {
+            Self::Forward(c) => Self::Backward(c),
+            Self::ForwardBefore(c) => Self::BackwardAfter(c),
+            Self::Backward(c) => Self::Forward(c),
+            Self::BackwardAfter(c) => Self::ForwardBefore(c),
+        }
+    }
+}
+
+/// Where to move
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Movement {
+    /// Whole current line (not really a movement but a range)
+    WholeLine,
+    /// beginning-of-line
+    BeginningOfLine,
+    /// end-of-line
+    EndOfLine,
+    /// backward-word, vi-prev-word
+    BackwardWord(RepeatCount, Word), // Backward until start of word
+    /// forward-word, vi-end-word, vi-next-word
+    ForwardWord(RepeatCount, At, Word), // Forward until start/end of word
+    /// character-search, character-search-backward, vi-char-search
+    ViCharSearch(RepeatCount, CharSearch),
+    /// vi-first-print
+    ViFirstPrint,
+    /// backward-char
+    BackwardChar(RepeatCount),
+    /// forward-char
+    ForwardChar(RepeatCount),
+    /// move to the same column on the previous line
+    LineUp(RepeatCount),
+    /// move to the same column on the next line
+    LineDown(RepeatCount),
+    /// Whole user input (not really a movement but a range)
+    WholeBuffer,
+    /// beginning-of-buffer
+    BeginningOfBuffer,
+    /// end-of-buffer
+    EndOfBuffer,
+    /// vi-goto-mark: exact position (`` ` ``) or first non-blank character of
+    /// the mark's line (`'`, line-wise range)
+    ViMark(char, bool),
+    /// backward-paragraph: move to the previous blank line
+    BackwardParagraph(RepeatCount),
+    /// forward-paragraph: move to the next blank line
+    ForwardParagraph(RepeatCount),
+    /// goto-line: first non-blank character of the `n`th line (clamped to
+    /// the last line, line-wise range)
+    GotoLine(RepeatCount),
+}
+
+impl Movement {
+    /// Replay this movement with a possible different `RepeatCount`.
+    #[must_use]
+    pub const fn redo(&self, new: Option<RepeatCount>) -> Self {
+        match *self {
+            Self::WholeLine => Self::WholeLine,
+            Self::BeginningOfLine => Self::BeginningOfLine,
+            Self::ViFirstPrint => Self::ViFirstPrint,
+            Self::EndOfLine => Self::EndOfLine,
+            Self::BackwardWord(previous, word) => {
+                Self::BackwardWord(repeat_count(previous, new), word)
+            }
+            Self::ForwardWord(previous, at, word) => {
+                Self::ForwardWord(repeat_count(previous, new), at, word)
+            }
+            Self::ViCharSearch(previous, char_search) => {
+                Self::ViCharSearch(repeat_count(previous, new), char_search)
+            }
+            Self::BackwardChar(previous) => Self::BackwardChar(repeat_count(previous, new)),
+            Self::ForwardChar(previous) => Self::ForwardChar(repeat_count(previous, new)),
+            Self::LineUp(previous) => Self::LineUp(repeat_count(previous, new)),
+            Self::LineDown(previous) => Self::LineDown(repeat_count(previous, new)),
+            Self::WholeBuffer => Self::WholeBuffer,
+            Self::BeginningOfBuffer => Self::BeginningOfBuffer,
+            Self::EndOfBuffer => Self::EndOfBuffer,
+            Self::ViMark(mark, line_wise) => Self::ViMark(mark, line_wise),
+            Self::BackwardParagraph(previous) => {
+                Self::BackwardParagraph(repeat_count(previous, new))
+            }
+            Self::ForwardParagraph(previous) => Self::ForwardParagraph(repeat_count(previous, new)),
+            Self::GotoLine(line) => Self::GotoLine(line),
+        }
+    }
+}
+
+/// `new` count if any, `previous` one otherwise
+#[must_use]
+pub const fn repeat_count(previous: RepeatCount, new: Option<RepeatCount>) -> RepeatCount {
+    match new {
+        Some(n) => n,
+        None => previous,
+    }
+}
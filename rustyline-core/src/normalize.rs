@@ -0,0 +1,233 @@
+//! Unicode normalization (Unicode Standard Annex #15) of the input, so that
+//! the same text typed with different keyboards or input methods (e.g. `é`
+//! as a single precomposed character or as `e` followed by a combining
+//! acute accent) is made of the same characters.
+//!
+//! ```
+//! use rustyline_core::normalize::NormalizationForm;
+//!
+//! let decomposed = "e\u{301}t\u{e9}";
+//! assert_eq!("\u{e9}t\u{e9}", NormalizationForm::Nfc.normalize(decomposed));
+//! assert_eq!("e\u{301}te\u{301}", NormalizationForm::Nfd.normalize(decomposed));
+//! ```
+//!
+//! Only the canonical forms are supported: compatibility forms (NFKC, NFKD)
+//! would change what the user typed (`ﬁ` into `fi`, `²` into `2`, ...).
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+#[rustfmt::skip]
+mod tables;
+
+/// Canonical normalization forms
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical decomposition followed by canonical composition: accented
+    /// letters are precomposed (the form produced by most keyboards)
+    #[default]
+    Nfc,
+    /// Canonical decomposition: accented letters are a base letter followed
+    /// by combining marks (the form of macOS file names)
+    Nfd,
+}
+
+impl NormalizationForm {
+    /// `s` in this normalization form, borrowed when already normalized
+    #[must_use]
+    pub fn normalize(self, s: &str) -> Cow<'_, str> {
+        if s.is_ascii() {
+            return Cow::Borrowed(s);
+        }
+        let mut chars = decompose(s);
+        if self == Self::Nfc {
+            compose(&mut chars);
+        }
+        if s.chars().eq(chars.iter().copied()) {
+            Cow::Borrowed(s)
+        } else {
+            Cow::Owned(chars.into_iter().collect())
+        }
+    }
+}
+
+/// Start of the characters of `s` which may change once some text is
+/// appended (the last starter and the combining marks after it): what
+/// precedes is left unchanged by the normalization of `s` followed by any
+/// text.
+#[must_use]
+pub fn last_starter(s: &str) -> usize {
+    s.char_indices()
+        .rev()
+        .find(|&(_, c)| combining_class(c) == 0)
+        .map_or(0, |(i, _)| i)
+}
+
+// Hangul syllables are (de)composed algorithmically
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// Canonical combining class (0 for starters)
+fn combining_class(c: char) -> u8 {
+    tables::COMBINING_CLASSES
+        .binary_search_by(|&(first, last, _)| {
+            if last < c {
+                Ordering::Less
+            } else if first > c {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+        .map_or(0, |i| tables::COMBINING_CLASSES[i].2)
+}
+
+/// Full canonical decomposition, in canonical order
+fn decompose(s: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        decompose_char(c, &mut chars);
+    }
+    // stable sort of each run of combining marks by class
+    let mut start = 0;
+    while start < chars.len() {
+        let len = chars[start..]
+            .iter()
+            .take_while(|&&c| combining_class(c) != 0)
+            .count();
+        if len > 1 {
+            chars[start..start + len].sort_by_key(|&c| combining_class(c));
+        }
+        start += len.max(1);
+    }
+    chars
+}
+
+fn decompose_char(c: char, chars: &mut Vec<char>) {
+    let s_index = u32::from(c).wrapping_sub(S_BASE);
+    if s_index < S_COUNT {
+        let l = L_BASE + s_index / N_COUNT;
+        let v = V_BASE + (s_index % N_COUNT) / T_COUNT;
+        let t = T_BASE + s_index % T_COUNT;
+        chars.extend(char::from_u32(l));
+        chars.extend(char::from_u32(v));
+        if t != T_BASE {
+            chars.extend(char::from_u32(t));
+        }
+        return;
+    }
+    match tables::DECOMPOSITIONS.binary_search_by_key(&c, |&(c, _, _)| c) {
+        Ok(i) => {
+            let (_, first, second) = tables::DECOMPOSITIONS[i];
+            decompose_char(first, chars);
+            if second != '\0' {
+                decompose_char(second, chars);
+            }
+        }
+        Err(_) => chars.push(c),
+    }
+}
+
+/// Canonical composition of decomposed `chars`
+fn compose(chars: &mut Vec<char>) {
+    let mut composed: Vec<char> = Vec::with_capacity(chars.len());
+    let mut starter = None;
+    // class of the last character not composed with the starter
+    let mut last_class = 0;
+    for &c in chars.iter() {
+        let class = combining_class(c);
+        if let Some(i) = starter {
+            // not blocked from the starter
+            if composed.len() == i + 1 || (last_class != 0 && last_class < class) {
+                if let Some(primary) = compose_pair(composed[i], c) {
+                    composed[i] = primary;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(composed.len());
+        }
+        last_class = class;
+        composed.push(c);
+    }
+    *chars = composed;
+}
+
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (u32::from(first), u32::from(second));
+    let l_index = a.wrapping_sub(L_BASE);
+    let v_index = b.wrapping_sub(V_BASE);
+    if l_index < L_COUNT && v_index < V_COUNT {
+        return char::from_u32(S_BASE + (l_index * V_COUNT + v_index) * T_COUNT);
+    }
+    let s_index = a.wrapping_sub(S_BASE);
+    let t_index = b.wrapping_sub(T_BASE);
+    if s_index < S_COUNT && s_index % T_COUNT == 0 && t_index > 0 && t_index < T_COUNT {
+        return char::from_u32(a + t_index);
+    }
+    tables::COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(a, b, _)| (a, b))
+        .ok()
+        .map(|i| tables::COMPOSITIONS[i].2)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{last_starter, NormalizationForm};
+    use alloc::string::String;
+
+    /// `s` made of the characters given as code points
+    fn string(cps: &[u32]) -> String {
+        cps.iter().copied().filter_map(char::from_u32).collect()
+    }
+
+    #[test]
+    fn normalize() {
+        use NormalizationForm::{Nfc, Nfd};
+        for (source, nfc, nfd) in [
+            // precomposed / decomposed
+            (&[0xe9][..], &[0xe9][..], &[0x65, 0x301][..]),
+            (&[0x65, 0x301], &[0xe9], &[0x65, 0x301]),
+            // canonical order of the marks (dot below before circumflex)
+            (&[0x61, 0x302, 0x323], &[0x1ead], &[0x61, 0x323, 0x302]),
+            (&[0xe2, 0x323], &[0x1ead], &[0x61, 0x323, 0x302]),
+            // blocked mark: same class as the one before it
+            (&[0x61, 0x301, 0x301], &[0xe1, 0x301], &[0x61, 0x301, 0x301]),
+            // singleton (Angstrom sign)
+            (&[0x212b], &[0xc5], &[0x41, 0x30a]),
+            // excluded composition (Devanagari qa)
+            (&[0x958], &[0x915, 0x93c], &[0x915, 0x93c]),
+            // Hangul syllables
+            (
+                &[0x1100, 0x1161, 0x11a8],
+                &[0xac01],
+                &[0x1100, 0x1161, 0x11a8],
+            ),
+            (&[0xac00, 0x11a8], &[0xac01], &[0x1100, 0x1161, 0x11a8]),
+        ] {
+            let source = string(source);
+            assert_eq!(string(nfc), Nfc.normalize(&source), "{source:?}");
+            assert_eq!(string(nfd), Nfd.normalize(&source), "{source:?}");
+        }
+        assert!(matches!(
+            Nfc.normalize("\u{e9}t\u{e9}"),
+            super::Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn starter() {
+        assert_eq!(0, last_starter(""));
+        assert_eq!(0, last_starter("\u{301}"));
+        assert_eq!(2, last_starter("abe\u{301}\u{323}"));
+        assert_eq!(2, last_starter("ab\u{e9}"));
+    }
+}
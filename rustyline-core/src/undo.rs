@@ -0,0 +1,772 @@
+//! Undo API
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::line_buffer::{ChangeListener, DeleteListener, Direction, LineBuffer, NoListener};
+use crate::movement::RepeatCount;
+use log::debug;
+use unicode_segmentation::UnicodeSegmentation;
+
+enum Change {
+    Begin,
+    End,
+    Insert {
+        idx: usize,
+        text: String,
+    }, // QuotedInsert, SelfInsert, Yank
+    Delete {
+        idx: usize,
+        text: String,
+    }, /* BackwardDeleteChar, BackwardKillWord, DeleteChar,
+        * KillLine, KillWholeLine, KillWord,
+        * UnixLikeDiscard, ViDeleteTo */
+    Replace {
+        idx: usize,
+        old: String,
+        new: String,
+    }, /* CapitalizeWord, Complete, DowncaseWord, Replace, TransposeChars, TransposeWords,
+        * UpcaseWord, YankPop */
+}
+
+impl Change {
+    fn undo(&self, line: &mut LineBuffer) {
+        match *self {
+            Self::Begin | Self::End => unreachable!(),
+            Self::Insert { idx, ref text } => {
+                line.delete_range(idx..idx + text.len(), &mut NoListener);
+            }
+            Self::Delete { idx, ref text } => {
+                line.insert_str(idx, text, &mut NoListener);
+                line.set_pos(idx + text.len());
+            }
+            Self::Replace {
+                idx,
+                ref old,
+                ref new,
+            } => {
+                line.replace(idx..idx + new.len(), old, &mut NoListener);
+            }
+        }
+    }
+
+    fn redo(&self, line: &mut LineBuffer) {
+        match *self {
+            Self::Begin | Self::End => unreachable!(),
+            Self::Insert { idx, ref text } => {
+                line.insert_str(idx, text, &mut NoListener);
+                line.set_pos(idx + text.len());
+            }
+            Self::Delete { idx, ref text } => {
+                line.delete_range(idx..idx + text.len(), &mut NoListener);
+            }
+            Self::Replace {
+                idx,
+                ref old,
+                ref new,
+            } => {
+                line.replace(idx..idx + old.len(), new, &mut NoListener);
+            }
+        }
+    }
+
+    fn insert_seq(&self, indx: usize) -> bool {
+        if let Self::Insert { idx, ref text } = *self {
+            idx + text.len() == indx
+        } else {
+            false
+        }
+    }
+
+    fn delete_seq(&self, indx: usize, len: usize) -> bool {
+        if let Self::Delete { idx, .. } = *self {
+            // delete or backspace
+            idx == indx || idx == indx + len
+        } else {
+            false
+        }
+    }
+
+    fn replace_seq(&self, indx: usize) -> bool {
+        if let Self::Replace { idx, ref new, .. } = *self {
+            idx + new.len() == indx
+        } else {
+            false
+        }
+    }
+}
+
+/// Granularity of undo steps
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UndoGrouping {
+    /// Each keystroke is undone separately
+    Keystroke,
+    /// Consecutive word characters are undone together
+    #[default]
+    Word,
+    /// Consecutive insertions (or deletions) are undone together, until
+    /// another command is executed
+    Command,
+    /// Consecutive insertions (or deletions) typed less than the specified
+    /// number of milliseconds apart are undone together
+    Time(u16),
+}
+
+/// Monotonic clock in milliseconds, see [`Changeset::set_clock`]
+pub type Clock = fn() -> u64;
+
+#[cfg(feature = "std")]
+fn std_clock() -> u64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static START: OnceLock<Instant> = OnceLock::new();
+    let start = START.get_or_init(Instant::now);
+    u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Alternative redo path of the undo tree
+struct Branch {
+    at: usize,             // length of `undos` where the branch starts
+    redos: Vec<Change>,    // like `Changeset::redos`
+    branches: Vec<Branch>, // branches forked from this one
+}
+
+/// Summary of the undo tree of the line being edited
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UndoTree {
+    /// Number of steps which can be undone
+    pub undos: usize,
+    /// Number of steps which can be redone on the current branch
+    pub redos: usize,
+    /// Number of alternative branches starting at the current position
+    pub branches: usize,
+    /// Number of alternative branches in the whole tree
+    pub total_branches: usize,
+}
+
+/// Undo manager
+///
+/// Changes undone then replaced by new changes are kept as alternative
+/// branches of an undo tree instead of being discarded.
+pub struct Changeset {
+    undo_group_level: u32,
+    undos: Vec<Change>,    // undoable changes
+    redos: Vec<Change>,    // undone changes, redoable
+    branches: Vec<Branch>, // alternative redo paths forked from the current path
+    grouping: UndoGrouping,
+    clock: Option<Clock>,
+    last_edit: Option<u64>, // for time-based grouping
+}
+
+impl Default for Changeset {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Changeset {
+    /// Empty changeset
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            undo_group_level: 0,
+            undos: vec![],
+            redos: vec![],
+            branches: vec![],
+            grouping: UndoGrouping::default(),
+            #[cfg(feature = "std")]
+            clock: Some(std_clock),
+            #[cfg(not(feature = "std"))]
+            clock: None,
+            last_edit: None,
+        }
+    }
+
+    /// Set the granularity of undo steps
+    pub fn set_grouping(&mut self, grouping: UndoGrouping) {
+        self.grouping = grouping;
+    }
+
+    /// Set the clock used by [`UndoGrouping::Time`] (by default, the
+    /// `Instant` based one with the `std` feature, none otherwise: each
+    /// keystroke is undone separately).
+    pub fn set_clock(&mut self, clock: Option<Clock>) {
+        self.clock = clock;
+    }
+
+    /// Take the recorded changes, leaving an empty changeset with the same
+    /// settings
+    #[must_use]
+    pub fn take(&mut self) -> Self {
+        let mut changes = Self::new();
+        changes.grouping = self.grouping;
+        changes.clock = self.clock;
+        core::mem::replace(self, changes)
+    }
+
+    /// Tell if no change has been recorded (or undone)
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.undos.is_empty() && self.redos.is_empty() && self.branches.is_empty()
+    }
+
+    /// Keep the undone changes as an alternative branch before a new change
+    /// is recorded
+    fn fork(&mut self) {
+        if self.redos.is_empty() {
+            return;
+        }
+        let at = self.undos.len();
+        // branches forked from the redo path go with it
+        let (nested, branches) = core::mem::take(&mut self.branches)
+            .into_iter()
+            .partition(|branch| branch.at > at);
+        self.branches = branches;
+        self.branches.push(Branch {
+            at,
+            redos: core::mem::take(&mut self.redos),
+            branches: nested,
+        });
+    }
+
+    /// Last change, unless a branch starts after it: a change shared by
+    /// several branches cannot be extended.
+    fn last_change(&self) -> Option<&Change> {
+        let at = self.undos.len();
+        if self.branches.iter().any(|branch| branch.at == at) {
+            return None;
+        }
+        self.undos.last()
+    }
+
+    /// Make the `n`th alternative branch starting at the current position
+    /// the redo path, the current redo path becoming an alternative.
+    /// Return `false` when there is no alternative.
+    pub fn switch_branch(&mut self, n: RepeatCount) -> bool {
+        debug!(target: "rustyline", "Changeset::switch_branch({})", n);
+        let at = self.undos.len();
+        let siblings: Vec<usize> = (0..self.branches.len())
+            .filter(|&i| self.branches[i].at == at)
+            .collect();
+        if siblings.is_empty() {
+            return false;
+        }
+        let n = usize::from(n.max(1));
+        // the current redo path is the last alternative
+        let cycle = siblings.len() + usize::from(!self.redos.is_empty());
+        let Some(&i) = siblings.get((n - 1) % cycle) else {
+            return true; // back to the current redo path
+        };
+        let branch = self.branches.remove(i);
+        self.fork();
+        self.redos = branch.redos;
+        self.branches.extend(branch.branches);
+        true
+    }
+
+    /// Summary of the undo tree
+    #[must_use]
+    pub fn tree(&self) -> UndoTree {
+        fn steps<'c>(changes: impl Iterator<Item = &'c Change>) -> usize {
+            let mut level = 0;
+            let mut count = 0;
+            for change in changes {
+                match change {
+                    Change::Begin => level += 1,
+                    Change::End => level -= 1,
+                    _ => {}
+                }
+                if level <= 0 {
+                    count += 1;
+                }
+            }
+            count
+        }
+        fn count(branches: &[Branch]) -> usize {
+            branches.len() + branches.iter().map(|b| count(&b.branches)).sum::<usize>()
+        }
+        let at = self.undos.len();
+        UndoTree {
+            undos: steps(self.undos.iter()),
+            // redos are stacked in reverse order
+            redos: steps(self.redos.iter().rev()),
+            branches: self.branches.iter().filter(|b| b.at == at).count(),
+            total_branches: count(&self.branches),
+        }
+    }
+
+    /// Tell if a single char edit may be merged with the previous change
+    fn coalesce(&mut self, word_char: bool) -> bool {
+        match self.grouping {
+            UndoGrouping::Keystroke => false,
+            UndoGrouping::Word => word_char,
+            UndoGrouping::Command => true,
+            UndoGrouping::Time(ms) => {
+                let Some(clock) = self.clock else {
+                    return false;
+                };
+                let now = clock();
+                let last = self.last_edit.replace(now);
+                last.is_some_and(|t| now.saturating_sub(t) < u64::from(ms))
+            }
+        }
+    }
+
+    /// Start a group of changes undone together, returning a mark for
+    /// [`Changeset::truncate`]
+    pub fn begin(&mut self) -> usize {
+        debug!(target: "rustyline", "Changeset::begin");
+        self.fork();
+        let mark = self.undos.len();
+        self.undos.push(Change::Begin);
+        self.undo_group_level += 1;
+        mark
+    }
+
+    /// Returns `true` when changes happen between the last call to `begin` and
+    /// this `end`.
+    pub fn end(&mut self) -> bool {
+        debug!(target: "rustyline", "Changeset::end");
+        self.fork();
+        let mut touched = false;
+        while self.undo_group_level > 0 {
+            self.undo_group_level -= 1;
+            if let Some(&Change::Begin) = self.undos.last() {
+                // empty Begin..End
+                self.undos.pop();
+            } else {
+                self.undos.push(Change::End);
+                touched = true;
+            }
+        }
+        touched
+    }
+
+    fn insert_char(idx: usize, c: char) -> Change {
+        let mut text = String::new();
+        text.push(c);
+        Change::Insert { idx, text }
+    }
+
+    /// Record the insertion of `c` at `idx`
+    pub fn insert(&mut self, idx: usize, c: char) {
+        debug!(target: "rustyline", "Changeset::insert({}, {:?})", idx, c);
+        self.fork();
+        if !self.coalesce(c.is_alphanumeric())
+            || !self.last_change().is_some_and(|lc| lc.insert_seq(idx))
+        {
+            self.undos.push(Self::insert_char(idx, c));
+            return;
+        }
+        // merge consecutive char insertions (alphanumeric only by default)
+        let mut last_change = self.undos.pop().unwrap();
+        if let Change::Insert { ref mut text, .. } = last_change {
+            text.push(c);
+        } else {
+            unreachable!();
+        }
+        self.undos.push(last_change);
+    }
+
+    /// Record the insertion of `string` at `idx`
+    pub fn insert_str<S: AsRef<str> + Into<String> + Debug>(&mut self, idx: usize, string: S) {
+        debug!(target: "rustyline", "Changeset::insert_str({}, {:?})", idx, string);
+        self.fork();
+        if string.as_ref().is_empty() {
+            return;
+        }
+        self.undos.push(Change::Insert {
+            idx,
+            text: string.into(),
+        });
+    }
+
+    /// Record the deletion of `string` at `indx`
+    pub fn delete<S: AsRef<str> + Into<String> + Debug>(&mut self, indx: usize, string: S) {
+        debug!(target: "rustyline", "Changeset::delete({}, {:?})", indx, string);
+        self.fork();
+        if string.as_ref().is_empty() {
+            return;
+        }
+
+        if !Self::single_char(string.as_ref()).is_some_and(|word_char| self.coalesce(word_char))
+            || !self
+                .last_change()
+                .is_some_and(|lc| lc.delete_seq(indx, string.as_ref().len()))
+        {
+            self.undos.push(Change::Delete {
+                idx: indx,
+                text: string.into(),
+            });
+            return;
+        }
+        // merge consecutive char deletions (alphanumeric only by default)
+        let mut last_change = self.undos.pop().unwrap();
+        if let Change::Delete {
+            ref mut idx,
+            ref mut text,
+        } = last_change
+        {
+            if *idx == indx {
+                text.push_str(string.as_ref());
+            } else {
+                text.insert_str(0, string.as_ref());
+                *idx = indx;
+            }
+        } else {
+            unreachable!();
+        }
+        self.undos.push(last_change);
+    }
+
+    /// Tell if `s` is a single grapheme and if so, whether it is alphanumeric
+    fn single_char(s: &str) -> Option<bool> {
+        let mut graphemes = s.graphemes(true);
+        let grapheme = graphemes.next()?;
+        if graphemes.next().is_some() {
+            return None;
+        }
+        Some(grapheme.chars().all(char::is_alphanumeric))
+    }
+
+    /// Record the replacement of `old_` by `new_` at `indx`
+    pub fn replace<S: AsRef<str> + Into<String> + Debug>(&mut self, indx: usize, old_: S, new_: S) {
+        debug!(target: "rustyline", "Changeset::replace({}, {:?}, {:?})", indx, old_, new_);
+        self.fork();
+
+        if !self.coalesce(true) || !self.last_change().is_some_and(|lc| lc.replace_seq(indx)) {
+            self.undos.push(Change::Replace {
+                idx: indx,
+                old: old_.into(),
+                new: new_.into(),
+            });
+            return;
+        }
+
+        // merge consecutive char replacements
+        let mut last_change = self.undos.pop().unwrap();
+        if let Change::Replace {
+            ref mut old,
+            ref mut new,
+            ..
+        } = last_change
+        {
+            old.push_str(old_.as_ref());
+            new.push_str(new_.as_ref());
+        } else {
+            unreachable!();
+        }
+        self.undos.push(last_change);
+    }
+
+    /// Undo the last `n` changes (or groups of changes)
+    pub fn undo(&mut self, line: &mut LineBuffer, n: RepeatCount) -> bool {
+        debug!(target: "rustyline", "Changeset::undo");
+        let mut count = 0;
+        let mut waiting_for_begin = 0;
+        let mut undone = false;
+        while let Some(change) = self.undos.pop() {
+            match change {
+                Change::Begin => {
+                    waiting_for_begin -= 1;
+                }
+                Change::End => {
+                    waiting_for_begin += 1;
+                }
+                _ => {
+                    change.undo(line);
+                    undone = true;
+                }
+            };
+            self.redos.push(change);
+            if waiting_for_begin <= 0 {
+                count += 1;
+                if count >= n {
+                    break;
+                }
+            }
+        }
+        undone
+    }
+
+    /// Forget the changes recorded after `len` (a mark returned by
+    /// [`Changeset::begin`])
+    pub fn truncate(&mut self, len: usize) {
+        debug!(target: "rustyline", "Changeset::truncate({})", len);
+        self.undos.truncate(len);
+        self.branches.retain(|branch| branch.at <= len);
+    }
+
+    /// Redo the last `n` undone changes (until a new change is recorded)
+    pub fn redo(&mut self, line: &mut LineBuffer, n: RepeatCount) -> bool {
+        debug!(target: "rustyline", "Changeset::redo");
+        let mut count = 0;
+        let mut waiting_for_end = 0;
+        let mut redone = false;
+        while let Some(change) = self.redos.pop() {
+            match change {
+                Change::Begin => {
+                    waiting_for_end += 1;
+                }
+                Change::End => {
+                    waiting_for_end -= 1;
+                }
+                _ => {
+                    change.redo(line);
+                    redone = true;
+                }
+            };
+            self.undos.push(change);
+            if waiting_for_end <= 0 {
+                count += 1;
+                if count >= n {
+                    break;
+                }
+            }
+        }
+        redone
+    }
+
+    /// Text of the last insertion (or replacement)
+    #[must_use]
+    pub fn last_insert(&self) -> Option<String> {
+        for change in self.undos.iter().rev() {
+            match change {
+                Change::Insert { ref text, .. } => return Some(text.clone()),
+                Change::Replace { ref new, .. } => return Some(new.clone()),
+                Change::End => {
+                    continue;
+                }
+                _ => {
+                    return None;
+                }
+            }
+        }
+        None
+    }
+}
+
+impl DeleteListener for Changeset {
+    fn delete(&mut self, idx: usize, string: &str, _: Direction) {
+        self.delete(idx, string);
+    }
+}
+impl ChangeListener for Changeset {
+    fn insert_char(&mut self, idx: usize, c: char) {
+        self.insert(idx, c);
+    }
+
+    fn insert_str(&mut self, idx: usize, string: &str) {
+        self.insert_str(idx, string);
+    }
+
+    fn replace(&mut self, idx: usize, old: &str, new: &str) {
+        self.replace(idx, old, new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Changeset, UndoGrouping, UndoTree};
+    use crate::line_buffer::{LineBuffer, NoListener};
+
+    #[test]
+    fn test_insert_chars() {
+        let mut cs = Changeset::new();
+        cs.insert(0, 'H');
+        cs.insert(1, 'i');
+        assert_eq!(1, cs.undos.len());
+        assert_eq!(0, cs.redos.len());
+        cs.insert(0, ' ');
+        assert_eq!(2, cs.undos.len());
+    }
+
+    #[test]
+    fn test_grouping() {
+        let mut cs = Changeset::new();
+        cs.set_grouping(UndoGrouping::Keystroke);
+        cs.insert(0, 'H');
+        cs.insert(1, 'i');
+        assert_eq!(2, cs.undos.len());
+
+        let mut cs = Changeset::new();
+        cs.set_grouping(UndoGrouping::Command);
+        cs.insert(0, 'H');
+        cs.insert(1, ' ');
+        cs.insert(2, '!');
+        assert_eq!(1, cs.undos.len());
+        cs.delete(2, "!");
+        cs.delete(1, " ");
+        assert_eq!(2, cs.undos.len());
+
+        let mut cs = Changeset::new();
+        cs.set_clock(Some(|| 0));
+        cs.set_grouping(UndoGrouping::Time(u16::MAX));
+        cs.insert(0, 'H');
+        cs.insert(1, ' ');
+        assert_eq!(1, cs.undos.len());
+        cs.set_grouping(UndoGrouping::Time(0));
+        cs.insert(2, 'i');
+        assert_eq!(2, cs.undos.len());
+        // no clock: each keystroke
+        cs.set_clock(None);
+        cs.set_grouping(UndoGrouping::Time(u16::MAX));
+        cs.insert(3, '!');
+        assert_eq!(3, cs.undos.len());
+    }
+
+    #[test]
+    fn test_undo_tree() {
+        let mut buf = LineBuffer::init("", 0);
+        let mut cs = Changeset::new();
+        buf.insert_str(0, "ab", &mut cs);
+        buf.insert_str(2, "c", &mut cs);
+        cs.undo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "ab");
+        // new edit: "c" is kept as an alternative branch
+        buf.insert_str(2, "d", &mut cs);
+        assert_eq!(
+            UndoTree {
+                undos: 2,
+                redos: 0,
+                branches: 0,
+                total_branches: 1
+            },
+            cs.tree()
+        );
+        assert!(!cs.switch_branch(1));
+        cs.undo(&mut buf, 1);
+        assert_eq!(
+            UndoTree {
+                undos: 1,
+                redos: 1,
+                branches: 1,
+                total_branches: 1
+            },
+            cs.tree()
+        );
+        assert!(cs.switch_branch(1));
+        cs.redo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "abc");
+        cs.undo(&mut buf, 1);
+        assert!(cs.switch_branch(1));
+        cs.redo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "abd");
+    }
+
+    #[test]
+    fn test_insert_strings() {
+        let mut cs = Changeset::new();
+        cs.insert_str(0, "Hello");
+        cs.insert_str(5, ", ");
+        assert_eq!(2, cs.undos.len());
+        assert_eq!(0, cs.redos.len());
+    }
+
+    #[test]
+    fn test_undo_insert() {
+        let mut buf = LineBuffer::init("", 0);
+        buf.insert_str(0, "Hello", &mut NoListener);
+        buf.insert_str(5, ", world!", &mut NoListener);
+        let mut cs = Changeset::new();
+        assert_eq!(buf.as_str(), "Hello, world!");
+
+        cs.insert_str(5, ", world!");
+
+        cs.undo(&mut buf, 1);
+        assert_eq!(0, cs.undos.len());
+        assert_eq!(1, cs.redos.len());
+        assert_eq!(buf.as_str(), "Hello");
+
+        cs.redo(&mut buf, 1);
+        assert_eq!(1, cs.undos.len());
+        assert_eq!(0, cs.redos.len());
+        assert_eq!(buf.as_str(), "Hello, world!");
+    }
+
+    #[test]
+    fn test_undo_delete() {
+        let mut buf = LineBuffer::init("", 0);
+        buf.insert_str(0, "Hello", &mut NoListener);
+        let mut cs = Changeset::new();
+        assert_eq!(buf.as_str(), "Hello");
+
+        cs.delete(5, ", world!");
+
+        cs.undo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "Hello, world!");
+
+        cs.redo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "Hello");
+    }
+
+    #[test]
+    fn test_delete_chars() {
+        let mut buf = LineBuffer::init("", 0);
+        buf.insert_str(0, "Hlo", &mut NoListener);
+
+        let mut cs = Changeset::new();
+        cs.delete(1, "e");
+        cs.delete(1, "l");
+        assert_eq!(1, cs.undos.len());
+
+        cs.undo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "Hello");
+    }
+
+    #[test]
+    fn test_backspace_chars() {
+        let mut buf = LineBuffer::init("", 0);
+        buf.insert_str(0, "Hlo", &mut NoListener);
+
+        let mut cs = Changeset::new();
+        cs.delete(2, "l");
+        cs.delete(1, "e");
+        assert_eq!(1, cs.undos.len());
+
+        cs.undo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "Hello");
+    }
+
+    #[test]
+    fn test_undo_replace() {
+        let mut buf = LineBuffer::init("", 0);
+        buf.insert_str(0, "Hello, world!", &mut NoListener);
+        let mut cs = Changeset::new();
+        assert_eq!(buf.as_str(), "Hello, world!");
+
+        buf.replace(1..5, "i", &mut NoListener);
+        assert_eq!(buf.as_str(), "Hi, world!");
+        cs.replace(1, "ello", "i");
+
+        cs.undo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "Hello, world!");
+
+        cs.redo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "Hi, world!");
+    }
+
+    #[test]
+    fn test_last_insert() {
+        let mut cs = Changeset::new();
+        cs.begin();
+        cs.delete(0, "Hello");
+        cs.insert_str(0, "Bye");
+        cs.end();
+        let insert = cs.last_insert();
+        assert_eq!(Some("Bye".to_owned()), insert);
+    }
+
+    #[test]
+    fn test_end() {
+        let mut cs = Changeset::new();
+        cs.begin();
+        assert!(!cs.end());
+        cs.begin();
+        cs.insert_str(0, "Hi");
+        assert!(cs.end());
+    }
+}
@@ -1,7 +1,10 @@
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
 use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput, Field, Index, Path};
+use rustyline_core::keys::KeyEvent;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Index, LitStr, Path, Token};
 
 fn get_field_by_attr<'a>(data: &'a Data, ident: &str) -> Option<(usize, &'a Field)> {
     if let Data::Struct(struct_data) = &data {
@@ -114,10 +117,58 @@ pub fn highlighter_macro_derive(input: TokenStream) -> TokenStream {
                     ::rustyline::highlight::Highlighter::highlight_prompt(&self.#field_name_or_index, prompt, default)
                 }
 
+                fn prompt_with_mode<'p>(
+                    &self,
+                    prompt: &'p str,
+                    mode: ::rustyline::highlight::PromptMode,
+                ) -> ::std::borrow::Cow<'p, str> {
+                    ::rustyline::highlight::Highlighter::prompt_with_mode(&self.#field_name_or_index, prompt, mode)
+                }
+
+                fn continuation_prompt(&self, line_number: usize) -> ::std::borrow::Cow<'_, str> {
+                    ::rustyline::highlight::Highlighter::continuation_prompt(&self.#field_name_or_index, line_number)
+                }
+
+                fn shrink_prompt<'p>(&self, prompt: &'p str, width: u16) -> ::std::borrow::Cow<'p, str> {
+                    ::rustyline::highlight::Highlighter::shrink_prompt(&self.#field_name_or_index, prompt, width)
+                }
+
+                fn highlight_search_match<'l>(
+                    &self,
+                    line: &'l str,
+                    range: ::std::ops::Range<usize>,
+                ) -> ::std::borrow::Cow<'l, str> {
+                    ::rustyline::highlight::Highlighter::highlight_search_match(&self.#field_name_or_index, line, range)
+                }
+
+                fn highlight_preedit<'l>(
+                    &self,
+                    line: &'l str,
+                    range: ::std::ops::Range<usize>,
+                ) -> ::std::borrow::Cow<'l, str> {
+                    ::rustyline::highlight::Highlighter::highlight_preedit(&self.#field_name_or_index, line, range)
+                }
+
+                fn highlight_selection<'l>(
+                    &self,
+                    line: &'l str,
+                    ranges: &[::std::ops::Range<usize>],
+                ) -> ::std::borrow::Cow<'l, str> {
+                    ::rustyline::highlight::Highlighter::highlight_selection(&self.#field_name_or_index, line, ranges)
+                }
+
                 fn highlight_hint<'h>(&self, hint: &'h str) -> ::std::borrow::Cow<'h, str> {
                     ::rustyline::highlight::Highlighter::highlight_hint(&self.#field_name_or_index, hint)
                 }
 
+                fn highlight_validation_message<'m>(
+                    &self,
+                    msg: &'m str,
+                    invalid: bool,
+                ) -> ::std::borrow::Cow<'m, str> {
+                    ::rustyline::highlight::Highlighter::highlight_validation_message(&self.#field_name_or_index, msg, invalid)
+                }
+
                 fn highlight_candidate<'c>(
                     &self,
                     candidate: &'c str,
@@ -129,6 +180,10 @@ pub fn highlighter_macro_derive(input: TokenStream) -> TokenStream {
                 fn highlight_char(&self, line: &str, pos: usize, kind: ::rustyline::highlight::CmdKind) -> bool {
                     ::rustyline::highlight::Highlighter::highlight_char(&self.#field_name_or_index, line, pos, kind)
                 }
+
+                fn set_background(&self, background: ::rustyline::highlight::Background) {
+                    ::rustyline::highlight::Highlighter::set_background(&self.#field_name_or_index, background)
+                }
             }
         }
     } else {
@@ -194,6 +249,21 @@ pub fn validator_macro_derive(input: TokenStream) -> TokenStream {
                 fn validate_while_typing(&self) -> bool {
                     ::rustyline::validate::Validator::validate_while_typing(&self.#field_name_or_index)
                 }
+
+                fn poll_validation(
+                    &self,
+                    ctx: &mut ::rustyline::validate::ValidationContext,
+                ) -> ::rustyline::Result<::rustyline::validate::ValidationResult> {
+                    ::rustyline::validate::Validator::poll_validation(&self.#field_name_or_index, ctx)
+                }
+
+                fn cancel_validation(&self) {
+                    ::rustyline::validate::Validator::cancel_validation(&self.#field_name_or_index)
+                }
+
+                fn indent(&self, line: &str, pos: usize) -> Option<String> {
+                    ::rustyline::validate::Validator::indent(&self.#field_name_or_index, line, pos)
+                }
             }
         }
     } else {
@@ -205,3 +275,68 @@ pub fn validator_macro_derive(input: TokenStream) -> TokenStream {
     };
     TokenStream::from(expanded)
 }
+
+/// Key sequence and command of a binding
+struct Binding {
+    keys: Vec<TokenStream2>,
+    cmd: TokenStream2,
+}
+
+impl Parse for Binding {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let spec: LitStr = input.parse()?;
+        let keys = spec
+            .value()
+            .split_whitespace()
+            .map(|key| {
+                key_event(key)
+                    .ok_or_else(|| syn::Error::new(spec.span(), format!("invalid key `{key}`")))
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        if keys.is_empty() {
+            return Err(syn::Error::new(spec.span(), "empty key sequence"));
+        }
+        input.parse::<Token![=>]>()?;
+        // `Cmd` variant, up to the next comma
+        let mut cmd = TokenStream2::new();
+        while !input.is_empty() && !input.peek(Token![,]) {
+            cmd.extend([input.parse::<TokenTree>()?]);
+        }
+        if cmd.is_empty() {
+            return Err(input.error("expected a command"));
+        }
+        Ok(Self { keys, cmd })
+    }
+}
+
+/// `KeyEvent` expression of a readline style key specification, parsed by
+/// `KeyEvent::parse`
+fn key_event(spec: &str) -> Option<TokenStream2> {
+    let KeyEvent(code, mods) = KeyEvent::parse(spec)?;
+    // `Char('x')`, `F(1)`, `Enter`, ...
+    let code: TokenStream2 = format!("{code:?}").parse().ok()?;
+    let mods = mods.bits();
+    Some(quote!(::rustyline::KeyEvent(
+        ::rustyline::KeyCode::#code,
+        ::rustyline::Modifiers::from_bits_retain(#mods)
+    )))
+}
+
+/// Binding table checked at compile time: readline style key sequences
+/// (like `"C-x C-e"`) bound to `Cmd` variants, as an array of
+/// `(Event, Cmd)` to be registered with `Editor::bind_sequence` (see the
+/// example of `rustyline::bindings`).
+#[proc_macro]
+pub fn bindings(input: TokenStream) -> TokenStream {
+    let bindings =
+        parse_macro_input!(input with Punctuated::<Binding, Token![,]>::parse_terminated);
+    let bindings = bindings.iter().map(|Binding { keys, cmd }| {
+        quote! {
+            (
+                ::rustyline::Event::KeySeq(::std::vec![#(#keys),*]),
+                ::rustyline::Cmd::#cmd,
+            )
+        }
+    });
+    TokenStream::from(quote!([#(#bindings),*]))
+}
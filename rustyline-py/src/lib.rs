@@ -0,0 +1,173 @@
+//! Python bindings: `rustyline.Editor`, built with `maturin build`.
+//!
+//! ```python
+//! import rustyline
+//!
+//! editor = rustyline.Editor(edit_mode="vi", auto_add_history=True)
+//! editor.set_completer(lambda line, pos: (0, ["help", "history"]))
+//! editor.set_hinter(lambda line, pos: "elp" if line == "h" else None)
+//! try:
+//!     while True:
+//!         print(editor.readline(">> "))
+//! except EOFError:
+//!     pass
+//! ```
+use std::io;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyEOFError, PyKeyboardInterrupt, PyOSError, PyValueError};
+use pyo3::prelude::*;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Config, Context, EditMode, Helper};
+
+/// Python callbacks
+#[derive(Default)]
+struct PyHelper {
+    /// `completer(line, pos) -> (start, candidates)`
+    completer: Option<PyObject>,
+    /// `hinter(line, pos) -> hint or None`
+    hinter: Option<PyObject>,
+}
+
+impl Completer for PyHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let Some(ref completer) = self.completer else {
+            return Ok((pos, vec![]));
+        };
+        Python::with_gil(|py| completer.call1(py, (line, pos))?.extract(py))
+            .map_err(|err| ReadlineError::Io(io::Error::other(err)))
+    }
+}
+
+impl Hinter for PyHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, _: &Context<'_>) -> Option<String> {
+        let hinter = self.hinter.as_ref()?;
+        Python::with_gil(|py| {
+            match hinter.call1(py, (line, pos)).and_then(|hint| hint.extract(py)) {
+                Ok(hint) => hint,
+                Err(err) => {
+                    // no error can be returned while the line is edited
+                    err.print(py);
+                    None
+                }
+            }
+        })
+    }
+}
+
+impl Highlighter for PyHelper {}
+impl Validator for PyHelper {}
+impl Helper for PyHelper {}
+
+/// Python exception matching `err` (the one raised by a callback
+/// included)
+fn to_py_err(err: ReadlineError) -> PyErr {
+    match err {
+        ReadlineError::Eof => PyEOFError::new_err(()),
+        ReadlineError::Interrupted => PyKeyboardInterrupt::new_err(()),
+        ReadlineError::Io(err) if err.get_ref().is_some_and(|e| e.is::<PyErr>()) => {
+            *err.into_inner().unwrap().downcast::<PyErr>().unwrap()
+        }
+        err => PyOSError::new_err(err.to_string()),
+    }
+}
+
+/// Line editor
+#[pyclass(module = "rustyline")]
+struct Editor {
+    inner: rustyline::Editor<PyHelper, DefaultHistory>,
+}
+
+#[pymethods]
+impl Editor {
+    #[new]
+    #[pyo3(signature = (*, edit_mode = "emacs", auto_add_history = false, max_history_size = 100))]
+    fn new(edit_mode: &str, auto_add_history: bool, max_history_size: usize) -> PyResult<Self> {
+        let edit_mode = match edit_mode {
+            "emacs" => EditMode::Emacs,
+            "vi" => EditMode::Vi,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "invalid edit mode: {edit_mode}"
+                )))
+            }
+        };
+        let config = Config::builder()
+            .edit_mode(edit_mode)
+            .auto_add_history(auto_add_history)
+            .max_history_size(max_history_size)
+            .map_err(to_py_err)?
+            .build();
+        let mut inner = rustyline::Editor::with_config(config).map_err(to_py_err)?;
+        inner.set_helper(Some(PyHelper::default()));
+        Ok(Self { inner })
+    }
+
+    /// Read a line (`EOFError` on end of file, `KeyboardInterrupt` on
+    /// Ctrl-C), other Python threads running meanwhile
+    #[pyo3(signature = (prompt = ""))]
+    fn readline(&mut self, py: Python<'_>, prompt: &str) -> PyResult<String> {
+        let inner = &mut self.inner;
+        py.allow_threads(|| inner.readline(prompt))
+            .map_err(to_py_err)
+    }
+
+    /// Set the `completer(line, pos) -> (start, candidates)` callback
+    fn set_completer(&mut self, completer: Option<PyObject>) {
+        self.helper().completer = completer;
+    }
+
+    /// Set the `hinter(line, pos) -> hint or None` callback
+    fn set_hinter(&mut self, hinter: Option<PyObject>) {
+        self.helper().hinter = hinter;
+    }
+
+    /// Add `line` to the history (`False` when ignored)
+    fn add_history(&mut self, line: &str) -> PyResult<bool> {
+        self.inner.add_history_entry(line).map_err(to_py_err)
+    }
+
+    /// History entries, the oldest first
+    fn history(&self) -> Vec<String> {
+        self.inner.history().iter().cloned().collect()
+    }
+
+    fn clear_history(&mut self) -> PyResult<()> {
+        self.inner.clear_history().map_err(to_py_err)
+    }
+
+    fn load_history(&mut self, path: PathBuf) -> PyResult<()> {
+        self.inner.load_history(&path).map_err(to_py_err)
+    }
+
+    fn save_history(&mut self, path: PathBuf) -> PyResult<()> {
+        self.inner.save_history(&path).map_err(to_py_err)
+    }
+}
+
+impl Editor {
+    fn helper(&mut self) -> &mut PyHelper {
+        self.inner.helper_mut().expect("helper set by the constructor")
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "rustyline")]
+fn module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Editor>()?;
+    Ok(())
+}
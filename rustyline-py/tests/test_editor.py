@@ -0,0 +1,136 @@
+"""Tests of the Python bindings, run with `pytest` once built by
+`maturin develop`."""
+import os
+import pty
+import select
+import subprocess
+import sys
+
+import pytest
+
+import rustyline
+
+
+def test_new():
+    rustyline.Editor()
+    rustyline.Editor(edit_mode="vi", auto_add_history=True, max_history_size=10)
+    with pytest.raises(ValueError):
+        rustyline.Editor(edit_mode="ed")
+
+
+def test_readline_not_a_tty():
+    # lines read from a pipe, without prompt nor history
+    script = """
+import rustyline
+
+editor = rustyline.Editor(auto_add_history=True)
+print(editor.readline(">> "))
+print(editor.readline(">> "))
+print(editor.history())
+try:
+    editor.readline(">> ")
+except EOFError:
+    print("EOF")
+"""
+    result = subprocess.run(
+        [sys.executable, "-c", script],
+        input="hello\nworld\n",
+        capture_output=True,
+        text=True,
+        check=True,
+    )
+    assert result.stdout.splitlines() == ["hello", "world", "[]", "EOF"]
+
+
+def test_history(tmp_path):
+    editor = rustyline.Editor(max_history_size=2)
+    assert editor.add_history("one")
+    assert not editor.add_history("one")  # duplicate ignored
+    assert editor.add_history("two")
+    assert editor.add_history("three")
+    assert editor.history() == ["two", "three"]
+
+    path = tmp_path / "history.txt"
+    editor.save_history(path)
+    editor.clear_history()
+    assert editor.history() == []
+    editor.load_history(path)
+    assert editor.history() == ["two", "three"]
+
+    with pytest.raises(OSError):
+        editor.load_history(tmp_path / "missing.txt")
+
+
+def run_in_pty(script, steps):
+    """Run `script` in a pseudo-terminal, typing the keys of each
+    `(expected, keys)` step once `expected` is displayed, and return its
+    stderr."""
+    master, slave = pty.openpty()
+    child = subprocess.Popen(
+        [sys.executable, "-c", script],
+        stdin=slave,
+        stdout=slave,
+        stderr=subprocess.PIPE,
+        # no controlling terminal: the editor uses stdin / stdout
+        start_new_session=True,
+        env=dict(os.environ, TERM="xterm"),
+        text=True,
+    )
+    os.close(slave)
+    output = b""
+    try:
+        for expected, keys in steps:
+            while expected not in output:
+                ready, _, _ = select.select([master], [], [], 10)
+                assert ready, f"{expected!r} not displayed: {output!r}"
+                output += os.read(master, 1024)
+            os.write(master, keys)
+        _, stderr = child.communicate(timeout=10)
+    finally:
+        child.kill()
+        os.close(master)
+    return stderr
+
+
+# callbacks called while `readline` runs without the GIL
+EDITOR = """
+import sys
+import rustyline
+
+editor = rustyline.Editor()
+editor.set_completer({completer})
+editor.set_hinter({hinter})
+try:
+    print(repr(editor.readline(">> ")), file=sys.stderr)
+except Exception as err:
+    print(type(err).__name__, err, file=sys.stderr)
+"""
+
+
+def test_completer_and_hinter():
+    script = EDITOR.format(
+        completer="lambda line, pos: (0, ['help'] if line[:pos] == 'h' else [])",
+        hinter="lambda line, pos: 'elp' if line == 'h' else None",
+    )
+    stderr = run_in_pty(script, [(b">> ", b"h"), (b"elp", b"\t\r")])
+    assert stderr.splitlines() == ["'help'"]
+
+
+def test_completer_raises():
+    script = EDITOR.format(
+        completer="lambda line, pos: 1 / 0",
+        hinter="None",
+    )
+    stderr = run_in_pty(script, [(b">> ", b"h\t")])
+    assert stderr.splitlines() == ["ZeroDivisionError division by zero"]
+
+
+def test_hinter_raises():
+    script = EDITOR.format(
+        completer="None",
+        hinter="lambda line, pos: 1 / 0",
+    )
+    stderr = run_in_pty(script, [(b">> ", b"h\r")])
+    # printed, the line being still edited
+    assert "ZeroDivisionError" in stderr
+    assert stderr.splitlines()[-1] == "'h'"
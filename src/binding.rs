@@ -1,6 +1,7 @@
 /// Custom event handlers
 use crate::{
     Cmd, EditMode, InputMode, InputState, KeyCode, KeyEvent, Modifiers, Refresher, RepeatCount,
+    UndoTree,
 };
 
 use radix_trie::TrieKey;
@@ -66,42 +67,46 @@ const MOUSE: u32 = /* F24 + 1 */ INSERT + 25;
 const PASTE_START: u32 = MOUSE + 1;
 const PASTE_FINISH: u32 = PASTE_START + 1;
 const ANY: u32 = PASTE_FINISH + 1;
+const FOCUS_IN: u32 = ANY + 1;
+const FOCUS_OUT: u32 = FOCUS_IN + 1;
 
-impl KeyEvent {
-    fn encode(&self) -> u32 {
-        let mut u = match self.0 {
-            KeyCode::UnknownEscSeq | KeyCode::Null => 0,
-            KeyCode::Backspace => u32::from('\x7f'),
-            KeyCode::BackTab => u32::from('\t') | BASE_SHIFT,
-            KeyCode::BracketedPasteStart => PASTE_START,
-            KeyCode::BracketedPasteEnd => PASTE_FINISH,
-            KeyCode::Char(c) => u32::from(c),
-            KeyCode::Delete => DELETE,
-            KeyCode::Down => DOWN,
-            KeyCode::End => END,
-            KeyCode::Enter => u32::from('\r'),
-            KeyCode::F(i) => INSERT + u32::from(i),
-            KeyCode::Esc => ESCAPE,
-            KeyCode::Home => HOME,
-            KeyCode::Insert => INSERT,
-            KeyCode::Left => LEFT,
-            KeyCode::PageDown => PAGE_DOWN,
-            KeyCode::PageUp => PAGE_UP,
-            KeyCode::Right => RIGHT,
-            KeyCode::Tab => u32::from('\t'),
-            KeyCode::Up => UP,
-        };
-        if self.1.contains(Modifiers::CTRL) {
-            u |= BASE_CONTROL;
-        }
-        if self.1.contains(Modifiers::ALT) {
-            u |= BASE_META;
-        }
-        if self.1.contains(Modifiers::SHIFT) {
-            u |= BASE_SHIFT;
-        }
-        u
+/// Trie key of `key`
+fn encode(key: &KeyEvent) -> u32 {
+    let mut u = match key.0 {
+        KeyCode::Backspace => u32::from('\x7f'),
+        KeyCode::BackTab => u32::from('\t') | BASE_SHIFT,
+        KeyCode::BracketedPasteStart => PASTE_START,
+        KeyCode::BracketedPasteEnd => PASTE_FINISH,
+        KeyCode::Char(c) => u32::from(c),
+        KeyCode::Delete => DELETE,
+        KeyCode::Down => DOWN,
+        KeyCode::End => END,
+        KeyCode::FocusIn => FOCUS_IN,
+        KeyCode::FocusOut => FOCUS_OUT,
+        KeyCode::Enter => u32::from('\r'),
+        KeyCode::F(i) => INSERT + u32::from(i),
+        KeyCode::Esc => ESCAPE,
+        KeyCode::Home => HOME,
+        KeyCode::Insert => INSERT,
+        KeyCode::Left => LEFT,
+        KeyCode::PageDown => PAGE_DOWN,
+        KeyCode::PageUp => PAGE_UP,
+        KeyCode::Right => RIGHT,
+        KeyCode::Tab => u32::from('\t'),
+        KeyCode::Up => UP,
+        // UnknownEscSeq, Null
+        _ => 0,
+    };
+    if key.1.contains(Modifiers::CTRL) {
+        u |= BASE_CONTROL;
+    }
+    if key.1.contains(Modifiers::ALT) {
+        u |= BASE_META;
     }
+    if key.1.contains(Modifiers::SHIFT) {
+        u |= BASE_SHIFT;
+    }
+    u
 }
 
 impl TrieKey for Event {
@@ -111,7 +116,7 @@ impl TrieKey for Event {
             Self::KeySeq(keys) => {
                 let mut dst = Vec::with_capacity(keys.len() * 4);
                 for key in keys {
-                    dst.extend_from_slice(&key.encode().to_be_bytes());
+                    dst.extend_from_slice(&encode(key).to_be_bytes());
                 }
                 dst
             }
@@ -120,6 +125,18 @@ impl TrieKey for Event {
     }
 }
 
+/// Input mode a key binding can be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
+pub enum BindingMode {
+    /// Emacs mode
+    Emacs,
+    /// Vi command (normal) mode
+    ViCommand,
+    /// Vi insert and replace modes
+    ViInsert,
+}
+
 /// Event handler
 #[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
 pub enum EventHandler {
@@ -189,6 +206,12 @@ impl<'r> EventContext<'r> {
     pub fn pos(&self) -> usize {
         self.wrt.pos()
     }
+
+    /// Summary of the undo tree of the currently edited line
+    #[must_use]
+    pub fn undo_tree(&self) -> UndoTree {
+        self.wrt.undo_tree()
+    }
 }
 
 /// May behave differently depending on:
@@ -248,6 +271,72 @@ mod test {
         trie.insert(E::from(K(C::Tab, M::CTRL)), H::from(Cmd::Noop));
     }
 
+    #[test]
+    #[cfg(feature = "derive")]
+    fn bindings_macro() {
+        // events of `bindings!` and of `KeyEvent::parse` for the same specs
+        macro_rules! check {
+            ($($spec:tt),*) => {
+                let bindings = crate::bindings! { $($spec => Noop),* };
+                for ((evt, cmd), spec) in bindings.into_iter().zip([$($spec),*]) {
+                    let keys = spec.split_whitespace().map(|key| KeyEvent::parse(key).unwrap());
+                    assert_eq!(Event::KeySeq(keys.collect()), evt, "{spec}");
+                    assert_eq!(Cmd::Noop, cmd);
+                }
+            };
+        }
+        check!(
+            "a",
+            "A",
+            "-",
+            "'",
+            "\\",
+            "\u{e9}",
+            "C-a",
+            "C-A",
+            "C--",
+            "M-x",
+            "S-a",
+            "Ctrl-Meta-Shift-x",
+            "Control-Alt-x",
+            "c-m-s-x",
+            "C-x C-e",
+            "Backspace",
+            "Rubout",
+            "BackTab",
+            "Del",
+            "Delete",
+            "Down",
+            "End",
+            "Enter",
+            "Return",
+            "Ret",
+            "Esc",
+            "Escape",
+            "Home",
+            "Ins",
+            "Insert",
+            "Left",
+            "PageDown",
+            "PgDn",
+            "PageUp",
+            "PgUp",
+            "Right",
+            "Space",
+            "Spc",
+            "Tab",
+            "S-Tab",
+            "Up",
+            "F1",
+            "F12",
+            "F24",
+            "C-Space",
+            "M-Enter",
+            "C-Left",
+            "M-Backspace"
+        );
+    }
+
     #[test]
     #[ignore]
     #[cfg(target_arch = "x86_64")]
@@ -0,0 +1,375 @@
+//! C ABI compatible with a subset of GNU Readline (`capi` feature).
+//!
+//! The `rustyline-capi` crate builds these entry points as a `cdylib` /
+//! `staticlib` (with the `readline/readline.h` and `readline/history.h`
+//! headers) which C programs can link instead of `libreadline`:
+//! - `readline`, `rl_initialize`,
+//! - `add_history`, `clear_history`, `stifle_history`, `read_history`,
+//!   `write_history`,
+//! - `rl_completion_entry_function` (generator called with the word being
+//!   completed, delimited by `rl_completer_word_break_characters`),
+//!   `rl_line_buffer` and `rl_point` (the line and cursor position while
+//!   completing), `rl_readline_name`.
+//!
+//! A Rust application can [`install`] its own editor (helper, history and
+//! configuration included) so that the C plugins it loads share it.
+//!
+//! While `readline` is reading a line, the history functions called (e.g. by
+//! the completion generator) are applied once the line is read, and a nested
+//! `readline` returns `NULL`.
+#![allow(unsafe_code, non_upper_case_globals)]
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::{io, mem, ptr};
+
+use crate::compat::{completion_word, DEFAULT_WORD_BREAK_CHARACTERS};
+use crate::completion::Completer;
+use crate::error::ReadlineError;
+use crate::highlight::Highlighter;
+use crate::hint::Hinter;
+use crate::history::DefaultHistory;
+use crate::validate::Validator;
+use crate::{Context, Editor, Helper, LineEditor, Result};
+
+/// Completion generator (`rl_compentry_func_t`): called with `state` 0 for
+/// the first match, then with increasing `state` until it returns `NULL`.
+/// The matches are allocated with `malloc` and freed by the caller.
+pub type CompEntryFunc = unsafe extern "C" fn(text: *const c_char, state: c_int) -> *mut c_char;
+
+/// Generator of the completions (`None`: no completion)
+#[no_mangle]
+pub static mut rl_completion_entry_function: Option<CompEntryFunc> = None;
+/// Characters delimiting the word to complete (`NULL`: the GNU Readline
+/// default)
+#[no_mangle]
+pub static mut rl_completer_word_break_characters: *const c_char = ptr::null();
+/// Line being edited, while the completion generator is called
+#[no_mangle]
+pub static mut rl_line_buffer: *mut c_char = ptr::null_mut();
+/// Cursor position (byte offset) in `rl_line_buffer`
+#[no_mangle]
+pub static mut rl_point: c_int = 0;
+/// Name of the application (unused, for compatibility)
+#[no_mangle]
+pub static mut rl_readline_name: *const c_char = ptr::null();
+
+/// Line editor behind the C entry points
+type Backend = Box<dyn LineEditor + Send>;
+/// Call made while a line is read
+type Deferred = Box<dyn FnOnce(&mut dyn LineEditor) -> Result<()> + Send>;
+
+/// State of the line editor behind the C entry points
+enum Slot {
+    /// Not created yet
+    Empty,
+    Ready(Backend),
+    /// Taken out by `readline` (not to be locked while the helper calls
+    /// back an entry point), with the calls to apply once the line is read
+    Reading(Vec<Deferred>),
+}
+
+static BACKEND: Mutex<Slot> = Mutex::new(Slot::Empty);
+
+/// Use `editor` (e.g. an [`Editor`] with its helper and history) for the
+/// next calls of the C entry points.
+pub fn install<E: LineEditor + Send + 'static>(editor: E) {
+    *lock() = Slot::Ready(Box::new(editor));
+}
+
+fn lock() -> MutexGuard<'static, Slot> {
+    BACKEND.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The installed backend, or an editor completing with
+/// `rl_completion_entry_function` by default
+fn take_backend(slot: &mut Slot) -> Result<Option<Backend>> {
+    match mem::replace(slot, Slot::Empty) {
+        Slot::Ready(backend) => Ok(Some(backend)),
+        Slot::Empty => {
+            let mut editor = Editor::<CHelper, DefaultHistory>::new()?;
+            editor.set_helper(Some(CHelper));
+            Ok(Some(Box::new(editor)))
+        }
+        reading => {
+            *slot = reading;
+            Ok(None)
+        }
+    }
+}
+
+/// Apply `f` to the backend, or once the line being read is read
+fn with_backend(f: impl FnOnce(&mut dyn LineEditor) -> Result<()> + Send + 'static) -> Result<()> {
+    let mut slot = lock();
+    match take_backend(&mut slot)? {
+        Some(mut backend) => {
+            let result = f(backend.as_mut());
+            *slot = Slot::Ready(backend);
+            result
+        }
+        None => {
+            if let Slot::Reading(ref mut deferred) = *slot {
+                deferred.push(Box::new(f));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Read a line without holding the lock, so that the helper can call the
+/// other entry points
+fn read_line(prompt: &str) -> Result<String> {
+    let mut backend = {
+        let mut slot = lock();
+        let Some(backend) = take_backend(&mut slot)? else {
+            return Err(io::Error::other("readline is already reading a line").into());
+        };
+        *slot = Slot::Reading(Vec::new());
+        backend
+    };
+    let result = backend.readline(prompt);
+    let mut slot = lock();
+    // unless another editor has been installed meanwhile
+    if let Slot::Reading(deferred) = mem::replace(&mut *slot, Slot::Empty) {
+        for f in deferred {
+            let _ = f(backend.as_mut());
+        }
+        *slot = Slot::Ready(backend);
+    }
+    result
+}
+
+/// Status returned to C: 0 on success, the OS error code (or -1) otherwise
+fn status(result: Result<()>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(ReadlineError::Io(e)) => e.raw_os_error().unwrap_or(-1),
+        Err(_) => -1,
+    }
+}
+
+/// # Safety
+/// `s` must be `NULL` or a valid C string.
+unsafe fn to_str<'a>(s: *const c_char) -> Option<std::borrow::Cow<'a, str>> {
+    if s.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(s).to_string_lossy())
+    }
+}
+
+/// Copy `s` in a buffer allocated with `malloc`
+fn to_malloc(s: &str) -> *mut c_char {
+    let bytes = s.as_bytes();
+    unsafe {
+        let buf = libc::malloc(bytes.len() + 1).cast::<u8>();
+        if !buf.is_null() {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+            *buf.add(bytes.len()) = 0;
+        }
+        buf.cast()
+    }
+}
+
+/// Initialize the editor (done by the first call of any entry point)
+#[no_mangle]
+pub extern "C" fn rl_initialize() -> c_int {
+    status(with_backend(|_| Ok(())))
+}
+
+/// Read a line with `prompt`, returned without the final newline in a
+/// buffer allocated with `malloc` (`NULL` at end of file).
+///
+/// # Safety
+/// `prompt` must be `NULL` or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn readline(prompt: *const c_char) -> *mut c_char {
+    let prompt = to_str(prompt).unwrap_or_default();
+    match read_line(&prompt) {
+        Ok(line) => to_malloc(&line),
+        Err(ReadlineError::Interrupted) => to_malloc(""),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Add `line` to the history
+///
+/// # Safety
+/// `line` must be `NULL` or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn add_history(line: *const c_char) {
+    if let Some(line) = to_str(line) {
+        let line = line.into_owned();
+        let _ = with_backend(move |backend| backend.add_history_entry(&line).map(drop));
+    }
+}
+
+/// Remove all the history entries
+#[no_mangle]
+pub extern "C" fn clear_history() {
+    let _ = with_backend(|backend| backend.clear_history());
+}
+
+/// Keep at most `max` history entries
+#[no_mangle]
+pub extern "C" fn stifle_history(max: c_int) {
+    let max = usize::try_from(max).unwrap_or(0);
+    let _ = with_backend(move |backend| backend.set_max_history_size(max));
+}
+
+/// Load the history from `filename` (`~/.history` when `NULL`)
+///
+/// # Safety
+/// `filename` must be `NULL` or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn read_history(filename: *const c_char) -> c_int {
+    let path = history_path(filename);
+    status(with_backend(move |backend| {
+        backend.load_history(Path::new(&path))
+    }))
+}
+
+/// Save the history in `filename` (`~/.history` when `NULL`)
+///
+/// # Safety
+/// `filename` must be `NULL` or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn write_history(filename: *const c_char) -> c_int {
+    let path = history_path(filename);
+    status(with_backend(move |backend| {
+        backend.save_history(Path::new(&path))
+    }))
+}
+
+unsafe fn history_path(filename: *const c_char) -> String {
+    match to_str(filename) {
+        Some(filename) => filename.into_owned(),
+        None => {
+            let home = std::env::var("HOME").unwrap_or_default();
+            format!("{home}/.history")
+        }
+    }
+}
+
+/// Helper completing with `rl_completion_entry_function`
+pub struct CHelper;
+
+impl Completer for CHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> Result<(usize, Vec<String>)> {
+        // read by value: no reference to the mutable statics
+        let (Some(generator), break_chars) = (unsafe { rl_completion_entry_function }, unsafe {
+            rl_completer_word_break_characters
+        }) else {
+            return Ok((pos, vec![]));
+        };
+        let break_chars = if break_chars.is_null() {
+            DEFAULT_WORD_BREAK_CHARACTERS.into()
+        } else {
+            unsafe { CStr::from_ptr(break_chars) }.to_string_lossy()
+        };
+        let (start, word) = completion_word(line, pos, &break_chars);
+        let (Ok(buffer), Ok(text)) = (CString::new(line), CString::new(word)) else {
+            return Ok((pos, vec![])); // NUL in the line
+        };
+        let buffer = buffer.into_raw();
+        let mut candidates = Vec::new();
+        unsafe {
+            rl_line_buffer = buffer;
+            rl_point = c_int::try_from(pos).unwrap_or(c_int::MAX);
+            for state in 0.. {
+                let candidate = generator(text.as_ptr(), state);
+                if candidate.is_null() {
+                    break;
+                }
+                candidates.push(CStr::from_ptr(candidate).to_string_lossy().into_owned());
+                libc::free(candidate.cast());
+            }
+            rl_line_buffer = ptr::null_mut();
+            drop(CString::from_raw(buffer));
+        }
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for CHelper {
+    type Hint = String;
+}
+impl Highlighter for CHelper {}
+impl Validator for CHelper {}
+impl Helper for CHelper {}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_char, c_int, CStr};
+    use std::sync::Mutex;
+
+    use super::{rl_completion_entry_function, CHelper, Slot};
+    use crate::completion::Completer;
+    use crate::history::{DefaultHistory, SearchDirection};
+    use crate::keys::{KeyCode, KeyEvent, Modifiers};
+    use crate::{Context, Editor};
+
+    /// Held by the tests setting `rl_completion_entry_function`
+    static GENERATOR: Mutex<()> = Mutex::new(());
+
+    unsafe extern "C" fn generator(text: *const c_char, state: c_int) -> *mut c_char {
+        let text = CStr::from_ptr(text).to_str().unwrap();
+        let line = CStr::from_ptr(super::rl_line_buffer).to_str().unwrap();
+        assert_eq!("git ch", line);
+        ["checkout", "cherry-pick", "commit"]
+            .iter()
+            .filter(|c| c.starts_with(text))
+            .nth(state as usize)
+            .map_or(std::ptr::null_mut(), |c| super::to_malloc(c))
+    }
+
+    #[test]
+    fn complete() {
+        let _generator = GENERATOR.lock().unwrap();
+        unsafe { rl_completion_entry_function = Some(generator) };
+        let history = DefaultHistory::new();
+        let (start, candidates) = CHelper
+            .complete("git ch", 6, &Context::new(&history))
+            .unwrap();
+        unsafe { rl_completion_entry_function = None };
+        assert_eq!(4, start);
+        assert_eq!(vec!["checkout", "cherry-pick"], candidates);
+    }
+
+    unsafe extern "C" fn add_history_generator(text: *const c_char, state: c_int) -> *mut c_char {
+        if state > 0 {
+            return std::ptr::null_mut();
+        }
+        // called back while `readline` is reading the line
+        super::add_history(text);
+        super::to_malloc("checkout")
+    }
+
+    #[test]
+    fn reentrant_generator() {
+        let _generator = GENERATOR.lock().unwrap();
+        let mut editor = Editor::<CHelper, DefaultHistory>::new().unwrap();
+        editor.set_helper(Some(CHelper));
+        editor.term.keys = "git ch"
+            .chars()
+            .map(KeyEvent::from)
+            .chain([KeyEvent(KeyCode::Tab, Modifiers::NONE), KeyEvent::ENTER])
+            .collect();
+        super::install(editor);
+        unsafe { rl_completion_entry_function = Some(add_history_generator) };
+        let line = unsafe { super::readline(c">> ".as_ptr()) };
+        unsafe { rl_completion_entry_function = None };
+        assert!(!line.is_null());
+        assert_eq!(Ok("git checkout"), unsafe { CStr::from_ptr(line) }.to_str());
+        unsafe { libc::free(line.cast()) };
+
+        let Slot::Ready(ref backend) = *super::lock() else {
+            panic!("no backend after readline");
+        };
+        let entry = backend.history().get(0, SearchDirection::Forward).unwrap();
+        assert_eq!("ch", entry.unwrap().entry);
+    }
+}
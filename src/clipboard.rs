@@ -0,0 +1,43 @@
+//! Native system clipboard (`with-arboard` feature)
+use log::debug;
+
+/// Lazily opened handle on the native clipboard.
+///
+/// Failures are logged and ignored: the kill ring keeps working without the
+/// system clipboard.
+#[derive(Default)]
+pub(crate) struct NativeClipboard {
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl NativeClipboard {
+    fn clipboard(&mut self) -> Option<&mut arboard::Clipboard> {
+        if self.clipboard.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(err) => debug!(target: "rustyline", "cannot open clipboard: {}", err),
+            }
+        }
+        self.clipboard.as_mut()
+    }
+
+    /// Copy `text` to the clipboard
+    pub fn copy(&mut self, text: &str) {
+        if let Some(clipboard) = self.clipboard() {
+            if let Err(err) = clipboard.set_text(text) {
+                debug!(target: "rustyline", "cannot copy to clipboard: {}", err);
+            }
+        }
+    }
+
+    /// Text currently in the clipboard
+    pub fn paste(&mut self) -> Option<String> {
+        match self.clipboard()?.get_text() {
+            Ok(text) => Some(text),
+            Err(err) => {
+                debug!(target: "rustyline", "cannot paste from clipboard: {}", err);
+                None
+            }
+        }
+    }
+}
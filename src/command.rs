@@ -4,10 +4,12 @@ use crate::edit::State;
 use crate::error;
 use crate::highlight::CmdKind;
 use crate::history::SearchDirection;
-use crate::keymap::{Anchor, At, Cmd, Movement, Word};
-use crate::keymap::{InputState, Refresher};
+use crate::keymap::{Anchor, At, Cmd, CursorOp, Movement, RegisterOp, Word};
+use crate::keymap::{InputMode, InputState, Refresher};
 use crate::kill_ring::{KillRing, Mode};
-use crate::line_buffer::WordAction;
+use crate::line_buffer::{Direction, WordAction};
+use crate::register::Registers;
+use crate::validate::ValidationResult;
 use crate::{Helper, Result};
 
 pub enum Status {
@@ -20,17 +22,59 @@ pub fn execute<H: Helper>(
     s: &mut State<'_, '_, H>,
     input_state: &InputState,
     kill_ring: &mut KillRing,
+    registers: &mut Registers,
     config: &Config,
 ) -> Result<Status> {
     use Status::{Proceed, Submit};
 
-    match cmd {
-        Cmd::EndOfFile | Cmd::AcceptLine | Cmd::AcceptOrInsertLine { .. } | Cmd::Newline => {
-            if s.has_hint() || !s.is_default_prompt() || s.highlight_char {
-                // Force a refresh without hints to leave the previous
-                // line as the user typed it after a newline.
-                s.refresh_line_with_msg(None, CmdKind::ForcedRefresh)?;
+    if s.has_cursors() {
+        // multi-cursor editing
+        let done = match cmd {
+            Cmd::SelfInsert(n, c) => {
+                s.edit_insert_at_cursors(&c.to_string().repeat(usize::from(n)))?;
+                true
+            }
+            Cmd::Insert(n, ref text) => {
+                s.edit_insert_at_cursors(&text.repeat(usize::from(n)))?;
+                true
+            }
+            Cmd::Kill(Movement::BackwardChar(n)) => {
+                s.edit_delete_at_cursors(n, Direction::Backward)?;
+                true
+            }
+            Cmd::Kill(Movement::ForwardChar(n)) => {
+                s.edit_delete_at_cursors(n, Direction::Forward)?;
+                true
+            }
+            Cmd::Move(Movement::BackwardChar(n)) => {
+                s.edit_move_cursors(n, Direction::Backward)?;
+                true
             }
+            Cmd::Move(Movement::ForwardChar(n)) => {
+                s.edit_move_cursors(n, Direction::Forward)?;
+                true
+            }
+            Cmd::MultiCursor(_) => false,
+            _ => {
+                s.edit_multi_cursor(CursorOp::Clear)?;
+                false
+            }
+        };
+        if done {
+            return Ok(Proceed);
+        }
+    }
+    match cmd {
+        Cmd::EndOfFile
+        | Cmd::AcceptLine
+        | Cmd::AcceptOrInsertLine { .. }
+        | Cmd::ForceAcceptLine
+        | Cmd::Newline
+            if s.has_hint() || !s.is_default_prompt() || s.highlight_char =>
+        {
+            // Force a refresh without hints to leave the previous
+            // line as the user typed it after a newline.
+            s.refresh_line_with_msg(None, CmdKind::ForcedRefresh)?;
         }
         _ => {}
     };
@@ -42,8 +86,19 @@ pub fn execute<H: Helper>(
             s.edit_insert(c, n)?;
         }
         Cmd::Insert(n, text) => {
+            if config
+                .paste_fold_lines()
+                .is_some_and(|lines| n == 1 && text.lines().count() > lines)
+            {
+                s.fold_paste();
+            }
             s.edit_yank(input_state, &text, Anchor::Before, n)?;
         }
+        Cmd::ExpandPaste => s.edit_expand_pastes()?,
+        Cmd::MultiCursor(op) => s.edit_multi_cursor(op)?,
+        Cmd::Move(Movement::BeginningOfLine) if config.visual_line_movement() => {
+            s.edit_move_visual_line_edge(Direction::Backward)?;
+        }
         Cmd::Move(Movement::BeginningOfLine) => {
             // Move to the beginning of line.
             s.edit_move_home()?;
@@ -65,9 +120,13 @@ pub fn execute<H: Helper>(
                 s.edit_insert_text(&text)?;
             }
         }
+        Cmd::SetLine(text, pos) => s.edit_set_line(&text, pos)?,
         Cmd::Overwrite(c) => {
             s.edit_overwrite_char(c)?;
         }
+        Cmd::Kill(Movement::BackwardChar(n)) if input_state.input_mode == InputMode::Replace => {
+            s.edit_overwrite_backspace(n)?;
+        }
         Cmd::EndOfFile => {
             if s.line.is_empty() {
                 return Err(error::ReadlineError::Eof);
@@ -75,6 +134,9 @@ pub fn execute<H: Helper>(
                 return Ok(Submit);
             }
         }
+        Cmd::Move(Movement::EndOfLine) if config.visual_line_movement() => {
+            s.edit_move_visual_line_edge(Direction::Forward)?;
+        }
         Cmd::Move(Movement::EndOfLine) => {
             // Move to the end of line.
             s.edit_move_end()?;
@@ -85,8 +147,10 @@ pub fn execute<H: Helper>(
         }
         Cmd::ClearScreen => {
             // Clear the screen leaving the current line at the top of the screen.
-            s.clear_screen()?;
-            s.refresh_line()?;
+            s.batch(|s| {
+                s.clear_screen()?;
+                s.refresh_line()
+            })?;
         }
         Cmd::NextHistory => {
             // Fetch the next command from the history list.
@@ -97,12 +161,22 @@ pub fn execute<H: Helper>(
             s.edit_history_next(true)?;
         }
         Cmd::LineUpOrPreviousHistory(n) => {
-            if !s.edit_move_line_up(n)? {
+            let moved = if config.visual_line_movement() {
+                s.edit_move_visual_line(n, Direction::Backward)?
+            } else {
+                s.edit_move_line_up(n)?
+            };
+            if !moved {
                 s.edit_history_next(true)?;
             }
         }
         Cmd::LineDownOrNextHistory(n) => {
-            if !s.edit_move_line_down(n)? {
+            let moved = if config.visual_line_movement() {
+                s.edit_move_visual_line(n, Direction::Forward)?
+            } else {
+                s.edit_move_line_down(n)?
+            };
+            if !moved {
                 s.edit_history_next(false)?;
             }
         }
@@ -131,6 +205,10 @@ pub fn execute<H: Helper>(
         }
         Cmd::AcceptLine | Cmd::AcceptOrInsertLine { .. } => {
             let validation_result = s.validate()?;
+            if let ValidationResult::Pending(_) = validation_result {
+                s.defer_validation(cmd);
+                return Ok(Proceed);
+            }
             let valid = validation_result.is_valid();
             let end = s.line.is_end_of_input();
             match (cmd, valid, end) {
@@ -148,12 +226,23 @@ pub fn execute<H: Helper>(
                 (Cmd::AcceptOrInsertLine { .. }, false, _)
                 | (Cmd::AcceptOrInsertLine { .. }, true, false) => {
                     if valid || !validation_result.has_message() {
-                        s.edit_insert('\n', 1)?;
+                        if let ValidationResult::IncompleteWith(ref continuation) =
+                            validation_result
+                        {
+                            s.edit_insert_continuation(continuation, config.auto_indent())?;
+                        } else if config.auto_indent() {
+                            s.edit_insert_indented_newline()?;
+                        } else {
+                            s.edit_insert('\n', 1)?;
+                        }
                     }
                 }
                 _ => unreachable!(),
             }
         }
+        Cmd::ForceAcceptLine => {
+            return Ok(Submit);
+        }
         Cmd::BeginningOfHistory => {
             // move to first entry in history
             s.edit_history(true)?;
@@ -180,6 +269,14 @@ pub fn execute<H: Helper>(
         Cmd::Move(Movement::LineUp(n)) => {
             s.edit_move_line_up(n)?;
         }
+        Cmd::VisualLineUp(n) => {
+            s.edit_move_visual_line(n, Direction::Backward)?;
+        }
+        Cmd::VisualLineDown(n) => {
+            s.edit_move_visual_line(n, Direction::Forward)?;
+        }
+        Cmd::BeginningOfVisualLine => s.edit_move_visual_line_edge(Direction::Backward)?,
+        Cmd::EndOfVisualLine => s.edit_move_visual_line_edge(Direction::Forward)?,
         Cmd::Move(Movement::LineDown(n)) => {
             s.edit_move_line_down(n)?;
         }
@@ -205,22 +302,73 @@ pub fn execute<H: Helper>(
         }
         Cmd::YankPop => {
             // yank-pop
-            if let Some((yank_size, text)) = kill_ring.yank_pop() {
-                s.edit_yank_pop(yank_size, text)?;
+            if let Some((yank_size, _)) = kill_ring.yank_pop() {
+                let (entries, current) = kill_ring.entries();
+                s.edit_yank_pop(yank_size, &entries, current)?;
             }
         }
         Cmd::Move(Movement::ViCharSearch(n, cs)) => s.edit_move_to(cs, n)?,
+        Cmd::Move(Movement::ViMark(mark, line_wise)) => s.edit_move_to_mark(mark, line_wise)?,
+        Cmd::Move(Movement::BackwardParagraph(n)) => {
+            s.edit_move_to_paragraph(n, Direction::Backward)?;
+        }
+        Cmd::Move(Movement::ForwardParagraph(n)) => {
+            s.edit_move_to_paragraph(n, Direction::Forward)?;
+        }
+        Cmd::Move(Movement::GotoLine(n)) => s.edit_move_to_line(n)?,
+        Cmd::ViSearch(ref search) => s.edit_vi_search(search)?,
+        Cmd::ViBlock(op) => s.edit_block(op, kill_ring)?,
+        Cmd::SetMark(mark) => {
+            s.line.set_mark(mark);
+        }
         Cmd::Undo(n) => {
-            if s.changes.undo(&mut s.line, n) {
+            let undone = s.changes.undo(&mut s.line, n);
+            if undone {
                 s.refresh_line()?;
             }
         }
+        Cmd::Redo(n) => {
+            let redone = s.changes.redo(&mut s.line, n);
+            if redone {
+                s.refresh_line()?;
+            }
+        }
+        Cmd::UndoBranch(n) => {
+            s.changes.switch_branch(n);
+        }
+        Cmd::Register(op, name) => match op {
+            RegisterOp::CopyLine => registers.set_text(name, s.line.as_str().to_owned()),
+            RegisterOp::Insert => {
+                if let Some(text) = registers.text(name) {
+                    s.edit_yank(input_state, text, Anchor::Before, 1)?;
+                }
+            }
+            RegisterOp::Point => registers.set_position(name, s.line.pos()),
+            RegisterOp::Jump => {
+                if let Some(pos) = registers.position(name) {
+                    s.edit_move_to_position(pos)?;
+                }
+            }
+        },
+        Cmd::Surround(mvt, c) => {
+            s.edit_surround(&mvt, c)?;
+        }
+        Cmd::DeleteSurround(c) => {
+            s.edit_delete_surround(c)?;
+        }
+        Cmd::ChangeSurround(old, new) => {
+            s.edit_change_surround(old, new)?;
+        }
         Cmd::Dedent(mvt) => {
             s.edit_indent(&mvt, config.indent_size(), true)?;
         }
         Cmd::Indent(mvt) => {
             s.edit_indent(&mvt, config.indent_size(), false)?;
         }
+        Cmd::Complete => {
+            // no completer
+            s.edit_insert_tab(config.tab_insertion(), config.indent_size())?;
+        }
         Cmd::Interrupt => {
             // Move to end, in case cursor was in the middle of the
             // line, so that next thing application prints goes after
@@ -0,0 +1,214 @@
+//! Helpers for applications migrating from GNU Readline or libedit, whose
+//! documentation refers to readline variables and hooks.
+//!
+//! [`set_variable`] applies `set name value` variables of `inputrc`:
+//!
+//! | Variable                  | Setting                                                        |
+//! |---------------------------|----------------------------------------------------------------|
+//! | `bell-style`              | [`bell_style`](Configurer::set_bell_style)                     |
+//! | `completion-query-items`  | [`completion_prompt_limit`](Configurer::set_completion_prompt_limit) |
+//! | `editing-mode`            | [`edit_mode`](Configurer::set_edit_mode)                       |
+//! | `enable-bracketed-paste`  | [`enable_bracketed_paste`](Configurer::enable_bracketed_paste) |
+//! | `horizontal-scroll-mode`  | [`horizontal_scroll`](Configurer::set_horizontal_scroll)       |
+//! | `history-size`            | [`max_history_size`](Configurer::set_max_history_size)         |
+//! | `keyseq-timeout`          | [`keyseq_timeout`](Configurer::set_keyseq_timeout)             |
+//! | `show-all-if-ambiguous`   | [`completion_show_all_if_ambiguous`](Configurer::set_completion_show_all_if_ambiguous) |
+//!
+//! [`completion_word`] delimits the word to complete like
+//! `rl_completer_word_break_characters`, and [`LineHook`] edits the line
+//! like the `bind -x` commands of bash (`READLINE_LINE` and
+//! `READLINE_POINT`).
+use std::io;
+
+use crate::config::{BellStyle, Configurer, EditMode};
+use crate::Result;
+
+/// Default GNU Readline `rl_basic_word_break_characters`
+pub const DEFAULT_WORD_BREAK_CHARACTERS: &str = " \t\n\"\\'`@$><=;|&{(";
+
+/// Apply the readline variable `name` (see the [module](self) documentation).
+///
+/// Like readline, booleans are on for an empty value, `on` (case
+/// insensitive) or `1`, off otherwise, and negative numbers mean no limit.
+/// Unknown variables and invalid values are reported as
+/// [`io::ErrorKind::InvalidInput`] errors.
+pub fn set_variable<C: Configurer + ?Sized>(config: &mut C, name: &str, value: &str) -> Result<()> {
+    let on = value.is_empty() || value.eq_ignore_ascii_case("on") || value == "1";
+    match name {
+        "bell-style" => config.set_bell_style(match value {
+            "none" => BellStyle::None,
+            "visible" => BellStyle::Visible,
+            "audible" => BellStyle::Audible,
+            _ => return Err(invalid(name, value)),
+        }),
+        "completion-query-items" => {
+            config.set_completion_prompt_limit(unlimited(name, value)?);
+        }
+        "editing-mode" => config.set_edit_mode(match value {
+            "emacs" => EditMode::Emacs,
+            "vi" => EditMode::Vi,
+            _ => return Err(invalid(name, value)),
+        }),
+        "enable-bracketed-paste" => config.enable_bracketed_paste(on),
+        "horizontal-scroll-mode" => config.set_horizontal_scroll(on),
+        "history-size" => config.set_max_history_size(unlimited(name, value)?)?,
+        // wait for the next key indefinitely when not a positive number
+        "keyseq-timeout" => config.set_keyseq_timeout(
+            value
+                .parse::<i64>()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .map(|ms| u16::try_from(ms).unwrap_or(u16::MAX)),
+        ),
+        "show-all-if-ambiguous" => config.set_completion_show_all_if_ambiguous(on),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported readline variable `{name}`"),
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
+
+/// Number, `usize::MAX` when negative
+fn unlimited(name: &str, value: &str) -> Result<usize> {
+    let n: i64 = value.parse().map_err(|_| invalid(name, value))?;
+    Ok(usize::try_from(n).unwrap_or(usize::MAX))
+}
+
+fn invalid(name: &str, value: &str) -> crate::error::ReadlineError {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("`{name}`: invalid value `{value}`"),
+    )
+    .into()
+}
+
+/// Given a `line` and a cursor `pos`ition, find backward the start of the
+/// word to complete, delimited by one of the `break_chars` (like
+/// `rl_completer_word_break_characters`, e.g.
+/// [`DEFAULT_WORD_BREAK_CHARACTERS`]).
+///
+/// Return the word and its start position (idx, `line[idx..pos]`).
+#[must_use]
+pub fn completion_word<'l>(line: &'l str, pos: usize, break_chars: &str) -> (usize, &'l str) {
+    let line = &line[..pos];
+    let start = line.rfind(|c| break_chars.contains(c)).map_or(0, |i| {
+        i + line[i..].chars().next().map_or(1, char::len_utf8)
+    });
+    (start, &line[start..])
+}
+
+/// Hook editing the line like the `bind -x` commands of bash: the closure
+/// receives the line (`READLINE_LINE`) and the cursor byte position
+/// (`READLINE_POINT`), and may change both.
+///
+/// ```
+/// use rustyline::compat::LineHook;
+/// use rustyline::{DefaultEditor, KeyEvent};
+///
+/// let mut rl = DefaultEditor::new()?;
+/// // uppercase the line, keeping the cursor
+/// rl.bind_sequence(
+///     KeyEvent::ctrl('X'),
+///     LineHook::new(|line: &mut String, _: &mut usize| *line = line.to_uppercase()),
+/// );
+/// # Ok::<(), rustyline::error::ReadlineError>(())
+/// ```
+#[cfg(feature = "custom-bindings")]
+#[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
+pub struct LineHook<F>(F);
+
+#[cfg(feature = "custom-bindings")]
+impl<F: Fn(&mut String, &mut usize) + Send + Sync> LineHook<F> {
+    /// Hook calling `f`
+    pub fn new(f: F) -> Self {
+        Self(f)
+    }
+}
+
+#[cfg(feature = "custom-bindings")]
+impl<F: Fn(&mut String, &mut usize) + Send + Sync> crate::ConditionalEventHandler for LineHook<F> {
+    fn handle(
+        &self,
+        _: &crate::Event,
+        _: crate::RepeatCount,
+        _: bool,
+        ctx: &crate::EventContext,
+    ) -> Option<crate::Cmd> {
+        let mut line = ctx.line().to_owned();
+        let mut point = ctx.pos();
+        (self.0)(&mut line, &mut point);
+        Some(if line == ctx.line() && point == ctx.pos() {
+            crate::Cmd::Noop
+        } else {
+            crate::Cmd::SetLine(line, point)
+        })
+    }
+}
+
+#[cfg(feature = "custom-bindings")]
+impl<F: Fn(&mut String, &mut usize) + Send + Sync + 'static> From<LineHook<F>>
+    for crate::EventHandler
+{
+    fn from(hook: LineHook<F>) -> Self {
+        Self::Conditional(Box::new(hook))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{completion_word, set_variable, DEFAULT_WORD_BREAK_CHARACTERS};
+    use crate::config::{BellStyle, Builder, EditMode};
+
+    #[test]
+    fn variables() {
+        let mut builder = Builder::new();
+        for (name, value) in [
+            ("editing-mode", "vi"),
+            ("bell-style", "none"),
+            ("horizontal-scroll-mode", "On"),
+            ("show-all-if-ambiguous", ""),
+            ("enable-bracketed-paste", "off"),
+            ("completion-query-items", "-1"),
+            ("history-size", "500"),
+            ("keyseq-timeout", "0"),
+        ] {
+            set_variable(&mut builder, name, value).unwrap();
+        }
+        let config = builder.build();
+        assert_eq!(EditMode::Vi, config.edit_mode());
+        assert_eq!(BellStyle::None, config.bell_style());
+        assert!(config.horizontal_scroll());
+        assert!(config.completion_show_all_if_ambiguous());
+        assert!(!config.enable_bracketed_paste());
+        assert_eq!(usize::MAX, config.completion_prompt_limit());
+        assert_eq!(500, config.max_history_size());
+        assert_eq!(None, config.keyseq_timeout());
+
+        let mut builder = Builder::new();
+        assert!(set_variable(&mut builder, "editing-mode", "ed").is_err());
+        assert!(set_variable(&mut builder, "history-size", "many").is_err());
+        assert!(set_variable(&mut builder, "mark-directories", "on").is_err());
+    }
+
+    #[test]
+    fn word() {
+        let line = "ls 'a b' \u{e9}t\u{e9}";
+        assert_eq!(
+            (9, "\u{e9}t\u{e9}"),
+            completion_word(line, line.len(), DEFAULT_WORD_BREAK_CHARACTERS)
+        );
+        assert_eq!(
+            (6, "b"),
+            completion_word(line, 7, DEFAULT_WORD_BREAK_CHARACTERS)
+        );
+        assert_eq!(
+            (0, "ls"),
+            completion_word(line, 2, DEFAULT_WORD_BREAK_CHARACTERS)
+        );
+        assert_eq!((3, "b"), completion_word("a\u{e9}b", 4, "\u{e9}"));
+    }
+}
@@ -355,16 +355,32 @@ fn filename_complete(
 }
 
 #[cfg(any(windows, target_os = "macos"))]
-fn normalize(s: &str) -> Cow<str> {
+fn normalize(s: &str) -> Cow<'_, str> {
     // case insensitive
     Owned(s.to_lowercase())
 }
 
 #[cfg(not(any(windows, target_os = "macos")))]
-fn normalize(s: &str) -> Cow<str> {
+fn normalize(s: &str) -> Cow<'_, str> {
     Cow::Borrowed(s)
 }
 
+/// Given a `line` and a cursor `pos`ition, find backward the start of the
+/// word made of alphanumeric characters and `word_chars` (e.g.
+/// [`Context::word_chars`](crate::Context::word_chars)).
+///
+/// Return the word and its start position (idx, `line[idx..pos]`).
+#[must_use]
+pub fn current_word<'l>(line: &'l str, pos: usize, word_chars: &str) -> (usize, &'l str) {
+    let line = &line[..pos];
+    let start = line
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !(c.is_alphanumeric() || word_chars.contains(c)))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    (start, &line[start..])
+}
+
 /// Given a `line` and a cursor `pos`ition,
 /// try to find backward the start of a word.
 ///
@@ -513,6 +529,15 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn current_word() {
+        let line = "(define-syntax my-macro";
+        assert_eq!((15, "my-macro"), super::current_word(line, line.len(), "-"));
+        assert_eq!((18, "macro"), super::current_word(line, line.len(), ""));
+        assert_eq!((0, ""), super::current_word(line, 0, "-"));
+        assert_eq!((1, "define"), super::current_word(line, 7, "-"));
+    }
+
     #[test]
     pub fn unescape() {
         use std::borrow::Cow::{self, Borrowed, Owned};
@@ -604,6 +629,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(dead_code)]
     pub fn candidate_impls() {
         struct StrCmp;
         impl Completer for StrCmp {
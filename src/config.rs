@@ -1,9 +1,24 @@
 //! Customize line editor
-use crate::{layout::GraphemeClusterMode, Result};
+use crate::layout::{AmbiguousWidth, GraphemeClusterMode};
+use crate::{InputMode, Result};
+use rustyline_core::normalize::NormalizationForm;
+pub use rustyline_core::undo::UndoGrouping;
+use std::borrow::Cow;
 use std::default::Default;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+// environment variables
+mod env;
+// configuration file
+mod file;
+pub(crate) use self::file::ConfigFile;
+#[cfg(feature = "serde")]
+pub(crate) use self::file::{set_value, settings, Value};
 
 /// User preferences
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Config {
     /// Maximum number of entries in History.
     max_history_size: usize, // history_max_entries
@@ -15,9 +30,23 @@ pub struct Config {
     /// When listing completion alternatives, only display
     /// one screen of possibilities at a time.
     completion_prompt_limit: usize,
+    /// Question asked when there are more than `completion_prompt_limit`
+    /// alternatives (`{}` replaced by their number)
+    completion_query: Cow<'static, str>,
+    /// Number of rows of alternatives displayed before a pause
+    completion_page_rows: Option<u16>,
+    /// Prompt displayed at each pause
+    completion_more_prompt: Cow<'static, str>,
+    /// Maximum number of columns of alternatives
+    completion_max_columns: Option<u16>,
+    /// Minimal number of spaces between two columns
+    completion_column_padding: u16,
+    /// Alternatives sorted across the rows instead of down the columns
+    completion_horizontal: bool,
     /// Duration (milliseconds) Rustyline will wait for a character when
     /// reading an ambiguous key sequence.
     keyseq_timeout: Option<u16>,
+    mapping_timeout: Option<u16>,
     /// Emacs or Vi mode
     edit_mode: EditMode,
     /// If true, each nonblank line returned by `readline` will be
@@ -29,18 +58,78 @@ pub struct Config {
     color_mode: ColorMode,
     /// if terminal supports grapheme clustering
     grapheme_cluster_mode: GraphemeClusterMode,
+    /// Replace `grapheme_cluster_mode` by the one reported by the terminal
+    detect_grapheme_cluster_mode: bool,
+    /// Width of East Asian ambiguous characters, detected when `None`
+    ambiguous_width: Option<AmbiguousWidth>,
     /// Whether to use stdio or not
     behavior: Behavior,
     /// Horizontal space taken by a tab.
     tab_stop: u8,
     /// Indentation size for indent/dedent commands
     indent_size: u8,
+    /// Copy indentation on continuation lines
+    auto_indent: bool,
+    /// Scroll long lines horizontally instead of wrapping them
+    horizontal_scroll: bool,
+    /// Reorder right-to-left text for display
+    bidi: bool,
+    /// Normalization of the inserted text
+    normalization: Option<NormalizationForm>,
+    /// Line number gutter for multi-line input
+    line_numbers: bool,
+    /// Fold pastes of more lines than this
+    paste_fold_lines: Option<usize>,
+    /// Up/Down and Home/End move by display line
+    visual_line_movement: bool,
+    /// Kill ring / system clipboard synchronisation
+    clipboard: ClipboardMode,
+    /// Maximum number of killed texts
+    kill_ring_size: usize,
+    /// Where the kill ring is synchronised
+    clipboard_backend: ClipboardBackend,
+    /// Edit on the alternate screen
+    alternate_screen: bool,
+    /// Baud rate of the serial console
+    serial_console: Option<u32>,
+    /// What to do with a prompt wider than the terminal
+    prompt_overflow: PromptOverflow,
+    /// Window title set to the prompt while editing
+    window_title: bool,
+    /// Probe the terminal for optional features
+    probe_terminal: bool,
+    /// Draw only the final state of an input burst
+    coalesce_refresh: bool,
+    /// Count the rendering work
+    render_stats: bool,
+    /// Simulated terminal size when stdin / stdout are not terminals
+    headless: Option<(u16, u16)>,
+    /// Honor the `RUSTYLINE_*` and `NO_COLOR` environment variables
+    env_overrides: bool,
+    /// Word constituents besides alphanumeric characters
+    word_chars: Arc<str>,
+    /// What the Tab key inserts when there is nothing to complete
+    tab_insertion: TabInsertion,
+    /// Terminal multiplexer passthrough
+    passthrough: Passthrough,
     /// Check if cursor position is at leftmost before displaying prompt
     check_cursor_position: bool,
     /// Bracketed paste on unix platform
     enable_bracketed_paste: bool,
+    enable_synchronized_output: bool,
+    /// Focus reporting (DEC private mode 1004) on unix platform
+    enable_focus_events: bool,
+    /// Minimal delay (milliseconds) between two external prints
+    external_print_interval: Option<u16>,
+    /// Maximum number of external messages waiting to be printed
+    external_print_capacity: usize,
+    external_print_policy: QueuePolicy,
     /// Whether to disable or not the signals in termios
     enable_signals: bool,
+    /// Cursor shape for each input mode (command, insert, replace)
+    cursor_shapes: [CursorShape; 3],
+    /// How consecutive edits are coalesced into undo steps
+    undo_grouping: UndoGrouping,
 }
 
 impl Config {
@@ -50,6 +139,43 @@ impl Config {
         Builder::new()
     }
 
+    /// Returns a `Config` behaving like the `preset` shell, the other
+    /// settings keeping their default value (see [`Builder::preset`]).
+    #[must_use]
+    pub fn preset(preset: Preset) -> Self {
+        Builder::new().preset(preset).build()
+    }
+
+    /// Load the settings of a configuration file: `edit_mode`,
+    /// `completion_type`, `bell_style`, `color_mode`, ... (named like the
+    /// [`Builder`] methods), the other settings keeping their default value.
+    ///
+    /// The file is made of `key = value` lines grouped by `[table]` headers,
+    /// a value being a quoted string, a boolean or an integer. It is not
+    /// parsed as TOML: the other TOML constructs (arrays, floats, ...) are
+    /// rejected.
+    ///
+    /// The settings of the `[profile.<name>]` tables are applied next when
+    /// `name` is the terminal type (`TERM` environment variable) or a prefix
+    /// of it followed by `*` (e.g. `[profile."xterm-*"]`).
+    /// The key bindings of the `[bindings]` tables are loaded by
+    /// [`Editor::load_bindings`](crate::Editor::load_bindings).
+    /// The errors report the file and the line of the invalid entry.
+    pub fn from_path<P: AsRef<Path> + ?Sized>(path: &P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut builder = Builder::new();
+        ConfigFile::load(path)?.configure(path, &mut builder)?;
+        Ok(builder.build())
+    }
+
+    /// Copy the settings which can be changed while a line is edited
+    pub(crate) fn set_live_settings(&mut self, other: &Self) {
+        self.color_mode = other.color_mode;
+        self.bell_style = other.bell_style;
+        self.completion_type = other.completion_type;
+        self.edit_mode = other.edit_mode;
+    }
+
     /// Tell the maximum length (i.e. number of entries) for the history.
     #[must_use]
     pub fn max_history_size(&self) -> usize {
@@ -114,6 +240,60 @@ impl Config {
         self.completion_show_all_if_ambiguous
     }
 
+    /// Question asked (answered with `y` or `n`) before listing more than
+    /// [`Config::completion_prompt_limit`] alternatives, `{}` being replaced
+    /// by their number.
+    ///
+    /// By default, `"Display all {} possibilities? (y or n)"`.
+    #[must_use]
+    pub fn completion_query(&self) -> &str {
+        &self.completion_query
+    }
+
+    /// Number of rows of alternatives listed before pausing (`y` or space
+    /// for the next page, enter for the next row, `n` or `q` to stop).
+    ///
+    /// By default, `None`: the height of the terminal minus one row.
+    #[must_use]
+    pub fn completion_page_rows(&self) -> Option<u16> {
+        self.completion_page_rows
+    }
+
+    /// Prompt displayed when the listing of the alternatives pauses.
+    ///
+    /// By default, `"--More--"`.
+    #[must_use]
+    pub fn completion_more_prompt(&self) -> &str {
+        &self.completion_more_prompt
+    }
+
+    /// Maximum number of columns of listed alternatives (`Some(1)` for one
+    /// alternative per row).
+    ///
+    /// By default, `None`: as many as fit in the terminal width.
+    #[must_use]
+    pub fn completion_max_columns(&self) -> Option<u16> {
+        self.completion_max_columns
+    }
+
+    /// Minimal number of spaces between two columns of listed alternatives.
+    ///
+    /// By default, 2.
+    #[must_use]
+    pub fn completion_column_padding(&self) -> u16 {
+        self.completion_column_padding
+    }
+
+    /// Tell if the listed alternatives are sorted across the rows (like
+    /// readline `print-completions-horizontally`) instead of down the
+    /// columns.
+    ///
+    /// By default, they are sorted down the columns.
+    #[must_use]
+    pub fn completion_horizontal(&self) -> bool {
+        self.completion_horizontal
+    }
+
     /// Duration (milliseconds) Rustyline will wait for a character when
     /// reading an ambiguous key sequence (used for [`EditMode::Vi`] mode on
     /// unix platform).
@@ -124,6 +304,16 @@ impl Config {
         self.keyseq_timeout
     }
 
+    /// Duration (milliseconds) Rustyline will wait for the next key when the
+    /// keys typed so far are the prefix of a custom key sequence binding
+    /// (like vim `timeoutlen`).
+    ///
+    /// By default, no timeout.
+    #[must_use]
+    pub fn mapping_timeout(&self) -> Option<u16> {
+        self.mapping_timeout
+    }
+
     /// Emacs or Vi mode
     #[must_use]
     pub fn edit_mode(&self) -> EditMode {
@@ -144,6 +334,10 @@ impl Config {
         self.bell_style
     }
 
+    pub(crate) fn set_bell_style(&mut self, bell_style: BellStyle) {
+        self.bell_style = bell_style;
+    }
+
     /// Tell if colors should be enabled.
     ///
     /// By default, they are except if stdout is not a TTY.
@@ -153,11 +347,39 @@ impl Config {
     }
 
     /// Tell if terminal supports grapheme clustering
+    ///
+    /// By default, it's detected on unix platform by printing an emoji
+    /// modifier sequence and a ZWJ sequence and querying the cursor position
+    /// (see [`probe_terminal`](Self::probe_terminal)), and guessed from the
+    /// `TERM_PROGRAM` and `TERM` environment variables when the terminal
+    /// doesn't answer (or on other platforms).
     #[must_use]
     pub fn grapheme_cluster_mode(&self) -> GraphemeClusterMode {
         self.grapheme_cluster_mode
     }
 
+    /// Whether the grapheme cluster mode is detected (not set explicitly)
+    pub(crate) fn detect_grapheme_cluster_mode(&self) -> bool {
+        self.detect_grapheme_cluster_mode
+    }
+
+    /// Width of the East Asian ambiguous characters (`§`, `○`, box
+    /// drawing, ...) on the terminal, which depends on its settings and
+    /// font. When `None`, it is detected on unix platform by printing such
+    /// a character and querying the cursor position (see
+    /// [`probe_terminal`](Self::probe_terminal)), and assumed to be narrow
+    /// when the terminal doesn't answer.
+    ///
+    /// By default, it's detected.
+    #[must_use]
+    pub fn ambiguous_width(&self) -> Option<AmbiguousWidth> {
+        self.ambiguous_width
+    }
+
+    pub(crate) fn set_ambiguous_width(&mut self, ambiguous_width: Option<AmbiguousWidth>) {
+        self.ambiguous_width = ambiguous_width;
+    }
+
     pub(crate) fn set_color_mode(&mut self, color_mode: ColorMode) {
         self.color_mode = color_mode;
     }
@@ -183,6 +405,7 @@ impl Config {
     }
 
     pub(crate) fn set_tab_stop(&mut self, tab_stop: u8) {
+        assert!(tab_stop > 0, "tab_stop must be at least 1");
         self.tab_stop = tab_stop;
     }
 
@@ -206,6 +429,260 @@ impl Config {
         self.indent_size = indent_size;
     }
 
+    /// Whether a newline inserted when input is incomplete is followed by
+    /// the indentation of the previous line (or the one computed by
+    /// [`Validator::indent`](crate::validate::Validator::indent)).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn auto_indent(&self) -> bool {
+        self.auto_indent
+    }
+
+    /// Whether a single-line input wider than the terminal is kept on one
+    /// row and scrolled horizontally around the cursor (with `<` / `>`
+    /// truncation markers) instead of being wrapped.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn horizontal_scroll(&self) -> bool {
+        self.horizontal_scroll
+    }
+
+    /// Whether lines containing right-to-left text (Arabic, Hebrew, ...)
+    /// are reordered for display (Unicode Bidirectional Algorithm, see
+    /// [`rustyline_core::bidi`]), the input itself staying in logical
+    /// order. Only for terminals displaying the characters in the order
+    /// they are written: terminals with their own bidi support (VTE based,
+    /// Konsole, mlterm, ...) would reorder the line twice.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn bidi(&self) -> bool {
+        self.bidi
+    }
+
+    /// Unicode normalization form of the inserted text (typed, pasted or
+    /// yanked), with the characters before the cursor it combines with (see
+    /// [`rustyline_core::normalize`]), so that text composed or decomposed
+    /// differently by keyboards and input methods (`é` or `e` followed by a
+    /// combining acute accent) compares equal in history searches and
+    /// completions. The incremental search query is normalized the same
+    /// way; the history entries and the completion candidates are not.
+    ///
+    /// By default, the text is inserted as is.
+    #[must_use]
+    pub fn normalization(&self) -> Option<NormalizationForm> {
+        self.normalization
+    }
+
+    /// Whether line numbers are displayed in a left gutter when the input
+    /// spans more than one line.
+    ///
+    /// By default, they are not.
+    #[must_use]
+    pub fn line_numbers(&self) -> bool {
+        self.line_numbers
+    }
+
+    /// Number of lines above which a bracketed paste is displayed as a folded
+    /// placeholder (`⟨ 240 lines pasted ⟩`) until it is expanded
+    /// ([`Cmd::ExpandPaste`](crate::Cmd::ExpandPaste)) or the cursor moves
+    /// into it.
+    ///
+    /// By default, pastes are not folded.
+    #[must_use]
+    pub fn paste_fold_lines(&self) -> Option<usize> {
+        self.paste_fold_lines
+    }
+
+    /// Whether Up/Down and Home/End (Ctrl-A/Ctrl-E) move by display line (a
+    /// logical line wrapped on several rows being several display lines)
+    /// instead of by logical line.
+    ///
+    /// By default, they move by logical line.
+    #[must_use]
+    pub fn visual_line_movement(&self) -> bool {
+        self.visual_line_movement
+    }
+
+    /// Synchronisation of the kill ring with the system clipboard, through the
+    /// terminal on unix (OSC 52 escape sequence, which works over SSH) or the
+    /// Win32 clipboard on windows (see [`Config::clipboard_backend`]).
+    ///
+    /// By default, the kill ring is not synchronised.
+    #[must_use]
+    pub fn clipboard(&self) -> ClipboardMode {
+        self.clipboard
+    }
+
+    /// Maximum number of killed texts kept in the kill ring (0 disables it).
+    ///
+    /// By default, 60.
+    #[must_use]
+    pub fn kill_ring_size(&self) -> usize {
+        self.kill_ring_size
+    }
+
+    pub(crate) fn set_kill_ring_size(&mut self, size: usize) {
+        self.kill_ring_size = size;
+    }
+
+    /// Which clipboard the kill ring is synchronised with.
+    ///
+    /// By default, [`ClipboardBackend::Terminal`].
+    #[must_use]
+    pub fn clipboard_backend(&self) -> ClipboardBackend {
+        self.clipboard_backend
+    }
+
+    /// Whether the line is edited on the alternate screen (like full-screen
+    /// applications), the primary screen being restored with the final line
+    /// echoed when it is accepted.
+    ///
+    /// By default, the line is edited on the primary screen.
+    #[must_use]
+    pub fn alternate_screen(&self) -> bool {
+        self.alternate_screen
+    }
+
+    /// Baud rate when the line is edited over a serial console (embedded
+    /// device shell): output is paced to the line speed, XON/XOFF flow
+    /// control is kept, there is no job control and optional escape
+    /// sequences (bracketed paste, synchronized output, cursor shape and
+    /// position report) are not used.
+    ///
+    /// By default, the terminal is not a serial console.
+    #[must_use]
+    pub fn serial_console(&self) -> Option<u32> {
+        self.serial_console
+    }
+
+    /// What to do with a prompt wider than the terminal.
+    ///
+    /// By default, [`PromptOverflow::Wrap`].
+    #[must_use]
+    pub fn prompt_overflow(&self) -> PromptOverflow {
+        self.prompt_overflow
+    }
+
+    /// Whether the terminal window title is set to the prompt while the line
+    /// is edited, the previous title being restored when `readline` returns.
+    ///
+    /// By default, the window title is left untouched.
+    #[must_use]
+    pub fn window_title(&self) -> bool {
+        self.window_title
+    }
+
+    /// Whether the terminal is asked (with `DECRQM` and `DA1` queries) which
+    /// optional features it supports on unix platform: bracketed paste and
+    /// synchronized output are only used when supported (or when the
+    /// terminal doesn't answer), extended keys (`modifyOtherKeys`) are
+    /// enabled when supported and the widths of the East Asian ambiguous
+    /// characters and grapheme clusters are measured (see
+    /// [`ambiguous_width`](Self::ambiguous_width) and
+    /// [`grapheme_cluster_mode`](Self::grapheme_cluster_mode)).
+    /// The answer is cached per `TERM`.
+    ///
+    /// By default, it's enabled.
+    #[must_use]
+    pub fn probe_terminal(&self) -> bool {
+        self.probe_terminal
+    }
+
+    /// Whether refreshes are postponed while more input is already
+    /// available (fast typing, key repeat, replayed macros), so that only
+    /// the final state of a burst is drawn.
+    ///
+    /// By default, it's enabled.
+    #[must_use]
+    pub fn coalesce_refresh(&self) -> bool {
+        self.coalesce_refresh
+    }
+
+    /// Whether the rendering work (redraws, layout computations, bytes
+    /// written) is counted, see [`Editor::render_stats`](crate::Editor::render_stats).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn render_stats(&self) -> bool {
+        self.render_stats
+    }
+
+    /// Headless mode on unix platform: the editor behaves as if attached to
+    /// an interactive terminal of this size (columns, rows) even when stdin
+    /// and stdout are pipes (raw mode is a no-op, no size or feature query
+    /// is done), so that the interactive code paths can run in CI. The
+    /// rendering, escape sequences included, is written to stdout (or to the
+    /// output given to `Editor::with_fds`).
+    ///
+    /// By default, `None`.
+    #[must_use]
+    pub fn headless(&self) -> Option<(u16, u16)> {
+        self.headless
+    }
+
+    /// Whether the end user can override some settings with environment
+    /// variables: `RUSTYLINE_EDIT_MODE` (`emacs` or `vi`),
+    /// `RUSTYLINE_COMPLETION_TYPE` (`circular` or `list`),
+    /// `RUSTYLINE_BELL_STYLE` (`audible`, `none` or `visible`),
+    /// `RUSTYLINE_COLOR` (`enabled`, `forced` or `disabled`) and `NO_COLOR`
+    /// (colors disabled when not empty). They are applied when the
+    /// [`Editor`](crate::Editor) is created.
+    ///
+    /// By default, they are honored.
+    #[must_use]
+    pub fn env_overrides(&self) -> bool {
+        self.env_overrides
+    }
+
+    /// Characters which are part of words, besides the alphanumeric ones
+    /// (and `_` for vi words): e.g. `"-/."` for paths or `"-*?!"` for Lisp
+    /// symbols. They apply to the word movements and deletions (`M-f`,
+    /// `M-b`, `M-d`, `M-Backspace`, vi `w`, `b`, `e`) and to the words
+    /// extracted by [`completion::current_word`](crate::completion::current_word).
+    /// `C-w` and vi `W`, `B` words are delimited by whitespaces only.
+    ///
+    /// By default, none.
+    #[must_use]
+    pub fn word_chars(&self) -> &str {
+        &self.word_chars
+    }
+
+    pub(crate) fn shared_word_chars(&self) -> Arc<str> {
+        Arc::clone(&self.word_chars)
+    }
+
+    /// What the Tab key inserts when there is no completer or when it finds
+    /// nothing to complete.
+    ///
+    /// By default, [`TabInsertion::Nothing`].
+    #[must_use]
+    pub fn tab_insertion(&self) -> TabInsertion {
+        self.tab_insertion
+    }
+
+    /// The settings overridden by the environment variables (when enabled)
+    pub(crate) fn with_env_overrides(self) -> Self {
+        if !self.env_overrides {
+            return self;
+        }
+        let mut builder = Builder { p: self };
+        env::apply(&mut builder, |name| std::env::var(name).ok());
+        builder.build()
+    }
+
+    /// How sequences which must reach the outer terminal (OSC 52 clipboard,
+    /// cursor shape, queries) are wrapped when running inside a terminal
+    /// multiplexer on unix platform.
+    ///
+    /// By default, [`Passthrough::Auto`].
+    #[must_use]
+    pub fn passthrough(&self) -> Passthrough {
+        self.passthrough
+    }
+
     /// Bracketed paste on unix platform
     ///
     /// By default, it's enabled.
@@ -214,6 +691,57 @@ impl Config {
         self.enable_bracketed_paste
     }
 
+    /// Synchronized output (DEC private mode 2026) on unix platform: each
+    /// refresh is displayed at once, without flicker, by terminals supporting
+    /// it (and ignored by the others).
+    ///
+    /// By default, it's enabled.
+    #[must_use]
+    pub fn enable_synchronized_output(&self) -> bool {
+        self.enable_synchronized_output
+    }
+
+    /// Focus reporting (DEC private mode 1004) on unix platform: the
+    /// terminal gaining or losing focus is notified with
+    /// [`Helper::on_focus`](crate::Helper::on_focus) and can be bound with
+    /// [`KeyCode::FocusIn`](crate::KeyCode::FocusIn) /
+    /// [`KeyCode::FocusOut`](crate::KeyCode::FocusOut).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn enable_focus_events(&self) -> bool {
+        self.enable_focus_events
+    }
+
+    /// Minimal duration (milliseconds) between two redraws of the prompt
+    /// caused by an [`ExternalPrinter`](crate::ExternalPrinter): messages
+    /// received in between are queued and printed together.
+    ///
+    /// By default, no batching: each message is printed as soon as it is
+    /// received.
+    #[must_use]
+    pub fn external_print_interval(&self) -> Option<u16> {
+        self.external_print_interval
+    }
+
+    /// Maximum number of messages sent by
+    /// [`ExternalPrinter`](crate::ExternalPrinter)s waiting to be printed.
+    ///
+    /// By default, 1.
+    #[must_use]
+    pub fn external_print_capacity(&self) -> usize {
+        self.external_print_capacity
+    }
+
+    /// What an [`ExternalPrinter`](crate::ExternalPrinter) does when the
+    /// queue of messages is full.
+    ///
+    /// By default, [`QueuePolicy::Block`].
+    #[must_use]
+    pub fn external_print_policy(&self) -> QueuePolicy {
+        self.external_print_policy
+    }
+
     /// Enable or disable signals in termios
     ///
     /// By default, it's disabled.
@@ -225,6 +753,38 @@ impl Config {
     pub(crate) fn set_enable_signals(&mut self, enable_signals: bool) {
         self.enable_signals = enable_signals;
     }
+
+    /// Cursor shape used in the specified input `mode`
+    /// ([`InputMode::Insert`] for emacs mode).
+    ///
+    /// By default, the terminal default shape is kept. Otherwise, once the
+    /// line is read, the cursor is reset to [`CursorShape::Default`] (the
+    /// shape it had before cannot be queried).
+    #[must_use]
+    pub fn cursor_shape(&self, mode: InputMode) -> CursorShape {
+        self.cursor_shapes[cursor_shape_index(mode)]
+    }
+
+    /// Tell how consecutive edits are coalesced into a single undo step.
+    ///
+    /// By default, [`UndoGrouping::Word`].
+    #[must_use]
+    pub fn undo_grouping(&self) -> UndoGrouping {
+        self.undo_grouping
+    }
+
+    /// Tell if the cursor shape must be changed for at least one input mode.
+    pub(crate) fn has_cursor_shapes(&self) -> bool {
+        self.cursor_shapes != [CursorShape::Default; 3] && self.serial_console.is_none()
+    }
+}
+
+const fn cursor_shape_index(mode: InputMode) -> usize {
+    match mode {
+        InputMode::Command => 0,
+        InputMode::Insert => 1,
+        InputMode::Replace => 2,
+    }
 }
 
 impl Default for Config {
@@ -236,18 +796,56 @@ impl Default for Config {
             completion_type: CompletionType::Circular, // TODO Validate
             completion_prompt_limit: 100,
             completion_show_all_if_ambiguous: false,
+            completion_query: Cow::Borrowed("Display all {} possibilities? (y or n)"),
+            completion_page_rows: None,
+            completion_more_prompt: Cow::Borrowed("--More--"),
+            completion_max_columns: None,
+            completion_column_padding: 2,
+            completion_horizontal: false,
             keyseq_timeout: None,
+            mapping_timeout: None,
             edit_mode: EditMode::Emacs,
             auto_add_history: false,
             bell_style: BellStyle::default(),
             color_mode: ColorMode::Enabled,
-            grapheme_cluster_mode: GraphemeClusterMode::from_env(),
+            grapheme_cluster_mode: crate::layout::grapheme_cluster_mode(),
+            detect_grapheme_cluster_mode: true,
+            ambiguous_width: None,
             behavior: Behavior::default(),
             tab_stop: 8,
             indent_size: 2,
+            auto_indent: false,
+            horizontal_scroll: false,
+            bidi: false,
+            normalization: None,
+            line_numbers: false,
+            paste_fold_lines: None,
+            visual_line_movement: false,
+            clipboard: ClipboardMode::default(),
+            kill_ring_size: 60,
+            clipboard_backend: ClipboardBackend::default(),
+            alternate_screen: false,
+            serial_console: None,
+            prompt_overflow: PromptOverflow::default(),
+            window_title: false,
+            probe_terminal: true,
+            coalesce_refresh: true,
+            render_stats: false,
+            headless: None,
+            env_overrides: true,
+            word_chars: Arc::from(""),
+            tab_insertion: TabInsertion::default(),
+            passthrough: Passthrough::default(),
             check_cursor_position: false,
             enable_bracketed_paste: true,
+            enable_synchronized_output: true,
+            enable_focus_events: false,
+            external_print_interval: None,
+            external_print_capacity: 1,
+            external_print_policy: QueuePolicy::default(),
             enable_signals: false,
+            cursor_shapes: [CursorShape::Default; 3],
+            undo_grouping: UndoGrouping::default(),
         }
     }
 }
@@ -327,6 +925,27 @@ pub enum ColorMode {
     Disabled,
 }
 
+/// Cursor shape (`DECSCUSR`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CursorShape {
+    /// Terminal default shape
+    #[default]
+    Default,
+    /// Blinking block
+    BlinkingBlock,
+    /// Steady block
+    SteadyBlock,
+    /// Blinking underline
+    BlinkingUnderline,
+    /// Steady underline
+    SteadyUnderline,
+    /// Blinking bar
+    BlinkingBar,
+    /// Steady bar
+    SteadyBar,
+}
+
 /// Should the editor use stdio
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[non_exhaustive]
@@ -342,6 +961,124 @@ pub enum Behavior {
     // useFile
 }
 
+/// Kill ring / system clipboard synchronisation (OSC 52 on unix, Win32
+/// clipboard on windows)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClipboardMode {
+    /// Kill ring only
+    #[default]
+    Disabled,
+    /// Copy killed / yanked (vi) text to the clipboard
+    Copy,
+    /// Copy killed text to the clipboard and paste (yank) the clipboard
+    /// content when it has been changed outside the terminal (the terminal
+    /// must allow clipboard reading)
+    CopyPaste,
+}
+
+/// System clipboard used to synchronise the kill ring
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClipboardBackend {
+    /// OSC 52 escape sequence on unix (which works over SSH), Win32 clipboard
+    /// on windows
+    #[default]
+    Terminal,
+    /// Native clipboard of the local machine (X11 / Wayland, macOS, windows),
+    /// through `arboard`
+    #[cfg(feature = "with-arboard")]
+    Native,
+}
+
+/// What to do with a prompt (or a line of a multi-line prompt) wider than the
+/// terminal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PromptOverflow {
+    /// Wrap the prompt on several rows, input starting on the last one
+    #[default]
+    Wrap,
+    /// Keep the end of the prompt, its start being replaced by an ellipsis
+    /// (`…`)
+    TruncateLeft,
+    /// Shorten the prompt with
+    /// [`Highlighter::shrink_prompt`](crate::highlight::Highlighter::shrink_prompt)
+    /// (e.g. by abbreviating path segments), then truncate it on the left if
+    /// it is still too wide
+    Shrink,
+}
+
+/// Terminal multiplexer passthrough: sequences the multiplexer doesn't
+/// handle itself are wrapped in a `DCS` string forwarded to the outer
+/// terminal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Passthrough {
+    /// Detect the multiplexer from the `TMUX`, `STY` and `TERM` environment
+    /// variables
+    #[default]
+    Auto,
+    /// Sequences are never wrapped
+    Disabled,
+    /// tmux (`allow-passthrough` must be enabled)
+    Tmux,
+    /// GNU screen
+    Screen,
+}
+
+/// What an [`ExternalPrinter`](crate::ExternalPrinter) does when the
+/// queue of messages waiting to be printed is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QueuePolicy {
+    /// Wait until a message is printed
+    #[default]
+    Block,
+    /// Discard the oldest message waiting to be printed
+    DropOldest,
+    /// Discard the new message
+    DropNewest,
+}
+
+/// What the Tab key inserts when there is nothing to complete
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TabInsertion {
+    /// Nothing (the bell rings when the completer finds nothing)
+    #[default]
+    Nothing,
+    /// A tab character (displayed up to the next multiple of
+    /// [`Config::tab_stop`])
+    Tab,
+    /// The specified number of spaces
+    Spaces(u8),
+    /// In the leading whitespace of a line, the missing part of the
+    /// indentation computed by [`Validator::indent`](crate::validate::Validator::indent)
+    /// for this line; otherwise (or when the line is already indented), spaces
+    /// up to the next multiple of [`Config::indent_size`]
+    SmartIndent,
+}
+
+/// Familiar shell whose behavior is emulated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Preset {
+    /// Emacs mode, completion till the longest match then list of the
+    /// matches, every line kept in the history, audible bell
+    Bash,
+    /// Emacs mode, completion of the next full match, consecutive duplicates
+    /// and lines beginning with a space not kept in the history
+    Zsh,
+    /// Emacs mode, list of the matches shown at once, duplicates and lines
+    /// beginning with a space not kept in the history, no bell.
+    ///
+    /// Autosuggestions are displayed by a [`Hinter`](crate::hint::Hinter)
+    /// like [`HistoryHinter`](crate::hint::HistoryHinter): the right arrow
+    /// at the end of the line accepts them.
+    Fish,
+}
+
 /// Configuration builder
 #[derive(Clone, Debug, Default)]
 pub struct Builder {
@@ -357,6 +1094,25 @@ impl Builder {
         }
     }
 
+    /// Apply the settings of the `preset` shell: edit mode, completion
+    /// style, history policies and bell style (history added automatically).
+    #[must_use]
+    pub fn preset(mut self, preset: Preset) -> Self {
+        let (completion_type, show_all, ignore_dups, bell_style) = match preset {
+            Preset::Bash => (CompletionType::List, false, false, BellStyle::Audible),
+            Preset::Zsh => (CompletionType::Circular, false, true, BellStyle::Audible),
+            Preset::Fish => (CompletionType::List, true, true, BellStyle::None),
+        };
+        self.set_edit_mode(EditMode::Emacs);
+        self.set_completion_type(completion_type);
+        self.set_completion_show_all_if_ambiguous(show_all);
+        self.p.set_history_ignore_dups(ignore_dups);
+        self.set_history_ignore_space(ignore_dups);
+        self.set_auto_add_history(true);
+        self.set_bell_style(bell_style);
+        self
+    }
+
     /// Set the maximum length for the history.
     pub fn max_history_size(mut self, max_size: usize) -> Result<Self> {
         self.set_max_history_size(max_size)?;
@@ -409,56 +1165,131 @@ impl Builder {
         self
     }
 
-    /// Timeout for ambiguous key sequences in milliseconds.
-    /// Currently, it is used only to distinguish a single ESC from an ESC
-    /// sequence.
-    /// After seeing an ESC key, wait at most `keyseq_timeout_ms` for another
-    /// byte.
+    /// Question asked before listing many alternatives (`{}` replaced by
+    /// their number)
+    ///
+    /// By default, `"Display all {} possibilities? (y or n)"`.
     #[must_use]
-    pub fn keyseq_timeout(mut self, keyseq_timeout_ms: Option<u16>) -> Self {
-        self.set_keyseq_timeout(keyseq_timeout_ms);
+    pub fn completion_query(mut self, query: impl Into<Cow<'static, str>>) -> Self {
+        self.set_completion_query(query.into());
         self
     }
 
-    /// Choose between Emacs or Vi mode.
+    /// Number of rows of alternatives listed before pausing
+    ///
+    /// By default, the height of the terminal minus one row.
     #[must_use]
-    pub fn edit_mode(mut self, edit_mode: EditMode) -> Self {
-        self.set_edit_mode(edit_mode);
+    pub fn completion_page_rows(mut self, rows: Option<u16>) -> Self {
+        self.set_completion_page_rows(rows);
         self
     }
 
-    /// Tell if lines are automatically added to the history.
+    /// Prompt displayed when the listing of the alternatives pauses
     ///
-    /// By default, they are not.
+    /// By default, `"--More--"`.
     #[must_use]
-    pub fn auto_add_history(mut self, yes: bool) -> Self {
-        self.set_auto_add_history(yes);
+    pub fn completion_more_prompt(mut self, prompt: impl Into<Cow<'static, str>>) -> Self {
+        self.set_completion_more_prompt(prompt.into());
         self
     }
 
-    /// Set bell style: beep, flash or nothing.
+    /// Maximum number of columns of listed alternatives
+    ///
+    /// By default, as many as fit in the terminal width.
     #[must_use]
-    pub fn bell_style(mut self, bell_style: BellStyle) -> Self {
-        self.set_bell_style(bell_style);
+    pub fn completion_max_columns(mut self, columns: Option<u16>) -> Self {
+        self.set_completion_max_columns(columns);
         self
     }
 
-    /// Forces colorization on or off.
+    /// Minimal number of spaces between two columns of listed alternatives
     ///
-    /// By default, colorization is on except if stdout is not a TTY.
+    /// By default, 2.
     #[must_use]
-    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+    pub fn completion_column_padding(mut self, padding: u16) -> Self {
+        self.set_completion_column_padding(padding);
+        self
+    }
+
+    /// Sort the listed alternatives across the rows instead of down the
+    /// columns
+    ///
+    /// By default, they are sorted down the columns.
+    #[must_use]
+    pub fn completion_horizontal(mut self, yes: bool) -> Self {
+        self.set_completion_horizontal(yes);
+        self
+    }
+
+    /// Timeout for ambiguous key sequences in milliseconds.
+    /// Currently, it is used only to distinguish a single ESC from an ESC
+    /// sequence.
+    /// After seeing an ESC key, wait at most `keyseq_timeout_ms` for another
+    /// byte.
+    #[must_use]
+    pub fn keyseq_timeout(mut self, keyseq_timeout_ms: Option<u16>) -> Self {
+        self.set_keyseq_timeout(keyseq_timeout_ms);
+        self
+    }
+
+    /// Timeout for ambiguous custom key sequence bindings in milliseconds.
+    /// For example, with `jk` bound in vi insert mode, after seeing `j`, wait
+    /// at most `mapping_timeout_ms` for `k` before inserting `j` literally.
+    #[must_use]
+    pub fn mapping_timeout(mut self, mapping_timeout_ms: Option<u16>) -> Self {
+        self.set_mapping_timeout(mapping_timeout_ms);
+        self
+    }
+
+    /// Choose between Emacs or Vi mode.
+    #[must_use]
+    pub fn edit_mode(mut self, edit_mode: EditMode) -> Self {
+        self.set_edit_mode(edit_mode);
+        self
+    }
+
+    /// Tell if lines are automatically added to the history.
+    ///
+    /// By default, they are not.
+    #[must_use]
+    pub fn auto_add_history(mut self, yes: bool) -> Self {
+        self.set_auto_add_history(yes);
+        self
+    }
+
+    /// Set bell style: beep, flash or nothing.
+    #[must_use]
+    pub fn bell_style(mut self, bell_style: BellStyle) -> Self {
+        self.set_bell_style(bell_style);
+        self
+    }
+
+    /// Forces colorization on or off.
+    ///
+    /// By default, colorization is on except if stdout is not a TTY.
+    #[must_use]
+    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
         self.set_color_mode(color_mode);
         self
     }
 
-    /// Tell if terminal supports grapheme clustering
+    /// Tell if terminal supports grapheme clustering (instead of detecting
+    /// it)
     #[must_use]
     pub fn grapheme_cluster_mode(mut self, grapheme_cluster_mode: GraphemeClusterMode) -> Self {
         self.set_grapheme_cluster_mode(grapheme_cluster_mode);
         self
     }
 
+    /// Width of the East Asian ambiguous characters, `None` to detect it
+    ///
+    /// By default, it's detected.
+    #[must_use]
+    pub fn ambiguous_width(mut self, ambiguous_width: Option<AmbiguousWidth>) -> Self {
+        self.set_ambiguous_width(ambiguous_width);
+        self
+    }
+
     /// Whether to use stdio or not
     ///
     /// By default, stdio is used.
@@ -471,6 +1302,10 @@ impl Builder {
     /// Horizontal space taken by a tab.
     ///
     /// By default, `8`
+    ///
+    /// # Panics
+    ///
+    /// Panics when `tab_stop` is 0.
     #[must_use]
     pub fn tab_stop(mut self, tab_stop: u8) -> Self {
         self.set_tab_stop(tab_stop);
@@ -495,6 +1330,206 @@ impl Builder {
         self
     }
 
+    /// Enable or disable auto-indentation of continuation lines
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn auto_indent(mut self, enabled: bool) -> Self {
+        self.set_auto_indent(enabled);
+        self
+    }
+
+    /// Scroll long lines horizontally instead of wrapping them
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn horizontal_scroll(mut self, enabled: bool) -> Self {
+        self.set_horizontal_scroll(enabled);
+        self
+    }
+
+    /// Reorder right-to-left text for display
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn bidi(mut self, enabled: bool) -> Self {
+        self.set_bidi(enabled);
+        self
+    }
+
+    /// Normalize the inserted text, with
+    /// [`NormalizationForm::Nfc`] usually
+    ///
+    /// By default, the text is inserted as is.
+    #[must_use]
+    pub fn normalization(mut self, form: Option<NormalizationForm>) -> Self {
+        self.set_normalization(form);
+        self
+    }
+
+    /// Display line numbers in a left gutter for multi-line input
+    ///
+    /// By default, they are not.
+    #[must_use]
+    pub fn line_numbers(mut self, enabled: bool) -> Self {
+        self.set_line_numbers(enabled);
+        self
+    }
+
+    /// Fold bracketed pastes of more than `lines` lines
+    ///
+    /// By default, pastes are not folded.
+    #[must_use]
+    pub fn paste_fold_lines(mut self, lines: Option<usize>) -> Self {
+        self.set_paste_fold_lines(lines);
+        self
+    }
+
+    /// Whether Up/Down and Home/End move by display line
+    ///
+    /// By default, they move by logical line.
+    #[must_use]
+    pub fn visual_line_movement(mut self, enabled: bool) -> Self {
+        self.set_visual_line_movement(enabled);
+        self
+    }
+
+    /// Synchronisation of the kill ring with the system clipboard (OSC 52)
+    ///
+    /// By default, the kill ring is not synchronised.
+    #[must_use]
+    pub fn clipboard(mut self, mode: ClipboardMode) -> Self {
+        self.set_clipboard(mode);
+        self
+    }
+
+    /// Maximum number of killed texts kept in the kill ring (0 disables it)
+    ///
+    /// By default, 60.
+    #[must_use]
+    pub fn kill_ring_size(mut self, size: usize) -> Self {
+        self.set_kill_ring_size(size);
+        self
+    }
+
+    /// Which clipboard the kill ring is synchronised with
+    ///
+    /// By default, [`ClipboardBackend::Terminal`].
+    #[must_use]
+    pub fn clipboard_backend(mut self, backend: ClipboardBackend) -> Self {
+        self.set_clipboard_backend(backend);
+        self
+    }
+
+    /// Whether the line is edited on the alternate screen
+    ///
+    /// By default, the line is edited on the primary screen.
+    #[must_use]
+    pub fn alternate_screen(mut self, enabled: bool) -> Self {
+        self.set_alternate_screen(enabled);
+        self
+    }
+
+    /// Baud rate of the serial console the line is edited over
+    ///
+    /// By default, the terminal is not a serial console.
+    #[must_use]
+    pub fn serial_console(mut self, baud_rate: Option<u32>) -> Self {
+        self.set_serial_console(baud_rate);
+        self
+    }
+
+    /// What to do with a prompt wider than the terminal
+    ///
+    /// By default, [`PromptOverflow::Wrap`].
+    #[must_use]
+    pub fn prompt_overflow(mut self, prompt_overflow: PromptOverflow) -> Self {
+        self.set_prompt_overflow(prompt_overflow);
+        self
+    }
+
+    /// Set the window title to the prompt while the line is edited
+    ///
+    /// By default, the window title is left untouched.
+    #[must_use]
+    pub fn window_title(mut self, enabled: bool) -> Self {
+        self.set_window_title(enabled);
+        self
+    }
+
+    /// Probe the terminal for optional features on unix platform
+    ///
+    /// By default, it's enabled.
+    #[must_use]
+    pub fn probe_terminal(mut self, enabled: bool) -> Self {
+        self.set_probe_terminal(enabled);
+        self
+    }
+
+    /// Draw only the final state of an input burst
+    ///
+    /// By default, it's enabled.
+    #[must_use]
+    pub fn coalesce_refresh(mut self, enabled: bool) -> Self {
+        self.set_coalesce_refresh(enabled);
+        self
+    }
+
+    /// Count the rendering work
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn render_stats(mut self, enabled: bool) -> Self {
+        self.set_render_stats(enabled);
+        self
+    }
+
+    /// Behave as if attached to a `cols`×`rows` terminal on unix platform
+    ///
+    /// By default, `None`.
+    #[must_use]
+    pub fn headless(mut self, size: Option<(u16, u16)>) -> Self {
+        self.set_headless(size);
+        self
+    }
+
+    /// Whether the `RUSTYLINE_*` and `NO_COLOR` environment variables
+    /// override the settings
+    ///
+    /// By default, they do.
+    #[must_use]
+    pub fn env_overrides(mut self, yes: bool) -> Self {
+        self.set_env_overrides(yes);
+        self
+    }
+
+    /// Characters which are part of words, besides the alphanumeric ones
+    ///
+    /// By default, none.
+    #[must_use]
+    pub fn word_chars(mut self, chars: impl Into<Arc<str>>) -> Self {
+        self.set_word_chars(chars.into());
+        self
+    }
+
+    /// What the Tab key inserts when there is nothing to complete
+    ///
+    /// By default, nothing.
+    #[must_use]
+    pub fn tab_insertion(mut self, insertion: TabInsertion) -> Self {
+        self.set_tab_insertion(insertion);
+        self
+    }
+
+    /// Terminal multiplexer passthrough on unix platform
+    ///
+    /// By default, [`Passthrough::Auto`].
+    #[must_use]
+    pub fn passthrough(mut self, passthrough: Passthrough) -> Self {
+        self.set_passthrough(passthrough);
+        self
+    }
+
     /// Enable or disable bracketed paste on unix platform
     ///
     /// By default, it's enabled.
@@ -504,6 +1539,53 @@ impl Builder {
         self
     }
 
+    /// Enable or disable synchronized output (mode 2026) on unix platform
+    ///
+    /// By default, it's enabled.
+    #[must_use]
+    pub fn synchronized_output(mut self, enabled: bool) -> Self {
+        self.enable_synchronized_output(enabled);
+        self
+    }
+
+    /// Enable or disable focus reporting (mode 1004) on unix platform
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn focus_events(mut self, enabled: bool) -> Self {
+        self.enable_focus_events(enabled);
+        self
+    }
+
+    /// Batch external prints so that the prompt is redrawn at most once
+    /// every `interval_ms`.
+    ///
+    /// By default, no batching.
+    #[must_use]
+    pub fn external_print_interval(mut self, interval_ms: Option<u16>) -> Self {
+        self.set_external_print_interval(interval_ms);
+        self
+    }
+
+    /// Bound the queue of messages sent by external printers to `capacity`
+    /// messages, `policy` telling what to do when it is full.
+    ///
+    /// By default, 1 message and [`QueuePolicy::Block`].
+    #[must_use]
+    pub fn external_print_queue(mut self, capacity: usize, policy: QueuePolicy) -> Self {
+        self.set_external_print_queue(capacity, policy);
+        self
+    }
+
+    /// Choose how consecutive edits are coalesced into a single undo step.
+    ///
+    /// By default, [`UndoGrouping::Word`].
+    #[must_use]
+    pub fn undo_grouping(mut self, grouping: UndoGrouping) -> Self {
+        self.set_undo_grouping(grouping);
+        self
+    }
+
     /// Enable or disable signals in termios
     ///
     /// By default, it's disabled.
@@ -513,6 +1595,18 @@ impl Builder {
         self
     }
 
+    /// Cursor shape used in the specified input `mode`
+    /// ([`InputMode::Insert`] for emacs mode).
+    ///
+    /// By default, the terminal default shape is kept. Otherwise, once the
+    /// line is read, the cursor is reset to [`CursorShape::Default`] (the
+    /// shape it had before cannot be queried).
+    #[must_use]
+    pub fn cursor_shape(mut self, mode: InputMode, shape: CursorShape) -> Self {
+        self.set_cursor_shape(mode, shape);
+        self
+    }
+
     /// Builds a [`Config`] with the settings specified so far.
     #[must_use]
     pub fn build(self) -> Config {
@@ -526,6 +1620,95 @@ impl Configurer for Builder {
     }
 }
 
+/// Configuration shared with an [`Editor`](crate::Editor), which can be
+/// changed from another thread.
+///
+/// The editor does not wake up for a change: while a line is being edited,
+/// the color mode, bell style, completion type and edit mode are applied
+/// once the next key pressed by the user has been handled. The other
+/// settings are applied when the next line is read, except the ones only
+/// used to create the terminal (e.g. `tab_stop` or
+/// `enable_bracketed_paste`).
+#[derive(Clone, Debug)]
+pub struct ConfigHandle {
+    shared: Arc<Shared>,
+    /// Last generation applied by the editor
+    seen: u64,
+}
+
+#[derive(Debug)]
+struct Shared {
+    config: Mutex<Config>,
+    /// Incremented each time the configuration is changed by the handle
+    generation: AtomicU64,
+}
+
+impl ConfigHandle {
+    pub(crate) fn new(config: Config) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                config: Mutex::new(config),
+                generation: AtomicU64::new(0),
+            }),
+            seen: 0,
+        }
+    }
+
+    /// Current configuration
+    #[must_use]
+    pub fn get(&self) -> Config {
+        self.shared.lock().clone()
+    }
+
+    /// Replace the configuration
+    pub fn set(&self, config: Config) {
+        self.update(|builder| builder.p = config);
+    }
+
+    /// Change some settings with the [`Configurer`] methods of a builder,
+    /// e.g. `handle.update(|b| b.set_edit_mode(EditMode::Vi))`
+    pub fn update<F: FnOnce(&mut Builder)>(&self, f: F) {
+        let mut config = self.shared.lock();
+        // a copy left untouched if `f` panics
+        let mut builder = Builder { p: config.clone() };
+        f(&mut builder);
+        *config = builder.p;
+        self.shared.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// The configuration changed by a handle since the last call (`None`
+    /// when it is unchanged)
+    pub(crate) fn changed(&mut self) -> Option<Config> {
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        if generation == self.seen {
+            return None;
+        }
+        self.seen = generation;
+        Some(self.get())
+    }
+
+    /// The configuration changed by a handle since the last call or, when
+    /// unchanged, publish `config` (as changed by the editor itself)
+    pub(crate) fn sync(&mut self, config: &Config) -> Option<Config> {
+        let mut shared = self.shared.lock();
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        if generation == self.seen {
+            shared.clone_from(config);
+            return None;
+        }
+        self.seen = generation;
+        Some(shared.clone())
+    }
+}
+
+impl Shared {
+    /// The configuration, even if a closure given to
+    /// [`ConfigHandle::update`] panicked on another thread
+    fn lock(&self) -> MutexGuard<'_, Config> {
+        self.config.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 /// Trait for component that holds a [`Config`].
 pub trait Configurer {
     /// `Config` accessor.
@@ -565,6 +1748,50 @@ pub trait Configurer {
         self.config_mut().completion_show_all_if_ambiguous = completion_show_all_if_ambiguous;
     }
 
+    /// Question asked before listing many alternatives (`{}` replaced by
+    /// their number)
+    ///
+    /// By default, `"Display all {} possibilities? (y or n)"`.
+    fn set_completion_query(&mut self, query: Cow<'static, str>) {
+        self.config_mut().completion_query = query;
+    }
+
+    /// Number of rows of alternatives listed before pausing
+    ///
+    /// By default, the height of the terminal minus one row.
+    fn set_completion_page_rows(&mut self, rows: Option<u16>) {
+        self.config_mut().completion_page_rows = rows.filter(|&rows| rows > 0);
+    }
+
+    /// Prompt displayed when the listing of the alternatives pauses
+    ///
+    /// By default, `"--More--"`.
+    fn set_completion_more_prompt(&mut self, prompt: Cow<'static, str>) {
+        self.config_mut().completion_more_prompt = prompt;
+    }
+
+    /// Maximum number of columns of listed alternatives
+    ///
+    /// By default, as many as fit in the terminal width.
+    fn set_completion_max_columns(&mut self, columns: Option<u16>) {
+        self.config_mut().completion_max_columns = columns.filter(|&columns| columns > 0);
+    }
+
+    /// Minimal number of spaces between two columns of listed alternatives
+    ///
+    /// By default, 2.
+    fn set_completion_column_padding(&mut self, padding: u16) {
+        self.config_mut().completion_column_padding = padding;
+    }
+
+    /// Sort the listed alternatives across the rows instead of down the
+    /// columns
+    ///
+    /// By default, they are sorted down the columns.
+    fn set_completion_horizontal(&mut self, yes: bool) {
+        self.config_mut().completion_horizontal = yes;
+    }
+
     /// The number of possible completions that determines when the user is
     /// asked whether the list of possibilities should be displayed.
     fn set_completion_prompt_limit(&mut self, completion_prompt_limit: usize) {
@@ -576,6 +1803,11 @@ pub trait Configurer {
         self.config_mut().keyseq_timeout = keyseq_timeout_ms;
     }
 
+    /// Timeout for ambiguous custom key sequence bindings in milliseconds.
+    fn set_mapping_timeout(&mut self, mapping_timeout_ms: Option<u16>) {
+        self.config_mut().mapping_timeout = mapping_timeout_ms;
+    }
+
     /// Choose between Emacs or Vi mode.
     fn set_edit_mode(&mut self, edit_mode: EditMode) {
         self.config_mut().edit_mode = edit_mode;
@@ -594,7 +1826,7 @@ pub trait Configurer {
 
     /// Set bell style: beep, flash or nothing.
     fn set_bell_style(&mut self, bell_style: BellStyle) {
-        self.config_mut().bell_style = bell_style;
+        self.config_mut().set_bell_style(bell_style);
     }
 
     /// Forces colorization on or off.
@@ -604,9 +1836,19 @@ pub trait Configurer {
         self.config_mut().set_color_mode(color_mode);
     }
 
-    /// Tell if terminal supports grapheme clustering
+    /// Tell if terminal supports grapheme clustering (instead of detecting
+    /// it)
     fn set_grapheme_cluster_mode(&mut self, grapheme_cluster_mode: GraphemeClusterMode) {
-        self.config_mut().grapheme_cluster_mode = grapheme_cluster_mode;
+        let config = self.config_mut();
+        config.grapheme_cluster_mode = grapheme_cluster_mode;
+        config.detect_grapheme_cluster_mode = false;
+    }
+
+    /// Width of the East Asian ambiguous characters, `None` to detect it
+    ///
+    /// By default, it's detected.
+    fn set_ambiguous_width(&mut self, ambiguous_width: Option<AmbiguousWidth>) {
+        self.config_mut().set_ambiguous_width(ambiguous_width);
     }
 
     /// Whether to use stdio or not
@@ -619,6 +1861,10 @@ pub trait Configurer {
     /// Horizontal space taken by a tab.
     ///
     /// By default, `8`
+    ///
+    /// # Panics
+    ///
+    /// Panics when `tab_stop` is 0.
     fn set_tab_stop(&mut self, tab_stop: u8) {
         self.config_mut().set_tab_stop(tab_stop);
     }
@@ -636,6 +1882,162 @@ pub trait Configurer {
         self.config_mut().set_indent_size(size);
     }
 
+    /// Enable or disable auto-indentation of continuation lines
+    ///
+    /// By default, it's disabled.
+    fn set_auto_indent(&mut self, enabled: bool) {
+        self.config_mut().auto_indent = enabled;
+    }
+
+    /// Scroll long lines horizontally instead of wrapping them
+    ///
+    /// By default, it's disabled.
+    fn set_horizontal_scroll(&mut self, enabled: bool) {
+        self.config_mut().horizontal_scroll = enabled;
+    }
+
+    /// Reorder right-to-left text for display
+    ///
+    /// By default, it's disabled.
+    fn set_bidi(&mut self, enabled: bool) {
+        self.config_mut().bidi = enabled;
+    }
+
+    /// Normalize the inserted text, with
+    /// [`NormalizationForm::Nfc`] usually
+    ///
+    /// By default, the text is inserted as is.
+    fn set_normalization(&mut self, form: Option<NormalizationForm>) {
+        self.config_mut().normalization = form;
+    }
+
+    /// Display line numbers in a left gutter for multi-line input
+    ///
+    /// By default, they are not.
+    fn set_line_numbers(&mut self, enabled: bool) {
+        self.config_mut().line_numbers = enabled;
+    }
+
+    /// Fold bracketed pastes of more than `lines` lines
+    ///
+    /// By default, pastes are not folded.
+    fn set_paste_fold_lines(&mut self, lines: Option<usize>) {
+        self.config_mut().paste_fold_lines = lines;
+    }
+
+    /// Whether Up/Down and Home/End move by display line
+    ///
+    /// By default, they move by logical line.
+    fn set_visual_line_movement(&mut self, enabled: bool) {
+        self.config_mut().visual_line_movement = enabled;
+    }
+
+    /// Synchronisation of the kill ring with the system clipboard (OSC 52)
+    ///
+    /// By default, the kill ring is not synchronised.
+    fn set_clipboard(&mut self, mode: ClipboardMode) {
+        self.config_mut().clipboard = mode;
+    }
+
+    /// Maximum number of killed texts kept in the kill ring (0 disables it)
+    ///
+    /// By default, 60.
+    fn set_kill_ring_size(&mut self, size: usize) {
+        self.config_mut().set_kill_ring_size(size);
+    }
+
+    /// Which clipboard the kill ring is synchronised with
+    ///
+    /// By default, [`ClipboardBackend::Terminal`].
+    fn set_clipboard_backend(&mut self, backend: ClipboardBackend) {
+        self.config_mut().clipboard_backend = backend;
+    }
+
+    /// Whether the line is edited on the alternate screen
+    ///
+    /// By default, the line is edited on the primary screen.
+    fn set_alternate_screen(&mut self, enabled: bool) {
+        self.config_mut().alternate_screen = enabled;
+    }
+
+    /// Baud rate of the serial console the line is edited over
+    ///
+    /// By default, the terminal is not a serial console.
+    fn set_serial_console(&mut self, baud_rate: Option<u32>) {
+        self.config_mut().serial_console = baud_rate;
+    }
+
+    /// What to do with a prompt wider than the terminal
+    ///
+    /// By default, [`PromptOverflow::Wrap`].
+    fn set_prompt_overflow(&mut self, prompt_overflow: PromptOverflow) {
+        self.config_mut().prompt_overflow = prompt_overflow;
+    }
+
+    /// Set the window title to the prompt while the line is edited
+    ///
+    /// By default, the window title is left untouched.
+    fn set_window_title(&mut self, enabled: bool) {
+        self.config_mut().window_title = enabled;
+    }
+
+    /// Probe the terminal for optional features on unix platform
+    ///
+    /// By default, it's enabled.
+    fn set_probe_terminal(&mut self, enabled: bool) {
+        self.config_mut().probe_terminal = enabled;
+    }
+
+    /// Draw only the final state of an input burst
+    ///
+    /// By default, it's enabled.
+    fn set_coalesce_refresh(&mut self, enabled: bool) {
+        self.config_mut().coalesce_refresh = enabled;
+    }
+
+    /// Count the rendering work
+    ///
+    /// By default, it's disabled.
+    fn set_render_stats(&mut self, enabled: bool) {
+        self.config_mut().render_stats = enabled;
+    }
+
+    /// Behave as if attached to a `cols`×`rows` terminal on unix platform
+    ///
+    /// By default, `None`.
+    fn set_headless(&mut self, size: Option<(u16, u16)>) {
+        self.config_mut().headless = size;
+    }
+
+    /// Whether the `RUSTYLINE_*` and `NO_COLOR` environment variables
+    /// override the settings
+    ///
+    /// By default, they do.
+    fn set_env_overrides(&mut self, yes: bool) {
+        self.config_mut().env_overrides = yes;
+    }
+
+    /// Characters which are part of words, besides the alphanumeric ones
+    ///
+    /// By default, none.
+    fn set_word_chars(&mut self, chars: Arc<str>) {
+        self.config_mut().word_chars = chars;
+    }
+
+    /// What the Tab key inserts when there is nothing to complete
+    ///
+    /// By default, nothing.
+    fn set_tab_insertion(&mut self, insertion: TabInsertion) {
+        self.config_mut().tab_insertion = insertion;
+    }
+
+    /// Terminal multiplexer passthrough on unix platform
+    ///
+    /// By default, [`Passthrough::Auto`].
+    fn set_passthrough(&mut self, passthrough: Passthrough) {
+        self.config_mut().passthrough = passthrough;
+    }
+
     /// Enable or disable bracketed paste on unix platform
     ///
     /// By default, it's enabled.
@@ -643,10 +2045,115 @@ pub trait Configurer {
         self.config_mut().enable_bracketed_paste = enabled;
     }
 
+    /// Enable or disable synchronized output (mode 2026) on unix platform
+    ///
+    /// By default, it's enabled.
+    fn enable_synchronized_output(&mut self, enabled: bool) {
+        self.config_mut().enable_synchronized_output = enabled;
+    }
+
+    /// Enable or disable focus reporting (mode 1004) on unix platform
+    ///
+    /// By default, it's disabled.
+    fn enable_focus_events(&mut self, enabled: bool) {
+        self.config_mut().enable_focus_events = enabled;
+    }
+
+    /// Batch external prints so that the prompt is redrawn at most once
+    /// every `interval_ms`.
+    ///
+    /// By default, no batching.
+    fn set_external_print_interval(&mut self, interval_ms: Option<u16>) {
+        self.config_mut().external_print_interval = interval_ms;
+    }
+
+    /// Bound the queue of messages sent by external printers to `capacity`
+    /// messages (at least one), `policy` telling what to do when it is full.
+    ///
+    /// By default, 1 message and [`QueuePolicy::Block`].
+    fn set_external_print_queue(&mut self, capacity: usize, policy: QueuePolicy) {
+        let config = self.config_mut();
+        config.external_print_capacity = capacity.max(1);
+        config.external_print_policy = policy;
+    }
+
+    /// Choose how consecutive edits are coalesced into a single undo step.
+    ///
+    /// By default, [`UndoGrouping::Word`].
+    fn set_undo_grouping(&mut self, grouping: UndoGrouping) {
+        self.config_mut().undo_grouping = grouping;
+    }
+
     /// Enable or disable signals in termios
     ///
     /// By default, it's disabled.
     fn set_enable_signals(&mut self, enable_signals: bool) {
         self.config_mut().set_enable_signals(enable_signals);
     }
+
+    /// Cursor shape used in the specified input `mode`
+    /// ([`InputMode::Insert`] for emacs mode).
+    ///
+    /// By default, the terminal default shape is kept. Otherwise, once the
+    /// line is read, the cursor is reset to [`CursorShape::Default`] (the
+    /// shape it had before cannot be queried).
+    fn set_cursor_shape(&mut self, mode: InputMode, shape: CursorShape) {
+        self.config_mut().cursor_shapes[cursor_shape_index(mode)] = shape;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BellStyle, CompletionType, Config, EditMode, HistoryDuplicates, Preset};
+
+    #[test]
+    fn presets() {
+        let bash = Config::preset(Preset::Bash);
+        assert_eq!(CompletionType::List, bash.completion_type());
+        assert_eq!(HistoryDuplicates::AlwaysAdd, bash.history_duplicates());
+        assert!(bash.auto_add_history());
+
+        let zsh = Config::preset(Preset::Zsh);
+        assert_eq!(CompletionType::Circular, zsh.completion_type());
+        assert_eq!(
+            HistoryDuplicates::IgnoreConsecutive,
+            zsh.history_duplicates()
+        );
+        assert!(zsh.history_ignore_space());
+
+        let fish = Config::builder()
+            .edit_mode(EditMode::Vi)
+            .preset(Preset::Fish)
+            .max_history_size(10)
+            .unwrap()
+            .build();
+        assert_eq!(EditMode::Emacs, fish.edit_mode());
+        assert!(fish.completion_show_all_if_ambiguous());
+        assert_eq!(BellStyle::None, fish.bell_style());
+        assert_eq!(10, fish.max_history_size());
+    }
+
+    #[test]
+    #[should_panic(expected = "tab_stop must be at least 1")]
+    fn zero_tab_stop() {
+        let _ = Config::builder().tab_stop(0);
+    }
+
+    #[test]
+    fn word_chars() {
+        let chars = String::from("_-");
+        let config = Config::builder().word_chars(chars).build();
+        assert_eq!("_-", config.word_chars());
+        assert_eq!("", Config::default().word_chars());
+    }
+
+    #[test]
+    fn completion_prompts() {
+        let config = Config::builder()
+            .completion_query(format!("Show {{}} of {}?", 42))
+            .completion_more_prompt("(more)")
+            .build();
+        assert_eq!("Show {} of 42?", config.completion_query());
+        assert_eq!("(more)", config.completion_more_prompt());
+    }
 }
@@ -0,0 +1,83 @@
+//! Environment variables overriding the settings chosen by the application:
+//!
+//! | Variable                    | Setting           | Values                              |
+//! |-----------------------------|-------------------|-------------------------------------|
+//! | `RUSTYLINE_EDIT_MODE`       | `edit_mode`       | `emacs`, `vi`                       |
+//! | `RUSTYLINE_COMPLETION_TYPE` | `completion_type` | `circular`, `list`                  |
+//! | `RUSTYLINE_BELL_STYLE`      | `bell_style`      | `audible`, `none`, `visible`        |
+//! | `RUSTYLINE_COLOR`           | `color_mode`      | `enabled`, `forced`, `disabled`     |
+//! | `NO_COLOR`                  | `color_mode`      | any non-empty value disables colors |
+//!
+//! `RUSTYLINE_COLOR` takes precedence over `NO_COLOR`.
+//! Invalid values are ignored.
+use log::debug;
+
+use super::file::set_string;
+use super::{Builder, ColorMode, Configurer};
+
+/// Variables and the settings they override
+const OVERRIDES: [(&str, &str); 4] = [
+    ("RUSTYLINE_EDIT_MODE", "edit_mode"),
+    ("RUSTYLINE_COMPLETION_TYPE", "completion_type"),
+    ("RUSTYLINE_BELL_STYLE", "bell_style"),
+    ("RUSTYLINE_COLOR", "color_mode"),
+];
+
+/// Apply the overrides found by `var` (`std::env::var` except in tests)
+pub(super) fn apply<F: Fn(&str) -> Option<String>>(builder: &mut Builder, var: F) {
+    if var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        builder.set_color_mode(ColorMode::Disabled);
+    }
+    for (name, key) in OVERRIDES {
+        if let Some(value) = var(name) {
+            if let Err(msg) = set_string(builder, key, &value) {
+                debug!(target: "rustyline", "{name} ignored: {msg}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{BellStyle, Builder, ColorMode, CompletionType, EditMode};
+
+    fn apply(vars: &[(&str, &str)]) -> crate::Config {
+        let mut builder = Builder::new().bell_style(BellStyle::Visible);
+        super::apply(&mut builder, |name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| (*v).to_owned())
+        });
+        builder.build()
+    }
+
+    #[test]
+    fn overrides() {
+        let config = apply(&[]);
+        assert_eq!(EditMode::Emacs, config.edit_mode());
+        assert_eq!(BellStyle::Visible, config.bell_style());
+        assert_eq!(ColorMode::Enabled, config.color_mode());
+
+        let config = apply(&[
+            ("RUSTYLINE_EDIT_MODE", "vi"),
+            ("RUSTYLINE_COMPLETION_TYPE", "list"),
+            ("RUSTYLINE_BELL_STYLE", "none"),
+        ]);
+        assert_eq!(EditMode::Vi, config.edit_mode());
+        assert_eq!(Some(500), config.keyseq_timeout());
+        assert_eq!(CompletionType::List, config.completion_type());
+        assert_eq!(BellStyle::None, config.bell_style());
+
+        assert_eq!(
+            ColorMode::Disabled,
+            apply(&[("NO_COLOR", "1")]).color_mode()
+        );
+        assert_eq!(ColorMode::Enabled, apply(&[("NO_COLOR", "")]).color_mode());
+        let config = apply(&[("NO_COLOR", "1"), ("RUSTYLINE_COLOR", "forced")]);
+        assert_eq!(ColorMode::Forced, config.color_mode());
+        // invalid values are ignored
+        let config = apply(&[("RUSTYLINE_EDIT_MODE", "ed"), ("RUSTYLINE_BELL_STYLE", "")]);
+        assert_eq!(EditMode::Emacs, config.edit_mode());
+        assert_eq!(BellStyle::Visible, config.bell_style());
+    }
+}
@@ -0,0 +1,1066 @@
+//! Configuration file describing the settings and the key bindings: `key =
+//! value` lines, grouped by `[table]` headers, whose value is a quoted
+//! string, a boolean or an integer. The syntax looks like TOML but is not:
+//! arrays, inline tables, multi-line strings, floats and dotted keys are
+//! rejected.
+//!
+//! ```ini
+//! edit_mode = "vi"
+//! completion_type = "list"
+//! bell_style = "none"
+//! color_mode = "disabled"
+//!
+//! [bindings]
+//! "C-x C-e" = "clear-screen"
+//!
+//! [bindings.vi-insert]
+//! "C-p" = "previous-history"
+//!
+//! # settings for the Linux console (`TERM=linux`)
+//! [profile.linux]
+//! color_mode = "disabled"
+//!
+//! # and for any `TERM` starting with `xterm-`
+//! [profile."xterm-*"]
+//! enable_bracketed_paste = true
+//! ```
+use std::io;
+use std::path::Path;
+
+use super::{
+    Behavior, BellStyle, Builder, ClipboardBackend, ClipboardMode, ColorMode, CompletionType,
+    Configurer, CursorShape, EditMode, Passthrough, PromptOverflow, QueuePolicy, TabInsertion,
+    UndoGrouping,
+};
+#[cfg(feature = "serde")]
+use super::{Config, HistoryDuplicates};
+use crate::layout::{AmbiguousWidth, GraphemeClusterMode};
+use crate::{InputMode, Result};
+use rustyline_core::normalize::NormalizationForm;
+
+/// Value of a key
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Value {
+    String(String),
+    Bool(bool),
+    Integer(i64),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "a string",
+            Self::Bool(_) => "a boolean",
+            Self::Integer(_) => "an integer",
+        }
+    }
+}
+
+/// `key = value` line
+#[derive(Debug)]
+struct Entry {
+    /// Header of the enclosing table (empty at top level)
+    table: String,
+    key: String,
+    value: Value,
+    /// Line number (from 1)
+    line: usize,
+}
+
+/// Parsed configuration file
+#[derive(Debug)]
+pub(crate) struct ConfigFile {
+    entries: Vec<Entry>,
+}
+
+/// Error at the line `.0`
+type Error = (usize, String);
+
+impl ConfigFile {
+    /// Read and parse the file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        Self::parse(&text).map_err(|(line, msg)| invalid(path, line, &msg))
+    }
+
+    fn parse(text: &str) -> std::result::Result<Self, Error> {
+        let mut entries = Vec::new();
+        let mut table = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let err = |msg: &str| (line_number, msg.to_owned());
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let (header, rest) = header
+                    .split_once(']')
+                    .ok_or_else(|| err("unterminated table header"))?;
+                if !is_comment(rest) {
+                    return Err(err("unexpected characters after the table header"));
+                }
+                table = parse_table(header).ok_or_else(|| err("invalid table name"))?;
+                continue;
+            }
+            let (key, rest) = parse_key(line).map_err(|msg| err(&msg))?;
+            let rest = rest
+                .trim_start()
+                .strip_prefix('=')
+                .ok_or_else(|| err("expected `=` after the key"))?;
+            let (value, rest) = parse_value(rest.trim_start()).map_err(|msg| err(&msg))?;
+            if !is_comment(rest) {
+                return Err(err("unexpected characters after the value"));
+            }
+            if entries
+                .iter()
+                .any(|e: &Entry| e.table == table && e.key == key)
+            {
+                return Err(err(&format!("duplicate key `{key}`")));
+            }
+            entries.push(Entry {
+                table: table.clone(),
+                key,
+                value,
+                line: line_number,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Apply the top level settings to `builder`, then the ones of the
+    /// `[profile.<name>]` tables matching the `TERM` environment variable
+    pub fn configure(&self, path: &Path, builder: &mut Builder) -> Result<()> {
+        let term = std::env::var("TERM").ok();
+        self.apply(builder, term.as_deref())
+            .map_err(|(line, msg)| invalid(path, line, &msg))
+    }
+
+    fn apply(&self, builder: &mut Builder, term: Option<&str>) -> std::result::Result<(), Error> {
+        for entry in &self.entries {
+            if !entry.table.is_empty()
+                && !is_bindings_table(&entry.table)
+                && profile(&entry.table).is_none()
+            {
+                return Err((entry.line, format!("unknown table `{}`", entry.table)));
+            }
+        }
+        self.apply_table(builder, "")?;
+        // profiles in the order of the file: the last one wins
+        let mut applied: Vec<&str> = Vec::new();
+        for entry in &self.entries {
+            let table = entry.table.as_str();
+            let Some(name) = profile(table) else {
+                continue;
+            };
+            if !applied.contains(&table) && term.is_some_and(|term| matches_term(name, term)) {
+                applied.push(table);
+                self.apply_table(builder, table)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the settings of `table`
+    fn apply_table(&self, builder: &mut Builder, table: &str) -> std::result::Result<(), Error> {
+        let settings = self.entries.iter().filter(|e| e.table == table);
+        // the edit mode resets the key sequence timeout
+        let (edit_mode, others): (Vec<_>, Vec<_>) = settings.partition(|e| e.key == "edit_mode");
+        for entry in edit_mode.into_iter().chain(others) {
+            set(builder, entry).map_err(|msg| (entry.line, msg))?;
+        }
+        Ok(())
+    }
+
+    /// Key bindings of the `[bindings]` tables, restricted to an input mode
+    /// for `[bindings.emacs]`, `[bindings.vi-insert]` and
+    /// `[bindings.vi-command]`
+    #[cfg(feature = "custom-bindings")]
+    pub fn bindings(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<(Option<crate::BindingMode>, crate::Event, crate::Cmd)>> {
+        use crate::BindingMode;
+
+        let mut bindings = Vec::new();
+        for entry in &self.entries {
+            let err = |msg: String| invalid(path, entry.line, &msg);
+            let mode = match entry.table.as_str() {
+                "bindings" => None,
+                "bindings.emacs" => Some(BindingMode::Emacs),
+                "bindings.vi-insert" => Some(BindingMode::ViInsert),
+                "bindings.vi-command" => Some(BindingMode::ViCommand),
+                _ => continue,
+            };
+            let keys = entry
+                .key
+                .split_whitespace()
+                .map(|spec| {
+                    crate::KeyEvent::parse(spec).ok_or_else(|| err(format!("invalid key `{spec}`")))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if keys.is_empty() {
+                return Err(err("empty key sequence".to_owned()));
+            }
+            let Value::String(ref name) = entry.value else {
+                return Err(err(format!(
+                    "expected a command name, found {}",
+                    entry.value.type_name()
+                )));
+            };
+            let cmd = crate::Cmd::from_name(name)
+                .ok_or_else(|| err(format!("unknown command `{name}`")))?;
+            bindings.push((mode, crate::Event::KeySeq(keys), cmd));
+        }
+        Ok(bindings)
+    }
+}
+
+fn is_bindings_table(table: &str) -> bool {
+    matches!(
+        table,
+        "bindings" | "bindings.emacs" | "bindings.vi-insert" | "bindings.vi-command"
+    )
+}
+
+/// Terminal name (or pattern) of a `[profile.<name>]` table
+fn profile(table: &str) -> Option<&str> {
+    table
+        .strip_prefix("profile.")
+        .filter(|name| !name.is_empty())
+}
+
+/// Whether the profile `name` applies to `term`: same name, or prefix when
+/// `name` ends with `*`
+fn matches_term(name: &str, term: &str) -> bool {
+    match name.strip_suffix('*') {
+        Some(prefix) => term.starts_with(prefix),
+        None => name == term,
+    }
+}
+
+/// Apply the setting `key` from a string (e.g. an environment variable)
+pub(super) fn set_string(
+    builder: &mut Builder,
+    key: &str,
+    value: &str,
+) -> std::result::Result<(), String> {
+    set_value(builder, key, Value::String(value.to_owned()))
+}
+
+/// Apply the setting `key`
+pub(crate) fn set_value(
+    builder: &mut Builder,
+    key: &str,
+    value: Value,
+) -> std::result::Result<(), String> {
+    let entry = Entry {
+        table: String::new(),
+        key: key.to_owned(),
+        value,
+        line: 0,
+    };
+    set(builder, &entry)
+}
+
+/// Settings of `config` written in a configuration file (the optional ones
+/// omitted when unset), applied back by [`set_value`]. Fails on a value
+/// which cannot be written.
+#[cfg(feature = "serde")]
+#[expect(clippy::cast_possible_wrap)]
+pub(crate) fn settings(config: &Config) -> std::result::Result<Vec<(&'static str, Value)>, String> {
+    use Value::{Bool, Integer};
+
+    let name = |name: &str| Value::String(name.to_owned());
+    let mut settings = vec![
+        (
+            "max_history_size",
+            Integer(config.max_history_size() as i64),
+        ),
+        (
+            "history_ignore_dups",
+            Bool(config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive),
+        ),
+        ("history_ignore_space", Bool(config.history_ignore_space())),
+        (
+            "completion_type",
+            name(match config.completion_type() {
+                CompletionType::Circular => "circular",
+                CompletionType::List => "list",
+                #[cfg(all(unix, feature = "with-fuzzy"))]
+                CompletionType::Fuzzy => "fuzzy",
+            }),
+        ),
+        (
+            "completion_show_all_if_ambiguous",
+            Bool(config.completion_show_all_if_ambiguous()),
+        ),
+        (
+            "completion_prompt_limit",
+            Integer(config.completion_prompt_limit() as i64),
+        ),
+        ("completion_query", name(config.completion_query())),
+        (
+            "completion_more_prompt",
+            name(config.completion_more_prompt()),
+        ),
+        (
+            "completion_column_padding",
+            Integer(config.completion_column_padding().into()),
+        ),
+        (
+            "completion_horizontal",
+            Bool(config.completion_horizontal()),
+        ),
+        (
+            "edit_mode",
+            name(match config.edit_mode() {
+                EditMode::Emacs => "emacs",
+                EditMode::Vi => "vi",
+            }),
+        ),
+        ("auto_add_history", Bool(config.auto_add_history())),
+        (
+            "bell_style",
+            name(match config.bell_style() {
+                BellStyle::Audible => "audible",
+                BellStyle::None => "none",
+                BellStyle::Visible => "visible",
+            }),
+        ),
+        (
+            "color_mode",
+            name(match config.color_mode() {
+                ColorMode::Enabled => "enabled",
+                ColorMode::Forced => "forced",
+                ColorMode::Disabled => "disabled",
+            }),
+        ),
+        (
+            "grapheme_cluster_mode",
+            name(if config.detect_grapheme_cluster_mode() {
+                "auto"
+            } else {
+                match config.grapheme_cluster_mode() {
+                    GraphemeClusterMode::Unicode => "unicode",
+                    GraphemeClusterMode::WcWidth => "wcwidth",
+                    GraphemeClusterMode::NoZwj => "no_zwj",
+                }
+            }),
+        ),
+        (
+            "ambiguous_width",
+            name(match config.ambiguous_width() {
+                None => "auto",
+                Some(AmbiguousWidth::Narrow) => "narrow",
+                Some(AmbiguousWidth::Wide) => "wide",
+            }),
+        ),
+        (
+            "behavior",
+            name(match config.behavior() {
+                Behavior::Stdio => "stdio",
+                Behavior::PreferTerm => "prefer_term",
+            }),
+        ),
+        ("tab_stop", Integer(config.tab_stop().into())),
+        (
+            "check_cursor_position",
+            Bool(config.check_cursor_position()),
+        ),
+        ("indent_size", Integer(config.indent_size().into())),
+        ("auto_indent", Bool(config.auto_indent())),
+        ("horizontal_scroll", Bool(config.horizontal_scroll())),
+        ("bidi", Bool(config.bidi())),
+        (
+            "normalization",
+            name(match config.normalization() {
+                None => "none",
+                Some(NormalizationForm::Nfc) => "nfc",
+                Some(NormalizationForm::Nfd) => "nfd",
+            }),
+        ),
+        ("line_numbers", Bool(config.line_numbers())),
+        ("visual_line_movement", Bool(config.visual_line_movement())),
+        (
+            "clipboard",
+            name(match config.clipboard() {
+                ClipboardMode::Disabled => "disabled",
+                ClipboardMode::Copy => "copy",
+                ClipboardMode::CopyPaste => "copy_paste",
+            }),
+        ),
+        ("kill_ring_size", Integer(config.kill_ring_size() as i64)),
+        (
+            "clipboard_backend",
+            name(match config.clipboard_backend() {
+                ClipboardBackend::Terminal => "terminal",
+                #[cfg(feature = "with-arboard")]
+                ClipboardBackend::Native => "native",
+            }),
+        ),
+        ("alternate_screen", Bool(config.alternate_screen())),
+        (
+            "prompt_overflow",
+            name(match config.prompt_overflow() {
+                PromptOverflow::Wrap => "wrap",
+                PromptOverflow::TruncateLeft => "truncate_left",
+                PromptOverflow::Shrink => "shrink",
+            }),
+        ),
+        ("window_title", Bool(config.window_title())),
+        ("probe_terminal", Bool(config.probe_terminal())),
+        ("coalesce_refresh", Bool(config.coalesce_refresh())),
+        ("render_stats", Bool(config.render_stats())),
+        ("env_overrides", Bool(config.env_overrides())),
+        ("word_chars", name(config.word_chars())),
+        (
+            "tab_insertion",
+            match config.tab_insertion() {
+                TabInsertion::Nothing => name("nothing"),
+                TabInsertion::Tab => name("tab"),
+                TabInsertion::Spaces(n) => Integer(n.into()),
+                TabInsertion::SmartIndent => name("smart_indent"),
+            },
+        ),
+        (
+            "passthrough",
+            name(match config.passthrough() {
+                Passthrough::Auto => "auto",
+                Passthrough::Disabled => "disabled",
+                Passthrough::Tmux => "tmux",
+                Passthrough::Screen => "screen",
+            }),
+        ),
+        (
+            "enable_bracketed_paste",
+            Bool(config.enable_bracketed_paste()),
+        ),
+        (
+            "enable_synchronized_output",
+            Bool(config.enable_synchronized_output()),
+        ),
+        ("enable_focus_events", Bool(config.enable_focus_events())),
+        (
+            "external_print_capacity",
+            Integer(config.external_print_capacity() as i64),
+        ),
+        (
+            "external_print_policy",
+            name(match config.external_print_policy() {
+                QueuePolicy::Block => "block",
+                QueuePolicy::DropOldest => "drop_oldest",
+                QueuePolicy::DropNewest => "drop_newest",
+            }),
+        ),
+        ("enable_signals", Bool(config.enable_signals())),
+        (
+            "undo_grouping",
+            match config.undo_grouping() {
+                UndoGrouping::Keystroke => name("keystroke"),
+                UndoGrouping::Word => name("word"),
+                UndoGrouping::Command => name("command"),
+                UndoGrouping::Time(ms) => Integer(ms.into()),
+                grouping => return Err(format!("`undo_grouping`: {grouping:?} is unsupported")),
+            },
+        ),
+    ];
+    for (key, mode) in CURSOR_SHAPES {
+        let shape = match config.cursor_shape(mode) {
+            CursorShape::Default => "default",
+            CursorShape::BlinkingBlock => "blinking_block",
+            CursorShape::SteadyBlock => "steady_block",
+            CursorShape::BlinkingUnderline => "blinking_underline",
+            CursorShape::SteadyUnderline => "steady_underline",
+            CursorShape::BlinkingBar => "blinking_bar",
+            CursorShape::SteadyBar => "steady_bar",
+        };
+        settings.push((key, name(shape)));
+    }
+    for (key, value) in [
+        ("completion_page_rows", config.completion_page_rows()),
+        ("completion_max_columns", config.completion_max_columns()),
+        ("keyseq_timeout", config.keyseq_timeout()),
+        ("mapping_timeout", config.mapping_timeout()),
+        ("external_print_interval", config.external_print_interval()),
+    ] {
+        if let Some(n) = value {
+            settings.push((key, Integer(n.into())));
+        }
+    }
+    if let Some(lines) = config.paste_fold_lines() {
+        settings.push(("paste_fold_lines", Integer(lines as i64)));
+    }
+    if let Some(baud_rate) = config.serial_console() {
+        settings.push(("serial_console", Integer(baud_rate.into())));
+    }
+    if let Some((cols, rows)) = config.headless() {
+        settings.push(("headless", name(&format!("{cols}x{rows}"))));
+    }
+    Ok(settings)
+}
+
+/// Setting of the cursor shape of each input mode
+const CURSOR_SHAPES: [(&str, InputMode); 3] = [
+    ("cursor_shape_command", InputMode::Command),
+    ("cursor_shape_insert", InputMode::Insert),
+    ("cursor_shape_replace", InputMode::Replace),
+];
+
+/// Apply the setting of `entry`
+fn set(builder: &mut Builder, entry: &Entry) -> std::result::Result<(), String> {
+    match entry.key.as_str() {
+        "edit_mode" => builder.set_edit_mode(match string(entry)? {
+            "emacs" => EditMode::Emacs,
+            "vi" => EditMode::Vi,
+            s => return Err(unexpected(entry, s, "`emacs` or `vi`")),
+        }),
+        "completion_type" => builder.set_completion_type(match string(entry)? {
+            "circular" => CompletionType::Circular,
+            "list" => CompletionType::List,
+            #[cfg(all(unix, feature = "with-fuzzy"))]
+            "fuzzy" => CompletionType::Fuzzy,
+            s => return Err(unexpected(entry, s, "`circular` or `list`")),
+        }),
+        "bell_style" => builder.set_bell_style(match string(entry)? {
+            "audible" => BellStyle::Audible,
+            "none" => BellStyle::None,
+            "visible" => BellStyle::Visible,
+            s => return Err(unexpected(entry, s, "`audible`, `none` or `visible`")),
+        }),
+        "color_mode" => builder.set_color_mode(match string(entry)? {
+            "enabled" => ColorMode::Enabled,
+            "forced" => ColorMode::Forced,
+            "disabled" => ColorMode::Disabled,
+            s => return Err(unexpected(entry, s, "`enabled`, `forced` or `disabled`")),
+        }),
+        "ambiguous_width" => builder.set_ambiguous_width(match string(entry)? {
+            "auto" => None,
+            "narrow" => Some(AmbiguousWidth::Narrow),
+            "wide" => Some(AmbiguousWidth::Wide),
+            s => return Err(unexpected(entry, s, "`auto`, `narrow` or `wide`")),
+        }),
+        "normalization" => builder.set_normalization(match string(entry)? {
+            "none" => None,
+            "nfc" => Some(NormalizationForm::Nfc),
+            "nfd" => Some(NormalizationForm::Nfd),
+            s => return Err(unexpected(entry, s, "`none`, `nfc` or `nfd`")),
+        }),
+        "max_history_size" => builder
+            .set_max_history_size(integer(entry)?)
+            .map_err(|e| e.to_string())?,
+        "history_ignore_dups" => builder
+            .set_history_ignore_dups(boolean(entry)?)
+            .map_err(|e| e.to_string())?,
+        "history_ignore_space" => builder.set_history_ignore_space(boolean(entry)?),
+        "auto_add_history" => builder.set_auto_add_history(boolean(entry)?),
+        "completion_prompt_limit" => builder.set_completion_prompt_limit(integer(entry)?),
+        "completion_show_all_if_ambiguous" => {
+            builder.set_completion_show_all_if_ambiguous(boolean(entry)?);
+        }
+        "completion_page_rows" => builder.set_completion_page_rows(Some(integer(entry)?)),
+        "completion_max_columns" => builder.set_completion_max_columns(Some(integer(entry)?)),
+        "completion_column_padding" => builder.set_completion_column_padding(integer(entry)?),
+        "completion_horizontal" => builder.set_completion_horizontal(boolean(entry)?),
+        "keyseq_timeout" => builder.set_keyseq_timeout(Some(integer(entry)?)),
+        "tab_stop" => match integer(entry)? {
+            0 => return Err("`tab_stop`: 0 is out of range".to_owned()),
+            n => builder.set_tab_stop(n),
+        },
+        "indent_size" => builder.set_indent_size(integer(entry)?),
+        "auto_indent" => builder.set_auto_indent(boolean(entry)?),
+        // number of spaces, or kind of insertion
+        "tab_insertion" => builder.set_tab_insertion(match entry.value {
+            Value::Integer(_) => TabInsertion::Spaces(integer(entry)?),
+            _ => match string(entry)? {
+                "nothing" => TabInsertion::Nothing,
+                "tab" => TabInsertion::Tab,
+                "smart_indent" => TabInsertion::SmartIndent,
+                s => return Err(unexpected(entry, s, "`nothing`, `tab` or `smart_indent`")),
+            },
+        }),
+        "enable_bracketed_paste" => builder.enable_bracketed_paste(boolean(entry)?),
+        "enable_synchronized_output" => builder.enable_synchronized_output(boolean(entry)?),
+        "completion_query" => builder.set_completion_query(string(entry)?.to_owned().into()),
+        "completion_more_prompt" => {
+            builder.set_completion_more_prompt(string(entry)?.to_owned().into())
+        }
+        "mapping_timeout" => builder.set_mapping_timeout(Some(integer(entry)?)),
+        "grapheme_cluster_mode" => match string(entry)? {
+            "auto" => builder.config_mut().detect_grapheme_cluster_mode = true,
+            s => builder.set_grapheme_cluster_mode(match s {
+                "unicode" => GraphemeClusterMode::Unicode,
+                "wcwidth" => GraphemeClusterMode::WcWidth,
+                "no_zwj" => GraphemeClusterMode::NoZwj,
+                s => {
+                    let expected = "`auto`, `unicode`, `wcwidth` or `no_zwj`";
+                    return Err(unexpected(entry, s, expected));
+                }
+            }),
+        },
+        "behavior" => builder.set_behavior(match string(entry)? {
+            "stdio" => Behavior::Stdio,
+            "prefer_term" => Behavior::PreferTerm,
+            s => return Err(unexpected(entry, s, "`stdio` or `prefer_term`")),
+        }),
+        "check_cursor_position" => builder.set_check_cursor_position(boolean(entry)?),
+        "horizontal_scroll" => builder.set_horizontal_scroll(boolean(entry)?),
+        "bidi" => builder.set_bidi(boolean(entry)?),
+        "line_numbers" => builder.set_line_numbers(boolean(entry)?),
+        "paste_fold_lines" => builder.set_paste_fold_lines(Some(integer(entry)?)),
+        "visual_line_movement" => builder.set_visual_line_movement(boolean(entry)?),
+        "clipboard" => builder.set_clipboard(match string(entry)? {
+            "disabled" => ClipboardMode::Disabled,
+            "copy" => ClipboardMode::Copy,
+            "copy_paste" => ClipboardMode::CopyPaste,
+            s => return Err(unexpected(entry, s, "`disabled`, `copy` or `copy_paste`")),
+        }),
+        "kill_ring_size" => builder.set_kill_ring_size(integer(entry)?),
+        "clipboard_backend" => builder.set_clipboard_backend(match string(entry)? {
+            "terminal" => ClipboardBackend::Terminal,
+            #[cfg(feature = "with-arboard")]
+            "native" => ClipboardBackend::Native,
+            s => return Err(unexpected(entry, s, "`terminal` or `native`")),
+        }),
+        "alternate_screen" => builder.set_alternate_screen(boolean(entry)?),
+        "serial_console" => builder.set_serial_console(Some(integer(entry)?)),
+        "prompt_overflow" => builder.set_prompt_overflow(match string(entry)? {
+            "wrap" => PromptOverflow::Wrap,
+            "truncate_left" => PromptOverflow::TruncateLeft,
+            "shrink" => PromptOverflow::Shrink,
+            s => return Err(unexpected(entry, s, "`wrap`, `truncate_left` or `shrink`")),
+        }),
+        "window_title" => builder.set_window_title(boolean(entry)?),
+        "probe_terminal" => builder.set_probe_terminal(boolean(entry)?),
+        "coalesce_refresh" => builder.set_coalesce_refresh(boolean(entry)?),
+        "render_stats" => builder.set_render_stats(boolean(entry)?),
+        // `{columns}x{rows}`
+        "headless" => {
+            let s = string(entry)?;
+            let size = s
+                .split_once('x')
+                .and_then(|(cols, rows)| Some((cols.parse().ok()?, rows.parse().ok()?)));
+            let Some(size) = size else {
+                return Err(unexpected(entry, s, "`{columns}x{rows}`"));
+            };
+            builder.set_headless(Some(size));
+        }
+        "env_overrides" => builder.set_env_overrides(boolean(entry)?),
+        "word_chars" => builder.set_word_chars(string(entry)?.into()),
+        "passthrough" => builder.set_passthrough(match string(entry)? {
+            "auto" => Passthrough::Auto,
+            "disabled" => Passthrough::Disabled,
+            "tmux" => Passthrough::Tmux,
+            "screen" => Passthrough::Screen,
+            s => {
+                return Err(unexpected(
+                    entry,
+                    s,
+                    "`auto`, `disabled`, `tmux` or `screen`",
+                ))
+            }
+        }),
+        "enable_focus_events" => builder.enable_focus_events(boolean(entry)?),
+        "external_print_interval" => builder.set_external_print_interval(Some(integer(entry)?)),
+        "external_print_capacity" => {
+            let policy = builder.config_mut().external_print_policy();
+            builder.set_external_print_queue(integer(entry)?, policy);
+        }
+        "external_print_policy" => {
+            let capacity = builder.config_mut().external_print_capacity();
+            builder.set_external_print_queue(
+                capacity,
+                match string(entry)? {
+                    "block" => QueuePolicy::Block,
+                    "drop_oldest" => QueuePolicy::DropOldest,
+                    "drop_newest" => QueuePolicy::DropNewest,
+                    s => {
+                        let expected = "`block`, `drop_oldest` or `drop_newest`";
+                        return Err(unexpected(entry, s, expected));
+                    }
+                },
+            );
+        }
+        "enable_signals" => builder.set_enable_signals(boolean(entry)?),
+        // milliseconds, or kind of grouping
+        "undo_grouping" => builder.set_undo_grouping(match entry.value {
+            Value::Integer(_) => UndoGrouping::Time(integer(entry)?),
+            _ => match string(entry)? {
+                "keystroke" => UndoGrouping::Keystroke,
+                "word" => UndoGrouping::Word,
+                "command" => UndoGrouping::Command,
+                s => return Err(unexpected(entry, s, "`keystroke`, `word` or `command`")),
+            },
+        }),
+        key => {
+            let Some(&(_, mode)) = CURSOR_SHAPES.iter().find(|(k, _)| *k == key) else {
+                return Err(format!("unknown setting `{key}`"));
+            };
+            builder.set_cursor_shape(
+                mode,
+                match string(entry)? {
+                    "default" => CursorShape::Default,
+                    "blinking_block" => CursorShape::BlinkingBlock,
+                    "steady_block" => CursorShape::SteadyBlock,
+                    "blinking_underline" => CursorShape::BlinkingUnderline,
+                    "steady_underline" => CursorShape::SteadyUnderline,
+                    "blinking_bar" => CursorShape::BlinkingBar,
+                    "steady_bar" => CursorShape::SteadyBar,
+                    s => return Err(unexpected(entry, s, "a cursor shape (`steady_bar`, ...)")),
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+fn string(entry: &Entry) -> std::result::Result<&str, String> {
+    match entry.value {
+        Value::String(ref s) => Ok(s),
+        ref v => Err(mismatch(entry, "a string", v)),
+    }
+}
+
+fn boolean(entry: &Entry) -> std::result::Result<bool, String> {
+    match entry.value {
+        Value::Bool(b) => Ok(b),
+        ref v => Err(mismatch(entry, "a boolean", v)),
+    }
+}
+
+fn integer<T: TryFrom<i64>>(entry: &Entry) -> std::result::Result<T, String> {
+    match entry.value {
+        Value::Integer(n) => {
+            T::try_from(n).map_err(|_| format!("`{}`: {n} is out of range", entry.key))
+        }
+        ref v => Err(mismatch(entry, "an integer", v)),
+    }
+}
+
+fn mismatch(entry: &Entry, expected: &str, found: &Value) -> String {
+    format!(
+        "`{}`: expected {expected}, found {}",
+        entry.key,
+        found.type_name()
+    )
+}
+
+fn unexpected(entry: &Entry, found: &str, expected: &str) -> String {
+    format!("`{}`: expected {expected}, found `{found}`", entry.key)
+}
+
+fn invalid(path: &Path, line: usize, msg: &str) -> crate::error::ReadlineError {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{line}: {msg}", path.display()),
+    )
+    .into()
+}
+
+fn is_comment(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+/// Dotted name of a table header (the text between the brackets), made of
+/// bare or quoted keys
+fn parse_table(header: &str) -> Option<String> {
+    let mut table = String::new();
+    let mut rest = header.trim();
+    loop {
+        let part = if rest.starts_with(['"', '\'']) {
+            let (part, tail) = parse_string(rest).ok()?;
+            rest = tail;
+            part
+        } else {
+            let end = rest.find('.').unwrap_or(rest.len());
+            let (part, tail) = rest.split_at(end);
+            rest = tail;
+            let part = part.trim_end();
+            if !is_bare_key(part) {
+                return None;
+            }
+            part.to_owned()
+        };
+        table.push_str(&part);
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Some(table);
+        }
+        rest = rest.strip_prefix('.')?.trim_start();
+        table.push('.');
+    }
+}
+
+/// Bare or quoted key, and the rest of the line
+fn parse_key(line: &str) -> std::result::Result<(String, &str), String> {
+    if line.starts_with(['"', '\'']) {
+        return parse_string(line);
+    }
+    let end = line.find(|c: char| c == '=' || c.is_whitespace());
+    let (key, rest) = line.split_at(end.unwrap_or(line.len()));
+    if !is_bare_key(key) {
+        return Err(format!("invalid key `{key}`"));
+    }
+    Ok((key.to_owned(), rest))
+}
+
+/// Value, and the rest of the line
+fn parse_value(s: &str) -> std::result::Result<(Value, &str), String> {
+    if s.starts_with(['"', '\'']) {
+        let (string, rest) = parse_string(s)?;
+        return Ok((Value::String(string), rest));
+    }
+    let end = s.find(|c: char| c == '#' || c.is_whitespace());
+    let (token, rest) = s.split_at(end.unwrap_or(s.len()));
+    let value = match token {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "" => return Err("missing value".to_owned()),
+        _ => Value::Integer(
+            token
+                .replace('_', "")
+                .parse()
+                .map_err(|_| format!("invalid value `{token}`"))?,
+        ),
+    };
+    Ok((value, rest))
+}
+
+/// Basic (`"`) or literal (`'`) string, and the rest of the line
+fn parse_string(s: &str) -> std::result::Result<(String, &str), String> {
+    let mut chars = s.char_indices();
+    let quote = chars.next().map(|(_, q)| q);
+    let mut string = String::new();
+    while let Some((i, c)) = chars.next() {
+        if Some(c) == quote {
+            return Ok((string, &s[i + 1..]));
+        } else if c != '\\' || quote == Some('\'') {
+            string.push(c);
+            continue;
+        }
+        let escaped = match chars.next().map(|(_, c)| c) {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('b') => '\x08',
+            Some('e') => '\x1b',
+            Some('f') => '\x0c',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some(u @ ('u' | 'U')) => {
+                let len = if u == 'u' { 4 } else { 8 };
+                let hex: String = chars.by_ref().take(len).map(|(_, c)| c).collect();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == len)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid escape `\\{u}{hex}`"))?
+            }
+            Some(c) => return Err(format!("invalid escape `\\{c}`")),
+            None => break,
+        };
+        string.push(escaped);
+    }
+    Err("unterminated string".to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfigFile, Value};
+    use crate::config::{BellStyle, Builder, ColorMode, CompletionType, EditMode, TabInsertion};
+    use crate::layout::AmbiguousWidth;
+    use rustyline_core::normalize::NormalizationForm;
+
+    fn configure(text: &str) -> Result<crate::Config, (usize, String)> {
+        let file = ConfigFile::parse(text)?;
+        let mut builder = Builder::new();
+        file.apply(&mut builder, None)?;
+        Ok(builder.build())
+    }
+
+    #[test]
+    fn parse() {
+        let file = ConfigFile::parse(
+            "# comment\n\
+             a = \"x\\\"y\\u00e9\" # trailing\n\
+             'b c' = 'C:\\path'\n\
+             [t . u]\n\
+             d = true\n\
+             e = -1_000\n",
+        )
+        .unwrap();
+        let entries: Vec<_> = file
+            .entries
+            .iter()
+            .map(|e| (e.table.as_str(), e.key.as_str(), &e.value, e.line))
+            .collect();
+        assert_eq!(
+            vec![
+                ("", "a", &Value::String("x\"y\u{e9}".to_owned()), 2),
+                ("", "b c", &Value::String("C:\\path".to_owned()), 3),
+                ("t.u", "d", &Value::Bool(true), 5),
+                ("t.u", "e", &Value::Integer(-1000), 6),
+            ],
+            entries
+        );
+        for (text, line, msg) in [
+            ("a = 1\n[t", 2, "unterminated table header"),
+            ("a 1", 1, "expected `=` after the key"),
+            ("a = \"x", 1, "unterminated string"),
+            ("a = 1 2", 1, "unexpected characters after the value"),
+            ("a = yes", 1, "invalid value `yes`"),
+            ("a = 1\na = 2", 2, "duplicate key `a`"),
+            ("[t.]", 1, "invalid table name"),
+            ("[t u]", 1, "invalid table name"),
+        ] {
+            assert_eq!((line, msg.to_owned()), ConfigFile::parse(text).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn settings() {
+        let config = configure(
+            "keyseq_timeout = 100\n\
+             edit_mode = \"vi\"\n\
+             completion_type = \"list\"\n\
+             bell_style = \"none\"\n\
+             color_mode = \"disabled\"\n\
+             ambiguous_width = \"wide\"\n\
+             normalization = \"nfc\"\n\
+             max_history_size = 1000\n\
+             tab_stop = 4\n\
+             tab_insertion = 4\n\
+             [bindings]\n\
+             \"C-x\" = \"undo\"\n",
+        )
+        .unwrap();
+        assert_eq!(EditMode::Vi, config.edit_mode());
+        assert_eq!(Some(100), config.keyseq_timeout());
+        assert_eq!(CompletionType::List, config.completion_type());
+        assert_eq!(BellStyle::None, config.bell_style());
+        assert_eq!(ColorMode::Disabled, config.color_mode());
+        assert_eq!(Some(AmbiguousWidth::Wide), config.ambiguous_width());
+        assert_eq!(Some(NormalizationForm::Nfc), config.normalization());
+        assert_eq!(1000, config.max_history_size());
+        assert_eq!(4, config.tab_stop());
+        assert_eq!(TabInsertion::Spaces(4), config.tab_insertion());
+
+        for (text, line, msg) in [
+            (
+                "edit_mode = \"ed\"",
+                1,
+                "`edit_mode`: expected `emacs` or `vi`, found `ed`",
+            ),
+            ("tab_stop = 256", 1, "`tab_stop`: 256 is out of range"),
+            ("\ntab_stop = 0", 2, "`tab_stop`: 0 is out of range"),
+            (
+                "headless = \"80\"",
+                1,
+                "`headless`: expected `{columns}x{rows}`, found `80`",
+            ),
+            (
+                "auto_add_history = 1",
+                1,
+                "`auto_add_history`: expected a boolean, found an integer",
+            ),
+            ("\n\nfoo = 1", 3, "unknown setting `foo`"),
+            ("[colors]\nprompt = 1", 2, "unknown table `colors`"),
+            ("[profile]\nprompt = 1", 2, "unknown table `profile`"),
+        ] {
+            assert_eq!((line, msg.to_owned()), configure(text).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn profiles() {
+        let file = ConfigFile::parse(
+            "bell_style = \"visible\"\n\
+             [profile.linux]\n\
+             color_mode = \"disabled\"\n\
+             enable_bracketed_paste = false\n\
+             [profile.\"xterm-*\"]\n\
+             bell_style = \"none\"\n\
+             [profile.xterm-kitty]\n\
+             bell_style = \"audible\"\n",
+        )
+        .unwrap();
+        let for_term = |term| {
+            let mut builder = Builder::new();
+            file.apply(&mut builder, term).unwrap();
+            builder.build()
+        };
+        let config = for_term(None);
+        assert_eq!(BellStyle::Visible, config.bell_style());
+        assert_eq!(ColorMode::Enabled, config.color_mode());
+        let config = for_term(Some("linux"));
+        assert_eq!(BellStyle::Visible, config.bell_style());
+        assert_eq!(ColorMode::Disabled, config.color_mode());
+        assert!(!config.enable_bracketed_paste());
+        let config = for_term(Some("xterm-256color"));
+        assert_eq!(BellStyle::None, config.bell_style());
+        assert!(config.enable_bracketed_paste());
+        // the last matching profile wins
+        assert_eq!(
+            BellStyle::Audible,
+            for_term(Some("xterm-kitty")).bell_style()
+        );
+        assert_eq!(BellStyle::Visible, for_term(Some("xterm")).bell_style());
+    }
+
+    #[test]
+    #[cfg(feature = "custom-bindings")]
+    fn bindings() {
+        use crate::{BindingMode, Cmd, Event, KeyEvent, Movement};
+        use std::path::Path;
+
+        let path = Path::new("rustyline.toml");
+        let file = ConfigFile::parse(
+            "[bindings]\n\
+             \"C-x C-u\" = \"undo\"\n\
+             [bindings.vi-insert]\n\
+             \"M-b\" = \"backward-word\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                (
+                    None,
+                    Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('U')]),
+                    Cmd::Undo(1)
+                ),
+                (
+                    Some(BindingMode::ViInsert),
+                    Event::from(KeyEvent::alt('b')),
+                    Cmd::Move(Movement::BackwardWord(1, crate::Word::Emacs))
+                ),
+            ],
+            file.bindings(path).unwrap()
+        );
+        for (text, msg) in [
+            (
+                "[bindings]\n\"C-x Hyper-y\" = \"undo\"",
+                "rustyline.toml:2: invalid key `Hyper-y`",
+            ),
+            (
+                "[bindings]\n\"C-x\" = \"fly\"",
+                "rustyline.toml:2: unknown command `fly`",
+            ),
+            (
+                "[bindings]\n\"C-x\" = true",
+                "rustyline.toml:2: expected a command name, found a boolean",
+            ),
+        ] {
+            let file = ConfigFile::parse(text).unwrap();
+            assert_eq!(msg, file.bindings(path).unwrap_err().to_string());
+        }
+    }
+}
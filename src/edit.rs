@@ -1,46 +1,194 @@
 //! Command processor
 
 use log::debug;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use unicode_segmentation::UnicodeSegmentation;
 
 use super::{Context, Helper, Result};
+use crate::config::{PromptOverflow, TabInsertion};
 use crate::error::{ReadlineError, Signal};
-use crate::highlight::{CmdKind, Highlighter};
+use crate::highlight::{CmdKind, Highlighter, PromptMode};
 use crate::hint::Hint;
 use crate::history::SearchDirection;
-use crate::keymap::{Anchor, At, CharSearch, Cmd, Movement, RepeatCount, Word};
+use crate::keymap::{
+    Anchor, At, BlockOp, CharSearch, Cmd, CursorOp, Movement, RepeatCount, SearchOffset, ViSearch,
+    Word,
+};
 use crate::keymap::{InputState, Invoke, Refresher};
-use crate::layout::{cwidh, Layout, Position};
+use crate::kill_ring::Mode;
+use crate::layout::{cwidh, Layout, Position, Unit};
 use crate::line_buffer::{
     ChangeListener, DeleteListener, Direction, LineBuffer, NoListener, WordAction, MAX_LINE,
 };
-use crate::tty::{Renderer, Term, Terminal};
-use crate::undo::Changeset;
-use crate::validate::{ValidationContext, ValidationResult};
+use crate::stats::RenderCounters;
+use crate::tty::{truncate_left, Renderer, Term, Terminal};
+use crate::undo::{Changeset, UndoTree};
+use crate::validate::{Continuation, ValidationContext, ValidationResult, Validator};
 use crate::KillRing;
+use rustyline_core::bidi;
+use rustyline_core::engine::UndoAndKill;
+use rustyline_core::normalize::{self, NormalizationForm};
+
+/// Milliseconds without keystroke before validating the input while typing
+const LIVE_VALIDATION_DELAY: u16 = 150;
+/// Lines highlighted above and below the visible ones when the input is
+/// taller than the terminal (context for multi-line constructs)
+const HIGHLIGHT_MARGIN: usize = 100;
 
 /// Represent the state during line editing.
 /// Implement rendering.
 pub struct State<'out, 'prompt, H: Helper> {
     pub out: &'out mut <Terminal as Term>::Writer,
-    prompt: &'prompt str,  // Prompt to display (rl_prompt)
-    prompt_size: Position, // Prompt Unicode/visible width and height
-    pub line: LineBuffer,  // Edited line buffer
+    prompt: &'prompt str,          // Prompt to display (rl_prompt)
+    mode_prompt: Option<String>,   // Prompt with mode indicator
+    fitted_prompt: Option<String>, // Prompt shortened to the terminal width
+    prompt_size: Position,         // Prompt Unicode/visible width and height
+    pub line: LineBuffer,          // Edited line buffer
     pub layout: Layout,
-    saved_line_for_history: LineBuffer, // Current edited line before history browsing
+    saved_entries: HashMap<usize, SavedEntry>, // history entries edited then left
     byte_buffer: [u8; 4],
-    pub changes: Changeset, // changes to line, for undo/redo
+    pub changes: Changes, // changes to line, for undo/redo
     pub helper: Option<&'out H>,
-    pub ctx: Context<'out>,          // Give access to history for `hinter`
-    pub hint: Option<Box<dyn Hint>>, // last hint displayed
-    pub highlight_char: bool,        // `true` if a char has been highlighted
+    pub ctx: Context<'out>,             // Give access to history for `hinter`
+    pub hint: Option<Box<dyn Hint>>,    // last hint displayed
+    pub highlight_char: bool,           // `true` if a char has been highlighted
+    search_match: Option<Range<usize>>, // text matched by the last vi search
+    overwritten: Vec<(Range<usize>, Option<String>)>, // vi replace mode: original text
+    horizontal_scroll: Option<Range<usize>>, // visible part of the line when scrolling
+    block_anchor: Option<usize>,        // vi visual block: selection start
+    block_insert: Option<BlockInsert>,  // vi visual block: pending insert
+    line_numbers: bool,                 // line number gutter for multi-line input
+    line_prompts: HashMap<usize, String>, // continuation prompts chosen by the validator
+    vertical_scroll: Option<Range<usize>>, // visible lines when the input is too tall
+    cursors: Vec<usize>,                // multi-cursor editing: extra cursors
+    preedit: String,                    // IME composition displayed at the cursor
+    bidi: bool,                         // right-to-left text reordered for display
+    normalization: Option<NormalizationForm>, // normalization of the inserted text
+    prompt_overflow: PromptOverflow,    // prompt wider than the terminal
+    coalesce: bool,                     // input burst: refreshes are postponed
+    dirty: bool,                        // a refresh has been postponed
+    print_interval: Option<u16>,        // minimal delay between external prints
+    last_print: Option<Instant>,        // last external print
+    queued_print: String,               // external messages not yet printed to stdout
+    queued_eprint: String,              // external messages not yet printed to stderr
+    region: String,                     // lines displayed above the prompt
+    pending_validation: Option<(Cmd, String)>, // accept command and validated input
+    resolved_validation: Option<ValidationResult>, // result of an async validation
+    validation_msg: bool,               // a validation message is displayed below the input
+    validated: bool,                    // the live validation indicator matches the input
+    render_counters: Option<Arc<RenderCounters>>, // rendering work, when counted
+}
+
+/// In-progress edits of a history entry (or of the current line) left while
+/// browsing history
+struct SavedEntry {
+    line: String,
+    pos: usize,
+    changes: Changeset,
+}
+
+/// Changes to the line: recorded for undo/redo, and tracked to keep the
+/// folded pastes in place
+#[derive(Default)]
+pub struct Changes {
+    changeset: Changeset,
+    folds: Vec<(usize, String)>, // folded pastes: offset and text
+    fold_next: bool,             // whether the next inserted text is folded
+}
+
+impl Changes {
+    /// Text from `idx` to `idx + removed` replaced by `inserted` bytes:
+    /// folds after it are shifted, folds edited are expanded.
+    fn shift_folds(&mut self, idx: usize, removed: usize, inserted: usize) {
+        self.folds.retain_mut(|(start, text)| {
+            if idx + removed <= *start {
+                *start = *start + inserted - removed;
+                true
+            } else {
+                idx >= *start + text.len()
+            }
+        });
+    }
+
+    /// Fold `text` inserted at `idx` when requested
+    fn insert_fold(&mut self, idx: usize, text: &str) -> bool {
+        if !std::mem::take(&mut self.fold_next) {
+            return false;
+        }
+        let pos = self.folds.partition_point(|(start, _)| *start < idx);
+        self.folds.insert(pos, (idx, text.to_owned()));
+        true
+    }
+}
+
+impl Deref for Changes {
+    type Target = Changeset;
+
+    fn deref(&self) -> &Changeset {
+        &self.changeset
+    }
+}
+
+impl DerefMut for Changes {
+    fn deref_mut(&mut self) -> &mut Changeset {
+        &mut self.changeset
+    }
+}
+
+impl DeleteListener for Changes {
+    fn start_killing(&mut self) {
+        self.changeset.start_killing();
+    }
+
+    fn delete(&mut self, idx: usize, string: &str, _: Direction) {
+        self.changeset.delete(idx, string);
+        self.shift_folds(idx, string.len(), 0);
+    }
+
+    fn stop_killing(&mut self) {
+        self.changeset.stop_killing();
+    }
+}
+
+impl ChangeListener for Changes {
+    fn insert_char(&mut self, idx: usize, c: char) {
+        self.changeset.insert(idx, c);
+        self.shift_folds(idx, 0, c.len_utf8());
+    }
+
+    fn insert_str(&mut self, idx: usize, string: &str) {
+        self.changeset.insert_str(idx, string);
+        self.shift_folds(idx, 0, string.len());
+        self.insert_fold(idx, string);
+    }
+
+    fn replace(&mut self, idx: usize, old: &str, new: &str) {
+        self.changeset.replace(idx, old, new);
+        self.shift_folds(idx, old.len(), new.len());
+        self.insert_fold(idx, new);
+    }
+}
+
+/// Text inserted on the first line of a vi visual block, to be repeated on
+/// the other lines when leaving insert mode
+struct BlockInsert {
+    start: usize,        // where the text is inserted on the first line
+    len: usize,          // line length before insertion
+    column: Unit,        // display column where the text is inserted
+    lines: Range<usize>, // other lines (numbers)
+    pad: bool,           // pad short lines with spaces
 }
 
 enum Info<'m> {
     NoHint,
     Hint,
     Msg(Option<&'m str>),
+    Validation(&'m str, bool), // message below the input, invalid or not
 }
 
 impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
@@ -52,19 +200,50 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     ) -> Self {
         let prompt_size = out.calculate_position(prompt, Position::default());
         let gcm = out.grapheme_cluster_mode();
+        let ambiguous_width = out.ambiguous_width();
         Self {
             out,
             prompt,
+            mode_prompt: None,
+            fitted_prompt: None,
             prompt_size,
             line: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
-            layout: Layout::new(gcm),
-            saved_line_for_history: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
+            layout: Layout {
+                ambiguous_width,
+                ..Layout::new(gcm)
+            },
+            saved_entries: HashMap::new(),
             byte_buffer: [0; 4],
-            changes: Changeset::new(),
+            changes: Changes::default(),
             helper,
             ctx,
             hint: None,
             highlight_char: false,
+            search_match: None,
+            overwritten: Vec::new(),
+            horizontal_scroll: None,
+            block_anchor: None,
+            block_insert: None,
+            line_numbers: false,
+            line_prompts: HashMap::new(),
+            vertical_scroll: None,
+            cursors: Vec::new(),
+            preedit: String::new(),
+            bidi: false,
+            normalization: None,
+            prompt_overflow: PromptOverflow::default(),
+            coalesce: false,
+            dirty: false,
+            print_interval: None,
+            last_print: None,
+            queued_print: String::new(),
+            queued_eprint: String::new(),
+            region: String::new(),
+            pending_validation: None,
+            resolved_validation: None,
+            validation_msg: false,
+            validated: false,
+            render_counters: None,
         }
     }
 
@@ -94,55 +273,155 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
                     }
                     Signal::Resize => {
                         debug!(target: "rustyline", "SIGWINCH");
-                        let old_cols = self.out.get_columns();
-                        self.out.update_size();
-                        let new_cols = self.out.get_columns();
-                        if new_cols != old_cols
-                            && (self.layout.end.row > 0 || self.layout.end.col >= new_cols)
-                        {
-                            self.prompt_size = self
-                                .out
-                                .calculate_position(self.prompt, Position::default());
-                            self.refresh_line()?;
-                        }
+                        self.resize()?;
                         continue;
                     }
                 }
             }
             if let Ok(Cmd::Replace(..)) = rc {
                 self.changes.begin();
+                self.overwritten.clear();
             }
             return rc;
         }
     }
 
-    pub fn backup(&mut self) {
-        self.saved_line_for_history
-            .update(self.line.as_str(), self.line.pos(), &mut NoListener);
+    /// Re-wrap prompt and line after the terminal has been resized
+    fn resize(&mut self) -> Result<()> {
+        let old_size = (self.out.get_columns(), self.out.get_rows());
+        self.out.update_size();
+        let (cols, rows) = (self.out.get_columns(), self.out.get_rows());
+        if (cols, rows) == old_size {
+            return Ok(());
+        }
+        if let Some(helper) = self.helper {
+            helper.on_resize(cols, rows);
+        }
+        self.update_prompt_size();
+        self.refresh_line()
     }
 
-    pub fn restore(&mut self) {
-        self.line.update(
-            self.saved_line_for_history.as_str(),
-            self.saved_line_for_history.pos(),
-            &mut self.changes,
-        );
+    /// Save the line being edited (with its undo state) before browsing
+    /// history. Recalled entries are saved only when they have been modified.
+    fn backup(&mut self) -> Result<()> {
+        let idx = self.ctx.history_index;
+        let changes = self.changes.take();
+        let modified = match self.ctx.history.get(idx, SearchDirection::Forward)? {
+            Some(stored) => stored.entry != self.line.as_str(),
+            None => true, // current line
+        };
+        if modified {
+            let entry = SavedEntry {
+                line: self.line.as_str().to_owned(),
+                pos: self.line.pos(),
+                changes,
+            };
+            self.saved_entries.insert(idx, entry);
+        }
+        Ok(())
+    }
+
+    /// Restore the in-progress edits of history entry `idx` (or of the
+    /// current line). Returns `false` if there is none.
+    fn restore(&mut self, idx: usize) -> bool {
+        if let Some(entry) = self.saved_entries.remove(&idx) {
+            self.line.update(&entry.line, entry.pos, &mut NoListener);
+            *self.changes = entry.changes;
+            self.changes.folds.clear();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Substitute the line being edited with history entry `idx`, keeping
+    /// in-progress edits of the line being left. In-progress edits of the
+    /// recalled entry are restored, unless `pos` (a search match in the
+    /// stored `entry`) is specified: they are then kept until the entry is
+    /// modified. Otherwise the substitution is undone as a single change.
+    fn recall_history_entry(&mut self, idx: usize, entry: &str, pos: Option<usize>) -> Result<()> {
+        self.backup()?;
+        self.ctx.history_index = idx;
+        if pos.is_some() || !self.restore(idx) {
+            self.changes.begin();
+            self.line
+                .update(entry, pos.unwrap_or(entry.len()), &mut self.changes);
+            self.changes.end();
+        }
+        Ok(())
+    }
+
+    /// Postpone refreshes while more input is `pending`
+    pub fn coalesce_refresh(&mut self, pending: bool) {
+        self.coalesce = pending;
+    }
+
+    /// Draw the line if a refresh has been postponed
+    pub fn flush_refresh(&mut self) -> Result<()> {
+        if self.dirty {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether drawing must wait for the end of the input burst
+    fn defer_refresh(&mut self) -> bool {
+        self.dirty |= self.coalesce;
+        self.coalesce
     }
 
     pub fn move_cursor(&mut self, kind: CmdKind) -> Result<()> {
+        if self.defer_refresh() {
+            return Ok(());
+        } else if self.dirty {
+            // the screen doesn't match the line
+            return self.refresh_line();
+        }
         // calculate the desired position of the cursor
-        let cursor = self
-            .out
-            .calculate_position(&self.line[..self.line.pos()], self.prompt_size);
+        let prompts = self.continuation_prompts();
+        let prompt_size = match self.with_gutter(self.default_prompt()) {
+            Some(prompt) => self.out.calculate_position(&prompt, Position::default()),
+            None => self.prompt_size,
+        };
+        let window = if prompts.is_empty() {
+            self.scroll_window(prompt_size)
+        } else {
+            None
+        };
+        if self.vertical_scroll.is_some()
+            || self.validation_msg
+            || !self.changes.folds.is_empty()
+            || !self.region.is_empty()
+            || !self.preedit.is_empty()
+            || self.reordered()
+            || window.is_some() && window != self.horizontal_scroll
+        {
+            // viewport scrolled, rows above the prompt, validation message or
+            // display line different from the edited one
+            self.highlight_char(kind);
+            return self.refresh(None, Info::NoHint);
+        }
+        let cursor = if let Some(window) = window {
+            let display = scrolled_line(&self.line, window);
+            self.out
+                .calculate_position(&display[..display.pos()], prompt_size)
+        } else if prompts.is_empty() {
+            self.out
+                .calculate_position(&self.line[..self.line.pos()], prompt_size)
+        } else {
+            let display = display_line(&self.line, &prompts);
+            self.out
+                .calculate_position(&display[..display.pos()], prompt_size)
+        };
         if self.layout.cursor == cursor {
             return Ok(());
         }
         if self.highlight_char(kind) {
-            let prompt_size = self.prompt_size;
-            self.refresh(self.prompt, prompt_size, true, Info::NoHint)?;
+            self.refresh(None, Info::NoHint)?;
         } else {
             self.out.move_cursor(self.layout.cursor, cursor)?;
-            self.layout.prompt_size = self.prompt_size;
+            self.layout.prompt_size = prompt_size;
             self.layout.cursor = cursor;
             debug_assert!(self.layout.prompt_size <= self.layout.cursor);
             debug_assert!(self.layout.cursor <= self.layout.end);
@@ -151,6 +430,11 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     }
 
     pub fn move_cursor_to_end(&mut self) -> Result<()> {
+        if self.defer_refresh() {
+            return Ok(());
+        } else if self.dirty {
+            return self.refresh_line();
+        }
         if self.layout.cursor == self.layout.end {
             return Ok(());
         }
@@ -163,43 +447,659 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         self.out.move_cursor_at_leftmost(rdr)
     }
 
-    fn refresh(
-        &mut self,
-        prompt: &str,
-        prompt_size: Position,
-        default_prompt: bool,
-        info: Info<'_>,
-    ) -> Result<()> {
+    /// Prompt displayed when there is no dynamic prompt
+    fn default_prompt(&self) -> &str {
+        self.fitted_prompt
+            .as_deref()
+            .or(self.mode_prompt.as_deref())
+            .unwrap_or(self.prompt)
+    }
+
+    /// Set the policy for a prompt wider than the terminal
+    pub fn set_prompt_overflow(&mut self, prompt_overflow: PromptOverflow) {
+        self.prompt_overflow = prompt_overflow;
+        self.update_prompt_size();
+    }
+
+    /// Batch external prints received less than `interval_ms` after the
+    /// previous one
+    pub fn set_external_print_interval(&mut self, interval_ms: Option<u16>) {
+        self.print_interval = interval_ms;
+    }
+
+    /// Count the rendering work with `counters`
+    pub fn set_render_counters(&mut self, counters: Arc<RenderCounters>) {
+        self.render_counters = Some(counters);
+    }
+
+    /// Queue `msg` and print the queued messages unless the last print is too
+    /// recent
+    fn queue_external_print(&mut self, msg: String, stderr: bool) -> Result<()> {
+        let queue = if stderr {
+            &mut self.queued_eprint
+        } else {
+            &mut self.queued_print
+        };
+        if queue.is_empty() {
+            *queue = msg;
+        } else {
+            queue.push_str(&msg);
+        }
+        if !queue.ends_with('\n') {
+            queue.push('\n');
+        }
+        if self.external_print_delay() == Some(0) {
+            self.flush_external_print()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Remove the region above the prompt (when the edition ends)
+    pub fn hide_region(&mut self) -> Result<()> {
+        if self.region.is_empty() {
+            return Ok(());
+        }
+        self.region.clear();
+        self.refresh_line()
+    }
+
+    /// Fit the default prompt to the terminal width and compute its size
+    fn update_prompt_size(&mut self) {
+        let prompt = self.mode_prompt.as_deref().unwrap_or(self.prompt);
+        self.fitted_prompt = self.fit_prompt(prompt);
+        self.prompt_size = self
+            .out
+            .calculate_position(self.default_prompt(), Position::default());
+    }
+
+    /// `prompt` shortened according to the overflow policy, or `None` when
+    /// it fits in the terminal
+    fn fit_prompt(&self, prompt: &str) -> Option<String> {
+        if self.prompt_overflow == PromptOverflow::Wrap {
+            return None;
+        }
+        let (gcm, aw) = (self.out.grapheme_cluster_mode(), self.out.ambiguous_width());
+        // leave a column for the cursor
+        let width = self.out.get_columns().saturating_sub(1);
+        let fits = prompt
+            .split('\n')
+            .all(|line| matches!(truncate_left(gcm, aw, line, width), Cow::Borrowed(_)));
+        if fits {
+            return None;
+        }
+        let prompt = match (self.prompt_overflow, self.helper) {
+            (PromptOverflow::Shrink, Some(helper)) => helper.shrink_prompt(prompt, width),
+            _ => Cow::Borrowed(prompt),
+        };
+        let lines: Vec<_> = prompt
+            .split('\n')
+            .map(|line| truncate_left(gcm, aw, line, width))
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Returns `prompt` decorated with the editing `mode` indicator
+    pub fn prompt_with_mode<'p>(&self, prompt: &'p str, mode: PromptMode) -> Cow<'p, str> {
+        match self.helper {
+            Some(helper) => helper.prompt_with_mode(prompt, mode),
+            None => Cow::Borrowed(prompt),
+        }
+    }
+
+    /// Update the default prompt according to the editing `mode`.
+    /// Return `true` when the prompt has changed.
+    pub fn set_prompt_mode(&mut self, mode: PromptMode) -> bool {
+        let prompt = self.prompt_with_mode(self.prompt, mode);
+        let mode_prompt = if prompt == self.prompt {
+            None
+        } else {
+            Some(prompt.into_owned())
+        };
+        if mode_prompt == self.mode_prompt {
+            return false;
+        }
+        self.mode_prompt = mode_prompt;
+        self.update_prompt_size();
+        true
+    }
+
+    /// Continuation prompts, one per line after the first, or nothing when
+    /// there is no continuation prompt to display
+    fn continuation_prompts(&self) -> Vec<Cow<'out, str>> {
+        let lines = self.line.matches('\n').count();
+        let prompts: Vec<_> = match self.helper {
+            Some(helper) => (1..=lines)
+                .map(|line_number| match self.line_prompts.get(&line_number) {
+                    Some(prompt) => Cow::Owned(prompt.clone()),
+                    None => helper.continuation_prompt(line_number),
+                })
+                .collect(),
+            None => vec![Cow::Borrowed(""); lines],
+        };
+        if let Some(width) = self.gutter_width() {
+            prompts
+                .into_iter()
+                .enumerate()
+                .map(|(i, prompt)| Cow::Owned(format!("{:>width$} {prompt}", i + 2)))
+                .collect()
+        } else if prompts.iter().all(|prompt| prompt.is_empty()) {
+            Vec::new()
+        } else {
+            prompts
+        }
+    }
+
+    /// Display line numbers in a left gutter for multi-line input
+    pub fn enable_line_numbers(&mut self) {
+        self.line_numbers = true;
+    }
+
+    /// Width of the line numbers, when the gutter is displayed
+    fn gutter_width(&self) -> Option<usize> {
+        if !self.line_numbers {
+            return None;
+        }
+        let lines = self.line.matches('\n').count() + 1;
+        (lines > 1).then(|| lines.to_string().len())
+    }
+
+    /// `prompt` of the first line prefixed by its line number, when the gutter
+    /// is displayed
+    fn with_gutter(&self, prompt: &str) -> Option<String> {
+        self.gutter_width()
+            .map(|width| format!("{:>width$} {prompt}", 1))
+    }
+
+    /// Enable horizontal scrolling of single-line input
+    pub fn enable_horizontal_scroll(&mut self) {
+        self.horizontal_scroll = Some(0..0);
+    }
+
+    /// Display right-to-left text in visual order
+    pub fn enable_bidi(&mut self) {
+        self.bidi = true;
+    }
+
+    /// Normalize the inserted text
+    pub fn set_normalization(&mut self, form: Option<NormalizationForm>) {
+        self.normalization = form;
+    }
+
+    /// `text` in the normalization form of the inserted text
+    pub fn normalize<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        match self.normalization {
+            Some(form) => form.normalize(text),
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    /// Insert `text` `n` times at the cursor, normalized with the characters
+    /// before it. Nothing is inserted (`false`) when the insertion is
+    /// already normalized.
+    fn insert_normalized(&mut self, text: &str, n: RepeatCount) -> bool {
+        if self.normalization.is_none() || text.is_ascii() {
+            return false;
+        }
+        let pos = self.line.pos();
+        let start = normalize::last_starter(&self.line[..pos]);
+        let inserted = format!("{}{}", &self.line[start..pos], text.repeat(usize::from(n)));
+        let Cow::Owned(normalized) = self.normalize(&inserted) else {
+            return false;
+        };
+        self.line
+            .replace(start..pos, &normalized, &mut self.changes);
+        true
+    }
+
+    /// The line is displayed in another order than the one it is edited in
+    fn reordered(&self) -> bool {
+        self.bidi && (bidi::has_rtl(&self.line) || bidi::has_rtl(&self.preedit))
+    }
+
+    /// Part of the line visible around the cursor when horizontal scrolling is
+    /// enabled and the line is too wide
+    fn scroll_window(&self, prompt_size: Position) -> Option<Range<usize>> {
+        let previous = self.horizontal_scroll.as_ref()?;
+        if prompt_size.row > 0 || self.line.contains('\n') {
+            return None;
+        }
+        let budget = self
+            .out
+            .get_columns()
+            .checked_sub(prompt_size.col + 1)
+            .filter(|budget| *budget > 2)?;
+        let (line, pos) = (self.line.as_str(), self.line.pos());
+        if self.layout.width(line) <= budget {
+            return Some(0..line.len());
+        }
+        let marker = |start: usize| Unit::from(start > 0);
+        // keep the previous offset unless the cursor moved out of the viewport
+        let mut start = previous.start;
+        if start > pos || !line.is_char_boundary(start) {
+            start = pos;
+        }
+        while marker(start) + self.layout.width(&line[start..pos]) >= budget {
+            start += line[start..].graphemes(true).next().map_or(0, str::len);
+        }
+        let mut end = line.len();
+        if marker(start) + self.layout.width(&line[start..]) > budget {
+            let mut width = marker(start) + 1; // right marker
+            end = start;
+            for g in line[start..].graphemes(true) {
+                width += self.layout.width(g);
+                if width > budget {
+                    break;
+                }
+                end += g.len();
+            }
+        }
+        Some(start..end)
+    }
+
+    /// Display the text about to be pasted at the cursor position folded
+    pub fn fold_paste(&mut self) {
+        self.changes.fold_next = true;
+    }
+
+    /// Expand all the folded pastes
+    pub fn edit_expand_pastes(&mut self) -> Result<()> {
+        if self.changes.folds.is_empty() {
+            return Ok(());
+        }
+        self.changes.folds.clear();
+        self.refresh_line()
+    }
+
+    /// Line with the folded pastes replaced by a placeholder.
+    /// Folds that have been edited (or undone) or that the cursor has
+    /// entered are expanded.
+    fn fold_pastes(&mut self) -> Option<LineBuffer> {
+        self.changes.fold_next = false;
+        let (line, pos) = (self.line.as_str(), self.line.pos());
+        self.changes.folds.retain(|(start, text)| {
+            let end = start + text.len();
+            line.get(*start..end) == Some(text) && !(*start < pos && pos < end)
+        });
+        if self.changes.folds.is_empty() {
+            return None;
+        }
+        let mut display = String::with_capacity(line.len());
+        let mut display_pos = pos;
+        let mut offset = 0;
+        for (start, text) in &self.changes.folds {
+            let placeholder = format!("\u{27e8} {} lines pasted \u{27e9}", text.lines().count());
+            display.push_str(&line[offset..*start]);
+            display.push_str(&placeholder);
+            offset = start + text.len();
+            if offset <= pos {
+                display_pos = display_pos - text.len() + placeholder.len();
+            }
+        }
+        display.push_str(&line[offset..]);
+        let mut buf = LineBuffer::with_capacity(display.len().max(MAX_LINE)).can_growth(true);
+        buf.update(&display, display_pos, &mut NoListener);
+        Some(buf)
+    }
+
+    /// Display `prompt` (or the default prompt when `None`), line and `info`
+    fn refresh(&mut self, prompt: Option<&str>, info: Info<'_>) -> Result<()> {
+        if self.defer_refresh() {
+            return Ok(());
+        }
+        self.dirty = false;
+        let folded = self.fold_pastes();
+        let reordered = self.reordered();
+        if folded.is_none() && self.preedit.is_empty() && !reordered {
+            return self.refresh_display(prompt, info);
+        }
+        let mut display = folded.unwrap_or_else(|| {
+            let mut line =
+                LineBuffer::with_capacity(self.line.len().max(MAX_LINE)).can_growth(true);
+            line.update(&self.line, self.line.pos(), &mut NoListener);
+            line
+        });
+        if !self.preedit.is_empty() {
+            // the composition is displayed before the cursor
+            display.yank(&self.preedit, 1, &mut NoListener);
+        }
+        if reordered {
+            if let Some((text, pos)) = bidi::visual(&display, display.pos()) {
+                display.update(&text, pos, &mut NoListener);
+            }
+        }
+        // positions of search match and selection are in the edited line
+        let line = std::mem::replace(&mut self.line, display);
+        let search_match = self.search_match.take();
+        let block_anchor = self.block_anchor.take();
+        let cursors = std::mem::take(&mut self.cursors);
+        let result = self.refresh_display(prompt, info);
+        self.line = line;
+        self.search_match = search_match;
+        self.block_anchor = block_anchor;
+        self.cursors = cursors;
+        result
+    }
+
+    /// Display `prompt`, the displayed line (with continuation prompts,
+    /// scrolled, ...) and `info`
+    fn refresh_display(&mut self, prompt: Option<&str>, info: Info<'_>) -> Result<()> {
+        let (prompt, prompt_size, default_prompt) = match prompt {
+            Some(prompt) => (
+                prompt,
+                self.out.calculate_position(prompt, Position::default()),
+                false,
+            ),
+            None => (
+                self.fitted_prompt
+                    .as_deref()
+                    .or(self.mode_prompt.as_deref())
+                    .unwrap_or(self.prompt),
+                self.prompt_size,
+                true,
+            ),
+        };
+        let gutter = self.with_gutter(prompt);
+        let (prompt, prompt_size) = match gutter {
+            Some(ref prompt) => (
+                prompt.as_str(),
+                self.out.calculate_position(prompt, Position::default()),
+            ),
+            None => (prompt, prompt_size),
+        };
+        let prompts = self.continuation_prompts();
+        let window = if prompts.is_empty() {
+            self.scroll_window(prompt_size)
+        } else {
+            None
+        };
+        if window.is_some() {
+            self.horizontal_scroll.clone_from(&window);
+        }
+        let viewport = if window.is_none() {
+            self.viewport(prompt, &prompts)
+        } else {
+            None
+        };
+        self.vertical_scroll.clone_from(&viewport);
+        // visible lines only
+        let (sub, visible, display_prompt, line_prompts) = match viewport {
+            Some(ref lines) => {
+                let Range { start, end } = self.line_range(lines.clone());
+                let mut sub = LineBuffer::with_capacity(MAX_LINE).can_growth(true);
+                sub.update(
+                    &self.line[start..end],
+                    self.line.pos() - start,
+                    &mut NoListener,
+                );
+                let display_prompt = match lines.start {
+                    0 => prompt,
+                    n => prompts.get(n - 1).map_or("", |prompt| prompt),
+                };
+                let line_prompts = prompts.get(lines.start..lines.end - 1).unwrap_or(&[]);
+                (Some(sub), start..end, display_prompt, line_prompts)
+            }
+            None => (
+                None,
+                window.clone().unwrap_or(0..self.line.len()),
+                prompt,
+                &prompts[..],
+            ),
+        };
+        let display_prompt_size = if viewport.is_some() {
+            self.out
+                .calculate_position(display_prompt, Position::default())
+        } else {
+            prompt_size
+        };
+        let base = sub.as_ref().unwrap_or(&self.line);
+        let validation = match info {
+            Info::Validation(msg, invalid) => Some((format!("\n{msg}"), invalid)),
+            _ => None,
+        };
+        self.validation_msg = validation.is_some();
+        self.validated = validation.is_some();
         let info = match info {
             Info::NoHint => None,
-            Info::Hint => self.hint.as_ref().map(|h| h.display()),
+            Info::Hint => self.hint.as_ref().map(|h| h.display()).filter(|hint| {
+                // a hint would wrap the scrolled line
+                window.as_ref().is_none_or(|window| {
+                    *window == (0..self.line.len())
+                        && prompt_size.col + self.layout.width(&self.line) + self.layout.width(hint)
+                            < self.out.get_columns()
+                })
+            }),
             Info::Msg(msg) => msg,
+            Info::Validation(..) => validation.as_ref().map(|(msg, _)| msg.as_str()),
         };
         let highlighter = if self.out.colors_enabled() {
             self.helper.map(|h| h as &dyn Highlighter)
         } else {
             None
         };
+        let search_match = self
+            .search_match
+            .clone()
+            .filter(|range| self.out.colors_enabled() && self.line.get(range.clone()).is_some())
+            .filter(|range| range.start >= visible.start && range.end <= visible.end)
+            .map(|range| SearchMatch {
+                highlighter,
+                range: range.start - visible.start..range.end - visible.start,
+            });
+        let preedit = self
+            .line
+            .pos()
+            .checked_sub(self.preedit.len())
+            .map(|start| start..self.line.pos())
+            // not underlined when reordered
+            .filter(|range| self.line.get(range.clone()) == Some(self.preedit.as_str()))
+            .filter(|range| !range.is_empty() && self.out.colors_enabled())
+            .filter(|range| range.start >= visible.start && range.end <= visible.end)
+            .map(|range| Preedit {
+                highlighter,
+                range: range.start - visible.start..range.end - visible.start,
+            });
+        let selected = match self.block_anchor {
+            Some(anchor) => Some(self.line.block(anchor, &self.layout).1),
+            None if !self.cursors.is_empty() => Some(
+                // extra cursors are displayed as a one grapheme selection
+                self.cursors
+                    .iter()
+                    .map(|&pos| {
+                        let grapheme = self.line[pos..]
+                            .graphemes(true)
+                            .next()
+                            .filter(|g| *g != "\n");
+                        pos..pos + grapheme.map_or(0, str::len)
+                    })
+                    .collect(),
+            ),
+            None => None,
+        };
+        let selection = selected
+            .filter(|_| self.out.colors_enabled())
+            .map(|ranges| Selection {
+                highlighter,
+                ranges: ranges
+                    .into_iter()
+                    .filter(|range| range.start >= visible.start && range.end <= visible.end)
+                    .map(|range| range.start - visible.start..range.end - visible.start)
+                    .collect(),
+            });
+        // only the visible lines (and a margin) of a tall input are highlighted
+        let context = viewport.as_ref().map(|lines| {
+            self.line_range(
+                lines.start.saturating_sub(HIGHLIGHT_MARGIN)..lines.end + HIGHLIGHT_MARGIN,
+            )
+        });
+        let margin = highlighter
+            .zip(context)
+            .map(|(highlighter, context)| Margin {
+                highlighter,
+                line: &self.line,
+                context,
+                visible: visible.clone(),
+            });
+        let highlighter = match (&preedit, &selection, &search_match, &margin) {
+            (Some(preedit), ..) => Some(preedit as &dyn Highlighter),
+            (None, Some(selection), ..) => Some(selection as &dyn Highlighter),
+            (None, None, Some(search_match), _) => Some(search_match as &dyn Highlighter),
+            (None, None, None, Some(margin)) => Some(margin as &dyn Highlighter),
+            (None, None, None, None) => highlighter,
+        };
+        let display = if let Some(ref window) = window {
+            Some(scrolled_line(&self.line, window.clone()))
+        } else if line_prompts.is_empty() {
+            None
+        } else {
+            Some(display_line(base, line_prompts))
+        };
+        let continuation_prompts = highlighter.map(|highlighter| ContinuationPrompts {
+            highlighter,
+            line: base,
+            prompts: line_prompts,
+        });
+        let scrolled = highlighter
+            .zip(window)
+            .map(|(highlighter, range)| Scrolled {
+                highlighter,
+                line: &self.line,
+                range,
+            });
+        let (line, highlighter) = match display {
+            Some(ref display) if scrolled.is_some() => {
+                (display, scrolled.as_ref().map(|s| s as &dyn Highlighter))
+            }
+            Some(ref display) => (
+                display,
+                continuation_prompts.as_ref().map(|c| c as &dyn Highlighter),
+            ),
+            None => (base, highlighter),
+        };
 
-        let new_layout = self
-            .out
-            .compute_layout(prompt_size, default_prompt, &self.line, info);
+        // the region is displayed as the first rows of the prompt
+        let region_prompt;
+        let (display_prompt, display_prompt_size) = if self.region.is_empty() {
+            (display_prompt, display_prompt_size)
+        } else {
+            region_prompt = format!("{}{display_prompt}", self.region);
+            let size = self
+                .out
+                .calculate_position(&region_prompt, Position::default());
+            (region_prompt.as_str(), size)
+        };
+        let region = highlighter.map(|highlighter| Region {
+            highlighter,
+            len: self.region.len(),
+        });
+        let highlighter = match region {
+            Some(ref region) if region.len > 0 => Some(region as &dyn Highlighter),
+            _ => highlighter,
+        };
+        let message = highlighter
+            .zip(validation.as_ref())
+            .map(|(highlighter, &(_, invalid))| ValidationMessage {
+                highlighter,
+                invalid,
+            });
+        let highlighter = match message {
+            Some(ref message) => Some(message as &dyn Highlighter),
+            None => highlighter,
+        };
+
+        let mut new_layout =
+            self.out
+                .compute_layout(display_prompt_size, default_prompt, line, info);
+        if let Some(ref counters) = self.render_counters {
+            counters.layout();
+        }
+        new_layout.continuation_prompt_cols = prompts
+            .iter()
+            .map(|prompt| self.out.calculate_position(prompt, Position::default()).col)
+            .collect();
+        if viewport.is_some() {
+            new_layout.first_prompt_col = Some(prompt_size.col);
+        }
 
         debug!(target: "rustyline", "old layout: {:?}", self.layout);
         debug!(target: "rustyline", "new layout: {:?}", new_layout);
+        let written = self.out.bytes_written();
         self.out.refresh_line(
-            prompt,
-            &self.line,
+            display_prompt,
+            line,
             info,
             &self.layout,
             &new_layout,
             highlighter,
         )?;
+        if let Some(ref counters) = self.render_counters {
+            counters.refresh(self.out.bytes_written() - written);
+        }
         self.layout = new_layout;
 
         Ok(())
     }
 
+    /// Bytes of the `lines` of the input (without the last newline)
+    fn line_range(&self, lines: Range<usize>) -> Range<usize> {
+        let start = match lines.start {
+            0 => 0,
+            n => self
+                .line
+                .match_indices('\n')
+                .nth(n - 1)
+                .map_or(0, |(n, _)| n + 1),
+        };
+        let end = self
+            .line
+            .match_indices('\n')
+            .nth(lines.end - 1)
+            .map_or(self.line.len(), |(n, _)| n);
+        start..end
+    }
+
+    /// Lines displayed when the input is taller than the terminal
+    fn viewport(&self, prompt: &str, prompts: &[Cow<'_, str>]) -> Option<Range<usize>> {
+        let budget = usize::from(self.out.get_rows()).saturating_sub(1).max(1);
+        let rows: Vec<usize> = self
+            .line
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                let prefix = match i {
+                    0 => prompt,
+                    n => prompts.get(n - 1).map_or("", |prompt| prompt),
+                };
+                let start = self.out.calculate_position(prefix, Position::default());
+                usize::from(self.out.calculate_position(line, start).row) + 1
+            })
+            .collect();
+        if rows.iter().sum::<usize>() <= budget {
+            return None;
+        }
+        let cursor = self.line[..self.line.pos()].matches('\n').count();
+        // keep the previous first line unless the cursor moved above
+        let mut first = self
+            .vertical_scroll
+            .as_ref()
+            .map_or(cursor, |lines| lines.start)
+            .min(cursor);
+        while first < cursor && rows[first..=cursor].iter().sum::<usize>() > budget {
+            first += 1;
+        }
+        let mut last = cursor + 1;
+        let mut used = rows[first..last].iter().sum::<usize>();
+        while last < rows.len() && used + rows[last] <= budget {
+            used += rows[last];
+            last += 1;
+        }
+        while first > 0 && used + rows[first - 1] <= budget {
+            first -= 1;
+            used += rows[first];
+        }
+        Some(first..last)
+    }
+
     pub fn hint(&mut self) {
         if let Some(hinter) = self.helper {
             let hint = hinter.hint(self.line.as_str(), self.line.pos(), &self.ctx);
@@ -213,6 +1113,15 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     }
 
     fn highlight_char(&mut self, kind: CmdKind) -> bool {
+        if self.block_anchor.is_some() || !self.cursors.is_empty() {
+            // selection follows the cursor
+            return true;
+        }
+        if self.search_match.take().is_some() {
+            // previously highlighted search match => force a full refresh
+            self.highlight_char = false;
+            return true;
+        }
         if let Some(highlighter) = self.highlighter() {
             let highlight_char = highlighter.highlight_char(&self.line, self.line.pos(), kind);
             if highlight_char {
@@ -236,22 +1145,29 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
 
     pub fn validate(&mut self) -> Result<ValidationResult> {
         if let Some(validator) = self.helper {
-            self.changes.begin();
-            let result = validator.validate(&mut ValidationContext::new(self))?;
-            let corrected = self.changes.end();
+            let (result, corrected) = if let Some(result) = self.resolved_validation.take() {
+                (result, false)
+            } else {
+                self.changes.begin();
+                let result = validator.validate(&mut ValidationContext::new(self))?;
+                (result, self.changes.end())
+            };
             match result {
-                ValidationResult::Incomplete => {}
+                ValidationResult::Incomplete | ValidationResult::IncompleteWith(_) => {}
+                ValidationResult::Pending(ref msg) => {
+                    self.refresh_validation(msg.as_deref(), false, CmdKind::Other)?;
+                }
                 ValidationResult::Valid(ref msg) => {
                     // Accept the line regardless of where the cursor is.
                     if corrected || self.has_hint() || msg.is_some() {
                         // Force a refresh without hints to leave the previous
                         // line as the user typed it after a newline.
-                        self.refresh_line_with_msg(msg.as_deref(), CmdKind::ForcedRefresh)?;
+                        self.refresh_validation(msg.as_deref(), false, CmdKind::ForcedRefresh)?;
                     }
                 }
                 ValidationResult::Invalid(ref msg) => {
                     if corrected || self.has_hint() || msg.is_some() {
-                        self.refresh_line_with_msg(msg.as_deref(), CmdKind::Other)?;
+                        self.refresh_validation(msg.as_deref(), true, CmdKind::Other)?;
                     }
                 }
             }
@@ -260,34 +1176,402 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             Ok(ValidationResult::Valid(None))
         }
     }
-}
 
-impl<H: Helper> Invoke for State<'_, '_, H> {
-    fn input(&self) -> &str {
-        self.line.as_str()
+    /// Display the validation `msg` below the input, until the next keystroke
+    fn refresh_validation(
+        &mut self,
+        msg: Option<&str>,
+        invalid: bool,
+        kind: CmdKind,
+    ) -> Result<()> {
+        self.hint = None;
+        self.highlight_char(kind);
+        match msg {
+            Some(msg) => self.refresh(None, Info::Validation(msg, invalid)),
+            None => self.refresh(None, Info::NoHint),
+        }
     }
-}
 
-impl<H: Helper> Refresher for State<'_, '_, H> {
-    fn refresh_line(&mut self) -> Result<()> {
-        let prompt_size = self.prompt_size;
-        self.hint();
-        self.highlight_char(CmdKind::Other);
-        self.refresh(self.prompt, prompt_size, true, Info::Hint)
+    /// Wait for the result of an asynchronous validation before executing
+    /// `cmd` again
+    pub fn defer_validation(&mut self, cmd: Cmd) {
+        self.pending_validation = Some((cmd, self.line.as_str().to_owned()));
     }
+}
 
-    fn refresh_line_with_msg(&mut self, msg: Option<&str>, kind: CmdKind) -> Result<()> {
-        let prompt_size = self.prompt_size;
-        self.hint = None;
-        self.highlight_char(kind);
-        self.refresh(self.prompt, prompt_size, true, Info::Msg(msg))
+/// Highlight the text matched by a vi search instead of the line
+struct SearchMatch<'h> {
+    highlighter: Option<&'h dyn Highlighter>,
+    range: Range<usize>,
+}
+
+impl Highlighter for SearchMatch<'_> {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let highlighter = self.highlighter.unwrap_or(&());
+        highlighter.highlight_search_match(line, self.range.clone())
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        match self.highlighter {
+            Some(highlighter) => highlighter.highlight_prompt(prompt, default),
+            None => Cow::Borrowed(prompt),
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        match self.highlighter {
+            Some(highlighter) => highlighter.highlight_hint(hint),
+            None => Cow::Borrowed(hint),
+        }
+    }
+}
+
+/// Underline the IME composition displayed at the cursor
+struct Preedit<'h> {
+    highlighter: Option<&'h dyn Highlighter>,
+    range: Range<usize>,
+}
+
+impl Highlighter for Preedit<'_> {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let highlighter = self.highlighter.unwrap_or(&());
+        highlighter.highlight_preedit(line, self.range.clone())
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        match self.highlighter {
+            Some(highlighter) => highlighter.highlight_prompt(prompt, default),
+            None => Cow::Borrowed(prompt),
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        match self.highlighter {
+            Some(highlighter) => highlighter.highlight_hint(hint),
+            None => Cow::Borrowed(hint),
+        }
+    }
+}
+
+/// Leave the region above the prompt as is
+struct Region<'h> {
+    highlighter: &'h dyn Highlighter,
+    len: usize, // region length (bytes) at the start of the prompt
+}
+
+impl Highlighter for Region<'_> {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        self.highlighter.highlight(line, pos)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        let (region, rest) = prompt.split_at(self.len);
+        match self.highlighter.highlight_prompt(rest, default) {
+            Cow::Borrowed(highlighted) if highlighted == rest => Cow::Borrowed(prompt),
+            highlighted => Cow::Owned(format!("{region}{highlighted}")),
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.highlighter.highlight_hint(hint)
+    }
+}
+
+/// Insert continuation `prompts` after each newline of `line`
+fn display_line(line: &LineBuffer, prompts: &[Cow<'_, str>]) -> LineBuffer {
+    let mut display = String::with_capacity(
+        line.len() + prompts.iter().map(|prompt| prompt.len()).sum::<usize>(),
+    );
+    let mut pos = line.pos();
+    let mut prompts = prompts.iter();
+    for (start, c) in line.char_indices() {
+        display.push(c);
+        if c == '\n' {
+            if let Some(prompt) = prompts.next() {
+                display.push_str(prompt);
+                if start < line.pos() {
+                    pos += prompt.len();
+                }
+            }
+        }
+    }
+    let mut buf = LineBuffer::with_capacity(display.len().max(MAX_LINE)).can_growth(true);
+    buf.update(&display, pos, &mut NoListener);
+    buf
+}
+
+/// Visible `window` of `line` with truncation markers
+fn scrolled_line(line: &LineBuffer, window: Range<usize>) -> LineBuffer {
+    let mut display = String::with_capacity(window.len() + 2);
+    if window.start > 0 {
+        display.push('<');
+    }
+    display.push_str(&line[window.clone()]);
+    if window.end < line.len() {
+        display.push('>');
+    }
+    let pos = line.pos() - window.start + usize::from(window.start > 0);
+    let mut buf = LineBuffer::with_capacity(display.len().max(MAX_LINE)).can_growth(true);
+    buf.update(&display, pos, &mut NoListener);
+    buf
+}
+
+/// Highlight only the visible part of a scrolled line
+struct Scrolled<'h> {
+    highlighter: &'h dyn Highlighter,
+    line: &'h LineBuffer,
+    range: Range<usize>,
+}
+
+impl Highlighter for Scrolled<'_> {
+    fn highlight<'l>(&self, _line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let visible = &self.line[self.range.clone()];
+        let highlighted = self
+            .highlighter
+            .highlight(visible, self.line.pos() - self.range.start);
+        let mut display = String::with_capacity(highlighted.len() + 2);
+        if self.range.start > 0 {
+            display.push('<');
+        }
+        display.push_str(&highlighted);
+        if self.range.end < self.line.len() {
+            display.push('>');
+        }
+        Cow::Owned(display)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        self.highlighter.highlight_prompt(prompt, default)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.highlighter.highlight_hint(hint)
+    }
+}
+
+/// Highlight the `visible` lines of a tall input, with some `context`
+/// lines around them for the constructs spanning several lines
+struct Margin<'h> {
+    highlighter: &'h dyn Highlighter,
+    line: &'h LineBuffer,
+    context: Range<usize>,
+    visible: Range<usize>,
+}
+
+impl Highlighter for Margin<'_> {
+    fn highlight<'l>(&self, _line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let context = &self.line[self.context.clone()];
+        let pos = self.line.pos() - self.context.start;
+        let highlighted = self.highlighter.highlight(context, pos);
+        let skip = self.line[self.context.start..self.visible.start]
+            .matches('\n')
+            .count();
+        let lines = self.line[self.visible.clone()].matches('\n').count() + 1;
+        // styles still active at the start of the visible lines
+        let mut styles = String::new();
+        let mut segments = highlighted.split_inclusive('\n');
+        for segment in segments.by_ref().take(skip) {
+            active_styles(&mut styles, segment);
+        }
+        let mut display = styles;
+        for segment in segments.take(lines) {
+            display.push_str(segment);
+        }
+        if display.ends_with('\n') {
+            display.pop();
+        }
+        let mut styles = String::new();
+        active_styles(&mut styles, &display);
+        if !styles.is_empty() {
+            display.push_str("\x1b[0m");
+        }
+        Cow::Owned(display)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        self.highlighter.highlight_prompt(prompt, default)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.highlighter.highlight_hint(hint)
+    }
+
+    fn highlight_validation_message<'m>(&self, msg: &'m str, invalid: bool) -> Cow<'m, str> {
+        self.highlighter.highlight_validation_message(msg, invalid)
+    }
+}
+
+/// Accumulate in `styles` the SGR sequences of `text` (cleared on reset)
+fn active_styles(styles: &mut String, text: &str) {
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1b[") {
+        rest = &rest[start..];
+        let params = rest[2..].trim_start_matches(|c: char| c.is_ascii_digit() || c == ';');
+        let Some(last) = params.chars().next() else {
+            break;
+        };
+        let len = rest.len() - params.len() + last.len_utf8();
+        let seq = &rest[..len];
+        if seq.ends_with('m') {
+            if seq == "\x1b[m" || seq == "\x1b[0m" {
+                styles.clear();
+            } else {
+                styles.push_str(seq);
+            }
+        }
+        rest = &rest[len..];
+    }
+}
+
+/// Highlight the original line and insert the continuation prompts
+struct ContinuationPrompts<'h> {
+    highlighter: &'h dyn Highlighter,
+    line: &'h LineBuffer,
+    prompts: &'h [Cow<'h, str>],
+}
+
+impl Highlighter for ContinuationPrompts<'_> {
+    fn highlight<'l>(&self, _line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let highlighted = self.highlighter.highlight(self.line, self.line.pos());
+        let mut prompts = self.prompts.iter();
+        let mut display = String::with_capacity(highlighted.len());
+        for segment in highlighted.split_inclusive('\n') {
+            display.push_str(segment);
+            if segment.ends_with('\n') {
+                if let Some(prompt) = prompts.next() {
+                    display.push_str(&self.highlighter.highlight_prompt(prompt, false));
+                }
+            }
+        }
+        Cow::Owned(display)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        self.highlighter.highlight_prompt(prompt, default)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.highlighter.highlight_hint(hint)
+    }
+}
+
+/// Highlight the validation message displayed instead of the hint
+struct ValidationMessage<'h> {
+    highlighter: &'h dyn Highlighter,
+    invalid: bool,
+}
+
+impl Highlighter for ValidationMessage<'_> {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        self.highlighter.highlight(line, pos)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        self.highlighter.highlight_prompt(prompt, default)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.highlighter
+            .highlight_validation_message(hint, self.invalid)
+    }
+}
+
+/// Highlight the text selected in vi visual block mode instead of the line
+struct Selection<'h> {
+    highlighter: Option<&'h dyn Highlighter>,
+    ranges: Vec<Range<usize>>,
+}
+
+impl Highlighter for Selection<'_> {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let highlighter = self.highlighter.unwrap_or(&());
+        highlighter.highlight_selection(line, &self.ranges)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        match self.highlighter {
+            Some(highlighter) => highlighter.highlight_prompt(prompt, default),
+            None => Cow::Borrowed(prompt),
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        match self.highlighter {
+            Some(highlighter) => highlighter.highlight_hint(hint),
+            None => Cow::Borrowed(hint),
+        }
+    }
+}
+
+impl<H: Helper> Invoke for State<'_, '_, H> {
+    fn input(&self) -> &str {
+        self.line.as_str()
+    }
+
+    fn pos(&self) -> usize {
+        self.line.pos()
+    }
+
+    fn context(&self) -> Option<&Context<'_>> {
+        Some(&self.ctx)
+    }
+}
+
+impl<H: Helper> Refresher for State<'_, '_, H> {
+    fn refresh_line(&mut self) -> Result<()> {
+        if self.defer_refresh() {
+            return Ok(());
+        }
+        self.hint();
+        self.highlight_char(CmdKind::Other);
+        self.refresh(None, Info::Hint)
+    }
+
+    fn refresh_line_with_msg(&mut self, msg: Option<&str>, kind: CmdKind) -> Result<()> {
+        self.hint = None;
+        self.highlight_char(kind);
+        self.refresh(None, Info::Msg(msg))
     }
 
     fn refresh_prompt_and_line(&mut self, prompt: &str) -> Result<()> {
-        let prompt_size = self.out.calculate_position(prompt, Position::default());
+        if self.defer_refresh() {
+            return Ok(());
+        }
         self.hint();
         self.highlight_char(CmdKind::Other);
-        self.refresh(prompt, prompt_size, false, Info::Hint)
+        self.refresh(Some(prompt), Info::Hint)
     }
 
     fn doing_insert(&mut self) {
@@ -322,16 +1606,117 @@ impl<H: Helper> Refresher for State<'_, '_, H> {
         self.line.pos()
     }
 
+    fn undo_tree(&self) -> UndoTree {
+        self.changes.tree()
+    }
+
     fn external_print(&mut self, msg: String) -> Result<()> {
-        self.out.clear_rows(&self.layout)?;
-        self.layout.end.row = 0;
-        self.layout.cursor.row = 0;
-        self.out.write_and_flush(msg.as_str())?;
-        if !msg.ends_with('\n') {
-            self.out.write_and_flush("\n")?;
+        self.queue_external_print(msg, false)
+    }
+
+    fn external_eprint(&mut self, msg: String) -> Result<()> {
+        self.queue_external_print(msg, true)
+    }
+
+    fn live_validation_delay(&self) -> Option<u16> {
+        self.helper
+            .filter(|validator| validator.validate_while_typing())
+            .filter(|_| !self.validated && !self.line.is_empty())
+            .map(|_| LIVE_VALIDATION_DELAY)
+    }
+
+    fn validate_live(&mut self) -> Result<()> {
+        let Some(validator) = self.helper else {
+            return Ok(());
+        };
+        self.validated = true;
+        match validator.validate(&mut ValidationContext::new(self))? {
+            ValidationResult::Valid(_) => {
+                self.refresh_validation(Some("\u{2713}"), false, CmdKind::Other)
+            }
+            ValidationResult::Invalid(msg) => {
+                let msg = match msg.as_deref().and_then(|msg| msg.lines().next()) {
+                    Some(error) => format!("\u{2717} {error}"),
+                    None => "\u{2717}".to_owned(),
+                };
+                self.refresh_validation(Some(&msg), true, CmdKind::Other)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn external_print_delay(&self) -> Option<u16> {
+        if self.queued_print.is_empty() && self.queued_eprint.is_empty() {
+            return None;
+        }
+        let (Some(interval), Some(last_print)) = (self.print_interval, self.last_print) else {
+            return Some(0);
+        };
+        let delay = Duration::from_millis(interval.into()).saturating_sub(last_print.elapsed());
+        Some(u16::try_from(delay.as_millis()).unwrap_or(interval))
+    }
+
+    fn external_region(&mut self, lines: Vec<String>) -> Result<()> {
+        let region: String = lines.iter().map(|line| format!("{line}\n")).collect();
+        if region == self.region {
+            return Ok(());
+        }
+        self.region = region;
+        self.refresh_line()
+    }
+
+    fn flush_external_print(&mut self) -> Result<()> {
+        if self.queued_print.is_empty() && self.queued_eprint.is_empty() {
+            return Ok(());
+        }
+        // erase, print and redraw at once
+        self.batch(Self::print_queued)
+    }
+
+    fn validation_pending(&self) -> bool {
+        self.pending_validation.is_some()
+    }
+
+    fn poll_validation(&mut self) -> Result<Option<Cmd>> {
+        let (Some(validator), Some((cmd, input))) = (self.helper, self.pending_validation.take())
+        else {
+            return Ok(None);
+        };
+        if input != self.line.as_str() {
+            // input edited: the result would be stale
+            validator.cancel_validation();
+            return self.refresh_line().map(|()| None);
+        }
+        match validator.poll_validation(&mut ValidationContext::new(self))? {
+            ValidationResult::Pending(_) => {
+                self.pending_validation = Some((cmd, input));
+                Ok(None)
+            }
+            result => {
+                self.resolved_validation = Some(result);
+                Ok(Some(cmd))
+            }
+        }
+    }
+
+    fn focus_changed(&mut self, focused: bool) -> Result<()> {
+        if let Some(helper) = self.helper {
+            helper.on_focus(focused);
+        }
+        self.refresh_line()
+    }
+
+    fn preedit(&mut self, text: String) -> Result<()> {
+        if text == self.preedit {
+            return Ok(());
         }
+        self.preedit = text;
         self.refresh_line()
     }
+
+    fn commit_preedit(&mut self) {
+        self.preedit.clear();
+    }
 }
 
 impl<H: Helper> fmt::Debug for State<'_, '_, H> {
@@ -342,12 +1727,47 @@ impl<H: Helper> fmt::Debug for State<'_, '_, H> {
             .field("buf", &self.line)
             .field("cols", &self.out.get_columns())
             .field("layout", &self.layout)
-            .field("saved_line_for_history", &self.saved_line_for_history)
+            .field("saved_entries", &self.saved_entries.keys())
             .finish()
     }
 }
 
 impl<H: Helper> State<'_, '_, H> {
+    /// Write the output of `f` to the terminal at once, with a single write
+    pub fn batch<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.out.begin_batch();
+        let result = f(self);
+        let end = self.out.end_batch();
+        let value = result?;
+        end?;
+        Ok(value)
+    }
+
+    /// Erase the prompt, print the queued messages and redraw
+    fn print_queued(&mut self) -> Result<()> {
+        use std::io::Write;
+        self.out.clear_rows(&self.layout)?;
+        self.layout.end.row = 0;
+        self.layout.cursor.row = 0;
+        if !self.queued_eprint.is_empty() {
+            // the rows must be erased before anything is written to stderr
+            self.out.end_batch()?;
+            self.out.begin_batch();
+            let mut stderr = std::io::stderr().lock();
+            stderr.write_all(self.queued_eprint.as_bytes())?;
+            stderr.flush()?;
+            self.queued_eprint.clear();
+        }
+        if !self.queued_print.is_empty() {
+            self.out.write_and_flush(&self.queued_print)?;
+            self.queued_print.clear();
+        }
+        if self.print_interval.is_some() {
+            self.last_print = Some(Instant::now());
+        }
+        self.refresh_line()
+    }
+
     pub fn clear_screen(&mut self) -> Result<()> {
         self.out.clear_screen()?;
         self.layout.cursor = Position::default();
@@ -357,27 +1777,35 @@ impl<H: Helper> State<'_, '_, H> {
 
     /// Insert the character `ch` at cursor current position.
     pub fn edit_insert(&mut self, ch: char, n: RepeatCount) -> Result<()> {
+        if !ch.is_ascii() && self.insert_normalized(ch.encode_utf8(&mut [0; 4]), n) {
+            return self.refresh_line();
+        }
         if let Some(push) = self.line.insert(ch, n, &mut self.changes) {
-            if push {
-                let prompt_size = self.prompt_size;
+            if self.defer_refresh() {
+                Ok(())
+            } else if push {
                 let no_previous_hint = self.hint.is_none();
                 self.hint();
-                let width = cwidh(ch);
+                let width = self.layout.ambiguous_width.char_width(ch);
                 if n == 1
+                    && !self.dirty
+                    && !self.validation_msg
                     && width != 0 // Ctrl-V + \t or \n ...
                     && self.layout.cursor.col + width < self.out.get_columns()
                     && (self.hint.is_none() && no_previous_hint) // TODO refresh only current line
                     && !self.highlight_char(CmdKind::Other)
+                    && !self.reordered()
                 {
                     // Avoid a full update of the line in the trivial case.
+                    self.validated = false;
                     self.layout.cursor.col += width;
                     self.layout.end.col += width;
                     debug_assert!(self.layout.prompt_size <= self.layout.cursor);
                     debug_assert!(self.layout.cursor <= self.layout.end);
                     let bits = ch.encode_utf8(&mut self.byte_buffer);
-                    self.out.write_and_flush(bits)
+                    self.out.append(bits, width)
                 } else {
-                    self.refresh(self.prompt, prompt_size, true, Info::Hint)
+                    self.refresh(None, Info::Hint)
                 }
             } else {
                 self.refresh_line()
@@ -389,6 +1817,10 @@ impl<H: Helper> State<'_, '_, H> {
 
     /// Replace a single (or n) character(s) under the cursor (Vi mode)
     pub fn edit_replace_char(&mut self, ch: char, n: RepeatCount) -> Result<()> {
+        if self.line[self.line.pos()..].graphemes(true).count() < usize::from(n) {
+            // like vi, nothing is replaced when there are not enough characters
+            return Ok(());
+        }
         self.changes.begin();
         let succeed = if let Some(chars) = self.line.delete(n, &mut self.changes) {
             let count = RepeatCount::try_from(chars.graphemes(true).count()).unwrap();
@@ -408,16 +1840,53 @@ impl<H: Helper> State<'_, '_, H> {
 
     /// Overwrite the character under the cursor (Vi mode)
     pub fn edit_overwrite_char(&mut self, ch: char) -> Result<()> {
-        if let Some(end) = self.line.next_pos(1) {
-            {
-                let text = ch.encode_utf8(&mut self.byte_buffer);
-                let start = self.line.pos();
-                self.line.replace(start..end, text, &mut self.changes);
+        let start = self.line.pos();
+        match self.overwritten.last_mut() {
+            // combining character: extend the previous grapheme
+            Some((range, _)) if range.end == start && cwidh(ch) == 0 && !ch.is_control() => {
+                self.line.insert(ch, 1, &mut self.changes);
+                range.end = self.line.pos();
+                return self.refresh_line();
             }
-            self.refresh_line()
+            _ => {}
+        }
+        let original = if let Some(end) = self.line.next_pos(1) {
+            let original = self.line[start..end].to_owned();
+            let text = ch.encode_utf8(&mut self.byte_buffer);
+            self.line.replace(start..end, text, &mut self.changes);
+            Some(original)
         } else {
-            Ok(())
+            // past the end of line: append
+            self.line.insert(ch, 1, &mut self.changes);
+            None
+        };
+        self.overwritten.push((start..self.line.pos(), original));
+        self.refresh_line()
+    }
+
+    /// Restore the `n` characters overwritten before the cursor (Vi replace
+    /// mode backspace)
+    pub fn edit_overwrite_backspace(&mut self, n: RepeatCount) -> Result<()> {
+        for _ in 0..n {
+            match self.overwritten.pop() {
+                Some((range, original)) if range.end == self.line.pos() => {
+                    match original {
+                        Some(original) => {
+                            self.line
+                                .replace(range.clone(), &original, &mut self.changes);
+                        }
+                        None => self.line.delete_range(range.clone(), &mut self.changes),
+                    }
+                    self.line.set_pos(range.start);
+                }
+                _ => {
+                    // not overwritten during this replace: only move backward
+                    self.overwritten.clear();
+                    self.line.move_backward(1);
+                }
+            }
         }
+        self.refresh_line()
     }
 
     // Yank/paste `text` at current position.
@@ -431,7 +1900,7 @@ impl<H: Helper> State<'_, '_, H> {
         if let Anchor::After = anchor {
             self.line.move_forward(1);
         }
-        if self.line.yank(text, n, &mut self.changes).is_some() {
+        if self.insert_normalized(text, n) || self.line.yank(text, n, &mut self.changes).is_some() {
             if !input_state.is_emacs_mode() {
                 self.line.move_backward(1);
             }
@@ -441,15 +1910,23 @@ impl<H: Helper> State<'_, '_, H> {
         }
     }
 
-    // Delete previously yanked text and yank/paste `text` at current position.
-    pub fn edit_yank_pop(&mut self, yank_size: usize, text: &str) -> Result<()> {
+    // Delete previously yanked text and yank/paste the `current` kill-ring
+    // entry at current position, the `entries` being listed below the line.
+    pub fn edit_yank_pop(
+        &mut self,
+        yank_size: usize,
+        entries: &[&str],
+        current: usize,
+    ) -> Result<()> {
+        let text = entries[current];
         self.changes.begin();
         let result = if self
             .line
             .yank_pop(yank_size, text, &mut self.changes)
             .is_some()
         {
-            self.refresh_line()
+            let menu = self.yank_menu(entries, current);
+            self.refresh_line_with_msg(Some(&menu), CmdKind::Other)
         } else {
             Ok(())
         };
@@ -457,6 +1934,36 @@ impl<H: Helper> State<'_, '_, H> {
         result
     }
 
+    /// One row per kill-ring entry (truncated to the terminal width), around
+    /// the `current` one
+    fn yank_menu(&self, entries: &[&str], current: usize) -> String {
+        const ROWS: usize = 5;
+        let start = current
+            .saturating_sub(ROWS / 2)
+            .min(entries.len().saturating_sub(ROWS));
+        let max_width = self.out.get_columns().saturating_sub(4);
+        let mut menu = String::new();
+        for (i, entry) in entries.iter().enumerate().skip(start).take(ROWS) {
+            menu.push('\n');
+            menu.push_str(if i == current { "> " } else { "  " });
+            let mut width = 0;
+            for grapheme in entry.graphemes(true) {
+                let grapheme = match grapheme {
+                    "\n" | "\r\n" => "\u{21b5}",
+                    "\t" => " ",
+                    g => g,
+                };
+                width += self.layout.width(grapheme);
+                if width > max_width {
+                    menu.push('\u{2026}');
+                    break;
+                }
+                menu.push_str(grapheme);
+            }
+        }
+        menu
+    }
+
     /// Move cursor on the left.
     pub fn edit_move_backward(&mut self, n: RepeatCount) -> Result<()> {
         if self.line.move_backward(n) {
@@ -512,35 +2019,7 @@ impl<H: Helper> State<'_, '_, H> {
     }
 
     pub fn edit_kill(&mut self, mvt: &Movement, kill_ring: &mut KillRing) -> Result<()> {
-        struct Proxy<'p>(&'p mut Changeset, &'p mut KillRing);
-        let mut proxy = Proxy(&mut self.changes, kill_ring);
-        impl DeleteListener for Proxy<'_> {
-            fn start_killing(&mut self) {
-                self.1.start_killing();
-            }
-
-            fn delete(&mut self, idx: usize, string: &str, dir: Direction) {
-                self.0.delete(idx, string);
-                self.1.delete(idx, string, dir);
-            }
-
-            fn stop_killing(&mut self) {
-                self.1.stop_killing()
-            }
-        }
-        impl ChangeListener for Proxy<'_> {
-            fn insert_char(&mut self, idx: usize, c: char) {
-                self.0.insert_char(idx, c)
-            }
-
-            fn insert_str(&mut self, idx: usize, string: &str) {
-                self.0.insert_str(idx, string)
-            }
-
-            fn replace(&mut self, idx: usize, old: &str, new: &str) {
-                self.0.replace(idx, old, new)
-            }
-        }
+        let mut proxy = UndoAndKill(&mut self.changes, kill_ring);
         if self.line.kill(mvt, &mut proxy) {
             self.refresh_line()
         } else {
@@ -557,6 +2036,92 @@ impl<H: Helper> State<'_, '_, H> {
         self.refresh_line()
     }
 
+    /// Replace the whole input with `text`, the cursor at `pos` (moved back
+    /// to a char boundary, or to the end when after it)
+    pub fn edit_set_line(&mut self, text: &str, pos: usize) -> Result<()> {
+        let mut pos = pos.min(text.len());
+        while !text.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        self.changes.begin();
+        self.line.update(text, pos, &mut self.changes);
+        self.changes.end();
+        self.refresh_line()
+    }
+
+    /// Insert what the Tab key inserts when there is nothing to complete
+    pub fn edit_insert_tab(&mut self, insertion: TabInsertion, indent_size: u8) -> Result<()> {
+        let text = match insertion {
+            TabInsertion::Tab => "\t".to_owned(),
+            TabInsertion::Spaces(n) => " ".repeat(usize::from(n)),
+            TabInsertion::SmartIndent => self.smart_indent(usize::from(indent_size)),
+            TabInsertion::Nothing => return Ok(()),
+        };
+        if self.line.yank(&text, 1, &mut self.changes).is_some() {
+            self.refresh_line()?;
+        }
+        Ok(())
+    }
+
+    /// Indentation missing before the cursor (see [`TabInsertion::SmartIndent`])
+    fn smart_indent(&self, indent_size: usize) -> String {
+        let pos = self.line.pos();
+        let start = self.line[..pos].rfind('\n').map_or(0, |n| n + 1);
+        let before = &self.line[start..pos];
+        if start > 0 && before.trim_start().is_empty() {
+            let validator = self
+                .helper
+                .map_or(&() as &dyn Validator, |h| h as &dyn Validator);
+            // as if the newline ending the previous line was being inserted
+            if let Some(indent) = validator.indent(&self.line, start - 1) {
+                if let Some(missing) = indent.strip_prefix(before).filter(|m| !m.is_empty()) {
+                    return missing.to_owned();
+                }
+            }
+        }
+        let width = before.chars().count();
+        let indent_size = indent_size.max(1);
+        " ".repeat(indent_size - width % indent_size)
+    }
+
+    /// Insert a newline followed by the indentation computed by the validator
+    pub fn edit_insert_indented_newline(&mut self) -> Result<()> {
+        self.edit_insert_continuation(&Continuation::default(), true)
+    }
+
+    /// Insert a newline continued as specified by the validator
+    pub fn edit_insert_continuation(
+        &mut self,
+        continuation: &Continuation,
+        auto_indent: bool,
+    ) -> Result<()> {
+        let validator = self
+            .helper
+            .map_or(&() as &dyn Validator, |h| h as &dyn Validator);
+        let indent = match continuation.indent {
+            Some(ref indent) => indent.clone(),
+            None if auto_indent => validator
+                .indent(&self.line, self.line.pos())
+                .unwrap_or_default(),
+            None => String::new(),
+        };
+        let line_number = self.line[..self.line.pos()].matches('\n').count() + 1;
+        if self
+            .line
+            .yank(&format!("\n{indent}"), 1, &mut self.changes)
+            .is_none()
+        {
+            return Ok(());
+        }
+        if let Some(ref prompt) = continuation.prompt {
+            self.line_prompts.insert(line_number, prompt.clone());
+        }
+        match continuation.closer {
+            Some(ref closer) => self.refresh_line_with_msg(Some(closer), CmdKind::Other),
+            None => self.refresh_line(),
+        }
+    }
+
     /// Exchange the char before cursor with the character at cursor.
     pub fn edit_transpose_chars(&mut self) -> Result<()> {
         self.changes.begin();
@@ -605,8 +2170,64 @@ impl<H: Helper> State<'_, '_, H> {
         }
     }
 
-    pub fn edit_move_to(&mut self, cs: CharSearch, n: RepeatCount) -> Result<()> {
-        if self.line.move_to(cs, n) {
+    /// Moves the cursor to the same column `n` display lines above or below
+    pub fn edit_move_visual_line(&mut self, n: RepeatCount, dir: Direction) -> Result<bool> {
+        let cols = self.out.get_columns();
+        if self.line.move_to_visual_line(n, dir, cols, &self.layout) {
+            self.move_cursor(CmdKind::MoveCursor)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Moves the cursor to the start or the end of the display line
+    pub fn edit_move_visual_line_edge(&mut self, dir: Direction) -> Result<()> {
+        let cols = self.out.get_columns();
+        if self.line.move_to_visual_line_edge(dir, cols, &self.layout) {
+            self.move_cursor(CmdKind::MoveCursor)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn edit_move_to(&mut self, cs: CharSearch, n: RepeatCount) -> Result<()> {
+        if self.line.move_to(cs, n) {
+            self.move_cursor(CmdKind::MoveCursor)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn edit_move_to_mark(&mut self, mark: char, line_wise: bool) -> Result<()> {
+        if self.line.move_to_mark(mark, line_wise) {
+            self.move_cursor(CmdKind::MoveCursor)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move cursor to the byte position `pos`.
+    pub fn edit_move_to_position(&mut self, pos: usize) -> Result<()> {
+        if self.line.move_to_position(pos) {
+            self.move_cursor(CmdKind::MoveCursor)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move cursor to the `n`th blank line before or after the current line.
+    pub fn edit_move_to_paragraph(&mut self, n: RepeatCount, dir: Direction) -> Result<()> {
+        if self.line.move_to_paragraph(n, dir) {
+            self.move_cursor(CmdKind::MoveCursor)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move cursor to the first non-blank character of the `n`th line.
+    pub fn edit_move_to_line(&mut self, n: RepeatCount) -> Result<()> {
+        if self.line.move_to_line(n) {
             self.move_cursor(CmdKind::MoveCursor)
         } else {
             Ok(())
@@ -642,35 +2263,23 @@ impl<H: Helper> State<'_, '_, H> {
         if history.is_empty() {
             return Ok(());
         }
-        if self.ctx.history_index == history.len() {
-            if prev {
-                // Save the current edited line before overwriting it
-                self.backup();
-            } else {
-                return Ok(());
-            }
-        } else if self.ctx.history_index == 0 && prev {
+        if self.ctx.history_index == history.len() && !prev || self.ctx.history_index == 0 && prev {
             return Ok(());
         }
         let (idx, dir) = if prev {
             (self.ctx.history_index - 1, SearchDirection::Reverse)
         } else {
-            self.ctx.history_index += 1;
-            (self.ctx.history_index, SearchDirection::Forward)
+            (self.ctx.history_index + 1, SearchDirection::Forward)
         };
         if idx < history.len() {
             if let Some(r) = history.get(idx, dir)? {
-                let buf = r.entry;
-                self.ctx.history_index = r.idx;
-                self.changes.begin();
-                self.line.update(&buf, buf.len(), &mut self.changes);
-                self.changes.end();
+                self.recall_history_entry(r.idx, &r.entry, None)?;
             } else {
                 return Ok(());
             }
         } else {
             // Restore current edited line
-            self.restore();
+            self.recall_history_entry(idx, "", None)?;
         }
         self.refresh_line()
     }
@@ -686,60 +2295,330 @@ impl<H: Helper> State<'_, '_, H> {
         {
             return self.out.beep();
         }
-        if dir == SearchDirection::Reverse {
-            self.ctx.history_index -= 1;
+        let start = if dir == SearchDirection::Reverse {
+            self.ctx.history_index - 1
         } else {
-            self.ctx.history_index += 1;
-        }
-        if let Some(sr) = history.starts_with(
-            &self.line.as_str()[..self.line.pos()],
-            self.ctx.history_index,
-            dir,
-        )? {
-            self.ctx.history_index = sr.idx;
-            self.changes.begin();
-            self.line.update(&sr.entry, sr.pos, &mut self.changes);
-            self.changes.end();
+            self.ctx.history_index + 1
+        };
+        if let Some(sr) = history.starts_with(&self.line.as_str()[..self.line.pos()], start, dir)? {
+            self.recall_history_entry(sr.idx, &sr.entry, Some(sr.pos))?;
             self.refresh_line()
         } else {
             self.out.beep()
         }
     }
 
+    /// vi search: substitute the currently edited line with the nearest
+    /// history entry containing `search.pattern`, and highlight the match.
+    pub fn edit_vi_search(&mut self, search: &ViSearch) -> Result<()> {
+        let history = self.ctx.history;
+        let start = match search.direction {
+            SearchDirection::Reverse if self.ctx.history_index > 0 => self.ctx.history_index - 1,
+            SearchDirection::Forward if self.ctx.history_index + 1 < history.len() => {
+                self.ctx.history_index + 1
+            }
+            _ => return self.out.beep(),
+        };
+        let Some(sr) = history.search(&search.pattern, start, search.direction)? else {
+            return self.out.beep();
+        };
+        self.recall_history_entry(sr.idx, &sr.entry, Some(sr.pos))?;
+        let end = sr.pos + search.pattern.len();
+        let range = if self.line.is_char_boundary(end) {
+            sr.pos..end
+        } else {
+            sr.pos..sr.pos
+        };
+        let n = match search.offset {
+            SearchOffset::Start(n) => n,
+            SearchOffset::End(n) => {
+                self.line.set_pos(range.end);
+                self.line.move_backward(1);
+                n
+            }
+        };
+        if n > 0 {
+            self.line.move_forward(n.unsigned_abs());
+        } else {
+            self.line.move_backward(n.unsigned_abs());
+        }
+        self.hint = None;
+        self.highlight_char(CmdKind::Other);
+        self.search_match = Some(range);
+        self.refresh(None, Info::NoHint)
+    }
+
     /// Substitute the currently edited line with the first/last history entry.
     pub fn edit_history(&mut self, first: bool) -> Result<()> {
         let history = self.ctx.history;
         if history.is_empty() {
             return Ok(());
         }
-        if self.ctx.history_index == history.len() {
-            if first {
-                // Save the current edited line before overwriting it
-                self.backup();
-            } else {
-                return Ok(());
-            }
-        } else if self.ctx.history_index == 0 && first {
+        if self.ctx.history_index == history.len() && !first || self.ctx.history_index == 0 && first
+        {
             return Ok(());
         }
         if first {
             if let Some(r) = history.get(0, SearchDirection::Forward)? {
-                let buf = r.entry;
-                self.ctx.history_index = r.idx;
-                self.changes.begin();
-                self.line.update(&buf, buf.len(), &mut self.changes);
-                self.changes.end();
+                self.recall_history_entry(r.idx, &r.entry, None)?;
             } else {
                 return Ok(());
             }
         } else {
-            self.ctx.history_index = history.len();
             // Restore current edited line
-            self.restore();
+            self.recall_history_entry(history.len(), "", None)?;
         }
         self.refresh_line()
     }
 
+    /// Surround the text covered by movement with quotes or brackets
+    pub fn edit_surround(&mut self, mvt: &Movement, c: char) -> Result<()> {
+        self.changes.begin();
+        let succeed = self.line.surround(mvt, c, &mut self.changes);
+        self.changes.end();
+        if succeed {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Delete the quotes or brackets surrounding the cursor
+    pub fn edit_delete_surround(&mut self, c: char) -> Result<()> {
+        self.changes.begin();
+        let succeed = self.line.delete_surround(c, &mut self.changes);
+        self.changes.end();
+        if succeed {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Change the quotes or brackets surrounding the cursor
+    pub fn edit_change_surround(&mut self, old: char, new: char) -> Result<()> {
+        self.changes.begin();
+        let succeed = self.line.change_surround(old, new, &mut self.changes);
+        self.changes.end();
+        if succeed {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Start, cancel or apply `op` on the vi visual block selection
+    pub fn edit_block(&mut self, op: BlockOp, kill_ring: &mut KillRing) -> Result<()> {
+        if op == BlockOp::Select {
+            self.block_anchor = Some(self.line.pos());
+            return self.refresh_line();
+        }
+        let Some(anchor) = self.block_anchor.take() else {
+            return Ok(());
+        };
+        let (columns, ranges) = self.line.block(anchor, &self.layout);
+        let first = ranges[0].clone();
+        let text = ranges
+            .iter()
+            .map(|range| &self.line[range.clone()])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let line_number = self.line[..first.start].matches('\n').count();
+        let lines = line_number + 1..line_number + ranges.len();
+        match op {
+            BlockOp::Yank => {
+                kill_ring.kill(&text, Mode::Append);
+                self.line.set_pos(first.start);
+            }
+            BlockOp::Kill | BlockOp::Change => {
+                kill_ring.kill(&text, Mode::Append);
+                self.changes.begin();
+                for range in ranges.into_iter().rev() {
+                    self.line.delete_range(range, &mut self.changes);
+                }
+                self.changes.end();
+                self.line.set_pos(first.start);
+                if op == BlockOp::Change {
+                    self.block_insert = Some(BlockInsert {
+                        start: first.start,
+                        len: self.line.len(),
+                        column: columns.start,
+                        lines,
+                        pad: false,
+                    });
+                }
+            }
+            BlockOp::Insert(anchor) => {
+                let (mut start, column) = match anchor {
+                    Anchor::Before => (first.start, columns.start),
+                    Anchor::After => (first.end, columns.end),
+                };
+                let line_start = self.line[..first.start].rfind('\n').map_or(0, |n| n + 1);
+                if anchor == Anchor::After
+                    && self
+                        .line
+                        .column_to_pos(line_start, column, &self.layout)
+                        .is_none()
+                {
+                    // short first line
+                    let width = self.line.column(first.end, &self.layout);
+                    let padding = " ".repeat(usize::from(column - width));
+                    self.line.insert_str(start, &padding, &mut self.changes);
+                    start += padding.len();
+                }
+                self.line.set_pos(start);
+                self.block_insert = Some(BlockInsert {
+                    start,
+                    len: self.line.len(),
+                    column,
+                    lines,
+                    pad: anchor == Anchor::After,
+                });
+            }
+            BlockOp::Select | BlockOp::Cancel => {}
+        }
+        self.refresh_line()
+    }
+
+    /// Repeat the text inserted on the first line of a vi visual block on the
+    /// other lines
+    pub fn edit_block_insert_end(&mut self) -> Result<()> {
+        let Some(insert) = self.block_insert.take() else {
+            return Ok(());
+        };
+        let Some(text) = self
+            .line
+            .len()
+            .checked_sub(insert.len)
+            .and_then(|len| self.line.get(insert.start..insert.start + len))
+            .filter(|text| !text.is_empty() && !text.contains('\n'))
+            .map(str::to_owned)
+        else {
+            return Ok(());
+        };
+        self.changes.begin();
+        for line_number in insert.lines {
+            let Some(start) = self
+                .line
+                .match_indices('\n')
+                .nth(line_number - 1)
+                .map(|(n, _)| n + 1)
+            else {
+                break;
+            };
+            if let Some(pos) = self.line.column_to_pos(start, insert.column, &self.layout) {
+                self.line.insert_str(pos, &text, &mut self.changes);
+            } else if insert.pad {
+                let end = self.line[start..]
+                    .find('\n')
+                    .map_or(self.line.len(), |n| start + n);
+                let width = self.line.column(end, &self.layout);
+                let padding = " ".repeat(usize::from(insert.column - width));
+                self.line
+                    .insert_str(end, &(padding + &text), &mut self.changes);
+            }
+        }
+        self.changes.end();
+        self.refresh_line()
+    }
+
+    /// Whether extra cursors are placed (multi-cursor editing)
+    pub fn has_cursors(&self) -> bool {
+        !self.cursors.is_empty()
+    }
+
+    /// Add or remove extra cursors
+    pub fn edit_multi_cursor(&mut self, op: CursorOp) -> Result<()> {
+        let pos = self.line.pos();
+        let last = self.cursors.iter().copied().fold(pos, usize::max);
+        match op {
+            CursorOp::AddBelow => {
+                let Some(start) = self.line[last..].find('\n').map(|n| last + n + 1) else {
+                    return self.out.beep();
+                };
+                let column = self.line.column(last, &self.layout);
+                let end = self.line[start..]
+                    .find('\n')
+                    .map_or(self.line.len(), |n| start + n);
+                let below = self
+                    .line
+                    .column_to_pos(start, column, &self.layout)
+                    .unwrap_or(end);
+                self.cursors.push(below);
+            }
+            CursorOp::AddOnMatch => {
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                let start = self.line[..pos]
+                    .char_indices()
+                    .rev()
+                    .find(|&(_, c)| !is_word(c))
+                    .map_or(0, |(i, c)| i + c.len_utf8());
+                let end = self.line[pos..]
+                    .find(|c| !is_word(c))
+                    .map_or(self.line.len(), |n| pos + n);
+                if start == end {
+                    return self.out.beep();
+                }
+                let offset = pos - start;
+                let word = &self.line[start..end];
+                let mut matches = self.line.match_indices(word).map(|(i, _)| i + offset);
+                let next = matches
+                    .clone()
+                    .find(|&i| i > last)
+                    .or_else(|| matches.next())
+                    .filter(|&i| i != pos && !self.cursors.contains(&i));
+                match next {
+                    Some(next) => self.cursors.push(next),
+                    None => return self.out.beep(),
+                }
+            }
+            CursorOp::Clear => {
+                if self.cursors.is_empty() {
+                    return Ok(());
+                }
+                self.cursors.clear();
+            }
+        }
+        self.refresh_line()
+    }
+
+    /// Insert `text` at every cursor
+    pub fn edit_insert_at_cursors(&mut self, text: &str) -> Result<()> {
+        self.changes.begin();
+        let inserted = self
+            .line
+            .insert_at_cursors(&mut self.cursors, text, &mut self.changes);
+        self.changes.end();
+        if inserted {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Delete `n` graphemes in direction `dir` at every cursor
+    pub fn edit_delete_at_cursors(&mut self, n: RepeatCount, dir: Direction) -> Result<()> {
+        self.changes.begin();
+        let deleted = self
+            .line
+            .delete_at_cursors(&mut self.cursors, n, dir, &mut self.changes);
+        self.changes.end();
+        if deleted {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move every cursor by `n` graphemes in direction `dir`
+    pub fn edit_move_cursors(&mut self, n: RepeatCount, dir: Direction) -> Result<()> {
+        if self.line.move_cursors(&mut self.cursors, n, dir) {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
     /// Change the indentation of the lines covered by movement
     pub fn edit_indent(&mut self, mvt: &Movement, amount: u8, dedent: bool) -> Result<()> {
         if self.line.indent(mvt, amount, dedent, &mut self.changes) {
@@ -761,24 +2640,343 @@ pub fn init_state<'out, H: Helper>(
     State {
         out,
         prompt: "",
+        mode_prompt: None,
+        fitted_prompt: None,
         prompt_size: Position::default(),
         line: LineBuffer::init(line, pos),
         layout: Layout::default(),
-        saved_line_for_history: LineBuffer::with_capacity(100),
+        saved_entries: HashMap::new(),
         byte_buffer: [0; 4],
-        changes: Changeset::new(),
+        changes: Changes::default(),
         helper,
         ctx: Context::new(history),
         hint: Some(Box::new("hint".to_owned())),
         highlight_char: false,
+        search_match: None,
+        overwritten: Vec::new(),
+        horizontal_scroll: None,
+        block_anchor: None,
+        block_insert: None,
+        line_numbers: false,
+        line_prompts: HashMap::new(),
+        vertical_scroll: None,
+        cursors: Vec::new(),
+        preedit: String::new(),
+        bidi: false,
+        normalization: None,
+        prompt_overflow: PromptOverflow::default(),
+        coalesce: false,
+        dirty: false,
+        print_interval: None,
+        last_print: None,
+        queued_print: String::new(),
+        queued_eprint: String::new(),
+        region: String::new(),
+        pending_validation: None,
+        resolved_validation: None,
+        validation_msg: false,
+        validated: false,
+        render_counters: None,
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::init_state;
+    use super::{display_line, init_state};
+    use crate::highlight::{Highlighter, PromptMode};
     use crate::history::{DefaultHistory, History};
+    use crate::keymap::Refresher;
+    use crate::test::{validate_with, TestHelper};
     use crate::tty::Sink;
+    use crate::validate::Validator;
+    use std::borrow::Cow::{self, Borrowed, Owned};
+
+    #[test]
+    fn set_prompt_mode() {
+        struct ModePrompt;
+        impl Highlighter for ModePrompt {
+            fn prompt_with_mode<'p>(&self, prompt: &'p str, mode: PromptMode) -> Cow<'p, str> {
+                match mode {
+                    PromptMode::ViCommand => Owned(format!("[N] {prompt}")),
+                    _ => Borrowed(prompt),
+                }
+            }
+        }
+
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper = TestHelper((), ModePrompt);
+        let mut s = init_state(&mut out, "", 0, Some(&helper), &history);
+        assert!(!s.set_prompt_mode(PromptMode::ViInsert));
+        assert!(s.set_prompt_mode(PromptMode::ViCommand));
+        assert_eq!("[N] ", s.default_prompt());
+        assert_eq!(4, s.prompt_size.col);
+        assert!(!s.set_prompt_mode(PromptMode::ViCommand));
+        assert!(s.set_prompt_mode(PromptMode::ViInsert));
+        assert_eq!("", s.default_prompt());
+        assert_eq!(0, s.prompt_size.col);
+    }
+
+    #[test]
+    fn prompt_overflow() {
+        use crate::config::PromptOverflow;
+        struct ShrinkPrompt;
+        impl Highlighter for ShrinkPrompt {
+            fn shrink_prompt<'p>(&self, prompt: &'p str, _width: u16) -> Cow<'p, str> {
+                Owned(prompt.replace("/home/user", "~"))
+            }
+        }
+
+        let prompt = concat!(
+            "/home/user/",
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "/src> "
+        );
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper = TestHelper((), ShrinkPrompt);
+        let mut s = init_state(&mut out, "", 0, Some(&helper), &history);
+        s.prompt = prompt;
+        s.set_prompt_overflow(PromptOverflow::Wrap);
+        assert_eq!(prompt, s.default_prompt());
+
+        s.set_prompt_overflow(PromptOverflow::TruncateLeft);
+        assert_eq!(format!("\u{2026}{}", &prompt[9..]), s.default_prompt());
+        assert_eq!(79, s.default_prompt().chars().count());
+
+        s.set_prompt_overflow(PromptOverflow::Shrink);
+        assert_eq!(format!("~{}", &prompt[10..]), s.default_prompt());
+    }
+
+    #[test]
+    fn continuation_prompt() {
+        struct ContinuationPrompt;
+        impl Highlighter for ContinuationPrompt {
+            fn continuation_prompt(&self, _line_number: usize) -> Cow<'_, str> {
+                Borrowed("... ")
+            }
+        }
+
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper = TestHelper((), ContinuationPrompt);
+        let mut s = init_state(&mut out, "ab\ncd\n", 4, Some(&helper), &history);
+        let prompts = s.continuation_prompts();
+        let display = display_line(&s.line, &prompts);
+        assert_eq!("ab\n... cd\n... ", display.as_str());
+        assert_eq!(8, display.pos());
+        s.refresh_line().unwrap();
+        assert_eq!(vec![4, 4], s.layout.continuation_prompt_cols);
+        assert_eq!(4, s.layout.line_offset(2));
+        assert_eq!(0, s.layout.line_offset(0));
+    }
+
+    #[test]
+    fn line_numbers() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let line = "a\n".repeat(9) + "b";
+        let mut s = init_state(&mut out, &line, 0, helper.as_ref(), &history);
+        s.refresh_line().unwrap();
+        assert!(s.continuation_prompts().is_empty());
+        assert_eq!(0, s.layout.prompt_size.col);
+
+        s.enable_line_numbers();
+        s.refresh_line().unwrap();
+        let prompts = s.continuation_prompts();
+        assert_eq!(9, prompts.len());
+        assert_eq!(" 2 ", prompts[0]);
+        assert_eq!("10 ", prompts[8]);
+        assert_eq!(3, s.layout.prompt_size.col);
+        assert_eq!(vec![3; 9], s.layout.continuation_prompt_cols);
+
+        s.line
+            .update("single", 0, &mut crate::line_buffer::NoListener);
+        s.refresh_line().unwrap();
+        assert!(s.continuation_prompts().is_empty());
+        assert_eq!(0, s.layout.prompt_size.col);
+    }
+
+    #[test]
+    fn vertical_scroll() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let line = (0..30)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut s = init_state(&mut out, &line, line.len(), helper.as_ref(), &history);
+        s.refresh_line().unwrap();
+        assert_eq!(Some(7..30), s.vertical_scroll);
+        assert_eq!(0, s.layout.line_offset(0));
+
+        s.edit_move_buffer_start().unwrap();
+        assert_eq!(Some(0..23), s.vertical_scroll);
+
+        s.edit_move_line_down(5).unwrap();
+        assert_eq!(5, s.line.pos() / 2);
+        assert_eq!(Some(0..23), s.vertical_scroll);
+        s.edit_move_buffer_end(crate::highlight::CmdKind::MoveCursor)
+            .unwrap();
+        assert_eq!(Some(7..30), s.vertical_scroll);
+
+        s.line
+            .update("short", 0, &mut crate::line_buffer::NoListener);
+        s.refresh_line().unwrap();
+        assert_eq!(None, s.vertical_scroll);
+    }
+
+    #[test]
+    fn fold_paste() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "x = ", 4, helper.as_ref(), &history);
+        let paste = "1\n2\n3\n";
+        s.fold_paste();
+        s.line.yank(paste, 1, &mut s.changes);
+        let folded = s.fold_pastes().unwrap();
+        assert_eq!("x = \u{27e8} 3 lines pasted \u{27e9}", folded.as_str());
+        assert_eq!(folded.len(), folded.pos());
+        assert_eq!(1, s.changes.folds.len());
+
+        s.edit_move_buffer_start().unwrap();
+        s.edit_insert('(', 1).unwrap();
+        assert_eq!(Some(5), s.changes.folds.first().map(|(start, _)| *start));
+
+        s.edit_move_buffer_end(crate::highlight::CmdKind::MoveCursor)
+            .unwrap();
+        s.edit_move_line_up(1).unwrap();
+        assert!(s.changes.folds.is_empty());
+        assert!(s.fold_pastes().is_none());
+    }
+
+    #[test]
+    fn fold_duplicated_paste() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+        let paste = "1\n2\n3\n";
+        // the same text pasted unfolded then folded
+        s.line.yank(paste, 1, &mut s.changes);
+        s.fold_paste();
+        s.line.yank(paste, 1, &mut s.changes);
+        assert_eq!(vec![(6, paste.to_owned())], s.changes.folds);
+        let folded = s.fold_pastes().unwrap();
+        assert_eq!("1\n2\n3\n\u{27e8} 3 lines pasted \u{27e9}", folded.as_str());
+
+        // edits before the fold shift it, not onto the first copy
+        s.edit_move_buffer_start().unwrap();
+        s.edit_insert('x', 1).unwrap();
+        assert_eq!(Some(7), s.changes.folds.first().map(|(start, _)| *start));
+        let mut kill_ring = crate::KillRing::new(1);
+        s.edit_kill(&crate::Movement::ForwardChar(2), &mut kill_ring)
+            .unwrap();
+        assert_eq!(Some(5), s.changes.folds.first().map(|(start, _)| *start));
+        let folded = s.fold_pastes().unwrap();
+        assert_eq!("x2\n3\n\u{27e8} 3 lines pasted \u{27e9}", folded.as_str());
+
+        // an edit inside the fold expands it
+        s.line.insert_str(6, "x", &mut s.changes);
+        assert!(s.changes.folds.is_empty());
+    }
+
+    #[test]
+    fn preedit() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "ac", 1, helper.as_ref(), &history);
+        s.preedit("ka".to_owned()).unwrap();
+        // displayed before the cursor, but not inserted
+        assert_eq!(3, s.layout.cursor.col);
+        assert_eq!(("ac", 1), (s.line.as_str(), s.line.pos()));
+        assert!(s.changes.is_empty());
+        s.preedit(String::new()).unwrap();
+        assert_eq!(1, s.layout.cursor.col);
+    }
+
+    #[test]
+    fn bidi() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        // cursor on the first (rightmost) Hebrew letter
+        let mut s = init_state(
+            &mut out,
+            "ls \u{5d0}\u{5d1}\u{5d2}",
+            3,
+            helper.as_ref(),
+            &history,
+        );
+        s.refresh_line().unwrap();
+        assert_eq!(3, s.layout.cursor.col);
+        s.enable_bidi();
+        s.refresh_line().unwrap();
+        assert_eq!(7, s.layout.cursor.col);
+        // stored in logical order
+        assert_eq!(
+            ("ls \u{5d0}\u{5d1}\u{5d2}", 3),
+            (s.line.as_str(), s.line.pos())
+        );
+    }
+
+    #[test]
+    fn normalization() {
+        use rustyline_core::normalize::NormalizationForm;
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "cafe", 4, helper.as_ref(), &history);
+        s.edit_insert('\u{301}', 1).unwrap();
+        assert_eq!(("cafe\u{301}", 6), (s.line.as_str(), s.line.pos()));
+
+        let mut s = init_state(&mut out, "cafe", 4, helper.as_ref(), &history);
+        s.set_normalization(Some(NormalizationForm::Nfc));
+        // combined with the `e` before the cursor
+        s.edit_insert('\u{301}', 1).unwrap();
+        assert_eq!(("caf\u{e9}", 5), (s.line.as_str(), s.line.pos()));
+        s.set_normalization(Some(NormalizationForm::Nfd));
+        s.edit_insert('\u{e0}', 1).unwrap();
+        // with the `é` before it
+        assert_eq!("cafe\u{301}a\u{300}", s.line.as_str());
+        assert_eq!("e\u{301}", s.normalize("\u{e9}"));
+    }
+
+    #[test]
+    fn horizontal_scroll() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let line = "a".repeat(100);
+        let mut s = init_state(&mut out, &line, 50, helper.as_ref(), &history);
+        let prompt_size = s.prompt_size;
+        assert_eq!(None, s.scroll_window(prompt_size));
+
+        s.enable_horizontal_scroll();
+        s.refresh_line().unwrap();
+        assert_eq!(Some(0..78), s.horizontal_scroll);
+        assert_eq!(79, s.layout.end.col);
+        assert_eq!(0, s.layout.end.row);
+
+        s.edit_move_end().unwrap();
+        assert_eq!(Some(23..100), s.horizontal_scroll);
+        assert_eq!(78, s.layout.cursor.col);
+
+        s.line.set_pos(10);
+        s.move_cursor(crate::highlight::CmdKind::MoveCursor)
+            .unwrap();
+        assert_eq!(Some(10..87), s.horizontal_scroll);
+        assert_eq!(1, s.layout.cursor.col);
+
+        s.line
+            .update("short", 5, &mut crate::line_buffer::NoListener);
+        s.refresh_line().unwrap();
+        assert_eq!(Some(0..5), s.horizontal_scroll);
+    }
 
     #[test]
     fn edit_history_next() {
@@ -797,25 +2995,349 @@ mod test {
         }
 
         s.edit_history_next(true).unwrap();
-        assert_eq!(line, s.saved_line_for_history.as_str());
+        assert_eq!(line, s.saved_entries[&history.len()].line);
         assert_eq!(1, s.ctx.history_index);
         assert_eq!("line1", s.line.as_str());
 
         for _ in 0..2 {
             s.edit_history_next(true).unwrap();
-            assert_eq!(line, s.saved_line_for_history.as_str());
+            assert_eq!(line, s.saved_entries[&history.len()].line);
             assert_eq!(0, s.ctx.history_index);
             assert_eq!("line0", s.line.as_str());
         }
 
         s.edit_history_next(false).unwrap();
-        assert_eq!(line, s.saved_line_for_history.as_str());
+        assert_eq!(line, s.saved_entries[&history.len()].line);
         assert_eq!(1, s.ctx.history_index);
         assert_eq!("line1", s.line.as_str());
 
         s.edit_history_next(false).unwrap();
-        // assert_eq!(line, s.saved_line_for_history);
+        assert!(s.saved_entries.is_empty());
         assert_eq!(2, s.ctx.history_index);
         assert_eq!(line, s.line.as_str());
     }
+
+    #[test]
+    fn edited_history_entry() {
+        let mut out = Sink::default();
+        let mut history = DefaultHistory::new();
+        history.add("line0").unwrap();
+        history.add("line1").unwrap();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "current", 7, helper.as_ref(), &history);
+        s.ctx.history_index = history.len();
+
+        s.edit_history_next(true).unwrap();
+        s.edit_insert('!', 1).unwrap();
+        assert_eq!("line1!", s.line.as_str());
+
+        s.edit_history_next(true).unwrap();
+        assert_eq!("line0", s.line.as_str());
+        s.edit_history_next(false).unwrap();
+        assert_eq!("line1!", s.line.as_str());
+        // undo state is kept too
+        assert!(s.changes.undo(&mut s.line, 1));
+        assert_eq!("line1", s.line.as_str());
+
+        s.edit_history_next(false).unwrap();
+        assert_eq!("current", s.line.as_str());
+    }
+
+    #[test]
+    fn undo_history_recall() {
+        let mut out = Sink::default();
+        let mut history = DefaultHistory::new();
+        history.add("line0").unwrap();
+        history.add("line1").unwrap();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "current", 7, helper.as_ref(), &history);
+        s.ctx.history_index = history.len();
+
+        s.edit_history_next(true).unwrap();
+        assert_eq!("line1", s.line.as_str());
+        // the recall is a single change
+        assert!(s.changes.undo(&mut s.line, 1));
+        assert_eq!("current", s.line.as_str());
+        assert!(s.changes.redo(&mut s.line, 1));
+        assert_eq!("line1", s.line.as_str());
+
+        s.edit_history_next(true).unwrap();
+        assert!(s.changes.undo(&mut s.line, 1));
+        assert_eq!("line1", s.line.as_str());
+        assert_eq!(0, s.ctx.history_index);
+    }
+
+    #[test]
+    fn search_keeps_edited_history_entry() {
+        let mut out = Sink::default();
+        let mut history = DefaultHistory::new();
+        history.add("line0").unwrap();
+        history.add("line1").unwrap();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "current", 7, helper.as_ref(), &history);
+        s.ctx.history_index = history.len();
+
+        s.edit_history_next(true).unwrap();
+        s.edit_insert('!', 1).unwrap();
+        s.edit_history_next(true).unwrap();
+        // a search match shows the stored entry
+        s.recall_history_entry(1, "line1", Some(0)).unwrap();
+        assert_eq!(("line1", 0), (s.line.as_str(), s.line.pos()));
+        s.edit_history_next(true).unwrap();
+        assert_eq!("line0", s.line.as_str());
+        // but in-progress edits are kept
+        s.edit_history_next(false).unwrap();
+        assert_eq!("line1!", s.line.as_str());
+    }
+
+    #[test]
+    fn coalesce_refresh() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+        s.coalesce_refresh(true);
+        s.edit_insert('a', 1).unwrap();
+        s.edit_insert('b', 1).unwrap();
+        assert_eq!("ab", s.line.as_str());
+        assert!(s.dirty);
+        assert_eq!(0, s.layout.end.col);
+
+        s.coalesce_refresh(false);
+        s.flush_refresh().unwrap();
+        assert!(!s.dirty);
+        assert_eq!(2, s.layout.end.col);
+        assert_eq!(2, s.layout.cursor.col);
+    }
+
+    #[test]
+    fn yank_menu() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+        let long = "x".repeat(100);
+        let entries = ["a\nb", &long, "c", "d", "e", "f"];
+        let menu = s.yank_menu(&entries, 1);
+        assert_eq!(
+            format!(
+                "\n  a\u{21b5}b\n> {}\u{2026}\n  c\n  d\n  e",
+                "x".repeat(76)
+            ),
+            menu
+        );
+        assert!(s.yank_menu(&entries, 5).ends_with("\n> f"));
+    }
+
+    #[test]
+    fn validation_message() {
+        use crate::validate::ValidationResult;
+
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper = Some(validate_with(|_| {
+            Ok(ValidationResult::Invalid(Some(
+                "error: line 1\n  expected ')'".to_owned(),
+            )))
+        }));
+        let mut s = init_state(&mut out, "f(x", 3, helper.as_ref(), &history);
+        s.refresh_line().unwrap();
+        let end = s.layout.end;
+        s.validate().unwrap();
+        assert!(s.validation_msg);
+        assert!(s.layout.cursor < s.layout.end);
+        // cleared by the next keystroke
+        s.edit_move_backward(1).unwrap();
+        assert!(!s.validation_msg);
+        assert_eq!(end, s.layout.end);
+    }
+
+    #[test]
+    fn live_validation() {
+        use crate::validate::{MatchingBracketValidator, ValidationContext, ValidationResult};
+
+        struct LiveValidator(MatchingBracketValidator);
+        impl Validator for LiveValidator {
+            fn validate(&self, ctx: &mut ValidationContext) -> crate::Result<ValidationResult> {
+                self.0.validate(ctx)
+            }
+
+            fn validate_while_typing(&self) -> bool {
+                true
+            }
+        }
+
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper = Some(TestHelper(
+            LiveValidator(MatchingBracketValidator::new()),
+            (),
+        ));
+        let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+        assert_eq!(None, s.live_validation_delay());
+        s.edit_insert('(', 1).unwrap();
+        assert!(s.live_validation_delay().is_some());
+        // incomplete: no indicator
+        s.validate_live().unwrap();
+        assert!(!s.validation_msg);
+        assert_eq!(None, s.live_validation_delay());
+        // validated again after the next edit
+        s.edit_insert(')', 1).unwrap();
+        assert!(s.live_validation_delay().is_some());
+        s.validate_live().unwrap();
+        assert!(s.validation_msg);
+        assert_eq!(None, s.live_validation_delay());
+        s.edit_insert(')', 1).unwrap();
+        assert!(!s.validation_msg);
+        s.validate_live().unwrap();
+        assert!(s.validation_msg);
+    }
+
+    #[test]
+    fn continuation_prompt_from_validator() {
+        use crate::validate::Continuation;
+
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper = Some(());
+        let mut s = init_state(&mut out, "f(", 2, helper.as_ref(), &history);
+        let continuation = Continuation::new().indent("  ").prompt(".. ");
+        s.edit_insert_continuation(&continuation, false).unwrap();
+        assert_eq!("f(\n  ", s.line.as_str());
+        s.edit_insert_continuation(&Continuation::new(), false)
+            .unwrap();
+        assert_eq!("f(\n  \n", s.line.as_str());
+        assert_eq!(vec![".. ", ""], s.continuation_prompts());
+    }
+
+    #[test]
+    fn batch_external_print() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+        s.external_print("first".to_owned()).unwrap();
+        assert_eq!(None, s.external_print_delay());
+
+        s.set_external_print_interval(Some(10_000));
+        s.external_print("second".to_owned()).unwrap();
+        assert_eq!(None, s.external_print_delay());
+        s.external_print("third".to_owned()).unwrap();
+        s.external_print("fourth\n".to_owned()).unwrap();
+        assert_eq!("third\nfourth\n", s.queued_print);
+        assert!(s.external_print_delay().is_some_and(|delay| delay > 0));
+
+        s.flush_external_print().unwrap();
+        assert!(s.queued_print.is_empty());
+        assert_eq!(None, s.external_print_delay());
+    }
+
+    #[test]
+    fn external_region() {
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "line", 4, helper.as_ref(), &history);
+        s.external_region(vec!["[==>  ] 50%".to_owned(), "eta 1s".to_owned()])
+            .unwrap();
+        assert_eq!("[==>  ] 50%\neta 1s\n", s.region);
+        // the region is displayed as the first rows of the prompt
+        assert_eq!(s.region.len(), usize::from(s.layout.prompt_size.col));
+
+        s.hide_region().unwrap();
+        assert!(s.region.is_empty());
+        assert_eq!(0, s.layout.prompt_size.col);
+    }
+
+    #[test]
+    fn region_highlight() {
+        use super::Region;
+        use crate::highlight::Highlighter;
+        use std::borrow::Cow;
+
+        struct Bold;
+        impl Highlighter for Bold {
+            fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+                &'s self,
+                prompt: &'p str,
+                _: bool,
+            ) -> Cow<'b, str> {
+                Cow::Owned(format!("\x1b[1m{prompt}\x1b[0m"))
+            }
+        }
+        let region = Region {
+            highlighter: &Bold,
+            len: 4,
+        };
+        assert_eq!(
+            "50%\n\x1b[1m> \x1b[0m",
+            region.highlight_prompt("50%\n> ", true)
+        );
+        let region = Region {
+            highlighter: &(),
+            len: 4,
+        };
+        assert!(matches!(
+            region.highlight_prompt("50%\n> ", true),
+            Cow::Borrowed("50%\n> ")
+        ));
+    }
+
+    #[test]
+    fn margin_highlight() {
+        use super::Margin;
+        use crate::line_buffer::LineBuffer;
+        use std::cell::Cell;
+
+        // strings (which may span several lines) in green
+        #[derive(Default)]
+        struct Strings(Cell<usize>);
+        impl Highlighter for Strings {
+            fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+                self.0.set(line.len());
+                let mut highlighted = String::new();
+                let mut in_string = false;
+                for c in line.chars() {
+                    if c == '"' && !in_string {
+                        highlighted.push_str("\x1b[32m");
+                    }
+                    highlighted.push(c);
+                    if c == '"' && in_string {
+                        highlighted.push_str("\x1b[0m");
+                    }
+                    in_string ^= c == '"';
+                }
+                Owned(highlighted)
+            }
+        }
+        let text = (0..300)
+            .map(|i| match i {
+                100 => format!("\"{i}"),
+                200 => format!("{i}\""),
+                _ => format!("{i:03}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        // 4 bytes per line but the quoted ones
+        let offset = |line: usize| line * 4 + usize::from(line > 100) + usize::from(line > 200);
+        let line = LineBuffer::init(&text, offset(155));
+        let strings = Strings::default();
+        let margin = Margin {
+            highlighter: &strings,
+            line: &line,
+            context: offset(50)..offset(260) - 1,
+            visible: offset(150)..offset(153) - 1,
+        };
+        assert_eq!("\x1b[32m150\n151\n152\x1b[0m", margin.highlight("", 0));
+        assert_eq!(offset(260) - 1 - offset(50), strings.0.get());
+        let line = LineBuffer::init(&text, offset(200));
+        let margin = Margin {
+            highlighter: &strings,
+            line: &line,
+            context: offset(190)..offset(210) - 1,
+            visible: offset(199)..offset(202) - 1,
+        };
+        // the start of the string is out of the margin
+        assert_eq!("199\n200\x1b[32m\"\n201\x1b[0m", margin.highlight("", 0));
+    }
 }
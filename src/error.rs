@@ -84,6 +84,7 @@ pub enum Signal {
 }
 
 #[cfg(unix)]
+#[cfg_attr(feature = "test-terminal", allow(dead_code))]
 impl Signal {
     pub(crate) fn from(b: u8) -> Self {
         match b {
@@ -0,0 +1,6 @@
+//! Entry points for fuzzing the terminal input parsers (`fuzzing` feature).
+//!
+//! Terminal input is decoded as it would be by
+//! [`Editor::readline`](crate::Editor::readline): malformed input must neither
+//! panic nor hang. See the `fuzz` directory for the `cargo fuzz` targets.
+pub use crate::tty::{decode_keys, decode_paste};
@@ -1,8 +1,10 @@
 //! Syntax highlighting
 
 use crate::config::CompletionType;
+use crate::layout::Unit;
 use std::borrow::Cow::{self, Borrowed, Owned};
 use std::cell::Cell;
+use std::ops::Range;
 
 /// Describe which kind of action has been triggering the call to
 /// [`Highlighter`].
@@ -17,6 +19,47 @@ pub enum CmdKind {
     ForcedRefresh,
 }
 
+/// Current editing mode, given to [`Highlighter::prompt_with_mode`] to render
+/// a mode indicator.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PromptMode {
+    /// Emacs mode
+    Emacs,
+    /// Vi insert mode
+    ViInsert,
+    /// Vi command (normal) mode
+    ViCommand,
+    /// Vi replace (overwrite) mode
+    ViReplace,
+    /// Incremental history search
+    Search,
+}
+
+/// Terminal background, given to [`Highlighter::set_background`] so that
+/// colors can be chosen to remain readable.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Background {
+    /// Dark background (light text)
+    Dark,
+    /// Light background (dark text)
+    Light,
+}
+
+impl Background {
+    /// Classify a background color from its RGB components
+    #[must_use]
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        // perceived brightness (ITU-R BT.601)
+        let luma = 299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b);
+        if luma > 1000 * 255 / 2 {
+            Self::Light
+        } else {
+            Self::Dark
+        }
+    }
+}
+
 /// Syntax highlighter with [ANSI color](https://en.wikipedia.org/wiki/ANSI_escape_code#SGR_(Select_Graphic_Rendition)_parameters).
 ///
 /// Currently, the highlighted version *must* have the same display width as
@@ -41,11 +84,96 @@ pub trait Highlighter {
         let _ = default;
         Borrowed(prompt)
     }
+    /// Takes the `prompt` and the current editing `mode` and
+    /// returns the prompt to display (with a mode indicator like `[N] `).
+    ///
+    /// Called when the mode changes, even if colors are disabled.
+    /// Unlike [`Highlighter::highlight_prompt`], the display width may change.
+    fn prompt_with_mode<'p>(&self, prompt: &'p str, mode: PromptMode) -> Cow<'p, str> {
+        let _ = mode;
+        Borrowed(prompt)
+    }
+    /// Returns the prompt to display at the start of the `line_number`th
+    /// continuation line (`1` for the second line of a multi-line input,
+    /// like `PS2`).
+    ///
+    /// Called even if colors are disabled. The returned prompt is then
+    /// highlighted by [`Highlighter::highlight_prompt`].
+    fn continuation_prompt(&self, line_number: usize) -> Cow<'_, str> {
+        let _ = line_number;
+        Borrowed("")
+    }
+    /// Returns a shorter version of the `prompt` (e.g. with abbreviated path
+    /// segments) when one of its lines is wider than `width` columns and
+    /// [`PromptOverflow::Shrink`](crate::PromptOverflow::Shrink) is
+    /// configured.
+    ///
+    /// Called even if colors are disabled. Lines still too wide are then
+    /// truncated on the left.
+    fn shrink_prompt<'p>(&self, prompt: &'p str, width: Unit) -> Cow<'p, str> {
+        let _ = width;
+        Borrowed(prompt)
+    }
+    /// Takes the currently edited `line` and the `range` of text matched by a
+    /// vi search (`/`, `?`, `n`, `N`) and returns the highlighted version
+    /// (reverse video by default).
+    ///
+    /// Replaces [`Highlighter::highlight`] until the next command.
+    fn highlight_search_match<'l>(&self, line: &'l str, range: Range<usize>) -> Cow<'l, str> {
+        Owned(format!(
+            "{}\x1b[7m{}\x1b[27m{}",
+            &line[..range.start],
+            &line[range.clone()],
+            &line[range.end..]
+        ))
+    }
+    /// Takes the displayed `line` and the `range` of the text being composed
+    /// with an input method (IME preedit, not yet inserted) and returns the
+    /// highlighted version (underlined by default).
+    ///
+    /// Replaces [`Highlighter::highlight`] until the composition is committed
+    /// or cancelled.
+    fn highlight_preedit<'l>(&self, line: &'l str, range: Range<usize>) -> Cow<'l, str> {
+        Owned(format!(
+            "{}\x1b[4m{}\x1b[24m{}",
+            &line[..range.start],
+            &line[range.clone()],
+            &line[range.end..]
+        ))
+    }
+    /// Takes the currently edited `line` and the `ranges` of text selected in
+    /// vi visual block mode (one per line) and returns the highlighted version
+    /// (reverse video by default).
+    ///
+    /// Replaces [`Highlighter::highlight`] while the selection is active.
+    fn highlight_selection<'l>(&self, line: &'l str, ranges: &[Range<usize>]) -> Cow<'l, str> {
+        let mut highlighted = String::with_capacity(line.len() + 9 * ranges.len());
+        let mut pos = 0;
+        for range in ranges.iter().filter(|range| !range.is_empty()) {
+            highlighted.push_str(&line[pos..range.start]);
+            highlighted.push_str("\x1b[7m");
+            highlighted.push_str(&line[range.clone()]);
+            highlighted.push_str("\x1b[27m");
+            pos = range.end;
+        }
+        highlighted.push_str(&line[pos..]);
+        Owned(highlighted)
+    }
     /// Takes the `hint` and
     /// returns the highlighted version (with ANSI color).
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
         Borrowed(hint)
     }
+    /// Takes the validation `msg` displayed below the input (possibly on
+    /// several lines) and returns the highlighted version (in red when the
+    /// input is `invalid` by default).
+    fn highlight_validation_message<'m>(&self, msg: &'m str, invalid: bool) -> Cow<'m, str> {
+        if invalid {
+            Owned(format!("\x1b[31m{msg}\x1b[39m"))
+        } else {
+            Borrowed(msg)
+        }
+    }
     /// Takes the completion `candidate` and
     /// returns the highlighted version (with ANSI color).
     ///
@@ -67,16 +195,34 @@ pub trait Highlighter {
         let _ = (line, pos, kind);
         false
     }
+    /// Called when the terminal `background` is known (queried once, at the
+    /// first [`Editor::readline`](crate::Editor::readline) on a terminal
+    /// answering `OSC 11`).
+    fn set_background(&self, background: Background) {
+        let _ = background;
+    }
 }
 
 impl Highlighter for () {}
 
+/// Wrap `text` in an `OSC 8` hyperlink to `uri` (e.g. `https://...` or
+/// `file:///...`), to be returned by [`Highlighter::highlight`] or
+/// [`Highlighter::highlight_hint`].
+///
+/// The link itself doesn't take any column, and terminals which don't
+/// support hyperlinks only display `text`.
+#[must_use]
+pub fn hyperlink(uri: &str, text: &str) -> String {
+    format!("\x1b]8;;{uri}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
 // TODO versus https://python-prompt-toolkit.readthedocs.io/en/master/pages/reference.html?highlight=HighlightMatchingBracketProcessor#prompt_toolkit.layout.processors.HighlightMatchingBracketProcessor
 
 /// Highlight matching bracket when typed or cursor moved on.
 #[derive(Default)]
 pub struct MatchingBracketHighlighter {
     bracket: Cell<Option<(u8, usize)>>, // memorize the character to search...
+    background: Cell<Option<Background>>,
 }
 
 impl MatchingBracketHighlighter {
@@ -85,6 +231,7 @@ impl MatchingBracketHighlighter {
     pub fn new() -> Self {
         Self {
             bracket: Cell::new(None),
+            background: Cell::new(None),
         }
     }
 }
@@ -97,8 +244,16 @@ impl Highlighter for MatchingBracketHighlighter {
         // highlight matching brace/bracket/parenthesis if it exists
         if let Some((bracket, pos)) = self.bracket.get() {
             if let Some((matching, idx)) = find_matching_bracket(line, pos, bracket) {
+                // blue is hard to read on a dark background
+                let color = match self.background.get() {
+                    Some(Background::Dark) => 36,
+                    _ => 34,
+                };
                 let mut copy = line.to_owned();
-                copy.replace_range(idx..=idx, &format!("\x1b[1;34m{}\x1b[0m", matching as char));
+                copy.replace_range(
+                    idx..=idx,
+                    &format!("\x1b[1;{color}m{}\x1b[0m", matching as char),
+                );
                 return Owned(copy);
             }
         }
@@ -114,6 +269,10 @@ impl Highlighter for MatchingBracketHighlighter {
         self.bracket.set(check_bracket(line, pos));
         self.bracket.get().is_some()
     }
+
+    fn set_background(&self, background: Background) {
+        self.background.set(Some(background));
+    }
 }
 
 fn find_matching_bracket(line: &str, pos: usize, bracket: u8) -> Option<(u8, usize)> {
@@ -214,6 +373,49 @@ const fn is_close_bracket(bracket: u8) -> bool {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn hyperlink() {
+        assert_eq!(
+            "\x1b]8;;https://example.com\x1b\\example\x1b]8;;\x1b\\",
+            super::hyperlink("https://example.com", "example")
+        );
+    }
+
+    #[test]
+    pub fn highlight_selection() {
+        use super::Highlighter;
+        let line = "ab\ncd\nef";
+        assert_eq!(
+            "a\x1b[7mb\x1b[27m\nc\x1b[7md\x1b[27m\nef",
+            ().highlight_selection(line, &[1..2, 4..5, 8..8])
+        );
+    }
+
+    #[test]
+    pub fn highlight_search_match() {
+        use super::Highlighter;
+        assert_eq!(
+            ().highlight_search_match("foo bar", 4..7),
+            "foo \x1b[7mbar\x1b[27m"
+        );
+    }
+    #[test]
+    pub fn highlight_preedit() {
+        use super::Highlighter;
+        assert_eq!(
+            ().highlight_preedit("ab\u{304b}c", 2..5),
+            "ab\x1b[4m\u{304b}\x1b[24mc"
+        );
+    }
+    #[test]
+    pub fn background() {
+        use super::Background;
+        assert_eq!(Background::Dark, Background::from_rgb(0, 0, 0));
+        assert_eq!(Background::Dark, Background::from_rgb(0x28, 0x2c, 0x34));
+        assert_eq!(Background::Light, Background::from_rgb(0xff, 0xff, 0xff));
+        assert_eq!(Background::Light, Background::from_rgb(0xfd, 0xf6, 0xe3));
+    }
+
     #[test]
     pub fn find_matching_bracket() {
         use super::find_matching_bracket;
@@ -13,11 +13,18 @@ use std::fs::{File, OpenOptions};
 use std::io::SeekFrom;
 use std::ops::Index;
 use std::path::Path;
+use std::sync::{Mutex, PoisonError};
 #[cfg(feature = "with-file-history")]
 use std::time::SystemTime;
 
 use super::Result;
 use crate::config::{Config, HistoryDuplicates};
+use index::TrigramIndex;
+
+mod index;
+
+/// Number of entries from which searches are indexed
+const INDEX_THRESHOLD: usize = 1024;
 
 /// Search direction
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -57,7 +64,7 @@ pub trait History {
     ///
     /// `SearchDirection` is useful only for implementations without direct
     /// indexing.
-    fn get(&self, index: usize, dir: SearchDirection) -> Result<Option<SearchResult>>;
+    fn get(&self, index: usize, dir: SearchDirection) -> Result<Option<SearchResult<'_>>>;
 
     // termwiz: fn last(&self) -> Option<HistoryIndex>;
 
@@ -144,7 +151,7 @@ pub trait History {
     //         style: SearchStyle,
     //         direction: SearchDirection,
     //         pattern: &str,
-    //     ) -> Option<SearchResult>;
+    //     ) -> Option<SearchResult<'_>>;
     // reedline: fn set_navigation(&mut self, navigation: HistoryNavigationQuery);
     // reedline: fn get_navigation(&self) -> HistoryNavigationQuery;
 
@@ -161,7 +168,7 @@ pub trait History {
         term: &str,
         start: usize,
         dir: SearchDirection,
-    ) -> Result<Option<SearchResult>>;
+    ) -> Result<Option<SearchResult<'_>>>;
 
     /// Anchored search
     fn starts_with(
@@ -169,7 +176,7 @@ pub trait History {
         term: &str,
         start: usize,
         dir: SearchDirection,
-    ) -> Result<Option<SearchResult>>;
+    ) -> Result<Option<SearchResult<'_>>>;
 
     /* TODO How ? DoubleEndedIterator may be difficult to implement (for an SQLite backend)
     /// Return a iterator.
@@ -179,11 +186,15 @@ pub trait History {
 }
 
 /// Transient in-memory history implementation.
+///
+/// Substring searches in a large history (more than a thousand entries)
+/// use an index built on the first search and then kept up to date.
 pub struct MemHistory {
     entries: VecDeque<String>,
     max_len: usize,
     ignore_space: bool,
     ignore_dups: bool,
+    index: Mutex<Option<TrigramIndex>>,
 }
 
 impl MemHistory {
@@ -204,6 +215,7 @@ impl MemHistory {
             max_len: config.max_history_size(),
             ignore_space: config.history_ignore_space(),
             ignore_dups: config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive,
+            index: Mutex::new(None),
         }
     }
 
@@ -213,13 +225,28 @@ impl MemHistory {
         start: usize,
         dir: SearchDirection,
         test: F,
-    ) -> Option<SearchResult>
+    ) -> Option<SearchResult<'_>>
     where
         F: Fn(&str) -> Option<usize>,
     {
         if term.is_empty() || start >= self.len() {
             return None;
         }
+        if self.len() >= INDEX_THRESHOLD {
+            let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+            let index = index.get_or_insert_with(|| TrigramIndex::new(&self.entries));
+            let candidates = index.candidates(term, start, dir == SearchDirection::Reverse);
+            if let Some(mut candidates) = candidates {
+                return candidates.find_map(|idx| {
+                    let entry = &self.entries[idx];
+                    test(entry).map(|cursor| SearchResult {
+                        idx,
+                        entry: Cow::Borrowed(entry),
+                        pos: cursor,
+                    })
+                });
+            }
+        }
         match dir {
             SearchDirection::Reverse => {
                 for (idx, entry) in self
@@ -274,8 +301,15 @@ impl MemHistory {
     }
 
     fn insert(&mut self, line: String) {
+        let index = self.index.get_mut().unwrap_or_else(PoisonError::into_inner);
         if self.entries.len() == self.max_len {
             self.entries.pop_front();
+            if let Some(index) = index {
+                index.remove_front(1);
+            }
+        }
+        if let Some(index) = index {
+            index.push(&line);
         }
         self.entries.push_back(line);
     }
@@ -288,7 +322,7 @@ impl Default for MemHistory {
 }
 
 impl History for MemHistory {
-    fn get(&self, index: usize, _: SearchDirection) -> Result<Option<SearchResult>> {
+    fn get(&self, index: usize, _: SearchDirection) -> Result<Option<SearchResult<'_>>> {
         Ok(self
             .entries
             .get(index)
@@ -328,7 +362,11 @@ impl History for MemHistory {
     fn set_max_len(&mut self, len: usize) -> Result<()> {
         self.max_len = len;
         if self.len() > len {
-            self.entries.drain(..self.len() - len);
+            let n = self.len() - len;
+            self.entries.drain(..n);
+            if let Some(index) = self.index.get_mut().unwrap_or_else(PoisonError::into_inner) {
+                index.remove_front(n);
+            }
         }
         Ok(())
     }
@@ -356,6 +394,7 @@ impl History for MemHistory {
 
     fn clear(&mut self) -> Result<()> {
         self.entries.clear();
+        *self.index.get_mut().unwrap_or_else(PoisonError::into_inner) = None;
         Ok(())
     }
 
@@ -364,7 +403,7 @@ impl History for MemHistory {
         term: &str,
         start: usize,
         dir: SearchDirection,
-    ) -> Result<Option<SearchResult>> {
+    ) -> Result<Option<SearchResult<'_>>> {
         #[cfg(not(feature = "case_insensitive_history_search"))]
         {
             let test = |entry: &str| entry.find(term);
@@ -392,7 +431,7 @@ impl History for MemHistory {
         term: &str,
         start: usize,
         dir: SearchDirection,
-    ) -> Result<Option<SearchResult>> {
+    ) -> Result<Option<SearchResult<'_>>> {
         #[cfg(not(feature = "case_insensitive_history_search"))]
         {
             let test = |entry: &str| {
@@ -498,24 +537,7 @@ impl FileHistory {
             0
         };
         for entry in self.mem.entries.iter().skip(first_new_entry) {
-            let mut bytes = entry.as_bytes();
-            while let Some(i) = memchr::memchr2(b'\\', b'\n', bytes) {
-                let (head, tail) = bytes.split_at(i);
-                wtr.write_all(head)?;
-
-                let (&escapable_byte, tail) = tail
-                    .split_first()
-                    .expect("memchr guarantees i is a valid index");
-                if escapable_byte == b'\n' {
-                    wtr.write_all(br"\n")?; // escaped line feed
-                } else {
-                    debug_assert_eq!(escapable_byte, b'\\');
-                    wtr.write_all(br"\\")?; // escaped backslash
-                }
-                bytes = tail;
-            }
-            wtr.write_all(bytes)?; // remaining bytes with no \n or \
-            wtr.write_all(b"\n")?;
+            write_escaped(&mut wtr, entry)?;
         }
         // https://github.com/rust-lang/rust/issues/32677#issuecomment-204833485
         wtr.flush()?;
@@ -543,39 +565,8 @@ impl FileHistory {
                 continue;
             }
             if v2 {
-                let mut copy = None; // lazily copy line if unescaping is needed
-                let mut str = line.as_str();
-                while let Some(i) = str.find('\\') {
-                    if copy.is_none() {
-                        copy = Some(String::with_capacity(line.len()));
-                    }
-                    let s = copy.as_mut().unwrap();
-                    s.push_str(&str[..i]);
-                    let j = i + 1; // escaped char idx
-                    let b = if j < str.len() {
-                        str.as_bytes()[j]
-                    } else {
-                        0 // unexpected if History::save works properly
-                    };
-                    match b {
-                        b'n' => {
-                            s.push('\n'); // unescaped line feed
-                        }
-                        b'\\' => {
-                            s.push('\\'); // unescaped back slash
-                        }
-                        _ => {
-                            // only line feed and back slash should have been escaped
-                            warn!(target: "rustyline", "bad escaped line: {}", line);
-                            copy = None;
-                            break;
-                        }
-                    }
-                    str = &str[j + 1..];
-                }
-                if let Some(mut s) = copy {
-                    s.push_str(str); // remaining bytes with no escaped char
-                    line = s;
+                if let Cow::Owned(unescaped) = unescape(&line) {
+                    line = unescaped;
                 }
             }
             appendable &= self.add_owned(line)?; // TODO truncate to MAX_LINE
@@ -644,7 +635,7 @@ pub type DefaultHistory = FileHistory;
 
 #[cfg(feature = "with-file-history")]
 impl History for FileHistory {
-    fn get(&self, index: usize, dir: SearchDirection) -> Result<Option<SearchResult>> {
+    fn get(&self, index: usize, dir: SearchDirection) -> Result<Option<SearchResult<'_>>> {
         self.mem.get(index, dir)
     }
 
@@ -734,6 +725,7 @@ impl History for FileHistory {
                 max_len: self.mem.max_len,
                 ignore_space: self.mem.ignore_space,
                 ignore_dups: self.mem.ignore_dups,
+                index: Mutex::new(None),
             },
             new_entries: 0,
             path_info: None,
@@ -776,7 +768,7 @@ impl History for FileHistory {
         term: &str,
         start: usize,
         dir: SearchDirection,
-    ) -> Result<Option<SearchResult>> {
+    ) -> Result<Option<SearchResult<'_>>> {
         self.mem.search(term, start, dir)
     }
 
@@ -785,7 +777,7 @@ impl History for FileHistory {
         term: &str,
         start: usize,
         dir: SearchDirection,
-    ) -> Result<Option<SearchResult>> {
+    ) -> Result<Option<SearchResult<'_>>> {
         self.mem.starts_with(term, start, dir)
     }
 }
@@ -809,27 +801,88 @@ impl<'a> IntoIterator for &'a FileHistory {
     }
 }
 
+/// Write `entry` on one line, newlines and backslashes being escaped
+#[cfg(feature = "with-file-history")]
+pub(crate) fn write_escaped<W: std::io::Write>(wtr: &mut W, entry: &str) -> std::io::Result<()> {
+    let mut bytes = entry.as_bytes();
+    while let Some(i) = memchr::memchr2(b'\\', b'\n', bytes) {
+        let (head, tail) = bytes.split_at(i);
+        wtr.write_all(head)?;
+
+        let (&escapable_byte, tail) = tail
+            .split_first()
+            .expect("memchr guarantees i is a valid index");
+        if escapable_byte == b'\n' {
+            wtr.write_all(br"\n")?; // escaped line feed
+        } else {
+            debug_assert_eq!(escapable_byte, b'\\');
+            wtr.write_all(br"\\")?; // escaped backslash
+        }
+        bytes = tail;
+    }
+    wtr.write_all(bytes)?; // remaining bytes with no \n or \
+    wtr.write_all(b"\n")
+}
+
+/// Unescape a `line` written by [`write_escaped`]
+#[cfg(feature = "with-file-history")]
+pub(crate) fn unescape(line: &str) -> Cow<'_, str> {
+    let mut copy = None; // lazily copy line if unescaping is needed
+    let mut str = line;
+    while let Some(i) = str.find('\\') {
+        let s = copy.get_or_insert_with(|| String::with_capacity(line.len()));
+        s.push_str(&str[..i]);
+        let j = i + 1; // escaped char idx
+        let b = if j < str.len() {
+            str.as_bytes()[j]
+        } else {
+            0 // unexpected if History::save works properly
+        };
+        match b {
+            b'n' => {
+                s.push('\n'); // unescaped line feed
+            }
+            b'\\' => {
+                s.push('\\'); // unescaped back slash
+            }
+            _ => {
+                // only line feed and back slash should have been escaped
+                warn!(target: "rustyline", "bad escaped line: {}", line);
+                return Cow::Borrowed(line);
+            }
+        }
+        str = &str[j + 1..];
+    }
+    match copy {
+        Some(mut s) => {
+            s.push_str(str); // remaining bytes with no escaped char
+            Cow::Owned(s)
+        }
+        None => Cow::Borrowed(line),
+    }
+}
+
 #[cfg(feature = "with-file-history")]
 cfg_if::cfg_if! {
     if #[cfg(any(windows, target_arch = "wasm32"))] {
-        fn umask() -> u16 {
+        pub(crate) fn umask() -> u16 {
             0
         }
 
-        fn restore_umask(_: u16) {}
+        pub(crate) fn restore_umask(_: u16) {}
 
-        fn fix_perm(_: &File) {}
+        pub(crate) fn fix_perm(_: &File) {}
     } else if #[cfg(unix)] {
         use nix::sys::stat::{self, Mode, fchmod};
-        fn umask() -> Mode {
+        pub(crate) fn umask() -> Mode {
             stat::umask(Mode::S_IXUSR | Mode::S_IRWXG | Mode::S_IRWXO)
         }
 
-        fn restore_umask(old_umask: Mode) {
+        pub(crate) fn restore_umask(old_umask: Mode) {
             stat::umask(old_umask);
         }
 
-        fn fix_perm(file: &File) {
+        pub(crate) fn fix_perm(file: &File) {
             use std::os::unix::io::AsRawFd;
             let _ = fchmod(file.as_raw_fd(), Mode::S_IRUSR | Mode::S_IWUSR);
         }
@@ -859,7 +912,7 @@ mod tests {
     #[test]
     fn add() {
         let config = Config::builder().history_ignore_space(true).build();
-        let mut history = DefaultHistory::with_config(config);
+        let mut history = DefaultHistory::with_config(config.clone());
         #[cfg(feature = "with-file-history")]
         assert_eq!(config.max_history_size(), history.mem.max_len);
         assert!(history.add("line1").unwrap());
@@ -1020,6 +1073,54 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn indexed_search() -> Result<()> {
+        use super::MemHistory;
+
+        let config = Config::builder().max_history_size(2000)?.build();
+        let mut history = MemHistory::with_config(config);
+        let mut entries = Vec::new();
+        let add = |history: &mut MemHistory, entries: &mut Vec<String>, i: usize| {
+            let entry = format!("cmd {} --opt={}", i % 97, i % 13);
+            history.add(&entry).unwrap();
+            entries.push(entry);
+        };
+        for i in 0..1500 {
+            add(&mut history, &mut entries, i);
+        }
+        // linear search
+        let expected = |entries: &[String], term: &str, start: usize, dir| {
+            let entries = &entries[entries.len().saturating_sub(2000)..];
+            let found = |&idx: &usize| entries[idx].contains(term);
+            match dir {
+                SearchDirection::Forward => (start..entries.len()).find(found),
+                SearchDirection::Reverse => (0..=start).rev().find(found),
+            }
+        };
+        for n in 0..3 {
+            for term in ["cmd 42 ", "md 9", "=12", "nope", "cm"] {
+                for start in [0, 10, 700, 1499] {
+                    for dir in [SearchDirection::Forward, SearchDirection::Reverse] {
+                        let found = history.search(term, start, dir)?.map(|r| r.idx);
+                        assert_eq!(expected(&entries, term, start, dir), found, "{n} {term}");
+                    }
+                }
+            }
+            // the oldest entries are removed
+            for i in 0..700 {
+                add(&mut history, &mut entries, 1500 + n * 700 + i);
+            }
+        }
+        history.set_max_len(1600)?;
+        entries.drain(..entries.len() - 1600);
+        let found = history.search("cmd 1 ", 1599, SearchDirection::Reverse)?;
+        assert_eq!(
+            expected(&entries, "cmd 1 ", 1599, SearchDirection::Reverse),
+            found.map(|r| r.idx)
+        );
+        Ok(())
+    }
+
     #[test]
     fn reverse_search() -> Result<()> {
         let history = init();
@@ -0,0 +1,140 @@
+//! Trigram index of the history entries: a substring search only checks the
+//! entries which contain one of the trigrams of the searched term.
+use std::collections::HashMap;
+
+type Trigram = [u8; 3];
+
+/// Entries are identified by their insertion number, which doesn't change
+/// when the oldest entries are removed.
+pub(super) struct TrigramIndex {
+    /// Identifier of the oldest entry
+    first: u64,
+    /// Identifier of the next entry
+    next: u64,
+    /// Entries removed but still referenced by `postings`
+    stale: u64,
+    /// Sorted identifiers of the entries containing each trigram
+    postings: HashMap<Trigram, Vec<u64>>,
+}
+
+impl TrigramIndex {
+    pub fn new<'e, I: IntoIterator<Item = &'e String>>(entries: I) -> Self {
+        let mut index = Self {
+            first: 0,
+            next: 0,
+            stale: 0,
+            postings: HashMap::new(),
+        };
+        for entry in entries {
+            index.push(entry);
+        }
+        index
+    }
+
+    /// Index a new (most recent) entry
+    pub fn push(&mut self, entry: &str) {
+        let mut trigrams = trigrams(entry.as_bytes()).collect::<Vec<_>>();
+        trigrams.sort_unstable();
+        trigrams.dedup();
+        for trigram in trigrams {
+            self.postings.entry(trigram).or_default().push(self.next);
+        }
+        self.next += 1;
+    }
+
+    /// Forget the `n` oldest entries
+    pub fn remove_front(&mut self, n: usize) {
+        self.first += n as u64;
+        self.stale += n as u64;
+        // compact when most of the identifiers are stale
+        if self.stale > self.next - self.first {
+            let first = self.first;
+            self.postings.retain(|_, ids| {
+                ids.drain(..ids.partition_point(|&id| id < first));
+                !ids.is_empty()
+            });
+            self.stale = 0;
+        }
+    }
+
+    /// Indexes (in the history) of the entries which may contain `term`,
+    /// from `start` in the search direction (`None` when the term is too short
+    /// to be looked up).
+    pub fn candidates(
+        &self,
+        term: &str,
+        start: usize,
+        reverse: bool,
+    ) -> Option<impl Iterator<Item = usize> + '_> {
+        let ids = trigrams(term.as_bytes())
+            .filter(|trigram| searchable(*trigram))
+            .map(|trigram| self.postings.get(&trigram).map_or(&[][..], Vec::as_slice))
+            .min_by_key(|ids| ids.len())?;
+        let first = self.first;
+        let start = first + start as u64;
+        let (older, newer) = if reverse {
+            (&ids[..ids.partition_point(|&id| id <= start)], &[][..])
+        } else {
+            (&[][..], &ids[ids.partition_point(|&id| id < start)..])
+        };
+        let older = older.iter().rev().take_while(move |&&id| id >= first);
+        Some(
+            older
+                .chain(newer.iter())
+                .map(move |&id| (id - first) as usize),
+        )
+    }
+}
+
+/// Trigrams of `text` (lower case when the search is case insensitive)
+fn trigrams(text: &[u8]) -> impl Iterator<Item = Trigram> + '_ {
+    text.windows(3).map(|w| {
+        let trigram = [w[0], w[1], w[2]];
+        if cfg!(feature = "case_insensitive_history_search") {
+            trigram.map(|b| b.to_ascii_lowercase())
+        } else {
+            trigram
+        }
+    })
+}
+
+/// Whether all the entries matching a trigram of the term contain it:
+/// with a case insensitive search, non ASCII characters may match
+/// differently encoded ones (and 'K' or 'ſ' match 'k' or 's').
+fn searchable(trigram: Trigram) -> bool {
+    !cfg!(feature = "case_insensitive_history_search")
+        || trigram
+            .iter()
+            .all(|&b| b.is_ascii() && b != b'k' && b != b's')
+}
+
+#[cfg(test)]
+mod test {
+    use super::TrigramIndex;
+
+    fn candidates(index: &TrigramIndex, term: &str, start: usize, reverse: bool) -> Vec<usize> {
+        index.candidates(term, start, reverse).unwrap().collect()
+    }
+
+    #[test]
+    fn candidates_in_order() {
+        let entries =
+            ["cargo build", "ls", "cargo test", "git log", "cargo run"].map(str::to_owned);
+        let mut index = TrigramIndex::new(&entries);
+        assert!(index.candidates("ca", 0, false).is_none());
+        assert_eq!(vec![0, 2, 4], candidates(&index, "cargo", 0, false));
+        assert_eq!(vec![2, 4], candidates(&index, "cargo", 1, false));
+        assert_eq!(vec![2, 0], candidates(&index, "cargo", 3, true));
+        assert_eq!(vec![4, 2, 0], candidates(&index, "cargo", 4, true));
+        assert!(candidates(&index, "xyz", 0, false).is_empty());
+
+        index.remove_front(3);
+        assert_eq!(vec![1], candidates(&index, "cargo", 0, false));
+        assert_eq!(vec![1], candidates(&index, "cargo", 1, true));
+        assert_eq!(vec![0], candidates(&index, "log", 1, true));
+        index.push("cargo doc");
+        index.remove_front(1);
+        assert_eq!(vec![0, 1], candidates(&index, "cargo", 0, false));
+        assert_eq!(vec![1, 0], candidates(&index, "cargo", 1, true));
+    }
+}
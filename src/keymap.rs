@@ -1,16 +1,20 @@
 //! Bindings from keys to command for Emacs and Vi modes
 use log::debug;
+use std::collections::VecDeque;
 
 use super::Result;
-use crate::highlight::CmdKind;
+use crate::highlight::{CmdKind, PromptMode};
+use crate::history::SearchDirection;
 use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
 use crate::tty::{self, RawReader, Term, Terminal};
-use crate::{Config, EditMode};
+use crate::undo::UndoTree;
 #[cfg(feature = "custom-bindings")]
-use crate::{Event, EventContext, EventHandler};
-
-/// The number of times one command should be repeated.
-pub type RepeatCount = u16;
+use crate::{BindingMode, Event, EventContext, EventHandler};
+use crate::{Config, Context, EditMode};
+use rustyline_core::keymap::Command;
+use rustyline_core::line_buffer::WordAction;
+use rustyline_core::movement::repeat_count;
+pub use rustyline_core::movement::{At, CharSearch, Movement, RepeatCount, Word};
 
 /// Commands
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -26,6 +30,8 @@ pub enum Cmd {
     BeginningOfHistory,
     /// capitalize-word
     CapitalizeWord,
+    /// Change the quotes or brackets surrounding the cursor (`old`, `new`)
+    ChangeSurround(char, char),
     /// clear-screen
     ClearScreen,
     /// Paste from the clipboard
@@ -39,12 +45,23 @@ pub enum Cmd {
     CompleteHint,
     /// Dedent current line
     Dedent(Movement),
+    /// Delete the quotes or brackets surrounding the cursor
+    DeleteSurround(char),
     /// downcase-word
     DowncaseWord,
     /// vi-eof-maybe
     EndOfFile,
     /// end-of-history
     EndOfHistory,
+    /// Expand the folded pastes
+    ///
+    /// See [`Config::paste_fold_lines`](crate::config::Config::paste_fold_lines)
+    ExpandPaste,
+    /// Accept the line regardless of the
+    /// [`Validator`](crate::validate::Validator) result (Alt-Enter)
+    ///
+    /// See [`Editor::validation_overridden`](crate::Editor::validation_overridden)
+    ForceAcceptLine,
     /// forward-search-history (incremental search)
     ForwardSearchHistory,
     /// history-search-backward (common prefix search)
@@ -65,6 +82,9 @@ pub enum Cmd {
     /// forward-char, forward-word, vi-char-search, vi-end-word, vi-next-word,
     /// vi-prev-word
     Move(Movement),
+    /// Multi-cursor editing: insertions, deletions and character movements
+    /// apply at every cursor until any other command is executed
+    MultiCursor(CursorOp),
     /// next-history
     NextHistory,
     /// No action
@@ -81,24 +101,54 @@ pub enum Cmd {
     ReplaceChar(RepeatCount, char),
     /// vi-change-to, vi-substitute
     Replace(Movement, Option<String>),
+    /// Replace the whole input with the text, the cursor at the byte
+    /// position (e.g. after a [`LineHook`](crate::compat::LineHook))
+    SetLine(String, usize),
     /// reverse-search-history (incremental search)
     ReverseSearchHistory,
     /// self-insert
     SelfInsert(RepeatCount, char),
+    /// vi-set-mark
+    SetMark(char),
     /// Suspend signal (Ctrl-Z on unix platform)
     Suspend,
+    /// Surround the text covered by movement with quotes or brackets
+    Surround(Movement, char),
     /// transpose-chars
     TransposeChars,
     /// transpose-words
     TransposeWords(RepeatCount),
     /// undo
     Undo(RepeatCount),
+    /// redo the last undone changes
+    Redo(RepeatCount),
+    /// Select the `n`th alternative redo branch of the undo tree at the
+    /// current position (changes undone then replaced by new ones)
+    UndoBranch(RepeatCount),
+    /// Emacs registers (`C-x r`): copy-to-register, insert-register,
+    /// point-to-register, jump-to-register
+    Register(RegisterOp, char),
     /// Unsupported / unexpected
     Unknown,
     /// upcase-word
     UpcaseWord,
+    /// vi visual block mode (`Ctrl-Q`): rectangular selection and editing
+    ViBlock(BlockOp),
+    /// vi-movement-mode (switch from insert to command mode)
+    ViMovementMode,
+    /// vi-search, vi-search-again
+    ViSearch(ViSearch),
     /// vi-yank-to
     ViYankTo(Movement),
+    /// Move to the same column `n` display lines above, a logical line wrapped
+    /// on several rows being several display lines
+    VisualLineUp(RepeatCount),
+    /// Move to the same column `n` display lines below
+    VisualLineDown(RepeatCount),
+    /// Move to the start of the display line
+    BeginningOfVisualLine,
+    /// Move to the end of the display line
+    EndOfVisualLine,
     /// yank, vi-put
     Yank(RepeatCount, Anchor),
     /// yank-pop
@@ -128,6 +178,101 @@ pub enum Cmd {
     },
 }
 
+/// Named commands
+#[cfg(any(feature = "custom-bindings", feature = "serde"))]
+pub(crate) const CMD_NAMES: &[(&str, Cmd)] = &[
+    ("abort", Cmd::Abort),
+    ("accept-line", Cmd::AcceptLine),
+    ("backward-char", Cmd::Move(Movement::BackwardChar(1))),
+    ("backward-delete-char", Cmd::Kill(Movement::BackwardChar(1))),
+    ("backward-kill-line", Cmd::Kill(Movement::BeginningOfLine)),
+    ("unix-line-discard", Cmd::Kill(Movement::BeginningOfLine)),
+    (
+        "backward-kill-word",
+        Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (
+        "backward-word",
+        Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (
+        "beginning-of-buffer",
+        Cmd::Move(Movement::BeginningOfBuffer),
+    ),
+    ("beginning-of-history", Cmd::BeginningOfHistory),
+    ("beginning-of-line", Cmd::Move(Movement::BeginningOfLine)),
+    ("capitalize-word", Cmd::CapitalizeWord),
+    ("clear-screen", Cmd::ClearScreen),
+    ("complete", Cmd::Complete),
+    ("complete-backward", Cmd::CompleteBackward),
+    ("complete-hint", Cmd::CompleteHint),
+    ("delete-char", Cmd::Kill(Movement::ForwardChar(1))),
+    ("downcase-word", Cmd::DowncaseWord),
+    ("end-of-buffer", Cmd::Move(Movement::EndOfBuffer)),
+    ("end-of-file", Cmd::EndOfFile),
+    ("end-of-history", Cmd::EndOfHistory),
+    ("end-of-line", Cmd::Move(Movement::EndOfLine)),
+    ("expand-paste", Cmd::ExpandPaste),
+    ("force-accept-line", Cmd::ForceAcceptLine),
+    ("forward-char", Cmd::Move(Movement::ForwardChar(1))),
+    ("forward-search-history", Cmd::ForwardSearchHistory),
+    (
+        "forward-word",
+        Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    ("history-search-backward", Cmd::HistorySearchBackward),
+    ("history-search-forward", Cmd::HistorySearchForward),
+    ("interrupt", Cmd::Interrupt),
+    ("kill-line", Cmd::Kill(Movement::EndOfLine)),
+    ("kill-whole-line", Cmd::Kill(Movement::WholeLine)),
+    (
+        "kill-word",
+        Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    ("newline", Cmd::Newline),
+    ("next-history", Cmd::NextHistory),
+    ("next-line", Cmd::LineDownOrNextHistory(1)),
+    ("noop", Cmd::Noop),
+    ("previous-history", Cmd::PreviousHistory),
+    ("previous-line", Cmd::LineUpOrPreviousHistory(1)),
+    ("quoted-insert", Cmd::QuotedInsert),
+    ("redo", Cmd::Redo(1)),
+    ("repaint", Cmd::Repaint),
+    ("reverse-search-history", Cmd::ReverseSearchHistory),
+    ("suspend", Cmd::Suspend),
+    ("transpose-chars", Cmd::TransposeChars),
+    ("transpose-words", Cmd::TransposeWords(1)),
+    ("undo", Cmd::Undo(1)),
+    (
+        "unix-word-rubout",
+        Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+    ),
+    ("upcase-word", Cmd::UpcaseWord),
+    ("vi-movement-mode", Cmd::ViMovementMode),
+    ("yank", Cmd::Yank(1, Anchor::Before)),
+    ("yank-pop", Cmd::YankPop),
+];
+
+impl From<Command> for Cmd {
+    fn from(cmd: Command) -> Self {
+        match cmd {
+            Command::SelfInsert(n, c) => Self::SelfInsert(n, c),
+            Command::Move(mvt) => Self::Move(mvt),
+            Command::Kill(mvt) => Self::Kill(mvt),
+            Command::Yank(n) => Self::Yank(n, Anchor::Before),
+            Command::YankPop => Self::YankPop,
+            Command::TransposeChars => Self::TransposeChars,
+            Command::TransposeWords(n) => Self::TransposeWords(n),
+            Command::EditWord(WordAction::Capitalize) => Self::CapitalizeWord,
+            Command::EditWord(WordAction::Lowercase) => Self::DowncaseWord,
+            Command::EditWord(WordAction::Uppercase) => Self::UpcaseWord,
+            Command::Undo(n) => Self::Undo(n),
+            Command::Redo(n) => Self::Redo(n),
+            _ => Self::Unknown,
+        }
+    }
+}
+
 impl Cmd {
     /// Tells if current command should reset kill ring.
     #[must_use]
@@ -145,16 +290,39 @@ impl Cmd {
         }
     }
 
+    /// Command named like its readline equivalent (`accept-line`,
+    /// `kill-word`, ...)
+    /// Command named `name` (like the GNU Readline commands)
+    #[cfg(any(feature = "custom-bindings", feature = "serde"))]
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        CMD_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, cmd)| cmd.clone())
+    }
+
+    /// Name of this command (the first one when it has aliases)
+    #[cfg(feature = "serde")]
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        CMD_NAMES
+            .iter()
+            .find(|(_, cmd)| cmd == self)
+            .map(|(n, _)| *n)
+    }
+
     const fn is_repeatable_change(&self) -> bool {
         matches!(
             *self,
-            Self::Dedent(..)
+            Self::ChangeSurround(..)
+                | Self::Dedent(..)
+                | Self::DeleteSurround(_)
                 | Self::Indent(..)
                 | Self::Insert(..)
                 | Self::Kill(_)
                 | Self::ReplaceChar(..)
                 | Self::Replace(..)
                 | Self::SelfInsert(..)
+                | Self::Surround(..)
                 | Self::ViYankTo(_)
                 | Self::Yank(..) // Cmd::TransposeChars | TODO Validate
         )
@@ -170,7 +338,9 @@ impl Cmd {
     // Replay this command with a possible different `RepeatCount`.
     fn redo(&self, new: Option<RepeatCount>, wrt: &dyn Refresher) -> Self {
         match *self {
+            Self::ChangeSurround(old, new) => Self::ChangeSurround(old, new),
             Self::Dedent(ref mvt) => Self::Dedent(mvt.redo(new)),
+            Self::DeleteSurround(c) => Self::DeleteSurround(c),
             Self::Indent(ref mvt) => Self::Indent(mvt.redo(new)),
             Self::Insert(previous, ref text) => {
                 Self::Insert(repeat_count(previous, new), text.clone())
@@ -204,6 +374,7 @@ impl Cmd {
                     Self::SelfInsert(repeat_count(previous, new), c)
                 }
             }
+            Self::Surround(ref mvt, c) => Self::Surround(mvt.redo(new), c),
             // Cmd::TransposeChars => Cmd::TransposeChars,
             Self::ViYankTo(ref mvt) => Self::ViYankTo(mvt.redo(new)),
             Self::Yank(previous, anchor) => Self::Yank(repeat_count(previous, new), anchor),
@@ -212,33 +383,49 @@ impl Cmd {
     }
 }
 
-const fn repeat_count(previous: RepeatCount, new: Option<RepeatCount>) -> RepeatCount {
-    match new {
-        Some(n) => n,
-        None => previous,
-    }
+/// Operation on the rectangular selection of vi visual block mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BlockOp {
+    /// Start selecting from the cursor position
+    Select,
+    /// Cancel the selection
+    Cancel,
+    /// Copy the selected text to the kill ring
+    Yank,
+    /// Delete the selected text
+    Kill,
+    /// Delete the selected text and insert on every line
+    Change,
+    /// Insert the same text on every line, before (`I`) or after (`A`) the
+    /// selection
+    Insert(Anchor),
 }
 
-/// Different word definitions
-#[derive(Debug, Clone, Eq, PartialEq, Copy)]
-pub enum Word {
-    /// non-blanks characters
-    Big,
-    /// alphanumeric characters
-    Emacs,
-    /// alphanumeric (and '_') characters
-    Vi,
+/// Operation on the extra cursors of multi-cursor editing
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CursorOp {
+    /// Add a cursor at the same column on the line below the last cursor
+    AddBelow,
+    /// Add a cursor on the next occurrence of the word under the cursor
+    AddOnMatch,
+    /// Remove the extra cursors
+    Clear,
 }
 
-/// Where to move with respect to word boundary
-#[derive(Debug, Clone, Eq, PartialEq, Copy)]
-pub enum At {
-    /// Start of word.
-    Start,
-    /// Before end of word.
-    BeforeEnd,
-    /// After end of word.
-    AfterEnd,
+/// Operation on an emacs register
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RegisterOp {
+    /// Copy the line to the register (`C-x r s`)
+    CopyLine,
+    /// Insert the text of the register at the cursor (`C-x r i`)
+    Insert,
+    /// Store the cursor position in the register (`C-x r SPC`)
+    Point,
+    /// Move the cursor to the position stored in the register (`C-x r j`)
+    Jump,
 }
 
 /// Where to paste (relative to cursor position)
@@ -250,89 +437,107 @@ pub enum Anchor {
     Before,
 }
 
-/// character search
-#[derive(Debug, Clone, Eq, PartialEq, Copy)]
-pub enum CharSearch {
-    /// Forward search
-    Forward(char),
-    /// Forward search until
-    ForwardBefore(char),
-    /// Backward search
-    Backward(char),
-    /// Backward search until
-    BackwardAfter(char),
+/// vi history search (`/pattern/e+1` or `?pattern?s-1`)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ViSearch {
+    /// Searched text
+    pub pattern: String,
+    /// Search direction in history
+    pub direction: SearchDirection,
+    /// Cursor position relative to the match
+    pub offset: SearchOffset,
 }
 
-impl CharSearch {
-    const fn opposite(self) -> Self {
-        match self {
-            Self::Forward(c) => Self::Backward(c),
-            Self::ForwardBefore(c) => Self::BackwardAfter(c),
-            Self::Backward(c) => Self::Forward(c),
-            Self::BackwardAfter(c) => Self::ForwardBefore(c),
+impl ViSearch {
+    /// Parse `input` typed after `/` (reverse) or `?` (forward).
+    ///
+    /// An unescaped `delimiter` separates the pattern from an optional
+    /// offset. An empty pattern reuses the `last` one.
+    fn parse(
+        input: &str,
+        delimiter: char,
+        direction: SearchDirection,
+        last: Option<&Self>,
+    ) -> Option<Self> {
+        let mut pattern = String::with_capacity(input.len());
+        let mut offset = None;
+        let mut chars = input.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some((_, c)) if c == delimiter => pattern.push(c),
+                    Some((_, c)) => {
+                        pattern.push('\\');
+                        pattern.push(c);
+                    }
+                    None => pattern.push(c),
+                }
+            } else if c == delimiter {
+                offset = Some(SearchOffset::parse(&input[i + c.len_utf8()..])?);
+                break;
+            } else {
+                pattern.push(c);
+            }
+        }
+        if pattern.is_empty() {
+            pattern = last?.pattern.clone();
+        }
+        Some(Self {
+            pattern,
+            direction,
+            offset: offset.unwrap_or_default(),
+        })
+    }
+
+    fn opposite(&self) -> Self {
+        Self {
+            pattern: self.pattern.clone(),
+            direction: match self.direction {
+                SearchDirection::Forward => SearchDirection::Reverse,
+                SearchDirection::Reverse => SearchDirection::Forward,
+            },
+            offset: self.offset,
         }
     }
 }
 
-/// Where to move
-#[derive(Debug, Clone, Eq, PartialEq)]
-#[non_exhaustive]
-pub enum Movement {
-    /// Whole current line (not really a movement but a range)
-    WholeLine,
-    /// beginning-of-line
-    BeginningOfLine,
-    /// end-of-line
-    EndOfLine,
-    /// backward-word, vi-prev-word
-    BackwardWord(RepeatCount, Word), // Backward until start of word
-    /// forward-word, vi-end-word, vi-next-word
-    ForwardWord(RepeatCount, At, Word), // Forward until start/end of word
-    /// character-search, character-search-backward, vi-char-search
-    ViCharSearch(RepeatCount, CharSearch),
-    /// vi-first-print
-    ViFirstPrint,
-    /// backward-char
-    BackwardChar(RepeatCount),
-    /// forward-char
-    ForwardChar(RepeatCount),
-    /// move to the same column on the previous line
-    LineUp(RepeatCount),
-    /// move to the same column on the next line
-    LineDown(RepeatCount),
-    /// Whole user input (not really a movement but a range)
-    WholeBuffer,
-    /// beginning-of-buffer
-    BeginningOfBuffer,
-    /// end-of-buffer
-    EndOfBuffer,
+/// Cursor offset of a vi search
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SearchOffset {
+    /// `s[+-n]` or `b[+-n]`: `n` characters from the start of the match
+    Start(i16),
+    /// `e[+-n]`: `n` characters from the last character of the match
+    End(i16),
 }
 
-impl Movement {
-    // Replay this movement with a possible different `RepeatCount`.
-    const fn redo(&self, new: Option<RepeatCount>) -> Self {
-        match *self {
-            Self::WholeLine => Self::WholeLine,
-            Self::BeginningOfLine => Self::BeginningOfLine,
-            Self::ViFirstPrint => Self::ViFirstPrint,
-            Self::EndOfLine => Self::EndOfLine,
-            Self::BackwardWord(previous, word) => {
-                Self::BackwardWord(repeat_count(previous, new), word)
-            }
-            Self::ForwardWord(previous, at, word) => {
-                Self::ForwardWord(repeat_count(previous, new), at, word)
-            }
-            Self::ViCharSearch(previous, char_search) => {
-                Self::ViCharSearch(repeat_count(previous, new), char_search)
+impl Default for SearchOffset {
+    fn default() -> Self {
+        Self::Start(0)
+    }
+}
+
+impl SearchOffset {
+    fn parse(s: &str) -> Option<Self> {
+        let (end, n) = match s.as_bytes().first() {
+            None => return Some(Self::default()),
+            Some(b's' | b'b') => (false, &s[1..]),
+            Some(b'e') => (true, &s[1..]),
+            _ => return None,
+        };
+        let n = match n.as_bytes().first() {
+            None => 0,
+            Some(b'+' | b'-') if n.len() == 1 => {
+                if n == "-" {
+                    -1
+                } else {
+                    1
+                }
             }
-            Self::BackwardChar(previous) => Self::BackwardChar(repeat_count(previous, new)),
-            Self::ForwardChar(previous) => Self::ForwardChar(repeat_count(previous, new)),
-            Self::LineUp(previous) => Self::LineUp(repeat_count(previous, new)),
-            Self::LineDown(previous) => Self::LineDown(repeat_count(previous, new)),
-            Self::WholeBuffer => Self::WholeBuffer,
-            Self::BeginningOfBuffer => Self::BeginningOfBuffer,
-            Self::EndOfBuffer => Self::EndOfBuffer,
-        }
+            Some(b'+') => n[1..].parse().ok()?,
+            Some(b'-') => n.parse().ok()?,
+            _ => return None,
+        };
+        Some(if end { Self::End(n) } else { Self::Start(n) })
     }
 }
 
@@ -347,6 +552,49 @@ pub enum InputMode {
     Replace,
 }
 
+/// Where the command bound to a key comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BindingSource {
+    /// Binding added with [`Editor::bind_sequence`](crate::Editor::bind_sequence)
+    /// (`scope` is `None`) or [`Editor::bind_sequence_in`](crate::Editor::bind_sequence_in)
+    #[cfg(feature = "custom-bindings")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
+    Custom {
+        /// Input mode the binding is restricted to
+        scope: Option<BindingMode>,
+        /// Bound to [`Event::Any`]
+        any: bool,
+        /// [`EventHandler::Conditional`] handler
+        conditional: bool,
+    },
+    /// Special character of the terminal (e.g. `stty` settings)
+    Terminal,
+    /// Default Emacs or Vi key map
+    KeyMap,
+}
+
+/// How key(s) have been resolved into a command, see
+/// [`Editor::set_binding_tracer`](crate::Editor::set_binding_tracer)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BindingTrace {
+    /// Keys read, including the numeric argument and the following keys of a
+    /// sequence
+    pub keys: Vec<KeyEvent>,
+    /// Editing mode when the first key has been read
+    pub mode: PromptMode,
+    /// Conditional handlers which have been tried but returned `None`
+    pub declined: Vec<BindingSource>,
+    /// Binding which produced `cmd`
+    pub source: BindingSource,
+    /// Resolved command
+    pub cmd: Cmd,
+}
+
+/// Sink of [`BindingTrace`]s
+pub type BindingTracer = Box<dyn Fn(&BindingTrace) + Send + Sync>;
+
 /// Transform key(s) to commands based on current input mode
 pub struct InputState<'b> {
     pub(crate) mode: EditMode,
@@ -357,12 +605,27 @@ pub struct InputState<'b> {
     num_args: i16,
     last_cmd: Cmd,                        // vi only
     last_char_search: Option<CharSearch>, // vi only
+    last_search: Option<ViSearch>,        // vi only
+    block_select: bool,                   // vi only
+    #[cfg_attr(not(feature = "custom-bindings"), expect(dead_code))]
+    mapping_timeout: Option<u16>,
+    pending_keys: VecDeque<KeyEvent>, // read ahead by an unmatched key sequence
+    tracer: Option<&'b BindingTracer>,
+    trace: Option<BindingTrace>, // resolution in progress, when traced
 }
 
 /// Provide indirect mutation to user input.
 pub trait Invoke {
     /// currently edited line
     fn input(&self) -> &str;
+    /// Current cursor position (byte position)
+    fn pos(&self) -> usize {
+        self.input().len()
+    }
+    /// Completion context (history)
+    fn context(&self) -> Option<&Context<'_>> {
+        None
+    }
     // TODO
     //fn invoke(&mut self, cmd: Cmd) -> Result<?>;
 }
@@ -373,6 +636,9 @@ impl Invoke for &str {
     }
 }
 
+/// Milliseconds between two polls of an asynchronous validation
+const VALIDATION_POLL_MS: u16 = 50;
+
 pub trait Refresher {
     /// Rewrite the currently edited line accordingly to the buffer content,
     /// cursor position, and number of columns of the terminal.
@@ -399,8 +665,37 @@ pub trait Refresher {
     /// Current cursor position (byte position)
     #[cfg_attr(not(feature = "custom-bindings"), expect(dead_code))]
     fn pos(&self) -> usize;
+    /// Summary of the undo tree of the current line
+    #[cfg_attr(not(feature = "custom-bindings"), expect(dead_code))]
+    fn undo_tree(&self) -> UndoTree;
     /// Display `msg` above currently edited line.
     fn external_print(&mut self, msg: String) -> Result<()>;
+    /// Print `msg` to stderr above the prompt
+    fn external_eprint(&mut self, msg: String) -> Result<()>;
+    /// Milliseconds to wait for a keystroke before validating the input
+    /// (`None` if it is not validated while typing or already validated)
+    fn live_validation_delay(&self) -> Option<u16>;
+    /// Validate the input and display the result indicator
+    fn validate_live(&mut self) -> Result<()>;
+    /// Milliseconds to wait before printing the queued external messages
+    /// (`None` if there is none)
+    fn external_print_delay(&self) -> Option<u16>;
+    /// Print the queued external messages
+    fn flush_external_print(&mut self) -> Result<()>;
+    /// Display `lines` above the prompt
+    fn external_region(&mut self, lines: Vec<String>) -> Result<()>;
+    /// Terminal gained or lost focus
+    fn focus_changed(&mut self, focused: bool) -> Result<()>;
+    /// Display the IME composition `text` at the cursor (nothing when empty)
+    fn preedit(&mut self, text: String) -> Result<()>;
+    /// Forget the IME composition without refreshing, its committed text
+    /// being inserted next
+    fn commit_preedit(&mut self);
+    /// Returns `true` if an asynchronous validation is in progress.
+    fn validation_pending(&self) -> bool;
+    /// Poll the asynchronous validation in progress, returns the accept
+    /// command to execute again once the result is available.
+    fn poll_validation(&mut self) -> Result<Option<Cmd>>;
 }
 
 impl<'b> InputState<'b> {
@@ -412,13 +707,34 @@ impl<'b> InputState<'b> {
             num_args: 0,
             last_cmd: Cmd::Noop,
             last_char_search: None,
+            last_search: None,
+            block_select: false,
+            mapping_timeout: config.mapping_timeout(),
+            pending_keys: VecDeque::new(),
+            tracer: None,
+            trace: None,
         }
     }
 
+    /// Report each key resolution to `tracer`
+    pub fn set_tracer(&mut self, tracer: Option<&'b BindingTracer>) {
+        self.tracer = tracer;
+    }
+
     pub fn is_emacs_mode(&self) -> bool {
         self.mode == EditMode::Emacs
     }
 
+    /// Current editing mode (for prompt mode indicator)
+    pub fn prompt_mode(&self) -> PromptMode {
+        match (self.mode, self.input_mode) {
+            (EditMode::Emacs, _) => PromptMode::Emacs,
+            (EditMode::Vi, InputMode::Command) => PromptMode::ViCommand,
+            (EditMode::Vi, InputMode::Insert) => PromptMode::ViInsert,
+            (EditMode::Vi, InputMode::Replace) => PromptMode::ViReplace,
+        }
+    }
+
     /// Parse user input into one command
     /// `single_esc_abort` is used in emacs mode on unix platform when a single
     /// esc key is expected to abort current action.
@@ -431,10 +747,31 @@ impl<'b> InputState<'b> {
     ) -> Result<Cmd> {
         let single_esc_abort = self.single_esc_abort(single_esc_abort);
         let key;
-        if ignore_external_print {
-            key = rdr.next_key(single_esc_abort)?;
+        if let Some(k) = self.pending_keys.pop_front() {
+            key = k;
+        } else if ignore_external_print {
+            key = self.next_key(rdr, single_esc_abort)?;
         } else {
             loop {
+                if wrt.validation_pending() {
+                    if let Some(cmd) = wrt.poll_validation()? {
+                        return Ok(cmd);
+                    }
+                    if !rdr.poll_key(VALIDATION_POLL_MS)? {
+                        continue;
+                    }
+                }
+                if let Some(delay) = wrt.live_validation_delay() {
+                    if !rdr.poll_key(delay)? {
+                        wrt.validate_live()?;
+                    }
+                }
+                if let Some(delay) = wrt.external_print_delay() {
+                    // batch messages received until the delay expires or a
+                    // key is pressed
+                    rdr.poll_key(delay)?;
+                    wrt.flush_external_print()?;
+                }
                 let event = rdr.wait_for_input(single_esc_abort)?;
                 match event {
                     tty::Event::KeyPress(k) => {
@@ -444,15 +781,83 @@ impl<'b> InputState<'b> {
                     tty::Event::ExternalPrint(msg) => {
                         wrt.external_print(msg)?;
                     }
+                    tty::Event::ExternalEprint(msg) => {
+                        wrt.external_eprint(msg)?;
+                    }
+                    tty::Event::ExternalRegion(lines) => {
+                        wrt.external_region(lines)?;
+                    }
+                    tty::Event::Preedit(text) => {
+                        wrt.preedit(text)?;
+                    }
+                    // inserted as a single change, bypassing the key bindings
+                    tty::Event::Commit(text) if !text.is_empty() => {
+                        wrt.commit_preedit();
+                        return Ok(Cmd::Insert(1, text));
+                    }
+                    tty::Event::Commit(_) => {
+                        wrt.preedit(String::new())?;
+                    }
                     #[cfg(target_os = "macos")]
                     _ => {}
                 }
             }
         }
-        match self.mode {
+        if let E(code @ (K::FocusIn | K::FocusOut), M::NONE) = key {
+            wrt.focus_changed(code == K::FocusIn)?;
+        }
+        self.trace = self.tracer.map(|_| BindingTrace {
+            keys: vec![key],
+            mode: self.prompt_mode(),
+            declined: vec![],
+            source: BindingSource::KeyMap,
+            cmd: Cmd::Noop,
+        });
+        let cmd = match self.mode {
             EditMode::Emacs => self.emacs(rdr, wrt, key),
             EditMode::Vi if self.input_mode != InputMode::Command => self.vi_insert(rdr, wrt, key),
+            EditMode::Vi if self.block_select => self.vi_block(rdr, wrt, key),
             EditMode::Vi => self.vi_command(rdr, wrt, key),
+        }?;
+        if let (Some(tracer), Some(mut trace)) = (self.tracer, self.trace.take()) {
+            trace.cmd = cmd.clone();
+            tracer(&trace);
+        }
+        Ok(cmd)
+    }
+
+    /// The command being resolved comes from `source`
+    fn resolved_by(&mut self, source: BindingSource) {
+        if let Some(ref mut trace) = self.trace {
+            trace.source = source;
+        }
+    }
+
+    /// Next key, replaying first the keys read ahead by an unmatched key
+    /// sequence
+    fn next_key<R: RawReader>(&mut self, rdr: &mut R, single_esc_abort: bool) -> Result<KeyEvent> {
+        let key = match self.pending_keys.pop_front() {
+            Some(key) => key,
+            None => rdr.next_key(single_esc_abort)?,
+        };
+        if let Some(ref mut trace) = self.trace {
+            trace.keys.push(key);
+        }
+        Ok(key)
+    }
+
+    /// Keys read after the first one of an unmatched key sequence are handled
+    /// as if there were no binding.
+    fn unread_keys(&mut self, evt: &Event) {
+        #[cfg_attr(not(feature = "custom-bindings"), expect(irrefutable_let_patterns))]
+        if let Event::KeySeq(ref key_seq) = *evt {
+            for key in key_seq.iter().skip(1).rev() {
+                self.pending_keys.push_front(*key);
+                // traced again when read again
+                if let Some(ref mut trace) = self.trace {
+                    trace.keys.pop();
+                }
+            }
         }
     }
 
@@ -464,11 +869,19 @@ impl<'b> InputState<'b> {
     }
 
     /// Terminal peculiar binding
-    fn term_binding<R: RawReader>(rdr: &R, wrt: &dyn Refresher, key: &KeyEvent) -> Option<Cmd> {
+    fn term_binding<R: RawReader>(
+        &mut self,
+        rdr: &R,
+        wrt: &dyn Refresher,
+        key: &KeyEvent,
+    ) -> Option<Cmd> {
         let cmd = rdr.find_binding(key);
         if cmd == Some(Cmd::EndOfFile) && !wrt.line().is_empty() {
             None // ReadlineError::Eof only if line is empty
         } else {
+            if cmd.is_some() {
+                self.resolved_by(BindingSource::Terminal);
+            }
             cmd
         }
     }
@@ -477,41 +890,18 @@ impl<'b> InputState<'b> {
         &mut self,
         rdr: &mut R,
         wrt: &mut dyn Refresher,
-        digit: char,
+        mut emacs: rustyline_core::keymap::Emacs,
     ) -> Result<KeyEvent> {
-        #[expect(clippy::cast_possible_truncation)]
-        match digit {
-            '0'..='9' => {
-                self.num_args = digit.to_digit(10).unwrap() as i16;
-            }
-            '-' => {
-                self.num_args = -1;
+        while let Some(arg) = emacs.argument() {
+            wrt.refresh_prompt_and_line(&format!("(arg: {arg}) "))?;
+            let key = self.next_key(rdr, true)?;
+            if !emacs.digit_argument(key) {
+                self.num_args = arg;
+                wrt.refresh_line()?;
+                return Ok(key);
             }
-            _ => unreachable!(),
-        }
-        loop {
-            wrt.refresh_prompt_and_line(&format!("(arg: {}) ", self.num_args))?;
-            let key = rdr.next_key(true)?;
-            #[expect(clippy::cast_possible_truncation)]
-            match key {
-                E(K::Char(digit @ '0'..='9'), m) if m == M::NONE || m == M::ALT => {
-                    if self.num_args == -1 {
-                        self.num_args *= digit.to_digit(10).unwrap() as i16;
-                    } else if self.num_args.abs() < 1000 {
-                        // shouldn't ever need more than 4 digits
-                        self.num_args = self
-                            .num_args
-                            .saturating_mul(10)
-                            .saturating_add(digit.to_digit(10).unwrap() as i16);
-                    }
-                }
-                E(K::Char('-'), m) if m == M::NONE || m == M::ALT => {}
-                _ => {
-                    wrt.refresh_line()?;
-                    return Ok(key);
-                }
-            };
         }
+        unreachable!()
     }
 
     fn emacs<R: RawReader>(
@@ -520,10 +910,9 @@ impl<'b> InputState<'b> {
         wrt: &mut dyn Refresher,
         mut key: KeyEvent,
     ) -> Result<Cmd> {
-        if let E(K::Char(digit @ '-'), M::ALT) = key {
-            key = self.emacs_digit_argument(rdr, wrt, digit)?;
-        } else if let E(K::Char(digit @ '0'..='9'), M::ALT) = key {
-            key = self.emacs_digit_argument(rdr, wrt, digit)?;
+        let mut emacs = rustyline_core::keymap::Emacs::new();
+        if emacs.digit_argument(key) {
+            key = self.emacs_digit_argument(rdr, wrt, emacs)?;
         }
         let (n, positive) = self.emacs_num_args(); // consume them in all cases
 
@@ -534,35 +923,12 @@ impl<'b> InputState<'b> {
             } else {
                 cmd
             });
-        } else if let Some(cmd) = InputState::term_binding(rdr, wrt, &key) {
+        } else if let Some(cmd) = self.term_binding(rdr, wrt, &key) {
             return Ok(cmd);
         }
         let cmd = match key {
-            E(K::Char(c), M::NONE) => {
-                if positive {
-                    Cmd::SelfInsert(n, c)
-                } else {
-                    Cmd::Unknown
-                }
-            }
-            E(K::Char('A'), M::CTRL) => Cmd::Move(Movement::BeginningOfLine),
-            E(K::Char('B'), M::CTRL) => Cmd::Move(if positive {
-                Movement::BackwardChar(n)
-            } else {
-                Movement::ForwardChar(n)
-            }),
-            E(K::Char('E'), M::CTRL) => Cmd::Move(Movement::EndOfLine),
-            E(K::Char('F'), M::CTRL) => Cmd::Move(if positive {
-                Movement::ForwardChar(n)
-            } else {
-                Movement::BackwardChar(n)
-            }),
+            // TODO ESC-R (r): Undo all changes made to this line.
             E(K::Char('G'), M::CTRL | M::CTRL_ALT) | E::ESC => Cmd::Abort,
-            E(K::Char('H'), M::CTRL) | E::BACKSPACE => Cmd::Kill(if positive {
-                Movement::BackwardChar(n)
-            } else {
-                Movement::ForwardChar(n)
-            }),
             E(K::BackTab, M::NONE) => Cmd::CompleteBackward,
             E(K::Char('I'), M::CTRL) | E(K::Tab, M::NONE) => {
                 if positive {
@@ -573,11 +939,6 @@ impl<'b> InputState<'b> {
             }
             // Don't complete hints when the cursor is not at the end of a line
             E(K::Right, M::NONE) if wrt.has_hint() && wrt.is_cursor_at_end() => Cmd::CompleteHint,
-            E(K::Char('K'), M::CTRL) => Cmd::Kill(if positive {
-                Movement::EndOfLine
-            } else {
-                Movement::BeginningOfLine
-            }),
             E(K::Char('L'), M::CTRL) => Cmd::ClearScreen,
             E(K::Char('N'), M::CTRL) => Cmd::NextHistory,
             E(K::Char('P'), M::CTRL) => Cmd::PreviousHistory,
@@ -585,15 +946,18 @@ impl<'b> InputState<'b> {
                 if let Some(cmd) = self.custom_seq_binding(rdr, wrt, &mut evt, n, positive)? {
                     cmd
                 } else {
-                    let snd_key = match evt {
-                        // we may have already read the second key in custom_seq_binding
-                        #[allow(clippy::out_of_bounds_indexing)]
-                        Event::KeySeq(ref key_seq) if key_seq.len() > 1 => key_seq[1],
-                        _ => rdr.next_key(true)?,
-                    };
+                    // we may have already read the second key in custom_seq_binding
+                    self.unread_keys(&evt);
+                    let snd_key = self.next_key(rdr, true)?;
                     match snd_key {
                         E(K::Char('G'), M::CTRL) | E::ESC => Cmd::Abort,
                         E(K::Char('U'), M::CTRL) => Cmd::Undo(n),
+                        E(K::Char('O'), M::CTRL) => Cmd::ExpandPaste,
+                        E(K::Char('N'), M::CTRL) => Cmd::MultiCursor(CursorOp::AddBelow),
+                        E(K::Char('D'), M::CTRL) => Cmd::MultiCursor(CursorOp::AddOnMatch),
+                        E(K::Char('r'), M::NONE) => self.emacs_register(rdr)?,
+                        E(K::Char('s'), M::NONE) => self.emacs_surround(rdr, n)?,
+                        E(K::Char('b'), M::NONE) => Cmd::UndoBranch(n),
                         E(K::Backspace, M::NONE) => Cmd::Kill(if positive {
                             Movement::BeginningOfLine
                         } else {
@@ -605,7 +969,7 @@ impl<'b> InputState<'b> {
             }
             // character-search, character-search-backward
             E(K::Char(']'), m @ (M::CTRL | M::CTRL_ALT)) => {
-                let ch = rdr.next_key(false)?;
+                let ch = self.next_key(rdr, false)?;
                 match ch {
                     E(K::Char(ch), M::NONE) => Cmd::Move(Movement::ViCharSearch(
                         n,
@@ -624,39 +988,18 @@ impl<'b> InputState<'b> {
                     _ => Cmd::Unknown,
                 }
             }
-            E(K::Backspace, M::ALT) => Cmd::Kill(if positive {
-                Movement::BackwardWord(n, Word::Emacs)
-            } else {
-                Movement::ForwardWord(n, At::AfterEnd, Word::Emacs)
-            }),
             E(K::Char('<'), M::ALT) => Cmd::BeginningOfHistory,
             E(K::Char('>'), M::ALT) => Cmd::EndOfHistory,
-            E(K::Char('B' | 'b') | K::Left, M::ALT) | E(K::Left, M::CTRL) => {
-                Cmd::Move(if positive {
-                    Movement::BackwardWord(n, Word::Emacs)
-                } else {
-                    Movement::ForwardWord(n, At::AfterEnd, Word::Emacs)
-                })
-            }
-            E(K::Char('C' | 'c'), M::ALT) => Cmd::CapitalizeWord,
-            E(K::Char('D' | 'd'), M::ALT) => Cmd::Kill(if positive {
-                Movement::ForwardWord(n, At::AfterEnd, Word::Emacs)
-            } else {
-                Movement::BackwardWord(n, Word::Emacs)
-            }),
-            E(K::Char('F' | 'f') | K::Right, M::ALT) | E(K::Right, M::CTRL) => {
-                Cmd::Move(if positive {
-                    Movement::ForwardWord(n, At::AfterEnd, Word::Emacs)
-                } else {
-                    Movement::BackwardWord(n, Word::Emacs)
-                })
-            }
-            E(K::Char('L' | 'l'), M::ALT) => Cmd::DowncaseWord,
-            E(K::Char('T' | 't'), M::ALT) => Cmd::TransposeWords(n),
-            // TODO ESC-R (r): Undo all changes made to this line.
-            E(K::Char('U' | 'u'), M::ALT) => Cmd::UpcaseWord,
-            E(K::Char('Y' | 'y'), M::ALT) => Cmd::YankPop,
-            _ => self.common(rdr, wrt, evt, key, n, positive)?,
+            E(K::Char('G' | 'g'), M::ALT) => match self.next_key(rdr, true)? {
+                // goto-line
+                E(K::Char('g'), M::NONE | M::ALT) => Cmd::Move(Movement::GotoLine(n)),
+                _ => Cmd::Unknown,
+            },
+            // line editing (see `rustyline_core::keymap`)
+            _ => match rustyline_core::keymap::emacs(key, n, positive) {
+                Some(cmd) => cmd.into(),
+                None => self.common(rdr, wrt, evt, key, n, positive)?,
+            },
         };
         debug!(target: "rustyline", "Emacs command: {:?}", cmd);
         Ok(cmd)
@@ -672,7 +1015,7 @@ impl<'b> InputState<'b> {
         self.num_args = digit.to_digit(10).unwrap() as i16;
         loop {
             wrt.refresh_prompt_and_line(&format!("(arg: {}) ", self.num_args))?;
-            let key = rdr.next_key(false)?;
+            let key = self.next_key(rdr, false)?;
             if let E(K::Char(digit @ '0'..='9'), M::NONE) = key {
                 if self.num_args.abs() < 1000 {
                     // shouldn't ever need more than 4 digits
@@ -699,8 +1042,12 @@ impl<'b> InputState<'b> {
         }
         let no_num_args = self.num_args == 0;
         let n = self.vi_num_args(); // consume them in all cases
-        let evt = key.into();
-        if let Some(cmd) = self.custom_binding(wrt, &evt, n, true) {
+        let mut evt = key.into();
+        let cmd = match self.custom_binding(wrt, &evt, n, true) {
+            None => self.custom_seq_binding(rdr, wrt, &mut evt, n, true)?,
+            cmd => cmd,
+        };
+        if let Some(cmd) = cmd {
             return Ok(if cmd.is_repeatable() {
                 if no_num_args {
                     cmd.redo(None, wrt)
@@ -710,7 +1057,9 @@ impl<'b> InputState<'b> {
             } else {
                 cmd
             });
-        } else if let Some(cmd) = InputState::term_binding(rdr, wrt, &key) {
+        }
+        self.unread_keys(&evt);
+        if let Some(cmd) = self.term_binding(rdr, wrt, &key) {
             return Ok(cmd);
         }
         let cmd = match key {
@@ -743,20 +1092,39 @@ impl<'b> InputState<'b> {
             }
             E(K::Char('b'), M::NONE) => Cmd::Move(Movement::BackwardWord(n, Word::Vi)), /* vi-prev-word */
             E(K::Char('B'), M::NONE) => Cmd::Move(Movement::BackwardWord(n, Word::Big)),
-            E(K::Char('c'), M::NONE) => {
-                self.input_mode = InputMode::Insert;
-                match self.vi_cmd_motion(rdr, wrt, key, n)? {
-                    Some(mvt) => Cmd::Replace(mvt, None),
-                    None => Cmd::Unknown,
+            E(K::Char('c'), M::NONE) => match self.next_key(rdr, false)? {
+                E(K::Char('s'), M::NONE) => {
+                    // change surrounding
+                    match self.vi_surround_char(rdr)? {
+                        Some(old) => match self.vi_surround_char(rdr)? {
+                            Some(new) => Cmd::ChangeSurround(old, new),
+                            None => Cmd::Unknown,
+                        },
+                        None => Cmd::Unknown,
+                    }
                 }
-            }
+                mvt => {
+                    self.input_mode = InputMode::Insert;
+                    match self.vi_motion(rdr, wrt, key, mvt, n)? {
+                        Some(mvt) => Cmd::Replace(mvt, None),
+                        None => Cmd::Unknown,
+                    }
+                }
+            },
             E(K::Char('C'), M::NONE) => {
                 self.input_mode = InputMode::Insert;
                 Cmd::Replace(Movement::EndOfLine, None)
             }
-            E(K::Char('d'), M::NONE) => match self.vi_cmd_motion(rdr, wrt, key, n)? {
-                Some(mvt) => Cmd::Kill(mvt),
-                None => Cmd::Unknown,
+            E(K::Char('d'), M::NONE) => match self.next_key(rdr, false)? {
+                // delete surrounding
+                E(K::Char('s'), M::NONE) => match self.vi_surround_char(rdr)? {
+                    Some(c) => Cmd::DeleteSurround(c),
+                    None => Cmd::Unknown,
+                },
+                mvt => match self.vi_motion(rdr, wrt, key, mvt, n)? {
+                    Some(mvt) => Cmd::Kill(mvt),
+                    None => Cmd::Unknown,
+                },
             },
             E(K::Char('D'), M::NONE) | E(K::Char('K'), M::CTRL) => Cmd::Kill(Movement::EndOfLine),
             E(K::Char('e'), M::NONE) => {
@@ -793,12 +1161,48 @@ impl<'b> InputState<'b> {
                 Some(ref cs) => Cmd::Move(Movement::ViCharSearch(n, cs.opposite())),
                 None => Cmd::Noop,
             },
-            // TODO E(K::Char('G'), M::NONE) => Cmd::???, Move to the history line n
+            E(K::Char(c @ ('/' | '?')), M::NONE) => match self.vi_search(rdr, wrt, c)? {
+                Some(search) => Cmd::ViSearch(search), // vi-search
+                None => Cmd::Noop,
+            },
+            E(K::Char('n'), M::NONE) => match self.last_search {
+                Some(ref search) => Cmd::ViSearch(search.clone()), // vi-search-again
+                None => Cmd::Noop,
+            },
+            E(K::Char('N'), M::NONE) => match self.last_search {
+                Some(ref search) => Cmd::ViSearch(search.opposite()),
+                None => Cmd::Noop,
+            },
+            E(K::Char('G'), M::NONE) => Cmd::Move(Movement::GotoLine(if no_num_args {
+                RepeatCount::MAX // last line
+            } else {
+                n
+            })),
+            E(K::Char('g'), M::NONE) => match self.next_key(rdr, false)? {
+                E(K::Char('g'), M::NONE) => Cmd::Move(Movement::GotoLine(n)),
+                // display line motions
+                E(K::Char('j') | K::Down, M::NONE) => Cmd::VisualLineDown(n),
+                E(K::Char('k') | K::Up, M::NONE) => Cmd::VisualLineUp(n),
+                E(K::Char('0') | K::Home, M::NONE) => Cmd::BeginningOfVisualLine,
+                E(K::Char('$') | K::End, M::NONE) => Cmd::EndOfVisualLine,
+                E(K::Char('+'), M::NONE) => Cmd::UndoBranch(n),
+                _ => Cmd::Unknown,
+            },
+            E(K::Char('{'), M::NONE) => Cmd::Move(Movement::BackwardParagraph(n)),
+            E(K::Char('}'), M::NONE) => Cmd::Move(Movement::ForwardParagraph(n)),
+            E(K::Char('m'), M::NONE) => match self.vi_mark(rdr)? {
+                Some(mark) => Cmd::SetMark(mark), // vi-set-mark
+                None => Cmd::Unknown,
+            },
+            E(K::Char(c @ ('`' | '\'')), M::NONE) => match self.vi_mark(rdr)? {
+                Some(mark) => Cmd::Move(Movement::ViMark(mark, c == '\'')), // vi-goto-mark
+                None => Cmd::Unknown,
+            },
             E(K::Char('p'), M::NONE) => Cmd::Yank(n, Anchor::After), // vi-put
             E(K::Char('P'), M::NONE) => Cmd::Yank(n, Anchor::Before), // vi-put
             E(K::Char('r'), M::NONE) => {
                 // vi-replace-char:
-                let ch = rdr.next_key(false)?;
+                let ch = self.next_key(rdr, false)?;
                 match ch {
                     E(K::Char(c), M::NONE) => Cmd::ReplaceChar(n, c),
                     E::ESC => Cmd::Noop,
@@ -827,9 +1231,22 @@ impl<'b> InputState<'b> {
             // TODO move backward if eol
             E(K::Char('x'), M::NONE) => Cmd::Kill(Movement::ForwardChar(n)), // vi-delete
             E(K::Char('X'), M::NONE) => Cmd::Kill(Movement::BackwardChar(n)), // vi-rubout
-            E(K::Char('y'), M::NONE) => match self.vi_cmd_motion(rdr, wrt, key, n)? {
-                Some(mvt) => Cmd::ViYankTo(mvt),
-                None => Cmd::Unknown,
+            E(K::Char('y'), M::NONE) => match self.next_key(rdr, false)? {
+                E(K::Char('s'), M::NONE) => {
+                    // surround: `yss` surrounds the whole line
+                    let key = E(K::Char('s'), M::NONE);
+                    match self.vi_cmd_motion(rdr, wrt, key, n)? {
+                        Some(mvt) => match self.vi_surround_char(rdr)? {
+                            Some(c) => Cmd::Surround(mvt, c),
+                            None => Cmd::Unknown,
+                        },
+                        None => Cmd::Unknown,
+                    }
+                }
+                mvt => match self.vi_motion(rdr, wrt, key, mvt, n)? {
+                    Some(mvt) => Cmd::ViYankTo(mvt),
+                    None => Cmd::Unknown,
+                },
             },
             // E(K::Char('Y'), M::NONE) => Cmd::???, // vi-yank-to
             E(K::Char('h'), M::NONE) | E(K::Char('H'), M::CTRL) | E::BACKSPACE => {
@@ -844,10 +1261,7 @@ impl<'b> InputState<'b> {
             E(K::Char('-' | 'k'), M::NONE) => Cmd::LineUpOrPreviousHistory(n),
             // TODO: move to the start of the line.
             E(K::Char('P'), M::CTRL) => Cmd::PreviousHistory,
-            E(K::Char('R'), M::CTRL) => {
-                self.input_mode = InputMode::Insert; // TODO Validate
-                Cmd::ReverseSearchHistory
-            }
+            E(K::Char('R'), M::CTRL) => Cmd::Redo(n),
             E(K::Char('S'), M::CTRL) => {
                 self.input_mode = InputMode::Insert; // TODO Validate
                 Cmd::ForwardSearchHistory
@@ -860,6 +1274,16 @@ impl<'b> InputState<'b> {
                 Some(mvt) => Cmd::Indent(mvt),
                 None => Cmd::Unknown,
             },
+            E(K::Char('z'), M::NONE) => match self.next_key(rdr, false)? {
+                // open folds
+                E(K::Char('o' | 'O' | 'R'), M::NONE) => Cmd::ExpandPaste,
+                _ => Cmd::Unknown,
+            },
+            E(K::Char('Q'), M::CTRL) => {
+                // visual block mode (like vim's Ctrl-Q, Ctrl-V being quoted-insert)
+                self.block_select = true;
+                Cmd::ViBlock(BlockOp::Select)
+            }
             E::ESC => Cmd::Noop,
             _ => self.common(rdr, wrt, evt, key, n, true)?,
         };
@@ -870,20 +1294,73 @@ impl<'b> InputState<'b> {
         Ok(cmd)
     }
 
+    /// Only movements and block operations are allowed in visual block mode
+    fn vi_block<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        wrt: &mut dyn Refresher,
+        key: KeyEvent,
+    ) -> Result<Cmd> {
+        let op = match key {
+            E(K::Char('y'), M::NONE) => Some(BlockOp::Yank),
+            E(K::Char('d' | 'x'), M::NONE) | E(K::Delete, M::NONE) => Some(BlockOp::Kill),
+            E(K::Char('c' | 's'), M::NONE) => Some(BlockOp::Change),
+            E(K::Char('I'), M::NONE) => Some(BlockOp::Insert(Anchor::Before)),
+            E(K::Char('A'), M::NONE) => Some(BlockOp::Insert(Anchor::After)),
+            E::ESC | E(K::Char('C' | 'G' | 'Q'), M::CTRL) => Some(BlockOp::Cancel),
+            _ => None,
+        };
+        let cmd = if let Some(op) = op {
+            self.block_select = false;
+            if let BlockOp::Change | BlockOp::Insert(_) = op {
+                self.input_mode = InputMode::Insert;
+                wrt.doing_insert();
+            }
+            Cmd::ViBlock(op)
+        } else {
+            let mvt = match key {
+                E(K::Left, M::NONE) => Some(Movement::BackwardChar(1)),
+                E(K::Right, M::NONE) => Some(Movement::ForwardChar(1)),
+                E(K::Up, M::NONE) => Some(Movement::LineUp(1)),
+                E(K::Down, M::NONE) => Some(Movement::LineDown(1)),
+                _ => self.vi_motion(rdr, wrt, E::ESC, key, 1)?,
+            };
+            match mvt {
+                // cursor on the last char of the word, not after
+                Some(Movement::ForwardWord(n, At::AfterEnd, word)) => {
+                    Cmd::Move(Movement::ForwardWord(n, At::BeforeEnd, word))
+                }
+                Some(mvt) => Cmd::Move(mvt),
+                None => Cmd::Noop,
+            }
+        };
+        debug!(target: "rustyline", "Vi block: {:?}", cmd);
+        Ok(cmd)
+    }
+
     fn vi_insert<R: RawReader>(
         &mut self,
         rdr: &mut R,
         wrt: &mut dyn Refresher,
         key: KeyEvent,
     ) -> Result<Cmd> {
-        let evt = key.into();
-        if let Some(cmd) = self.custom_binding(wrt, &evt, 0, true) {
-            return Ok(if cmd.is_repeatable() {
+        let mut evt = key.into();
+        let cmd = match self.custom_binding(wrt, &evt, 0, true) {
+            None => self.custom_seq_binding(rdr, wrt, &mut evt, 0, true)?,
+            cmd => cmd,
+        };
+        if let Some(cmd) = cmd {
+            return Ok(if cmd == Cmd::ViMovementMode {
+                self.vi_movement_mode(wrt)
+            } else if cmd.is_repeatable() {
                 cmd.redo(None, wrt)
             } else {
                 cmd
             });
-        } else if let Some(cmd) = InputState::term_binding(rdr, wrt, &key) {
+        }
+        // no binding: the keys are handled literally
+        self.unread_keys(&evt);
+        if let Some(cmd) = self.term_binding(rdr, wrt, &key) {
             return Ok(cmd);
         }
         let cmd = match key {
@@ -906,12 +1383,7 @@ impl<'b> InputState<'b> {
 
                 self.vi_command(rdr, wrt, E(K::Char(k), M::NONE))?
             }
-            E::ESC => {
-                // vi-movement-mode/vi-command-mode
-                self.input_mode = InputMode::Command;
-                wrt.done_inserting();
-                Cmd::Move(Movement::BackwardChar(1))
-            }
+            E::ESC => self.vi_movement_mode(wrt),
             _ => self.common(rdr, wrt, evt, key, 1, true)?,
         };
         debug!(target: "rustyline", "Vi insert: {:?}", cmd);
@@ -920,6 +1392,8 @@ impl<'b> InputState<'b> {
                 // replacing...
             } else if let (Cmd::SelfInsert(..), Cmd::SelfInsert(..)) = (&self.last_cmd, &cmd) {
                 // inserting...
+            } else if self.input_mode == InputMode::Replace {
+                // restoring overwritten chars...
             } else {
                 self.last_cmd = cmd.clone();
             }
@@ -927,6 +1401,13 @@ impl<'b> InputState<'b> {
         Ok(cmd)
     }
 
+    /// vi-movement-mode/vi-command-mode
+    fn vi_movement_mode(&mut self, wrt: &mut dyn Refresher) -> Cmd {
+        self.input_mode = InputMode::Command;
+        wrt.done_inserting();
+        Cmd::Move(Movement::BackwardChar(1))
+    }
+
     fn vi_cmd_motion<R: RawReader>(
         &mut self,
         rdr: &mut R,
@@ -934,17 +1415,41 @@ impl<'b> InputState<'b> {
         key: KeyEvent,
         n: RepeatCount,
     ) -> Result<Option<Movement>> {
-        let mut mvt = rdr.next_key(false)?;
+        let mvt = self.next_key(rdr, false)?;
+        self.vi_motion(rdr, wrt, key, mvt, n)
+    }
+
+    fn vi_motion<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        wrt: &mut dyn Refresher,
+        key: KeyEvent,
+        mut mvt: KeyEvent,
+        n: RepeatCount,
+    ) -> Result<Option<Movement>> {
         if mvt == key {
             return Ok(Some(Movement::WholeLine));
         }
         let mut n = n;
+        let mut counted = n != 1;
         if let E(K::Char(digit @ '1'..='9'), M::NONE) = mvt {
             // vi-arg-digit
             mvt = self.vi_arg_digit(rdr, wrt, digit)?;
             n = self.vi_num_args().saturating_mul(n);
+            counted = true;
         }
         Ok(match mvt {
+            E(K::Char('G'), M::NONE) => Some(Movement::GotoLine(if counted {
+                n
+            } else {
+                RepeatCount::MAX // last line
+            })),
+            E(K::Char('g'), M::NONE) => match self.next_key(rdr, false)? {
+                E(K::Char('g'), M::NONE) => Some(Movement::GotoLine(n)),
+                _ => None,
+            },
+            E(K::Char('{'), M::NONE) => Some(Movement::BackwardParagraph(n)),
+            E(K::Char('}'), M::NONE) => Some(Movement::ForwardParagraph(n)),
             E(K::Char('$'), M::NONE) => Some(Movement::EndOfLine),
             E(K::Char('0'), M::NONE) => Some(Movement::BeginningOfLine),
             E(K::Char('^'), M::NONE) => Some(Movement::ViFirstPrint),
@@ -965,6 +1470,9 @@ impl<'b> InputState<'b> {
             E(K::Char('h'), M::NONE) | E(K::Char('H'), M::CTRL) | E::BACKSPACE => {
                 Some(Movement::BackwardChar(n))
             }
+            E(K::Char(c @ ('`' | '\'')), M::NONE) => self
+                .vi_mark(rdr)?
+                .map(|mark| Movement::ViMark(mark, c == '\'')),
             E(K::Char('l' | ' '), M::NONE) => Some(Movement::ForwardChar(n)),
             E(K::Char('j' | '+'), M::NONE) => Some(Movement::LineDown(n)),
             E(K::Char('k' | '-'), M::NONE) => Some(Movement::LineUp(n)),
@@ -993,7 +1501,7 @@ impl<'b> InputState<'b> {
         rdr: &mut R,
         cmd: char,
     ) -> Result<Option<CharSearch>> {
-        let ch = rdr.next_key(false)?;
+        let ch = self.next_key(rdr, false)?;
         Ok(match ch {
             E(K::Char(ch), M::NONE) => {
                 let cs = match cmd {
@@ -1010,6 +1518,106 @@ impl<'b> InputState<'b> {
         })
     }
 
+    fn vi_surround_char<R: RawReader>(&mut self, rdr: &mut R) -> Result<Option<char>> {
+        let ch = self.next_key(rdr, false)?;
+        Ok(match ch {
+            E(K::Char(c), M::NONE) => Some(c),
+            _ => None,
+        })
+    }
+
+    /// Read a search pattern displayed after `delimiter` (`/` or `?`) until
+    /// Enter. Return `None` if the search is aborted.
+    fn vi_search<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        wrt: &mut dyn Refresher,
+        delimiter: char,
+    ) -> Result<Option<ViSearch>> {
+        let mut input = String::new();
+        let accepted = loop {
+            wrt.refresh_prompt_and_line(&format!("{delimiter}{input}"))?;
+            match self.next_key(rdr, true)? {
+                E(K::Char(c), M::NONE) => input.push(c),
+                E(K::Backspace, M::NONE) | E(K::Char('H'), M::CTRL) if input.is_empty() => {
+                    break false;
+                }
+                E(K::Backspace, M::NONE) | E(K::Char('H'), M::CTRL) => {
+                    input.pop();
+                }
+                E(K::Enter, M::NONE) | E(K::Char('J' | 'M'), M::CTRL) => break true,
+                E(K::Esc, M::NONE) | E(K::Char('C' | 'G'), M::CTRL) => break false,
+                _ => {}
+            }
+        };
+        wrt.refresh_line()?; // restore prompt
+        if !accepted {
+            return Ok(None);
+        }
+        // `/` searches older entries like readline
+        let direction = if delimiter == '/' {
+            SearchDirection::Reverse
+        } else {
+            SearchDirection::Forward
+        };
+        let search = ViSearch::parse(&input, delimiter, direction, self.last_search.as_ref());
+        if let Some(ref search) = search {
+            self.last_search = Some(search.clone());
+        }
+        Ok(search)
+    }
+
+    /// Read the register operation and name following `C-x r`
+    fn emacs_register<R: RawReader>(&mut self, rdr: &mut R) -> Result<Cmd> {
+        let op = match self.next_key(rdr, false)? {
+            E(K::Char('s'), M::NONE) => RegisterOp::CopyLine,
+            E(K::Char('i'), M::NONE) => RegisterOp::Insert,
+            E(K::Char(' '), M::NONE) => RegisterOp::Point,
+            E(K::Char('j'), M::NONE) => RegisterOp::Jump,
+            _ => return Ok(Cmd::Unknown),
+        };
+        Ok(match self.next_key(rdr, false)? {
+            E(K::Char(name), M::NONE) => Cmd::Register(op, name),
+            _ => Cmd::Unknown,
+        })
+    }
+
+    /// Read the surround operation and characters following `C-x s`
+    fn emacs_surround<R: RawReader>(&mut self, rdr: &mut R, n: RepeatCount) -> Result<Cmd> {
+        let mvt = match self.next_key(rdr, false)? {
+            E(K::Char('w'), M::NONE) => Movement::ForwardWord(n, At::AfterEnd, Word::Emacs),
+            E(K::Char('s'), M::NONE) => Movement::WholeLine,
+            E(K::Char('d'), M::NONE) => {
+                return Ok(match self.vi_surround_char(rdr)? {
+                    Some(c) => Cmd::DeleteSurround(c),
+                    None => Cmd::Unknown,
+                });
+            }
+            E(K::Char('c'), M::NONE) => {
+                return Ok(match self.vi_surround_char(rdr)? {
+                    Some(old) => match self.vi_surround_char(rdr)? {
+                        Some(new) => Cmd::ChangeSurround(old, new),
+                        None => Cmd::Unknown,
+                    },
+                    None => Cmd::Unknown,
+                });
+            }
+            _ => return Ok(Cmd::Unknown),
+        };
+        Ok(match self.vi_surround_char(rdr)? {
+            Some(c) => Cmd::Surround(mvt, c),
+            None => Cmd::Unknown,
+        })
+    }
+
+    fn vi_mark<R: RawReader>(&mut self, rdr: &mut R) -> Result<Option<char>> {
+        let ch = self.next_key(rdr, false)?;
+        Ok(match ch {
+            E(K::Char(mark @ 'a'..='z'), M::NONE) => Some(mark),
+            _ => None,
+        })
+    }
+
     fn common<R: RawReader>(
         &mut self,
         rdr: &mut R,
@@ -1020,12 +1628,6 @@ impl<'b> InputState<'b> {
         positive: bool,
     ) -> Result<Cmd> {
         Ok(match key {
-            E(K::Home, M::NONE) => Cmd::Move(Movement::BeginningOfLine),
-            E(K::Left, M::NONE) => Cmd::Move(if positive {
-                Movement::BackwardChar(n)
-            } else {
-                Movement::ForwardChar(n)
-            }),
             #[cfg(any(windows, test))]
             E(K::Char('C'), M::CTRL) => Cmd::Interrupt,
             E(K::Char('D'), M::CTRL) => {
@@ -1041,58 +1643,35 @@ impl<'b> InputState<'b> {
                     Cmd::Unknown
                 }
             }
-            E(K::Delete, M::NONE) => Cmd::Kill(if positive {
-                Movement::ForwardChar(n)
-            } else {
-                Movement::BackwardChar(n)
-            }),
-            E(K::End, M::NONE) => Cmd::Move(Movement::EndOfLine),
-            E(K::Right, M::NONE) => Cmd::Move(if positive {
-                Movement::ForwardChar(n)
-            } else {
-                Movement::BackwardChar(n)
-            }),
             E(K::Char('J' | 'M'), M::CTRL) | E::ENTER => Cmd::AcceptOrInsertLine {
                 accept_in_the_middle: true,
             },
+            E(K::Enter, M::ALT) => Cmd::ForceAcceptLine,
             E(K::Down, M::NONE) => Cmd::LineDownOrNextHistory(1),
             E(K::Up, M::NONE) => Cmd::LineUpOrPreviousHistory(1),
             E(K::Char('R'), M::CTRL) => Cmd::ReverseSearchHistory,
             // most terminals override Ctrl+S to suspend execution
             E(K::Char('S'), M::CTRL) => Cmd::ForwardSearchHistory,
-            E(K::Char('T'), M::CTRL) => Cmd::TransposeChars,
-            E(K::Char('U'), M::CTRL) => Cmd::Kill(if positive {
-                Movement::BeginningOfLine
-            } else {
-                Movement::EndOfLine
-            }),
             // most terminals override Ctrl+Q to resume execution
             E(K::Char('Q'), M::CTRL) => Cmd::QuotedInsert,
             #[cfg(not(windows))]
             E(K::Char('V'), M::CTRL) => Cmd::QuotedInsert,
             #[cfg(windows)]
             E(K::Char('V'), M::CTRL) => Cmd::PasteFromClipboard,
-            E(K::Char('W'), M::CTRL) => Cmd::Kill(if positive {
-                Movement::BackwardWord(n, Word::Big)
-            } else {
-                Movement::ForwardWord(n, At::AfterEnd, Word::Big)
-            }),
-            E(K::Char('Y'), M::CTRL) => {
-                if positive {
-                    Cmd::Yank(n, Anchor::Before)
-                } else {
-                    Cmd::Unknown // TODO Validate
-                }
-            }
-            E(K::Char('_'), M::CTRL) => Cmd::Undo(n),
-            E(K::UnknownEscSeq, M::NONE) => Cmd::Noop,
+            #[cfg(windows)]
+            E(K::Char('V'), M::CTRL_SHIFT) => Cmd::PasteFromClipboard,
+            E(K::UnknownEscSeq | K::FocusIn | K::FocusOut, M::NONE) => Cmd::Noop,
             E(K::BracketedPasteStart, M::NONE) => {
                 let paste = rdr.read_pasted_text()?;
                 Cmd::Insert(1, paste)
             }
-            _ => self
-                .custom_seq_binding(rdr, wrt, &mut evt, n, positive)?
-                .unwrap_or(Cmd::Unknown),
+            // line editing (see `rustyline_core::keymap`)
+            _ => match rustyline_core::keymap::common(key, n, positive) {
+                Some(cmd) => cmd.into(),
+                None => self
+                    .custom_seq_binding(rdr, wrt, &mut evt, n, positive)?
+                    .unwrap_or(Cmd::Unknown),
+            },
         })
     }
 
@@ -1131,46 +1710,105 @@ impl<'b> InputState<'b> {
 
 #[cfg(feature = "custom-bindings")]
 impl InputState<'_> {
+    /// Current mode for scoped bindings
+    fn binding_mode(&self) -> BindingMode {
+        match (self.mode, self.input_mode) {
+            (EditMode::Emacs, _) => BindingMode::Emacs,
+            (EditMode::Vi, InputMode::Command) => BindingMode::ViCommand,
+            (EditMode::Vi, InputMode::Insert | InputMode::Replace) => BindingMode::ViInsert,
+        }
+    }
+
     /// Application customized binding
     fn custom_binding(
-        &self,
+        &mut self,
         wrt: &dyn Refresher,
         evt: &Event,
         n: RepeatCount,
         positive: bool,
     ) -> Option<Cmd> {
-        let bindings = self.custom_bindings;
-        let handler = bindings.get(evt).or_else(|| bindings.get(&Event::Any));
-        if let Some(handler) = handler {
-            match handler {
-                EventHandler::Simple(cmd) => Some(cmd.clone()),
-                EventHandler::Conditional(handler) => {
-                    let ctx = EventContext::new(self, wrt);
-                    handler.handle(evt, n, positive, &ctx)
-                }
+        let scope = self.binding_mode();
+        let [mode, global] = self.custom_bindings.tries(scope);
+        let handler = mode
+            .get(evt)
+            .map(|h| (Some(scope), false, h))
+            .or_else(|| global.get(evt).map(|h| (None, false, h)))
+            .or_else(|| mode.get(&Event::Any).map(|h| (Some(scope), true, h)))
+            .or_else(|| global.get(&Event::Any).map(|h| (None, true, h)));
+        let (scope, any, handler) = handler?;
+        let cmd = match handler {
+            EventHandler::Simple(cmd) => Some(cmd.clone()),
+            EventHandler::Conditional(handler) => {
+                let ctx = EventContext::new(self, wrt);
+                handler.handle(evt, n, positive, &ctx)
             }
+        };
+        self.traced_custom(scope, any, handler, cmd.is_some());
+        cmd
+    }
+
+    /// Record the outcome of a custom binding
+    fn traced_custom(
+        &mut self,
+        scope: Option<BindingMode>,
+        any: bool,
+        handler: &EventHandler,
+        fired: bool,
+    ) {
+        let Some(ref mut trace) = self.trace else {
+            return;
+        };
+        let source = BindingSource::Custom {
+            scope,
+            any,
+            conditional: matches!(handler, EventHandler::Conditional(_)),
+        };
+        if fired {
+            trace.source = source;
         } else {
-            None
+            trace.declined.push(source);
         }
     }
 
     fn custom_seq_binding<R: RawReader>(
-        &self,
+        &mut self,
         rdr: &mut R,
         wrt: &dyn Refresher,
         evt: &mut Event,
         n: RepeatCount,
         positive: bool,
     ) -> Result<Option<Cmd>> {
-        while let Some(subtrie) = self.custom_bindings.get_raw_descendant(evt) {
-            let snd_key = rdr.next_key(true)?;
+        let bindings = self.custom_bindings;
+        let [mode, global] = bindings.tries(self.binding_mode());
+        loop {
+            let mode_subtrie = mode.get_raw_descendant(evt);
+            let global_subtrie = global.get_raw_descendant(evt);
+            if mode_subtrie.is_none() && global_subtrie.is_none() {
+                break;
+            }
+            if let Some(timeout_ms) = self.mapping_timeout {
+                if self.pending_keys.is_empty() && !rdr.poll_key(timeout_ms)? {
+                    break;
+                }
+            }
+            let snd_key = self.next_key(rdr, true)?;
             if let Event::KeySeq(ref mut key_seq) = evt {
                 key_seq.push(snd_key);
             } else {
                 break;
             }
-            let handler = subtrie.get(evt).unwrap();
-            if let Some(handler) = handler {
+            let scope = self.binding_mode();
+            let handler = mode_subtrie
+                .as_ref()
+                .and_then(|subtrie| subtrie.get(evt).ok().flatten())
+                .map(|h| (Some(scope), h))
+                .or_else(|| {
+                    global_subtrie
+                        .as_ref()
+                        .and_then(|subtrie| subtrie.get(evt).ok().flatten())
+                        .map(|h| (None, h))
+                });
+            if let Some((scope, handler)) = handler {
                 let cmd = match handler {
                     EventHandler::Simple(cmd) => Some(cmd.clone()),
                     EventHandler::Conditional(handler) => {
@@ -1178,6 +1816,7 @@ impl InputState<'_> {
                         handler.handle(evt, n, positive, &ctx)
                     }
                 };
+                self.traced_custom(scope, false, handler, cmd.is_some());
                 if cmd.is_some() {
                     return Ok(cmd);
                 }
@@ -1189,12 +1828,18 @@ impl InputState<'_> {
 
 #[cfg(not(feature = "custom-bindings"))]
 impl<'b> InputState<'b> {
-    fn custom_binding(&self, _: &dyn Refresher, _: &Event, _: RepeatCount, _: bool) -> Option<Cmd> {
+    fn custom_binding(
+        &mut self,
+        _: &dyn Refresher,
+        _: &Event,
+        _: RepeatCount,
+        _: bool,
+    ) -> Option<Cmd> {
         None
     }
 
     fn custom_seq_binding<R: RawReader>(
-        &self,
+        &mut self,
         _: &mut R,
         _: &dyn Refresher,
         _: &mut Event,
@@ -1207,7 +1852,45 @@ impl<'b> InputState<'b> {
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "custom-bindings")] {
-pub type Bindings = radix_trie::Trie<Event, EventHandler>;
+type Trie = radix_trie::Trie<Event, EventHandler>;
+
+/// Global and mode scoped bindings
+#[derive(Default)]
+pub struct Bindings {
+    global: Trie,
+    modes: [Trie; 3],
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn trie_mut(&mut self, mode: Option<BindingMode>) -> &mut Trie {
+        match mode {
+            None => &mut self.global,
+            Some(mode) => &mut self.modes[mode as usize],
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        mode: Option<BindingMode>,
+        evt: Event,
+        handler: EventHandler,
+    ) -> Option<EventHandler> {
+        self.trie_mut(mode).insert(evt, handler)
+    }
+
+    pub fn remove(&mut self, mode: Option<BindingMode>, evt: &Event) -> Option<EventHandler> {
+        self.trie_mut(mode).remove(evt)
+    }
+
+    /// Bindings scoped to `mode` first then global ones
+    fn tries(&self, mode: BindingMode) -> [&Trie; 2] {
+        [&self.modes[mode as usize], &self.global]
+    }
+}
     } else {
 enum Event {
    KeySeq([KeyEvent; 1]),
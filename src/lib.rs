@@ -21,11 +21,23 @@
 
 #[cfg(feature = "custom-bindings")]
 mod binding;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "with-arboard")]
+mod clipboard;
 mod command;
+pub mod compat;
 pub mod completion;
 pub mod config;
 mod edit;
 pub mod error;
+#[cfg(all(
+    feature = "fuzzing",
+    unix,
+    not(target_arch = "wasm32"),
+    not(feature = "test-terminal")
+))]
+pub mod fuzzing;
 pub mod highlight;
 pub mod hint;
 pub mod history;
@@ -33,9 +45,17 @@ mod keymap;
 mod keys;
 mod kill_ring;
 mod layout;
+pub use rustyline_core::engine;
 pub mod line_buffer;
+mod line_editor;
+pub mod logging;
+mod register;
+#[cfg(feature = "serde")]
+mod serde;
+mod service;
 #[cfg(feature = "with-sqlite-history")]
 pub mod sqlite_history;
+mod stats;
 mod tty;
 mod undo;
 pub mod validate;
@@ -44,8 +64,38 @@ use std::fmt;
 use std::io::{self, BufRead, Write};
 use std::path::Path;
 use std::result;
+use std::sync::Arc;
 
 use log::debug;
+#[cfg(all(feature = "derive", feature = "custom-bindings"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "derive", feature = "custom-bindings"))))]
+///
+/// ```
+/// use rustyline::{DefaultEditor, Movement, Word};
+///
+/// let mut editor = DefaultEditor::new()?;
+/// for (keys, cmd) in rustyline::bindings! {
+///     "C-x C-e" => ClearScreen,
+///     "M-Left" => Move(Movement::BackwardWord(1, Word::Emacs)),
+/// } {
+///     editor.bind_sequence(keys, cmd);
+/// }
+/// # Ok::<(), rustyline::error::ReadlineError>(())
+/// ```
+///
+/// Invalid key specifications are compile errors:
+///
+/// ```compile_fail
+/// let _ = rustyline::bindings! { "C-Foo" => Noop };
+/// ```
+///
+/// ```compile_fail
+/// let _ = rustyline::bindings! { "" => Noop };
+/// ```
+pub use rustyline_derive::bindings;
+// `::rustyline` paths generated by `bindings!`, in tests
+#[cfg(all(test, feature = "derive", feature = "custom-bindings"))]
+extern crate self as rustyline;
 #[cfg(feature = "derive")]
 #[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
 pub use rustyline_derive::{Completer, Helper, Highlighter, Hinter, Validator};
@@ -53,23 +103,40 @@ pub use rustyline_derive::{Completer, Helper, Highlighter, Hinter, Validator};
 use crate::tty::{Buffer, RawMode, RawReader, Renderer, Term, Terminal};
 
 #[cfg(feature = "custom-bindings")]
-pub use crate::binding::{ConditionalEventHandler, Event, EventContext, EventHandler};
+pub use crate::binding::{BindingMode, ConditionalEventHandler, Event, EventContext, EventHandler};
 use crate::completion::{longest_common_prefix, Candidate, Completer};
-pub use crate::config::{Behavior, ColorMode, CompletionType, Config, EditMode, HistoryDuplicates};
+pub use crate::config::{
+    Behavior, ClipboardBackend, ClipboardMode, ColorMode, CompletionType, Config, ConfigHandle,
+    CursorShape, EditMode, HistoryDuplicates, Passthrough, Preset, PromptOverflow, QueuePolicy,
+    TabInsertion, UndoGrouping,
+};
 use crate::edit::State;
 use crate::error::ReadlineError;
-use crate::highlight::{CmdKind, Highlighter};
+use crate::highlight::{Background, CmdKind, Highlighter, PromptMode};
 use crate::hint::Hinter;
 use crate::history::{DefaultHistory, History, SearchDirection};
-pub use crate::keymap::{Anchor, At, CharSearch, Cmd, InputMode, Movement, RepeatCount, Word};
+pub use crate::keymap::{
+    Anchor, At, BindingSource, BindingTrace, BindingTracer, BlockOp, CharSearch, Cmd, CursorOp,
+    InputMode, Movement, RegisterOp, RepeatCount, SearchOffset, ViSearch, Word,
+};
 use crate::keymap::{Bindings, InputState, Refresher};
 pub use crate::keys::{KeyCode, KeyEvent, Modifiers};
 use crate::kill_ring::KillRing;
-pub use crate::layout::GraphemeClusterMode;
 use crate::layout::Unit;
+pub use crate::layout::{AmbiguousWidth, GraphemeClusterMode};
+pub use crate::line_editor::{DynHelper, LineEditor};
+use crate::register::Registers;
+pub use crate::service::{EditorService, ServiceEvent, ServiceRequest};
+use crate::stats::RenderCounters;
+pub use crate::stats::RenderStats;
 pub use crate::tty::ExternalPrinter;
-pub use crate::undo::Changeset;
+#[cfg(all(any(target_arch = "wasm32", feature = "test-terminal"), not(test)))]
+pub use crate::tty::{set_host, Host, HostEvent};
+#[cfg(all(feature = "test-terminal", not(test)))]
+pub use crate::tty::{Cell, Color, Screen, Style, TestTerminal, Transcript, TranscriptEvent};
+pub use crate::undo::{Changeset, UndoTree};
 use crate::validate::Validator;
+pub use rustyline_core::normalize::NormalizationForm;
 
 /// The error type for I/O and Linux Syscalls (Errno)
 pub type Result<T> = result::Result<T, ReadlineError>;
@@ -91,7 +158,11 @@ fn complete_line<H: Helper>(
     let (start, candidates) = completer.complete(&s.line, s.line.pos(), &s.ctx)?;
     // if no completions, we are done
     if candidates.is_empty() {
-        s.out.beep()?;
+        if config.tab_insertion() == TabInsertion::Nothing {
+            s.out.beep()?;
+        } else {
+            s.edit_insert_tab(config.tab_insertion(), config.indent_size())?;
+        }
         Ok(None)
     } else if CompletionType::Circular == config.completion_type() {
         let mark = s.changes.begin();
@@ -178,8 +249,10 @@ fn complete_line<H: Helper>(
         s.line.set_pos(save_pos);
         // we got a second tab, maybe show list of possible completions
         let show_completions = if candidates.len() > config.completion_prompt_limit() {
-            let msg = format!("\nDisplay all {} possibilities? (y or n)", candidates.len());
-            s.out.write_and_flush(msg.as_str())?;
+            let query = config
+                .completion_query()
+                .replace("{}", &candidates.len().to_string());
+            s.out.write_and_flush(&format!("\n{query}"))?;
             s.layout.end.row += 1;
             while cmd != Cmd::SelfInsert(1, 'y')
                 && cmd != Cmd::SelfInsert(1, 'Y')
@@ -194,7 +267,7 @@ fn complete_line<H: Helper>(
             true
         };
         if show_completions {
-            page_completions(rdr, s, input_state, &candidates)
+            page_completions(rdr, s, input_state, &candidates, config)
         } else {
             s.refresh_line()?;
             Ok(None)
@@ -287,10 +360,11 @@ fn page_completions<C: Candidate, H: Helper>(
     s: &mut State<'_, '_, H>,
     input_state: &mut InputState,
     candidates: &[C],
+    config: &Config,
 ) -> Result<Option<Cmd>> {
     use std::cmp;
 
-    let min_col_pad = 2;
+    let min_col_pad = config.completion_column_padding();
     let cols = s.out.get_columns();
     let max_width = cmp::min(
         cols,
@@ -299,17 +373,33 @@ fn page_completions<C: Candidate, H: Helper>(
             .map(|c| s.layout.width(c.display()))
             .max()
             .unwrap()
-            + min_col_pad,
-    );
-    let num_cols = cols / max_width;
+            .saturating_add(min_col_pad),
+    )
+    .max(1);
+    let mut num_cols = (cols / max_width).max(1);
+    if let Some(max_cols) = config.completion_max_columns() {
+        num_cols = num_cols.min(max_cols);
+    }
     let nbc = u16::try_from(candidates.len()).unwrap();
 
-    let mut pause_row = s.out.get_rows() - 1;
+    let page_rows = config
+        .completion_page_rows()
+        .unwrap_or_else(|| s.out.get_rows() - 1);
+    let mut pause_row = page_rows;
     let num_rows = nbc.div_ceil(num_cols);
+    // index of the candidate at `row`, `col`
+    let index = |row: u16, col: u16| {
+        if config.completion_horizontal() {
+            row * num_cols + col
+        } else {
+            col * num_rows + row
+        }
+    };
     let mut ab = String::new();
     for row in 0..num_rows {
         if row == pause_row {
-            s.out.write_and_flush("\n--More--")?;
+            s.out
+                .write_and_flush(&format!("\n{}", config.completion_more_prompt()))?;
             let mut cmd = Cmd::Noop;
             while cmd != Cmd::SelfInsert(1, 'y')
                 && cmd != Cmd::SelfInsert(1, 'Y')
@@ -327,7 +417,7 @@ fn page_completions<C: Candidate, H: Helper>(
             }
             match cmd {
                 Cmd::SelfInsert(1, 'y' | 'Y' | ' ') => {
-                    pause_row += s.out.get_rows() - 1;
+                    pause_row += page_rows;
                 }
                 Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. } => {
                     pause_row += 1;
@@ -338,7 +428,7 @@ fn page_completions<C: Candidate, H: Helper>(
         s.out.write_and_flush("\n")?;
         ab.clear();
         for col in 0..num_cols {
-            let i = (col * num_rows) + row;
+            let i = index(row, col);
             if i < nbc {
                 let candidate = &candidates[i as usize].display();
                 let width = s.layout.width(candidate);
@@ -347,7 +437,7 @@ fn page_completions<C: Candidate, H: Helper>(
                 } else {
                     ab.push_str(candidate);
                 }
-                if ((col + 1) * num_rows) + row < nbc {
+                if col + 1 < num_cols && index(row, col + 1) < nbc {
                     for _ in width..max_width {
                         ab.push(' ');
                     }
@@ -391,11 +481,15 @@ fn reverse_incremental_search<H: Helper, I: History>(
         } else {
             format!("(failed reverse-i-search)`{search_buf}': ")
         };
+        let prompt = s.prompt_with_mode(&prompt, PromptMode::Search);
         s.refresh_prompt_and_line(&prompt)?;
 
         cmd = s.next_cmd(input_state, rdr, true, true)?;
         if let Cmd::SelfInsert(_, c) = cmd {
             search_buf.push(c);
+            if !c.is_ascii() {
+                search_buf = s.normalize(&search_buf).into_owned();
+            }
         } else {
             match cmd {
                 Cmd::Kill(Movement::BackwardChar(_)) => {
@@ -517,7 +611,12 @@ fn readline_direct(
                 let mut ctx = input.as_str();
                 let mut ctx = validate::ValidationContext::new(&mut ctx);
 
-                match v.validate(&mut ctx)? {
+                let mut result = v.validate(&mut ctx)?;
+                while let validate::ValidationResult::Pending(_) = result {
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    result = v.poll_validation(&mut ctx)?;
+                }
+                match result {
                     validate::ValidationResult::Valid(msg) => {
                         if let Some(msg) = msg {
                             writer.write_all(msg.as_bytes())?;
@@ -527,7 +626,8 @@ fn readline_direct(
                     validate::ValidationResult::Invalid(Some(msg)) => {
                         writer.write_all(msg.as_bytes())?;
                     }
-                    validate::ValidationResult::Incomplete => {
+                    validate::ValidationResult::Incomplete
+                    | validate::ValidationResult::IncompleteWith(_) => {
                         // Add newline and keep on taking input
                         if trailing_r {
                             input.push('\r');
@@ -551,6 +651,24 @@ pub trait Helper
 where
     Self: Completer + Hinter + Highlighter + Validator,
 {
+    /// Called when the terminal is resized while a line is being edited
+    /// (`SIGWINCH` on unix, console event on windows), with the new number of
+    /// columns and rows.
+    ///
+    /// Prompt and line are then re-wrapped and the hint is recomputed, so any
+    /// size-dependent state should be updated here.
+    fn on_resize(&self, cols: Unit, rows: Unit) {
+        let _ = (cols, rows);
+    }
+
+    /// Called when the terminal gains or loses focus while a line is being
+    /// edited (see [`Config::enable_focus_events`]), e.g. to pause expensive
+    /// hints or dim the prompt while unfocused.
+    ///
+    /// The line is then refreshed.
+    fn on_focus(&self, focused: bool) {
+        let _ = focused;
+    }
 }
 
 impl Helper for () {}
@@ -559,6 +677,7 @@ impl Helper for () {}
 pub struct Context<'h> {
     history: &'h dyn History,
     history_index: usize,
+    word_chars: Arc<str>,
 }
 
 impl<'h> Context<'h> {
@@ -568,9 +687,14 @@ impl<'h> Context<'h> {
         Self {
             history,
             history_index: history.len(),
+            word_chars: Arc::from(""),
         }
     }
 
+    pub(crate) fn set_word_chars(&mut self, word_chars: Arc<str>) {
+        self.word_chars = word_chars;
+    }
+
     /// Return an immutable reference to the history object.
     #[must_use]
     pub fn history(&self) -> &dyn History {
@@ -582,6 +706,13 @@ impl<'h> Context<'h> {
     pub fn history_index(&self) -> usize {
         self.history_index
     }
+
+    /// Characters which are part of words besides the alphanumeric ones
+    /// (see [`Config::word_chars`])
+    #[must_use]
+    pub fn word_chars(&self) -> &str {
+        &self.word_chars
+    }
 }
 
 /// Line editor
@@ -592,8 +723,17 @@ pub struct Editor<H: Helper, I: History> {
     history: I,
     helper: Option<H>,
     kill_ring: KillRing,
+    registers: Registers,
+    #[cfg(feature = "with-arboard")]
+    clipboard: clipboard::NativeClipboard,
     config: Config,
     custom_bindings: Bindings,
+    validation_overridden: bool,
+    background: Option<Background>,
+    background_queried: bool,
+    render_counters: Option<Arc<RenderCounters>>,
+    binding_tracer: Option<BindingTracer>,
+    config_handle: Option<ConfigHandle>,
 }
 
 /// Default editor with no helper and `DefaultHistory`
@@ -607,33 +747,83 @@ impl<H: Helper> Editor<H, DefaultHistory> {
 
     /// Create an editor with a specific configuration.
     pub fn with_config(config: Config) -> Result<Self> {
-        Self::with_history(config, DefaultHistory::with_config(config))
+        Self::with_history(config.clone(), DefaultHistory::with_config(config))
     }
 }
 
 impl<H: Helper, I: History> Editor<H, I> {
     /// Create an editor with a custom history impl.
     pub fn with_history(config: Config, history: I) -> Result<Self> {
-        let term = Terminal::new(
+        let config = config.with_env_overrides();
+        // minimal escape usage on serial consoles
+        let serial = config.serial_console().is_some();
+        let mut term = Terminal::new(
             config.color_mode(),
             config.grapheme_cluster_mode(),
+            config.detect_grapheme_cluster_mode(),
             config.behavior(),
             config.tab_stop(),
             config.bell_style(),
-            config.enable_bracketed_paste(),
+            config.enable_bracketed_paste() && !serial,
+            config.enable_synchronized_output() && !serial,
             config.enable_signals(),
+            config.serial_console(),
+            config.probe_terminal() && !serial,
+            config.passthrough(),
+            config.enable_focus_events() && !serial,
+            config.headless(),
         )?;
+        term.ambiguous_width = config.ambiguous_width();
         Ok(Self {
             term,
             buffer: None,
             history,
             helper: None,
-            kill_ring: KillRing::new(60),
+            kill_ring: KillRing::new(config.kill_ring_size()),
+            registers: Registers::default(),
+            #[cfg(feature = "with-arboard")]
+            clipboard: clipboard::NativeClipboard::default(),
             config,
             custom_bindings: Bindings::new(),
+            validation_overridden: false,
+            background: None,
+            background_queried: false,
+            render_counters: None,
+            binding_tracer: None,
+            config_handle: None,
         })
     }
 
+    /// Create an editor reading from `input` and writing to `output` (e.g.
+    /// `/dev/tty` or a pseudo-terminal opened by the application) instead of
+    /// stdin and stdout.
+    ///
+    /// Both are closed when the editor is dropped. Note that resizes are
+    /// only notified (`SIGWINCH`) for the controlling terminal.
+    #[cfg(all(
+        unix,
+        not(target_arch = "wasm32"),
+        not(feature = "test-terminal"),
+        not(test)
+    ))]
+    pub fn with_fds(
+        config: Config,
+        history: I,
+        input: std::os::unix::io::OwnedFd,
+        output: std::os::unix::io::OwnedFd,
+    ) -> Result<Self> {
+        let mut editor = Self::with_history(config, history)?;
+        editor.term.set_fds(input, output)?;
+        Ok(editor)
+    }
+
+    /// Drive this editor with `host` (e.g. a [`TestTerminal`]) instead of the
+    /// host installed by [`set_host`].
+    #[cfg(all(any(target_arch = "wasm32", feature = "test-terminal"), not(test)))]
+    pub fn set_host<T: Host + 'static>(&mut self, host: T) {
+        self.term.set_host(host);
+    }
+
     /// This method will read a line from STDIN and will display a `prompt`.
     ///
     /// `prompt` should not be styled (in case the terminal doesn't support
@@ -658,7 +848,23 @@ impl<H: Helper, I: History> Editor<H, I> {
         self.readline_with(prompt, Some(initial))
     }
 
+    /// Tells if the line returned by the last [`Editor::readline`] call was
+    /// accepted with [`Cmd::ForceAcceptLine`] (Alt-Enter by default),
+    /// bypassing the [`Validator`](validate::Validator).
+    #[must_use]
+    pub fn validation_overridden(&self) -> bool {
+        self.validation_overridden
+    }
+
     fn readline_with(&mut self, prompt: &str, initial: Option<(&str, &str)>) -> Result<String> {
+        self.validation_overridden = false;
+        if let Some(config) = self
+            .config_handle
+            .as_mut()
+            .and_then(|h| h.sync(&self.config))
+        {
+            self.apply_config(config)?;
+        }
         if self.term.is_unsupported() {
             debug!(target: "rustyline", "unsupported terminal");
             // Write prompt and flush it to stdout
@@ -670,7 +876,31 @@ impl<H: Helper, I: History> Editor<H, I> {
         } else if self.term.is_input_tty() {
             let (original_mode, term_key_map) = self.term.enable_raw_mode()?;
             let guard = Guard(&original_mode);
+            if self.config.alternate_screen() {
+                self.term.create_writer().set_alternate_screen(true)?;
+            }
+            if self.config.window_title() {
+                self.term.save_title(true)?;
+                self.set_title(prompt)?;
+            }
             let user_input = self.readline_edit(prompt, initial, &original_mode, term_key_map);
+            if self.config.window_title() {
+                self.term.save_title(false)?;
+            }
+            if self.config.alternate_screen() {
+                // back to the primary screen, with the accepted line echoed
+                let mut out = self.term.create_writer();
+                out.set_alternate_screen(false)?;
+                if let Ok(ref line) = user_input {
+                    out.write_and_flush(&format!("{prompt}{line}"))?;
+                }
+            }
+            if self.config.has_cursor_shapes() {
+                // restore terminal default cursor shape
+                self.term
+                    .create_writer()
+                    .set_cursor_shape(CursorShape::Default)?;
+            }
             if self.config.auto_add_history() {
                 if let Ok(ref line) = user_input {
                     self.add_history_entry(line.as_str())?;
@@ -699,10 +929,31 @@ impl<H: Helper, I: History> Editor<H, I> {
         let mut stdout = self.term.create_writer();
 
         self.kill_ring.reset(); // TODO recreate a new kill ring vs reset
-        let ctx = Context::new(&self.history);
+        let mut ctx = Context::new(&self.history);
+        ctx.set_word_chars(self.config.shared_word_chars());
         let mut s = State::new(&mut stdout, prompt, self.helper.as_ref(), ctx);
+        s.line.set_word_chars(self.config.shared_word_chars());
+        if self.config.horizontal_scroll() {
+            s.enable_horizontal_scroll();
+        }
+        if self.config.bidi() {
+            s.enable_bidi();
+        }
+        s.set_normalization(self.config.normalization());
+        if self.config.line_numbers() {
+            s.enable_line_numbers();
+        }
+        s.set_prompt_overflow(self.config.prompt_overflow());
+        s.set_external_print_interval(self.config.external_print_interval());
+        if self.config.render_stats() {
+            let counters = self.render_counters.get_or_insert_with(Arc::default);
+            s.set_render_counters(counters.clone());
+        }
+        s.changes.set_grouping(self.config.undo_grouping());
 
         let mut input_state = InputState::new(&self.config, &self.custom_bindings);
+        input_state.set_tracer(self.binding_tracer.as_ref());
+        s.set_prompt_mode(input_state.prompt_mode());
 
         if let Some((left, right)) = initial {
             s.line.update(
@@ -715,7 +966,10 @@ impl<H: Helper, I: History> Editor<H, I> {
         let mut rdr = self
             .term
             .create_reader(self.buffer.take(), &self.config, term_key_map);
-        if self.term.is_output_tty() && self.config.check_cursor_position() {
+        if self.term.is_output_tty()
+            && self.config.check_cursor_position()
+            && self.config.serial_console().is_none()
+        {
             if let Err(e) = s.move_cursor_at_leftmost(&mut rdr) {
                 if let ReadlineError::Signal(error::Signal::Resize) = e {
                     s.out.update_size();
@@ -724,11 +978,67 @@ impl<H: Helper, I: History> Editor<H, I> {
                 }
             }
         }
+        if !self.background_queried
+            && self.term.is_output_tty()
+            && s.out.colors_enabled()
+            && self.config.serial_console().is_none()
+        {
+            self.background_queried = true;
+            match s.out.query_background(&mut rdr) {
+                Ok(background) => self.background = background,
+                Err(ReadlineError::Signal(error::Signal::Resize)) => s.out.update_size(),
+                Err(e) => return Err(e),
+            }
+            debug!(target: "rustyline", "terminal background: {:?}", self.background);
+            if let (Some(background), Some(helper)) = (self.background, self.helper.as_ref()) {
+                helper.set_background(background);
+            }
+        }
         s.refresh_line()?;
 
+        let mut input_mode = input_state.input_mode;
+        if self.config.has_cursor_shapes() {
+            s.out
+                .set_cursor_shape(self.config.cursor_shape(input_mode))?;
+        }
+
         loop {
             let mut cmd = s.next_cmd(&mut input_state, &mut rdr, false, false)?;
 
+            // settings changed through a `ConfigHandle`
+            if let Some(config) = self.config_handle.as_mut().and_then(ConfigHandle::changed) {
+                self.config.set_live_settings(&config);
+                self.term.color_mode = config.color_mode();
+                self.term.bell_style = config.bell_style();
+                s.out
+                    .reconfigure(self.term.colors_enabled(), config.bell_style());
+                if input_state.mode != config.edit_mode() {
+                    input_state.mode = config.edit_mode();
+                    input_state.input_mode = InputMode::Insert;
+                }
+                s.set_prompt_mode(input_state.prompt_mode());
+                s.refresh_line()?;
+            }
+            // input burst: only its final state is drawn
+            let burst = self.config.coalesce_refresh() && rdr.poll_key(0)?;
+            if !burst {
+                s.flush_refresh()?;
+            }
+
+            if input_state.input_mode != input_mode {
+                input_mode = input_state.input_mode;
+                if input_mode == InputMode::Command {
+                    s.edit_block_insert_end()?;
+                }
+                if self.config.has_cursor_shapes() {
+                    s.out
+                        .set_cursor_shape(self.config.cursor_shape(input_mode))?;
+                }
+                if s.set_prompt_mode(input_state.prompt_mode()) {
+                    s.refresh_line()?;
+                }
+            }
+
             if cmd.should_reset_kill_ring() {
                 self.kill_ring.reset();
             }
@@ -755,8 +1065,9 @@ impl<H: Helper, I: History> Editor<H, I> {
                 }
             }
 
+            // no job control on serial consoles
             #[cfg(unix)]
-            if cmd == Cmd::Suspend {
+            if cmd == Cmd::Suspend && self.config.serial_console().is_none() {
                 debug!(target: "rustyline", "SIGTSTP");
                 original_mode.disable_raw_mode()?;
                 tty::suspend()?;
@@ -784,13 +1095,51 @@ impl<H: Helper, I: History> Editor<H, I> {
             #[cfg(test)]
             if matches!(
                 cmd,
-                Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. }
+                Cmd::AcceptLine
+                    | Cmd::Newline
+                    | Cmd::AcceptOrInsertLine { .. }
+                    | Cmd::ForceAcceptLine
             ) {
                 self.term.cursor = s.layout.cursor.col as usize;
             }
 
+            if let Cmd::Yank(..) = cmd {
+                if self.config.clipboard() == ClipboardMode::CopyPaste {
+                    // text copied outside the terminal
+                    let text = match self.config.clipboard_backend() {
+                        #[cfg(feature = "with-arboard")]
+                        ClipboardBackend::Native => self.clipboard.paste(),
+                        _ => s.out.paste_from_clipboard(&mut rdr)?,
+                    };
+                    if let Some(text) = text {
+                        self.kill_ring.sync(&text);
+                    }
+                }
+            }
+
+            self.validation_overridden = cmd == Cmd::ForceAcceptLine;
             // Execute things can be done solely on a state object
-            match command::execute(cmd, &mut s, &input_state, &mut self.kill_ring, &self.config)? {
+            s.coalesce_refresh(burst);
+            let status = command::execute(
+                cmd,
+                &mut s,
+                &input_state,
+                &mut self.kill_ring,
+                &mut self.registers,
+                &self.config,
+            );
+            s.coalesce_refresh(false);
+            let status = status?;
+            if let Some(text) = self.kill_ring.take_killed() {
+                if self.config.clipboard() != ClipboardMode::Disabled {
+                    match self.config.clipboard_backend() {
+                        #[cfg(feature = "with-arboard")]
+                        ClipboardBackend::Native => self.clipboard.copy(text),
+                        _ => s.out.copy_to_clipboard(text)?,
+                    }
+                }
+            }
+            match status {
                 command::Status::Proceed => continue,
                 command::Status::Submit => break,
             }
@@ -798,6 +1147,9 @@ impl<H: Helper, I: History> Editor<H, I> {
 
         // Move to end, in case cursor was in the middle of the line, so that
         // next thing application prints goes after the input
+        s.flush_refresh()?;
+        s.flush_external_print()?;
+        s.hide_region()?;
         s.edit_move_buffer_end(CmdKind::ForcedRefresh)?;
 
         if cfg!(windows) {
@@ -822,6 +1174,20 @@ impl<H: Helper, I: History> Editor<H, I> {
         self.history.append(path.as_ref())
     }
 
+    /// Load the kill ring from the specified file (saved by
+    /// [`Editor::save_kill_ring`]), so that killed texts survive across
+    /// sessions.
+    #[cfg(feature = "with-file-history")]
+    pub fn load_kill_ring<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        kill_ring::load(&mut self.kill_ring, path.as_ref())
+    }
+
+    /// Save the kill ring in the specified file.
+    #[cfg(feature = "with-file-history")]
+    pub fn save_kill_ring<P: AsRef<Path> + ?Sized>(&self, path: &P) -> Result<()> {
+        kill_ring::save(&self.kill_ring, path.as_ref())
+    }
+
     /// Add a new entry in the history.
     pub fn add_history_entry<S: AsRef<str> + Into<String>>(&mut self, line: S) -> Result<bool> {
         self.history.add(line.as_ref())
@@ -842,9 +1208,42 @@ impl<H: Helper, I: History> Editor<H, I> {
         &self.history
     }
 
+    /// Store `text` in the register `name`, to be inserted with
+    /// `C-x r i <name>` (e.g. the path of the last command's output).
+    pub fn set_register(&mut self, name: char, text: impl Into<String>) {
+        self.registers.set_text(name, text.into());
+    }
+
+    /// Text stored in the register `name` (`C-x r s <name>`).
+    #[must_use]
+    pub fn register(&self, name: char) -> Option<&str> {
+        self.registers.text(name)
+    }
+
+    /// Set the terminal window title (e.g. to the command being run).
+    ///
+    /// Escape sequences and control characters are removed from `title`.
+    /// See [`Config::window_title`] to display the prompt while editing.
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        let mut printable = String::with_capacity(title.len());
+        tty::printable(title, &mut printable);
+        let title = printable.trim().replace(['\n', '\t'], " ");
+        self.term.set_title(&title)
+    }
+
+    /// Terminal background, once reported by the terminal (queried at the
+    /// first [`Editor::readline`] when colors are enabled).
+    #[must_use]
+    pub fn background(&self) -> Option<Background> {
+        self.background
+    }
+
     /// Register a callback function to be called for tab-completion
     /// or to show hints to the user at the right of the prompt.
     pub fn set_helper(&mut self, helper: Option<H>) {
+        if let (Some(background), Some(helper)) = (self.background, helper.as_ref()) {
+            helper.set_background(background);
+        }
         self.helper = helper;
     }
 
@@ -867,7 +1266,47 @@ impl<H: Helper, I: History> Editor<H, I> {
         handler: R,
     ) -> Option<EventHandler> {
         self.custom_bindings
-            .insert(Event::normalize(key_seq.into()), handler.into())
+            .insert(None, Event::normalize(key_seq.into()), handler.into())
+    }
+
+    /// Bind a sequence to a command only in the specified input `mode`.
+    ///
+    /// Mode bindings take precedence over the ones registered with
+    /// [`Editor::bind_sequence`].
+    #[cfg(feature = "custom-bindings")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
+    pub fn bind_sequence_in<E: Into<Event>, R: Into<EventHandler>>(
+        &mut self,
+        mode: BindingMode,
+        key_seq: E,
+        handler: R,
+    ) -> Option<EventHandler> {
+        self.custom_bindings
+            .insert(Some(mode), Event::normalize(key_seq.into()), handler.into())
+    }
+
+    /// Bind the key sequences of the `[bindings]` tables of a configuration
+    /// file (see [`Config::from_path`]) to commands named like their readline
+    /// equivalent.
+    ///
+    /// ```ini
+    /// [bindings]
+    /// "C-x C-u" = "undo"
+    /// "M-Enter" = "newline"
+    ///
+    /// # only in vi insert mode (or `emacs`, `vi-command`)
+    /// [bindings.vi-insert]
+    /// "C-p" = "previous-history"
+    /// ```
+    #[cfg(feature = "custom-bindings")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
+    pub fn load_bindings<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        let path = path.as_ref();
+        for (mode, keys, cmd) in config::ConfigFile::load(path)?.bindings(path)? {
+            self.custom_bindings
+                .insert(mode, keys.normalize(), cmd.into());
+        }
+        Ok(())
     }
 
     /// Remove a binding for the given sequence.
@@ -875,7 +1314,19 @@ impl<H: Helper, I: History> Editor<H, I> {
     #[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
     pub fn unbind_sequence<E: Into<Event>>(&mut self, key_seq: E) -> Option<EventHandler> {
         self.custom_bindings
-            .remove(&Event::normalize(key_seq.into()))
+            .remove(None, &Event::normalize(key_seq.into()))
+    }
+
+    /// Remove a binding for the given sequence in the specified input `mode`.
+    #[cfg(feature = "custom-bindings")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "custom-bindings")))]
+    pub fn unbind_sequence_in<E: Into<Event>>(
+        &mut self,
+        mode: BindingMode,
+        key_seq: E,
+    ) -> Option<EventHandler> {
+        self.custom_bindings
+            .remove(Some(mode), &Event::normalize(key_seq.into()))
     }
 
     /// Returns an iterator over edited lines.
@@ -923,9 +1374,75 @@ impl<H: Helper, I: History> Editor<H, I> {
         }
     }
 
+    /// Share the configuration of this editor with other threads: the
+    /// changes made through the returned handle are applied to the line being
+    /// edited after the next key pressed (see [`ConfigHandle`]) or to the
+    /// next line.
+    ///
+    /// ```
+    /// use rustyline::config::Configurer;
+    /// use rustyline::EditMode;
+    /// let mut rl = rustyline::DefaultEditor::new()?;
+    /// let handle = rl.config_handle();
+    /// std::thread::spawn(move || handle.update(|b| b.set_edit_mode(EditMode::Vi)));
+    /// # Ok::<(), rustyline::error::ReadlineError>(())
+    /// ```
+    pub fn config_handle(&mut self) -> ConfigHandle {
+        self.config_handle
+            .get_or_insert_with(|| ConfigHandle::new(self.config.clone()))
+            .clone()
+    }
+
+    /// Apply a configuration changed through a [`ConfigHandle`]
+    fn apply_config(&mut self, config: Config) -> Result<()> {
+        use config::Configurer;
+        self.set_max_history_size(config.max_history_size())?;
+        self.set_history_ignore_dups(
+            config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive,
+        )?;
+        self.set_history_ignore_space(config.history_ignore_space());
+        self.set_color_mode(config.color_mode());
+        self.set_bell_style(config.bell_style());
+        self.set_kill_ring_size(config.kill_ring_size());
+        self.config = config;
+        Ok(())
+    }
+
     /// Create an external printer
+    ///
+    /// Messages are queued until the line is refreshed, according to
+    /// [`Config::external_print_capacity`] and
+    /// [`Config::external_print_policy`] (when the first printer is created).
     pub fn create_external_printer(&mut self) -> Result<<Terminal as Term>::ExternalPrinter> {
-        self.term.create_external_printer()
+        self.term.create_external_printer(&self.config)
+    }
+
+    /// Report how each key is resolved into a command (which binding or key
+    /// map, which conditional handlers declined it) to `tracer`, to
+    /// diagnose a binding which doesn't work.
+    ///
+    /// ```
+    /// let mut rl = rustyline::DefaultEditor::new()?;
+    /// rl.set_binding_tracer(Some(Box::new(|trace| eprintln!("{trace:?}"))));
+    /// # Ok::<(), rustyline::error::ReadlineError>(())
+    /// ```
+    pub fn set_binding_tracer(&mut self, tracer: Option<BindingTracer>) {
+        self.binding_tracer = tracer;
+    }
+
+    /// Rendering work done so far, `None` unless
+    /// [`Config::render_stats`] is enabled
+    #[must_use]
+    pub fn render_stats(&self) -> Option<RenderStats> {
+        self.render_counters
+            .as_ref()
+            .filter(|_| self.config.render_stats())
+            .map(|counters| counters.snapshot())
+    }
+
+    /// Reset the rendering counters
+    pub fn reset_render_stats(&mut self) {
+        self.render_counters = self.config.render_stats().then(Arc::default);
     }
 
     /// Change cursor visibility
@@ -961,6 +1478,21 @@ impl<H: Helper, I: History> config::Configurer for Editor<H, I> {
         self.config_mut().set_color_mode(color_mode);
         self.term.color_mode = color_mode;
     }
+
+    fn set_bell_style(&mut self, bell_style: config::BellStyle) {
+        self.config_mut().set_bell_style(bell_style);
+        self.term.bell_style = bell_style;
+    }
+
+    fn set_ambiguous_width(&mut self, ambiguous_width: Option<AmbiguousWidth>) {
+        self.config_mut().set_ambiguous_width(ambiguous_width);
+        self.term.ambiguous_width = ambiguous_width;
+    }
+
+    fn set_kill_ring_size(&mut self, size: usize) {
+        self.config_mut().set_kill_ring_size(size);
+        self.kill_ring.resize(size);
+    }
 }
 
 impl<H: Helper, I: History> fmt::Debug for Editor<H, I> {
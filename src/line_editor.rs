@@ -0,0 +1,304 @@
+//! Object-safe facade of [`Editor`], so that frameworks can hold a
+//! `Box<dyn LineEditor>` without the helper and history type parameters.
+use std::any::Any;
+use std::borrow::Cow;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::completion::{Completer, Pair};
+use crate::config::{CompletionType, Configurer};
+use crate::highlight::{Background, CmdKind, Highlighter, PromptMode};
+use crate::hint::Hinter;
+use crate::history::History;
+use crate::layout::Unit;
+use crate::line_buffer::LineBuffer;
+use crate::validate::{ValidationContext, ValidationResult, Validator};
+use crate::{Changeset, Context, Editor, Helper, Result};
+
+/// Line editor, whatever its helper and history types.
+///
+/// The helper can be set by parts only when the editor helper type is
+/// [`DynHelper`] (e.g. `Editor<DynHelper, DefaultHistory>`).
+pub trait LineEditor: Configurer {
+    /// See [`Editor::readline`]
+    fn readline(&mut self, prompt: &str) -> Result<String>;
+    /// See [`Editor::readline_with_initial`]
+    fn readline_with_initial(&mut self, prompt: &str, initial: (&str, &str)) -> Result<String>;
+    /// See [`Editor::add_history_entry`]
+    fn add_history_entry(&mut self, line: &str) -> Result<bool>;
+    /// See [`Editor::clear_history`]
+    fn clear_history(&mut self) -> Result<()>;
+    /// See [`Editor::load_history`]
+    fn load_history(&mut self, path: &Path) -> Result<()>;
+    /// See [`Editor::save_history`]
+    fn save_history(&mut self, path: &Path) -> Result<()>;
+    /// See [`Editor::history`]
+    fn history(&self) -> &dyn History;
+    /// See [`Editor::history_mut`]
+    fn history_mut(&mut self) -> &mut dyn History;
+    /// Set (or remove) the completer of the [`DynHelper`]
+    fn set_completer(
+        &mut self,
+        completer: Option<Box<dyn Completer<Candidate = Pair> + Send>>,
+    ) -> Result<()>;
+    /// Set (or remove) the hinter of the [`DynHelper`]
+    fn set_hinter(&mut self, hinter: Option<Box<dyn Hinter<Hint = String> + Send>>) -> Result<()>;
+    /// Set (or remove) the highlighter of the [`DynHelper`]
+    fn set_highlighter(&mut self, highlighter: Option<Box<dyn Highlighter + Send>>) -> Result<()>;
+    /// Set (or remove) the validator of the [`DynHelper`]
+    fn set_validator(&mut self, validator: Option<Box<dyn Validator + Send>>) -> Result<()>;
+}
+
+impl<H: Helper + 'static, I: History> LineEditor for Editor<H, I> {
+    fn readline(&mut self, prompt: &str) -> Result<String> {
+        Self::readline(self, prompt)
+    }
+
+    fn readline_with_initial(&mut self, prompt: &str, initial: (&str, &str)) -> Result<String> {
+        Self::readline_with_initial(self, prompt, initial)
+    }
+
+    fn add_history_entry(&mut self, line: &str) -> Result<bool> {
+        Self::add_history_entry(self, line)
+    }
+
+    fn clear_history(&mut self) -> Result<()> {
+        Self::clear_history(self)
+    }
+
+    fn load_history(&mut self, path: &Path) -> Result<()> {
+        Self::load_history(self, path)
+    }
+
+    fn save_history(&mut self, path: &Path) -> Result<()> {
+        Self::save_history(self, path)
+    }
+
+    fn history(&self) -> &dyn History {
+        &self.history
+    }
+
+    fn history_mut(&mut self) -> &mut dyn History {
+        &mut self.history
+    }
+
+    fn set_completer(
+        &mut self,
+        completer: Option<Box<dyn Completer<Candidate = Pair> + Send>>,
+    ) -> Result<()> {
+        self.dyn_helper()?.completer = completer;
+        Ok(())
+    }
+
+    fn set_hinter(&mut self, hinter: Option<Box<dyn Hinter<Hint = String> + Send>>) -> Result<()> {
+        self.dyn_helper()?.hinter = hinter;
+        Ok(())
+    }
+
+    fn set_highlighter(&mut self, highlighter: Option<Box<dyn Highlighter + Send>>) -> Result<()> {
+        if let (Some(background), Some(highlighter)) = (self.background, highlighter.as_ref()) {
+            highlighter.set_background(background);
+        }
+        self.dyn_helper()?.highlighter = highlighter;
+        Ok(())
+    }
+
+    fn set_validator(&mut self, validator: Option<Box<dyn Validator + Send>>) -> Result<()> {
+        self.dyn_helper()?.validator = validator;
+        Ok(())
+    }
+}
+
+impl<H: Helper + 'static, I: History> Editor<H, I> {
+    /// The helper made of parts (created if needed), or an `Unsupported`
+    /// error when `H` is not [`DynHelper`]
+    fn dyn_helper(&mut self) -> Result<&mut DynHelper> {
+        let helper: &mut dyn Any = &mut self.helper;
+        match helper.downcast_mut::<Option<DynHelper>>() {
+            Some(helper) => Ok(helper.get_or_insert_with(DynHelper::default)),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "helper cannot be set by parts (not a DynHelper)",
+            )
+            .into()),
+        }
+    }
+}
+
+/// Helper made of optional boxed parts (see [`LineEditor`]), each one
+/// replaced by the default behaviour when absent.
+#[derive(Default)]
+pub struct DynHelper {
+    completer: Option<Box<dyn Completer<Candidate = Pair> + Send>>,
+    hinter: Option<Box<dyn Hinter<Hint = String> + Send>>,
+    highlighter: Option<Box<dyn Highlighter + Send>>,
+    validator: Option<Box<dyn Validator + Send>>,
+}
+
+impl Completer for DynHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
+        match self.completer {
+            Some(ref completer) => completer.complete(line, pos, ctx),
+            None => Ok((0, Vec::with_capacity(0))),
+        }
+    }
+
+    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str, cl: &mut Changeset) {
+        match self.completer {
+            Some(ref completer) => completer.update(line, start, elected, cl),
+            None => {
+                let end = line.pos();
+                line.replace(start..end, elected, cl);
+            }
+        }
+    }
+}
+
+impl Hinter for DynHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        self.hinter.as_ref()?.hint(line, pos, ctx)
+    }
+}
+
+impl DynHelper {
+    fn highlighter(&self) -> &dyn Highlighter {
+        self.highlighter.as_deref().unwrap_or(&())
+    }
+
+    fn validator(&self) -> &dyn Validator {
+        self.validator.as_deref().unwrap_or(&())
+    }
+}
+
+impl Highlighter for DynHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        self.highlighter().highlight(line, pos)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        self.highlighter().highlight_prompt(prompt, default)
+    }
+
+    fn prompt_with_mode<'p>(&self, prompt: &'p str, mode: PromptMode) -> Cow<'p, str> {
+        self.highlighter().prompt_with_mode(prompt, mode)
+    }
+
+    fn continuation_prompt(&self, line_number: usize) -> Cow<'_, str> {
+        self.highlighter().continuation_prompt(line_number)
+    }
+
+    fn shrink_prompt<'p>(&self, prompt: &'p str, width: Unit) -> Cow<'p, str> {
+        self.highlighter().shrink_prompt(prompt, width)
+    }
+
+    fn highlight_search_match<'l>(&self, line: &'l str, range: Range<usize>) -> Cow<'l, str> {
+        self.highlighter().highlight_search_match(line, range)
+    }
+
+    fn highlight_preedit<'l>(&self, line: &'l str, range: Range<usize>) -> Cow<'l, str> {
+        self.highlighter().highlight_preedit(line, range)
+    }
+
+    fn highlight_selection<'l>(&self, line: &'l str, ranges: &[Range<usize>]) -> Cow<'l, str> {
+        self.highlighter().highlight_selection(line, ranges)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.highlighter().highlight_hint(hint)
+    }
+
+    fn highlight_validation_message<'m>(&self, msg: &'m str, invalid: bool) -> Cow<'m, str> {
+        self.highlighter()
+            .highlight_validation_message(msg, invalid)
+    }
+
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        completion: CompletionType,
+    ) -> Cow<'c, str> {
+        self.highlighter()
+            .highlight_candidate(candidate, completion)
+    }
+
+    fn highlight_char(&self, line: &str, pos: usize, kind: CmdKind) -> bool {
+        self.highlighter().highlight_char(line, pos, kind)
+    }
+
+    fn set_background(&self, background: Background) {
+        self.highlighter().set_background(background);
+    }
+}
+
+impl Validator for DynHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        self.validator().validate(ctx)
+    }
+
+    fn validate_while_typing(&self) -> bool {
+        self.validator().validate_while_typing()
+    }
+
+    fn poll_validation(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        self.validator().poll_validation(ctx)
+    }
+
+    fn cancel_validation(&self) {
+        self.validator().cancel_validation();
+    }
+
+    fn indent(&self, line: &str, pos: usize) -> Option<String> {
+        self.validator().indent(line, pos)
+    }
+}
+
+impl Helper for DynHelper {}
+
+#[cfg(test)]
+mod test {
+    use super::{DynHelper, LineEditor};
+    use crate::completion::{Completer, Pair};
+    use crate::history::DefaultHistory;
+    use crate::{Context, DefaultEditor, Editor, Result};
+
+    struct Words;
+
+    impl Completer for Words {
+        type Candidate = Pair;
+
+        fn complete(&self, _: &str, _: usize, _: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
+            Ok((
+                0,
+                vec![Pair {
+                    display: "help".to_owned(),
+                    replacement: "help".to_owned(),
+                }],
+            ))
+        }
+    }
+
+    #[test]
+    fn set_by_parts() {
+        let mut editor = Editor::<DynHelper, DefaultHistory>::new().unwrap();
+        LineEditor::set_completer(&mut editor, Some(Box::new(Words))).unwrap();
+        let ctx = Context::new(editor.history());
+        let (_, candidates) = editor.helper().unwrap().complete("h", 1, &ctx).unwrap();
+        assert_eq!("help", candidates[0].replacement);
+
+        let mut editor: Box<dyn LineEditor> = Box::new(editor);
+        assert!(editor.add_history_entry("line").unwrap());
+        assert_eq!(1, editor.history().len());
+
+        let mut editor: Box<dyn LineEditor> = Box::new(DefaultEditor::new().unwrap());
+        assert!(editor.set_completer(Some(Box::new(Words))).is_err());
+    }
+}
@@ -0,0 +1,455 @@
+//! Logging through an [`ExternalPrinter`]
+//!
+//! Records are printed above the prompt instead of being mixed with the
+//! edited line, optionally styled according to their [`Severity`].
+//! [`ExternalWriter`] does the same for any code writing to an
+//! [`io::Write`].
+use std::borrow::Cow;
+use std::io;
+use std::mem;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::error::ReadlineError;
+use crate::{ExternalPrinter, Result};
+
+/// Severity of a message printed with
+/// [`ExternalPrinter::print_styled`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Severity {
+    /// Error
+    Error,
+    /// Warning
+    Warning,
+    /// Information
+    Info,
+    /// Debug
+    Debug,
+    /// Trace
+    Trace,
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => Self::Error,
+            Level::Warn => Self::Warning,
+            Level::Info => Self::Info,
+            Level::Debug => Self::Debug,
+            Level::Trace => Self::Trace,
+        }
+    }
+}
+
+/// Styles (ANSI SGR sequences) applied to messages depending on their
+/// [`Severity`]
+#[derive(Clone, Debug)]
+pub struct MessageStyle {
+    styles: [Cow<'static, str>; 5],
+    timestamps: bool,
+}
+
+impl MessageStyle {
+    /// No style, no timestamp
+    #[must_use]
+    pub fn plain() -> Self {
+        Self {
+            styles: Default::default(),
+            timestamps: false,
+        }
+    }
+
+    /// Style (like `"\x1b[1;31m"`) of messages with `severity`
+    #[must_use]
+    pub fn style(mut self, severity: Severity, sgr: impl Into<Cow<'static, str>>) -> Self {
+        self.styles[severity as usize] = sgr.into();
+        self
+    }
+
+    /// Prefix messages with the (UTC) time at which they are formatted
+    ///
+    /// By default, no timestamp.
+    #[must_use]
+    pub fn timestamps(mut self, enabled: bool) -> Self {
+        self.timestamps = enabled;
+        self
+    }
+
+    /// Styled version of `msg`
+    #[must_use]
+    pub fn format(&self, severity: Severity, msg: &str) -> String {
+        let style = &self.styles[severity as usize];
+        let mut styled = String::with_capacity(msg.len() + style.len() + 15);
+        if self.timestamps {
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let (h, m, s) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+            styled.push_str(&format!("{h:02}:{m:02}:{s:02} "));
+        }
+        if style.is_empty() {
+            styled.push_str(msg);
+        } else {
+            styled.push_str(style);
+            styled.push_str(msg);
+            styled.push_str("\x1b[0m");
+        }
+        styled
+    }
+}
+
+impl Default for MessageStyle {
+    /// Errors in bold red, warnings in yellow, debug and trace messages dimmed
+    fn default() -> Self {
+        Self::plain()
+            .style(Severity::Error, "\x1b[1;31m")
+            .style(Severity::Warning, "\x1b[33m")
+            .style(Severity::Debug, "\x1b[2m")
+            .style(Severity::Trace, "\x1b[2m")
+    }
+}
+
+/// [`ExternalPrinter`] applying its own [`MessageStyle`] to
+/// [`ExternalPrinter::print_styled`] messages
+pub struct StyledPrinter<P> {
+    printer: P,
+    style: MessageStyle,
+}
+
+impl<P: ExternalPrinter> StyledPrinter<P> {
+    /// Print with `printer`, styled by `style`
+    #[must_use]
+    pub fn new(printer: P, style: MessageStyle) -> Self {
+        Self { printer, style }
+    }
+}
+
+impl<P: ExternalPrinter> ExternalPrinter for StyledPrinter<P> {
+    fn print(&mut self, msg: String) -> Result<()> {
+        self.printer.print(msg)
+    }
+
+    fn eprint(&mut self, msg: String) -> Result<()> {
+        self.printer.eprint(msg)
+    }
+
+    fn print_styled(&mut self, severity: Severity, msg: &str) -> Result<()> {
+        self.printer.print(self.style.format(severity, msg))
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.printer.dropped_messages()
+    }
+
+    fn set_region(&mut self, lines: Vec<String>) -> Result<()> {
+        self.printer.set_region(lines)
+    }
+}
+
+/// Line-buffered [`io::Write`] adapter printing complete lines with an
+/// [`ExternalPrinter`]
+///
+/// A trailing incomplete line is printed when the writer is flushed or
+/// dropped.
+pub struct ExternalWriter<P: ExternalPrinter> {
+    printer: P,
+    buf: Vec<u8>,
+}
+
+impl<P: ExternalPrinter> ExternalWriter<P> {
+    /// Writer printing with `printer`
+    #[must_use]
+    pub fn new(printer: P) -> Self {
+        Self {
+            printer,
+            buf: Vec::new(),
+        }
+    }
+
+    fn print(&mut self, bytes: Vec<u8>) -> io::Result<()> {
+        let msg = match String::from_utf8(bytes) {
+            Ok(msg) => msg,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        };
+        self.printer.print(msg).map_err(|err| match err {
+            ReadlineError::Io(err) => err,
+            err => io::Error::other(err),
+        })
+    }
+}
+
+impl<P: ExternalPrinter> io::Write for ExternalWriter<P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if let Some(end) = memchr::memrchr(b'\n', &self.buf) {
+            let rest = self.buf.split_off(end + 1);
+            let lines = mem::replace(&mut self.buf, rest);
+            self.print(lines)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let line = mem::take(&mut self.buf);
+        self.print(line)
+    }
+}
+
+impl<P: ExternalPrinter> Drop for ExternalWriter<P> {
+    fn drop(&mut self) {
+        let _ = io::Write::flush(self);
+    }
+}
+
+/// [`log`] logger printing records with an [`ExternalPrinter`]
+///
+/// Records from rustyline itself are discarded: printing them would produce
+/// new records.
+pub struct PrinterLogger<P> {
+    printer: Mutex<P>,
+    level: LevelFilter,
+}
+
+impl<P: ExternalPrinter + Send> PrinterLogger<P> {
+    /// Logger printing records up to `level`
+    #[must_use]
+    pub fn new(printer: P, level: LevelFilter) -> Self {
+        Self {
+            printer: Mutex::new(printer),
+            level,
+        }
+    }
+
+    /// Install this logger as the global logger
+    pub fn init(self) -> std::result::Result<(), SetLoggerError>
+    where
+        P: 'static,
+    {
+        let level = self.level;
+        log::set_logger(Box::leak(Box::new(self)))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<P: ExternalPrinter + Send> Log for PrinterLogger<P> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !metadata.target().starts_with("rustyline")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let msg = format!("[{} {}] {}", record.level(), record.target(), record.args());
+        if let Ok(mut printer) = self.printer.lock() {
+            let _ = printer.print(msg);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// [`MakeWriter`](tracing_subscriber::fmt::MakeWriter) printing events with
+/// an [`ExternalPrinter`]
+///
+/// ```no_run
+/// use rustyline::logging::PrinterWriter;
+/// use rustyline::DefaultEditor;
+///
+/// let mut rl = DefaultEditor::new()?;
+/// let printer = rl.create_external_printer()?;
+/// tracing_subscriber::fmt()
+///     .with_writer(PrinterWriter::new(printer))
+///     .init();
+/// # Ok::<(), rustyline::error::ReadlineError>(())
+/// ```
+#[cfg(feature = "with-tracing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "with-tracing")))]
+pub struct PrinterWriter<P> {
+    printer: Mutex<P>,
+}
+
+#[cfg(feature = "with-tracing")]
+impl<P: ExternalPrinter + Send> PrinterWriter<P> {
+    /// Writer printing with `printer`
+    #[must_use]
+    pub fn new(printer: P) -> Self {
+        Self {
+            printer: Mutex::new(printer),
+        }
+    }
+}
+
+#[cfg(feature = "with-tracing")]
+impl<'a, P: ExternalPrinter + Send + 'a> tracing_subscriber::fmt::MakeWriter<'a>
+    for PrinterWriter<P>
+{
+    type Writer = EventWriter<'a, P>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EventWriter {
+            printer: &self.printer,
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// Buffer one formatted event, printed when dropped
+#[cfg(feature = "with-tracing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "with-tracing")))]
+pub struct EventWriter<'a, P: ExternalPrinter> {
+    printer: &'a Mutex<P>,
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "with-tracing")]
+impl<P: ExternalPrinter> io::Write for EventWriter<'_, P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "with-tracing")]
+impl<P: ExternalPrinter> Drop for EventWriter<'_, P> {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let msg = String::from_utf8_lossy(&self.buf).into_owned();
+        if let Ok(mut printer) = self.printer.lock() {
+            let _ = printer.print(msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use log::{Level, LevelFilter, Log, Record};
+
+    use super::{MessageStyle, PrinterLogger, Severity, StyledPrinter};
+    use crate::{ExternalPrinter, Result};
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl ExternalPrinter for Recorder {
+        fn print(&mut self, msg: String) -> Result<()> {
+            self.0.lock().unwrap().push(msg);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn message_style() {
+        let style = MessageStyle::default();
+        assert_eq!(
+            "\x1b[1;31mfailed\x1b[0m",
+            style.format(Severity::Error, "failed")
+        );
+        assert_eq!("done", style.format(Severity::Info, "done"));
+        assert_eq!(
+            "done",
+            MessageStyle::plain().format(Severity::Error, "done")
+        );
+
+        let styled = MessageStyle::plain()
+            .timestamps(true)
+            .format(Severity::Info, "done");
+        assert_eq!(13, styled.len());
+        assert_eq!(b':', styled.as_bytes()[2]);
+        assert!(styled.ends_with(" done"));
+    }
+
+    #[test]
+    fn styled_printer() {
+        let recorder = Recorder::default();
+        let mut printer = StyledPrinter::new(
+            recorder.clone(),
+            MessageStyle::plain().style(Severity::Warning, "\x1b[35m"),
+        );
+        printer.print_styled(Severity::Warning, "careful").unwrap();
+        printer.print_styled(Severity::Error, "failed").unwrap();
+        let mut recorder = recorder;
+        recorder.print_styled(Severity::Warning, "careful").unwrap();
+        assert_eq!(
+            vec!["\x1b[35mcareful\x1b[0m", "failed", "\x1b[33mcareful\x1b[0m"],
+            *recorder.0.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn external_writer() {
+        use super::ExternalWriter;
+        use std::io::Write;
+
+        let recorder = Recorder::default();
+        let mut writer = ExternalWriter::new(recorder.clone());
+        write!(writer, "first ").unwrap();
+        assert!(recorder.0.lock().unwrap().is_empty());
+        write!(writer, "line\nsecond line\nthird").unwrap();
+        writer.flush().unwrap();
+        write!(writer, "last").unwrap();
+        drop(writer);
+        assert_eq!(
+            vec!["first line\nsecond line\n", "third", "last"],
+            *recorder.0.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn logger() {
+        let recorder = Recorder::default();
+        let logger = PrinterLogger::new(recorder.clone(), LevelFilter::Info);
+        for (level, target) in [
+            (Level::Warn, "app"),
+            (Level::Debug, "app"),
+            (Level::Warn, "rustyline"),
+        ] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("msg"))
+                    .build(),
+            );
+        }
+        assert_eq!(vec!["[WARN app] msg"], *recorder.0.lock().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "with-tracing")]
+    fn event_writer() {
+        use super::EventWriter;
+        use std::io::Write;
+
+        let recorder = Recorder::default();
+        let printer = Mutex::new(recorder.clone());
+        {
+            let mut writer = EventWriter {
+                printer: &printer,
+                buf: Vec::new(),
+            };
+            write!(writer, "INFO ").unwrap();
+            writeln!(writer, "event").unwrap();
+        }
+        drop(EventWriter {
+            printer: &printer,
+            buf: Vec::new(),
+        });
+        assert_eq!(vec!["INFO event\n"], *recorder.0.lock().unwrap());
+    }
+}
@@ -0,0 +1,63 @@
+//! Emacs-style registers
+use std::collections::HashMap;
+
+/// Content of a register
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Register {
+    Text(String),
+    Position(usize),
+}
+
+/// Registers, named by a single character, storing text or a cursor
+/// position.
+#[derive(Debug, Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    /// Store `text` in register `name`.
+    pub fn set_text(&mut self, name: char, text: String) {
+        self.registers.insert(name, Register::Text(text));
+    }
+
+    /// Store the cursor position `pos` in register `name`.
+    pub fn set_position(&mut self, name: char, pos: usize) {
+        self.registers.insert(name, Register::Position(pos));
+    }
+
+    /// Text stored in register `name`.
+    /// Return `None` when the register is empty or holds a position.
+    pub fn text(&self, name: char) -> Option<&str> {
+        match self.registers.get(&name) {
+            Some(Register::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Cursor position stored in register `name`.
+    /// Return `None` when the register is empty or holds text.
+    pub fn position(&self, name: char) -> Option<usize> {
+        match self.registers.get(&name) {
+            Some(Register::Position(pos)) => Some(*pos),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registers;
+
+    #[test]
+    fn registers() {
+        let mut registers = Registers::default();
+        assert_eq!(None, registers.text('a'));
+        registers.set_text('a', "text".to_owned());
+        assert_eq!(Some("text"), registers.text('a'));
+        assert_eq!(None, registers.position('a'));
+        registers.set_position('a', 2);
+        assert_eq!(None, registers.text('a'));
+        assert_eq!(Some(2), registers.position('a'));
+    }
+}
@@ -0,0 +1,277 @@
+//! `Serialize` and `Deserialize` implementations (`serde` feature): keys as
+//! readline style specifications (`C-x`, `M-Enter`), commands by name
+//! (`beginning-of-line`) and the configuration as the settings of the
+//! configuration file (`edit_mode = "vi"`).
+use std::fmt;
+
+use serde_core::de::{self, Deserialize, Deserializer, MapAccess, Unexpected, Visitor};
+use serde_core::ser::{self, Serialize, SerializeMap, Serializer};
+
+use crate::config::{self, Config, EditMode, Value};
+use crate::Cmd;
+
+/// Visitor of the strings parsed by `.1`, expecting `.0`
+struct Parse<T>(&'static str, fn(&str) -> Option<T>);
+
+impl<T> Visitor<'_> for Parse<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        (self.1)(v).ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+/// Only the named commands (like the GNU Readline ones) can be serialized.
+impl Serialize for Cmd {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.name() {
+            Some(name) => serializer.serialize_str(name),
+            None => Err(ser::Error::custom(format_args!(
+                "no name for the command {self:?}"
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Cmd {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(Parse("a command name", Self::from_name))
+    }
+}
+
+impl Serialize for EditMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Self::Emacs => "emacs",
+            Self::Vi => "vi",
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for EditMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(Parse("`emacs` or `vi`", |s| match s {
+            "emacs" => Some(Self::Emacs),
+            "vi" => Some(Self::Vi),
+            _ => None,
+        }))
+    }
+}
+
+/// The settings of the configuration file, as a map
+impl Serialize for Config {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let settings = config::settings(self).map_err(ser::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(settings.len()))?;
+        for (key, value) in &settings {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// The settings of the configuration file, the missing ones keeping their
+/// default value
+impl<'de> Deserialize<'de> for Config {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Settings;
+
+        impl<'de> Visitor<'de> for Settings {
+            type Value = Config;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map of settings")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Config, A::Error> {
+                let mut builder = Config::builder();
+                while let Some((key, value)) = map.next_entry::<String, Value>()? {
+                    config::set_value(&mut builder, &key, value).map_err(de::Error::custom)?;
+                }
+                Ok(builder.build())
+            }
+        }
+
+        deserializer.deserialize_map(Settings)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Self::String(ref s) => serializer.serialize_str(s),
+            Self::Bool(b) => serializer.serialize_bool(b),
+            Self::Integer(n) => serializer.serialize_i64(n),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Any;
+
+        impl Visitor<'_> for Any {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string, a boolean or an integer")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+                Ok(Value::String(v.to_owned()))
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+                Ok(Value::Bool(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+                Ok(Value::Integer(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+                i64::try_from(v)
+                    .map(Value::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+            }
+        }
+
+        deserializer.deserialize_any(Any)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_core::de::value::{Error, MapDeserializer, StrDeserializer};
+    use serde_core::de::{Deserialize, IntoDeserializer};
+
+    use crate::config::{
+        self, Behavior, BellStyle, ClipboardMode, ColorMode, CompletionType, Config, CursorShape,
+        EditMode, Passthrough, PromptOverflow, QueuePolicy, TabInsertion, UndoGrouping,
+    };
+    use crate::keymap::CMD_NAMES;
+    use crate::layout::{AmbiguousWidth, GraphemeClusterMode};
+    use crate::InputMode;
+    use crate::{Cmd, KeyCode as K, KeyEvent as E, Modifiers as M};
+    use rustyline_core::normalize::NormalizationForm;
+
+    fn from_str<'de, T: Deserialize<'de>>(s: &'de str) -> Result<T, Error> {
+        let deserializer: StrDeserializer<'_, Error> = s.into_deserializer();
+        T::deserialize(deserializer)
+    }
+
+    #[test]
+    fn key_event() {
+        for key in [
+            E::ctrl('X'),
+            E::alt('-'),
+            E(K::Enter, M::ALT),
+            E(K::F(12), M::CTRL_SHIFT),
+            E::from(' '),
+            E::from('é'),
+        ] {
+            assert_eq!(key, from_str(&key.spec().unwrap()).unwrap());
+        }
+        assert_eq!("C-M-x", E(K::Char('x'), M::CTRL_ALT).spec().unwrap());
+        assert!(from_str::<E>("Hyper-x").is_err());
+        assert_eq!(None, E(K::BracketedPasteStart, M::NONE).spec());
+    }
+
+    #[test]
+    fn cmd() {
+        for (name, cmd) in CMD_NAMES {
+            assert_eq!(*cmd, from_str::<Cmd>(name).unwrap());
+            assert_eq!(*cmd, Cmd::from_name(cmd.name().unwrap()).unwrap());
+        }
+        assert_eq!(
+            Some("beginning-of-line"),
+            from_str::<Cmd>("beginning-of-line").unwrap().name()
+        );
+        assert!(from_str::<Cmd>("self-destruct").is_err());
+        assert_eq!(EditMode::Vi, from_str("vi").unwrap());
+    }
+
+    #[test]
+    fn config() {
+        let map = MapDeserializer::<_, Error>::new(
+            [("edit_mode", "vi"), ("bell_style", "none")].into_iter(),
+        );
+        let config = Config::deserialize(map).unwrap();
+        assert_eq!(EditMode::Vi, config.edit_mode());
+        assert_eq!(BellStyle::None, config.bell_style());
+        let map = MapDeserializer::<_, Error>::new([("edit_mode", "ed")].into_iter());
+        assert!(Config::deserialize(map).is_err());
+
+        // settings applied back: the default ones, and all changed
+        let config = Config::builder()
+            .max_history_size(10)
+            .unwrap()
+            .history_ignore_dups(false)
+            .unwrap()
+            .history_ignore_space(true)
+            .completion_type(CompletionType::List)
+            .completion_prompt_limit(50)
+            .completion_show_all_if_ambiguous(true)
+            .completion_query("{} completions?")
+            .completion_page_rows(Some(5))
+            .completion_more_prompt("(more)")
+            .completion_max_columns(Some(3))
+            .completion_column_padding(4)
+            .completion_horizontal(true)
+            .keyseq_timeout(Some(100))
+            .mapping_timeout(Some(300))
+            .edit_mode(EditMode::Vi)
+            .auto_add_history(true)
+            .bell_style(BellStyle::Visible)
+            .color_mode(ColorMode::Forced)
+            .grapheme_cluster_mode(GraphemeClusterMode::NoZwj)
+            .ambiguous_width(Some(AmbiguousWidth::Wide))
+            .behavior(Behavior::PreferTerm)
+            .tab_stop(4)
+            .check_cursor_position(true)
+            .indent_size(4)
+            .auto_indent(true)
+            .horizontal_scroll(true)
+            .bidi(true)
+            .normalization(Some(NormalizationForm::Nfd))
+            .line_numbers(true)
+            .paste_fold_lines(Some(20))
+            .visual_line_movement(true)
+            .clipboard(ClipboardMode::CopyPaste)
+            .kill_ring_size(5)
+            .alternate_screen(true)
+            .serial_console(Some(9600))
+            .prompt_overflow(PromptOverflow::Shrink)
+            .window_title(true)
+            .probe_terminal(false)
+            .coalesce_refresh(false)
+            .render_stats(true)
+            .headless(Some((100, 30)))
+            .env_overrides(false)
+            .word_chars("_-")
+            .tab_insertion(TabInsertion::Spaces(4))
+            .passthrough(Passthrough::Tmux)
+            .bracketed_paste(false)
+            .synchronized_output(false)
+            .focus_events(true)
+            .external_print_interval(Some(50))
+            .external_print_queue(16, QueuePolicy::DropOldest)
+            .undo_grouping(UndoGrouping::Time(500))
+            .enable_signals(true)
+            .cursor_shape(InputMode::Command, CursorShape::SteadyBlock)
+            .cursor_shape(InputMode::Insert, CursorShape::BlinkingBar)
+            .cursor_shape(InputMode::Replace, CursorShape::SteadyUnderline)
+            .build();
+        for config in [Config::default(), config] {
+            let mut builder = Config::builder();
+            for (key, value) in config::settings(&config).unwrap() {
+                config::set_value(&mut builder, key, value).unwrap();
+            }
+            assert_eq!(config, builder.build());
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Editor running on its own thread, driven through channels, so that GUI
+//! or async applications don't block their main thread.
+use std::any::Any;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::error::ReadlineError;
+use crate::{LineEditor, Result};
+
+/// Request sent to the editor thread
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ServiceRequest {
+    /// Read a line, pre-populated with `initial` (text at the left and at
+    /// the right of the cursor)
+    Readline {
+        /// Prompt
+        prompt: String,
+        /// See [`Editor::readline_with_initial`](crate::Editor::readline_with_initial)
+        initial: Option<(String, String)>,
+    },
+    /// Add a line to the history
+    AddHistoryEntry(String),
+}
+
+/// Outcome of a request, sent by the editor thread
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ServiceEvent {
+    /// Line accepted
+    Line(String),
+    /// Line cancelled (Ctrl-C)
+    Interrupted,
+    /// End of input (Ctrl-D)
+    Eof,
+    /// Other error (the service keeps running)
+    Error(ReadlineError),
+    /// The editor panicked with this message: the service is stopped
+    Panicked(String),
+}
+
+/// Editor running on a dedicated thread: requests are queued, and their
+/// outcomes are received with [`EditorService::events`] (a line per
+/// [`ServiceRequest::Readline`], only errors otherwise).
+///
+/// Dropping the service lets the thread end once the line being edited (if
+/// any) is accepted; [`EditorService::shutdown`] also waits for it.
+#[derive(Debug)]
+pub struct EditorService {
+    requests: Option<Sender<ServiceRequest>>,
+    events: Receiver<ServiceEvent>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EditorService {
+    /// Move `editor` (e.g. an [`Editor`](crate::Editor)) to a new thread
+    pub fn spawn<E: LineEditor + Send + 'static>(editor: E) -> Result<Self> {
+        let (requests, rx) = mpsc::channel();
+        let (tx, events) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("rustyline".to_owned())
+            .spawn(move || serve(editor, &rx, &tx))?;
+        Ok(Self {
+            requests: Some(requests),
+            events,
+            thread: Some(thread),
+        })
+    }
+
+    /// Queue `request`: fails when the service is stopped
+    pub fn send(&self, request: ServiceRequest) -> Result<()> {
+        self.requests
+            .as_ref()
+            .and_then(|requests| requests.send(request).ok())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::BrokenPipe, "editor service stopped").into()
+            })
+    }
+
+    /// Queue the reading of a line
+    pub fn readline(&self, prompt: &str) -> Result<()> {
+        self.send(ServiceRequest::Readline {
+            prompt: prompt.to_owned(),
+            initial: None,
+        })
+    }
+
+    /// Queue the addition of `line` to the history
+    pub fn add_history_entry(&self, line: &str) -> Result<()> {
+        self.send(ServiceRequest::AddHistoryEntry(line.to_owned()))
+    }
+
+    /// Outcomes of the requests, in order (disconnected once the service is
+    /// stopped)
+    #[must_use]
+    pub fn events(&self) -> &Receiver<ServiceEvent> {
+        &self.events
+    }
+
+    /// Stop the service, once the requests already queued are handled
+    pub fn shutdown(mut self) {
+        self.requests = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join(); // panics are caught
+        }
+    }
+}
+
+fn serve<E: LineEditor>(
+    mut editor: E,
+    requests: &Receiver<ServiceRequest>,
+    events: &Sender<ServiceEvent>,
+) {
+    for request in requests {
+        let event = match panic::catch_unwind(AssertUnwindSafe(|| handle(&mut editor, request))) {
+            Ok(None) => continue,
+            Ok(Some(event)) => event,
+            Err(payload) => {
+                // the editor state may be inconsistent
+                let _ = events.send(ServiceEvent::Panicked(panic_message(&*payload)));
+                return;
+            }
+        };
+        if events.send(event).is_err() {
+            return; // service dropped
+        }
+    }
+}
+
+fn handle(editor: &mut dyn LineEditor, request: ServiceRequest) -> Option<ServiceEvent> {
+    match request {
+        ServiceRequest::Readline { prompt, initial } => {
+            let result = match initial {
+                Some((left, right)) => editor.readline_with_initial(&prompt, (&left, &right)),
+                None => editor.readline(&prompt),
+            };
+            Some(match result {
+                Ok(line) => ServiceEvent::Line(line),
+                Err(ReadlineError::Interrupted) => ServiceEvent::Interrupted,
+                Err(ReadlineError::Eof) => ServiceEvent::Eof,
+                Err(err) => ServiceEvent::Error(err),
+            })
+        }
+        ServiceRequest::AddHistoryEntry(line) => editor
+            .add_history_entry(&line)
+            .err()
+            .map(ServiceEvent::Error),
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        (*msg).to_owned()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "editor panicked".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EditorService, ServiceEvent, ServiceRequest};
+    use crate::history::DefaultHistory;
+    use crate::test::validate_with;
+    use crate::{DefaultEditor, DynHelper, Editor, KeyEvent as E, LineEditor};
+
+    #[test]
+    fn lines() {
+        let mut editor = DefaultEditor::new().unwrap();
+        editor.term.keys.extend([E::from('a'), E::ENTER]);
+        let service = EditorService::spawn(editor).unwrap();
+        service.readline("> ").unwrap();
+        service.add_history_entry("a").unwrap();
+        service
+            .send(ServiceRequest::Readline {
+                prompt: "> ".to_owned(),
+                initial: Some(("b".to_owned(), String::new())),
+            })
+            .unwrap();
+        let events = service.events();
+        assert!(matches!(events.recv(), Ok(ServiceEvent::Line(line)) if line == "a"));
+        // same keys replayed
+        assert!(matches!(events.recv(), Ok(ServiceEvent::Line(line)) if line == "ba"));
+        service.shutdown();
+    }
+
+    #[test]
+    fn dyn_helper() {
+        let mut editor = Editor::<DynHelper, DefaultHistory>::new().unwrap();
+        LineEditor::set_validator(&mut editor, Some(Box::new(()))).unwrap();
+        editor.term.keys.extend([E::from('a'), E::ENTER]);
+        let service = EditorService::spawn(editor).unwrap();
+        service.readline("> ").unwrap();
+        assert!(matches!(service.events().recv(), Ok(ServiceEvent::Line(line)) if line == "a"));
+        service.shutdown();
+    }
+
+    #[test]
+    fn panic() {
+        let mut editor = Editor::<_, DefaultHistory>::new().unwrap();
+        editor.set_helper(Some(validate_with(|_| panic!("invalid validator"))));
+        editor.term.keys.extend([E::ENTER]);
+        let service = EditorService::spawn(editor).unwrap();
+        service.readline("> ").unwrap();
+        assert!(matches!(
+            service.events().recv(),
+            Ok(ServiceEvent::Panicked(msg)) if msg == "invalid validator"
+        ));
+        assert!(service.events().recv().is_err());
+    }
+}
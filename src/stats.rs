@@ -0,0 +1,89 @@
+//! Rendering performance counters
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Rendering work done since the counters have been enabled or reset (see
+/// [`Config::render_stats`](crate::config::Config::render_stats))
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct RenderStats {
+    /// Number of full redraws (prompt, line and hint)
+    pub refreshes: u64,
+    /// Number of layout computations
+    pub layouts: u64,
+    /// Number of bytes written by the redraws
+    pub bytes: u64,
+    /// Number of bytes written by the most expensive redraw
+    pub max_bytes: u64,
+    /// Time elapsed since the counters have been enabled or reset
+    pub elapsed: Duration,
+}
+
+impl RenderStats {
+    /// Average number of bytes written per redraw
+    #[must_use]
+    pub fn bytes_per_refresh(&self) -> f64 {
+        if self.refreshes == 0 {
+            0.
+        } else {
+            self.bytes as f64 / self.refreshes as f64
+        }
+    }
+
+    /// Average number of redraws per second
+    #[must_use]
+    pub fn refreshes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0. {
+            0.
+        } else {
+            self.refreshes as f64 / secs
+        }
+    }
+}
+
+/// Counters shared by the editor and the line being edited
+#[derive(Debug)]
+pub(crate) struct RenderCounters {
+    refreshes: AtomicU64,
+    layouts: AtomicU64,
+    bytes: AtomicU64,
+    max_bytes: AtomicU64,
+    started: Instant,
+}
+
+impl Default for RenderCounters {
+    fn default() -> Self {
+        Self {
+            refreshes: AtomicU64::new(0),
+            layouts: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            max_bytes: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl RenderCounters {
+    /// A layout has been computed
+    pub fn layout(&self) {
+        self.layouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A redraw has written `bytes`
+    pub fn refresh(&self, bytes: u64) {
+        self.refreshes.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.max_bytes.fetch_max(bytes, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> RenderStats {
+        RenderStats {
+            refreshes: self.refreshes.load(Ordering::Relaxed),
+            layouts: self.layouts.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            max_bytes: self.max_bytes.load(Ordering::Relaxed),
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
@@ -338,3 +338,15 @@ fn ctrl__() {
         }
     }
 }
+
+#[test]
+fn focus_events() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_cursor(
+            *mode,
+            ("H", "i"),
+            &[E(K::FocusOut, M::NONE), E(K::FocusIn, M::NONE), E::ENTER],
+            ("H", "i"),
+        );
+    }
+}
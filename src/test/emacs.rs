@@ -244,6 +244,78 @@ fn ctrl_x_ctrl_u() {
     );
 }
 
+#[test]
+fn ctrl_x_ctrl_n() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "a\nb\nc"),
+        &[
+            E::ctrl('X'),
+            E::ctrl('N'),
+            E::ctrl('X'),
+            E::ctrl('N'),
+            E::from('x'),
+            E::ENTER,
+        ],
+        ("x", "a\nxb\nxc"),
+    );
+}
+
+#[test]
+fn ctrl_x_ctrl_d() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("foo", " foo foo"),
+        &[E::ctrl('X'), E::ctrl('D'), E::BACKSPACE, E::ENTER],
+        ("fo", " fo foo"),
+    );
+    // any other command removes the extra cursors
+    assert_cursor(
+        EditMode::Emacs,
+        ("foo", " foo"),
+        &[
+            E::ctrl('X'),
+            E::ctrl('D'),
+            E::ctrl('A'),
+            E::from('x'),
+            E::ENTER,
+        ],
+        ("x", "foo foo"),
+    );
+}
+
+#[test]
+fn meta_braces() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "a\n\nb\n\nc"),
+        &[E::alt('2'), E::alt('}'), E::ENTER],
+        ("a\n\nb\n", "\nc"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("a\n\nb\n\nc", ""),
+        &[E::alt('{'), E::ENTER],
+        ("a\n\nb\n", "\nc"),
+    );
+}
+
+#[test]
+fn meta_g() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("a\nb\nc", ""),
+        &[E::alt('2'), E::alt('g'), E::from('g'), E::ENTER],
+        ("a\n", "b\nc"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("a\nb", ""),
+        &[E(K::Home, M::CTRL), E::ENTER],
+        ("", "a\nb"),
+    );
+}
+
 #[test]
 fn meta_b() {
     assert_cursor(
@@ -422,3 +494,160 @@ fn meta_digit() {
         ("hhh", ""),
     );
 }
+
+#[test]
+fn redo() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("", ""),
+        &[
+            E::from('a'),
+            E::from(' '),
+            E::from('b'),
+            E::ctrl('_'),
+            E(K::Char('_'), M::CTRL_ALT),
+            E::ENTER,
+        ],
+        ("a b", ""),
+    );
+    // a new edit discards the undone changes
+    assert_cursor(
+        EditMode::Emacs,
+        ("", ""),
+        &[
+            E::from('a'),
+            E::from(' '),
+            E::from('b'),
+            E::ctrl('_'),
+            E::from('c'),
+            E(K::Char('_'), M::CTRL_ALT),
+            E::ENTER,
+        ],
+        ("ac", ""),
+    );
+}
+
+#[test]
+fn registers() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hi", ""),
+        &[
+            E::ctrl('X'),
+            E::from('r'),
+            E::from('s'),
+            E::from('a'),
+            E::ctrl('U'),
+            E::ctrl('X'),
+            E::from('r'),
+            E::from('i'),
+            E::from('a'),
+            E::ENTER,
+        ],
+        ("Hi", ""),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hi", " there"),
+        &[
+            E::ctrl('X'),
+            E::from('r'),
+            E::from(' '),
+            E::from('b'),
+            E::ctrl('E'),
+            E::ctrl('X'),
+            E::from('r'),
+            E::from('j'),
+            E::from('b'),
+            E::ENTER,
+        ],
+        ("Hi", " there"),
+    );
+}
+
+#[test]
+fn surround() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "Hello big world"),
+        &[
+            E::alt('2'),
+            E::ctrl('X'),
+            E::from('s'),
+            E::from('w'),
+            E::from('"'),
+            E::ENTER,
+        ],
+        ("", "\"Hello big\" world"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hel", "lo"),
+        &[
+            E::ctrl('X'),
+            E::from('s'),
+            E::from('s'),
+            E::from(')'),
+            E::ENTER,
+        ],
+        ("", "(Hello)"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("(a (b", ") c)"),
+        &[
+            E::ctrl('X'),
+            E::from('s'),
+            E::from('d'),
+            E::from('('),
+            E::ENTER,
+        ],
+        ("(a ", "b c)"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("\"Hel", "lo\" world"),
+        &[
+            E::ctrl('X'),
+            E::from('s'),
+            E::from('c'),
+            E::from('"'),
+            E::from('['),
+            E::ENTER,
+        ],
+        ("", "[Hello] world"),
+    );
+    // undone as a single change
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "Hello"),
+        &[
+            E::ctrl('X'),
+            E::from('s'),
+            E::from('s'),
+            E::from('\''),
+            E::ctrl('_'),
+            E::ENTER,
+        ],
+        ("Hello", ""),
+    );
+}
+
+#[test]
+fn undo_branch() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("", ""),
+        &[
+            E::from('a'),
+            E::ctrl('_'),
+            E::from('b'),
+            E::ctrl('_'),
+            E::ctrl('X'),
+            E::from('b'),
+            E(K::Char('_'), M::CTRL_ALT),
+            E::ENTER,
+        ],
+        ("a", ""),
+    );
+}
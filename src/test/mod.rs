@@ -1,20 +1,25 @@
+use std::borrow::Cow;
+use std::ops::Range;
 use std::vec::IntoIter;
 
 use crate::completion::Completer;
 use crate::config::{CompletionType, Config, EditMode};
 use crate::edit::init_state;
-use crate::highlight::Highlighter;
+use crate::highlight::{Background, CmdKind, Highlighter, PromptMode};
 use crate::hint::Hinter;
 use crate::history::History;
 use crate::keymap::{Bindings, Cmd, InputState};
 use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
+use crate::layout::Unit;
 use crate::tty::Sink;
-use crate::validate::Validator;
+use crate::validate::{ValidationContext, ValidationResult, Validator};
 use crate::{apply_backspace_direct, readline_direct, Context, DefaultEditor, Helper, Result};
 
+#[allow(clippy::single_element_loop)] // placeholders for modes still to fix
 mod common;
 mod emacs;
 mod history;
+#[allow(clippy::single_element_loop)]
 mod vi_cmd;
 mod vi_insert;
 
@@ -59,6 +64,124 @@ impl Helper for SimpleCompleter {}
 impl Highlighter for SimpleCompleter {}
 impl Validator for SimpleCompleter {}
 
+/// Helper validating with `V` and highlighting with `H`, without completion
+/// nor hints
+pub(crate) struct TestHelper<V = (), H = ()>(pub V, pub H);
+
+/// Helper validating with `validate`
+pub(crate) fn validate_with<F>(validate: F) -> TestHelper<ValidateFn<F>>
+where
+    F: Fn(&mut ValidationContext) -> Result<ValidationResult>,
+{
+    TestHelper(ValidateFn(validate), ())
+}
+
+/// Validator of a closure
+pub(crate) struct ValidateFn<F>(F);
+
+impl<F> Validator for ValidateFn<F>
+where
+    F: Fn(&mut ValidationContext) -> Result<ValidationResult>,
+{
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        (self.0)(ctx)
+    }
+}
+
+impl<V, H> Completer for TestHelper<V, H> {
+    type Candidate = String;
+}
+
+impl<V, H> Hinter for TestHelper<V, H> {
+    type Hint = String;
+}
+
+impl<V, H: Highlighter> Highlighter for TestHelper<V, H> {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        self.1.highlight(line, pos)
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        self.1.highlight_prompt(prompt, default)
+    }
+
+    fn prompt_with_mode<'p>(&self, prompt: &'p str, mode: PromptMode) -> Cow<'p, str> {
+        self.1.prompt_with_mode(prompt, mode)
+    }
+
+    fn continuation_prompt(&self, line_number: usize) -> Cow<'_, str> {
+        self.1.continuation_prompt(line_number)
+    }
+
+    fn shrink_prompt<'p>(&self, prompt: &'p str, width: Unit) -> Cow<'p, str> {
+        self.1.shrink_prompt(prompt, width)
+    }
+
+    fn highlight_search_match<'l>(&self, line: &'l str, range: Range<usize>) -> Cow<'l, str> {
+        self.1.highlight_search_match(line, range)
+    }
+
+    fn highlight_preedit<'l>(&self, line: &'l str, range: Range<usize>) -> Cow<'l, str> {
+        self.1.highlight_preedit(line, range)
+    }
+
+    fn highlight_selection<'l>(&self, line: &'l str, ranges: &[Range<usize>]) -> Cow<'l, str> {
+        self.1.highlight_selection(line, ranges)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.1.highlight_hint(hint)
+    }
+
+    fn highlight_validation_message<'m>(&self, msg: &'m str, invalid: bool) -> Cow<'m, str> {
+        self.1.highlight_validation_message(msg, invalid)
+    }
+
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        completion: CompletionType,
+    ) -> Cow<'c, str> {
+        self.1.highlight_candidate(candidate, completion)
+    }
+
+    fn highlight_char(&self, line: &str, pos: usize, kind: CmdKind) -> bool {
+        self.1.highlight_char(line, pos, kind)
+    }
+
+    fn set_background(&self, background: Background) {
+        self.1.set_background(background);
+    }
+}
+
+impl<V: Validator, H> Validator for TestHelper<V, H> {
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        self.0.validate(ctx)
+    }
+
+    fn validate_while_typing(&self) -> bool {
+        self.0.validate_while_typing()
+    }
+
+    fn poll_validation(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        self.0.poll_validation(ctx)
+    }
+
+    fn cancel_validation(&self) {
+        self.0.cancel_validation();
+    }
+
+    fn indent(&self, line: &str, pos: usize) -> Option<String> {
+        self.0.indent(line, pos)
+    }
+}
+
+impl<V: Validator, H: Highlighter> Helper for TestHelper<V, H> {}
+
 #[test]
 fn complete_line() {
     let mut out = Sink::default();
@@ -100,6 +223,29 @@ fn complete_symbol() {
     assert_eq!(3, s.line.pos());
 }
 
+#[test]
+fn page_completions() {
+    let mut out = Sink::default();
+    let history = crate::history::DefaultHistory::new();
+    let helper = Some(SimpleCompleter);
+    let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+    let config = Config::builder()
+        .completion_page_rows(Some(2))
+        .completion_more_prompt("[more]")
+        .completion_max_columns(Some(2))
+        .completion_column_padding(1)
+        .completion_horizontal(true)
+        .build();
+    let bindings = Bindings::new();
+    let mut input_state = InputState::new(&config, &bindings);
+    let mut rdr: IntoIter<KeyEvent> = vec![E::from(' ')].into_iter();
+    let candidates = ["a", "bb", "ccc", "dd", "e"].map(str::to_owned);
+    let cmd =
+        super::page_completions(&mut rdr, &mut s, &mut input_state, &candidates, &config).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!("\na   bb\nccc dd\n[more]\ne\n", out.written);
+}
+
 // `keys`: keys to press
 // `expected_line`: line after enter key
 fn assert_line(mode: EditMode, keys: &[KeyEvent], expected_line: &str) {
@@ -160,6 +306,491 @@ fn unknown_esc_key() {
     }
 }
 
+#[test]
+#[cfg(feature = "custom-bindings")]
+fn bind_sequence_in() {
+    use crate::BindingMode;
+
+    let keys = [E::from('x'), E::ESC, E::from('x'), E::ENTER];
+    let mut editor = init_editor(EditMode::Vi, &keys);
+    editor.bind_sequence_in(
+        BindingMode::ViCommand,
+        E::from('x'),
+        Cmd::Insert(1, "!".to_owned()),
+    );
+    // insert mode is not affected
+    assert_eq!("!x", editor.readline("").unwrap());
+
+    let keys = [E::from('x'), E::ENTER];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(E::from('x'), Cmd::Insert(1, "global".to_owned()));
+    editor.bind_sequence_in(
+        BindingMode::Emacs,
+        E::from('x'),
+        Cmd::Insert(1, "emacs".to_owned()),
+    );
+    // mode binding takes precedence
+    assert_eq!("emacs", editor.readline("").unwrap());
+    editor.term.keys.extend(keys);
+    editor.unbind_sequence_in(BindingMode::Emacs, E::from('x'));
+    assert_eq!("global", editor.readline("").unwrap());
+}
+
+#[test]
+#[cfg(feature = "custom-bindings")]
+fn line_hook() {
+    use crate::compat::LineHook;
+
+    let keys = [
+        E::from('a'),
+        E::from('b'),
+        E::ctrl('X'),
+        E::from('c'),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(
+        E::ctrl('X'),
+        LineHook::new(|line: &mut String, point: &mut usize| {
+            *line = line.to_uppercase();
+            *point = 0;
+        }),
+    );
+    assert_eq!("cAB", editor.readline("").unwrap());
+}
+
+#[test]
+#[cfg(feature = "custom-bindings")]
+fn binding_tracer() {
+    use crate::{BindingMode, BindingSource, BindingTrace, ConditionalEventHandler, Event};
+    use crate::{EventContext, EventHandler, RepeatCount};
+    use std::sync::{Arc, Mutex};
+
+    struct Decline;
+    impl ConditionalEventHandler for Decline {
+        fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+            None
+        }
+    }
+
+    let keys = [
+        E::from('a'),
+        E::ctrl('X'),
+        E::ctrl('U'),
+        E::from('b'),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence_in(
+        BindingMode::Emacs,
+        E::from('b'),
+        EventHandler::Conditional(Box::new(Decline)),
+    );
+    editor.bind_sequence(E::from('b'), Cmd::Insert(1, "c".to_owned()));
+    let traces = Arc::new(Mutex::new(Vec::<BindingTrace>::new()));
+    let sink = traces.clone();
+    editor.set_binding_tracer(Some(Box::new(move |trace| {
+        sink.lock().unwrap().push(trace.clone());
+    })));
+    assert_eq!("b", editor.readline("").unwrap());
+
+    let traces = traces.lock().unwrap();
+    let resolved: Vec<_> = traces
+        .iter()
+        .map(|t| (t.keys.clone(), t.source, t.cmd.clone()))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                vec![E::from('a')],
+                BindingSource::KeyMap,
+                Cmd::SelfInsert(1, 'a')
+            ),
+            (
+                vec![E::ctrl('X'), E::ctrl('U')],
+                BindingSource::KeyMap,
+                Cmd::Undo(1)
+            ),
+            (
+                vec![E::from('b')],
+                BindingSource::KeyMap,
+                Cmd::SelfInsert(1, 'b')
+            ),
+            (
+                vec![E::ENTER],
+                BindingSource::KeyMap,
+                Cmd::AcceptOrInsertLine {
+                    accept_in_the_middle: true
+                }
+            ),
+        ],
+        resolved
+    );
+    // the mode binding declined, the global one is not tried
+    assert_eq!(
+        vec![BindingSource::Custom {
+            scope: Some(BindingMode::Emacs),
+            any: false,
+            conditional: true
+        }],
+        traces[2].declined
+    );
+
+    editor.term.keys.extend([E::from('b'), E::ENTER]);
+    editor.unbind_sequence_in(BindingMode::Emacs, E::from('b'));
+    editor.set_binding_tracer(Some(Box::new(|trace| {
+        if trace.keys == [E::from('b')] {
+            assert_eq!(
+                BindingSource::Custom {
+                    scope: None,
+                    any: false,
+                    conditional: false
+                },
+                trace.source
+            );
+        }
+    })));
+    assert_eq!("c", editor.readline("").unwrap());
+}
+
+#[test]
+fn auto_indent() {
+    let config = Config::builder().auto_indent(true).build();
+    let mut editor = crate::Editor::with_config(config).unwrap();
+    editor.set_helper(Some(TestHelper(
+        crate::validate::MatchingBracketValidator::new(),
+        (),
+    )));
+    editor.term.keys.extend([
+        E::from(' '),
+        E::from(' '),
+        E::from('f'),
+        E::from('('),
+        E::ENTER,
+        E::from('x'),
+        E::ENTER,
+        E::from(')'),
+        E::ENTER,
+    ]);
+    let line = editor.readline(">>").unwrap();
+    assert_eq!("  f(\n  x\n  )", line);
+}
+
+#[test]
+fn incomplete_with_continuation() {
+    use crate::validate::Continuation;
+
+    let mut editor = crate::Editor::new().unwrap();
+    editor.set_helper(Some(validate_with(|ctx| {
+        Ok(if ctx.input().ends_with('(') {
+            ValidationResult::IncompleteWith(Continuation::new().indent("    ").closer(")"))
+        } else {
+            ValidationResult::Valid(None)
+        })
+    })));
+    editor.term.keys.extend([
+        E::from('f'),
+        E::from('('),
+        E::ENTER,
+        E::from('x'),
+        E::from(')'),
+        E::ENTER,
+    ]);
+    assert_eq!("f(\n    x)", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn tab_insertion() {
+    use crate::config::TabInsertion;
+
+    for (insertion, expected) in [
+        (TabInsertion::Nothing, "  a\nb"),
+        (TabInsertion::Tab, "  a\n\tb\t"),
+        (TabInsertion::Spaces(3), "  a\n   b   "),
+        (TabInsertion::SmartIndent, "  a\n  b "),
+    ] {
+        let config = Config::builder().tab_insertion(insertion).build();
+        let mut editor = DefaultEditor::with_config(config).unwrap();
+        editor.term.keys.extend([
+            E(K::Tab, M::NONE),
+            E::from('b'),
+            E(K::Tab, M::NONE),
+            E::ENTER,
+        ]);
+        assert_eq!(
+            expected,
+            editor.readline_with_initial(">>", ("  a\n", "")).unwrap()
+        );
+    }
+}
+
+#[test]
+fn config_handle() {
+    use crate::config::Configurer;
+
+    let mut editor = crate::Editor::new().unwrap();
+    let handle = editor.config_handle();
+    // switch to vi mode while the line is edited
+    let switch = handle.clone();
+    editor.set_helper(Some(validate_with(move |ctx| {
+        Ok(if ctx.input().ends_with('(') {
+            switch.update(|b| b.set_edit_mode(EditMode::Vi));
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Valid(None)
+        })
+    })));
+    editor.term.keys.extend([
+        E::from('('),
+        E::ENTER,
+        E::from('b'),
+        E::ESC,
+        E::from('x'),
+        E::ENTER,
+    ]);
+    assert_eq!("(\n", editor.readline(">>").unwrap());
+    assert_eq!(EditMode::Vi, editor.config.edit_mode());
+
+    // changes made by the editor are published at the next line
+    editor.set_max_history_size(5).unwrap();
+    editor.term.keys.extend([E::ENTER]);
+    editor.readline(">>").unwrap();
+    assert_eq!(5, handle.get().max_history_size());
+    // and the ones made by the handle applied
+    handle.update(|b| b.set_max_history_size(3).unwrap());
+    editor.term.keys.extend([E::ENTER]);
+    editor.readline(">>").unwrap();
+    assert_eq!(3, editor.config.max_history_size());
+}
+
+#[test]
+fn config_handle_panic() {
+    use crate::config::Configurer;
+
+    let mut editor = DefaultEditor::new().unwrap();
+    let handle = editor.config_handle();
+    let poison = handle.clone();
+    std::thread::spawn(move || {
+        poison.update(|b| {
+            b.set_edit_mode(EditMode::Vi);
+            panic!("poisoned");
+        })
+    })
+    .join()
+    .unwrap_err();
+    // the configuration is left as it was before the panic
+    assert_eq!(EditMode::Emacs, handle.get().edit_mode());
+    handle.update(|b| b.set_max_history_size(3).unwrap());
+    editor.term.keys.extend([E::ENTER]);
+    editor.readline(">>").unwrap();
+    assert_eq!(3, editor.config.max_history_size());
+    assert_eq!(EditMode::Emacs, editor.config.edit_mode());
+}
+
+#[test]
+fn force_accept_line() {
+    for mode in [EditMode::Emacs, EditMode::Vi] {
+        let config = Config::builder().edit_mode(mode).build();
+        let mut editor = crate::Editor::with_config(config).unwrap();
+        editor.set_helper(Some(validate_with(|_| Ok(ValidationResult::Incomplete))));
+        editor
+            .term
+            .keys
+            .extend([E::from('a'), E::ENTER, E::from('b'), E(K::Enter, M::ALT)]);
+        assert_eq!("a\nb", editor.readline(">>").unwrap());
+        assert!(editor.validation_overridden());
+    }
+}
+
+#[test]
+fn render_stats() {
+    let mut editor = DefaultEditor::new().unwrap();
+    editor.term.keys.extend([E::from('a'), E::ENTER]);
+    editor.readline(">>").unwrap();
+    assert_eq!(None, editor.render_stats());
+
+    let config = Config::builder()
+        .render_stats(true)
+        .coalesce_refresh(false)
+        .build();
+    let mut editor = DefaultEditor::with_config(config).unwrap();
+    editor.term.keys.extend([E::from('a'), E::ENTER]);
+    editor.readline(">>").unwrap();
+    let stats = editor.render_stats().unwrap();
+    assert!(stats.refreshes > 0);
+    assert_eq!(stats.refreshes, stats.layouts);
+    editor.term.keys.extend([E::from('a'), E::ENTER]);
+    editor.readline(">>").unwrap();
+    assert!(editor.render_stats().unwrap().refreshes > stats.refreshes);
+    editor.reset_render_stats();
+    assert_eq!(0, editor.render_stats().unwrap().refreshes);
+}
+
+/// Sequences changing the cursor shape in `rendered`
+fn cursor_shapes(rendered: &str) -> Vec<&str> {
+    rendered
+        .match_indices("\x1b[")
+        .filter_map(|(i, _)| rendered.get(i..i + 5))
+        .filter(|seq| seq.ends_with(" q"))
+        .collect()
+}
+
+#[test]
+fn cursor_shape() {
+    use crate::config::CursorShape;
+    use crate::InputMode;
+
+    let mut editor = init_editor(
+        EditMode::Vi,
+        &[E::from('a'), E::ESC, E::from('i'), E::ENTER],
+    );
+    editor.readline(">>").unwrap();
+    assert!(cursor_shapes(&editor.term.output.lock().unwrap().rendered).is_empty());
+
+    let config = Config::builder()
+        .edit_mode(EditMode::Vi)
+        .cursor_shape(InputMode::Insert, CursorShape::BlinkingBar)
+        .cursor_shape(InputMode::Command, CursorShape::SteadyBlock)
+        .build();
+    let mut editor = DefaultEditor::with_config(config).unwrap();
+    editor
+        .term
+        .keys
+        .extend([E::from('a'), E::ESC, E::from('i'), E::ENTER]);
+    editor.readline(">>").unwrap();
+    // reset to the terminal default on exit
+    assert_eq!(
+        vec!["\x1b[5 q", "\x1b[2 q", "\x1b[5 q", "\x1b[0 q"],
+        cursor_shapes(&editor.term.output.lock().unwrap().rendered)
+    );
+}
+
+#[test]
+fn alternate_screen() {
+    let config = Config::builder().alternate_screen(true).build();
+    let mut editor = DefaultEditor::with_config(config).unwrap();
+    editor
+        .term
+        .keys
+        .extend([E::from('a'), E::from('b'), E::ENTER]);
+    assert_eq!("ab", editor.readline(">> ").unwrap());
+    let output = editor.term.output.lock().unwrap();
+    // editing on the cleared alternate screen
+    assert!(output.rendered.starts_with("\x1b[?1049h\x1b[H\x1b[2J"));
+    // then back to the primary screen with the accepted line
+    assert!(output.rendered.ends_with("\x1b[?1049l>> ab"));
+    drop(output);
+
+    // nothing echoed when interrupted
+    editor.term.output = Default::default();
+    editor.term.keys = vec![E::from('a'), E::ctrl('C')];
+    assert!(editor.readline(">> ").is_err());
+    let output = editor.term.output.lock().unwrap();
+    assert!(output.rendered.starts_with("\x1b[?1049h"));
+    assert!(output.rendered.ends_with("\x1b[?1049l"));
+}
+
+#[test]
+fn resize() {
+    use crate::tty::RESIZE;
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct ResizeHelper(Cell<Option<(Unit, Unit)>>);
+    impl Completer for ResizeHelper {
+        type Candidate = String;
+    }
+    impl Hinter for ResizeHelper {
+        type Hint = String;
+    }
+    impl Highlighter for ResizeHelper {}
+    impl Validator for ResizeHelper {}
+    impl Helper for ResizeHelper {
+        fn on_resize(&self, cols: Unit, rows: Unit) {
+            self.0.set(Some((cols, rows)));
+        }
+    }
+
+    let mut editor = crate::Editor::<ResizeHelper, _>::new().unwrap();
+    editor.set_helper(Some(ResizeHelper::default()));
+    editor.term.keys = vec![E::from('a'), E::from('b'), E::ENTER];
+    editor.readline(">> ").unwrap();
+    let output = std::mem::take(&mut *editor.term.output.lock().unwrap());
+    assert_eq!(">> >> ab", output.rendered);
+
+    editor.term.output.lock().unwrap().resized = Some((40, 12));
+    // the second resize doesn't change the size
+    editor.term.keys = vec![E::from('a'), RESIZE, RESIZE, E::from('b'), E::ENTER];
+    assert_eq!("ab", editor.readline(">> ").unwrap());
+    assert_eq!(Some((40, 12)), editor.helper().unwrap().0.get());
+    // prompt and line redrawn once after the resize
+    assert_eq!(">> >> a>> ab", editor.term.output.lock().unwrap().rendered);
+}
+
+#[test]
+fn async_validation() {
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct AsyncValidator {
+        polls: Cell<usize>,
+        cancelled: Cell<bool>,
+    }
+    impl Validator for AsyncValidator {
+        fn validate(&self, _: &mut ValidationContext) -> Result<ValidationResult> {
+            Ok(ValidationResult::Pending(Some("checking...".to_owned())))
+        }
+
+        fn poll_validation(&self, _: &mut ValidationContext) -> Result<ValidationResult> {
+            // resolved on the second poll
+            self.polls.set(self.polls.get() + 1);
+            if self.polls.get() < 2 {
+                Ok(ValidationResult::Pending(None))
+            } else {
+                Ok(ValidationResult::Valid(None))
+            }
+        }
+
+        fn cancel_validation(&self) {
+            self.cancelled.set(true);
+        }
+    }
+
+    let mut editor = crate::Editor::new().unwrap();
+    editor.set_helper(Some(TestHelper(AsyncValidator::default(), ())));
+    // 'b' typed while the validation of "a" is pending cancels it
+    editor
+        .term
+        .keys
+        .extend([E::from('a'), E::ENTER, E::from('b'), E::ENTER]);
+    assert_eq!("ab", editor.readline(">>").unwrap());
+    let validator = &editor.helper().unwrap().0;
+    assert_eq!(2, validator.polls.get());
+    assert!(validator.cancelled.get());
+}
+
+#[test]
+fn validation_context() {
+    use std::cell::Cell;
+
+    let pos = Cell::new(0);
+    let history_len = Cell::new(0);
+    let mut editor = crate::Editor::new().unwrap();
+    editor.set_helper(Some(validate_with(|ctx| {
+        pos.set(ctx.pos());
+        history_len.set(ctx.context().map_or(0, |ctx| ctx.history().len()));
+        Ok(ValidationResult::Valid(None))
+    })));
+    editor.add_history_entry("x").unwrap();
+    editor
+        .term
+        .keys
+        .extend([E::from('a'), E::from('b'), E(K::Left, M::NONE), E::ENTER]);
+    assert_eq!("ab", editor.readline(">>").unwrap());
+    assert_eq!(1, pos.get());
+    assert_eq!(1, history_len.get());
+}
+
 #[test]
 fn test_send() {
     fn assert_send<T: Send>() {}
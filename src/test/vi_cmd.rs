@@ -223,6 +223,45 @@ fn uppercase_f() {
     );
 }
 
+#[test]
+fn g() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", "a\n  b\nc"),
+        &[E::ESC, E::from('2'), E::from('g'), E::from('g'), E::ENTER],
+        ("a\n  ", "b\nc"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "a\nb\nc"),
+        &[
+            E::ESC,
+            E::from('d'),
+            E::from('2'),
+            E::from('g'),
+            E::from('g'),
+            E::ENTER,
+        ],
+        ("", "\nc"),
+    );
+}
+
+#[test]
+fn uppercase_g() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", "a\nb\nc"),
+        &[E::ESC, E::from('G'), E::ENTER],
+        ("a\nb\n", "c"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "a\nb\nc"),
+        &[E::ESC, E::from('2'), E::from('G'), E::ENTER],
+        ("a\n", "b\nc"),
+    );
+}
+
 #[test]
 fn i() {
     assert_cursor(
@@ -426,6 +465,77 @@ fn ctrl_p() {
     }
 }
 
+#[test]
+fn m() {
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello", ", world!"),
+        &[
+            E::ESC,
+            E::from('m'),
+            E::from('a'),
+            E::from('0'),
+            E::from('`'),
+            E::from('a'),
+            E::ENTER,
+        ],
+        ("Hell", "o, world!"),
+    );
+    // marks follow edits
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello", ", world!"),
+        &[
+            E::ESC,
+            E::from('m'),
+            E::from('a'),
+            E::from('I'),
+            E::from('X'),
+            E::ESC,
+            E::from('`'),
+            E::from('a'),
+            E::ENTER,
+        ],
+        ("XHell", "o, world!"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("line1\n  li", "ne2"),
+        &[
+            E::ESC,
+            E::from('m'),
+            E::from('a'),
+            E::from('k'),
+            E::from('\''),
+            E::from('a'),
+            E::ENTER,
+        ],
+        ("line1\n  ", "line2"),
+    );
+    // unknown mark
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello", ""),
+        &[E::ESC, E::from('0'), E::from('`'), E::from('b'), E::ENTER],
+        ("", "Hello"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello, ", "world!"),
+        &[
+            E::ESC,
+            E::from('m'),
+            E::from('a'),
+            E::from('0'),
+            E::from('d'),
+            E::from('`'),
+            E::from('a'),
+            E::ENTER,
+        ],
+        ("", " world!"),
+    );
+}
+
 #[test]
 fn p() {
     assert_cursor(
@@ -460,6 +570,112 @@ fn r() {
         &[E::ESC, E::from('4'), E::from('r'), E::from('i'), E::ENTER],
         ("Hiii", "i, world!"),
     );
+    // not enough characters
+    assert_cursor(
+        EditMode::Vi,
+        ("H", "i"),
+        &[E::ESC, E::from('3'), E::from('r'), E::from('o'), E::ENTER],
+        ("", "Hi"),
+    );
+    // wide graphemes
+    assert_cursor(
+        EditMode::Vi,
+        ("a", "\u{4e2d}\u{6587}"),
+        &[E::ESC, E::from('l'), E::from('r'), E::from('b'), E::ENTER],
+        ("a", "b\u{6587}"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "e\u{301}f"),
+        &[E::ESC, E::from('r'), E::from('\u{4e2d}'), E::ENTER],
+        ("", "\u{4e2d}f"),
+    );
+}
+
+#[test]
+fn uppercase_r() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", "Hello"),
+        &[
+            E::ESC,
+            E::from('R'),
+            E::from('J'),
+            E::from('e'),
+            E::ESC,
+            E::ENTER,
+        ],
+        ("J", "ello"),
+    );
+    // past the end of line
+    assert_cursor(
+        EditMode::Vi,
+        ("Hi", ""),
+        &[
+            E::ESC,
+            E::from('R'),
+            E::from('o'),
+            E::from('o'),
+            E::from('k'),
+            E::ENTER,
+        ],
+        ("Hook", ""),
+    );
+    // backspace restores overwritten (and removes appended) chars
+    assert_cursor(
+        EditMode::Vi,
+        ("Hi", ""),
+        &[
+            E::ESC,
+            E::from('R'),
+            E::from('o'),
+            E::from('o'),
+            E::BACKSPACE,
+            E::BACKSPACE,
+            E::ENTER,
+        ],
+        ("H", "i"),
+    );
+    // backspace before the replace start only moves the cursor
+    assert_cursor(
+        EditMode::Vi,
+        ("Hel", "lo"),
+        &[
+            E::ESC,
+            E::from('R'),
+            E::from('L'),
+            E::BACKSPACE,
+            E::BACKSPACE,
+            E::ENTER,
+        ],
+        ("H", "ello"),
+    );
+    // wide graphemes
+    assert_cursor(
+        EditMode::Vi,
+        ("", "\u{4e2d}e\u{301}"),
+        &[
+            E::ESC,
+            E::from('R'),
+            E::from('a'),
+            E::from('b'),
+            E::BACKSPACE,
+            E::ENTER,
+        ],
+        ("a", "e\u{301}"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "ab"),
+        &[
+            E::ESC,
+            E::from('R'),
+            E::from('e'),
+            E::from('\u{301}'),
+            E::ENTER,
+        ],
+        ("e\u{301}", "b"),
+    );
 }
 
 #[test]
@@ -488,6 +704,61 @@ fn uppercase_s() {
     );
 }
 
+#[test]
+fn surround() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", "Hello world"),
+        &[
+            E::ESC,
+            E::from('y'),
+            E::from('s'),
+            E::from('e'),
+            E::from('"'),
+            E::ENTER,
+        ],
+        ("", "\"Hello\" world"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "Hello"),
+        &[
+            E::ESC,
+            E::from('y'),
+            E::from('s'),
+            E::from('s'),
+            E::from(')'),
+            E::ENTER,
+        ],
+        ("", "(Hello)"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("\"Hel", "lo\" world"),
+        &[E::ESC, E::from('d'), E::from('s'), E::from('"'), E::ENTER],
+        ("", "Hello world"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("(a (b", ") c)"),
+        &[E::ESC, E::from('d'), E::from('s'), E::from('('), E::ENTER],
+        ("(a ", "b c)"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("\"Hel", "lo\" world"),
+        &[
+            E::ESC,
+            E::from('c'),
+            E::from('s'),
+            E::from('"'),
+            E::from('['),
+            E::ENTER,
+        ],
+        ("", "[Hello] world"),
+    );
+}
+
 #[test]
 fn t() {
     assert_cursor(
@@ -596,3 +867,229 @@ fn dedent() {
         ("lin", "e1\nline2"),
     );
 }
+
+#[test]
+fn search() {
+    let entries = &["foo bar", "baz", "bar qux", "quux"];
+    // `/` searches older entries
+    assert_history(
+        EditMode::Vi,
+        entries,
+        &[
+            E::ESC,
+            E::from('/'),
+            E::from('b'),
+            E::from('a'),
+            E::ENTER,
+            E::ENTER,
+        ],
+        "",
+        ("", "bar qux"),
+    );
+    // `n` repeats, `N` reverses
+    assert_history(
+        EditMode::Vi,
+        entries,
+        &[
+            E::ESC,
+            E::from('/'),
+            E::from('b'),
+            E::from('a'),
+            E::from('r'),
+            E::ENTER,
+            E::from('n'),
+            E::ENTER,
+        ],
+        "",
+        ("foo ", "bar"),
+    );
+    assert_history(
+        EditMode::Vi,
+        entries,
+        &[
+            E::ESC,
+            E::from('/'),
+            E::from('b'),
+            E::from('a'),
+            E::ENTER,
+            E::from('n'),
+            E::from('n'),
+            E::from('N'),
+            E::ENTER,
+        ],
+        "",
+        ("", "baz"),
+    );
+    // offsets
+    assert_history(
+        EditMode::Vi,
+        entries,
+        &[
+            E::ESC,
+            E::from('/'),
+            E::from('q'),
+            E::from('u'),
+            E::from('/'),
+            E::from('e'),
+            E::ENTER,
+            E::ENTER,
+        ],
+        "",
+        ("q", "uux"),
+    );
+    assert_history(
+        EditMode::Vi,
+        entries,
+        &[
+            E::ESC,
+            E::from('/'),
+            E::from('q'),
+            E::from('/'),
+            E::from('s'),
+            E::from('-'),
+            E::from('2'),
+            E::ENTER,
+            E::from('n'),
+            E::ENTER,
+        ],
+        "",
+        ("ba", "r qux"),
+    );
+    // aborted search
+    assert_history(
+        EditMode::Vi,
+        entries,
+        &[E::ESC, E::from('/'), E::from('b'), E::ESC, E::ENTER],
+        "",
+        ("", ""),
+    );
+}
+
+#[test]
+fn ctrl_q() {
+    // block delete
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc\ndef\nghi"),
+        &[
+            E::ESC,
+            E::ctrl('Q'),
+            E::from('l'),
+            E::from('j'),
+            E::from('d'),
+            E::ENTER,
+        ],
+        ("", "c\nf\nghi"),
+    );
+    // cancelled selection
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc\ndef"),
+        &[E::ESC, E::ctrl('Q'), E::from('j'), E::ESC, E::ENTER],
+        ("abc\n", "def"),
+    );
+    // block yank
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc\ndef"),
+        &[
+            E::ESC,
+            E::from('l'),
+            E::ctrl('Q'),
+            E::from('j'),
+            E::from('l'),
+            E::from('y'),
+            E::from('P'),
+            E::ENTER,
+        ],
+        ("abc\ne", "fbc\ndef"),
+    );
+    // insert on every line
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc\ndef\nghi"),
+        &[
+            E::ESC,
+            E::from('l'),
+            E::ctrl('Q'),
+            E::from('j'),
+            E::from('j'),
+            E::from('I'),
+            E::from('-'),
+            E::ESC,
+            E::ENTER,
+        ],
+        ("a", "-bc\nd-ef\ng-hi"),
+    );
+    // append on every line, padding short lines
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc\nd\nghi"),
+        &[
+            E::ESC,
+            E::from('l'),
+            E::ctrl('Q'),
+            E::from('j'),
+            E::from('j'),
+            E::from('A'),
+            E::from('+'),
+            E::ESC,
+            E::ENTER,
+        ],
+        ("ab", "+c\nd +\ngh+i"),
+    );
+    // change
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc\ndef"),
+        &[
+            E::ESC,
+            E::ctrl('Q'),
+            E::from('j'),
+            E::from('c'),
+            E::from('x'),
+            E::ESC,
+            E::ENTER,
+        ],
+        ("", "xbc\nxef"),
+    );
+}
+
+#[test]
+fn braces() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", "a\nb\n\nc"),
+        &[E::ESC, E::from('}'), E::ENTER],
+        ("a\nb\n", "\nc"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "a\nb\n\nc"),
+        &[E::ESC, E::from('G'), E::from('{'), E::ENTER],
+        ("a\nb\n", "\nc"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "a\nb\n\nc"),
+        &[E::ESC, E::from('d'), E::from('}'), E::ENTER],
+        ("", "\nc"),
+    );
+}
+
+#[test]
+fn ctrl_r() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", ""),
+        &[
+            E::from('a'),
+            E::from('b'),
+            E::ESC,
+            E::from('u'),
+            E::ctrl('R'),
+            E::ENTER,
+        ],
+        ("ab", ""),
+    );
+}
@@ -44,3 +44,74 @@ fn esc() {
         ("", "a"),
     );
 }
+
+#[test]
+#[cfg(feature = "custom-bindings")]
+fn mapping() {
+    use crate::tty::PAUSE;
+    use crate::{BindingMode, Cmd, Config, DefaultEditor, Event};
+
+    fn readline(mapping_timeout: Option<u16>, keys: &[E]) -> String {
+        let config = Config::builder()
+            .edit_mode(EditMode::Vi)
+            .mapping_timeout(mapping_timeout)
+            .build();
+        let mut editor = DefaultEditor::with_config(config).unwrap();
+        editor.bind_sequence_in(
+            BindingMode::ViInsert,
+            Event::KeySeq(vec![E::from('j'), E::from('k')]),
+            Cmd::ViMovementMode,
+        );
+        editor.term.keys.extend(keys.iter().copied());
+        editor.readline("").unwrap()
+    }
+
+    // `jk` leaves insert mode
+    assert_eq!(
+        "b",
+        readline(
+            None,
+            &[
+                E::from('a'),
+                E::from('j'),
+                E::from('k'),
+                E::from('x'),
+                E::from('a'),
+                E::from('b'),
+                E::ENTER
+            ]
+        )
+    );
+    // keys not matching the sequence are inserted literally
+    assert_eq!(
+        "jjx",
+        readline(None, &[E::from('j'), E::from('j'), E::from('x'), E::ENTER])
+    );
+    assert_eq!("j", readline(Some(100), &[E::from('j'), E::ENTER]));
+    // a pending `j` is inserted when no key follows within the timeout
+    assert_eq!(
+        "jk",
+        readline(Some(100), &[E::from('j'), PAUSE, E::from('k'), E::ENTER])
+    );
+    // but `jk` typed within the timeout still leaves insert mode
+    assert_eq!(
+        "b",
+        readline(
+            Some(100),
+            &[
+                E::from('a'),
+                E::from('j'),
+                E::from('k'),
+                E::from('x'),
+                E::from('a'),
+                E::from('b'),
+                E::ENTER
+            ]
+        )
+    );
+    // without timeout, the sequence waits for its next key
+    assert_eq!(
+        "",
+        readline(None, &[E::from('j'), PAUSE, E::from('k'), E::ENTER])
+    );
+}
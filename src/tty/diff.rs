@@ -0,0 +1,336 @@
+//! Incremental redraw: only the rows (or the end of the rows) which differ
+//! from the previous rendering are written.
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::width;
+use crate::layout::{ascii_run, AmbiguousWidth, GraphemeClusterMode, Layout, Unit};
+
+/// Clear to the end of the row
+const CLEAR_EOL: &str = "\x1b[K";
+/// Reset the character attributes
+const RESET: &str = "\x1b[0m";
+
+/// Row of rendered text, as wrapped by the terminal
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct Row {
+    /// Escape sequences (styles) preceding the row
+    styles: String,
+    /// Content, escape sequences included
+    text: String,
+    /// Number of columns used
+    width: Unit,
+}
+
+impl Row {
+    fn is_plain(&self) -> bool {
+        self.styles.is_empty() && !self.text.contains('\x1b')
+    }
+}
+
+/// Rows previously rendered, and the rows being rendered (buffers reused
+/// from one refresh to the next)
+#[derive(Debug, Default)]
+pub(super) struct Frame {
+    rows: Vec<Row>,
+    next: Vec<Row>,
+    cols: Unit,
+    valid: bool,
+    /// Styles active while the text is split
+    styles: String,
+    /// Escape sequence being split
+    seq: String,
+}
+
+impl Frame {
+    /// Forget what has been rendered, when something else has been
+    /// written or the terminal has been resized.
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Split `text` in the next rows, wrapped like
+    /// [`Renderer::calculate_position`](super::Renderer::calculate_position)
+    pub fn split(
+        &mut self,
+        text: &str,
+        cols: Unit,
+        tab_stop: Unit,
+        gcm: GraphemeClusterMode,
+        aw: AmbiguousWidth,
+    ) {
+        let Self {
+            next: rows,
+            styles,
+            seq,
+            ..
+        } = self;
+        styles.clear();
+        seq.clear();
+        let mut n = 0;
+        start_row(rows, n, styles);
+        let mut col = 0;
+        let mut esc_seq = 0;
+        let mut offset = 0;
+        while offset < text.len() {
+            let run = if esc_seq == 0 && cols > 0 {
+                ascii_run(&text.as_bytes()[offset..])
+            } else {
+                0
+            };
+            let mut ascii = &text[offset..offset + run];
+            offset += run;
+            while !ascii.is_empty() {
+                if col >= cols {
+                    rows[n].width = col;
+                    n += 1;
+                    start_row(rows, n, styles);
+                    col = 0;
+                }
+                let k = ascii.len().min(usize::from(cols - col));
+                rows[n].text.push_str(&ascii[..k]);
+                col += k as Unit;
+                ascii = &ascii[k..];
+            }
+            let mut next = text.len();
+            for (i, g) in text[offset..].grapheme_indices(true) {
+                let end = offset + i + g.len();
+                if g == "\n" {
+                    rows[n].width = col;
+                    n += 1;
+                    start_row(rows, n, styles);
+                    col = 0;
+                } else {
+                    let in_esc = esc_seq != 0 || g == "\x1b";
+                    let cw = if g == "\t" {
+                        tab_stop - (col % tab_stop)
+                    } else {
+                        width(gcm, aw, g, &mut esc_seq)
+                    };
+                    if in_esc {
+                        seq.push_str(g);
+                        if esc_seq == 0 {
+                            apply(styles, seq);
+                            seq.clear();
+                        }
+                    }
+                    col += cw;
+                    if col > cols {
+                        rows[n].width = col - cw;
+                        n += 1;
+                        start_row(rows, n, styles);
+                        col = cw;
+                    }
+                    rows[n].text.push_str(g);
+                }
+                if esc_seq == 0 && cols > 0 && ascii_run(&text.as_bytes()[end..]) > 0 {
+                    next = end;
+                    break;
+                }
+            }
+            offset = next;
+        }
+        rows[n].width = col;
+        if col == cols {
+            n += 1;
+            start_row(rows, n, styles);
+        }
+        rows.truncate(n + 1);
+    }
+
+    /// Record the next rows as displayed (when they match `layout`)
+    pub fn rendered(&mut self, layout: &Layout, cols: Unit) {
+        self.valid = self.next.len() == usize::from(layout.end.row) + 1;
+        std::mem::swap(&mut self.rows, &mut self.next);
+        self.cols = cols;
+    }
+
+    /// `text` (`width` columns) has been appended to the last row
+    pub fn append(&mut self, text: &str, width: Unit) {
+        if let Some(row) = self.rows.last_mut().filter(|_| self.valid) {
+            row.text.push_str(text);
+            row.width += width;
+        }
+    }
+}
+
+/// Reset (or add) the `n`th row
+fn start_row(rows: &mut Vec<Row>, n: usize, styles: &str) {
+    if n == rows.len() {
+        rows.push(Row::default());
+    }
+    let row = &mut rows[n];
+    row.styles.clear();
+    row.styles.push_str(styles);
+    row.text.clear();
+    row.width = 0;
+}
+
+/// Cursor position while the update is generated (`None` column when
+/// unknown: after a line feed or on the right margin)
+struct Cursor {
+    row: Unit,
+    col: Option<Unit>,
+}
+
+/// Generate in `buf` the update of the `frame` from the displayed rows
+/// (`old`) to the next ones (`new`), the cursor being moved from
+/// `old.cursor` to `new.cursor`.
+///
+/// `cursor_move` moves the cursor `n` times in direction `'A'` (up),
+/// `'C'` (right) or `'D'` (left).
+/// Return `false` (with nothing generated) when what is displayed is unknown.
+pub(super) fn update<F: Fn(&mut String, Unit, char)>(
+    buf: &mut String,
+    frame: &Frame,
+    old: &Layout,
+    new: &Layout,
+    cols: Unit,
+    cursor_move: F,
+) -> bool {
+    let (old_rows, rows) = (&frame.rows, &frame.next);
+    if !frame.valid
+        || frame.cols != cols
+        || old_rows.len() != usize::from(old.end.row) + 1
+        || rows.len() != usize::from(new.end.row) + 1
+    {
+        return false;
+    }
+    let (gcm, aw) = (new.grapheme_cluster_mode, new.ambiguous_width);
+    let styled = !(old_rows.iter().all(Row::is_plain) && rows.iter().all(Row::is_plain));
+    let mut cursor = Cursor {
+        row: old.cursor.row,
+        col: Some(old.cursor.col),
+    };
+    let move_to = |buf: &mut String, cursor: &mut Cursor, row: Unit, col: Unit| {
+        if row > cursor.row {
+            // may scroll
+            for _ in cursor.row..row {
+                buf.push('\n');
+            }
+            cursor.col = None;
+        } else if row < cursor.row {
+            cursor_move(buf, cursor.row - row, 'A');
+        }
+        cursor.row = row;
+        match cursor.col {
+            Some(c) if c == col => {}
+            Some(c) if c > col && col > 0 => cursor_move(buf, c - col, 'D'),
+            Some(c) if c < col => cursor_move(buf, col - c, 'C'),
+            _ => {
+                buf.push('\r');
+                if col > 0 {
+                    cursor_move(buf, col, 'C');
+                }
+            }
+        }
+        cursor.col = Some(col);
+    };
+    let mut written = false;
+    for (i, row) in rows.iter().enumerate() {
+        let old_row = old_rows.get(i);
+        if old_row == Some(row) {
+            continue;
+        }
+        let (offset, col, styles) = match old_row {
+            Some(old_row) if old_row.styles == row.styles => common_prefix(old_row, row, gcm, aw),
+            _ => (0, 0, row.styles.clone()),
+        };
+        move_to(buf, &mut cursor, i as Unit, col);
+        if styled {
+            buf.push_str(RESET);
+        }
+        let text = &row.text[offset..];
+        // tabs move the cursor without erasing
+        let clear_first = old_row.is_some() && text.contains('\t');
+        if clear_first {
+            buf.push_str(CLEAR_EOL);
+        }
+        if styled {
+            buf.push_str(&styles);
+        }
+        buf.push_str(text);
+        if !clear_first && old_row.is_some_and(|o| o.width > row.width) {
+            if styled {
+                buf.push_str(RESET);
+            }
+            buf.push_str(CLEAR_EOL);
+        }
+        cursor.col = if row.width < cols {
+            Some(row.width)
+        } else {
+            None // deferred wrap
+        };
+        written = true;
+    }
+    // rows not used anymore
+    for i in rows.len()..old_rows.len() {
+        move_to(buf, &mut cursor, i as Unit, 0);
+        if styled {
+            buf.push_str(RESET);
+        }
+        buf.push_str(CLEAR_EOL);
+        written = true;
+    }
+    if styled && written {
+        // restore the styles active at the end of the text
+        buf.push_str(RESET);
+        if let Some(last) = rows.last() {
+            let mut styles = last.styles.clone();
+            let mut seq = String::new();
+            let mut esc_seq = 0;
+            for g in last.text.graphemes(true) {
+                if esc_seq != 0 || g == "\x1b" {
+                    seq.push_str(g);
+                    width(gcm, aw, g, &mut esc_seq);
+                    if esc_seq == 0 {
+                        apply(&mut styles, &seq);
+                        seq.clear();
+                    }
+                }
+            }
+            buf.push_str(&styles);
+        }
+    }
+    move_to(buf, &mut cursor, new.cursor.row, new.cursor.col);
+    true
+}
+
+/// Accumulate the escape sequence `seq` in the active `styles`
+fn apply(styles: &mut String, seq: &str) {
+    if seq == RESET || seq == "\x1b[m" {
+        styles.clear();
+    } else {
+        styles.push_str(seq);
+    }
+}
+
+/// Byte offset, column and active styles where `new` starts to differ from
+/// `old` (stopping at the first tab: no tab stop can be crossed differently)
+fn common_prefix(
+    old: &Row,
+    new: &Row,
+    gcm: GraphemeClusterMode,
+    aw: AmbiguousWidth,
+) -> (usize, Unit, String) {
+    let (mut offset, mut col, mut styles) = (0, 0, new.styles.clone());
+    let (mut seq, mut esc_seq) = (String::new(), 0);
+    for (g, o) in new.text.graphemes(true).zip(old.text.graphemes(true)) {
+        if g != o || g == "\t" {
+            break;
+        }
+        let in_esc = esc_seq != 0 || g == "\x1b";
+        col += width(gcm, aw, g, &mut esc_seq);
+        if !in_esc {
+            offset += g.len();
+            continue;
+        }
+        // a sequence is skipped only once complete
+        seq.push_str(g);
+        if esc_seq == 0 {
+            offset += seq.len();
+            apply(&mut styles, &seq);
+            seq.clear();
+        }
+    }
+    (offset, col, styles)
+}
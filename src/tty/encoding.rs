@@ -0,0 +1,118 @@
+//! Transcoding of terminal input/output for non UTF-8 locales
+use std::collections::VecDeque;
+use std::env;
+
+use encoding_rs::{EncoderResult, Encoding, UTF_8};
+
+/// Encoding of the current locale (`LC_ALL`, `LC_CTYPE` or `LANG`) unless
+/// it is UTF-8 or unknown.
+pub fn locale_encoding() -> Option<&'static Encoding> {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())?;
+    let encoding = codeset_encoding(&locale)?;
+    (encoding != UTF_8).then_some(encoding)
+}
+
+/// Encoding of the codeset part of `language[_territory][.codeset][@modifier]`
+fn codeset_encoding(locale: &str) -> Option<&'static Encoding> {
+    let (_, codeset) = locale.split_once('.')?;
+    let codeset = codeset.split('@').next()?.to_ascii_lowercase();
+    // glibc spellings which are not WHATWG labels
+    let label = if let Some(part) = codeset.strip_prefix("iso8859") {
+        format!("iso-8859-{}", part.trim_start_matches(['-', '_']))
+    } else if let Some(region) = codeset.strip_prefix("euc").filter(|r| !r.starts_with('-')) {
+        format!("euc-{region}")
+    } else {
+        codeset
+    };
+    Encoding::for_label(label.as_bytes())
+}
+
+/// Encode `s`, unmappable characters are replaced by `?`.
+pub fn encode(encoding: &'static Encoding, s: &str) -> Vec<u8> {
+    let mut encoder = encoding.new_encoder();
+    let mut out = Vec::with_capacity(s.len());
+    let mut src = s;
+    loop {
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(src, &mut out, true);
+        src = &src[read..];
+        match result {
+            EncoderResult::InputEmpty => return out,
+            EncoderResult::OutputFull => out.reserve(src.len() + 16),
+            EncoderResult::Unmappable(_) => out.push(b'?'),
+        }
+    }
+}
+
+/// Incremental decoder of terminal input
+pub struct Decoder {
+    decoder: encoding_rs::Decoder,
+    chars: VecDeque<char>,
+}
+
+impl Decoder {
+    pub fn new(encoding: &'static Encoding) -> Self {
+        Self {
+            decoder: encoding.new_decoder_without_bom_handling(),
+            chars: VecDeque::new(),
+        }
+    }
+
+    /// Feed one byte of input
+    pub fn push(&mut self, b: u8) {
+        let mut buf = [0; 16];
+        let (_, _, written, _) = self.decoder.decode_to_utf8(&[b], &mut buf, false);
+        if let Ok(s) = std::str::from_utf8(&buf[..written]) {
+            self.chars.extend(s.chars());
+        }
+    }
+
+    /// Next decoded character, if any
+    pub fn pop(&mut self) -> Option<char> {
+        self.chars.pop_front()
+    }
+
+    /// Whether some decoded characters have not been consumed yet
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{codeset_encoding, encode, Decoder};
+    use encoding_rs::{EUC_JP, ISO_8859_15, SHIFT_JIS, UTF_8, WINDOWS_1252};
+
+    #[test]
+    fn codeset() {
+        assert_eq!(None, codeset_encoding("C"));
+        assert_eq!(Some(UTF_8), codeset_encoding("en_US.UTF-8"));
+        assert_eq!(Some(UTF_8), codeset_encoding("en_US.utf8"));
+        assert_eq!(Some(WINDOWS_1252), codeset_encoding("fr_FR.ISO-8859-1"));
+        assert_eq!(Some(ISO_8859_15), codeset_encoding("fr_FR.ISO8859-15@euro"));
+        assert_eq!(Some(SHIFT_JIS), codeset_encoding("ja_JP.SJIS"));
+        assert_eq!(Some(EUC_JP), codeset_encoding("ja_JP.eucJP"));
+        assert_eq!(None, codeset_encoding("xx_XX.unknown"));
+    }
+
+    #[test]
+    fn encode_unmappable() {
+        assert_eq!(b"caf\xe9 ?".to_vec(), encode(WINDOWS_1252, "café \u{3042}"));
+        assert_eq!(b"\x82\xa0".to_vec(), encode(SHIFT_JIS, "\u{3042}"));
+    }
+
+    #[test]
+    fn decode() {
+        let mut decoder = Decoder::new(SHIFT_JIS);
+        decoder.push(0x82);
+        assert_eq!(None, decoder.pop());
+        decoder.push(0xa0);
+        assert_eq!(Some('\u{3042}'), decoder.pop());
+        decoder.push(b'\x1b');
+        assert_eq!(Some('\x1b'), decoder.pop());
+        assert!(decoder.is_empty());
+    }
+}
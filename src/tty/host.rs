@@ -0,0 +1,1413 @@
+//! Terminal emulated by the embedding application (e.g. xterm.js in the
+//! browser): bytes in, bytes out and size notifications go through a
+//! [`Host`].
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+#[cfg(any(test, feature = "test-terminal"))]
+use super::Screen;
+use super::{
+    alternate_screen_seq, cursor_shape_seq, diff, osc52_copy_seq, pasted_text, title_seq,
+    title_stack_seq, vt_key, width_cache::WidthCache, Event, RawMode, RawReader, Renderer, Term,
+};
+use crate::config::{Behavior, BellStyle, ColorMode, Config, CursorShape, Passthrough};
+use crate::error::{ReadlineError, Signal};
+use crate::highlight::{Background, Highlighter};
+use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
+use crate::layout::{AmbiguousWidth, GraphemeClusterMode, Layout, Position, Unit};
+use crate::line_buffer::LineBuffer;
+use crate::{Cmd, Result};
+
+const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
+const BRACKETED_PASTE_OFF: &str = "\x1b[?2004l";
+
+/// Input from the terminal emulator
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HostEvent {
+    /// Bytes typed or pasted by the user (xterm.js `onData`)
+    Data(Vec<u8>),
+    /// The terminal has been resized (xterm.js `onResize`): the new size is
+    /// then queried with [`Host::size`]
+    Resize,
+    /// Text being composed with an input method (xterm.js
+    /// `compositionupdate`), displayed underlined at the cursor until
+    /// committed. An empty text cancels the composition.
+    Preedit(String),
+    /// Text composed with an input method (xterm.js `compositionend`),
+    /// inserted as a single change. It must not be sent as
+    /// [`HostEvent::Data`] too.
+    Commit(String),
+}
+
+/// Terminal emulator driving the line editor.
+///
+/// `next_event` must block, so the editor is usually run in a web worker
+/// while the emulator lives in the main thread.
+pub trait Host: Send {
+    /// Wait for the next input, at most `timeout_ms` when specified.
+    /// Return `Ok(None)` on timeout and an `UnexpectedEof` error when there
+    /// is no more input.
+    fn next_event(&mut self, timeout_ms: Option<u16>) -> io::Result<Option<HostEvent>>;
+    /// Display `data` (text and ANSI escape sequences)
+    fn write(&mut self, data: &str) -> io::Result<()>;
+    /// Number of columns and rows
+    fn size(&self) -> (u16, u16);
+}
+
+type SharedHost = Arc<Mutex<Box<dyn Host>>>;
+
+static HOST: Mutex<Option<SharedHost>> = Mutex::new(None);
+
+/// Install the terminal emulator used by editors created afterwards.
+///
+/// Without host, the terminal is unsupported (except on WASI, where stdin
+/// and stdout are used when stdin is a terminal).
+pub fn set_host<H: Host + 'static>(host: H) {
+    *HOST.lock().unwrap() = Some(Arc::new(Mutex::new(Box::new(host))));
+}
+
+/// Host used when none is installed
+fn default_host() -> Option<SharedHost> {
+    #[cfg(target_os = "wasi")]
+    if io::IsTerminal::is_terminal(&io::stdin()) {
+        return Some(Arc::new(Mutex::new(Box::new(WasiHost))));
+    }
+    None
+}
+
+/// Host over the WASI stdin and stdout.
+///
+/// There is no termios: the runtime must forward the keys unbuffered for the
+/// editing to be interactive (otherwise lines are read once completed by the
+/// terminal). Timed waits cannot be implemented and return immediately, so
+/// only the escape sequences read at once are recognized.
+#[cfg(target_os = "wasi")]
+struct WasiHost;
+
+#[cfg(target_os = "wasi")]
+impl Host for WasiHost {
+    fn next_event(&mut self, timeout_ms: Option<u16>) -> io::Result<Option<HostEvent>> {
+        use std::io::Read;
+        if timeout_ms.is_some() {
+            return Ok(None);
+        }
+        let mut buf = [0; 1024];
+        match io::stdin().read(&mut buf)? {
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            n => Ok(Some(HostEvent::Data(buf[..n].to_vec()))),
+        }
+    }
+
+    fn write(&mut self, data: &str) -> io::Result<()> {
+        use std::io::Write;
+        let mut out = io::stdout().lock();
+        out.write_all(data.as_bytes())?;
+        out.flush()
+    }
+
+    /// `COLUMNS` x `LINES`, 80x24 by default
+    fn size(&self) -> (u16, u16) {
+        let dim = |var, default| {
+            std::env::var(var)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        (dim("COLUMNS", 80), dim("LINES", 24))
+    }
+}
+
+/// Scripted terminal for integration tests: keys are queued before calling
+/// [`Editor::readline`](crate::Editor::readline) and the rendered output is
+/// captured.
+///
+/// Clones share the same input queue and output, so a clone can be given to
+/// [`Editor::set_host`](crate::Editor::set_host) while the original one is
+/// kept to inspect the output. When all the input has been read,
+/// `readline` fails with [`ReadlineError::Eof`].
+#[cfg(any(test, feature = "test-terminal"))]
+#[derive(Clone, Debug)]
+pub struct TestTerminal {
+    inner: Arc<Mutex<Script>>,
+}
+
+#[cfg(any(test, feature = "test-terminal"))]
+#[derive(Debug)]
+struct Script {
+    input: VecDeque<(Option<u32>, HostEvent)>, // delay before the event when replayed
+    output: String,
+    size: (u16, u16),
+    resizes: VecDeque<(u16, u16)>, // sizes of the queued resize events
+    pause: bool,                   // a lone ESC has been read: next timed wait times out
+    screen: Screen,
+}
+
+#[cfg(any(test, feature = "test-terminal"))]
+impl TestTerminal {
+    /// Terminal with `cols` columns and `rows` rows
+    #[must_use]
+    pub fn new(cols: u16, rows: u16) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Script {
+                input: VecDeque::new(),
+                output: String::new(),
+                size: (cols, rows),
+                resizes: VecDeque::new(),
+                pause: false,
+                screen: Screen::new(cols, rows),
+            })),
+        }
+    }
+
+    /// Queue `keys`, each one typed separately
+    pub fn push_keys<K: IntoIterator<Item = KeyEvent>>(&self, keys: K) {
+        let mut script = self.inner.lock().unwrap();
+        for key in keys {
+            let seq = key_seq(key);
+            if !seq.is_empty() {
+                script
+                    .input
+                    .push_back((None, HostEvent::Data(seq.into_bytes())));
+            }
+        }
+    }
+
+    /// Queue `text`, typed character by character
+    pub fn push_str(&self, text: &str) {
+        self.push_keys(text.chars().map(KeyEvent::from));
+    }
+
+    /// Queue raw `bytes` (escape sequences, bracketed paste, ...), read at
+    /// once
+    pub fn push_bytes(&self, bytes: &[u8]) {
+        let mut script = self.inner.lock().unwrap();
+        script
+            .input
+            .push_back((None, HostEvent::Data(bytes.to_vec())));
+    }
+
+    /// Queue an input method composition update (see [`HostEvent::Preedit`])
+    pub fn push_preedit(&self, text: &str) {
+        let mut script = self.inner.lock().unwrap();
+        script
+            .input
+            .push_back((None, HostEvent::Preedit(text.to_owned())));
+    }
+
+    /// Queue text committed by an input method (see [`HostEvent::Commit`])
+    pub fn push_commit(&self, text: &str) {
+        let mut script = self.inner.lock().unwrap();
+        script
+            .input
+            .push_back((None, HostEvent::Commit(text.to_owned())));
+    }
+
+    /// Resize the terminal once the previously queued input has been read
+    pub fn resize(&self, cols: u16, rows: u16) {
+        self.push_resize(None, cols, rows);
+    }
+
+    /// Queue `bytes` received `delay_ms` after the previous event
+    pub(super) fn push_timed(&self, delay_ms: u32, bytes: &[u8]) {
+        let mut script = self.inner.lock().unwrap();
+        script
+            .input
+            .push_back((Some(delay_ms), HostEvent::Data(bytes.to_vec())));
+    }
+
+    pub(super) fn push_resize(&self, delay_ms: Option<u32>, cols: u16, rows: u16) {
+        let mut script = self.inner.lock().unwrap();
+        script.input.push_back((delay_ms, HostEvent::Resize));
+        script.resizes.push_back((cols, rows));
+    }
+
+    /// Text and escape sequences written so far
+    #[must_use]
+    pub fn output(&self) -> String {
+        self.inner.lock().unwrap().output.clone()
+    }
+
+    /// Text and escape sequences written since the last call
+    #[must_use]
+    pub fn take_output(&self) -> String {
+        std::mem::take(&mut self.inner.lock().unwrap().output)
+    }
+
+    /// What is displayed: the output written so far, as the user would see
+    /// it
+    #[must_use]
+    pub fn screen(&self) -> Screen {
+        self.inner.lock().unwrap().screen.clone()
+    }
+}
+
+#[cfg(any(test, feature = "test-terminal"))]
+impl Host for TestTerminal {
+    fn next_event(&mut self, timeout_ms: Option<u16>) -> io::Result<Option<HostEvent>> {
+        let mut script = self.inner.lock().unwrap();
+        if timeout_ms.is_some() && std::mem::take(&mut script.pause) {
+            return Ok(None);
+        }
+        // replayed events: the time waited is deducted from their delay
+        if let (Some((Some(delay), _)), Some(timeout)) = (script.input.front_mut(), timeout_ms) {
+            if *delay > u32::from(timeout) {
+                *delay -= u32::from(timeout);
+                return Ok(None);
+            }
+        }
+        match script.input.pop_front() {
+            Some((delay, event)) => {
+                if event == HostEvent::Resize {
+                    script.size = script.resizes.pop_front().unwrap_or(script.size);
+                    let (cols, rows) = script.size;
+                    script.screen.resize(cols, rows);
+                }
+                script.pause = delay.is_none() && event == HostEvent::Data(b"\x1b".to_vec());
+                Ok(Some(event))
+            }
+            None if timeout_ms.is_some() => Ok(None),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn write(&mut self, data: &str) -> io::Result<()> {
+        let mut script = self.inner.lock().unwrap();
+        script.output.push_str(data);
+        script.screen.feed(data);
+        Ok(())
+    }
+
+    fn size(&self) -> (u16, u16) {
+        self.inner.lock().unwrap().size
+    }
+}
+
+/// Bytes sent by an xterm compatible terminal when `key` is pressed
+#[cfg(any(test, feature = "test-terminal"))]
+fn key_seq(key: KeyEvent) -> String {
+    let E(code, mods) = KeyEvent::normalize(key);
+    // xterm modifier parameter: 1 + (shift | alt << 1 | ctrl << 2)
+    let m = 1
+        + u8::from(mods.contains(M::SHIFT))
+        + 2 * u8::from(mods.contains(M::ALT))
+        + 4 * u8::from(mods.contains(M::CTRL));
+    let csi = |n: u8, final_byte: char| match (m, n) {
+        (1, 1) => format!("\x1b[{final_byte}"),
+        (1, n) => format!("\x1b[{n}{final_byte}"),
+        (m, n) => format!("\x1b[{n};{m}{final_byte}"),
+    };
+    let ss3 = |final_byte: char| match m {
+        1 => format!("\x1bO{final_byte}"),
+        m => format!("\x1b[1;{m}{final_byte}"),
+    };
+    let other = |c: char| match m {
+        1 => c.to_string(),
+        // ESC prefix for alt
+        3 => format!("\x1b{c}"),
+        // modifyOtherKeys
+        m => format!("\x1b[27;{m};{}~", u32::from(c)),
+    };
+    match code {
+        K::Char(c)
+            if mods.contains(M::CTRL) && !mods.contains(M::SHIFT) && ('@'..='_').contains(&c) =>
+        {
+            let c = char::from(c as u8 & 0x1f);
+            if mods.contains(M::ALT) {
+                format!("\x1b{c}")
+            } else {
+                c.to_string()
+            }
+        }
+        K::Char(c) => other(c),
+        K::Enter => other('\r'),
+        K::Tab => other('\t'),
+        K::Backspace => other('\x7f'),
+        K::Esc => other('\x1b'),
+        K::BackTab => "\x1b[Z".to_owned(),
+        K::Up => csi(1, 'A'),
+        K::Down => csi(1, 'B'),
+        K::Right => csi(1, 'C'),
+        K::Left => csi(1, 'D'),
+        K::Home => csi(1, 'H'),
+        K::End => csi(1, 'F'),
+        K::Insert => csi(2, '~'),
+        K::Delete => csi(3, '~'),
+        K::PageUp => csi(5, '~'),
+        K::PageDown => csi(6, '~'),
+        K::F(n @ 1..=4) => ss3(char::from(b'O' + n)),
+        K::F(n @ 5) => csi(n + 10, '~'),
+        K::F(n @ 6..=10) => csi(n + 11, '~'),
+        K::F(n @ 11..=12) => csi(n + 12, '~'),
+        K::BracketedPasteStart => "\x1b[200~".to_owned(),
+        K::BracketedPasteEnd => "\x1b[201~".to_owned(),
+        K::FocusIn => "\x1b[I".to_owned(),
+        K::FocusOut => "\x1b[O".to_owned(),
+        _ => String::new(),
+    }
+}
+
+fn write_all(host: &SharedHost, data: &str) -> Result<()> {
+    host.lock().unwrap().write(data)?;
+    Ok(())
+}
+
+fn size(host: &SharedHost) -> (Unit, Unit) {
+    let (cols, rows) = host.lock().unwrap().size();
+    (cols.max(1), rows.max(1))
+}
+
+pub type HostBuffer = ();
+#[cfg(not(test))]
+pub type Buffer = HostBuffer;
+
+pub type HostKeyMap = ();
+#[cfg(not(test))]
+pub type KeyMap = HostKeyMap;
+
+#[cfg(not(test))]
+pub type Mode = HostMode;
+
+pub struct HostMode {
+    host: SharedHost,
+    bracketed_paste: bool,
+}
+
+impl RawMode for HostMode {
+    fn disable_raw_mode(&self) -> Result<()> {
+        if self.bracketed_paste {
+            write_all(&self.host, BRACKETED_PASTE_OFF)?;
+        }
+        Ok(())
+    }
+}
+
+/// Host input reader
+pub struct HostReader {
+    host: SharedHost,
+    timeout_ms: Option<u16>,
+    // incomplete UTF-8 sequence
+    bytes: Vec<u8>,
+    chars: VecDeque<char>,
+    // IME composition events not yet handled
+    composition: VecDeque<Event>,
+}
+
+impl HostReader {
+    fn new(host: SharedHost, config: &Config) -> Self {
+        Self {
+            host,
+            timeout_ms: config.keyseq_timeout(),
+            bytes: vec![],
+            chars: VecDeque::new(),
+            composition: VecDeque::new(),
+        }
+    }
+
+    /// Wait for input, at most `timeout_ms` when specified.
+    /// Return `false` on timeout.
+    fn fill(&mut self, timeout_ms: Option<u16>) -> Result<bool> {
+        let event = self.host.lock().unwrap().next_event(timeout_ms);
+        match event {
+            Ok(Some(HostEvent::Data(data))) => {
+                self.bytes.extend(data);
+                self.decode();
+                Ok(true)
+            }
+            Ok(Some(HostEvent::Resize)) => Err(ReadlineError::Signal(Signal::Resize)),
+            Ok(Some(HostEvent::Preedit(text))) => {
+                self.composition.push_back(Event::Preedit(text));
+                Ok(true)
+            }
+            Ok(Some(HostEvent::Commit(text))) => {
+                self.composition.push_back(Event::Commit(text));
+                Ok(true)
+            }
+            Ok(None) => Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(ReadlineError::Eof),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Decode complete UTF-8 sequences
+    fn decode(&mut self) {
+        let valid = match std::str::from_utf8(&self.bytes) {
+            Ok(s) => s.len(),
+            // incomplete sequence at the end
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.bytes.len(),
+        };
+        let rest = self.bytes.split_off(valid);
+        self.chars
+            .extend(String::from_utf8_lossy(&self.bytes).chars());
+        self.bytes = rest;
+    }
+
+    fn read_char(&mut self) -> Result<char> {
+        loop {
+            if let Some(c) = self.chars.pop_front() {
+                return Ok(c);
+            }
+            self.fill(None)?;
+        }
+    }
+
+    /// Handle sequences starting with ESC
+    fn escape_sequence(&mut self) -> Result<KeyEvent> {
+        if self.chars.is_empty() && !self.fill(self.timeout_ms)? {
+            return Ok(E::ESC);
+        }
+        Ok(match self.read_char()? {
+            '[' => {
+                // CSI: parameters then a final byte
+                let mut params = String::new();
+                loop {
+                    let c = self.read_char()?;
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break vt_key(&params, c);
+                    } else if params.len() >= 16 {
+                        break E(K::UnknownEscSeq, M::NONE);
+                    }
+                    params.push(c);
+                }
+            }
+            // SS3
+            'O' => {
+                let c = self.read_char()?;
+                vt_key("", c)
+            }
+            '\x1b' => E(K::Esc, M::ALT),
+            c => E::alt(c),
+        })
+    }
+}
+
+impl RawReader for HostReader {
+    type Buffer = HostBuffer;
+
+    fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<Event> {
+        loop {
+            if let Some(event) = self.composition.pop_front() {
+                return Ok(event);
+            } else if !self.chars.is_empty() {
+                return self.next_key(single_esc_abort).map(Event::KeyPress);
+            }
+            self.fill(None)?;
+        }
+    }
+
+    fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
+        Ok(match self.read_char()? {
+            '\x1b' => self.escape_sequence()?,
+            '\r' => E(K::Enter, M::NONE),
+            '\t' => E(K::Tab, M::NONE),
+            '\x08' | '\x7f' => E(K::Backspace, M::NONE),
+            c => KeyEvent::new(c, M::NONE),
+        })
+    }
+
+    fn poll_key(&mut self, timeout_ms: u16) -> Result<bool> {
+        Ok(
+            !self.chars.is_empty()
+                || !self.composition.is_empty()
+                || self.fill(Some(timeout_ms))?,
+        )
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        self.read_char()
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        let mut buffer = String::new();
+        loop {
+            match self.read_char()? {
+                '\x1b' => {
+                    if self.escape_sequence()? == E(K::BracketedPasteEnd, M::NONE) {
+                        break;
+                    }
+                }
+                c => buffer.push(c),
+            }
+        }
+        Ok(pasted_text(&buffer))
+    }
+
+    fn find_binding(&self, _: &KeyEvent) -> Option<Cmd> {
+        None
+    }
+
+    fn unbuffer(self) -> Option<HostBuffer> {
+        None
+    }
+}
+
+/// Host output writer
+pub struct HostRenderer {
+    host: SharedHost,
+    cols: Unit, // Number of columns in terminal
+    buffer: String,
+    text: String, // prompt, line and hint being displayed
+    tab_stop: Unit,
+    colors_enabled: bool,
+    grapheme_cluster_mode: GraphemeClusterMode,
+    ambiguous_width: AmbiguousWidth,
+    bell_style: BellStyle,
+    written: Cell<u64>,             // number of bytes written
+    batch: RefCell<Option<String>>, // output buffered until the end of a batch
+    frame: diff::Frame,             // rows displayed by the last refresh
+    widths: WidthCache,
+}
+
+impl HostRenderer {
+    fn new(
+        host: SharedHost,
+        tab_stop: Unit,
+        colors_enabled: bool,
+        grapheme_cluster_mode: GraphemeClusterMode,
+        bell_style: BellStyle,
+    ) -> Self {
+        let (cols, _) = size(&host);
+        Self {
+            host,
+            cols,
+            buffer: String::with_capacity(1024),
+            text: String::with_capacity(1024),
+            tab_stop,
+            colors_enabled,
+            grapheme_cluster_mode,
+            ambiguous_width: AmbiguousWidth::Narrow,
+            bell_style,
+            written: Cell::new(0),
+            batch: RefCell::new(None),
+            frame: diff::Frame::default(),
+            widths: WidthCache::default(),
+        }
+    }
+
+    fn write(&self, data: &str) -> Result<()> {
+        if let Some(ref mut batch) = *self.batch.borrow_mut() {
+            batch.push_str(data);
+        } else {
+            write_all(&self.host, data)?;
+        }
+        self.written.set(self.written.get() + data.len() as u64);
+        Ok(())
+    }
+
+    fn clear_old_rows(&mut self, layout: &Layout) {
+        use std::fmt::Write;
+        let current_row = layout.cursor.row;
+        let old_rows = layout.end.row;
+        // move the cursor down as required
+        let cursor_row_movement = old_rows.saturating_sub(current_row);
+        if cursor_row_movement > 0 {
+            write!(self.buffer, "\x1b[{cursor_row_movement}B").unwrap();
+        }
+        // clear old rows
+        for _ in 0..old_rows {
+            self.buffer.push_str("\r\x1b[K\x1b[A");
+        }
+        // clear the line
+        self.buffer.push_str("\r\x1b[K");
+    }
+}
+
+impl Renderer for HostRenderer {
+    type Reader = HostReader;
+
+    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        use std::fmt::Write;
+        self.buffer.clear();
+        if new.row > old.row {
+            write!(self.buffer, "\x1b[{}B", new.row - old.row)?;
+        } else if new.row < old.row {
+            write!(self.buffer, "\x1b[{}A", old.row - new.row)?;
+        }
+        if new.col > old.col {
+            write!(self.buffer, "\x1b[{}C", new.col - old.col)?;
+        } else if new.col < old.col {
+            write!(self.buffer, "\x1b[{}D", old.col - new.col)?;
+        }
+        self.write(&self.buffer)
+    }
+
+    fn refresh_line(
+        &mut self,
+        prompt: &str,
+        line: &LineBuffer,
+        hint: Option<&str>,
+        old_layout: &Layout,
+        new_layout: &Layout,
+        highlighter: Option<&dyn Highlighter>,
+    ) -> Result<()> {
+        use std::fmt::Write;
+        self.buffer.clear();
+
+        let default_prompt = new_layout.default_prompt;
+        let cursor = new_layout.cursor;
+        let end_pos = new_layout.end;
+
+        let text = &mut self.text;
+        text.clear();
+        if let Some(highlighter) = highlighter {
+            text.push_str(&highlighter.highlight_prompt(prompt, default_prompt));
+            text.push_str(&highlighter.highlight(line, line.pos()));
+        } else {
+            text.push_str(prompt);
+            text.push_str(line);
+        }
+        if let Some(hint) = hint {
+            if let Some(highlighter) = highlighter {
+                text.push_str(&highlighter.highlight_hint(hint));
+            } else {
+                text.push_str(hint);
+            }
+        }
+        self.frame.split(
+            &self.text,
+            self.cols,
+            self.tab_stop,
+            self.grapheme_cluster_mode,
+            self.ambiguous_width,
+        );
+        if diff::update(
+            &mut self.buffer,
+            &self.frame,
+            old_layout,
+            new_layout,
+            self.cols,
+            |buf, n, dir| write!(buf, "\x1b[{n}{dir}").unwrap(),
+        ) {
+            self.frame.rendered(new_layout, self.cols);
+            return self.write(&self.buffer);
+        }
+
+        self.clear_old_rows(old_layout);
+        self.buffer.push_str(&self.text);
+        // xterm.js defers wrapping like xterm: generate our own newline
+        if end_pos.col == 0
+            && end_pos.row > 0
+            && !hint.map_or_else(|| line.ends_with('\n'), |h| h.ends_with('\n'))
+        {
+            self.buffer.push('\n');
+        }
+        // position the cursor
+        let new_cursor_row_movement = end_pos.row - cursor.row;
+        if new_cursor_row_movement > 0 {
+            write!(self.buffer, "\x1b[{new_cursor_row_movement}A")?;
+        }
+        self.buffer.push('\r');
+        if cursor.col > 0 {
+            write!(self.buffer, "\x1b[{}C", cursor.col)?;
+        }
+        self.frame.rendered(new_layout, self.cols);
+        self.write(&self.buffer)
+    }
+
+    fn write_and_flush(&mut self, buf: &str) -> Result<()> {
+        self.frame.invalidate();
+        self.write(buf)
+    }
+
+    fn append(&mut self, text: &str, width: Unit) -> Result<()> {
+        self.frame.append(text, width);
+        self.write(text)
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.written.get()
+    }
+
+    fn begin_batch(&mut self) {
+        self.batch.get_mut().get_or_insert_with(String::new);
+    }
+
+    fn end_batch(&mut self) -> Result<()> {
+        match self.batch.get_mut().take() {
+            Some(data) if !data.is_empty() => write_all(&self.host, &data),
+            _ => Ok(()),
+        }
+    }
+
+    fn reconfigure(&mut self, colors_enabled: bool, bell_style: BellStyle) {
+        self.colors_enabled = colors_enabled;
+        self.bell_style = bell_style;
+    }
+
+    fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        self.widths.calculate_position(
+            s,
+            orig,
+            self.cols,
+            self.tab_stop,
+            self.grapheme_cluster_mode,
+            self.ambiguous_width,
+        )
+    }
+
+    fn beep(&mut self) -> Result<()> {
+        match self.bell_style {
+            BellStyle::Audible => self.write("\x07"),
+            _ => Ok(()),
+        }
+    }
+
+    fn clear_screen(&mut self) -> Result<()> {
+        self.frame.invalidate();
+        self.write("\x1b[H\x1b[J")
+    }
+
+    fn clear_rows(&mut self, layout: &Layout) -> Result<()> {
+        self.frame.invalidate();
+        self.buffer.clear();
+        self.clear_old_rows(layout);
+        self.write(&self.buffer)
+    }
+
+    fn update_size(&mut self) {
+        let (cols, _) = size(&self.host);
+        self.cols = cols;
+        self.frame.invalidate();
+        self.widths.clear();
+    }
+
+    fn get_columns(&self) -> Unit {
+        self.cols
+    }
+
+    fn get_rows(&self) -> Unit {
+        let (_, rows) = size(&self.host);
+        rows
+    }
+
+    fn colors_enabled(&self) -> bool {
+        self.colors_enabled
+    }
+
+    fn grapheme_cluster_mode(&self) -> GraphemeClusterMode {
+        self.grapheme_cluster_mode
+    }
+
+    fn ambiguous_width(&self) -> AmbiguousWidth {
+        self.ambiguous_width
+    }
+
+    fn move_cursor_at_leftmost(&mut self, _: &mut HostReader) -> Result<()> {
+        // the cursor position cannot be queried without blocking the host
+        self.frame.invalidate();
+        self.write("\r")
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        self.write(cursor_shape_seq(shape))
+    }
+
+    fn set_alternate_screen(&mut self, enabled: bool) -> Result<()> {
+        self.frame.invalidate();
+        self.write(alternate_screen_seq(enabled))
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        // needs xterm.js clipboard addon
+        self.write(&osc52_copy_seq(text))
+    }
+
+    fn paste_from_clipboard(&mut self, _: &mut HostReader) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn query_background(&mut self, _: &mut HostReader) -> Result<Option<Background>> {
+        Ok(None)
+    }
+}
+
+/// Print messages directly through the host
+pub struct HostPrinter {
+    host: SharedHost,
+}
+
+impl super::ExternalPrinter for HostPrinter {
+    fn print(&mut self, msg: String) -> Result<()> {
+        write_all(&self.host, &msg)
+    }
+}
+
+pub struct HostCursorGuard(SharedHost);
+
+impl Drop for HostCursorGuard {
+    fn drop(&mut self) {
+        let _ = write_all(&self.0, "\x1b[?25h");
+    }
+}
+
+#[cfg(not(test))]
+pub type Terminal = HostTerminal;
+
+#[derive(Clone)]
+pub struct HostTerminal {
+    host: Option<SharedHost>,
+    pub(crate) color_mode: ColorMode,
+    grapheme_cluster_mode: GraphemeClusterMode,
+    // narrow when `None`
+    pub(crate) ambiguous_width: Option<AmbiguousWidth>,
+    tab_stop: u8,
+    pub(crate) bell_style: BellStyle,
+    enable_bracketed_paste: bool,
+}
+
+impl HostTerminal {
+    /// Drive this terminal with `host` instead of the installed one
+    #[cfg(not(test))]
+    pub(crate) fn set_host<H: Host + 'static>(&mut self, host: H) {
+        self.host = Some(Arc::new(Mutex::new(Box::new(host))));
+    }
+
+    fn host(&self) -> Result<&SharedHost> {
+        match self.host {
+            Some(ref host) => Ok(host),
+            None => Err(io::Error::other("no host installed"))?,
+        }
+    }
+}
+
+impl fmt::Debug for HostTerminal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostTerminal")
+            .field("host", &self.host.is_some())
+            .field("color_mode", &self.color_mode)
+            .finish()
+    }
+}
+
+impl Term for HostTerminal {
+    type Buffer = HostBuffer;
+    type CursorGuard = HostCursorGuard;
+    type ExternalPrinter = HostPrinter;
+    type KeyMap = HostKeyMap;
+    type Mode = HostMode;
+    type Reader = HostReader;
+    type Writer = HostRenderer;
+
+    fn new(
+        color_mode: ColorMode,
+        grapheme_cluster_mode: GraphemeClusterMode,
+        _detect_grapheme_cluster_mode: bool,
+        _behavior: Behavior,
+        tab_stop: u8,
+        bell_style: BellStyle,
+        enable_bracketed_paste: bool,
+        _enable_synchronized_output: bool,
+        _enable_signals: bool,
+        _serial_baud_rate: Option<u32>,
+        _probe: bool,
+        _passthrough: Passthrough,
+        _enable_focus_events: bool,
+        _headless: Option<(u16, u16)>,
+    ) -> Result<Self> {
+        Ok(Self {
+            host: HOST.lock().unwrap().clone().or_else(default_host),
+            color_mode,
+            grapheme_cluster_mode,
+            ambiguous_width: None,
+            tab_stop,
+            bell_style,
+            enable_bracketed_paste,
+        })
+    }
+
+    fn is_unsupported(&self) -> bool {
+        self.host.is_none()
+    }
+
+    fn is_input_tty(&self) -> bool {
+        self.host.is_some()
+    }
+
+    fn is_output_tty(&self) -> bool {
+        self.host.is_some()
+    }
+
+    fn colors_enabled(&self) -> bool {
+        match self.color_mode {
+            ColorMode::Enabled | ColorMode::Forced => true,
+            ColorMode::Disabled => false,
+        }
+    }
+
+    fn enable_raw_mode(&mut self) -> Result<(HostMode, HostKeyMap)> {
+        let host = self.host()?.clone();
+        if self.enable_bracketed_paste {
+            write_all(&host, BRACKETED_PASTE_ON)?;
+        }
+        Ok((
+            HostMode {
+                host,
+                bracketed_paste: self.enable_bracketed_paste,
+            },
+            (),
+        ))
+    }
+
+    fn create_reader(&self, _: Option<HostBuffer>, config: &Config, _: HostKeyMap) -> HostReader {
+        let host = self.host.clone().expect("no host installed");
+        HostReader::new(host, config)
+    }
+
+    fn create_writer(&self) -> HostRenderer {
+        let host = self.host.clone().expect("no host installed");
+        let mut writer = HostRenderer::new(
+            host,
+            Unit::from(self.tab_stop),
+            self.colors_enabled(),
+            self.grapheme_cluster_mode,
+            self.bell_style,
+        );
+        writer.ambiguous_width = self.ambiguous_width.unwrap_or_default();
+        writer
+    }
+
+    fn writeln(&self) -> Result<()> {
+        write_all(self.host()?, "\n")
+    }
+
+    fn create_external_printer(&mut self, _: &Config) -> Result<HostPrinter> {
+        Ok(HostPrinter {
+            host: self.host()?.clone(),
+        })
+    }
+
+    fn set_cursor_visibility(&mut self, visible: bool) -> Result<Option<HostCursorGuard>> {
+        let host = self.host()?;
+        write_all(host, if visible { "\x1b[?25h" } else { "\x1b[?25l" })?;
+        Ok(if visible {
+            None
+        } else {
+            Some(HostCursorGuard(host.clone()))
+        })
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        write_all(self.host()?, &title_seq(title))
+    }
+
+    fn save_title(&mut self, save: bool) -> Result<()> {
+        write_all(self.host()?, title_stack_seq(save))
+    }
+}
+
+/// No job control
+#[cfg(all(unix, not(test)))]
+pub fn suspend() -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use std::collections::VecDeque;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        set_host, Event, Host, HostEvent, HostReader, HostRenderer, HostTerminal, SharedHost,
+        TestTerminal,
+    };
+    use crate::config::{Behavior, BellStyle, ColorMode, Config, Passthrough};
+    use crate::error::{ReadlineError, Signal};
+    use crate::highlight::Highlighter;
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+    use crate::layout::{GraphemeClusterMode, Layout, Position};
+    use crate::line_buffer::LineBuffer;
+    use crate::tty::transcript::Transcript;
+    use crate::tty::{ExternalPrinter, RawMode, RawReader, Renderer, Term};
+
+    struct Fake {
+        events: VecDeque<HostEvent>,
+        output: Arc<Mutex<String>>,
+    }
+
+    impl Host for Fake {
+        fn next_event(&mut self, _: Option<u16>) -> io::Result<Option<HostEvent>> {
+            match self.events.pop_front() {
+                Some(event) => Ok(Some(event)),
+                None => Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+
+        fn write(&mut self, data: &str) -> io::Result<()> {
+            self.output.lock().unwrap().push_str(data);
+            Ok(())
+        }
+
+        fn size(&self) -> (u16, u16) {
+            (80, 24)
+        }
+    }
+
+    fn host(events: &[&[u8]]) -> (SharedHost, Arc<Mutex<String>>) {
+        let output = Arc::new(Mutex::new(String::new()));
+        let fake = Fake {
+            events: events
+                .iter()
+                .map(|data| HostEvent::Data(data.to_vec()))
+                .collect(),
+            output: output.clone(),
+        };
+        (Arc::new(Mutex::new(Box::new(fake))), output)
+    }
+
+    #[test]
+    fn keys() {
+        let (host, _) = host(&[b"a\x1b[1;5C\r", b"\xc3", b"\xa9\x1b", b"OP\x7f"]);
+        let mut rdr = HostReader::new(host, &Config::default());
+        assert_eq!(E::from('a'), rdr.next_key(false).unwrap());
+        assert_eq!(E(K::Right, M::CTRL), rdr.next_key(false).unwrap());
+        assert_eq!(E(K::Enter, M::NONE), rdr.next_key(false).unwrap());
+        assert_eq!(E::from('é'), rdr.next_key(false).unwrap());
+        assert_eq!(E(K::F(1), M::NONE), rdr.next_key(false).unwrap());
+        assert_eq!(E(K::Backspace, M::NONE), rdr.next_key(false).unwrap());
+        assert!(matches!(rdr.next_key(false), Err(ReadlineError::Eof)));
+    }
+
+    #[test]
+    fn test_terminal() {
+        let keys = [
+            E::from('a'),
+            E::ctrl('A'),
+            E(K::Char('b'), M::ALT),
+            E::ESC,
+            E::from('c'),
+            E(K::Enter, M::NONE),
+            E(K::Enter, M::SHIFT),
+            E(K::Tab, M::NONE),
+            E(K::BackTab, M::NONE),
+            E(K::Backspace, M::NONE),
+            E(K::Left, M::NONE),
+            E(K::Right, M::CTRL),
+            E(K::Delete, M::NONE),
+            E(K::PageUp, M::ALT),
+            E(K::F(1), M::NONE),
+            E(K::F(5), M::SHIFT),
+            E(K::F(12), M::NONE),
+            E(K::Char('X'), M::CTRL_ALT),
+            E::from('é'),
+        ];
+        let term = TestTerminal::new(40, 10);
+        term.push_keys(keys);
+        term.push_str("d\n");
+        term.push_bytes(b"\x1b[1;2A");
+        term.resize(20, 5);
+        let host: SharedHost = Arc::new(Mutex::new(Box::new(term.clone())));
+        let config = Config::builder().keyseq_timeout(Some(10)).build();
+        let mut rdr = HostReader::new(host.clone(), &config);
+        for key in keys {
+            assert_eq!(key, rdr.next_key(false).unwrap());
+        }
+        assert_eq!(E::from('d'), rdr.next_key(false).unwrap());
+        assert_eq!(E::ctrl('J'), rdr.next_key(false).unwrap());
+        assert_eq!(E(K::Up, M::SHIFT), rdr.next_key(false).unwrap());
+        assert_eq!((40, 10), term.size());
+        assert!(matches!(
+            rdr.next_key(false),
+            Err(ReadlineError::Signal(Signal::Resize))
+        ));
+        assert_eq!((20, 5), term.size());
+        assert!(!rdr.poll_key(0).unwrap());
+        assert!(matches!(rdr.next_key(false), Err(ReadlineError::Eof)));
+
+        let mut out = HostRenderer::new(
+            host,
+            4,
+            false,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        );
+        out.write_and_flush("abc").unwrap();
+        assert_eq!("abc", term.take_output());
+        assert_eq!("", term.output());
+        let screen = term.screen();
+        assert_eq!("abc", screen.text());
+        assert_eq!((20, 5), (screen.cols(), screen.rows()));
+    }
+
+    #[test]
+    fn replay_timing() {
+        let transcript = Transcript::new(40, 10)
+            .data(0, "a")
+            .data(10, "\x1b")
+            .data(1000, "[D")
+            .data(0, "\x1b")
+            .data(5, "[D")
+            .resize(20, 20, 5);
+        let term = transcript.terminal();
+        let host: SharedHost = Arc::new(Mutex::new(Box::new(term.clone())));
+        let config = Config::builder().keyseq_timeout(Some(500)).build();
+        let mut rdr = HostReader::new(host, &config);
+        assert_eq!(E::from('a'), rdr.next_key(false).unwrap());
+        // nothing received in time
+        assert_eq!(E::ESC, rdr.next_key(false).unwrap());
+        assert_eq!(E::from('['), rdr.next_key(false).unwrap());
+        assert_eq!(E::from('D'), rdr.next_key(false).unwrap());
+        // received in time
+        assert_eq!(E(K::Left, M::NONE), rdr.next_key(false).unwrap());
+        assert!(matches!(
+            rdr.next_key(false),
+            Err(ReadlineError::Signal(Signal::Resize))
+        ));
+        assert_eq!((20, 5), term.size());
+    }
+
+    #[test]
+    fn paste_and_resize() {
+        let (host, _) = host(&[b"\x1b[200~a\r\nb\x1b[201~"]);
+        let mut rdr = HostReader::new(host.clone(), &Config::default());
+        assert_eq!(
+            E(K::BracketedPasteStart, M::NONE),
+            rdr.next_key(false).unwrap()
+        );
+        assert_eq!("a\nb", rdr.read_pasted_text().unwrap());
+
+        let fake = Fake {
+            events: VecDeque::from([HostEvent::Resize]),
+            output: Arc::default(),
+        };
+        *host.lock().unwrap() = Box::new(fake);
+        assert!(matches!(
+            rdr.next_key(false),
+            Err(ReadlineError::Signal(Signal::Resize))
+        ));
+    }
+
+    #[test]
+    fn composition() {
+        let term = TestTerminal::new(80, 24);
+        term.push_str("a");
+        term.push_preedit("k");
+        term.push_preedit("\u{304b}");
+        term.push_commit("\u{304b}");
+        term.push_str("b");
+        let host: SharedHost = Arc::new(Mutex::new(Box::new(term)));
+        let mut rdr = HostReader::new(host, &Config::default());
+        let mut events = vec![];
+        while let Ok(event) = rdr.wait_for_input(false) {
+            events.push(event);
+        }
+        assert_eq!(
+            vec![
+                Event::KeyPress(E::from('a')),
+                Event::Preedit("k".to_owned()),
+                Event::Preedit("\u{304b}".to_owned()),
+                Event::Commit("\u{304b}".to_owned()),
+                Event::KeyPress(E::from('b')),
+            ],
+            events
+        );
+    }
+
+    struct Digits;
+
+    impl Highlighter for Digits {
+        fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+            Cow::Owned(line.replace('1', "\x1b[1;31m1\x1b[0m"))
+        }
+
+        fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+            Cow::Owned(format!("\x1b[2m{hint}"))
+        }
+    }
+
+    #[test]
+    fn incremental_refresh() {
+        // prompt, line, cursor, hint
+        let states: [(&str, &str, usize, Option<&str>); 17] = [
+            ("> ", "", 0, None),
+            ("> ", "a", 1, None),
+            ("> ", "abc", 3, None),
+            ("> ", "abc", 1, None),
+            ("> ", "aXbc", 2, None),
+            ("> ", "abcdefghijkl", 12, None),
+            ("> ", "abcdefgh", 8, None),
+            ("> ", "abcdefg", 7, Some("hint")),
+            ("> ", "中文中文中", 15, None),
+            ("> ", "a\tb", 3, None),
+            ("> ", "1a1", 3, Some("1")),
+            ("> ", "11111111111", 4, None),
+            ("> ", "121", 1, Some("23")),
+            ("> ", "abcdefghijklmnopqrstuvwxyz012345", 32, None),
+            ("> ", "abcdefghijklmnopqrstuvwXyz01234", 24, Some("x")),
+            ("> ", "", 0, None),
+            ("$ ", "x", 1, None),
+        ];
+        for highlighter in [None, Some(&Digits as &dyn Highlighter)] {
+            let term = TestTerminal::new(10, 8);
+            let mut out = renderer(&term);
+            // full redraws only
+            let mut reference = renderer(&TestTerminal::new(10, 8));
+            let mut layout = Layout::default();
+            for (prompt, text, pos, hint) in states {
+                let line = LineBuffer::init(text, pos);
+                let prompt_size = out.calculate_position(prompt, Position::default());
+                let new_layout = out.compute_layout(prompt_size, true, &line, hint);
+                out.refresh_line(prompt, &line, hint, &layout, &new_layout, highlighter)
+                    .unwrap();
+                reference.frame.invalidate();
+                reference
+                    .refresh_line(prompt, &line, hint, &layout, &new_layout, highlighter)
+                    .unwrap();
+                layout = new_layout;
+                // same as a full redraw
+                let expected = TestTerminal::new(10, 8);
+                let mut full = renderer(&expected);
+                full.refresh_line(
+                    prompt,
+                    &line,
+                    hint,
+                    &Layout::default(),
+                    &layout,
+                    highlighter,
+                )
+                .unwrap();
+                let (expected, actual) = (expected.screen(), term.screen());
+                assert_eq!(expected.text(), actual.text());
+                assert_eq!(expected.cursor(), actual.cursor(), "{text:?}");
+                assert!(expected == actual, "{text:?}");
+            }
+            assert!(out.bytes_written() < reference.bytes_written());
+        }
+    }
+
+    #[test]
+    fn append() {
+        let term = TestTerminal::new(10, 4);
+        let mut out = renderer(&term);
+        let prompt_size = out.calculate_position("> ", Position::default());
+        let layout = |out: &HostRenderer, line: &LineBuffer| {
+            out.compute_layout(prompt_size, true, line, None)
+        };
+        let line = LineBuffer::init("ab", 2);
+        let old_layout = layout(&out, &line);
+        out.refresh_line("> ", &line, None, &Layout::default(), &old_layout, None)
+            .unwrap();
+        out.append("c", 1).unwrap();
+        let old_layout = layout(&out, &LineBuffer::init("abc", 3));
+        // the appended text is known: only the new character is written
+        let line = LineBuffer::init("abcd", 4);
+        let new_layout = layout(&out, &line);
+        let written = out.bytes_written();
+        out.refresh_line("> ", &line, None, &old_layout, &new_layout, None)
+            .unwrap();
+        assert_eq!(1, out.bytes_written() - written);
+        assert_eq!("> abcd", term.screen().line(0));
+        assert_eq!((0, 6), term.screen().cursor());
+    }
+
+    #[test]
+    fn batch() {
+        let (host, output) = host(&[]);
+        let mut out = HostRenderer::new(
+            host,
+            8,
+            true,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        );
+        let prompt_size = out.calculate_position("> ", Position::default());
+        let line = LineBuffer::init("ab", 2);
+        let layout = out.compute_layout(prompt_size, true, &line, None);
+        out.begin_batch();
+        out.clear_rows(&Layout::default()).unwrap();
+        out.write_and_flush("message\n").unwrap();
+        out.refresh_line("> ", &line, None, &Layout::default(), &layout, None)
+            .unwrap();
+        assert!(output.lock().unwrap().is_empty());
+        out.end_batch().unwrap();
+        let output = std::mem::take(&mut *output.lock().unwrap());
+        assert_eq!("\r\x1b[Kmessage\n\r\x1b[K> ab\r\x1b[4C", output);
+        assert_eq!(output.len() as u64, out.bytes_written());
+        out.end_batch().unwrap();
+        out.write_and_flush("x").unwrap();
+        assert_eq!(output.len() as u64 + 1, out.bytes_written());
+    }
+
+    fn renderer(term: &TestTerminal) -> HostRenderer {
+        let host: SharedHost = Arc::new(Mutex::new(Box::new(term.clone())));
+        HostRenderer::new(
+            host,
+            8,
+            true,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        )
+    }
+
+    #[test]
+    fn refresh_line() {
+        let (host, output) = host(&[]);
+        let mut out = HostRenderer::new(
+            host,
+            4,
+            false,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        );
+        let prompt = "> ";
+        let prompt_size = out.calculate_position(prompt, Position::default());
+        let line = LineBuffer::init("abc", 1);
+        let old_layout = Layout::default();
+        let new_layout = out.compute_layout(prompt_size, true, &line, None);
+        out.refresh_line(prompt, &line, None, &old_layout, &new_layout, None)
+            .unwrap();
+        assert_eq!("\r\u{1b}[K> abc\r\u{1b}[3C", *output.lock().unwrap());
+        assert_eq!(14, out.bytes_written());
+    }
+
+    #[test]
+    fn terminal() {
+        let output = Arc::new(Mutex::new(String::new()));
+        set_host(Fake {
+            events: VecDeque::from([HostEvent::Data(b"x".to_vec())]),
+            output: output.clone(),
+        });
+        let mut term = HostTerminal::new(
+            ColorMode::Enabled,
+            GraphemeClusterMode::default(),
+            false,
+            Behavior::default(),
+            4,
+            BellStyle::default(),
+            true,
+            false,
+            false,
+            None,
+            false,
+            Passthrough::default(),
+            false,
+            None,
+        )
+        .unwrap();
+        assert!(!term.is_unsupported());
+        let (mode, key_map) = term.enable_raw_mode().unwrap();
+        let mut rdr = term.create_reader(None, &Config::default(), key_map);
+        assert_eq!(E::from('x'), rdr.next_key(false).unwrap());
+        let mut out = term.create_writer();
+        assert_eq!((80, 24), (out.get_columns(), out.get_rows()));
+        out.beep().unwrap();
+        let guard = term.set_cursor_visibility(false).unwrap();
+        drop(guard);
+        term.create_external_printer(&Config::default())
+            .unwrap()
+            .print("msg\n".to_owned())
+            .unwrap();
+        mode.disable_raw_mode().unwrap();
+        term.writeln().unwrap();
+        assert_eq!(
+            "\x1b[?2004h\x07\x1b[?25l\x1b[?25hmsg\n\x1b[?2004l\n",
+            *output.lock().unwrap()
+        );
+    }
+}
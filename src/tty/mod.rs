@@ -1,13 +1,29 @@
 //! This module implements and describes common TTY methods & traits
+// helpers of the platform terminals, compiled out by the test terminal
+#![cfg_attr(feature = "test-terminal", allow(dead_code))]
 
 /// Unsupported Terminals that don't support RAW mode
-const UNSUPPORTED_TERM: [&str; 3] = ["dumb", "cons25", "emacs"];
+const UNSUPPORTED_TERM: [&str; 2] = ["cons25", "emacs"];
+/// Terminal without cursor movement
+const DUMB_TERM: &str = "dumb";
+/// Replaces the truncated part of a prompt
+const ELLIPSIS: &str = "\u{2026}";
 
-use crate::config::{Behavior, BellStyle, ColorMode, Config};
-use crate::highlight::Highlighter;
-use crate::keys::KeyEvent;
-use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use log::debug;
+
+use crate::config::{
+    Behavior, BellStyle, ColorMode, Config, CursorShape, Passthrough, QueuePolicy,
+};
+use crate::highlight::{Background, Highlighter};
+use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
+use crate::layout::{AmbiguousWidth, GraphemeClusterMode, Layout, Position, Unit};
 use crate::line_buffer::LineBuffer;
+use crate::logging::{MessageStyle, Severity};
 use crate::{Cmd, Result};
 
 /// Terminal state
@@ -17,9 +33,16 @@ pub trait RawMode: Sized {
 }
 
 /// Input event
+#[derive(Debug, PartialEq)]
 pub enum Event {
     KeyPress(KeyEvent),
     ExternalPrint(String),
+    ExternalEprint(String),
+    ExternalRegion(Vec<String>),
+    /// IME composition in progress (empty when cancelled)
+    Preedit(String),
+    /// Text committed by the IME, inserted at once
+    Commit(String),
     #[cfg(target_os = "macos")]
     Timeout(bool),
 }
@@ -31,6 +54,9 @@ pub trait RawReader {
     fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<Event>; // TODO replace calls to `next_key` by `wait_for_input` where relevant
     /// Blocking read of key pressed.
     fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyEvent>;
+    /// Wait at most `timeout_ms` for a key press.
+    /// Return `false` on timeout.
+    fn poll_key(&mut self, timeout_ms: u16) -> Result<bool>;
     /// For CTRL-V support
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char>;
@@ -84,10 +110,13 @@ pub trait Renderer {
 
         let new_layout = Layout {
             grapheme_cluster_mode: self.grapheme_cluster_mode(),
+            ambiguous_width: self.ambiguous_width(),
             prompt_size,
             default_prompt,
             cursor,
             end,
+            continuation_prompt_cols: Vec::new(),
+            first_prompt_col: None,
         };
         debug_assert!(new_layout.prompt_size <= new_layout.cursor);
         debug_assert!(new_layout.cursor <= new_layout.end);
@@ -99,6 +128,26 @@ pub trait Renderer {
     fn calculate_position(&self, s: &str, orig: Position) -> Position;
 
     fn write_and_flush(&mut self, buf: &str) -> Result<()>;
+    /// Write `text` (`width` columns, without line wrap) at the end of the
+    /// line just refreshed, where the cursor is
+    fn append(&mut self, text: &str, width: Unit) -> Result<()> {
+        let _ = width;
+        self.write_and_flush(text)
+    }
+    /// Number of bytes written to the terminal so far
+    fn bytes_written(&self) -> u64 {
+        0
+    }
+    /// Buffer the output until [`Renderer::end_batch`], so that a sequence
+    /// of updates is displayed at once
+    fn begin_batch(&mut self) {}
+    /// Apply the color mode or bell style changed while a line is edited
+    fn reconfigure(&mut self, _colors_enabled: bool, _bell_style: BellStyle) {}
+    /// Write the output buffered since [`Renderer::begin_batch`] with a
+    /// single write
+    fn end_batch(&mut self) -> Result<()> {
+        Ok(())
+    }
 
     /// Beep, used for completion when there is nothing to complete or when all
     /// the choices were already shown.
@@ -119,17 +168,279 @@ pub trait Renderer {
     fn colors_enabled(&self) -> bool;
     /// Tell how grapheme clusters are rendered.
     fn grapheme_cluster_mode(&self) -> GraphemeClusterMode;
+    /// Tell how East Asian ambiguous characters are rendered.
+    fn ambiguous_width(&self) -> AmbiguousWidth;
 
     /// Make sure prompt is at the leftmost edge of the screen
     fn move_cursor_at_leftmost(&mut self, rdr: &mut Self::Reader) -> Result<()>;
+
+    /// Change cursor shape
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()>;
+
+    /// Switch to (`enabled`) or from the alternate screen
+    fn set_alternate_screen(&mut self, enabled: bool) -> Result<()>;
+
+    /// Copy `text` to the system clipboard (OSC 52 on unix, Win32 clipboard
+    /// on windows)
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()>;
+    /// Request the content of the system clipboard.
+    /// Return `None` when the terminal doesn't answer.
+    fn paste_from_clipboard(&mut self, rdr: &mut Self::Reader) -> Result<Option<String>>;
+    /// Request the background color of the terminal (OSC 11 on unix).
+    /// Return `None` when the terminal doesn't answer.
+    fn query_background(&mut self, rdr: &mut Self::Reader) -> Result<Option<Background>>;
+}
+
+/// `DECSCUSR` sequence for `shape`
+fn cursor_shape_seq(shape: CursorShape) -> &'static str {
+    match shape {
+        CursorShape::Default => "\x1b[0 q",
+        CursorShape::BlinkingBlock => "\x1b[1 q",
+        CursorShape::SteadyBlock => "\x1b[2 q",
+        CursorShape::BlinkingUnderline => "\x1b[3 q",
+        CursorShape::SteadyUnderline => "\x1b[4 q",
+        CursorShape::BlinkingBar => "\x1b[5 q",
+        CursorShape::SteadyBar => "\x1b[6 q",
+    }
+}
+
+/// Sequence switching to or from the alternate screen
+fn alternate_screen_seq(enabled: bool) -> &'static str {
+    if enabled {
+        // save the cursor, switch and clear
+        "\x1b[?1049h\x1b[H\x1b[2J"
+    } else {
+        "\x1b[?1049l"
+    }
+}
+
+/// Maximum length of a `DCS` string forwarded by GNU screen
+const SCREEN_DCS_MAX: usize = 768;
+
+/// Resolve [`Passthrough::Auto`] from the environment
+#[cfg_attr(windows, expect(dead_code))]
+fn detect_passthrough(passthrough: Passthrough) -> Passthrough {
+    if passthrough != Passthrough::Auto {
+        return passthrough;
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    if std::env::var_os("TMUX").is_some() || term.starts_with("tmux") {
+        Passthrough::Tmux
+    } else if std::env::var_os("STY").is_some() || term.starts_with("screen") {
+        Passthrough::Screen
+    } else {
+        Passthrough::Disabled
+    }
+}
+
+/// Wrap `seq` so that the multiplexer forwards it to the outer terminal
+#[cfg_attr(all(windows, not(test)), expect(dead_code))]
+fn passthrough_seq(passthrough: Passthrough, seq: &str) -> Cow<'_, str> {
+    match passthrough {
+        // escape characters are doubled
+        Passthrough::Tmux => Cow::Owned(format!(
+            "\x1bPtmux;{}\x1b\\",
+            seq.replace('\x1b', "\x1b\x1b")
+        )),
+        // long sequences are split (sequences are ASCII)
+        Passthrough::Screen => Cow::Owned(
+            seq.as_bytes()
+                .chunks(SCREEN_DCS_MAX - 4)
+                .map(|chunk| format!("\x1bP{}\x1b\\", String::from_utf8_lossy(chunk)))
+                .collect(),
+        ),
+        _ => Cow::Borrowed(seq),
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// OSC 52 sequence copying `text` to the clipboard
+#[cfg_attr(all(windows, not(test)), expect(dead_code))]
+fn osc52_copy_seq(text: &str) -> String {
+    let mut seq = String::with_capacity(text.len() * 4 / 3 + 12);
+    seq.push_str("\x1b]52;c;");
+    for chunk in text.as_bytes().chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                seq.push(char::from(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                seq.push('=');
+            }
+        }
+    }
+    seq.push('\x07');
+    seq
+}
+
+/// Append to `out` what a dumb terminal can display (or a window title can
+/// contain): escape sequences and control characters other than `\n` and
+/// `\t` are dropped.
+pub(crate) fn printable(s: &str, out: &mut String) {
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters up to the final byte
+                Some('[') => {
+                    chars.by_ref().find(|c| ('@'..='~').contains(c));
+                }
+                // OSC: up to BEL or ST
+                Some(']') => {
+                    chars.by_ref().find(|&c| c == '\x07' || c == '\\');
+                }
+                _ => {}
+            },
+            '\n' | '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+}
+
+/// `OSC 2` sequence setting the window title (see [`printable`])
+#[cfg_attr(windows, expect(dead_code))]
+fn title_seq(title: &str) -> String {
+    format!("\x1b]2;{title}\x07")
+}
+
+/// Sequence saving the window title on the terminal title stack, or
+/// restoring the last one saved
+#[cfg_attr(windows, expect(dead_code))]
+fn title_stack_seq(save: bool) -> &'static str {
+    if save {
+        "\x1b[22;2t"
+    } else {
+        "\x1b[23;2t"
+    }
+}
+
+/// What was read after an OSC query
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(
+    all(any(windows, target_arch = "wasm32"), not(test)),
+    expect(dead_code)
+)]
+enum OscAnswer {
+    /// Answer, without its `ESC ] code ;` prefix and its terminator
+    Answer(String),
+    /// Input unrelated to the query (keys typed by the user), to be read
+    /// again
+    Input(String),
+    /// No answer, an incomplete one or one longer than expected
+    Missing,
 }
 
-// ignore ANSI escape sequence
-fn width(gcm: GraphemeClusterMode, s: &str, esc_seq: &mut u8) -> Unit {
+/// Read the answer (`ESC ] code ; answer` terminated by `BEL` or `ESC \`)
+/// to an OSC query, `next` returning the next character received within
+/// the timeout. Answers longer than `max_len` are read but discarded.
+#[cfg_attr(
+    all(any(windows, target_arch = "wasm32"), not(test)),
+    expect(dead_code)
+)]
+fn read_osc_answer(
+    code: &str,
+    max_len: usize,
+    mut next: impl FnMut() -> Result<Option<char>>,
+) -> Result<OscAnswer> {
+    let mut read = String::new();
+    for expected in format!("\x1b]{code};").chars() {
+        match next()? {
+            Some(c) => {
+                read.push(c);
+                if c != expected {
+                    return Ok(OscAnswer::Input(read));
+                }
+            }
+            None if read.is_empty() => return Ok(OscAnswer::Missing),
+            // e.g. a single escape
+            None => return Ok(OscAnswer::Input(read)),
+        }
+    }
+    let mut answer = String::new();
+    let mut too_long = false;
+    loop {
+        match next()? {
+            Some('\x07') => break,
+            Some('\x1b') if next()? == Some('\\') => break,
+            Some(c) if answer.len() < max_len => answer.push(c),
+            Some(_) => too_long = true,
+            None => return Ok(OscAnswer::Missing),
+        }
+    }
+    Ok(if too_long {
+        OscAnswer::Missing
+    } else {
+        OscAnswer::Answer(answer)
+    })
+}
+
+/// Decode the color (`rgb:RRRR/GGGG/BBBB`) of an OSC 11 answer
+#[cfg_attr(
+    all(any(windows, target_arch = "wasm32"), not(test)),
+    expect(dead_code)
+)]
+fn osc11_decode(color: &str) -> Option<Background> {
+    let mut channels = color.strip_prefix("rgb:")?.split('/').map(|c| {
+        // 1 to 4 hex digits, scaled to 8 bits
+        if c.is_empty() || c.len() > 4 || !c.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let max = (1u32 << (4 * c.len())) - 1;
+        let v = u32::from_str_radix(c, 16).ok()?;
+        u8::try_from(v * 255 / max).ok()
+    });
+    let r = channels.next()??;
+    let g = channels.next()??;
+    let b = channels.next()??;
+    Some(Background::from_rgb(r, g, b))
+}
+
+/// Maximal length of the base64 data of an OSC 52 answer (768 KiB once
+/// decoded)
+#[cfg_attr(
+    all(any(windows, target_arch = "wasm32"), not(test)),
+    expect(dead_code)
+)]
+const OSC52_MAX_LEN: usize = 1 << 20;
+
+/// Decode the base64 `data` of an OSC 52 answer, `None` when invalid or
+/// longer than [`OSC52_MAX_LEN`]
+#[cfg_attr(
+    all(any(windows, target_arch = "wasm32"), not(test)),
+    expect(dead_code)
+)]
+fn osc52_decode(data: &str) -> Option<String> {
+    if data.len() > OSC52_MAX_LEN {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for b in data.bytes().take_while(|b| *b != b'=') {
+        let v = BASE64.iter().position(|c| *c == b)?;
+        n = n << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits & 0xff) as u8);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// ignore ANSI escape sequence (and OSC strings like hyperlinks)
+fn width(gcm: GraphemeClusterMode, aw: AmbiguousWidth, s: &str, esc_seq: &mut u8) -> Unit {
     if *esc_seq == 1 {
         if s == "[" {
             // CSI
             *esc_seq = 2;
+        } else if s == "]" {
+            // OSC
+            *esc_seq = 3;
         } else {
             // two-character sequence
             *esc_seq = 0;
@@ -145,20 +456,278 @@ fn width(gcm: GraphemeClusterMode, s: &str, esc_seq: &mut u8) -> Unit {
             *esc_seq = 0;
         }
         0
+    } else if *esc_seq == 3 {
+        // terminated by BEL or ST (ESC \)
+        if s == "\x07" {
+            *esc_seq = 0;
+        } else if s == "\x1b" {
+            *esc_seq = 1;
+        }
+        0
     } else if s == "\x1b" {
         *esc_seq = 1;
         0
     } else if s == "\n" {
         0
     } else {
-        gcm.width(s)
+        gcm.width_with(s, aw)
+    }
+}
+
+/// Keep the end of `s` (a single row) within `max` columns, its start being
+/// replaced by an ellipsis. Escape sequences are kept.
+pub(crate) fn truncate_left(
+    gcm: GraphemeClusterMode,
+    aw: AmbiguousWidth,
+    s: &str,
+    max: Unit,
+) -> Cow<'_, str> {
+    use unicode_segmentation::UnicodeSegmentation;
+    let mut esc_seq = 0;
+    let graphemes: Vec<_> = s
+        .graphemes(true)
+        .map(|g| (g, width(gcm, aw, g, &mut esc_seq)))
+        .collect();
+    if graphemes.iter().map(|(_, w)| w).sum::<Unit>() <= max {
+        return Cow::Borrowed(s);
+    }
+    let mut budget = max.saturating_sub(gcm.width_with(ELLIPSIS, aw));
+    let mut start = graphemes.len();
+    while start > 0 && graphemes[start - 1].1 <= budget {
+        start -= 1;
+        budget -= graphemes[start].1;
+    }
+    let mut truncated = String::with_capacity(s.len());
+    truncated.push_str(ELLIPSIS);
+    // colors of the dropped part
+    for (g, _) in graphemes[..start].iter().filter(|(_, w)| *w == 0) {
+        truncated.push_str(g);
+    }
+    for (g, _) in &graphemes[start..] {
+        truncated.push_str(g);
     }
+    Cow::Owned(truncated)
+}
+
+/// Convert the line endings (`\r\n` or `\r`) of a bracketed paste to `\n`
+fn pasted_text(text: &str) -> String {
+    let mut pasted = String::with_capacity(text.len());
+    let mut lines = text.split('\r');
+    if let Some(first) = lines.next() {
+        pasted.push_str(first);
+    }
+    for line in lines {
+        pasted.push('\n');
+        pasted.push_str(line.strip_prefix('\n').unwrap_or(line));
+    }
+    pasted
+}
+
+/// Decode a CSI / SS3 sequence: `params` (`;` separated) and `final_byte`
+fn vt_key(params: &str, final_byte: char) -> KeyEvent {
+    let mut values = params.split(';').map(|p| p.parse::<u16>().ok());
+    let first = values.next().flatten().unwrap_or(1);
+    // xterm modifier parameter: 1 + (shift | alt << 1 | ctrl << 2)
+    let m = values.next().flatten().unwrap_or(1).saturating_sub(1);
+    let mut mods = M::NONE;
+    if m & 1 != 0 {
+        mods |= M::SHIFT;
+    }
+    if m & 2 != 0 {
+        mods |= M::ALT;
+    }
+    if m & 4 != 0 {
+        mods |= M::CTRL;
+    }
+    let code = match final_byte {
+        'A' => K::Up,
+        'B' => K::Down,
+        'C' => K::Right,
+        'D' => K::Left,
+        'H' => K::Home,
+        'F' => K::End,
+        'P' => K::F(1),
+        'Q' => K::F(2),
+        'R' => K::F(3),
+        'S' => K::F(4),
+        'Z' => K::BackTab,
+        'I' => K::FocusIn,
+        'O' => K::FocusOut,
+        '~' if first == 27 => {
+            // modifyOtherKeys: 27 ; modifiers ; code
+            let Some(c) = values
+                .next()
+                .flatten()
+                .and_then(|c| char::from_u32(c.into()))
+            else {
+                return E(K::UnknownEscSeq, M::NONE);
+            };
+            // same as the legacy encoding of ctrl + letter
+            let c = if mods.contains(M::CTRL) {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            };
+            return KeyEvent::new(c, mods);
+        }
+        '~' => match first {
+            1 | 7 => K::Home,
+            2 => K::Insert,
+            3 => K::Delete,
+            4 | 8 => K::End,
+            5 => K::PageUp,
+            6 => K::PageDown,
+            11..=15 => K::F((first - 10) as u8),
+            17..=21 => K::F((first - 11) as u8),
+            23 | 24 => K::F((first - 12) as u8),
+            200 => K::BracketedPasteStart,
+            201 => K::BracketedPasteEnd,
+            _ => K::UnknownEscSeq,
+        },
+        _ => K::UnknownEscSeq,
+    };
+    if code == K::UnknownEscSeq {
+        debug!(target: "rustyline", "unsupported esc sequence: \\E[{}{}", params, final_byte);
+    }
+    E(code, mods)
 }
 
 /// External printer
 pub trait ExternalPrinter {
     /// Print message to stdout
     fn print(&mut self, msg: String) -> Result<()>;
+    /// Print message to stderr (the prompt being redrawn below it on
+    /// stdout), so that diagnostics don't end up in piped output.
+    ///
+    /// By default, the message is printed to stdout.
+    fn eprint(&mut self, msg: String) -> Result<()> {
+        self.print(msg)
+    }
+    /// Print `msg` styled according to its `severity`.
+    ///
+    /// By default, with the default [`MessageStyle`] (see
+    /// [`StyledPrinter`](crate::logging::StyledPrinter) for another style).
+    fn print_styled(&mut self, severity: Severity, msg: &str) -> Result<()> {
+        self.print(MessageStyle::default().format(severity, msg))
+    }
+    /// Number of messages discarded because the queue was full (see
+    /// [`QueuePolicy`](crate::QueuePolicy)), for all printers created by the
+    /// same editor.
+    fn dropped_messages(&self) -> u64 {
+        0
+    }
+    /// Display `lines` above the prompt while a line is edited (like a
+    /// progress bar repainted in place while the user keeps typing), replacing
+    /// the previous ones. An empty `lines` frees the region.
+    ///
+    /// The region is hidden when the edition ends and displayed again on the
+    /// next [`Editor::readline`](crate::Editor::readline). Each line should
+    /// fit in the terminal width.
+    ///
+    /// By default, the region is not supported and `lines` are ignored.
+    fn set_region(&mut self, lines: Vec<String>) -> Result<()> {
+        let _ = lines;
+        Ok(())
+    }
+}
+
+/// Messages sent by external printers, waiting to be printed
+///
+/// Messages are only queued while a line is edited (the queue is active),
+/// otherwise they are printed directly.
+#[cfg_attr(target_arch = "wasm32", expect(dead_code))]
+#[derive(Debug)]
+pub(crate) struct PrintQueue {
+    state: Mutex<QueueState>,
+    not_full: Condvar,
+    capacity: usize,
+    policy: QueuePolicy,
+    dropped: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    events: VecDeque<Event>,
+    active: bool,
+    // last lines of the region above the prompt
+    region: Vec<String>,
+}
+
+#[cfg_attr(target_arch = "wasm32", expect(dead_code))]
+impl PrintQueue {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+            not_full: Condvar::new(),
+            capacity: config.external_print_capacity().max(1),
+            policy: config.external_print_policy(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue `event`, returns `Ok(false)` if it has been discarded or
+    /// `Err(event)` if the queue is not active (the event must be printed
+    /// directly).
+    pub fn push(&self, event: Event) -> std::result::Result<bool, Event> {
+        let mut state = self.state.lock().unwrap();
+        if let Event::ExternalRegion(ref lines) = event {
+            state.region.clone_from(lines);
+        }
+        while state.active && state.events.len() >= self.capacity {
+            match self.policy {
+                QueuePolicy::Block => state = self.not_full.wait(state).unwrap(),
+                QueuePolicy::DropOldest => {
+                    state.events.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                QueuePolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(false);
+                }
+            }
+        }
+        if !state.active {
+            return Err(event);
+        }
+        state.events.push_back(event);
+        Ok(true)
+    }
+
+    /// Queue the next events (when a line starts being edited), returns
+    /// `true` if the region above the prompt has been queued to be restored
+    pub fn activate(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.active = true;
+        if state.region.is_empty() {
+            return false;
+        }
+        let region = Event::ExternalRegion(state.region.clone());
+        state.events.push_front(region);
+        true
+    }
+
+    /// Stop queuing events (when the edition ends), returns the events not
+    /// printed yet
+    pub fn deactivate(&self) -> VecDeque<Event> {
+        let mut state = self.state.lock().unwrap();
+        state.active = false;
+        self.not_full.notify_all();
+        std::mem::take(&mut state.events)
+    }
+
+    /// Oldest queued event
+    pub fn pop(&self) -> Option<Event> {
+        let event = self.state.lock().unwrap().events.pop_front();
+        if event.is_some() {
+            self.not_full.notify_one();
+        }
+        event
+    }
+
+    /// Number of discarded events
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
 }
 
 /// Terminal contract
@@ -171,14 +740,22 @@ pub trait Term {
     type ExternalPrinter: ExternalPrinter;
     type CursorGuard;
 
+    #[expect(clippy::too_many_arguments)]
     fn new(
         color_mode: ColorMode,
         grapheme_cluster_mode: GraphemeClusterMode,
+        detect_grapheme_cluster_mode: bool,
         behavior: Behavior,
         tab_stop: u8,
         bell_style: BellStyle,
         enable_bracketed_paste: bool,
+        enable_synchronized_output: bool,
         enable_signals: bool,
+        serial_baud_rate: Option<u32>,
+        probe: bool,
+        passthrough: Passthrough,
+        enable_focus_events: bool,
+        headless: Option<(u16, u16)>,
     ) -> Result<Self>
     where
         Self: Sized;
@@ -189,6 +766,8 @@ pub trait Term {
     fn is_input_tty(&self) -> bool;
     /// check if output stream is connected to a terminal.
     fn is_output_tty(&self) -> bool;
+    /// Check if output supports colors (with the current color mode).
+    fn colors_enabled(&self) -> bool;
     /// Enable RAW mode for the terminal.
     fn enable_raw_mode(&mut self) -> Result<(Self::Mode, Self::KeyMap)>;
     /// Create a RAW reader
@@ -202,9 +781,13 @@ pub trait Term {
     fn create_writer(&self) -> Self::Writer;
     fn writeln(&self) -> Result<()>;
     /// Create an external printer
-    fn create_external_printer(&mut self) -> Result<Self::ExternalPrinter>;
+    fn create_external_printer(&mut self, config: &Config) -> Result<Self::ExternalPrinter>;
     /// Change cursor visibility
     fn set_cursor_visibility(&mut self, visible: bool) -> Result<Option<Self::CursorGuard>>;
+    /// Set the window title (OSC 2 on unix)
+    fn set_title(&mut self, title: &str) -> Result<()>;
+    /// Save (`true`) the window title or restore (`false`) the last one saved
+    fn save_title(&mut self, save: bool) -> Result<()>;
 }
 
 /// Check TERM environment variable to see if current term is in our
@@ -223,33 +806,294 @@ fn is_unsupported_term() -> bool {
     }
 }
 
+/// Check TERM environment variable to see if current term is a dumb terminal
+fn is_dumb_term() -> bool {
+    std::env::var("TERM").is_ok_and(|term| DUMB_TERM.eq_ignore_ascii_case(&term))
+}
+
 // If on Windows platform import Windows TTY module
 // and re-export into mod.rs scope
-#[cfg(all(windows, not(target_arch = "wasm32")))]
+#[cfg(all(windows, not(target_arch = "wasm32"), not(feature = "test-terminal")))]
 mod windows;
-#[cfg(all(windows, not(target_arch = "wasm32"), not(test)))]
+#[cfg(all(
+    windows,
+    not(target_arch = "wasm32"),
+    not(feature = "test-terminal"),
+    not(test)
+))]
 pub use self::windows::*;
 
 // If on Unix platform import Unix TTY module
 // and re-export into mod.rs scope
-#[cfg(all(unix, not(target_arch = "wasm32")))]
+#[cfg(all(unix, not(target_arch = "wasm32"), not(feature = "test-terminal")))]
 mod unix;
-#[cfg(all(unix, not(target_arch = "wasm32"), not(test)))]
+#[cfg(all(
+    unix,
+    not(target_arch = "wasm32"),
+    not(feature = "test-terminal"),
+    not(test)
+))]
 pub use self::unix::*;
+#[cfg(all(
+    feature = "fuzzing",
+    unix,
+    not(target_arch = "wasm32"),
+    not(feature = "test-terminal")
+))]
+pub use self::unix::{decode_keys, decode_paste};
+#[cfg(all(
+    unix,
+    not(target_arch = "wasm32"),
+    not(feature = "test-terminal"),
+    feature = "with-encoding"
+))]
+mod encoding;
+#[cfg(all(
+    unix,
+    not(target_arch = "wasm32"),
+    not(feature = "test-terminal"),
+    feature = "terminfo"
+))]
+mod terminfo;
+
+// incremental redraw of the terminals driven by escape sequences
+#[cfg(any(unix, test, target_arch = "wasm32", feature = "test-terminal"))]
+mod diff;
+// memoized layout computations
+mod width_cache;
 
-#[cfg(any(test, target_arch = "wasm32"))]
+// If on WebAssembly platform (or testing an application) import the
+// host-driven TTY module
+#[cfg(any(test, target_arch = "wasm32", feature = "test-terminal"))]
+mod host;
+#[cfg(all(any(target_arch = "wasm32", feature = "test-terminal"), not(test)))]
+pub use self::host::*;
+#[cfg(any(test, feature = "test-terminal"))]
+mod screen;
+#[cfg(test)]
+pub use self::screen::Screen;
+#[cfg(all(feature = "test-terminal", not(test)))]
+pub use self::screen::{Cell, Color, Screen, Style};
+#[cfg(any(test, feature = "test-terminal"))]
+mod transcript;
+#[cfg(all(feature = "test-terminal", not(test)))]
+pub use self::transcript::{Transcript, TranscriptEvent};
+
+#[cfg(test)]
 mod test;
-#[cfg(any(test, target_arch = "wasm32"))]
+#[cfg(test)]
 pub use self::test::*;
 
 #[cfg(test)]
 mod test_ {
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+
+    #[test]
+    fn vt_keys() {
+        assert_eq!(E(K::Up, M::NONE), super::vt_key("", 'A'));
+        assert_eq!(E(K::Right, M::CTRL), super::vt_key("1;5", 'C'));
+        assert_eq!(E(K::Home, M::ALT | M::SHIFT), super::vt_key("1;4", 'H'));
+        assert_eq!(E(K::Delete, M::NONE), super::vt_key("3", '~'));
+        assert_eq!(E(K::F(5), M::NONE), super::vt_key("15", '~'));
+        assert_eq!(E(K::F(12), M::CTRL), super::vt_key("24;5", '~'));
+        assert_eq!(E(K::F(1), M::NONE), super::vt_key("", 'P'));
+        assert_eq!(E(K::BackTab, M::NONE), super::vt_key("", 'Z'));
+        assert_eq!(E(K::FocusIn, M::NONE), super::vt_key("", 'I'));
+        assert_eq!(E(K::FocusOut, M::NONE), super::vt_key("", 'O'));
+        assert_eq!(
+            E(K::BracketedPasteStart, M::NONE),
+            super::vt_key("200", '~')
+        );
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), super::vt_key("42", '~'));
+        assert_eq!(E(K::Enter, M::SHIFT), super::vt_key("27;2;13", '~'));
+        assert_eq!(E(K::Tab, M::CTRL), super::vt_key("27;5;9", '~'));
+        assert_eq!(E(K::Char('A'), M::CTRL_ALT), super::vt_key("27;7;97", '~'));
+    }
+
+    #[test]
+    fn pasted_text() {
+        assert_eq!("", super::pasted_text(""));
+        assert_eq!("a\nb\nc\n", super::pasted_text("a\r\nb\rc\n"));
+        assert_eq!("\n\n\n", super::pasted_text("\r\r\n\n"));
+    }
+
+    #[test]
+    fn print_queue() {
+        use super::{Event, PrintQueue};
+        use crate::config::{Config, QueuePolicy};
+
+        let print = |msg: &str| Event::ExternalPrint(msg.to_owned());
+        let pop = |queue: &PrintQueue| match queue.pop() {
+            Some(Event::ExternalPrint(msg)) => Some(msg),
+            _ => None,
+        };
+        let config = Config::builder()
+            .external_print_queue(2, QueuePolicy::DropOldest)
+            .build();
+        let queue = PrintQueue::new(&config);
+        assert!(queue.push(print("1")).is_err());
+        queue.activate();
+        assert_eq!(Ok(true), queue.push(print("1")));
+        assert_eq!(Ok(true), queue.push(print("2")));
+        assert_eq!(Ok(true), queue.push(print("3")));
+        assert_eq!(1, queue.dropped());
+        assert_eq!(Some("2".to_owned()), pop(&queue));
+        assert_eq!(Some("3".to_owned()), pop(&queue));
+        assert_eq!(None, pop(&queue));
+
+        let config = Config::builder()
+            .external_print_queue(1, QueuePolicy::DropNewest)
+            .build();
+        let queue = PrintQueue::new(&config);
+        queue.activate();
+        assert_eq!(Ok(true), queue.push(print("1")));
+        assert_eq!(Ok(false), queue.push(print("2")));
+        assert_eq!(1, queue.dropped());
+        assert_eq!(1, queue.deactivate().len());
+        assert!(queue.push(print("3")).is_err());
+        assert_eq!(None, pop(&queue));
+
+        let region = vec!["50%".to_owned()];
+        assert!(queue.push(Event::ExternalRegion(region.clone())).is_err());
+        assert!(queue.activate());
+        assert_eq!(Some(Event::ExternalRegion(region)), queue.pop());
+    }
+
+    #[test]
+    fn passthrough() {
+        use super::{passthrough_seq, Passthrough};
+        assert_eq!(
+            "\x1b[2 q",
+            passthrough_seq(Passthrough::Disabled, "\x1b[2 q")
+        );
+        assert_eq!(
+            "\x1bPtmux;\x1b\x1b]11;?\x07\x1b\\",
+            passthrough_seq(Passthrough::Tmux, "\x1b]11;?\x07")
+        );
+        assert_eq!(
+            "\x1bP\x1b]11;?\x07\x1b\\",
+            passthrough_seq(Passthrough::Screen, "\x1b]11;?\x07")
+        );
+        let seq = "x".repeat(1000);
+        assert_eq!(
+            2,
+            passthrough_seq(Passthrough::Screen, &seq)
+                .matches("\x1bP")
+                .count()
+        );
+    }
+
+    #[test]
+    fn osc52() {
+        assert_eq!("\x1b]52;c;\x07", super::osc52_copy_seq(""));
+        assert_eq!("\x1b]52;c;Zm9v\x07", super::osc52_copy_seq("foo"));
+        assert_eq!("\x1b]52;c;Zm9vYg==\x07", super::osc52_copy_seq("foob"));
+        assert_eq!("\x1b]52;c;Zm9vYmE=\x07", super::osc52_copy_seq("fooba"));
+        assert_eq!(Some("fooba".to_owned()), super::osc52_decode("Zm9vYmE="));
+        assert_eq!(Some("foob".to_owned()), super::osc52_decode("Zm9vYg=="));
+        assert_eq!(None, super::osc52_decode("Zm9v!"));
+        let data = "Zm9v".repeat(super::OSC52_MAX_LEN / 4);
+        assert_eq!(
+            super::OSC52_MAX_LEN / 4 * 3,
+            super::osc52_decode(&data).unwrap().len()
+        );
+        assert_eq!(None, super::osc52_decode(&(data + "Zm9v")));
+    }
+
+    #[test]
+    fn osc_answer() {
+        use super::{read_osc_answer, OscAnswer};
+        let read = |input: &str, max_len| {
+            let mut chars = input.chars();
+            read_osc_answer("11", max_len, || Ok(chars.next())).unwrap()
+        };
+        assert_eq!(
+            OscAnswer::Answer("rgb:0/0/0".to_owned()),
+            read("\x1b]11;rgb:0/0/0\x07", 64)
+        );
+        assert_eq!(
+            OscAnswer::Answer("rgb:0/0/0".to_owned()),
+            read("\x1b]11;rgb:0/0/0\x1b\\", 64)
+        );
+        // no answer: typed keys (up to the first one not expected)
+        assert_eq!(OscAnswer::Input("l".to_owned()), read("ls -l", 64));
+        assert_eq!(OscAnswer::Input("\x1b".to_owned()), read("\x1b", 64));
+        assert_eq!(OscAnswer::Input("\x1b]2".to_owned()), read("\x1b]2;", 64));
+        assert_eq!(OscAnswer::Missing, read("", 64));
+        // truncated or too long
+        assert_eq!(OscAnswer::Missing, read("\x1b]11;rgb:0/0", 64));
+        assert_eq!(OscAnswer::Missing, read("\x1b]11;rgb:0/0/0\x07", 4));
+    }
+
+    #[test]
+    fn osc11() {
+        use crate::highlight::Background;
+        assert_eq!(
+            Some(Background::Dark),
+            super::osc11_decode("rgb:0000/0000/0000")
+        );
+        assert_eq!(
+            Some(Background::Light),
+            super::osc11_decode("rgb:ffff/ffff/ffff")
+        );
+        assert_eq!(Some(Background::Light), super::osc11_decode("rgb:fd/f6/e3"));
+        assert_eq!(Some(Background::Dark), super::osc11_decode("rgb:2/2/3"));
+        assert_eq!(None, super::osc11_decode("rgb:ffff/ffff"));
+        assert_eq!(None, super::osc11_decode("rgb:+fff/ffff/ffff"));
+        assert_eq!(None, super::osc11_decode("#ffffff"));
+    }
+
+    #[test]
+    fn printable() {
+        let mut title = String::new();
+        super::printable(
+            "\x1b[1;32m~/src\x1b[0m\x07 \x1b]2;evil\x07$\r\n",
+            &mut title,
+        );
+        assert_eq!("~/src $\n", title);
+    }
+
+    #[test]
+    fn truncate_left() {
+        use crate::layout::{AmbiguousWidth, GraphemeClusterMode};
+        let (gcm, aw) = (GraphemeClusterMode::Unicode, AmbiguousWidth::Narrow);
+        assert_eq!("~/src> ", super::truncate_left(gcm, aw, "~/src> ", 7));
+        assert_eq!("\u{2026}src> ", super::truncate_left(gcm, aw, "~/src> ", 6));
+        assert_eq!("\u{2026}> ", super::truncate_left(gcm, aw, "~/src> ", 3));
+        assert_eq!(
+            "\u{2026}\x1b[1;32mc>\x1b[0m ",
+            super::truncate_left(gcm, aw, "\x1b[1;32mabc>\x1b[0m ", 4)
+        );
+        // a wide character is dropped as a whole
+        assert_eq!("\u{2026}> ", super::truncate_left(gcm, aw, "\u{4f60}> ", 3));
+    }
+
+    #[test]
+    fn hyperlink_width() {
+        use crate::layout::{AmbiguousWidth, GraphemeClusterMode};
+        use unicode_segmentation::UnicodeSegmentation;
+        let (gcm, aw) = (GraphemeClusterMode::Unicode, AmbiguousWidth::Narrow);
+        let width = |s: &str| {
+            let mut esc_seq = 0;
+            s.graphemes(true)
+                .map(|g| super::width(gcm, aw, g, &mut esc_seq))
+                .sum::<u16>()
+        };
+        let link = crate::highlight::hyperlink("file:///tmp/foo.rs", "foo.rs");
+        assert_eq!(6, width(&link));
+        assert_eq!(3, width("\x1b]8;;https://example.com\x07abc\x1b]8;;\x07"));
+    }
+
     #[test]
     fn test_unsupported_term() {
         std::env::set_var("TERM", "xterm");
         assert!(!super::is_unsupported_term());
 
-        std::env::set_var("TERM", "dumb");
+        std::env::set_var("TERM", "cons25");
         assert!(super::is_unsupported_term());
+
+        std::env::set_var("TERM", "dumb");
+        assert!(!super::is_unsupported_term());
+        assert!(super::is_dumb_term());
     }
 }
@@ -0,0 +1,436 @@
+//! Logical character grid rebuilt from the text and escape sequences written
+//! to a [`TestTerminal`](super::TestTerminal)
+use std::fmt;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::layout::GraphemeClusterMode;
+
+/// Color of a [`Cell`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Color {
+    /// One of the 256 palette colors: 0-7 for the standard colors (`30`-`37`
+    /// SGR), 8-15 for the bright ones (`90`-`97`)
+    Indexed(u8),
+    /// True color
+    Rgb(u8, u8, u8),
+}
+
+/// Graphic rendition of a [`Cell`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Style {
+    /// Foreground color, `None` for the terminal default
+    pub fg: Option<Color>,
+    /// Background color, `None` for the terminal default
+    pub bg: Option<Color>,
+    /// Bold
+    pub bold: bool,
+    /// Dim / faint
+    pub dim: bool,
+    /// Italic
+    pub italic: bool,
+    /// Underline
+    pub underline: bool,
+    /// Reverse video
+    pub reverse: bool,
+}
+
+impl Style {
+    /// Whether this is the terminal default rendition
+    #[must_use]
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Select Graphic Rendition
+    fn apply(&mut self, params: &[u16]) {
+        let mut params = params.iter().copied();
+        while let Some(p) = params.next() {
+            match p {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.reverse = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.reverse = false,
+                30..=37 => self.fg = Some(Color::Indexed((p - 30) as u8)),
+                38 => self.fg = extended_color(&mut params),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Indexed((p - 40) as u8)),
+                48 => self.bg = extended_color(&mut params),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Indexed((p - 82) as u8)),
+                100..=107 => self.bg = Some(Color::Indexed((p - 92) as u8)),
+                _ => {}
+            }
+        }
+    }
+}
+
+// `5;n` or `2;r;g;b` after `38` / `48`
+fn extended_color<I: Iterator<Item = u16>>(params: &mut I) -> Option<Color> {
+    let mut next = || params.next().map(|p| p as u8);
+    match next()? {
+        5 => Some(Color::Indexed(next()?)),
+        2 => Some(Color::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// One column of a [`Screen`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cell {
+    symbol: String,
+    style: Style,
+}
+
+impl Cell {
+    /// Grapheme displayed in this cell: a space when blank, empty for the
+    /// second column of a wide grapheme
+    #[must_use]
+    pub fn symbol(&self) -> &str {
+        if self.is_continuation() {
+            ""
+        } else if self.symbol.is_empty() {
+            " "
+        } else {
+            &self.symbol
+        }
+    }
+
+    /// Rendition of this cell
+    #[must_use]
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    fn blank(style: Style) -> Self {
+        Self {
+            symbol: String::new(),
+            style,
+        }
+    }
+
+    fn continuation() -> Self {
+        Self {
+            symbol: "\0".to_owned(),
+            style: Style::default(),
+        }
+    }
+
+    fn is_continuation(&self) -> bool {
+        self.symbol == "\0"
+    }
+}
+
+/// What the user would see: `cols`×`rows` cells plus the cursor position,
+/// independently of the escape sequences used to get there.
+///
+/// Only the sequences emitted by the line editor are interpreted (cursor
+/// movements, erase, SGR); the others are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screen {
+    cols: usize,
+    rows: usize,
+    grid: Vec<Vec<Cell>>,
+    cursor: (usize, usize), // row, col
+    wrap_pending: bool,     // last column has been written
+    style: Style,
+    parser: Parser,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum Parser {
+    #[default]
+    Ground,
+    Escape,
+    Csi(String),
+    Osc,
+    OscEscape,
+}
+
+impl Screen {
+    /// Blank screen with the cursor at the top left corner
+    #[must_use]
+    pub fn new(cols: u16, rows: u16) -> Self {
+        let (cols, rows) = (usize::from(cols.max(1)), usize::from(rows.max(1)));
+        Self {
+            cols,
+            rows,
+            grid: vec![vec![Cell::default(); cols]; rows],
+            cursor: (0, 0),
+            wrap_pending: false,
+            style: Style::default(),
+            parser: Parser::Ground,
+        }
+    }
+
+    /// Number of columns
+    #[must_use]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Number of rows
+    #[must_use]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Cursor position: row, column
+    #[must_use]
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    /// Cell at `row`, `col`
+    ///
+    /// # Panics
+    ///
+    /// Panics when out of the screen.
+    #[must_use]
+    pub fn cell(&self, row: usize, col: usize) -> &Cell {
+        &self.grid[row][col]
+    }
+
+    /// Text of `row` without trailing blanks
+    #[must_use]
+    pub fn line(&self, row: usize) -> String {
+        let line: String = self.grid[row]
+            .iter()
+            .filter(|c| !c.is_continuation())
+            .map(Cell::symbol)
+            .collect();
+        line.trim_end().to_owned()
+    }
+
+    /// Text of all rows without trailing blanks nor trailing blank rows
+    #[must_use]
+    pub fn text(&self) -> String {
+        let lines: Vec<String> = (0..self.rows).map(|r| self.line(r)).collect();
+        let end = lines
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(0, |r| r + 1);
+        lines[..end].join("\n")
+    }
+
+    /// Change the size, keeping the top left content
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        let (cols, rows) = (usize::from(cols.max(1)), usize::from(rows.max(1)));
+        for line in &mut self.grid {
+            line.resize(cols, Cell::default());
+            let last = &mut line[cols - 1];
+            if GraphemeClusterMode::Unicode.width(&last.symbol) > 1 {
+                *last = Cell::default(); // its second half has been cut
+            }
+        }
+        self.grid.resize(rows, vec![Cell::default(); cols]);
+        self.cols = cols;
+        self.rows = rows;
+        self.cursor = (self.cursor.0.min(rows - 1), self.cursor.1.min(cols - 1));
+        self.wrap_pending = false;
+    }
+
+    /// Interpret `data` (text and escape sequences)
+    pub fn feed(&mut self, data: &str) {
+        let mut text = String::new();
+        for c in data.chars() {
+            match std::mem::take(&mut self.parser) {
+                Parser::Ground if c.is_control() => {
+                    self.print(&text);
+                    text.clear();
+                    if c == '\x1b' {
+                        self.parser = Parser::Escape;
+                    } else {
+                        self.control(c);
+                    }
+                }
+                Parser::Ground => text.push(c),
+                Parser::Escape => match c {
+                    '[' => self.parser = Parser::Csi(String::new()),
+                    ']' => self.parser = Parser::Osc,
+                    _ => {}
+                },
+                Parser::Csi(mut seq) => {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        self.csi(&seq, c);
+                    } else {
+                        seq.push(c);
+                        self.parser = Parser::Csi(seq);
+                    }
+                }
+                Parser::Osc => match c {
+                    '\x07' => {}
+                    '\x1b' => self.parser = Parser::OscEscape,
+                    _ => self.parser = Parser::Osc,
+                },
+                Parser::OscEscape => {}
+            }
+        }
+        self.print(&text);
+    }
+
+    fn print(&mut self, text: &str) {
+        for g in text.graphemes(true) {
+            let width = usize::from(GraphemeClusterMode::Unicode.width(g));
+            if width == 0 {
+                // combining mark written separately: join the previous cell
+                let (row, col) = self.cursor;
+                let col = if self.wrap_pending {
+                    col
+                } else {
+                    col.saturating_sub(1)
+                };
+                self.grid[row][col].symbol.push_str(g);
+                continue;
+            }
+            if self.wrap_pending || self.cursor.1 + width > self.cols {
+                self.cursor.1 = 0;
+                self.line_feed();
+            }
+            let (row, col) = self.cursor;
+            self.grid[row][col] = Cell {
+                symbol: g.to_owned(),
+                style: self.style,
+            };
+            if width > 1 && col + 1 < self.cols {
+                self.grid[row][col + 1] = Cell::continuation();
+            }
+            if col + width >= self.cols {
+                self.cursor.1 = self.cols - 1;
+                self.wrap_pending = true;
+            } else {
+                self.cursor.1 = col + width;
+            }
+        }
+    }
+
+    fn control(&mut self, c: char) {
+        match c {
+            '\r' => self.cursor.1 = 0,
+            '\n' => self.line_feed(),
+            '\x08' => self.cursor.1 = self.cursor.1.saturating_sub(1),
+            '\t' => self.cursor.1 = ((self.cursor.1 / 8 + 1) * 8).min(self.cols - 1),
+            _ => return, // BEL, ...
+        }
+        self.wrap_pending = false;
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.0 + 1 < self.rows {
+            self.cursor.0 += 1;
+        } else {
+            self.grid.remove(0);
+            self.grid.push(vec![Cell::default(); self.cols]);
+        }
+        self.wrap_pending = false;
+    }
+
+    fn csi(&mut self, seq: &str, action: char) {
+        if seq.starts_with(['?', '>', '<', '=']) {
+            return; // private modes
+        }
+        let params: Vec<u16> = seq.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+        let n = usize::from(params[0].max(1));
+        let (row, col) = self.cursor;
+        match action {
+            'A' => self.cursor.0 = row.saturating_sub(n),
+            'B' => self.cursor.0 = (row + n).min(self.rows - 1),
+            'C' => self.cursor.1 = (col + n).min(self.cols - 1),
+            'D' => self.cursor.1 = col.saturating_sub(n),
+            'G' => self.cursor.1 = (n - 1).min(self.cols - 1),
+            'H' | 'f' => {
+                let c = params.get(1).map_or(1, |&c| usize::from(c.max(1)));
+                self.cursor = ((n - 1).min(self.rows - 1), (c - 1).min(self.cols - 1));
+            }
+            'J' => {
+                let rows = match params[0] {
+                    0 => {
+                        self.erase(row, col..self.cols);
+                        row + 1..self.rows
+                    }
+                    1 => {
+                        self.erase(row, 0..col + 1);
+                        0..row
+                    }
+                    _ => 0..self.rows,
+                };
+                for r in rows {
+                    self.erase(r, 0..self.cols);
+                }
+            }
+            'K' => match params[0] {
+                0 => self.erase(row, col..self.cols),
+                1 => self.erase(row, 0..col + 1),
+                _ => self.erase(row, 0..self.cols),
+            },
+            'm' => {
+                self.style.apply(&params);
+                return;
+            }
+            _ => return,
+        }
+        self.wrap_pending = false;
+    }
+
+    fn erase(&mut self, row: usize, cols: std::ops::Range<usize>) {
+        let blank = Cell::blank(Style {
+            bg: self.style.bg,
+            ..Style::default()
+        });
+        for cell in &mut self.grid[row][cols] {
+            *cell = blank.clone();
+        }
+    }
+}
+
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Color, Screen};
+
+    #[test]
+    fn wrap_and_erase() {
+        let mut screen = Screen::new(5, 3);
+        screen.feed("> abcdef\x1b[1A\r\x1b[2C\x1b[K");
+        assert_eq!(">\ndef", screen.text());
+        assert_eq!((0, 2), screen.cursor());
+        screen.feed("\r\n\n\nx");
+        assert_eq!("def\n\nx", screen.text());
+        screen.feed("\x1b[H\x1b[J");
+        assert_eq!("", screen.text());
+        assert_eq!((0, 0), screen.cursor());
+    }
+
+    #[test]
+    fn wide_and_styles() {
+        let mut screen = Screen::new(4, 2);
+        screen.feed("a\x1b[1;31m中\x1b[0m\x1b]0;title\x07e\u{301}b");
+        assert_eq!("a中e\u{301}\nb", screen.text());
+        assert_eq!("", screen.cell(0, 2).symbol());
+        let style = screen.cell(0, 1).style();
+        assert!(style.bold);
+        assert_eq!(Some(Color::Indexed(1)), style.fg);
+        assert!(screen.cell(0, 3).style().is_default());
+        screen.feed("\x1b[38;2;1;2;3m\x1b[4Gz");
+        assert_eq!(Some(Color::Rgb(1, 2, 3)), screen.cell(1, 3).style().fg);
+        screen.resize(2, 3);
+        assert_eq!((2, 3), (screen.cols(), screen.rows()));
+        assert_eq!("a\nb", screen.text());
+    }
+}
@@ -0,0 +1,286 @@
+//! Compiled terminfo entries (see `term(5)`)
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Boolean capability: terminal has automatic margins
+pub const AUTO_RIGHT_MARGIN: usize = 1;
+/// Boolean capability: newline ignored after 80 cols
+pub const EAT_NEWLINE_GLITCH: usize = 4;
+
+/// Numeric capability: maximum number of colors on screen
+pub const MAX_COLORS: usize = 13;
+
+/// String capability: audible signal
+pub const BELL: usize = 1;
+/// String capability: clear screen and home cursor
+pub const CLEAR_SCREEN: usize = 5;
+/// String capability: clear to end of line
+pub const CLR_EOL: usize = 6;
+/// String capability: up one line
+pub const CURSOR_UP: usize = 19;
+/// String capability: move down `%p1` lines
+pub const PARM_DOWN_CURSOR: usize = 107;
+/// String capability: move left `%p1` columns
+pub const PARM_LEFT_CURSOR: usize = 111;
+/// String capability: move right `%p1` columns
+pub const PARM_RIGHT_CURSOR: usize = 112;
+/// String capability: move up `%p1` lines
+pub const PARM_UP_CURSOR: usize = 114;
+
+/// Legacy storage format (16-bit numbers)
+const MAGIC: i16 = 0o432;
+/// Extended storage format (32-bit numbers)
+const MAGIC_32BIT: i16 = 0o1036;
+
+/// Capabilities of a terminal type
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TermInfo {
+    booleans: Vec<bool>,
+    numbers: Vec<Option<i32>>,
+    strings: Vec<Option<String>>,
+}
+
+impl TermInfo {
+    /// Load the entry matching the `TERM` environment variable
+    pub fn from_env() -> Option<Self> {
+        let term = env::var("TERM").ok()?;
+        Self::load(&term)
+    }
+
+    /// Search the terminfo database for `term`
+    pub fn load(term: &str) -> Option<Self> {
+        let first = term.chars().next()?;
+        if term.contains('/') {
+            return None;
+        }
+        search_dirs().into_iter().find_map(|dir| {
+            // usual layout is `x/xterm` but macOS uses `78/xterm`
+            [first.to_string(), format!("{:x}", u32::from(first))]
+                .iter()
+                .find_map(|sub| fs::read(dir.join(sub).join(term)).ok())
+                .and_then(|data| Self::parse(&data))
+        })
+    }
+
+    /// Parse a compiled entry
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let word = |i: usize| data.get(i..i + 2).map(|b| i16::from_le_bytes([b[0], b[1]]));
+        let count = |i: usize| word(i).and_then(|n| usize::try_from(n).ok());
+        let num_size = match word(0)? {
+            MAGIC => 2,
+            MAGIC_32BIT => 4,
+            _ => return None,
+        };
+        let names_size = count(2)?;
+        let bool_count = count(4)?;
+        let num_count = count(6)?;
+        let str_count = count(8)?;
+        let table_size = count(10)?;
+
+        let mut offset = 12 + names_size;
+        let booleans = data
+            .get(offset..offset + bool_count)?
+            .iter()
+            .map(|&b| b == 1)
+            .collect();
+        offset += bool_count;
+        // numbers are aligned on an even byte boundary
+        offset += offset % 2;
+        let numbers = data
+            .get(offset..offset + num_count * num_size)?
+            .chunks_exact(num_size)
+            .map(|b| {
+                let n = if num_size == 2 {
+                    i32::from(i16::from_le_bytes([b[0], b[1]]))
+                } else {
+                    i32::from_le_bytes([b[0], b[1], b[2], b[3]])
+                };
+                (n >= 0).then_some(n)
+            })
+            .collect();
+        offset += num_count * num_size;
+        let offsets = data.get(offset..offset + str_count * 2)?;
+        offset += str_count * 2;
+        let table = data.get(offset..offset + table_size)?;
+        let strings = offsets
+            .chunks_exact(2)
+            .map(|b| {
+                // -1: absent, -2: cancelled
+                let start = usize::try_from(i16::from_le_bytes([b[0], b[1]])).ok()?;
+                let s = table.get(start..)?;
+                let end = s.iter().position(|&b| b == 0)?;
+                String::from_utf8(s[..end].to_vec()).ok()
+            })
+            .collect();
+        Some(Self {
+            booleans,
+            numbers,
+            strings,
+        })
+    }
+
+    /// Boolean capability at index `cap`
+    #[must_use]
+    pub fn flag(&self, cap: usize) -> bool {
+        self.booleans.get(cap).copied().unwrap_or(false)
+    }
+
+    /// Numeric capability at index `cap`
+    #[must_use]
+    pub fn number(&self, cap: usize) -> Option<i32> {
+        self.numbers.get(cap).copied().flatten()
+    }
+
+    /// String capability at index `cap`
+    #[must_use]
+    pub fn string(&self, cap: usize) -> Option<&str> {
+        self.strings.get(cap)?.as_deref()
+    }
+
+    /// String capability at index `cap` instantiated with `params`
+    #[must_use]
+    pub fn expand(&self, cap: usize, params: &[i32]) -> Option<String> {
+        tparm(self.string(cap)?, params)
+    }
+}
+
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    let defaults = [
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ];
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        for dir in list.split(':') {
+            if dir.is_empty() {
+                // an empty entry stands for the default locations
+                dirs.extend(defaults.iter().map(PathBuf::from));
+            } else {
+                dirs.push(PathBuf::from(dir));
+            }
+        }
+    }
+    dirs.extend(defaults.iter().map(PathBuf::from));
+    dirs
+}
+
+/// Instantiate a parameterized string.
+///
+/// Only the operators needed for cursor movement are supported (`%%`,
+/// `%pN`, `%d`, `%i`); `None` is returned for anything else so that the
+/// caller can fall back to its own sequence.
+fn tparm(cap: &str, params: &[i32]) -> Option<String> {
+    let mut params = params.to_vec();
+    let mut stack = vec![];
+    let mut out = String::with_capacity(cap.len());
+    let mut chars = cap.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => match chars.next()? {
+                '%' => out.push('%'),
+                'i' => params.iter_mut().take(2).for_each(|p| *p += 1),
+                'p' => {
+                    let i = chars.next()?.to_digit(10)?.checked_sub(1)?;
+                    stack.push(params.get(i as usize).copied().unwrap_or(0));
+                }
+                'd' => out.push_str(&stack.pop()?.to_string()),
+                _ => return None,
+            },
+            // padding: $<delay>
+            '$' if chars.peek() == Some(&'<') => {
+                chars.by_ref().find(|&c| c == '>')?;
+            }
+            _ => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tparm, TermInfo};
+
+    /// Build a legacy compiled entry
+    fn compile(booleans: &[bool], numbers: &[i16], strings: &[Option<&str>]) -> Vec<u8> {
+        let names = b"test|synthetic terminal\0";
+        let mut table = vec![];
+        let mut offsets = vec![];
+        for s in strings {
+            let offset = match s {
+                Some(s) => {
+                    let offset = table.len() as i16;
+                    table.extend_from_slice(s.as_bytes());
+                    table.push(0);
+                    offset
+                }
+                None => -1,
+            };
+            offsets.extend_from_slice(&offset.to_le_bytes());
+        }
+        let mut data = vec![];
+        for n in [
+            0o432,
+            names.len() as i16,
+            booleans.len() as i16,
+            numbers.len() as i16,
+            strings.len() as i16,
+            table.len() as i16,
+        ] {
+            data.extend_from_slice(&n.to_le_bytes());
+        }
+        data.extend_from_slice(names);
+        data.extend(booleans.iter().map(|&b| u8::from(b)));
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        for n in numbers {
+            data.extend_from_slice(&n.to_le_bytes());
+        }
+        data.extend_from_slice(&offsets);
+        data.extend_from_slice(&table);
+        data
+    }
+
+    #[test]
+    fn parse() {
+        let data = compile(
+            &[false, true, false],
+            &[80, -1],
+            &[None, Some("\x07"), None, Some("\x1b[%p1%dA")],
+        );
+        let info = TermInfo::parse(&data).unwrap();
+        assert!(info.flag(1));
+        assert!(!info.flag(2));
+        assert!(!info.flag(4));
+        assert_eq!(Some(80), info.number(0));
+        assert_eq!(None, info.number(1));
+        assert_eq!(None, info.string(0));
+        assert_eq!(Some("\x07"), info.string(1));
+        assert_eq!(Some("\x1b[3A".to_owned()), info.expand(3, &[3]));
+        assert_eq!(None, info.string(5));
+
+        assert_eq!(None, TermInfo::parse(&data[..20]));
+        assert_eq!(None, TermInfo::parse(b"not terminfo"));
+    }
+
+    #[test]
+    fn expand() {
+        assert_eq!(Some("\x1b[5C".to_owned()), tparm("\x1b[%p1%dC", &[5]));
+        assert_eq!(
+            Some("\x1b[3;8H".to_owned()),
+            tparm("\x1b[%i%p1%d;%p2%dH", &[2, 7])
+        );
+        assert_eq!(Some("\x1b[J".to_owned()), tparm("\x1b[J$<50>", &[]));
+        assert_eq!(Some("100%".to_owned()), tparm("100%%", &[]));
+        assert_eq!(None, tparm("%?%p1%t;%;", &[1]));
+    }
+}
@@ -1,13 +1,17 @@
 //! Tests specific definitions
 use std::slice::Iter;
+use std::sync::{Arc, Mutex};
 use std::vec::IntoIter;
 
-use super::{Event, ExternalPrinter, RawMode, RawReader, Renderer, Term};
-use crate::config::{Behavior, BellStyle, ColorMode, Config};
-use crate::error::ReadlineError;
-use crate::highlight::Highlighter;
-use crate::keys::KeyEvent;
-use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use super::{
+    alternate_screen_seq, cursor_shape_seq, Event, ExternalPrinter, RawMode, RawReader, Renderer,
+    Term,
+};
+use crate::config::{Behavior, BellStyle, ColorMode, Config, CursorShape, Passthrough};
+use crate::error::{ReadlineError, Signal};
+use crate::highlight::{Background, Highlighter};
+use crate::keys::{KeyCode, KeyEvent, Modifiers};
+use crate::layout::{AmbiguousWidth, GraphemeClusterMode, Layout, Position, Unit};
 use crate::line_buffer::LineBuffer;
 use crate::{Cmd, Result};
 
@@ -21,6 +25,12 @@ impl RawMode for Mode {
     }
 }
 
+/// Pause in the queued keys: no key is typed within the timeout of the next
+/// `poll_key` (which consumes it, unless the timeout is zero)
+pub const PAUSE: KeyEvent = KeyEvent(KeyCode::Null, Modifiers::all());
+/// Resize of the terminal in the queued keys (to [`Output::resized`])
+pub const RESIZE: KeyEvent = KeyEvent(KeyCode::Null, Modifiers::SHIFT);
+
 impl RawReader for Iter<'_, KeyEvent> {
     type Buffer = Buffer;
 
@@ -29,12 +39,23 @@ impl RawReader for Iter<'_, KeyEvent> {
     }
 
     fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
-        match self.next() {
+        match self.find(|key| **key != PAUSE) {
+            Some(&RESIZE) => Err(ReadlineError::Signal(Signal::Resize)),
             Some(key) => Ok(*key),
             None => Err(ReadlineError::Eof),
         }
     }
 
+    fn poll_key(&mut self, timeout_ms: u16) -> Result<bool> {
+        if self.as_slice().first() == Some(&PAUSE) {
+            if timeout_ms > 0 {
+                self.next();
+            }
+            return Ok(false);
+        }
+        Ok(self.len() > 0)
+    }
+
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char> {
         unimplemented!();
@@ -61,12 +82,23 @@ impl RawReader for IntoIter<KeyEvent> {
     }
 
     fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
-        match self.next() {
+        match self.find(|key| *key != PAUSE) {
+            Some(RESIZE) => Err(ReadlineError::Signal(Signal::Resize)),
             Some(key) => Ok(key),
             None => Err(ReadlineError::Eof),
         }
     }
 
+    fn poll_key(&mut self, timeout_ms: u16) -> Result<bool> {
+        if self.as_slice().first() == Some(&PAUSE) {
+            if timeout_ms > 0 {
+                self.next();
+            }
+            return Ok(false);
+        }
+        Ok(self.len() > 0)
+    }
+
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char> {
         use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
@@ -90,8 +122,41 @@ impl RawReader for IntoIter<KeyEvent> {
     }
 }
 
-#[derive(Default)]
-pub struct Sink {}
+/// What the [`Sink`]s of a [`DummyTerminal`] have rendered
+#[derive(Debug, Default)]
+pub struct Output {
+    /// Texts written, sequences changing the cursor shape or the screen, and
+    /// refreshed lines (as `{prompt}{line}`)
+    pub rendered: String,
+    /// Size reported once a [`RESIZE`] key is read (80x24 otherwise)
+    pub resized: Option<(Unit, Unit)>,
+}
+
+pub struct Sink {
+    pub written: String, // text written with `write_and_flush`
+    output: Arc<Mutex<Output>>,
+    size: (Unit, Unit),
+}
+
+impl Sink {
+    fn new(output: Arc<Mutex<Output>>) -> Self {
+        Self {
+            written: String::new(),
+            output,
+            size: (80, 24),
+        }
+    }
+
+    fn render(&self, text: &str) {
+        self.output.lock().unwrap().rendered.push_str(text);
+    }
+}
+
+impl Default for Sink {
+    fn default() -> Self {
+        Self::new(Arc::default())
+    }
+}
 
 impl Renderer for Sink {
     type Reader = IntoIter<KeyEvent>;
@@ -102,13 +167,15 @@ impl Renderer for Sink {
 
     fn refresh_line(
         &mut self,
-        _prompt: &str,
-        _line: &LineBuffer,
+        prompt: &str,
+        line: &LineBuffer,
         _hint: Option<&str>,
         _old_layout: &Layout,
         _new_layout: &Layout,
         _highlighter: Option<&dyn Highlighter>,
     ) -> Result<()> {
+        self.render(prompt);
+        self.render(line);
         Ok(())
     }
 
@@ -118,7 +185,9 @@ impl Renderer for Sink {
         pos
     }
 
-    fn write_and_flush(&mut self, _: &str) -> Result<()> {
+    fn write_and_flush(&mut self, buf: &str) -> Result<()> {
+        self.written.push_str(buf);
+        self.render(buf);
         Ok(())
     }
 
@@ -134,14 +203,18 @@ impl Renderer for Sink {
         Ok(())
     }
 
-    fn update_size(&mut self) {}
+    fn update_size(&mut self) {
+        if let Some(size) = self.output.lock().unwrap().resized {
+            self.size = size;
+        }
+    }
 
     fn get_columns(&self) -> Unit {
-        80
+        self.size.0
     }
 
     fn get_rows(&self) -> Unit {
-        24
+        self.size.1
     }
 
     fn colors_enabled(&self) -> bool {
@@ -152,9 +225,35 @@ impl Renderer for Sink {
         GraphemeClusterMode::Unicode
     }
 
+    fn ambiguous_width(&self) -> AmbiguousWidth {
+        AmbiguousWidth::Narrow
+    }
+
     fn move_cursor_at_leftmost(&mut self, _: &mut IntoIter<KeyEvent>) -> Result<()> {
         Ok(())
     }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        self.render(cursor_shape_seq(shape));
+        Ok(())
+    }
+
+    fn set_alternate_screen(&mut self, enabled: bool) -> Result<()> {
+        self.render(alternate_screen_seq(enabled));
+        Ok(())
+    }
+
+    fn copy_to_clipboard(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn paste_from_clipboard(&mut self, _: &mut IntoIter<KeyEvent>) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn query_background(&mut self, _: &mut IntoIter<KeyEvent>) -> Result<Option<Background>> {
+        Ok(None)
+    }
 }
 
 pub struct DummyExternalPrinter {}
@@ -173,6 +272,8 @@ pub struct DummyTerminal {
     pub cursor: usize, // cursor position before last command
     pub color_mode: ColorMode,
     pub bell_style: BellStyle,
+    pub ambiguous_width: Option<AmbiguousWidth>,
+    pub output: Arc<Mutex<Output>>,
 }
 
 impl Term for DummyTerminal {
@@ -187,32 +288,35 @@ impl Term for DummyTerminal {
     fn new(
         color_mode: ColorMode,
         _grapheme_cluster_mode: GraphemeClusterMode,
+        _detect_grapheme_cluster_mode: bool,
         _behavior: Behavior,
         _tab_stop: u8,
         bell_style: BellStyle,
         _enable_bracketed_paste: bool,
+        _enable_synchronized_output: bool,
         _enable_signals: bool,
+        _serial_baud_rate: Option<u32>,
+        _probe: bool,
+        _passthrough: Passthrough,
+        _enable_focus_events: bool,
+        _headless: Option<(u16, u16)>,
     ) -> Result<Self> {
         Ok(Self {
             keys: vec![],
             cursor: 0,
             color_mode,
             bell_style,
+            ambiguous_width: None,
+            output: Arc::default(),
         })
     }
 
     // Init checks:
 
-    #[cfg(not(target_arch = "wasm32"))]
     fn is_unsupported(&self) -> bool {
         false
     }
 
-    #[cfg(target_arch = "wasm32")]
-    fn is_unsupported(&self) -> bool {
-        true
-    }
-
     fn is_input_tty(&self) -> bool {
         true
     }
@@ -221,6 +325,10 @@ impl Term for DummyTerminal {
         false
     }
 
+    fn colors_enabled(&self) -> bool {
+        false
+    }
+
     // Interactive loop:
 
     fn enable_raw_mode(&mut self) -> Result<(Mode, KeyMap)> {
@@ -232,10 +340,10 @@ impl Term for DummyTerminal {
     }
 
     fn create_writer(&self) -> Sink {
-        Sink::default()
+        Sink::new(self.output.clone())
     }
 
-    fn create_external_printer(&mut self) -> Result<DummyExternalPrinter> {
+    fn create_external_printer(&mut self, _: &Config) -> Result<DummyExternalPrinter> {
         Ok(DummyExternalPrinter {})
     }
 
@@ -243,6 +351,14 @@ impl Term for DummyTerminal {
         Ok(None)
     }
 
+    fn set_title(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_title(&mut self, _: bool) -> Result<()> {
+        Ok(())
+    }
+
     fn writeln(&self) -> Result<()> {
         Ok(())
     }
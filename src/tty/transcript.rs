@@ -0,0 +1,293 @@
+//! Recorded terminal input, replayed to reproduce an interaction
+use std::fmt::{self, Write};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::host::TestTerminal;
+use crate::error::ReadlineError;
+use crate::Result;
+#[cfg(not(test))]
+use crate::{history::History, Editor, Helper};
+
+/// Input received by the terminal
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TranscriptEvent {
+    /// Bytes read at once (a key, an escape sequence, a paste, ...)
+    Data(Vec<u8>),
+    /// New number of columns and rows
+    Resize(u16, u16),
+}
+
+/// Timestamped terminal input: what the user typed and how the terminal has
+/// been resized, replayed with [`Transcript::replay`].
+///
+/// The text format has one event per line, each one prefixed by the number
+/// of milliseconds elapsed since the previous one:
+/// ```text
+/// # comments and blank lines are ignored
+/// size 80 24
+/// 0 data "ls\t"
+/// 350 data "\x1b[A\r"
+/// 20 resize 100 30
+/// ```
+/// `size` (80×24 by default) is the initial size of the terminal. In the
+/// quoted bytes, `\\`, `\"`, `\n`, `\r`, `\t`, `\e` and `\xNN` are escaped.
+///
+/// Delays matter only when the editor waits for input with a timeout
+/// (escape sequences, [`Config::keyseq_timeout`](crate::config::Config::keyseq_timeout),
+/// live validation): time is simulated so that the replay is deterministic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript {
+    size: (u16, u16),
+    events: Vec<(u32, TranscriptEvent)>,
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self::new(80, 24)
+    }
+}
+
+impl Transcript {
+    /// Empty transcript of a `cols`×`rows` terminal
+    #[must_use]
+    pub fn new(cols: u16, rows: u16) -> Self {
+        Self {
+            size: (cols, rows),
+            events: Vec::new(),
+        }
+    }
+
+    /// Read a transcript file
+    pub fn load<P: AsRef<Path> + ?Sized>(path: &P) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Initial number of columns and rows
+    #[must_use]
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Events with their delay (milliseconds since the previous one)
+    #[must_use]
+    pub fn events(&self) -> &[(u32, TranscriptEvent)] {
+        &self.events
+    }
+
+    /// Append `bytes` received `delay_ms` after the previous event
+    #[must_use]
+    pub fn data<B: Into<Vec<u8>>>(mut self, delay_ms: u32, bytes: B) -> Self {
+        self.events
+            .push((delay_ms, TranscriptEvent::Data(bytes.into())));
+        self
+    }
+
+    /// Append a resize to `cols`×`rows`, `delay_ms` after the previous event
+    #[must_use]
+    pub fn resize(mut self, delay_ms: u32, cols: u16, rows: u16) -> Self {
+        self.events
+            .push((delay_ms, TranscriptEvent::Resize(cols, rows)));
+        self
+    }
+
+    /// Terminal with all the events queued
+    #[must_use]
+    pub fn terminal(&self) -> TestTerminal {
+        let term = TestTerminal::new(self.size.0, self.size.1);
+        for (delay, event) in &self.events {
+            match event {
+                TranscriptEvent::Data(bytes) => term.push_timed(*delay, bytes),
+                TranscriptEvent::Resize(cols, rows) => term.push_resize(Some(*delay), *cols, *rows),
+            }
+        }
+        term
+    }
+
+    /// Replay the transcript through `editor` (which then keeps using the
+    /// returned terminal): lines are read with `prompt` and added to the
+    /// history until all the input has been read or an error other than
+    /// [`ReadlineError::Interrupted`] occurs.
+    ///
+    /// Return the result of each `readline` (except the final
+    /// [`ReadlineError::Eof`]) and the terminal, whose output can then be
+    /// inspected.
+    ///
+    /// ```
+    /// use rustyline::{DefaultEditor, Transcript};
+    ///
+    /// let transcript: Transcript = "0 data \"ab\\x1b[Dc\\r\"\n".parse()?;
+    /// let mut editor = DefaultEditor::new()?;
+    /// let (lines, term) = transcript.replay(&mut editor, "> ");
+    /// assert_eq!("acb", lines[0].as_ref().unwrap());
+    /// assert_eq!("> acb", term.screen().line(0));
+    /// # Ok::<(), rustyline::error::ReadlineError>(())
+    /// ```
+    #[cfg(not(test))]
+    pub fn replay<H: Helper, I: History>(
+        &self,
+        editor: &mut Editor<H, I>,
+        prompt: &str,
+    ) -> (Vec<Result<String>>, TestTerminal) {
+        let term = self.terminal();
+        editor.set_host(term.clone());
+        let mut results = vec![];
+        loop {
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    let _ = editor.add_history_entry(line.as_str());
+                    results.push(Ok(line));
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(ReadlineError::Interrupted) => results.push(Err(ReadlineError::Interrupted)),
+                Err(e) => {
+                    results.push(Err(e));
+                    break;
+                }
+            }
+        }
+        (results, term)
+    }
+}
+
+impl FromStr for Transcript {
+    type Err = ReadlineError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut transcript = Self::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            parse_line(&mut transcript, line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid transcript line {}: {line}", i + 1),
+                )
+            })?;
+        }
+        Ok(transcript)
+    }
+}
+
+fn parse_line(transcript: &mut Transcript, line: &str) -> Option<()> {
+    let mut words = line.splitn(3, ' ');
+    let first = words.next()?;
+    if first == "size" {
+        let (cols, rows) = parse_size(words.next()?, words.next()?)?;
+        transcript.size = (cols, rows);
+        return Some(());
+    }
+    let delay = first.parse().ok()?;
+    let event = match words.next()? {
+        "data" => TranscriptEvent::Data(unescape(words.next()?)?),
+        "resize" => {
+            let (cols, rows) = words.next()?.split_once(' ')?;
+            let (cols, rows) = parse_size(cols, rows)?;
+            TranscriptEvent::Resize(cols, rows)
+        }
+        _ => return None,
+    };
+    transcript.events.push((delay, event));
+    Some(())
+}
+
+fn parse_size(cols: &str, rows: &str) -> Option<(u16, u16)> {
+    Some((cols.trim().parse().ok()?, rows.trim().parse().ok()?))
+}
+
+/// Bytes of a double quoted string
+fn unescape(s: &str) -> Option<Vec<u8>> {
+    let s = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next()? {
+            '\\' => b'\\',
+            '"' => b'"',
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            'e' => b'\x1b',
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                if hex.len() != 2 {
+                    return None;
+                }
+                u8::from_str_radix(&hex, 16).ok()?
+            }
+            _ => return None,
+        });
+    }
+    Some(bytes)
+}
+
+fn escape(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() + 2);
+    s.push('"');
+    for &b in bytes {
+        match b {
+            b'\\' => s.push_str("\\\\"),
+            b'"' => s.push_str("\\\""),
+            b'\n' => s.push_str("\\n"),
+            b'\r' => s.push_str("\\r"),
+            b'\t' => s.push_str("\\t"),
+            0x20..=0x7e => s.push(char::from(b)),
+            _ => write!(s, "\\x{b:02x}").unwrap(),
+        }
+    }
+    s.push('"');
+    s
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "size {} {}", self.size.0, self.size.1)?;
+        for (delay, event) in &self.events {
+            match event {
+                TranscriptEvent::Data(bytes) => writeln!(f, "{delay} data {}", escape(bytes))?,
+                TranscriptEvent::Resize(cols, rows) => {
+                    writeln!(f, "{delay} resize {cols} {rows}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Transcript, TranscriptEvent};
+
+    #[test]
+    fn parse() {
+        let text = "# bug #42\nsize 40 10\n\n0 data \"ab\\x1b[D\\\"\\e\"\n120 resize 20 5\n";
+        let transcript: Transcript = text.parse().unwrap();
+        assert_eq!((40, 10), transcript.size());
+        assert_eq!(
+            [
+                (0, TranscriptEvent::Data(b"ab\x1b[D\"\x1b".to_vec())),
+                (120, TranscriptEvent::Resize(20, 5))
+            ],
+            transcript.events()
+        );
+        let text = transcript.to_string();
+        assert_eq!(
+            "size 40 10\n0 data \"ab\\x1b[D\\\"\\x1b\"\n120 resize 20 5\n",
+            text
+        );
+        assert_eq!(transcript, text.parse().unwrap());
+        assert!("0 data ab".parse::<Transcript>().is_err());
+        assert!("0 data \"\\x1\"".parse::<Transcript>().is_err());
+        assert!("data \"a\"".parse::<Transcript>().is_err());
+        assert!(Transcript::load("does/not/exist").is_err());
+    }
+}
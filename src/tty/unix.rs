@@ -1,14 +1,14 @@
 //! Unix specific definitions
+use std::cell::{Cell, RefCell};
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs::{File, OpenOptions};
 #[cfg(not(feature = "buffer-redux"))]
 use std::io::BufReader;
-use std::io::{self, ErrorKind, Read, Write};
-use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, RawFd};
+use std::io::{self, BufRead, ErrorKind, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
 use std::os::unix::net::UnixStream;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{self, SyncSender};
 use std::sync::{Arc, Mutex};
 
 #[cfg(feature = "buffer-redux")]
@@ -26,16 +26,40 @@ use termios::Termios;
 use unicode_segmentation::UnicodeSegmentation;
 use utf8parse::{Parser, Receiver};
 
-use super::{width, Event, RawMode, RawReader, Renderer, Term};
-use crate::config::{Behavior, BellStyle, ColorMode, Config};
-use crate::highlight::Highlighter;
+#[cfg(feature = "with-encoding")]
+use super::encoding;
+use super::{
+    alternate_screen_seq, cursor_shape_seq, detect_passthrough, diff, osc11_decode, osc52_copy_seq,
+    osc52_decode, passthrough_seq, pasted_text, printable, read_osc_answer, title_seq,
+    title_stack_seq, vt_key, width, width_cache::WidthCache, Event, OscAnswer, PrintQueue, RawMode,
+    RawReader, Renderer, Term, OSC52_MAX_LEN,
+};
+use crate::config::{Behavior, BellStyle, ColorMode, Config, CursorShape, Passthrough};
+use crate::highlight::{Background, Highlighter};
 use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
-use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use crate::layout::{AmbiguousWidth, GraphemeClusterMode, Layout, Position, Unit};
 use crate::line_buffer::LineBuffer;
 use crate::{error, error::Signal, Cmd, ReadlineError, Result};
 
 const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
 const BRACKETED_PASTE_OFF: &str = "\x1b[?2004l";
+// modifyOtherKeys level 1: modified keys without a legacy encoding
+const EXTENDED_KEYS_ON: &str = "\x1b[>4;1m";
+const EXTENDED_KEYS_OFF: &str = "\x1b[>4m";
+const FOCUS_EVENTS_ON: &str = "\x1b[?1004h";
+const FOCUS_EVENTS_OFF: &str = "\x1b[?1004l";
+// DECRQM bracketed paste and synchronized output, XTQMODKEYS
+const PROBE_MODES: &str = "\x1b[?2004$p\x1b[?2026$p\x1b[?4m";
+// from column 0 of a cleared line, cursor positions around an ambiguous width
+// character, an emoji with a skin tone modifier and a ZWJ sequence (erased)
+const PROBE_WIDTHS: &str = concat!(
+    "\r\x1b[K\x1b[6n\u{2500}\x1b[6n\u{1f44b}\u{1f3ff}\x1b[6n\u{1f469}\u{200d}\u{1f680}\x1b[6n",
+    "\r\x1b[K"
+);
+// Cursor Position Report
+const CPR: &str = "\x1b[6n";
+// Primary Device Attributes, answered by every terminal
+const DA1: &str = "\x1b[c";
 
 nix::ioctl_read_bad!(win_size, libc::TIOCGWINSZ, libc::winsize);
 
@@ -85,10 +109,17 @@ pub type KeyMap = PosixKeyMap;
 
 #[must_use = "You must restore default mode (disable_raw_mode)"]
 pub struct PosixMode {
-    termios: Termios,
+    termios: Option<Termios>, // `None` when headless
     tty_in: RawFd,
-    tty_out: Option<RawFd>,
+    tty_out: RawFd,
+    bracketed_paste: bool,
+    extended_keys: bool,
+    focus_events: bool,
     raw_mode: Arc<AtomicBool>,
+    // messages of external printers
+    print_queue: Option<Arc<PrintQueue>>,
+    #[cfg(feature = "with-encoding")]
+    encoding: Option<&'static encoding_rs::Encoding>,
 }
 
 #[cfg(not(test))]
@@ -97,12 +128,32 @@ pub type Mode = PosixMode;
 impl RawMode for PosixMode {
     /// Disable RAW mode for the terminal.
     fn disable_raw_mode(&self) -> Result<()> {
-        termios_::disable_raw_mode(self.tty_in, &self.termios)?;
+        if let Some(ref termios) = self.termios {
+            termios_::disable_raw_mode(self.tty_in, termios)?;
+        }
         // disable bracketed paste
-        if let Some(out) = self.tty_out {
-            write_all(out, BRACKETED_PASTE_OFF)?;
+        if self.bracketed_paste {
+            write_all(self.tty_out, BRACKETED_PASTE_OFF)?;
+        }
+        if self.extended_keys {
+            write_all(self.tty_out, EXTENDED_KEYS_OFF)?;
+        }
+        if self.focus_events {
+            write_all(self.tty_out, FOCUS_EVENTS_OFF)?;
         }
         self.raw_mode.store(false, Ordering::SeqCst);
+        // external messages not printed yet and the next ones are printed
+        // directly
+        if let Some(ref queue) = self.print_queue {
+            for event in queue.deactivate() {
+                print_event(
+                    self.tty_out,
+                    event,
+                    #[cfg(feature = "with-encoding")]
+                    self.encoding,
+                )?;
+            }
+        }
         Ok(())
     }
 }
@@ -162,18 +213,23 @@ impl TtyIn {
 }
 
 // (native receiver with a selectable file descriptor, actual message receiver)
-type PipeReader = Arc<Mutex<(File, mpsc::Receiver<String>)>>;
+type PipeReader = Arc<Mutex<(File, Arc<PrintQueue>)>>;
 // (native sender, actual message sender)
-type PipeWriter = (Arc<Mutex<File>>, SyncSender<String>);
+type PipeWriter = (Arc<Mutex<File>>, Arc<PrintQueue>);
 
 /// Console input reader
 pub struct PosixRawReader {
     tty_in: BufReader<TtyIn>,
     timeout_ms: PollTimeout,
     parser: Parser,
+    // non UTF-8 locale
+    #[cfg(feature = "with-encoding")]
+    decoder: Option<encoding::Decoder>,
     key_map: PosixKeyMap,
     // external print reader
     pipe_reader: Option<PipeReader>,
+    // keys typed while waiting for the answer to a query, read first
+    pending: VecDeque<char>,
     #[cfg(target_os = "macos")]
     is_dev_tty: bool,
 }
@@ -239,8 +295,11 @@ impl PosixRawReader {
             tty_in,
             timeout_ms: config.keyseq_timeout().into(),
             parser: Parser::new(),
+            #[cfg(feature = "with-encoding")]
+            decoder: None,
             key_map,
             pipe_reader,
+            pending: VecDeque::new(),
             #[cfg(target_os = "macos")]
             is_dev_tty,
         }
@@ -347,6 +406,8 @@ impl PosixRawReader {
                 //'M' => E(K::, M::), // kmous
                 //'P' => E(K::Delete, M::NONE), // dch1
                 'Z' => E(K::BackTab, M::NONE),
+                'I' => E(K::FocusIn, M::NONE),
+                'O' => E(K::FocusOut, M::NONE),
                 'a' => E(K::Up, M::SHIFT),    // rxvt: kind or kUP
                 'b' => E(K::Down, M::SHIFT),  // rxvt: kri or kDN
                 'c' => E(K::Right, M::SHIFT), // rxvt
@@ -410,6 +471,21 @@ impl PosixRawReader {
                         Ok(E(K::UnknownEscSeq, M::NONE))
                     } else if seq6 == 'R' {
                         Ok(E(K::UnknownEscSeq, M::NONE))
+                    } else if seq6 == ';' && (seq2, seq3) == ('2', '7') {
+                        // modifyOtherKeys: \E[27;modifiers;code~
+                        let mut params = format!("27;{seq5};");
+                        loop {
+                            match self.next_char()? {
+                                '~' => break,
+                                c if c.is_ascii_digit() && params.len() < 16 => params.push(c),
+                                c => {
+                                    debug!(target: "rustyline",
+                                           "unsupported esc sequence: \\E[{}{:?}", params, c);
+                                    return Ok(E(K::UnknownEscSeq, M::NONE));
+                                }
+                            }
+                        }
+                        Ok(vt_key(&params, '~'))
                     } else if seq6 == '~' {
                         Ok(match (seq2, seq3, seq5) {
                             ('1', '5', CTRL) => E(K::F(5), M::CTRL),
@@ -685,11 +761,35 @@ impl PosixRawReader {
         })
     }
 
+    /// Read a bracketed paste char by char (non UTF-8 locale)
+    #[cfg(feature = "with-encoding")]
+    fn read_pasted_chars(&mut self) -> Result<String> {
+        let mut buffer = String::new();
+        loop {
+            match self.next_char()? {
+                '\x1b' => {
+                    let key = self.escape_sequence()?;
+                    if key == E(K::BracketedPasteEnd, M::NONE) {
+                        break;
+                    } else {
+                        continue; // TODO validate
+                    }
+                }
+                c => buffer.push(c),
+            };
+        }
+        Ok(pasted_text(&buffer))
+    }
+
     fn poll(&mut self, timeout: PollTimeout) -> Result<bool> {
-        let n = self.tty_in.buffer().len();
+        let n = self.tty_in.buffer().len() + self.pending.len();
         if n > 0 {
             return Ok(true);
         }
+        #[cfg(feature = "with-encoding")]
+        if self.decoder.as_ref().is_some_and(|d| !d.is_empty()) {
+            return Ok(true);
+        }
         #[cfg(target_os = "macos")]
         if self.is_dev_tty {
             // poll doesn't work for /dev/tty on MacOS but select does
@@ -717,6 +817,9 @@ impl PosixRawReader {
 
     // timeout is used only with /dev/tty on MacOs
     fn select(&mut self, timeout: Option<PollTimeout>, single_esc_abort: bool) -> Result<Event> {
+        if timeout.is_none() && !self.pending.is_empty() {
+            return self.next_key(single_esc_abort).map(Event::KeyPress);
+        }
         let tty_in = self.as_fd();
         let sig_pipe = self
             .tty_in
@@ -778,8 +881,9 @@ impl PosixRawReader {
                 let mut guard = pipe_reader.lock().unwrap();
                 let mut buf = [0; 1];
                 guard.0.read_exact(&mut buf)?;
-                if let Ok(msg) = guard.1.try_recv() {
-                    return Ok(Event::ExternalPrint(msg));
+                // the event may have been discarded
+                if let Some(event) = guard.1.pop() {
+                    return Ok(event);
                 }
             }
         }
@@ -831,18 +935,34 @@ impl RawReader for PosixRawReader {
         Ok(key)
     }
 
+    fn poll_key(&mut self, timeout_ms: u16) -> Result<bool> {
+        self.poll(timeout_ms.into())
+    }
+
     fn next_char(&mut self) -> Result<char> {
+        if let Some(c) = self.pending.pop_front() {
+            return Ok(c);
+        }
         let mut buf = [0; 1];
         let mut receiver = Utf8 {
             c: None,
             valid: true,
         };
         loop {
+            #[cfg(feature = "with-encoding")]
+            if let Some(c) = self.decoder.as_mut().and_then(encoding::Decoder::pop) {
+                return Ok(c);
+            }
             let n = self.tty_in.read(&mut buf)?;
             if n == 0 {
                 return Err(ReadlineError::Eof);
             }
             let b = buf[0];
+            #[cfg(feature = "with-encoding")]
+            if let Some(ref mut decoder) = self.decoder {
+                decoder.push(b);
+                continue;
+            }
             self.parser.advance(&mut receiver, b);
             if !receiver.valid {
                 return Err(ReadlineError::from(ErrorKind::InvalidData));
@@ -853,23 +973,36 @@ impl RawReader for PosixRawReader {
     }
 
     fn read_pasted_text(&mut self) -> Result<String> {
-        let mut buffer = String::new();
+        #[cfg(feature = "with-encoding")]
+        if self.decoder.is_some() {
+            return self.read_pasted_chars();
+        }
+        // copy the buffered bytes up to the next escape sequence at once
+        let mut bytes = Vec::new();
         loop {
-            match self.next_char()? {
-                '\x1b' => {
-                    let key = self.escape_sequence()?;
-                    if key == E(K::BracketedPasteEnd, M::NONE) {
-                        break;
-                    } else {
-                        continue; // TODO validate
-                    }
+            let buf = self.tty_in.fill_buf()?;
+            if buf.is_empty() {
+                return Err(ReadlineError::Eof);
+            }
+            let esc = buf.iter().position(|&b| b == b'\x1b');
+            let n = esc.unwrap_or(buf.len());
+            bytes.extend_from_slice(&buf[..n]);
+            if esc.is_some() {
+                self.tty_in.consume(n + 1);
+                if self.escape_sequence()? == E(K::BracketedPasteEnd, M::NONE) {
+                    break;
                 }
-                c => buffer.push(c),
-            };
+            } else {
+                self.tty_in.consume(n);
+            }
         }
-        let buffer = buffer.replace("\r\n", "\n");
-        let buffer = buffer.replace('\r', "\n");
-        Ok(buffer)
+        #[cfg(feature = "simd")]
+        let buffer = simdutf8::basic::from_utf8(&bytes)
+            .map_err(|_| ReadlineError::from(ErrorKind::InvalidData))?;
+        #[cfg(not(feature = "simd"))]
+        let buffer =
+            std::str::from_utf8(&bytes).map_err(|_| ReadlineError::from(ErrorKind::InvalidData))?;
+        Ok(pasted_text(buffer))
     }
 
     fn find_binding(&self, key: &KeyEvent) -> Option<Cmd> {
@@ -906,39 +1039,426 @@ impl Receiver for Utf8 {
     }
 }
 
+/// Decode the keys sent by a terminal as `bytes`, up to the first error
+/// (truncated escape sequence, invalid UTF-8, ...)
+#[cfg(feature = "fuzzing")]
+pub fn decode_keys(bytes: &[u8]) -> Vec<KeyEvent> {
+    let mut keys = vec![];
+    with_input(bytes, |rdr| {
+        while let Ok(key) = rdr.next_key(false) {
+            keys.push(key);
+        }
+    });
+    keys
+}
+
+/// Decode the text pasted by a terminal as `bytes` (what follows the
+/// bracketed paste start sequence)
+#[cfg(feature = "fuzzing")]
+pub fn decode_paste(bytes: &[u8]) -> Option<String> {
+    let mut text = None;
+    with_input(bytes, |rdr| text = rdr.read_pasted_text().ok());
+    text
+}
+
+// Feed a reader with `bytes` followed by end of file
+#[cfg(feature = "fuzzing")]
+fn with_input<F: FnOnce(&mut PosixRawReader)>(bytes: &[u8], f: F) {
+    let Ok((input, mut feeder)) = UnixStream::pair() else {
+        return;
+    };
+    std::thread::scope(|scope| {
+        // a separate writer so that the socket buffer size doesn't matter
+        scope.spawn(move || feeder.write_all(bytes));
+        let mut rdr = PosixRawReader::new(
+            input.as_raw_fd(),
+            None,
+            None,
+            &Config::default(),
+            PosixKeyMap::default(),
+            None,
+            #[cfg(target_os = "macos")]
+            false,
+        );
+        f(&mut rdr);
+        // unblock the writer when not everything has been read
+        let _ = input.shutdown(std::net::Shutdown::Read);
+    });
+}
+
+/// Escape sequences selected for the current terminal.
+///
+/// Standard ANSI sequences are used unless the `terminfo` feature is enabled
+/// and the entry for `TERM` provides its own.
+#[derive(Clone, Debug, Default)]
+pub struct Capabilities {
+    #[cfg(feature = "terminfo")]
+    info: Option<Arc<super::terminfo::TermInfo>>,
+    /// No cursor movement: only printable characters, `\r` and `\n`
+    dumb: bool,
+}
+
+impl Capabilities {
+    fn load() -> Self {
+        #[cfg(feature = "terminfo")]
+        let info = super::terminfo::TermInfo::from_env();
+        #[cfg(feature = "terminfo")]
+        let dumb = info.as_ref().is_some_and(|info| {
+            use super::terminfo::{CURSOR_UP, PARM_UP_CURSOR};
+            info.string(CURSOR_UP).is_none() && info.string(PARM_UP_CURSOR).is_none()
+        });
+        #[cfg(not(feature = "terminfo"))]
+        let dumb = false;
+        Self {
+            #[cfg(feature = "terminfo")]
+            info: info.map(Arc::new),
+            dumb: dumb || super::is_dumb_term(),
+        }
+    }
+
+    #[cfg(feature = "terminfo")]
+    fn expand(&self, cap: usize, n: Unit) -> Option<String> {
+        let n = i32::from(n);
+        self.info.as_ref()?.expand(cap, &[n])
+    }
+
+    /// Append the sequence moving the cursor `n` times in the direction
+    /// given by the ANSI final byte (`A`: up, `B`: down, `C`: right, `D`:
+    /// left)
+    fn cursor_move(&self, buf: &mut String, n: Unit, dir: char) {
+        use std::fmt::Write;
+        #[cfg(feature = "terminfo")]
+        {
+            use super::terminfo::{
+                PARM_DOWN_CURSOR, PARM_LEFT_CURSOR, PARM_RIGHT_CURSOR, PARM_UP_CURSOR,
+            };
+            let cap = match dir {
+                'A' => PARM_UP_CURSOR,
+                'B' => PARM_DOWN_CURSOR,
+                'C' => PARM_RIGHT_CURSOR,
+                _ => PARM_LEFT_CURSOR,
+            };
+            if let Some(seq) = self.expand(cap, n) {
+                buf.push_str(&seq);
+                return;
+            }
+        }
+        if n == 1 {
+            write!(buf, "\x1b[{dir}").unwrap();
+        } else {
+            write!(buf, "\x1b[{n}{dir}").unwrap();
+        }
+    }
+
+    #[cfg(feature = "terminfo")]
+    fn string(&self, cap: usize) -> Option<&str> {
+        self.info.as_ref()?.string(cap)
+    }
+
+    /// Clear to end of line
+    fn clear_eol(&self) -> &str {
+        #[cfg(feature = "terminfo")]
+        if let Some(seq) = self.string(super::terminfo::CLR_EOL) {
+            return seq;
+        }
+        "\x1b[K"
+    }
+
+    /// Clear the screen and home the cursor
+    fn clear_screen(&self) -> &str {
+        #[cfg(feature = "terminfo")]
+        if let Some(seq) = self.string(super::terminfo::CLEAR_SCREEN) {
+            return seq;
+        }
+        "\x1b[H\x1b[J"
+    }
+
+    /// Audible signal
+    fn bell(&self) -> &str {
+        #[cfg(feature = "terminfo")]
+        if let Some(seq) = self.string(super::terminfo::BELL) {
+            return seq;
+        }
+        "\x07"
+    }
+
+    /// Whether the terminal wraps as soon as the last column is written
+    /// (`am` without `xenl`), in which case no explicit newline is needed
+    /// when the line ends on the right margin.
+    fn wraps_immediately(&self) -> bool {
+        #[cfg(feature = "terminfo")]
+        if let Some(ref info) = self.info {
+            use super::terminfo::{AUTO_RIGHT_MARGIN, EAT_NEWLINE_GLITCH};
+            return info.flag(AUTO_RIGHT_MARGIN) && !info.flag(EAT_NEWLINE_GLITCH);
+        }
+        false
+    }
+
+    /// Whether the terminal supports colors
+    fn has_colors(&self) -> bool {
+        if self.dumb {
+            return false;
+        }
+        #[cfg(feature = "terminfo")]
+        if let Some(ref info) = self.info {
+            return info.number(super::terminfo::MAX_COLORS).is_some();
+        }
+        true
+    }
+}
+
 /// Console output writer
 pub struct PosixRenderer {
     out: RawFd,
     cols: Unit, // Number of columns in terminal
     buffer: String,
+    text: String, // prompt, line and hint being displayed
     tab_stop: Unit,
     colors_enabled: bool,
     grapheme_cluster_mode: GraphemeClusterMode,
+    ambiguous_width: AmbiguousWidth,
     bell_style: BellStyle,
+    synchronized_output: bool, // DEC private mode 2026
+    caps: Capabilities,
+    baud_rate: Option<u32>,   // serial console
+    row: String,              // current row content (dumb terminal)
+    passthrough: Passthrough, // terminal multiplexer
+    #[cfg(feature = "with-encoding")]
+    encoding: Option<&'static encoding_rs::Encoding>, // non UTF-8 locale
+    written: Cell<u64>,       // number of bytes written
+    batch: RefCell<Option<Vec<u8>>>, // output buffered until the end of a batch
+    size: Option<(Unit, Unit)>, // fixed size when headless
+    frame: diff::Frame,       // rows displayed by the last refresh
+    widths: WidthCache,
 }
 
 impl PosixRenderer {
+    #[expect(clippy::too_many_arguments)]
     fn new(
         out: RawFd,
         tab_stop: Unit,
         colors_enabled: bool,
         grapheme_cluster_mode: GraphemeClusterMode,
         bell_style: BellStyle,
+        synchronized_output: bool,
+        caps: Capabilities,
+        baud_rate: Option<u32>,
     ) -> Self {
         let (cols, _) = get_win_size(out);
         Self {
             out,
             cols,
             buffer: String::with_capacity(1024),
+            text: String::with_capacity(1024),
             tab_stop,
             colors_enabled,
             grapheme_cluster_mode,
+            ambiguous_width: AmbiguousWidth::Narrow,
             bell_style,
+            synchronized_output,
+            caps,
+            baud_rate,
+            row: String::new(),
+            passthrough: Passthrough::Disabled,
+            #[cfg(feature = "with-encoding")]
+            encoding: None,
+            written: Cell::new(0),
+            batch: RefCell::new(None),
+            size: None,
+            frame: diff::Frame::default(),
+            widths: WidthCache::default(),
+        }
+    }
+
+    fn win_size(&self) -> (Unit, Unit) {
+        self.size.unwrap_or_else(|| get_win_size(self.out))
+    }
+
+    /// Write `buf`, no faster than the serial line
+    fn write(&self, buf: &str) -> Result<()> {
+        #[cfg(feature = "with-encoding")]
+        if let Some(encoding) = self.encoding {
+            return self.write_bytes(&encoding::encode(encoding, buf));
+        }
+        self.write_bytes(buf.as_bytes())
+    }
+
+    fn write_bytes(&self, bytes: &[u8]) -> Result<()> {
+        if let Some(ref mut batch) = *self.batch.borrow_mut() {
+            batch.extend_from_slice(bytes);
+        } else {
+            self.output(bytes)?;
+        }
+        self.written.set(self.written.get() + bytes.len() as u64);
+        Ok(())
+    }
+
+    fn output(&self, bytes: &[u8]) -> Result<()> {
+        match self.baud_rate {
+            Some(baud_rate) => write_paced(self.out, bytes, baud_rate)?,
+            None => write_bytes(self.out, bytes)?,
+        }
+        Ok(())
+    }
+
+    /// Display width of `s` (a single row)
+    fn row_width(&self, s: &str) -> Unit {
+        let mut col = 0;
+        let mut esc_seq = 0;
+        for g in s.graphemes(true) {
+            col += if g == "\t" {
+                self.tab_stop - (col % self.tab_stop)
+            } else {
+                width(
+                    self.grapheme_cluster_mode,
+                    self.ambiguous_width,
+                    g,
+                    &mut esc_seq,
+                )
+            };
+        }
+        col
+    }
+
+    /// Byte offset of column `target` in `s` (a single row)
+    fn column_offset(&self, s: &str, target: Unit) -> usize {
+        let mut col = 0;
+        let mut esc_seq = 0;
+        for (i, g) in s.grapheme_indices(true) {
+            if col >= target {
+                return i;
+            }
+            col += if g == "\t" {
+                self.tab_stop - (col % self.tab_stop)
+            } else {
+                width(
+                    self.grapheme_cluster_mode,
+                    self.ambiguous_width,
+                    g,
+                    &mut esc_seq,
+                )
+            };
+        }
+        s.len()
+    }
+
+    /// Byte offset where the last row of `s` starts, once wrapped
+    fn last_row_offset(&self, s: &str) -> usize {
+        let mut start = 0;
+        let mut col = 0;
+        let mut esc_seq = 0;
+        for (i, g) in s.grapheme_indices(true) {
+            if g == "\n" {
+                start = i + 1;
+                col = 0;
+                continue;
+            }
+            let cw = if g == "\t" {
+                self.tab_stop - (col % self.tab_stop)
+            } else {
+                width(
+                    self.grapheme_cluster_mode,
+                    self.ambiguous_width,
+                    g,
+                    &mut esc_seq,
+                )
+            };
+            col += cw;
+            if col > self.cols {
+                start = i;
+                col = cw;
+            }
         }
+        if col == self.cols {
+            start = s.len();
+        }
+        start
+    }
+
+    /// Redraw without any cursor movement: the current row is re-echoed
+    /// after a carriage return, or the whole line on a new row when it does
+    /// not fit on a single one.
+    fn refresh_dumb(
+        &mut self,
+        prompt: &str,
+        line: &LineBuffer,
+        hint: Option<&str>,
+        old_layout: &Layout,
+        new_layout: &Layout,
+    ) -> Result<()> {
+        self.buffer.clear();
+        let old_width = if old_layout.end.row > 0 || new_layout.end.row > 0 {
+            // rows above cannot be reached
+            self.buffer.push_str("\r\n");
+            0
+        } else {
+            self.buffer.push('\r');
+            self.row_width(&self.row)
+        };
+        let mut text = String::with_capacity(prompt.len() + line.len());
+        printable(prompt, &mut text);
+        printable(line, &mut text);
+        if let Some(hint) = hint {
+            printable(hint, &mut text);
+        }
+        self.buffer.push_str(&text);
+        self.row = text.split_off(self.last_row_offset(&text));
+        // erase what remains of the previous content
+        let mut col = self.row_width(&self.row);
+        if old_width > col {
+            let padding = usize::from(old_width - col);
+            self.buffer.extend(std::iter::repeat_n(' ', padding));
+            col = old_width;
+        }
+        let cursor = new_layout.cursor;
+        if cursor.row == new_layout.end.row && cursor.col < col {
+            self.buffer.push('\r');
+            let offset = self.column_offset(&self.row, cursor.col);
+            self.buffer.push_str(&self.row[..offset]);
+        }
+        self.write(&self.buffer)
+    }
+
+    /// Send the OSC query `ESC ] ps ; ? BEL` and return the answer, without
+    /// its `ESC ] code ;` prefix (`code` being the number starting `ps`) and
+    /// its terminator, unless the terminal doesn't answer within 100 ms per
+    /// character or answers more than `max_len` characters. Keys typed
+    /// instead of an answer are read again by `rdr`.
+    fn query_osc(
+        &mut self,
+        rdr: &mut PosixRawReader,
+        ps: &str,
+        max_len: usize,
+    ) -> Result<Option<String>> {
+        if self.caps.dumb {
+            return Ok(None);
+        }
+        if rdr.poll(PollTimeout::ZERO)? {
+            debug!(target: "rustyline", "cannot send query while input is pending");
+            return Ok(None);
+        }
+        let query = format!("\x1b]{ps};?\x07");
+        self.write_and_flush(&passthrough_seq(self.passthrough, &query))?;
+        let code = ps.split(';').next().unwrap_or(ps);
+        let answer = read_osc_answer(code, max_len, || {
+            Ok(if rdr.poll(PollTimeout::from(100u8))? {
+                Some(rdr.next_char()?)
+            } else {
+                None
+            })
+        })?;
+        Ok(match answer {
+            OscAnswer::Answer(answer) => Some(answer),
+            OscAnswer::Input(keys) => {
+                debug!(target: "rustyline", "no answer to OSC {ps} but {keys:?}");
+                rdr.pending.extend(keys.chars());
+                None
+            }
+            OscAnswer::Missing => None,
+        })
     }
 
     fn clear_old_rows(&mut self, layout: &Layout) {
-        use std::fmt::Write;
         let current_row = layout.cursor.row;
         let old_rows = layout.end.row;
         // old_rows < cursor_row if the prompt spans multiple lines and if
@@ -946,14 +1466,18 @@ impl PosixRenderer {
         let cursor_row_movement = old_rows.saturating_sub(current_row);
         // move the cursor down as required
         if cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{cursor_row_movement}B").unwrap();
+            self.caps
+                .cursor_move(&mut self.buffer, cursor_row_movement, 'B');
         }
         // clear old rows
         for _ in 0..old_rows {
-            self.buffer.push_str("\r\x1b[K\x1b[A");
+            self.buffer.push('\r');
+            self.buffer.push_str(self.caps.clear_eol());
+            self.caps.cursor_move(&mut self.buffer, 1, 'A');
         }
         // clear the line
-        self.buffer.push_str("\r\x1b[K");
+        self.buffer.push('\r');
+        self.buffer.push_str(self.caps.clear_eol());
     }
 }
 
@@ -961,46 +1485,42 @@ impl Renderer for PosixRenderer {
     type Reader = PosixRawReader;
 
     fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
-        use std::fmt::Write;
         self.buffer.clear();
+        if self.caps.dumb {
+            // only moves within the current row are possible
+            if new.row == old.row {
+                let from = self.column_offset(&self.row, old.col);
+                let to = self.column_offset(&self.row, new.col);
+                if to < from {
+                    self.buffer.push('\r');
+                    self.buffer.push_str(&self.row[..to]);
+                } else {
+                    self.buffer.push_str(&self.row[from..to]);
+                }
+            }
+            return self.write(&self.buffer);
+        }
         let row_ordering = new.row.cmp(&old.row);
         if row_ordering == cmp::Ordering::Greater {
             // move down
             let row_shift = new.row - old.row;
-            if row_shift == 1 {
-                self.buffer.push_str("\x1b[B");
-            } else {
-                write!(self.buffer, "\x1b[{row_shift}B")?;
-            }
+            self.caps.cursor_move(&mut self.buffer, row_shift, 'B');
         } else if row_ordering == cmp::Ordering::Less {
             // move up
             let row_shift = old.row - new.row;
-            if row_shift == 1 {
-                self.buffer.push_str("\x1b[A");
-            } else {
-                write!(self.buffer, "\x1b[{row_shift}A")?;
-            }
+            self.caps.cursor_move(&mut self.buffer, row_shift, 'A');
         }
         let col_ordering = new.col.cmp(&old.col);
         if col_ordering == cmp::Ordering::Greater {
             // move right
             let col_shift = new.col - old.col;
-            if col_shift == 1 {
-                self.buffer.push_str("\x1b[C");
-            } else {
-                write!(self.buffer, "\x1b[{col_shift}C")?;
-            }
+            self.caps.cursor_move(&mut self.buffer, col_shift, 'C');
         } else if col_ordering == cmp::Ordering::Less {
             // move left
             let col_shift = old.col - new.col;
-            if col_shift == 1 {
-                self.buffer.push_str("\x1b[D");
-            } else {
-                write!(self.buffer, "\x1b[{col_shift}D")?;
-            }
+            self.caps.cursor_move(&mut self.buffer, col_shift, 'D');
         }
-        write_all(self.out, self.buffer.as_str())?;
-        Ok(())
+        self.write(&self.buffer)
     }
 
     fn refresh_line(
@@ -1012,39 +1532,71 @@ impl Renderer for PosixRenderer {
         new_layout: &Layout,
         highlighter: Option<&dyn Highlighter>,
     ) -> Result<()> {
-        use std::fmt::Write;
+        if self.caps.dumb {
+            return self.refresh_dumb(prompt, line, hint, old_layout, new_layout);
+        }
         self.buffer.clear();
+        if self.synchronized_output {
+            // begin synchronized update
+            self.buffer.push_str("\x1b[?2026h");
+        }
 
         let default_prompt = new_layout.default_prompt;
         let cursor = new_layout.cursor;
         let end_pos = new_layout.end;
 
-        self.clear_old_rows(old_layout);
-
+        let text = &mut self.text;
+        text.clear();
         if let Some(highlighter) = highlighter {
             // display the prompt
-            self.buffer
-                .push_str(&highlighter.highlight_prompt(prompt, default_prompt));
+            text.push_str(&highlighter.highlight_prompt(prompt, default_prompt));
             // display the input line
-            self.buffer
-                .push_str(&highlighter.highlight(line, line.pos()));
+            text.push_str(&highlighter.highlight(line, line.pos()));
         } else {
             // display the prompt
-            self.buffer.push_str(prompt);
+            text.push_str(prompt);
             // display the input line
-            self.buffer.push_str(line);
+            text.push_str(line);
         }
         // display hint
         if let Some(hint) = hint {
             if let Some(highlighter) = highlighter {
-                self.buffer.push_str(&highlighter.highlight_hint(hint));
+                text.push_str(&highlighter.highlight_hint(hint));
             } else {
-                self.buffer.push_str(hint);
+                text.push_str(hint);
             }
         }
+        self.frame.split(
+            &self.text,
+            self.cols,
+            self.tab_stop,
+            self.grapheme_cluster_mode,
+            self.ambiguous_width,
+        );
+        // only write what has changed since the last refresh
+        if !self.caps.wraps_immediately()
+            && diff::update(
+                &mut self.buffer,
+                &self.frame,
+                old_layout,
+                new_layout,
+                self.cols,
+                |buf, n, dir| self.caps.cursor_move(buf, n, dir),
+            )
+        {
+            if self.synchronized_output {
+                self.buffer.push_str("\x1b[?2026l");
+            }
+            self.frame.rendered(new_layout, self.cols);
+            return self.write(&self.buffer);
+        }
+
+        self.clear_old_rows(old_layout);
+        self.buffer.push_str(&self.text);
         // we have to generate our own newline on line wrap
         if end_pos.col == 0
             && end_pos.row > 0
+            && !self.caps.wraps_immediately()
             && !hint.map_or_else(|| line.ends_with('\n'), |h| h.ends_with('\n'))
         {
             self.buffer.push('\n');
@@ -1053,76 +1605,117 @@ impl Renderer for PosixRenderer {
         let new_cursor_row_movement = end_pos.row - cursor.row;
         // move the cursor up as required
         if new_cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{new_cursor_row_movement}A")?;
+            self.caps
+                .cursor_move(&mut self.buffer, new_cursor_row_movement, 'A');
         }
         // position the cursor within the line
+        self.buffer.push('\r');
         if cursor.col > 0 {
-            write!(self.buffer, "\r\x1b[{}C", cursor.col)?;
-        } else {
-            self.buffer.push('\r');
+            self.caps.cursor_move(&mut self.buffer, cursor.col, 'C');
+        }
+        if self.synchronized_output {
+            // end synchronized update
+            self.buffer.push_str("\x1b[?2026l");
         }
+        self.frame.rendered(new_layout, self.cols);
 
-        write_all(self.out, self.buffer.as_str())?;
-        Ok(())
+        self.write(&self.buffer)
+    }
+
+    fn append(&mut self, text: &str, width: Unit) -> Result<()> {
+        if self.caps.dumb {
+            return self.write_and_flush(text);
+        }
+        self.frame.append(text, width);
+        self.write(text)
     }
 
     fn write_and_flush(&mut self, buf: &str) -> Result<()> {
-        write_all(self.out, buf)?;
-        Ok(())
+        self.frame.invalidate();
+        if self.caps.dumb {
+            let mut text = String::with_capacity(buf.len());
+            printable(buf, &mut text);
+            match text.rfind('\n') {
+                Some(i) => self.row = text[i + 1..].to_owned(),
+                None => self.row.push_str(&text),
+            }
+            return self.write(&text);
+        }
+        self.write(buf)
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.written.get()
+    }
+
+    fn begin_batch(&mut self) {
+        self.batch.get_mut().get_or_insert_with(Vec::new);
+    }
+
+    fn end_batch(&mut self) -> Result<()> {
+        match self.batch.get_mut().take() {
+            Some(bytes) if !bytes.is_empty() => self.output(&bytes),
+            _ => Ok(()),
+        }
+    }
+
+    fn reconfigure(&mut self, colors_enabled: bool, bell_style: BellStyle) {
+        self.colors_enabled = colors_enabled;
+        self.bell_style = bell_style;
     }
 
     /// Control characters are treated as having zero width.
     /// Characters with 2 column width are correctly handled (not split).
     fn calculate_position(&self, s: &str, orig: Position) -> Position {
-        let mut pos = orig;
-        let mut esc_seq = 0;
-        for c in s.graphemes(true) {
-            if c == "\n" {
-                pos.row += 1;
-                pos.col = 0;
-                continue;
-            }
-            let cw = if c == "\t" {
-                self.tab_stop - (pos.col % self.tab_stop)
-            } else {
-                width(self.grapheme_cluster_mode, c, &mut esc_seq)
-            };
-            pos.col += cw;
-            if pos.col > self.cols {
-                pos.row += 1;
-                pos.col = cw;
-            }
-        }
-        if pos.col == self.cols {
-            pos.col = 0;
-            pos.row += 1;
-        }
-        pos
+        self.widths.calculate_position(
+            s,
+            orig,
+            self.cols,
+            self.tab_stop,
+            self.grapheme_cluster_mode,
+            self.ambiguous_width,
+        )
     }
 
     fn beep(&mut self) -> Result<()> {
         match self.bell_style {
-            BellStyle::Audible => self.write_and_flush("\x07"),
+            BellStyle::Audible => self.write(self.caps.bell()),
             _ => Ok(()),
         }
     }
 
     /// Clear the screen. Used to handle ctrl+l
     fn clear_screen(&mut self) -> Result<()> {
-        self.write_and_flush("\x1b[H\x1b[J")
+        self.frame.invalidate();
+        if self.caps.dumb {
+            self.row.clear();
+            return self.write("\r\n");
+        }
+        self.write(self.caps.clear_screen())
     }
 
     fn clear_rows(&mut self, layout: &Layout) -> Result<()> {
+        self.frame.invalidate();
         self.buffer.clear();
-        self.clear_old_rows(layout);
-        write_all(self.out, self.buffer.as_str())?;
-        Ok(())
+        if self.caps.dumb {
+            // blank the current row
+            let padding = usize::from(self.row_width(&self.row));
+            self.buffer.push('\r');
+            self.buffer.extend(std::iter::repeat_n(' ', padding));
+            self.buffer.push('\r');
+            self.row.clear();
+        } else {
+            self.clear_old_rows(layout);
+        }
+        self.write(&self.buffer)
     }
 
     /// Try to update the number of columns in the current terminal,
     fn update_size(&mut self) {
-        let (cols, _) = get_win_size(self.out);
+        let (cols, _) = self.win_size();
         self.cols = cols;
+        self.frame.invalidate();
+        self.widths.clear();
     }
 
     fn get_columns(&self) -> Unit {
@@ -1132,7 +1725,7 @@ impl Renderer for PosixRenderer {
     /// Try to get the number of rows in the current terminal,
     /// or assume 24 if it fails.
     fn get_rows(&self) -> Unit {
-        let (_, rows) = get_win_size(self.out);
+        let (_, rows) = self.win_size();
         rows
     }
 
@@ -1144,7 +1737,14 @@ impl Renderer for PosixRenderer {
         self.grapheme_cluster_mode
     }
 
+    fn ambiguous_width(&self) -> AmbiguousWidth {
+        self.ambiguous_width
+    }
+
     fn move_cursor_at_leftmost(&mut self, rdr: &mut PosixRawReader) -> Result<()> {
+        if self.caps.dumb {
+            return Ok(());
+        }
         if rdr.poll(PollTimeout::ZERO)? {
             debug!(target: "rustyline", "cannot request cursor location");
             return Ok(());
@@ -1167,6 +1767,54 @@ impl Renderer for PosixRenderer {
         }
         Ok(())
     }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        if self.caps.dumb {
+            return Ok(());
+        }
+        let seq = cursor_shape_seq(shape);
+        if self.passthrough == Passthrough::Screen {
+            // tmux handles DECSCUSR itself
+            self.write_and_flush(&passthrough_seq(self.passthrough, seq))
+        } else {
+            self.write_and_flush(seq)
+        }
+    }
+
+    fn set_alternate_screen(&mut self, enabled: bool) -> Result<()> {
+        if self.caps.dumb {
+            return Ok(());
+        }
+        self.write_and_flush(alternate_screen_seq(enabled))
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        if self.caps.dumb {
+            return Ok(());
+        }
+        self.write_and_flush(&passthrough_seq(self.passthrough, &osc52_copy_seq(text)))
+    }
+
+    fn paste_from_clipboard(&mut self, rdr: &mut PosixRawReader) -> Result<Option<String>> {
+        /* Read the response: ESC ] 52 ; c ; data (BEL | ESC \) */
+        // data and selection parameter (`c;`, `s0;`, ...)
+        let max_len = OSC52_MAX_LEN + 16;
+        let Some(answer) = self.query_osc(rdr, "52;c", max_len)? else {
+            debug!(target: "rustyline", "clipboard reading not allowed");
+            return Ok(None);
+        };
+        let data = answer.split_once(';').map(|(_, data)| data);
+        Ok(data.and_then(osc52_decode))
+    }
+
+    fn query_background(&mut self, rdr: &mut PosixRawReader) -> Result<Option<Background>> {
+        /* Read the response: ESC ] 11 ; rgb:RRRR/GGGG/BBBB (BEL | ESC \) */
+        let Some(answer) = self.query_osc(rdr, "11", 64)? else {
+            debug!(target: "rustyline", "cannot read background color");
+            return Ok(None);
+        };
+        Ok(osc11_decode(&answer))
+    }
 }
 
 fn read_digits_until(rdr: &mut PosixRawReader, sep: char) -> Result<Option<u32>> {
@@ -1187,7 +1835,10 @@ fn read_digits_until(rdr: &mut PosixRawReader, sep: char) -> Result<Option<u32>>
 }
 
 fn write_all(fd: RawFd, buf: &str) -> nix::Result<()> {
-    let mut bytes = buf.as_bytes();
+    write_bytes(fd, buf.as_bytes())
+}
+
+fn write_bytes(fd: RawFd, mut bytes: &[u8]) -> nix::Result<()> {
     while !bytes.is_empty() {
         match write(unsafe { BorrowedFd::borrow_raw(fd) }, bytes) {
             Ok(0) => return Err(Errno::EIO),
@@ -1199,6 +1850,196 @@ fn write_all(fd: RawFd, buf: &str) -> nix::Result<()> {
     Ok(())
 }
 
+/// Write `buf` no faster than `baud_rate` (10 bits per byte), for serial
+/// lines without hardware flow control
+fn write_paced(fd: RawFd, buf: &[u8], baud_rate: u32) -> nix::Result<()> {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    let bytes_per_sec = u64::from(baud_rate / 10).max(1);
+    // about 10ms of output at a time
+    let chunk_size = usize::try_from(bytes_per_sec / 100)
+        .unwrap_or(usize::MAX)
+        .max(1);
+    for chunk in buf.chunks(chunk_size) {
+        write_bytes(fd, chunk)?;
+        sleep(Duration::from_micros(
+            chunk.len() as u64 * 1_000_000 / bytes_per_sec,
+        ));
+    }
+    Ok(())
+}
+
+/// Optional features reported by the terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Features {
+    bracketed_paste: bool,
+    synchronized_output: bool,
+    extended_keys: bool,
+    ambiguous_width: Option<AmbiguousWidth>,
+    grapheme_cluster_mode: Option<GraphemeClusterMode>,
+}
+
+impl Features {
+    /// Parse the answers to [`PROBE_MODES`] or [`PROBE_WIDTHS`].
+    ///
+    /// `None` is returned until the DA1 answer, which every terminal sends,
+    /// has been received. Bracketed paste is assumed when DECRQM is not
+    /// answered because many terminals support the mode but not the query.
+    fn parse(answers: &str) -> Option<Self> {
+        let mut features = Self {
+            bracketed_paste: true,
+            synchronized_output: false,
+            extended_keys: false,
+            ambiguous_width: None,
+            grapheme_cluster_mode: None,
+        };
+        // columns advanced by each probed text
+        let mut advances = Vec::new();
+        let mut cpr = None;
+        for seq in answers.split('\x1b').filter_map(|s| s.strip_prefix('[')) {
+            if let Some(params) = seq.strip_prefix('?').and_then(|s| s.strip_suffix("$y")) {
+                // DECRPM: 0 unknown, 1 set, 2 reset, 3 always set, 4 always reset
+                let Some((mode, status)) = params.split_once(';') else {
+                    continue;
+                };
+                let supported = matches!(status, "1" | "2" | "3");
+                match mode {
+                    "2004" => features.bracketed_paste = supported,
+                    "2026" => features.synchronized_output = supported,
+                    _ => {}
+                }
+            } else if seq.strip_prefix(">4").is_some_and(|s| s.ends_with('m')) {
+                // XTQMODKEYS: modifyOtherKeys is known
+                features.extended_keys = true;
+            } else if let Some((row, col)) = seq.strip_suffix('R').and_then(|s| s.split_once(';')) {
+                // CPR before and after each probed text
+                let pos = row.parse::<Unit>().ok().zip(col.parse::<Unit>().ok());
+                if let Some(before) = cpr.replace(pos) {
+                    advances.push(before.zip(pos).and_then(
+                        |((row, before), (after_row, after))| {
+                            after.checked_sub(before).filter(|_| row == after_row)
+                        },
+                    ));
+                }
+            } else if seq.starts_with('?') && seq.ends_with('c') {
+                features.measure(&advances);
+                return Some(features);
+            }
+        }
+        None
+    }
+
+    /// Deduce the widths from the columns advanced by the texts of
+    /// [`PROBE_WIDTHS`]
+    /// (unknown when wrapped or not answered)
+    fn measure(&mut self, advances: &[Option<Unit>]) {
+        let advance = |i: usize| advances.get(i).copied().flatten();
+        self.ambiguous_width = match advance(0) {
+            Some(1) => Some(AmbiguousWidth::Narrow),
+            Some(2) => Some(AmbiguousWidth::Wide),
+            _ => None,
+        };
+        // skin tone modifier, zero-width joiner
+        self.grapheme_cluster_mode = match (advance(1), advance(2)) {
+            (Some(2), Some(2)) => Some(GraphemeClusterMode::Unicode),
+            (Some(2), Some(4)) => Some(GraphemeClusterMode::NoZwj),
+            (Some(4), Some(4)) => Some(GraphemeClusterMode::WcWidth),
+            _ => None,
+        };
+    }
+}
+
+/// Column (1-based) of the first cursor position reported in `answers`
+fn reported_column(answers: &str) -> Option<Unit> {
+    answers
+        .split('\x1b')
+        .filter_map(|s| s.strip_prefix('['))
+        .find_map(|seq| seq.strip_suffix('R')?.split_once(';')?.1.parse().ok())
+}
+
+/// Features already probed, by `TERM` and whether the widths were measured
+static FEATURES: Mutex<Vec<(String, bool, Option<Features>)>> = Mutex::new(Vec::new());
+
+/// Probe the terminal (once per `TERM`) in raw mode.
+///
+/// The widths are not measured when `widths` is false (texts of
+/// [`PROBE_WIDTHS`] cannot be written with a non UTF-8 encoding).
+fn probe_features(tty_in: RawFd, tty_out: RawFd, widths: bool) -> Option<Features> {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let mut cache = FEATURES.lock().unwrap();
+    if let Some((.., features)) = cache.iter().find(|(t, w, _)| *t == term && *w == widths) {
+        return *features;
+    }
+    let features = match query_features(tty_in, tty_out, widths) {
+        Ok(features) => features,
+        Err(e) => {
+            debug!(target: "rustyline", "cannot probe terminal: {}", e);
+            return None;
+        }
+    };
+    debug!(target: "rustyline", "terminal features: {:?}", features);
+    cache.push((term, widths, features));
+    features
+}
+
+fn query_features(tty_in: RawFd, tty_out: RawFd, widths: bool) -> nix::Result<Option<Features>> {
+    if wait_input(tty_in, 0)? {
+        debug!(target: "rustyline", "cannot probe terminal while input is pending");
+        return Ok(None);
+    }
+    let cpr = if widths { CPR } else { "" };
+    let Some(answers) = query(tty_in, tty_out, &format!("{PROBE_MODES}{cpr}{DA1}"))? else {
+        return Ok(None);
+    };
+    let Some(mut features) = Features::parse(&answers) else {
+        return Ok(None);
+    };
+    // measure from column 0 of a new line like `move_cursor_at_leftmost`
+    match reported_column(&answers) {
+        Some(1) => {}
+        Some(_) => write_all(tty_out, "\n")?,
+        None => return Ok(Some(features)),
+    }
+    if let Some(measured) = query(tty_in, tty_out, &format!("{PROBE_WIDTHS}{DA1}"))?
+        .as_deref()
+        .and_then(Features::parse)
+    {
+        features.ambiguous_width = measured.ambiguous_width;
+        features.grapheme_cluster_mode = measured.grapheme_cluster_mode;
+    }
+    Ok(Some(features))
+}
+
+/// Tell if some input is available within `ms` milliseconds
+fn wait_input(tty_in: RawFd, ms: i64) -> nix::Result<bool> {
+    let mut readfds = FdSet::new();
+    readfds.insert(unsafe { BorrowedFd::borrow_raw(tty_in) });
+    let mut timeout = nix::sys::time::TimeVal::milliseconds(ms);
+    select::select(None, Some(&mut readfds), None, None, Some(&mut timeout)).map(|n| n > 0)
+}
+
+/// Write `probe` and read the answers until the DA1 one
+fn query(tty_in: RawFd, tty_out: RawFd, probe: &str) -> nix::Result<Option<String>> {
+    write_all(tty_out, probe)?;
+    let mut answers = vec![];
+    let mut byte = [0; 1];
+    // read byte by byte to not consume any key typed after the answers
+    while answers.len() < 256 && wait_input(tty_in, 100)? {
+        if read(tty_in, &mut byte)? == 0 {
+            break;
+        }
+        answers.push(byte[0]);
+        if byte[0] == b'c' {
+            let answers = String::from_utf8_lossy(&answers);
+            if Features::parse(&answers).is_some() {
+                return Ok(Some(answers.into_owned()));
+            }
+        }
+    }
+    Ok(None)
+}
+
 pub struct PosixCursorGuard(RawFd);
 
 impl Drop for PosixCursorGuard {
@@ -1298,9 +2139,15 @@ pub struct PosixTerminal {
     close_on_drop: bool,
     pub(crate) color_mode: ColorMode,
     grapheme_cluster_mode: GraphemeClusterMode,
+    // replaced by the probed one
+    detect_grapheme_cluster_mode: bool,
+    // detected when `None`
+    pub(crate) ambiguous_width: Option<AmbiguousWidth>,
     tab_stop: u8,
-    bell_style: BellStyle,
+    pub(crate) bell_style: BellStyle,
     enable_bracketed_paste: bool,
+    enable_synchronized_output: bool,
+    enable_focus_events: bool,
     raw_mode: Arc<AtomicBool>,
     // external print reader
     pipe_reader: Option<PipeReader>,
@@ -1308,14 +2155,46 @@ pub struct PosixTerminal {
     pipe_writer: Option<PipeWriter>,
     sig: Option<Sig>,
     enable_signals: bool,
+    caps: Capabilities,
+    serial_baud_rate: Option<u32>,
+    // probe the terminal on the next `enable_raw_mode`
+    probe: bool,
+    features: Option<Features>,
+    passthrough: Passthrough,
+    // simulated terminal size
+    headless: Option<(u16, u16)>,
+    #[cfg(feature = "with-encoding")]
+    encoding: Option<&'static encoding_rs::Encoding>,
 }
 
 impl PosixTerminal {
-    fn colors_enabled(&self) -> bool {
-        match self.color_mode {
-            ColorMode::Enabled => self.is_out_a_tty,
-            ColorMode::Forced => true,
-            ColorMode::Disabled => false,
+    /// Use `input` and `output` instead of stdin and stdout (or `/dev/tty`).
+    /// Both are closed when the terminal is dropped.
+    pub(crate) fn set_fds(&mut self, input: OwnedFd, output: OwnedFd) -> Result<()> {
+        if self.close_on_drop {
+            self.close_fds();
+        }
+        self.tty_in = input.into_raw_fd();
+        self.tty_out = output.into_raw_fd();
+        let headless = self.headless.is_some();
+        self.is_in_a_tty = headless || is_a_tty(self.tty_in);
+        self.is_out_a_tty = headless || is_a_tty(self.tty_out);
+        self.close_on_drop = true;
+        if self.sig.is_none()
+            && !self.unsupported
+            && !headless
+            && self.is_in_a_tty
+            && self.is_out_a_tty
+        {
+            self.sig = Some(Sig::install_sigwinch_handler()?);
+        }
+        Ok(())
+    }
+
+    fn close_fds(&self) {
+        let _ = close(self.tty_in);
+        if self.tty_out != self.tty_in {
+            let _ = close(self.tty_out);
         }
     }
 }
@@ -1332,13 +2211,20 @@ impl Term for PosixTerminal {
     fn new(
         color_mode: ColorMode,
         grapheme_cluster_mode: GraphemeClusterMode,
+        detect_grapheme_cluster_mode: bool,
         behavior: Behavior,
         tab_stop: u8,
         bell_style: BellStyle,
         enable_bracketed_paste: bool,
+        enable_synchronized_output: bool,
         enable_signals: bool,
+        serial_baud_rate: Option<u32>,
+        probe: bool,
+        passthrough: Passthrough,
+        enable_focus_events: bool,
+        headless: Option<(u16, u16)>,
     ) -> Result<Self> {
-        let (tty_in, is_in_a_tty, tty_out, is_out_a_tty, close_on_drop) =
+        let (tty_in, mut is_in_a_tty, tty_out, mut is_out_a_tty, close_on_drop) =
             if behavior == Behavior::PreferTerm {
                 let tty = OpenOptions::new().read(true).write(true).open("/dev/tty");
                 if let Ok(tty) = tty {
@@ -1363,12 +2249,17 @@ impl Term for PosixTerminal {
                     false,
                 )
             };
-        let unsupported = super::is_unsupported_term();
-        let sig = if !unsupported && is_in_a_tty && is_out_a_tty {
+        let unsupported = headless.is_none() && super::is_unsupported_term();
+        let sig = if !unsupported && headless.is_none() && is_in_a_tty && is_out_a_tty {
             Some(Sig::install_sigwinch_handler()?)
         } else {
             None
         };
+        if headless.is_some() {
+            is_in_a_tty = true;
+            is_out_a_tty = true;
+        }
+        let caps = Capabilities::load();
         Ok(Self {
             unsupported,
             tty_in,
@@ -1378,14 +2269,26 @@ impl Term for PosixTerminal {
             close_on_drop,
             color_mode,
             grapheme_cluster_mode,
+            detect_grapheme_cluster_mode,
+            ambiguous_width: None,
             tab_stop,
             bell_style,
-            enable_bracketed_paste,
+            enable_bracketed_paste: enable_bracketed_paste && !caps.dumb,
+            enable_synchronized_output: enable_synchronized_output && !caps.dumb,
+            enable_focus_events: enable_focus_events && !caps.dumb,
             raw_mode: Arc::new(AtomicBool::new(false)),
             pipe_reader: None,
             pipe_writer: None,
             sig,
             enable_signals,
+            probe: probe && headless.is_none() && is_out_a_tty && !caps.dumb,
+            caps,
+            serial_baud_rate,
+            features: None,
+            passthrough: detect_passthrough(passthrough),
+            headless,
+            #[cfg(feature = "with-encoding")]
+            encoding: encoding::locale_encoding(),
         })
     }
 
@@ -1405,6 +2308,14 @@ impl Term for PosixTerminal {
         self.is_out_a_tty
     }
 
+    fn colors_enabled(&self) -> bool {
+        match self.color_mode {
+            ColorMode::Enabled => self.is_out_a_tty && self.caps.has_colors(),
+            ColorMode::Forced => true,
+            ColorMode::Disabled => false,
+        }
+    }
+
     // Interactive loop:
 
     fn enable_raw_mode(&mut self) -> Result<(Self::Mode, PosixKeyMap)> {
@@ -1412,31 +2323,69 @@ impl Term for PosixTerminal {
         if !self.is_in_a_tty {
             return Err(ENOTTY.into());
         }
-        let (original_mode, key_map) = termios_::enable_raw_mode(self.tty_in, self.enable_signals)?;
+        let (original_mode, key_map) = if self.headless.is_some() {
+            (None, headless_key_map())
+        } else {
+            let (original_mode, key_map) = termios_::enable_raw_mode(
+                self.tty_in,
+                self.enable_signals,
+                self.serial_baud_rate.is_some(),
+            )?;
+            (Some(original_mode), key_map)
+        };
 
         self.raw_mode.store(true, Ordering::SeqCst);
+        if self.probe {
+            self.probe = false;
+            #[cfg(feature = "with-encoding")]
+            let widths = self.encoding.is_none();
+            #[cfg(not(feature = "with-encoding"))]
+            let widths = true;
+            self.features = probe_features(self.tty_in, self.tty_out, widths);
+        }
         // enable bracketed paste
-        let out = if !self.enable_bracketed_paste {
-            None
-        } else if let Err(e) = write_all(self.tty_out, BRACKETED_PASTE_ON) {
-            debug!(target: "rustyline", "Cannot enable bracketed paste: {}", e);
-            None
-        } else {
-            Some(self.tty_out)
-        };
+        let bracketed_paste =
+            if !self.enable_bracketed_paste || self.features.is_some_and(|f| !f.bracketed_paste) {
+                false
+            } else if let Err(e) = write_all(self.tty_out, BRACKETED_PASTE_ON) {
+                debug!(target: "rustyline", "Cannot enable bracketed paste: {}", e);
+                false
+            } else {
+                true
+            };
+        // enable extended keys only when the terminal knows about them
+        let extended_keys = self.features.is_some_and(|f| f.extended_keys)
+            && write_all(self.tty_out, EXTENDED_KEYS_ON).is_ok();
+        let focus_events =
+            self.enable_focus_events && write_all(self.tty_out, FOCUS_EVENTS_ON).is_ok();
 
         // when all ExternalPrinter are dropped there is no need to use `pipe_reader`
         if Arc::strong_count(&self.raw_mode) == 1 {
             self.pipe_writer = None;
             self.pipe_reader = None;
         }
+        let print_queue = self.pipe_writer.as_ref().map(|writer| writer.1.clone());
+        if let Some((ref writer, ref queue)) = self.pipe_writer {
+            if queue.activate() {
+                // restore the region above the prompt
+                let mut writer = writer.lock().unwrap();
+                writer.write_all(b"m")?;
+                writer.flush()?;
+            }
+        }
 
         Ok((
             PosixMode {
                 termios: original_mode,
                 tty_in: self.tty_in,
-                tty_out: out,
+                tty_out: self.tty_out,
+                bracketed_paste,
+                extended_keys,
+                focus_events,
                 raw_mode: self.raw_mode.clone(),
+                print_queue,
+                #[cfg(feature = "with-encoding")]
+                encoding: self.encoding,
             },
             key_map,
         ))
@@ -1449,7 +2398,7 @@ impl Term for PosixTerminal {
         config: &Config,
         key_map: PosixKeyMap,
     ) -> PosixRawReader {
-        PosixRawReader::new(
+        let mut reader = PosixRawReader::new(
             self.tty_in,
             self.sig.as_ref().map(|s| s.pipe),
             buffer,
@@ -1458,17 +2407,47 @@ impl Term for PosixTerminal {
             self.pipe_reader.clone(),
             #[cfg(target_os = "macos")]
             self.close_on_drop,
-        )
+        );
+        if self.headless.is_some() && reader.tty_in.buffer().is_empty() {
+            // no read ahead: the input following an accepted line (which
+            // may be piped at once) is left to the next `readline`
+            reader.tty_in = BufReader::with_capacity(1, reader.tty_in.into_inner());
+        }
+        #[cfg(feature = "with-encoding")]
+        {
+            reader.decoder = self.encoding.map(encoding::Decoder::new);
+        }
+        reader
     }
 
     fn create_writer(&self) -> PosixRenderer {
-        PosixRenderer::new(
+        let mut writer = PosixRenderer::new(
             self.tty_out,
             Unit::from(self.tab_stop),
             self.colors_enabled(),
-            self.grapheme_cluster_mode,
+            self.features
+                .and_then(|f| f.grapheme_cluster_mode)
+                .filter(|_| self.detect_grapheme_cluster_mode)
+                .unwrap_or(self.grapheme_cluster_mode),
             self.bell_style,
-        )
+            self.enable_synchronized_output && self.features.is_none_or(|f| f.synchronized_output),
+            self.caps.clone(),
+            self.serial_baud_rate,
+        );
+        writer.passthrough = self.passthrough;
+        writer.ambiguous_width = self
+            .ambiguous_width
+            .or(self.features.and_then(|f| f.ambiguous_width))
+            .unwrap_or_default();
+        if let Some((cols, rows)) = self.headless {
+            writer.size = Some((cols, rows));
+            writer.cols = cols;
+        }
+        #[cfg(feature = "with-encoding")]
+        {
+            writer.encoding = self.encoding;
+        }
+        writer
     }
 
     fn writeln(&self) -> Result<()> {
@@ -1476,46 +2455,63 @@ impl Term for PosixTerminal {
         Ok(())
     }
 
-    fn create_external_printer(&mut self) -> Result<ExternalPrinter> {
+    fn create_external_printer(&mut self, config: &Config) -> Result<ExternalPrinter> {
         use nix::unistd::pipe;
         if let Some(ref writer) = self.pipe_writer {
             return Ok(ExternalPrinter {
                 writer: writer.clone(),
                 raw_mode: self.raw_mode.clone(),
                 tty_out: self.tty_out,
+                #[cfg(feature = "with-encoding")]
+                encoding: self.encoding,
             });
         }
         if self.unsupported || !self.is_input_tty() || !self.is_output_tty() {
             return Err(nix::Error::ENOTTY.into());
         }
-        let (sender, receiver) = mpsc::sync_channel(1); // TODO validate: bound
+        let queue = Arc::new(PrintQueue::new(config));
         let (r, w) = pipe()?;
-        let reader = Arc::new(Mutex::new((r.into(), receiver)));
-        let writer = (Arc::new(Mutex::new(w.into())), sender);
+        let reader = Arc::new(Mutex::new((r.into(), queue.clone())));
+        let writer = (Arc::new(Mutex::new(w.into())), queue);
         self.pipe_reader.replace(reader);
         self.pipe_writer.replace(writer.clone());
         Ok(ExternalPrinter {
             writer,
             raw_mode: self.raw_mode.clone(),
             tty_out: self.tty_out,
+            #[cfg(feature = "with-encoding")]
+            encoding: self.encoding,
         })
     }
 
     fn set_cursor_visibility(&mut self, visible: bool) -> Result<Option<PosixCursorGuard>> {
-        if self.is_out_a_tty {
+        if self.is_out_a_tty && !self.caps.dumb {
             set_cursor_visibility(self.tty_out, visible)
         } else {
             Ok(None)
         }
     }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        if self.is_out_a_tty && !self.caps.dumb {
+            write_all(self.tty_out, &title_seq(title))?;
+        }
+        Ok(())
+    }
+
+    fn save_title(&mut self, save: bool) -> Result<()> {
+        if self.is_out_a_tty && !self.caps.dumb {
+            write_all(self.tty_out, title_stack_seq(save))?;
+        }
+        Ok(())
+    }
 }
 
 #[expect(unused_must_use)]
 impl Drop for PosixTerminal {
     fn drop(&mut self) {
         if self.close_on_drop {
-            close(self.tty_in);
-            debug_assert_eq!(self.tty_in, self.tty_out);
+            self.close_fds();
         }
         if let Some(sig) = self.sig.take() {
             sig.uninstall_sigwinch_handler();
@@ -1528,24 +2524,77 @@ pub struct ExternalPrinter {
     writer: PipeWriter,
     raw_mode: Arc<AtomicBool>,
     tty_out: RawFd,
+    #[cfg(feature = "with-encoding")]
+    encoding: Option<&'static encoding_rs::Encoding>,
 }
 
-impl super::ExternalPrinter for ExternalPrinter {
-    fn print(&mut self, msg: String) -> Result<()> {
-        // write directly to stdout/stderr while not in raw mode
-        if !self.raw_mode.load(Ordering::SeqCst) {
-            write_all(self.tty_out, msg.as_str())?;
+/// Print an external message directly to stdout/stderr (while no line is
+/// edited)
+fn print_event(
+    tty_out: RawFd,
+    event: Event,
+    #[cfg(feature = "with-encoding")] encoding: Option<&'static encoding_rs::Encoding>,
+) -> Result<()> {
+    let (fd, msg) = match event {
+        Event::ExternalPrint(msg) => (tty_out, msg),
+        Event::ExternalEprint(msg) => (libc::STDERR_FILENO, msg),
+        _ => return Ok(()),
+    };
+    #[cfg(feature = "with-encoding")]
+    if let Some(encoding) = encoding {
+        write_bytes(fd, &encoding::encode(encoding, &msg))?;
+        return Ok(());
+    }
+    write_all(fd, msg.as_str())?;
+    Ok(())
+}
+
+impl ExternalPrinter {
+    fn send(&mut self, event: Event) -> Result<()> {
+        // write directly to stdout/stderr while not in raw mode (the region
+        // is remembered by the queue)
+        let event = if !self.raw_mode.load(Ordering::SeqCst)
+            && !matches!(event, Event::ExternalRegion(_))
+        {
+            event
         } else if let Ok(mut writer) = self.writer.0.lock() {
-            self.writer
-                .1
-                .send(msg)
-                .map_err(|_| io::Error::from(ErrorKind::Other))?; // FIXME
-            writer.write_all(b"m")?;
-            writer.flush()?;
+            match self.writer.1.push(event) {
+                Ok(true) => {
+                    writer.write_all(b"m")?;
+                    writer.flush()?;
+                    return Ok(());
+                }
+                Ok(false) => return Ok(()),
+                // edition ended in the meantime
+                Err(event) => event,
+            }
         } else {
             return Err(io::Error::from(ErrorKind::Other).into()); // FIXME
-        }
-        Ok(())
+        };
+        print_event(
+            self.tty_out,
+            event,
+            #[cfg(feature = "with-encoding")]
+            self.encoding,
+        )
+    }
+}
+
+impl super::ExternalPrinter for ExternalPrinter {
+    fn print(&mut self, msg: String) -> Result<()> {
+        self.send(Event::ExternalPrint(msg))
+    }
+
+    fn eprint(&mut self, msg: String) -> Result<()> {
+        self.send(Event::ExternalEprint(msg))
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.writer.1.dropped()
+    }
+
+    fn set_region(&mut self, lines: Vec<String>) -> Result<()> {
+        self.send(Event::ExternalRegion(lines))
     }
 }
 
@@ -1558,6 +2607,16 @@ pub fn suspend() -> Result<()> {
     Ok(())
 }
 
+/// Default special characters of a terminal line discipline
+fn headless_key_map() -> PosixKeyMap {
+    HashMap::from([
+        (KeyEvent::ctrl('D'), Cmd::EndOfFile),
+        (KeyEvent::ctrl('C'), Cmd::Interrupt),
+        (KeyEvent::ctrl('\\'), Cmd::Interrupt),
+        (KeyEvent::ctrl('Z'), Cmd::Suspend),
+    ])
+}
+
 #[cfg(not(feature = "termios"))]
 mod termios_ {
     use super::PosixKeyMap;
@@ -1570,7 +2629,11 @@ mod termios_ {
         let fd = unsafe { BorrowedFd::borrow_raw(tty_in) };
         Ok(termios::tcsetattr(fd, SetArg::TCSADRAIN, termios)?)
     }
-    pub fn enable_raw_mode(tty_in: RawFd, enable_signals: bool) -> Result<(Termios, PosixKeyMap)> {
+    pub fn enable_raw_mode(
+        tty_in: RawFd,
+        enable_signals: bool,
+        flow_control: bool,
+    ) -> Result<(Termios, PosixKeyMap)> {
         use nix::sys::termios::{ControlFlags, InputFlags, LocalFlags};
 
         let fd = unsafe { BorrowedFd::borrow_raw(tty_in) };
@@ -1583,6 +2646,10 @@ mod termios_ {
             | InputFlags::INPCK
             | InputFlags::ISTRIP
             | InputFlags::IXON);
+        if flow_control {
+            // software flow control (XON/XOFF), both ways
+            raw.input_flags |= InputFlags::IXON | InputFlags::IXOFF;
+        }
         // we don't want raw output, it turns newlines into straight line feeds
         // disable all output processing
         // raw.c_oflag = raw.c_oflag & !(OutputFlags::OPOST);
@@ -1633,13 +2700,21 @@ mod termios_ {
     pub fn disable_raw_mode(tty_in: RawFd, termios: &Termios) -> Result<()> {
         Ok(termios::tcsetattr(tty_in, termios::TCSADRAIN, termios)?)
     }
-    pub fn enable_raw_mode(tty_in: RawFd, enable_signals: bool) -> Result<(Termios, PosixKeyMap)> {
+    pub fn enable_raw_mode(
+        tty_in: RawFd,
+        enable_signals: bool,
+        flow_control: bool,
+    ) -> Result<(Termios, PosixKeyMap)> {
         let original_mode = Termios::from_fd(tty_in)?;
         let mut raw = original_mode;
         // disable BREAK interrupt, CR to NL conversion on input,
         // input parity check, strip high bit (bit 8), output flow control
         raw.c_iflag &=
             !(termios::BRKINT | termios::ICRNL | termios::INPCK | termios::ISTRIP | termios::IXON);
+        if flow_control {
+            // software flow control (XON/XOFF), both ways
+            raw.c_iflag |= termios::IXON | termios::IXOFF;
+        }
         // we don't want raw output, it turns newlines into straight line feeds
         // disable all output processing
         // raw.c_oflag = raw.c_oflag & !(OutputFlags::OPOST);
@@ -1681,9 +2756,9 @@ mod termios_ {
 
 #[cfg(test)]
 mod test {
-    use super::{Position, PosixRenderer, PosixTerminal, Renderer};
+    use super::{Capabilities, Features, Position, PosixRenderer, PosixTerminal, Renderer};
     use crate::config::BellStyle;
-    use crate::layout::GraphemeClusterMode;
+    use crate::layout::{AmbiguousWidth, GraphemeClusterMode};
     use crate::line_buffer::{LineBuffer, NoListener};
 
     #[test]
@@ -1695,12 +2770,48 @@ mod test {
             true,
             GraphemeClusterMode::default(),
             BellStyle::default(),
+            false,
+            Capabilities::default(),
+            None,
         );
         let pos = out.calculate_position("\x1b[1;32m>>\x1b[0m ", Position::default());
         assert_eq!(3, pos.col);
         assert_eq!(0, pos.row);
     }
 
+    #[test]
+    fn read_large_paste() {
+        use super::{PosixRawReader, RawReader};
+        use crate::config::Config;
+        use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+        use std::io::Write;
+        use std::os::unix::io::AsRawFd;
+
+        let (rd, wr) = nix::unistd::pipe().unwrap();
+        let text = "fn main() {\r\n    println!(\"\u{e9}\");\r\n}\r\n".repeat(2_000);
+        let input = format!("\x1b[200~{text}\x1b[201~x");
+        let writer = std::thread::spawn(move || {
+            std::fs::File::from(wr).write_all(input.as_bytes()).unwrap();
+        });
+        let mut rdr = PosixRawReader::new(
+            rd.as_raw_fd(),
+            None,
+            None,
+            &Config::default(),
+            Default::default(),
+            None,
+            #[cfg(target_os = "macos")]
+            false,
+        );
+        assert_eq!(
+            E(K::BracketedPasteStart, M::NONE),
+            rdr.next_key(false).unwrap()
+        );
+        assert_eq!(text.replace("\r\n", "\n"), rdr.read_pasted_text().unwrap());
+        assert_eq!(E(K::Char('x'), M::NONE), rdr.next_key(false).unwrap());
+        writer.join().unwrap();
+    }
+
     #[test]
     fn test_send() {
         fn assert_send<T: Send>() {}
@@ -1721,6 +2832,9 @@ mod test {
             true,
             GraphemeClusterMode::default(),
             BellStyle::default(),
+            false,
+            Capabilities::default(),
+            None,
         );
         let prompt = "> ";
         let default_prompt = true;
@@ -1742,8 +2856,304 @@ mod test {
             .unwrap();
         #[rustfmt::skip]
         assert_eq!(
-            "\r\u{1b}[K> aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\u{1b}[1C",
+            "\r\u{1b}[K> aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\u{1b}[C",
             out.buffer
         );
     }
+
+    #[test]
+    fn synchronized_output() {
+        let mut out = PosixRenderer::new(
+            libc::STDOUT_FILENO,
+            4,
+            true,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+            true,
+            Capabilities::default(),
+            None,
+        );
+        let prompt_size = out.calculate_position("> ", Position::default());
+        let line = LineBuffer::init("a", 1);
+        let layout = out.compute_layout(prompt_size, true, &line, None);
+        out.refresh_line("> ", &line, None, &layout, &layout, None)
+            .unwrap();
+        assert_eq!(
+            "\u{1b}[?2026h\r\u{1b}[K> a\r\u{1b}[3C\u{1b}[?2026l",
+            out.buffer
+        );
+    }
+
+    #[test]
+    fn dumb_terminal() {
+        let mut out = PosixRenderer::new(
+            libc::STDOUT_FILENO,
+            4,
+            false,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+            false,
+            Capabilities {
+                #[cfg(feature = "terminfo")]
+                info: None,
+                dumb: true,
+            },
+            None,
+        );
+        let prompt = "\x1b[1;32m>\x1b[0m ";
+        let prompt_size = out.calculate_position(prompt, Position::default());
+        let old_layout = out.compute_layout(prompt_size, true, &LineBuffer::init("", 0), None);
+        let line = LineBuffer::init("abc", 1);
+        let new_layout = out.compute_layout(prompt_size, true, &line, None);
+        out.refresh_line(prompt, &line, None, &old_layout, &new_layout, None)
+            .unwrap();
+        assert_eq!("\r> abc\r> a", out.buffer);
+
+        out.move_cursor(new_layout.cursor, new_layout.end).unwrap();
+        assert_eq!("bc", out.buffer);
+        out.move_cursor(new_layout.end, new_layout.cursor).unwrap();
+        assert_eq!("\r> a", out.buffer);
+
+        // shorter line: leftovers are blanked
+        let line = LineBuffer::init("a", 1);
+        let layout = out.compute_layout(prompt_size, true, &line, None);
+        out.refresh_line(prompt, &line, None, &new_layout, &layout, None)
+            .unwrap();
+        assert_eq!("\r> a  \r> a", out.buffer);
+    }
+
+    #[test]
+    fn write_paced() {
+        use std::os::unix::io::AsRawFd;
+        use std::time::Instant;
+
+        let (r, w) = nix::unistd::pipe().unwrap();
+        let start = Instant::now();
+        // 960 bytes per second
+        super::write_paced(w.as_raw_fd(), "x".repeat(96).as_bytes(), 9600).unwrap();
+        assert!(start.elapsed().as_millis() >= 90);
+        let mut buf = [0; 128];
+        let n = nix::unistd::read(r.as_raw_fd(), &mut buf).unwrap();
+        assert_eq!(96, n);
+    }
+
+    #[test]
+    fn features() {
+        assert_eq!(None, Features::parse(""));
+        assert_eq!(None, Features::parse("\x1b[?2004;2$y\x1b[?64;1"));
+        assert_eq!(
+            Some(Features {
+                bracketed_paste: true,
+                synchronized_output: true,
+                extended_keys: true,
+                ambiguous_width: None,
+                grapheme_cluster_mode: None,
+            }),
+            Features::parse("\x1b[?2004;2$y\x1b[?2026;2$y\x1b[>4;0m\x1b[?64;1;22c")
+        );
+        assert_eq!(
+            Some(Features {
+                bracketed_paste: false,
+                synchronized_output: false,
+                extended_keys: false,
+                ambiguous_width: None,
+                grapheme_cluster_mode: None,
+            }),
+            Features::parse("\x1b[?2004;0$y\x1b[?2026;4$y\x1b[?1;2c")
+        );
+        // no DECRQM support
+        assert_eq!(
+            Some(Features {
+                bracketed_paste: true,
+                synchronized_output: false,
+                extended_keys: false,
+                ambiguous_width: None,
+                grapheme_cluster_mode: None,
+            }),
+            Features::parse("\x1b[?6c")
+        );
+        // cursor moved by two columns
+        assert_eq!(
+            Some(Some(AmbiguousWidth::Wide)),
+            Features::parse("\x1b[3;1R\x1b[3;3R\x1b[?6c").map(|f| f.ambiguous_width)
+        );
+        assert_eq!(
+            Some(Some(AmbiguousWidth::Narrow)),
+            Features::parse("\x1b[3;5R\x1b[3;6R\x1b[?6c").map(|f| f.ambiguous_width)
+        );
+        // wrapped
+        assert_eq!(
+            Some(None),
+            Features::parse("\x1b[3;80R\x1b[4;3R\x1b[?6c").map(|f| f.ambiguous_width)
+        );
+        // skin tone modifier supported, not ZWJ sequences
+        let features = Features::parse("\x1b[1;1R\x1b[1;2R\x1b[1;4R\x1b[1;8R\x1b[?6c").unwrap();
+        assert_eq!(Some(AmbiguousWidth::Narrow), features.ambiguous_width);
+        assert_eq!(
+            Some(GraphemeClusterMode::NoZwj),
+            features.grapheme_cluster_mode
+        );
+    }
+
+    /// Probe a fake terminal which sends `answers` to the successive queries,
+    /// returning the features and what has been written to the terminal
+    fn probe(widths: bool, answers: &[&str]) -> (Option<Features>, String) {
+        use std::io::{Read, Write};
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let (tty, mut fake) = UnixStream::pair().unwrap();
+        let answers: Vec<String> = answers.iter().map(|&s| s.to_owned()).collect();
+        let terminal = std::thread::spawn(move || {
+            let mut written = String::new();
+            let mut answers = answers.into_iter();
+            let mut buf = [0; 256];
+            loop {
+                let n = fake.read(&mut buf).unwrap();
+                if n == 0 {
+                    return written;
+                }
+                written.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+                if written.ends_with(super::DA1) {
+                    if let Some(answer) = answers.next() {
+                        fake.write_all(answer.as_bytes()).unwrap();
+                    }
+                }
+            }
+        });
+        let fd = tty.as_raw_fd();
+        let features = super::query_features(fd, fd, widths).unwrap();
+        drop(tty);
+        (features, terminal.join().unwrap())
+    }
+
+    #[test]
+    fn query_features() {
+        use super::{CPR, DA1, PROBE_MODES, PROBE_WIDTHS};
+        // with a non UTF-8 encoding: no width measured
+        let (features, written) = probe(false, &["\x1b[?6c"]);
+        assert_eq!(format!("{PROBE_MODES}{DA1}"), written);
+        assert!(written.is_ascii());
+        assert_eq!(None, features.unwrap().ambiguous_width);
+        // measured from column 0 of a new line
+        let (features, written) = probe(
+            true,
+            &[
+                "\x1b[2;5R\x1b[?6c",
+                "\x1b[3;1R\x1b[3;2R\x1b[3;4R\x1b[3;6R\x1b[?6c",
+            ],
+        );
+        assert_eq!(
+            format!("{PROBE_MODES}{CPR}{DA1}\n{PROBE_WIDTHS}{DA1}"),
+            written
+        );
+        let features = features.unwrap();
+        assert_eq!(Some(AmbiguousWidth::Narrow), features.ambiguous_width);
+        assert_eq!(
+            Some(GraphemeClusterMode::Unicode),
+            features.grapheme_cluster_mode
+        );
+        // already at column 0
+        let (_, written) = probe(true, &["\x1b[2;1R\x1b[?6c", "\x1b[?6c"]);
+        assert_eq!(
+            format!("{PROBE_MODES}{CPR}{DA1}{PROBE_WIDTHS}{DA1}"),
+            written
+        );
+    }
+
+    #[test]
+    fn set_fds() {
+        use crate::config::{Behavior, ColorMode, Passthrough};
+        use crate::tty::Term;
+
+        let mut term = PosixTerminal::new(
+            ColorMode::Enabled,
+            GraphemeClusterMode::default(),
+            false,
+            Behavior::Stdio,
+            4,
+            BellStyle::default(),
+            false,
+            false,
+            false,
+            None,
+            false,
+            Passthrough::Disabled,
+            false,
+            None,
+        )
+        .unwrap();
+        let (r, w) = nix::unistd::pipe().unwrap();
+        term.set_fds(r, w).unwrap();
+        assert!(!term.is_input_tty());
+        assert!(!term.is_output_tty());
+        term.writeln().unwrap();
+    }
+
+    #[test]
+    fn headless() {
+        use crate::config::{Behavior, ColorMode, Config, Passthrough};
+        use crate::keys::KeyEvent as E;
+        use crate::tty::{RawMode, RawReader, Term};
+        use crate::Cmd;
+        use std::io::Read;
+
+        let mut term = PosixTerminal::new(
+            ColorMode::Disabled,
+            GraphemeClusterMode::default(),
+            false,
+            Behavior::Stdio,
+            4,
+            BellStyle::default(),
+            false,
+            false,
+            false,
+            None,
+            true,
+            Passthrough::Disabled,
+            false,
+            Some((40, 10)),
+        )
+        .unwrap();
+        let (in_r, in_w) = nix::unistd::pipe().unwrap();
+        let (out_r, out_w) = nix::unistd::pipe().unwrap();
+        term.set_fds(in_r, out_w).unwrap();
+        assert!(!term.is_unsupported());
+        assert!(term.is_input_tty());
+        assert!(term.is_output_tty());
+        let (mode, key_map) = term.enable_raw_mode().unwrap();
+        assert_eq!(Some(&Cmd::EndOfFile), key_map.get(&E::ctrl('D')));
+        let mut out = term.create_writer();
+        assert_eq!((40, 10), (out.get_columns(), out.get_rows()));
+        out.update_size();
+        assert_eq!(40, out.get_columns());
+        out.write_and_flush("> ").unwrap();
+        nix::unistd::write(&in_w, b"a").unwrap();
+        let mut rdr = term.create_reader(None, &Config::default(), key_map);
+        assert_eq!(E::from('a'), rdr.next_key(false).unwrap());
+        mode.disable_raw_mode().unwrap();
+        drop(term);
+        let mut output = String::new();
+        std::fs::File::from(out_r)
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!("> ", output);
+    }
+
+    #[test]
+    #[cfg(feature = "fuzzing")]
+    fn decode() {
+        use super::{decode_keys, decode_paste};
+        use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+
+        assert_eq!(
+            vec![E::from('a'), E(K::Right, M::CTRL), E::from('é')],
+            decode_keys(b"a\x1b[1;5C\xc3\xa9\x1b[")
+        );
+        assert_eq!(vec![E::from('a')], decode_keys(b"a\xff"));
+        let big = vec![b'a'; 1 << 20];
+        assert_eq!(1 << 20, decode_keys(&big).len());
+        assert_eq!(Some("a\nb".to_owned()), decode_paste(b"a\r\nb\x1b[201~"));
+        assert_eq!(None, decode_paste(&big));
+    }
 }
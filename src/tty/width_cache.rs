@@ -0,0 +1,265 @@
+//! Memoized width computations: the prompt and the line are not segmented
+//! and measured again from the start on each keystroke.
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::width;
+use crate::layout::{ascii_run, AmbiguousWidth, GraphemeClusterMode, Position, Unit};
+
+/// Maximum number of texts whose position is memoized
+const MAX_MEASURES: usize = 4;
+/// Maximum number of grapheme clusters whose width is memoized
+const MAX_GRAPHEMES: usize = 1024;
+
+/// Position reached by `text` (measured from `orig`) just before its last
+/// grapheme cluster, which may be extended by the next characters.
+#[derive(Debug)]
+struct Measure {
+    orig: Position,
+    text: String,
+    /// Byte offset of the last grapheme cluster in `text`
+    last: usize,
+    pos: Position,
+    esc_seq: u8,
+}
+
+/// Widths of the grapheme clusters and positions of the texts (prompts,
+/// lines) measured by a renderer
+#[derive(Debug, Default)]
+pub(super) struct WidthCache {
+    /// Clusters of more than one `char` (emoji sequences, combining marks)
+    graphemes: RefCell<HashMap<Box<str>, Unit>>,
+    /// Most recently used first
+    measures: RefCell<Vec<Measure>>,
+}
+
+impl WidthCache {
+    /// Forget the positions (when the number of columns changes)
+    pub fn clear(&self) {
+        self.measures.borrow_mut().clear();
+    }
+
+    /// Same as [`GraphemeClusterMode::width_with`]
+    pub fn grapheme_width(&self, gcm: GraphemeClusterMode, aw: AmbiguousWidth, g: &str) -> Unit {
+        let mut chars = g.chars();
+        if chars.next().is_none() || chars.next().is_none() {
+            return gcm.width_with(g, aw);
+        }
+        let mut graphemes = self.graphemes.borrow_mut();
+        if let Some(&w) = graphemes.get(g) {
+            return w;
+        }
+        if graphemes.len() >= MAX_GRAPHEMES {
+            graphemes.clear();
+        }
+        let w = gcm.width_with(g, aw);
+        graphemes.insert(g.into(), w);
+        w
+    }
+
+    /// Same as [`Renderer::calculate_position`](super::Renderer::calculate_position)
+    /// for a `cols` width terminal, resuming the measure of a previous text
+    /// when `s` starts with it.
+    pub fn calculate_position(
+        &self,
+        s: &str,
+        orig: Position,
+        cols: Unit,
+        tab_stop: Unit,
+        gcm: GraphemeClusterMode,
+        aw: AmbiguousWidth,
+    ) -> Position {
+        let mut measures = self.measures.borrow_mut();
+        let found = measures
+            .iter()
+            .position(|m| m.orig == orig && s.starts_with(m.text.as_str()));
+        let mut m = match found {
+            Some(i) => measures.remove(i),
+            None => {
+                measures.truncate(MAX_MEASURES - 1);
+                Measure {
+                    orig,
+                    text: String::new(),
+                    last: 0,
+                    pos: orig,
+                    esc_seq: 0,
+                }
+            }
+        };
+        let start = m.last;
+        let (mut pos, mut esc_seq) = (m.pos, m.esc_seq);
+        let mut offset = start;
+        while offset < s.len() {
+            let run = if esc_seq == 0 && cols > 0 {
+                ascii_run(&s.as_bytes()[offset..])
+            } else {
+                0
+            };
+            if run > 0 {
+                let before = advance(pos, run - 1, cols);
+                (m.last, m.pos, m.esc_seq) = (offset + run - 1, before, 0);
+                pos = advance(before, 1, cols);
+                offset += run;
+            }
+            let mut next = s.len();
+            for (i, g) in s[offset..].grapheme_indices(true) {
+                (m.last, m.pos, m.esc_seq) = (offset + i, pos, esc_seq);
+                if g == "\n" {
+                    pos.row += 1;
+                    pos.col = 0;
+                } else {
+                    let cw = if g == "\t" {
+                        tab_stop - (pos.col % tab_stop)
+                    } else if esc_seq != 0 || g == "\x1b" {
+                        width(gcm, aw, g, &mut esc_seq)
+                    } else {
+                        self.grapheme_width(gcm, aw, g)
+                    };
+                    pos.col += cw;
+                    if pos.col > cols {
+                        pos.row += 1;
+                        pos.col = cw;
+                    }
+                }
+                let end = offset + i + g.len();
+                if esc_seq == 0 && cols > 0 && ascii_run(&s.as_bytes()[end..]) > 0 {
+                    next = end;
+                    break;
+                }
+            }
+            offset = next;
+        }
+        m.text.truncate(start);
+        m.text.push_str(&s[start..]);
+        measures.insert(0, m);
+        if pos.col == cols {
+            pos.col = 0;
+            pos.row += 1;
+        }
+        pos
+    }
+}
+
+/// Position reached by `n` one column wide characters written from `pos`
+fn advance(pos: Position, n: usize, cols: Unit) -> Position {
+    if pos.col > cols && n > 0 {
+        // after a character wider than the terminal
+        let pos = Position {
+            col: 0,
+            row: pos.row.saturating_add(1),
+        };
+        return advance(pos, n, cols);
+    }
+    let (col, cols) = (usize::from(pos.col) + n, usize::from(cols));
+    if col <= cols {
+        return Position {
+            col: col as Unit,
+            row: pos.row,
+        };
+    }
+    let over = col - cols - 1;
+    Position {
+        col: (over % cols + 1) as Unit,
+        row: pos
+            .row
+            .saturating_add(Unit::try_from(over / cols + 1).unwrap_or(Unit::MAX)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WidthCache;
+    use crate::layout::{AmbiguousWidth, GraphemeClusterMode, Position};
+
+    #[test]
+    fn calculate_position() {
+        let (gcm, aw) = (GraphemeClusterMode::Unicode, AmbiguousWidth::Narrow);
+        let texts = [
+            "> ",
+            "> abc",
+            "> abcdefghijkl",
+            "> a",
+            "> a\u{301}",
+            "> a\u{301}\u{1f469}",
+            "> a\u{301}\u{1f469}\u{200d}\u{1f680}",
+            "> a\u{301}\u{1f469}\u{200d}\u{1f680}\tb",
+            "> \x1b[1;31m",
+            "> \x1b[1;31mabc\x1b[0m",
+            "\u{1f1eb}\u{1f1f7}\u{1f1eb}",
+            "\u{1f1eb}\u{1f1f7}\u{1f1eb}\u{1f1f7}",
+            "ab\ncd",
+            "\u{4e2d}\u{6587}\u{4e2d}\u{6587}\u{4e2d}",
+        ];
+        let cache = WidthCache::default();
+        for orig in [Position::default(), Position { col: 3, row: 1 }] {
+            for text in texts {
+                for _ in 0..2 {
+                    let cached = cache.calculate_position(text, orig, 10, 8, gcm, aw);
+                    let fresh =
+                        WidthCache::default().calculate_position(text, orig, 10, 8, gcm, aw);
+                    assert_eq!(fresh, cached, "{text:?}");
+                }
+            }
+        }
+        assert_eq!(
+            Position { col: 4, row: 0 },
+            cache.calculate_position(
+                "a\u{301}\u{1f469}\u{200d}\u{1f680}b",
+                Position::default(),
+                10,
+                8,
+                gcm,
+                aw
+            )
+        );
+    }
+
+    #[test]
+    fn ascii_runs() {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        let (gcm, aw) = (GraphemeClusterMode::Unicode, AmbiguousWidth::Narrow);
+        let long = "0123456789abcdefghijklmnopqrstuvwxyz";
+        let texts = [
+            long.to_owned(),
+            format!("{long}\u{301}"),
+            format!("\u{4e2d}{long}\u{4e2d}{long}#\u{fe0f}\u{20e3}"),
+            format!("{long}\n{long}"),
+        ];
+        for cols in [1, 7, 10, 36, 80] {
+            for text in &texts {
+                let mut expected = Position::default();
+                for g in text.graphemes(true) {
+                    if g == "\n" {
+                        expected = Position {
+                            col: 0,
+                            row: expected.row + 1,
+                        };
+                        continue;
+                    }
+                    let cw = gcm.width(g);
+                    expected.col += cw;
+                    if expected.col > cols {
+                        expected.row += 1;
+                        expected.col = cw;
+                    }
+                }
+                if expected.col == cols {
+                    expected.col = 0;
+                    expected.row += 1;
+                }
+                let cache = WidthCache::default();
+                for end in [long.len() / 2, text.len()] {
+                    cache.calculate_position(&text[..end], Position::default(), cols, 8, gcm, aw);
+                }
+                assert_eq!(
+                    expected,
+                    cache.calculate_position(text, Position::default(), cols, 8, gcm, aw),
+                    "{text:?} {cols}"
+                );
+            }
+        }
+    }
+}
@@ -2,13 +2,12 @@
 #![expect(clippy::try_err)] // suggested fix does not work (cannot infer...)
 
 use std::fs::OpenOptions;
-use std::io;
+use std::io::{self, Write};
 use std::mem;
 use std::os::windows::io::IntoRawHandle;
 use std::ptr;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use std::sync::Arc;
 
 use log::{debug, warn};
@@ -18,14 +17,22 @@ use windows_sys::Win32::System::Console as console;
 use windows_sys::Win32::System::Threading as threading;
 use windows_sys::Win32::UI::Input::KeyboardAndMouse;
 
-use super::{width, Event, RawMode, RawReader, Renderer, Term};
-use crate::config::{Behavior, BellStyle, ColorMode, Config};
-use crate::highlight::Highlighter;
-use crate::keys::{KeyCode as K, KeyEvent, Modifiers as M};
-use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use super::{
+    alternate_screen_seq, cursor_shape_seq, pasted_text, vt_key, width, width_cache::WidthCache,
+    Event, PrintQueue, RawMode, RawReader, Renderer, Term,
+};
+use crate::config::{Behavior, BellStyle, ColorMode, Config, CursorShape, Passthrough};
+use crate::highlight::{Background, Highlighter};
+use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
+use crate::layout::{AmbiguousWidth, GraphemeClusterMode, Layout, Position, Unit};
 use crate::line_buffer::LineBuffer;
 use crate::{error, Cmd, Result};
 
+const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
+const BRACKETED_PASTE_OFF: &str = "\x1b[?2004l";
+// delay between ESC and the rest of a VT sequence
+const ESCAPE_TIMEOUT_MS: u16 = 50;
+
 fn get_std_handle(fd: console::STD_HANDLE) -> Result<HANDLE> {
     let handle = unsafe { console::GetStdHandle(fd) };
     check_handle(handle)
@@ -86,17 +93,30 @@ pub struct ConsoleMode {
     conin: HANDLE,
     original_conout_mode: Option<console::CONSOLE_MODE>,
     conout: HANDLE,
+    bracketed_paste: bool,
     raw_mode: Arc<AtomicBool>,
+    // messages of external printers
+    print_queue: Option<Arc<PrintQueue>>,
 }
 
 impl RawMode for ConsoleMode {
     /// Disable RAW mode for the terminal.
     fn disable_raw_mode(&self) -> Result<()> {
+        if self.bracketed_paste {
+            write_to_console(self.conout, BRACKETED_PASTE_OFF, &mut vec![])?;
+        }
         check(unsafe { console::SetConsoleMode(self.conin, self.original_conin_mode) })?;
         if let Some(original_stdstream_mode) = self.original_conout_mode {
             check(unsafe { console::SetConsoleMode(self.conout, original_stdstream_mode) })?;
         }
         self.raw_mode.store(false, Ordering::SeqCst);
+        // external messages not printed yet and the next ones are printed
+        // directly
+        if let Some(ref queue) = self.print_queue {
+            for event in queue.deactivate() {
+                print_event(self.conout, event)?;
+            }
+        }
         Ok(())
     }
 }
@@ -106,18 +126,88 @@ pub struct ConsoleRawReader {
     conin: HANDLE,
     // external print reader
     pipe_reader: Option<Rc<AsyncPipe>>,
+    // keys are reported as VT sequences (ENABLE_VIRTUAL_TERMINAL_INPUT)
+    vt_input: bool,
+    // bracketed paste start has been read
+    paste_pending: bool,
 }
 
 impl ConsoleRawReader {
-    fn create(conin: HANDLE, pipe_reader: Option<Rc<AsyncPipe>>) -> Self {
-        Self { conin, pipe_reader }
+    fn create(conin: HANDLE, pipe_reader: Option<Rc<AsyncPipe>>, vt_input: bool) -> Self {
+        Self {
+            conin,
+            pipe_reader,
+            vt_input,
+            paste_pending: false,
+        }
+    }
+
+    fn read_key(&mut self, max_count: u32) -> Result<KeyEvent> {
+        if !self.vt_input {
+            return read_input(self.conin, max_count);
+        }
+        let key = match read_char(self.conin)? {
+            '\x1b' => self.escape_sequence()?,
+            '\r' => E(K::Enter, M::NONE),
+            '\t' => E(K::Tab, M::NONE),
+            '\x08' | '\x7f' => E(K::Backspace, M::NONE),
+            c => KeyEvent::new(c, M::NONE),
+        };
+        if key == E(K::BracketedPasteStart, M::NONE) {
+            self.paste_pending = true;
+        }
+        debug!(target: "rustyline", "VT input => key: {:?}", key);
+        Ok(key)
+    }
+
+    /// Handle VT sequences starting with ESC
+    fn escape_sequence(&mut self) -> Result<KeyEvent> {
+        if !poll_char(self.conin, ESCAPE_TIMEOUT_MS)? {
+            return Ok(E::ESC);
+        }
+        Ok(match read_char(self.conin)? {
+            '[' => {
+                // CSI: parameters then a final byte
+                let mut params = String::new();
+                loop {
+                    let c = read_char(self.conin)?;
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break vt_key(&params, c);
+                    } else if params.len() >= 16 {
+                        break E(K::UnknownEscSeq, M::NONE);
+                    }
+                    params.push(c);
+                }
+            }
+            // SS3
+            'O' => vt_key("", read_char(self.conin)?),
+            '\x1b' => E(K::Esc, M::ALT),
+            c => E::alt(c),
+        })
+    }
+
+    /// The characters of an IME composition are reported at once, as key
+    /// presses without virtual key: they are gathered with the first one
+    /// (`key`) so that they are inserted as a single change.
+    fn ime_commit(&self, key: KeyEvent) -> Result<Event> {
+        if let KeyEvent(K::Char(c), M::NONE | M::SHIFT) = key {
+            if !self.vt_input {
+                let mut text = String::from(c);
+                read_ime_text(self.conin, &mut text)?;
+                if text.len() > c.len_utf8() {
+                    debug!(target: "rustyline", "IME commit: {:?}", text);
+                    return Ok(Event::Commit(text));
+                }
+            }
+        }
+        Ok(Event::KeyPress(key))
     }
 
     fn select(&mut self) -> Result<Event> {
         use foundation::WAIT_OBJECT_0;
         use threading::{WaitForMultipleObjects, INFINITE};
 
-        let pipe_reader = self.pipe_reader.as_ref().unwrap();
+        let pipe_reader = self.pipe_reader.clone().unwrap();
         let handles = [self.conin, pipe_reader.event.0];
         let n = handles.len().try_into().unwrap();
         loop {
@@ -125,16 +215,18 @@ impl ConsoleRawReader {
             if rc == WAIT_OBJECT_0 {
                 let mut count = 0;
                 check(unsafe { console::GetNumberOfConsoleInputEvents(self.conin, &mut count) })?;
-                match read_input(self.conin, count)? {
+                match self.read_key(count)? {
                     KeyEvent(K::UnknownEscSeq, M::NONE) => continue, // no relevant
-                    key => return Ok(Event::KeyPress(key)),
+                    key => return self.ime_commit(key),
                 };
             } else if rc == WAIT_OBJECT_0 + 1 {
                 debug!(target: "rustyline", "ExternalPrinter::receive");
                 check(unsafe { threading::ResetEvent(pipe_reader.event.0) })?;
-                match pipe_reader.receiver.recv() {
-                    Ok(msg) => return Ok(Event::ExternalPrint(msg)),
-                    Err(e) => Err(io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                // the event may have been discarded
+                if let Some(event) = pipe_reader.queue.pop() {
+                    // other events may be queued
+                    check(unsafe { threading::SetEvent(pipe_reader.event.0) })?;
+                    return Ok(event);
                 }
             } else {
                 Err(io::Error::last_os_error())?
@@ -149,16 +241,45 @@ impl RawReader for ConsoleRawReader {
     fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<Event> {
         match self.pipe_reader {
             Some(_) => self.select(),
-            None => self.next_key(single_esc_abort).map(Event::KeyPress),
+            None => {
+                let key = self.next_key(single_esc_abort)?;
+                self.ime_commit(key)
+            }
         }
     }
 
     fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
-        read_input(self.conin, u32::MAX)
+        self.read_key(u32::MAX)
+    }
+
+    fn poll_key(&mut self, timeout_ms: u16) -> Result<bool> {
+        use foundation::{WAIT_OBJECT_0, WAIT_TIMEOUT};
+        use threading::WaitForSingleObject;
+
+        match unsafe { WaitForSingleObject(self.conin, u32::from(timeout_ms)) } {
+            WAIT_OBJECT_0 => Ok(true),
+            WAIT_TIMEOUT => Ok(false),
+            _ => Err(io::Error::last_os_error())?,
+        }
     }
 
     fn read_pasted_text(&mut self) -> Result<String> {
-        Ok(clipboard_win::get_clipboard_string()?)
+        if !mem::take(&mut self.paste_pending) {
+            return Ok(clipboard_win::get_clipboard_string()?);
+        }
+        // bracketed paste
+        let mut buffer = String::new();
+        loop {
+            match read_char(self.conin)? {
+                '\x1b' => {
+                    if self.escape_sequence()? == E(K::BracketedPasteEnd, M::NONE) {
+                        break;
+                    }
+                }
+                c => buffer.push(c),
+            }
+        }
+        Ok(pasted_text(&buffer))
     }
 
     fn find_binding(&self, _: &KeyEvent) -> Option<Cmd> {
@@ -283,6 +404,112 @@ fn read_input(handle: HANDLE, max_count: u32) -> Result<KeyEvent> {
     }
 }
 
+/// Wait for a character to be typed (VT input), discarding key releases and
+/// other events
+fn poll_char(handle: HANDLE, timeout_ms: u16) -> Result<bool> {
+    use foundation::{WAIT_OBJECT_0, WAIT_TIMEOUT};
+    use threading::WaitForSingleObject;
+
+    let mut rec: console::INPUT_RECORD = unsafe { mem::zeroed() };
+    let mut count = 0;
+    loop {
+        match unsafe { WaitForSingleObject(handle, u32::from(timeout_ms)) } {
+            WAIT_OBJECT_0 => {}
+            WAIT_TIMEOUT => return Ok(false),
+            _ => Err(io::Error::last_os_error())?,
+        }
+        check(unsafe { console::PeekConsoleInputW(handle, &mut rec, 1, &mut count) })?;
+        if count == 0 {
+            continue;
+        }
+        match u32::from(rec.EventType) {
+            console::WINDOW_BUFFER_SIZE_EVENT => return Ok(true),
+            console::KEY_EVENT => {
+                let key_event = unsafe { rec.Event.KeyEvent };
+                if key_event.bKeyDown != 0 && unsafe { key_event.uChar.UnicodeChar } != 0 {
+                    return Ok(true);
+                }
+            }
+            _ => {}
+        }
+        check(unsafe { console::ReadConsoleInputW(handle, &mut rec, 1, &mut count) })?;
+    }
+}
+
+/// Append to `text` the characters committed by an IME already queued:
+/// key presses with `VK_PROCESSKEY` or without virtual key (key releases are
+/// skipped)
+fn read_ime_text(handle: HANDLE, text: &mut String) -> Result<()> {
+    use std::char::decode_utf16;
+
+    let mut rec: console::INPUT_RECORD = unsafe { mem::zeroed() };
+    let mut count = 0;
+    let mut utf16 = vec![];
+    loop {
+        check(unsafe { console::GetNumberOfConsoleInputEvents(handle, &mut count) })?;
+        if count == 0 {
+            break;
+        }
+        check(unsafe { console::PeekConsoleInputW(handle, &mut rec, 1, &mut count) })?;
+        if count == 0 || u32::from(rec.EventType) != console::KEY_EVENT {
+            break;
+        }
+        let key_event = unsafe { rec.Event.KeyEvent };
+        let unicode = unsafe { key_event.uChar.UnicodeChar };
+        let vk = key_event.wVirtualKeyCode;
+        let composed = key_event.bKeyDown != 0
+            && unicode != 0
+            && (vk == 0 || vk == KeyboardAndMouse::VK_PROCESSKEY);
+        // alt release ends an Alt+numpad sequence
+        let released = key_event.bKeyDown == 0 && vk != KeyboardAndMouse::VK_MENU;
+        if !composed && !released {
+            break;
+        }
+        check(unsafe { console::ReadConsoleInputW(handle, &mut rec, 1, &mut count) })?;
+        if composed {
+            utf16.push(unicode);
+        }
+    }
+    text.extend(decode_utf16(utf16).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+    Ok(())
+}
+
+/// Read the next character typed (VT input)
+fn read_char(handle: HANDLE) -> Result<char> {
+    use std::char::decode_utf16;
+
+    let mut rec: console::INPUT_RECORD = unsafe { mem::zeroed() };
+    let mut count = 0;
+    let mut surrogate = 0;
+    loop {
+        check(unsafe { console::ReadConsoleInputW(handle, &mut rec, 1, &mut count) })?;
+        if u32::from(rec.EventType) == console::WINDOW_BUFFER_SIZE_EVENT {
+            debug!(target: "rustyline", "SIGWINCH");
+            return Err(error::ReadlineError::Signal(error::Signal::Resize));
+        } else if u32::from(rec.EventType) != console::KEY_EVENT {
+            continue;
+        }
+        let key_event = unsafe { rec.Event.KeyEvent };
+        let utf16 = unsafe { key_event.uChar.UnicodeChar };
+        if key_event.bKeyDown == 0 || utf16 == 0 {
+            continue;
+        }
+        if (0xD800..0xDC00).contains(&utf16) {
+            surrogate = utf16;
+            continue;
+        }
+        let orc = if surrogate == 0 {
+            decode_utf16(Some(utf16)).next()
+        } else {
+            decode_utf16([surrogate, utf16].iter().copied()).next()
+        };
+        let Some(rc) = orc else {
+            return Err(error::ReadlineError::Eof);
+        };
+        return Ok(rc?);
+    }
+}
+
 pub struct ConsoleRenderer {
     conout: HANDLE,
     cols: Unit, // Number of columns in terminal
@@ -290,7 +517,10 @@ pub struct ConsoleRenderer {
     utf16: Vec<u16>,
     colors_enabled: bool,
     grapheme_cluster_mode: GraphemeClusterMode,
+    ambiguous_width: AmbiguousWidth,
     bell_style: BellStyle,
+    written: u64, // number of bytes written
+    widths: WidthCache,
 }
 
 impl ConsoleRenderer {
@@ -309,7 +539,10 @@ impl ConsoleRenderer {
             utf16: Vec::with_capacity(1024),
             colors_enabled,
             grapheme_cluster_mode,
+            ambiguous_width: AmbiguousWidth::Narrow,
             bell_style,
+            written: 0,
+            widths: WidthCache::default(),
         }
     }
 
@@ -355,7 +588,12 @@ impl ConsoleRenderer {
             if c == "\n" {
                 col = 0;
             } else {
-                let cw = width(self.grapheme_cluster_mode, c, &mut esc_seq);
+                let cw = width(
+                    self.grapheme_cluster_mode,
+                    self.ambiguous_width,
+                    c,
+                    &mut esc_seq,
+                );
                 col += cw;
                 if col > self.cols {
                     self.buffer.push('\n');
@@ -484,6 +722,7 @@ impl Renderer for ConsoleRenderer {
         self.clear_old_rows(&info, old_layout)?;
         // display prompt, input line and hint
         write_to_console(self.conout, self.buffer.as_str(), &mut self.utf16)?;
+        self.written += self.buffer.len() as u64;
 
         // position the cursor
         let info = self.get_console_screen_buffer_info()?;
@@ -496,7 +735,13 @@ impl Renderer for ConsoleRenderer {
     }
 
     fn write_and_flush(&mut self, buf: &str) -> Result<()> {
-        write_to_console(self.conout, buf, &mut self.utf16)
+        write_to_console(self.conout, buf, &mut self.utf16)?;
+        self.written += buf.len() as u64;
+        Ok(())
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.written
     }
 
     /// Characters with 2 column width are correctly handled (not split).
@@ -507,7 +752,9 @@ impl Renderer for ConsoleRenderer {
                 pos.col = 0;
                 pos.row += 1;
             } else {
-                let cw = self.grapheme_cluster_mode.width(c);
+                let cw =
+                    self.widths
+                        .grapheme_width(self.grapheme_cluster_mode, self.ambiguous_width, c);
                 pos.col += cw;
                 if pos.col > self.cols {
                     pos.row += 1;
@@ -522,6 +769,11 @@ impl Renderer for ConsoleRenderer {
         pos
     }
 
+    fn reconfigure(&mut self, colors_enabled: bool, bell_style: BellStyle) {
+        self.colors_enabled = colors_enabled;
+        self.bell_style = bell_style;
+    }
+
     fn beep(&mut self) -> Result<()> {
         match self.bell_style {
             BellStyle::Audible => write_all(self.conout, &[7; 1]),
@@ -569,6 +821,10 @@ impl Renderer for ConsoleRenderer {
         self.grapheme_cluster_mode
     }
 
+    fn ambiguous_width(&self) -> AmbiguousWidth {
+        self.ambiguous_width
+    }
+
     fn move_cursor_at_leftmost(&mut self, _: &mut ConsoleRawReader) -> Result<()> {
         let info = self.get_console_screen_buffer_info()?;
         let mut cursor = info.dwCursorPosition;
@@ -588,6 +844,48 @@ impl Renderer for ConsoleRenderer {
         }
         res.map(|_| ())
     }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        // only supported with virtual terminal processing
+        if self.colors_enabled {
+            self.write_and_flush(cursor_shape_seq(shape))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_alternate_screen(&mut self, enabled: bool) -> Result<()> {
+        // only supported with virtual terminal processing
+        if self.colors_enabled {
+            self.write_and_flush(alternate_screen_seq(enabled))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        Ok(clipboard_win::set_clipboard_string(text)?)
+    }
+
+    fn paste_from_clipboard(&mut self, _: &mut ConsoleRawReader) -> Result<Option<String>> {
+        Ok(clipboard_win::get_clipboard_string().ok())
+    }
+
+    fn query_background(&mut self, _: &mut ConsoleRawReader) -> Result<Option<Background>> {
+        // approximate the legacy console palette
+        let attr = self.get_console_screen_buffer_info()?.wAttributes;
+        let level = if attr & console::BACKGROUND_INTENSITY == 0 {
+            128
+        } else {
+            255
+        };
+        let channel = |bit| if attr & bit == 0 { 0 } else { level };
+        Ok(Some(Background::from_rgb(
+            channel(console::BACKGROUND_RED),
+            channel(console::BACKGROUND_GREEN),
+            channel(console::BACKGROUND_BLUE),
+        )))
+    }
 }
 
 fn write_to_console(handle: HANDLE, s: &str, utf16: &mut Vec<u16>) -> Result<()> {
@@ -638,24 +936,18 @@ pub struct Console {
     close_on_drop: bool,
     pub(crate) color_mode: ColorMode,
     grapheme_cluster_mode: GraphemeClusterMode,
+    // narrow when `None`
+    pub(crate) ambiguous_width: Option<AmbiguousWidth>,
     ansi_colors_supported: bool,
-    bell_style: BellStyle,
+    pub(crate) bell_style: BellStyle,
+    enable_bracketed_paste: bool,
+    vt_input: bool,
+    titles: Vec<Vec<u16>>, // saved window titles
     raw_mode: Arc<AtomicBool>,
     // external print reader
     pipe_reader: Option<Rc<AsyncPipe>>,
     // external print writer
-    pipe_writer: Option<SyncSender<String>>,
-}
-
-impl Console {
-    fn colors_enabled(&self) -> bool {
-        // TODO ANSI Colors & Windows <10
-        match self.color_mode {
-            ColorMode::Enabled => self.conout_isatty && self.ansi_colors_supported,
-            ColorMode::Forced => true,
-            ColorMode::Disabled => false,
-        }
-    }
+    pipe_writer: Option<Arc<PrintQueue>>,
 }
 
 impl Term for Console {
@@ -670,11 +962,18 @@ impl Term for Console {
     fn new(
         color_mode: ColorMode,
         grapheme_cluster_mode: GraphemeClusterMode,
+        _detect_grapheme_cluster_mode: bool,
         behavior: Behavior,
         _tab_stop: u8,
         bell_style: BellStyle,
-        _enable_bracketed_paste: bool,
+        enable_bracketed_paste: bool,
+        _enable_synchronized_output: bool,
         _enable_signals: bool,
+        _serial_baud_rate: Option<u32>,
+        _probe: bool,
+        _passthrough: Passthrough,
+        _enable_focus_events: bool,
+        _headless: Option<(u16, u16)>,
     ) -> Result<Self> {
         let (conin, conout, close_on_drop) = if behavior == Behavior::PreferTerm {
             if let (Ok(conin), Ok(conout)) = (
@@ -724,8 +1023,12 @@ impl Term for Console {
             close_on_drop,
             color_mode,
             grapheme_cluster_mode,
+            ambiguous_width: None,
             ansi_colors_supported: false,
             bell_style,
+            enable_bracketed_paste,
+            vt_input: false,
+            titles: Vec::new(),
             raw_mode: Arc::new(AtomicBool::new(false)),
             pipe_reader: None,
             pipe_writer: None,
@@ -733,7 +1036,7 @@ impl Term for Console {
     }
 
     fn is_unsupported(&self) -> bool {
-        super::is_unsupported_term()
+        super::is_unsupported_term() || super::is_dumb_term()
     }
 
     fn is_input_tty(&self) -> bool {
@@ -744,6 +1047,15 @@ impl Term for Console {
         self.conout_isatty
     }
 
+    fn colors_enabled(&self) -> bool {
+        // TODO ANSI Colors & Windows <10
+        match self.color_mode {
+            ColorMode::Enabled => self.conout_isatty && self.ansi_colors_supported,
+            ColorMode::Forced => true,
+            ColorMode::Disabled => false,
+        }
+    }
+
     // pub fn install_sigwinch_handler(&mut self) {
     // See ReadConsoleInputW && WINDOW_BUFFER_SIZE_EVENT
     // }
@@ -804,12 +1116,37 @@ impl Term for Console {
             None
         };
 
+        // Prefer VT input when VT output is active (Windows 10+, Windows
+        // Terminal, ConPTY) so that extended keys and bracketed paste are
+        // reported as escape sequences; otherwise keep legacy key events.
+        let vt_output = self.conout_isatty
+            && get_console_mode(self.conout)
+                .is_ok_and(|mode| mode & console::ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0);
+        self.vt_input = vt_output
+            && unsafe {
+                console::SetConsoleMode(self.conin, raw | console::ENABLE_VIRTUAL_TERMINAL_INPUT)
+                    != 0
+            };
+        debug!(target: "rustyline", "vt_input: {}", self.vt_input);
+        // enable bracketed paste
+        let bracketed_paste = self.vt_input
+            && self.enable_bracketed_paste
+            && write_to_console(self.conout, BRACKETED_PASTE_ON, &mut vec![]).is_ok();
+
         self.raw_mode.store(true, Ordering::SeqCst);
         // when all ExternalPrinter are dropped there is no need to use `pipe_reader`
         if Arc::strong_count(&self.raw_mode) == 1 {
             self.pipe_writer = None;
             self.pipe_reader = None;
         }
+        let print_queue = self.pipe_writer.clone();
+        if let Some(ref queue) = print_queue {
+            if queue.activate() {
+                // restore the region above the prompt
+                let event = self.pipe_reader.as_ref().unwrap().event.0;
+                check(unsafe { threading::SetEvent(event) })?;
+            }
+        }
 
         Ok((
             ConsoleMode {
@@ -817,7 +1154,9 @@ impl Term for Console {
                 conin: self.conin,
                 original_conout_mode,
                 conout: self.conout,
+                bracketed_paste,
                 raw_mode: self.raw_mode.clone(),
+                print_queue,
             },
             (),
         ))
@@ -829,27 +1168,29 @@ impl Term for Console {
         _: &Config,
         _: ConsoleKeyMap,
     ) -> ConsoleRawReader {
-        ConsoleRawReader::create(self.conin, self.pipe_reader.clone())
+        ConsoleRawReader::create(self.conin, self.pipe_reader.clone(), self.vt_input)
     }
 
     fn create_writer(&self) -> ConsoleRenderer {
-        ConsoleRenderer::new(
+        let mut writer = ConsoleRenderer::new(
             self.conout,
             self.colors_enabled(),
             self.grapheme_cluster_mode,
             self.bell_style,
-        )
+        );
+        writer.ambiguous_width = self.ambiguous_width.unwrap_or_default();
+        writer
     }
 
     fn writeln(&self) -> Result<()> {
         write_all(self.conout, &[10; 1])
     }
 
-    fn create_external_printer(&mut self) -> Result<ExternalPrinter> {
-        if let Some(ref sender) = self.pipe_writer {
+    fn create_external_printer(&mut self, config: &Config) -> Result<ExternalPrinter> {
+        if let Some(ref queue) = self.pipe_writer {
             return Ok(ExternalPrinter {
                 event: self.pipe_reader.as_ref().unwrap().event.0,
-                sender: sender.clone(),
+                queue: queue.clone(),
                 raw_mode: self.raw_mode.clone(),
                 conout: self.conout,
             });
@@ -861,17 +1202,17 @@ impl Term for Console {
         if event.is_null() {
             Err(io::Error::last_os_error())?;
         }
-        let (sender, receiver) = sync_channel(1);
+        let queue = Arc::new(PrintQueue::new(config));
 
         let reader = Rc::new(AsyncPipe {
             event: Handle(event),
-            receiver,
+            queue: queue.clone(),
         });
         self.pipe_reader.replace(reader);
-        self.pipe_writer.replace(sender.clone());
+        self.pipe_writer.replace(queue.clone());
         Ok(ExternalPrinter {
             event,
-            sender,
+            queue,
             raw_mode: self.raw_mode.clone(),
             conout: self.conout,
         })
@@ -884,6 +1225,25 @@ impl Term for Console {
             Ok(None)
         }
     }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        let title: Vec<u16> = title.encode_utf16().chain(Some(0)).collect();
+        check(unsafe { console::SetConsoleTitleW(title.as_ptr()) })?;
+        Ok(())
+    }
+
+    fn save_title(&mut self, save: bool) -> Result<()> {
+        if save {
+            let mut title = vec![0u16; 1024];
+            let len = unsafe { console::GetConsoleTitleW(title.as_mut_ptr(), title.len() as u32) };
+            title.truncate(len as usize);
+            title.push(0);
+            self.titles.push(title);
+        } else if let Some(title) = self.titles.pop() {
+            check(unsafe { console::SetConsoleTitleW(title.as_ptr()) })?;
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Console {
@@ -901,13 +1261,13 @@ unsafe impl Sync for Console {}
 #[derive(Debug)]
 struct AsyncPipe {
     event: Handle,
-    receiver: Receiver<String>,
+    queue: Arc<PrintQueue>,
 }
 
 #[derive(Debug)]
 pub struct ExternalPrinter {
     event: HANDLE,
-    sender: SyncSender<String>,
+    queue: Arc<PrintQueue>,
     raw_mode: Arc<AtomicBool>,
     conout: HANDLE,
 }
@@ -915,18 +1275,51 @@ pub struct ExternalPrinter {
 unsafe impl Send for ExternalPrinter {}
 unsafe impl Sync for ExternalPrinter {}
 
+/// Print an external message directly to stdout/stderr (while no line is
+/// edited)
+fn print_event(conout: HANDLE, event: Event) -> Result<()> {
+    match event {
+        Event::ExternalPrint(msg) => write_to_console(conout, msg.as_str(), &mut vec![]),
+        Event::ExternalEprint(msg) => {
+            let mut stderr = io::stderr().lock();
+            stderr.write_all(msg.as_bytes())?;
+            Ok(stderr.flush()?)
+        }
+        _ => Ok(()),
+    }
+}
+
+impl ExternalPrinter {
+    fn send(&mut self, event: Event) -> Result<()> {
+        // write directly to stdout/stderr while not in raw mode (the region
+        // is remembered by the queue)
+        if !self.raw_mode.load(Ordering::SeqCst) && !matches!(event, Event::ExternalRegion(_)) {
+            return print_event(self.conout, event);
+        }
+        match self.queue.push(event) {
+            Ok(true) => Ok(check(unsafe { threading::SetEvent(self.event) })?),
+            Ok(false) => Ok(()),
+            // edition ended in the meantime
+            Err(event) => print_event(self.conout, event),
+        }
+    }
+}
+
 impl super::ExternalPrinter for ExternalPrinter {
     fn print(&mut self, msg: String) -> Result<()> {
-        // write directly to stdout/stderr while not in raw mode
-        if !self.raw_mode.load(Ordering::SeqCst) {
-            let mut utf16 = vec![];
-            write_to_console(self.conout, msg.as_str(), &mut utf16)
-        } else {
-            self.sender
-                .send(msg)
-                .map_err(|_| io::Error::from(io::ErrorKind::Other))?; // FIXME
-            Ok(check(unsafe { threading::SetEvent(self.event) })?)
-        }
+        self.send(Event::ExternalPrint(msg))
+    }
+
+    fn eprint(&mut self, msg: String) -> Result<()> {
+        self.send(Event::ExternalEprint(msg))
+    }
+
+    fn set_region(&mut self, lines: Vec<String>) -> Result<()> {
+        self.send(Event::ExternalRegion(lines))
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.queue.dropped()
     }
 }
 
@@ -1,18 +1,25 @@
 //! Input validation API (Multi-line editing)
 
 use crate::keymap::Invoke;
-use crate::Result;
+use crate::{Context, Result};
 
 /// Input validation result
 #[non_exhaustive]
 pub enum ValidationResult {
     /// Incomplete input
     Incomplete,
+    /// Incomplete input, with hints about how the new line continues
+    IncompleteWith(Continuation),
     /// Validation fails with an optional error message. User must fix the
     /// input.
     Invalid(Option<String>),
     /// Validation succeeds with an optional message
     Valid(Option<String>),
+    /// Validation is still running (e.g. the input has been sent to a server)
+    /// with an optional message displayed meanwhile.
+    /// [`Validator::poll_validation`] is called until another result is
+    /// returned.
+    Pending(Option<String>),
 }
 
 impl ValidationResult {
@@ -25,6 +32,50 @@ impl ValidationResult {
     }
 }
 
+/// How the input continues on the line inserted after
+/// [`ValidationResult::IncompleteWith`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Continuation {
+    /// Expected closing delimiter (e.g. `)` or `end`), displayed as a hint on
+    /// the new line
+    pub closer: Option<String>,
+    /// Indentation of the new line, instead of [`Validator::indent`]
+    pub indent: Option<String>,
+    /// Prompt of the new line, instead of
+    /// [`Highlighter::continuation_prompt`](crate::highlight::Highlighter::continuation_prompt)
+    pub prompt: Option<String>,
+}
+
+impl Continuation {
+    /// Constructor
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the expected closing delimiter
+    #[must_use]
+    pub fn closer(mut self, closer: impl Into<String>) -> Self {
+        self.closer = Some(closer.into());
+        self
+    }
+
+    /// Set the indentation of the new line
+    #[must_use]
+    pub fn indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = Some(indent.into());
+        self
+    }
+
+    /// Set the prompt of the new line
+    #[must_use]
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+}
+
 /// Give access to user input.
 pub struct ValidationContext<'i> {
     i: &'i mut dyn Invoke,
@@ -41,6 +92,20 @@ impl<'i> ValidationContext<'i> {
         self.i.input()
     }
 
+    /// Returns the cursor position (byte offset in the input, which may span
+    /// several lines).
+    #[must_use]
+    pub fn pos(&self) -> usize {
+        self.i.pos()
+    }
+
+    /// Returns the context given to the completer and hinter (access to
+    /// history), `None` when the input is not read from a terminal.
+    #[must_use]
+    pub fn context(&self) -> Option<&Context<'_>> {
+        self.i.context()
+    }
+
     // TODO
     //fn invoke(&mut self, cmd: Cmd) -> Result<?> {
     //    self.i.invoke(cmd)
@@ -75,18 +140,50 @@ pub trait Validator {
     /// Configure whether validation is performed while typing or only
     /// when user presses the Enter key.
     ///
-    /// Default is `false`.
+    /// When `true`, [`Validator::validate`] is called each time the user
+    /// stops typing and a ✓ (valid) or ✗ (invalid, with the first line of
+    /// the error message) indicator is displayed below the input until the
+    /// next keystroke.
     ///
-    /// This feature is not yet implemented, so this function is currently a
-    /// no-op
+    /// Default is `false`.
     fn validate_while_typing(&self) -> bool {
         false
     }
+
+    /// Called periodically, while the user is not typing, after
+    /// [`Validator::validate`] returned [`ValidationResult::Pending`]. Returns
+    /// `Pending` until the result of the asynchronous validation is
+    /// available.
+    ///
+    /// By default, [`Validator::validate`] is called again.
+    fn poll_validation(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        self.validate(ctx)
+    }
+
+    /// Called when a pending validation is abandoned because the input has
+    /// been edited.
+    fn cancel_validation(&self) {}
+
+    /// Takes the currently edited `line` with the cursor `pos`ition where a
+    /// newline is about to be inserted (because the input is incomplete) and
+    /// returns the indentation of the new line.
+    ///
+    /// Called only when [`Config::auto_indent`](crate::config::Config::auto_indent)
+    /// is enabled. By default, the leading whitespace of the current line is
+    /// reused.
+    fn indent(&self, line: &str, pos: usize) -> Option<String> {
+        let start = line[..pos].rfind('\n').map_or(0, |n| n + 1);
+        let current = &line[start..pos];
+        let indent = &current[..current.len() - current.trim_start().len()];
+        Some(indent.to_owned())
+    }
 }
 
 impl Validator for () {}
 
 /// Simple matching bracket validator.
+///
+/// Strings and comments are not recognized, see [`BalanceValidator`].
 #[derive(Default)]
 pub struct MatchingBracketValidator {
     _priv: (),
@@ -133,3 +230,325 @@ fn validate_brackets(input: &str) -> ValidationResult {
         ValidationResult::Incomplete
     }
 }
+
+/// Lexical rules of a language, used by [`BalanceValidator`] to skip strings
+/// and comments while matching brackets.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct LanguageProfile {
+    /// Pairs of opening and closing brackets
+    pub brackets: Vec<(char, char)>,
+    /// String delimiters, `escape` applies inside
+    pub quotes: Vec<char>,
+    /// String delimiters without escape sequence
+    pub raw_quotes: Vec<char>,
+    /// Escape character, inside strings and outside
+    pub escape: Option<char>,
+    /// Line comment marker
+    pub line_comment: Option<String>,
+    /// Line comments start only at the beginning of a word (like `#` in
+    /// shell)
+    pub word_comment: bool,
+    /// Block comment start and end markers
+    pub block_comment: Option<(String, String)>,
+    /// Shell here-documents (`<<EOF`, `<<-EOF`, `<<'EOF'`)
+    pub heredoc: bool,
+    /// Input is incomplete until it ends with this character (like `;` in
+    /// SQL)
+    pub terminator: Option<char>,
+}
+
+impl LanguageProfile {
+    /// POSIX shell: quotes, backslash, `#` comments and here-documents
+    #[must_use]
+    pub fn shell() -> Self {
+        Self {
+            brackets: vec![('(', ')'), ('[', ']'), ('{', '}')],
+            quotes: vec!['"', '`'],
+            raw_quotes: vec!['\''],
+            escape: Some('\\'),
+            line_comment: Some("#".to_owned()),
+            word_comment: true,
+            block_comment: None,
+            heredoc: true,
+            terminator: None,
+        }
+    }
+
+    /// SQL: statements terminated by `;`, `--` and `/* */` comments
+    #[must_use]
+    pub fn sql() -> Self {
+        Self {
+            brackets: vec![('(', ')'), ('[', ']')],
+            quotes: vec![],
+            raw_quotes: vec!['\'', '"', '`'],
+            escape: None,
+            line_comment: Some("--".to_owned()),
+            word_comment: false,
+            block_comment: Some(("/*".to_owned(), "*/".to_owned())),
+            heredoc: false,
+            terminator: Some(';'),
+        }
+    }
+
+    /// Lisp: `;` and `#| |#` comments
+    #[must_use]
+    pub fn lisp() -> Self {
+        Self {
+            brackets: vec![('(', ')'), ('[', ']')],
+            quotes: vec!['"'],
+            raw_quotes: vec![],
+            escape: Some('\\'),
+            line_comment: Some(";".to_owned()),
+            word_comment: false,
+            block_comment: Some(("#|".to_owned(), "|#".to_owned())),
+            heredoc: false,
+            terminator: None,
+        }
+    }
+
+    /// C-like languages: `//` and `/* */` comments
+    #[must_use]
+    pub fn braces() -> Self {
+        Self {
+            brackets: vec![('(', ')'), ('[', ']'), ('{', '}')],
+            quotes: vec!['"', '\''],
+            raw_quotes: vec![],
+            escape: Some('\\'),
+            line_comment: Some("//".to_owned()),
+            word_comment: false,
+            block_comment: Some(("/*".to_owned(), "*/".to_owned())),
+            heredoc: false,
+            terminator: None,
+        }
+    }
+}
+
+impl Default for LanguageProfile {
+    fn default() -> Self {
+        Self::braces()
+    }
+}
+
+/// Validator matching brackets outside of strings, comments and
+/// here-documents, according to a [`LanguageProfile`].
+#[derive(Default)]
+pub struct BalanceValidator {
+    profile: LanguageProfile,
+}
+
+impl BalanceValidator {
+    /// Constructor
+    #[must_use]
+    pub fn new(profile: LanguageProfile) -> Self {
+        Self { profile }
+    }
+}
+
+impl Validator for BalanceValidator {
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        Ok(validate_balance(ctx.input(), &self.profile))
+    }
+}
+
+fn validate_balance(input: &str, profile: &LanguageProfile) -> ValidationResult {
+    let mut stack = vec![];
+    let mut heredocs = vec![]; // delimiters whose body starts on the next line
+    let mut last = None; // last char outside comments
+    let mut prev = None;
+    let mut i = 0;
+    while let Some(c) = input[i..].chars().next() {
+        let start = i;
+        let rest = &input[start..];
+        i += c.len_utf8();
+        if c == '\n' && !heredocs.is_empty() {
+            match skip_heredocs(input, i, &heredocs) {
+                Some(end) => i = end,
+                None => return ValidationResult::Incomplete,
+            }
+            heredocs.clear();
+        } else if Some(c) == profile.escape {
+            i += input[i..].chars().next().map_or(0, char::len_utf8);
+        } else if let Some(marker) = profile.line_comment.as_deref().filter(|marker| {
+            rest.starts_with(marker)
+                && (!profile.word_comment || prev.is_none_or(char::is_whitespace))
+        }) {
+            // up to the end of line
+            i = start
+                + rest[marker.len()..]
+                    .find('\n')
+                    .map_or(rest.len(), |n| marker.len() + n);
+            continue;
+        } else if let Some((open, close)) = profile
+            .block_comment
+            .as_ref()
+            .filter(|(open, _)| rest.starts_with(open.as_str()))
+        {
+            match rest[open.len()..].find(close.as_str()) {
+                Some(n) => i = start + open.len() + n + close.len(),
+                None => return ValidationResult::Incomplete,
+            }
+            continue;
+        } else if profile.heredoc && rest.starts_with("<<") {
+            if rest.starts_with("<<<") {
+                // here-string
+                i = start + 3;
+            } else if let Some((delimiter, len)) = heredoc_delimiter(&rest[2..]) {
+                heredocs.push(delimiter);
+                i = start + 2 + len;
+            } else {
+                i = start + 2;
+            }
+        } else if profile.quotes.contains(&c) || profile.raw_quotes.contains(&c) {
+            let escape = profile.escape.filter(|_| profile.quotes.contains(&c));
+            let mut chars = input[i..].chars();
+            loop {
+                match chars.next() {
+                    None => return ValidationResult::Incomplete,
+                    Some(q) if q == c => break,
+                    Some(e) if Some(e) == escape => {
+                        chars.next();
+                    }
+                    Some(_) => {}
+                }
+            }
+            i = input.len() - chars.as_str().len();
+        } else if profile.brackets.iter().any(|&(open, _)| open == c) {
+            stack.push(c);
+        } else if let Some(&(open, _)) = profile.brackets.iter().find(|&&(_, close)| close == c) {
+            match stack.pop() {
+                Some(wanted) if wanted == open => {}
+                Some(wanted) => {
+                    return ValidationResult::Invalid(Some(format!(
+                        "Mismatched brackets: {wanted:?} is not properly closed"
+                    )))
+                }
+                None => {
+                    return ValidationResult::Invalid(Some(format!(
+                        "Mismatched brackets: {c:?} is unpaired"
+                    )))
+                }
+            }
+        }
+        if !c.is_whitespace() {
+            last = Some(c);
+        }
+        prev = Some(c);
+    }
+    let unterminated = profile
+        .terminator
+        .is_some_and(|t| last.is_some_and(|c| c != t));
+    if !stack.is_empty() || !heredocs.is_empty() || unterminated {
+        ValidationResult::Incomplete
+    } else {
+        ValidationResult::Valid(None)
+    }
+}
+
+/// Parse the here-document delimiter following `<<`, returns it (prefixed
+/// with `-` when leading tabs are stripped) and the length parsed
+fn heredoc_delimiter(s: &str) -> Option<(String, usize)> {
+    let dash = s.starts_with('-');
+    let word = s[usize::from(dash)..].trim_start_matches([' ', '\t']);
+    let offset = s.len() - word.len();
+    let (delimiter, len) = match word.chars().next()? {
+        q @ ('\'' | '"') => {
+            let n = word[1..].find(q)?;
+            (&word[1..=n], n + 2)
+        }
+        c if c.is_alphabetic() || c == '_' => {
+            let n = word
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(word.len());
+            (&word[..n], n)
+        }
+        _ => return None,
+    };
+    let prefix = if dash { "-" } else { "" };
+    Some((format!("{prefix}{delimiter}"), offset + len))
+}
+
+/// Skip the bodies of the here-documents starting at `pos`, returns the
+/// position following the last delimiter line (`None` if a body is not
+/// terminated)
+fn skip_heredocs(input: &str, mut pos: usize, heredocs: &[String]) -> Option<usize> {
+    for delimiter in heredocs {
+        let (delimiter, strip) = match delimiter.strip_prefix('-') {
+            Some(delimiter) => (delimiter, true),
+            None => (delimiter.as_str(), false),
+        };
+        loop {
+            let end = input[pos..].find('\n').map(|n| pos + n);
+            let line = &input[pos..end.unwrap_or(input.len())];
+            let line = if strip {
+                line.trim_start_matches('\t')
+            } else {
+                line
+            };
+            pos = end.map_or(input.len(), |n| n + 1);
+            if line == delimiter {
+                break;
+            }
+            end?;
+        }
+    }
+    Some(pos)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{validate_balance, LanguageProfile, ValidationResult};
+
+    fn check(profile: &LanguageProfile, input: &str) -> &'static str {
+        match validate_balance(input, profile) {
+            ValidationResult::Valid(_) => "valid",
+            ValidationResult::Invalid(_) => "invalid",
+            ValidationResult::Incomplete | ValidationResult::IncompleteWith(_) => "incomplete",
+            ValidationResult::Pending(_) => "pending",
+        }
+    }
+
+    #[test]
+    fn shell() {
+        let profile = LanguageProfile::shell();
+        assert_eq!("valid", check(&profile, "echo ')' \"(\" \\( # (\nls"));
+        assert_eq!("incomplete", check(&profile, "echo 'a\nb"));
+        assert_eq!("incomplete", check(&profile, "f() {\n  echo a"));
+        // not a comment
+        assert_eq!("incomplete", check(&profile, "echo a#'"));
+        assert_eq!("incomplete", check(&profile, "cat <<EOF\n(\n"));
+        assert_eq!("valid", check(&profile, "cat <<EOF\n(\nEOF"));
+        assert_eq!(
+            "valid",
+            check(&profile, "cat <<-'EOF' | wc\n\t)\n\tEOF\necho")
+        );
+        assert_eq!("valid", check(&profile, "cat <<<')'"));
+        assert_eq!("invalid", check(&profile, "echo )"));
+    }
+
+    #[test]
+    fn sql() {
+        let profile = LanguageProfile::sql();
+        assert_eq!("incomplete", check(&profile, "select 1"));
+        assert_eq!("valid", check(&profile, "select ';' -- (\n;"));
+        assert_eq!("valid", check(&profile, "select 'it''s' /* ; ( */;"));
+        assert_eq!("incomplete", check(&profile, "select /* ;"));
+        assert_eq!("valid", check(&profile, ""));
+    }
+
+    #[test]
+    fn lisp() {
+        let profile = LanguageProfile::lisp();
+        assert_eq!("incomplete", check(&profile, "(defun f (x) ; )\n"));
+        assert_eq!("valid", check(&profile, "(print \"\\\")\") #| ( |#"));
+        assert_eq!("invalid", check(&profile, "(a]"));
+    }
+
+    #[test]
+    fn braces() {
+        let profile = LanguageProfile::braces();
+        assert_eq!("incomplete", check(&profile, "fn main() {"));
+        assert_eq!("valid", check(&profile, "f('}', \"{\") // {"));
+        assert_eq!("incomplete", check(&profile, "/* {"));
+    }
+}